@@ -0,0 +1,56 @@
+//! # build.rs
+//!
+//! Baut die Plattenabbilder der Integrationstests mit den Skripten daneben,
+//! wenn sie fehlen oder älter als ihr Skript sind. Die Abbilder selbst sind
+//! nicht im Repository. Fehlen die Werkzeuge eines Skripts, wird der Kernel
+//! trotzdem gebaut; nur die Tests mit diesem Abbild schlagen dann fehl.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// Abbilder und die Skripte, die sie erzeugen.
+const IMAGES: [(&str, &str); 2] =
+[
+    ("tests/fat/disk.img", "tests/fat/build.sh"),
+    ("tests/ext2/disk.img", "tests/ext2/build.sh"),
+];
+
+/// Beschreibbare Kopie des ext2-Abbilds für tests/ext2.rs; `tests/run.sh`
+/// legt sie vor jedem Lauf neu an.
+const EXT2_SCRATCH: &str = "tests/ext2/scratch.img";
+
+fn main()
+{
+    for (image, script) in IMAGES
+    {
+        println!("cargo:rerun-if-changed={}", script);
+        println!("cargo:rerun-if-changed={}", image);
+        if is_up_to_date(image, script)
+        {
+            continue;
+        }
+        match Command::new("sh").arg(script).status()
+        {
+            Ok(status) if status.success() => {}
+            _ => println!("cargo:warning={} failed, the tests using {} will fail", script, image),
+        }
+    }
+
+    if !Path::new(EXT2_SCRATCH).exists() && Path::new(IMAGES[1].0).exists()
+    {
+        fs::copy(IMAGES[1].0, EXT2_SCRATCH).expect("copying the ext2 test image failed");
+    }
+}
+
+/// Gibt an, ob `image` existiert und nicht älter als `script` ist.
+fn is_up_to_date(image: &str, script: &str) -> bool
+{
+    let modified = |path: &str| fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    match (modified(image), modified(script))
+    {
+        (Some(image), Some(script)) => image >= script,
+        (Some(_), None) => true,
+        _ => false,
+    }
+}
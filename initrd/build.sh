@@ -0,0 +1,9 @@
+#!/bin/sh
+# Baut initrd.tar aus dem Verzeichnis root/; der Kernel bindet das Archiv über
+# include_bytes! ein (siehe src/initrd.rs).
+#
+# Besitzer und Zeitstempel werden festgelegt, damit das Archiv reproduzierbar ist.
+set -e
+cd "$(dirname "$0")"
+tar --format=ustar --sort=name --owner=0 --group=0 --numeric-owner --mtime=@0 \
+    -cf initrd.tar -C root .
@@ -0,0 +1,133 @@
+//! # Modul: acpi
+//!
+//! Sucht Tabellen des **Advanced Configuration and Power Interface**, mit
+//! denen die Firmware die Hardware beschreibt (z. B. `MCFG` für den
+//! PCI-Konfigurationsraum).
+//!
+//! Einstieg ist der **RSDP** (Root System Description Pointer). Das BIOS legt
+//! ihn an eine 16-Byte-Grenze entweder in das erste KiB der Extended BIOS Data
+//! Area (EBDA) oder in den Bereich `0xE0000..0x100000`. Er zeigt auf die
+//! Wurzeltabelle – die RSDT mit 32-Bit-Zeigern oder, ab ACPI 2.0, die XSDT mit
+//! 64-Bit-Zeigern – in der alle weiteren Tabellen aufgeführt sind.
+//!
+//! Jede Tabelle beginnt mit demselben Kopf:
+//!
+//! | Offset | Größe | Inhalt |
+//! |--------|-------|--------|
+//! | 0 | 4 | Signatur, z. B. `MCFG` |
+//! | 4 | 4 | Länge der ganzen Tabelle in Bytes |
+//! | 8 | 1 | Revision |
+//! | 9 | 1 | Prüfsumme: alle Bytes ergeben zusammen 0 |
+//! | 10 | 26 | Hersteller- und Tabellenkennungen |
+
+use alloc::vec::Vec;
+use x86_64::PhysAddr;
+
+use crate::memory;
+use crate::sync::Once;
+
+/// Länge des gemeinsamen Tabellenkopfs.
+pub const HEADER_SIZE: usize = 36;
+
+/// Segment der EBDA (in Einheiten von 16 Bytes), abgelegt in der BIOS Data Area.
+const EBDA_POINTER: u64 = 0x40E;
+
+/// Bereich des BIOS, in dem der RSDP liegen kann.
+const BIOS_AREA: core::ops::Range<u64> = 0xE0000..0x100000;
+
+/// Physische Adressen aller Tabellen aus der Wurzeltabelle.
+static TABLES: Once<Vec<PhysAddr>> = Once::new();
+
+/// Bildet `length` Bytes ab `address` ab und gibt sie als Slice zurück.
+fn bytes(address: PhysAddr, length: usize) -> Option<&'static [u8]>
+{
+    let start = memory::map_mmio(address, length as u64).ok()?;
+    Some(unsafe { core::slice::from_raw_parts(start.as_ptr::<u8>(), length) })
+}
+
+/// Gibt an, ob die Bytes zusammen 0 ergeben.
+fn checksum_ok(bytes: &[u8]) -> bool
+{
+    bytes.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)) == 0
+}
+
+/// Sucht den RSDP und gibt die Adresse der Wurzeltabelle zurück und ob es
+/// sich um eine XSDT handelt.
+fn find_root() -> Option<(PhysAddr, bool)>
+{
+    let ebda = u64::from(u16::from_le_bytes(bytes(PhysAddr::new(EBDA_POINTER), 2)?.try_into().ok()?)) << 4;
+    let areas = [ebda..ebda + 1024, BIOS_AREA];
+    for area in areas.into_iter().filter(|area| area.start != 0)
+    {
+        let memory = bytes(PhysAddr::new(area.start), (area.end - area.start) as usize)?;
+        for rsdp in (0..=memory.len() - 36).step_by(16).map(|offset| &memory[offset..offset + 36])
+        {
+            // ACPI 1.0 prüft die ersten 20 Bytes, ab 2.0 die erweiterte Struktur
+            // von 36 Bytes zusätzlich.
+            if !rsdp.starts_with(b"RSD PTR ") || !checksum_ok(&rsdp[..20])
+            {
+                continue;
+            }
+            if rsdp[15] >= 2 && checksum_ok(rsdp)
+            {
+                let xsdt = u64::from_le_bytes(rsdp[24..32].try_into().unwrap());
+                if xsdt != 0
+                {
+                    return Some((PhysAddr::new(xsdt), true));
+                }
+            }
+            let rsdt = u32::from_le_bytes(rsdp[16..20].try_into().unwrap());
+            return Some((PhysAddr::new(u64::from(rsdt)), false));
+        }
+    }
+    None
+}
+
+/// Liest die Wurzeltabelle und gibt die Adressen der übrigen Tabellen zurück.
+fn read_tables() -> Vec<PhysAddr>
+{
+    let Some((root, extended)) = find_root()
+    else
+    {
+        return Vec::new();
+    };
+    let Some(table) = table_at(root)
+    else
+    {
+        return Vec::new();
+    };
+    let entry_size = if extended { 8 } else { 4 };
+    table[HEADER_SIZE..]
+        .chunks_exact(entry_size)
+        .map(|entry|
+        {
+            let mut address = [0; 8];
+            address[..entry_size].copy_from_slice(entry);
+            PhysAddr::new(u64::from_le_bytes(address))
+        })
+        .collect()
+}
+
+/// Die vollständige Tabelle an `address`, sofern ihre Prüfsumme stimmt.
+fn table_at(address: PhysAddr) -> Option<&'static [u8]>
+{
+    let header = bytes(address, HEADER_SIZE)?;
+    let length = u32::from_le_bytes(header[4..8].try_into().unwrap()) as usize;
+    if length < HEADER_SIZE
+    {
+        return None;
+    }
+    let table = bytes(address, length)?;
+    checksum_ok(table).then_some(table)
+}
+
+/// Sucht die Tabelle mit der Signatur `signature` (z. B. `b"MCFG"`) und gibt
+/// sie einschließlich Kopf zurück. Ohne ACPI-Firmware gibt es keine Tabellen.
+pub fn find_table(signature: &[u8; 4]) -> Option<&'static [u8]>
+{
+    TABLES
+        .call_once(read_tables)
+        .iter()
+        .filter_map(|&address| table_at(address))
+        .find(|table| table.starts_with(signature))
+}
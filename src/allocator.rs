@@ -0,0 +1,88 @@
+//! # Modul: allocator
+//!
+//! Dieses Modul stellt den **Kernel-Heap** bereit, damit Typen aus der
+//! `alloc`-Bibliothek wie `Box`, `Vec` oder `BTreeMap` verwendet werden können.
+//!
+//! Der Heap liegt in der oberen Hälfte des Adressraums (siehe [crate::memory])
+//! und ist damit in jedem Adressraum sichtbar. Verwaltet wird er von einem
+//! [Heap] aus `linked_list_allocator`, der durch einen [IrqSpinlock] geschützt
+//! ist – so können auch Interrupt-Handler gefahrlos allokieren.
+//!
+//! [Heap]: linked_list_allocator::Heap
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr::NonNull;
+
+use linked_list_allocator::Heap;
+use x86_64::VirtAddr;
+use x86_64::structures::paging::mapper::MapToError;
+use x86_64::structures::paging::{FrameAllocator, Mapper, Page, PageTableFlags, Size4KiB};
+
+use crate::memory;
+use crate::sync::IrqSpinlock;
+
+/// Startadresse des Kernel-Heaps (Level-4-Eintrag 384).
+pub const HEAP_START: u64 = 0xffff_c000_0000_0000;
+
+/// Größe des Kernel-Heaps in Bytes.
+pub const HEAP_SIZE: u64 = 2 * 1024 * 1024;
+
+#[global_allocator]
+static ALLOCATOR: KernelAllocator = KernelAllocator { heap: IrqSpinlock::new(Heap::empty()) };
+
+/// Globaler Allocator: ein [Heap] hinter einem [IrqSpinlock].
+///
+/// [Heap]: linked_list_allocator::Heap
+struct KernelAllocator
+{
+    heap: IrqSpinlock<Heap>,
+}
+
+unsafe impl GlobalAlloc for KernelAllocator
+{
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8
+    {
+        self.heap
+            .lock()
+            .allocate_first_fit(layout)
+            .map_or(core::ptr::null_mut(), NonNull::as_ptr)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout)
+    {
+        if let Some(ptr) = NonNull::new(ptr)
+        {
+            unsafe { self.heap.lock().deallocate(ptr, layout) };
+        }
+    }
+}
+
+/// ## Heap anlegen
+///
+/// Bildet den Bereich `HEAP_START..HEAP_START + HEAP_SIZE` auf frische Frames
+/// ab und übergibt ihn dem Allocator. Wird von [memory::init] aufgerufen, bevor
+/// der erste [AddressSpace](memory::AddressSpace) angelegt wird, damit der
+/// Heap in alle Adressräume übernommen wird.
+pub(crate) fn init_heap() -> Result<(), MapToError<Size4KiB>>
+{
+    let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_EXECUTE;
+    let first = Page::<Size4KiB>::containing_address(VirtAddr::new(HEAP_START));
+    let last = Page::<Size4KiB>::containing_address(VirtAddr::new(HEAP_START + HEAP_SIZE - 1));
+
+    let mut mapper = unsafe { memory::active_mapper() };
+    let mut allocator = memory::frame_allocator();
+    for page in Page::range_inclusive(first, last)
+    {
+        let frame = allocator.allocate_frame().ok_or(MapToError::FrameAllocationFailed)?;
+        unsafe { mapper.map_to(page, frame, flags, &mut *allocator)?.flush() };
+    }
+
+    unsafe { ALLOCATOR.heap.lock().init(HEAP_START as *mut u8, HEAP_SIZE as usize) };
+    Ok(())
+}
+
+/// Anzahl der aktuell belegten Bytes auf dem Heap.
+pub fn used() -> usize
+{
+    ALLOCATOR.heap.lock().used()
+}
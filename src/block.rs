@@ -0,0 +1,140 @@
+//! # Modul: block
+//!
+//! Dieses Modul stellt die Schnittstelle für **Blockgeräte** bereit: Geräte,
+//! die nur ganze Sektoren fester Größe lesen und schreiben, z. B. Festplatten.
+//!
+//! # Aufbau
+//!
+//! | Komponente | Aufgabe |
+//! |------------|---------|
+//! | [BlockDevice] | Gemeinsame Schnittstelle aller Blockgeräte |
+//! | [register], [get], [devices] | Liste der erkannten Geräte mit Namen |
+//! | [ata] | Treiber für ATA/IDE-Festplatten im PIO-Modus |
+//! | [partition] | Partitionen aus dem Master Boot Record |
+//! | [cache] | Gemeinsamer Puffercache mit LRU und Write-back |
+//! | [queue] | Warteschlange, die benachbarte Zugriffe zusammenfasst |
+//! | [virtio] | Treiber für virtio-blk über PCI |
+//! | [RamDisk] | Blockgerät im Arbeitsspeicher |
+//!
+//! Treiber melden erkannte Geräte mit [register] an; Dateisysteme holen sie
+//! sich über [get] anhand ihres Namens (z. B. `"ata0"`).
+
+pub mod ata;
+pub mod cache;
+pub mod partition;
+pub mod queue;
+mod ram;
+pub mod virtio;
+
+pub use cache::CachedDevice;
+pub use ram::RamDisk;
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::sync::IrqSpinlock;
+use crate::vfs::VfsError;
+
+/// Fehler beim Zugriff auf ein Blockgerät.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockError
+{
+    /// Die Sektoren liegen (teilweise) hinter dem Ende des Geräts.
+    OutOfRange,
+    /// Die Puffergröße ist kein Vielfaches der Sektorgröße.
+    BadBufferSize,
+    /// Das Gerät ist schreibgeschützt.
+    ReadOnly,
+    /// Das Gerät hat nicht rechtzeitig geantwortet.
+    Timeout,
+    /// Das Gerät hat einen Fehler gemeldet.
+    Io,
+}
+
+impl From<BlockError> for VfsError
+{
+    fn from(error: BlockError) -> VfsError
+    {
+        match error
+        {
+            BlockError::ReadOnly => VfsError::ReadOnly,
+            BlockError::OutOfRange | BlockError::BadBufferSize => VfsError::InvalidArgument,
+            BlockError::Timeout | BlockError::Io => VfsError::Io,
+        }
+    }
+}
+
+/// ## BlockDevice
+///
+/// Ein Gerät, das in Sektoren von [sector_size](BlockDevice::sector_size)
+/// Bytes adressiert wird. Lese- und Schreibpuffer müssen ein Vielfaches der
+/// Sektorgröße lang sein; es werden so viele Sektoren ab `lba` übertragen,
+/// wie in den Puffer passen.
+pub trait BlockDevice: Send + Sync
+{
+    /// Name des Geräts, z. B. `"ata0"`.
+    fn name(&self) -> &str;
+
+    /// Größe eines Sektors in Bytes.
+    fn sector_size(&self) -> usize;
+
+    /// Anzahl der Sektoren.
+    fn sector_count(&self) -> u64;
+
+    /// Kapazität in Bytes.
+    fn capacity(&self) -> u64
+    {
+        self.sector_count() * self.sector_size() as u64
+    }
+
+    /// Liest Sektoren ab `lba` nach `buffer`.
+    fn read_sectors(&self, lba: u64, buffer: &mut [u8]) -> Result<(), BlockError>;
+
+    /// Schreibt `buffer` in die Sektoren ab `lba`.
+    fn write_sectors(&self, lba: u64, buffer: &[u8]) -> Result<(), BlockError>;
+
+    /// Schreibt den Schreibcache des Geräts auf das Medium.
+    fn flush(&self) -> Result<(), BlockError>
+    {
+        Ok(())
+    }
+}
+
+/// Prüft Puffergröße und Sektorbereich eines Zugriffs und gibt die Anzahl der
+/// Sektoren zurück. Für Treiber gedacht.
+pub fn check_range(device: &dyn BlockDevice, lba: u64, length: usize) -> Result<u64, BlockError>
+{
+    if !length.is_multiple_of(device.sector_size())
+    {
+        return Err(BlockError::BadBufferSize);
+    }
+    let count = (length / device.sector_size()) as u64;
+    match lba.checked_add(count)
+    {
+        Some(end) if end <= device.sector_count() => Ok(count),
+        _ => Err(BlockError::OutOfRange),
+    }
+}
+
+/// Alle angemeldeten Blockgeräte.
+static DEVICES: IrqSpinlock<Vec<Arc<dyn BlockDevice>>> = IrqSpinlock::new(Vec::new());
+
+/// Meldet ein erkanntes Gerät an. Ein Gerät mit gleichem Namen wird ersetzt.
+pub fn register(device: Arc<dyn BlockDevice>)
+{
+    let mut devices = DEVICES.lock();
+    devices.retain(|existing| existing.name() != device.name());
+    devices.push(device);
+}
+
+/// Das Gerät mit dem Namen `name`.
+pub fn get(name: &str) -> Option<Arc<dyn BlockDevice>>
+{
+    DEVICES.lock().iter().find(|device| device.name() == name).cloned()
+}
+
+/// Alle angemeldeten Geräte in der Reihenfolge ihrer Anmeldung.
+pub fn devices() -> Vec<Arc<dyn BlockDevice>>
+{
+    DEVICES.lock().clone()
+}
@@ -0,0 +1,481 @@
+//! Treiber für ATA/IDE-Festplatten im PIO-Modus.
+//!
+//! Der klassische IDE-Controller hat zwei **Kanäle** mit je zwei Laufwerken
+//! (Master und Slave). Jeder Kanal wird über zwei Portbereiche gesteuert und
+//! meldet sich über eine eigene IRQ-Leitung:
+//!
+//! | Kanal | Befehlsregister | Steuerregister | IRQ |
+//! |-------|-----------------|----------------|-----|
+//! | primär | `0x1F0..0x1F8` | `0x3F6` | 14 |
+//! | sekundär | `0x170..0x178` | `0x376` | 15 |
+//!
+//! Im **PIO-Modus** überträgt die CPU jeden Sektor selbst als 256 16-Bit-Wörter
+//! über das Datenregister. Nach dem Befehl löst das Laufwerk für jeden Sektor
+//! einen Interrupt aus, sobald es Daten bereitstellt (Lesen) bzw. den Sektor
+//! übernommen hat (Schreiben). Der Treiber schläft solange über
+//! [crate::sync::wait]; sind Interrupts gesperrt, fragt er stattdessen das
+//! Statusregister ab.
+//!
+//! Adressiert wird mit **LBA28** (bis 128 GiB) und, falls das Laufwerk es
+//! unterstützt, mit **LBA48** für Sektoren jenseits dieser Grenze.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use x86_64::instructions::interrupts;
+use x86_64::instructions::port::Port;
+
+use super::{BlockDevice, BlockError, check_range};
+use crate::sync::{Mutex, Semaphore};
+
+/// Sektorgröße aller ATA-Laufwerke in diesem Treiber.
+pub const SECTOR_SIZE: usize = 512;
+
+/// Höchste Anzahl Sektoren pro Befehl.
+const MAX_SECTORS_PER_COMMAND: usize = 256;
+
+/// Erster Sektor, der nur noch mit LBA48 erreichbar ist.
+const LBA28_LIMIT: u64 = 1 << 28;
+
+/// Anzahl der Abfragen des Statusregisters, bevor ein Befehl als
+/// [BlockError::Timeout] gilt.
+const POLL_LIMIT: usize = 1_000_000;
+
+/// Wartezeit auf einen Interrupt, bevor das Statusregister abgefragt wird.
+const INTERRUPT_TIMEOUT_MS: u64 = 1000;
+
+/// Register relativ zur Basis der Befehlsregister.
+mod register
+{
+    pub const DATA: u16 = 0;
+    pub const SECTOR_COUNT: u16 = 2;
+    pub const LBA_LOW: u16 = 3;
+    pub const LBA_MID: u16 = 4;
+    pub const LBA_HIGH: u16 = 5;
+    pub const DRIVE: u16 = 6;
+    pub const STATUS: u16 = 7;
+    pub const COMMAND: u16 = 7;
+}
+
+mod command
+{
+    pub const READ_SECTORS: u8 = 0x20;
+    pub const READ_SECTORS_EXT: u8 = 0x24;
+    pub const WRITE_SECTORS: u8 = 0x30;
+    pub const WRITE_SECTORS_EXT: u8 = 0x34;
+    pub const CACHE_FLUSH: u8 = 0xE7;
+    pub const CACHE_FLUSH_EXT: u8 = 0xEA;
+    pub const IDENTIFY: u8 = 0xEC;
+}
+
+mod status
+{
+    pub const ERROR: u8 = 1 << 0;
+    pub const DATA_REQUEST: u8 = 1 << 3;
+    pub const DRIVE_FAULT: u8 = 1 << 5;
+    pub const BUSY: u8 = 1 << 7;
+}
+
+/// Einer der beiden Kanäle des Controllers.
+struct Channel
+{
+    io_base: u16,
+    control_base: u16,
+    irq: u8,
+    /// Serialisiert Befehle: pro Kanal arbeitet immer nur ein Laufwerk.
+    lock: Mutex<()>,
+    /// Eine Erlaubnis pro empfangenem Interrupt.
+    interrupts: Semaphore,
+}
+
+static CHANNELS: [Channel; 2] = [Channel::new(0x1F0, 0x3F6, 14), Channel::new(0x170, 0x376, 15)];
+
+impl Channel
+{
+    const fn new(io_base: u16, control_base: u16, irq: u8) -> Channel
+    {
+        Channel { io_base, control_base, irq, lock: Mutex::new(()), interrupts: Semaphore::new(0) }
+    }
+
+    fn read(&self, register: u16) -> u8
+    {
+        unsafe { Port::new(self.io_base + register).read() }
+    }
+
+    fn write(&self, register: u16, value: u8)
+    {
+        unsafe { Port::new(self.io_base + register).write(value) }
+    }
+
+    /// Liest das Statusregister, ohne einen anstehenden Interrupt zu quittieren.
+    fn alternate_status(&self) -> u8
+    {
+        unsafe { Port::new(self.control_base).read() }
+    }
+
+    /// Wählt das Laufwerk aus und wartet die vorgeschriebenen 400 ns, indem
+    /// das Statusregister viermal gelesen wird.
+    fn select(&self, value: u8)
+    {
+        self.write(register::DRIVE, value);
+        for _ in 0..4
+        {
+            self.alternate_status();
+        }
+    }
+
+    /// Wartet, bis das Laufwerk nicht mehr beschäftigt ist, und gibt den Status zurück.
+    fn wait_not_busy(&self) -> Result<u8, BlockError>
+    {
+        for _ in 0..POLL_LIMIT
+        {
+            let status = self.alternate_status();
+            if status & status::BUSY == 0
+            {
+                return Ok(status);
+            }
+            core::hint::spin_loop();
+        }
+        Err(BlockError::Timeout)
+    }
+
+    /// Wartet, bis das Laufwerk Daten anfordert bzw. bereitstellt.
+    fn wait_data_request(&self) -> Result<(), BlockError>
+    {
+        for _ in 0..POLL_LIMIT
+        {
+            let status = self.wait_not_busy()?;
+            if status & (status::ERROR | status::DRIVE_FAULT) != 0
+            {
+                return Err(BlockError::Io);
+            }
+            if status & status::DATA_REQUEST != 0
+            {
+                return Ok(());
+            }
+        }
+        Err(BlockError::Timeout)
+    }
+
+    /// Verwirft Interrupts, die zu früheren Befehlen gehören.
+    fn discard_interrupts(&self)
+    {
+        while let Some(permit) = self.interrupts.try_acquire()
+        {
+            permit.forget();
+        }
+    }
+
+    /// Wartet auf den Interrupt des laufenden Befehls und prüft den Status.
+    ///
+    /// Mit aktiven Interrupts schläft der Aufrufer, bis der Handler die
+    /// [Semaphore] freigibt. Ohne sie (oder falls der Interrupt ausbleibt) wird
+    /// das Statusregister abgefragt.
+    fn wait_interrupt(&self) -> Result<(), BlockError>
+    {
+        if interrupts::are_enabled()
+        {
+            let deadline = crate::time::ticks() + crate::time::ms_to_ticks(INTERRUPT_TIMEOUT_MS);
+            while crate::time::ticks() < deadline
+            {
+                if let Some(permit) = self.interrupts.try_acquire()
+                {
+                    permit.forget();
+                    break;
+                }
+                crate::sync::wait();
+            }
+        }
+
+        self.wait_not_busy()?;
+        // Das Lesen des Statusregisters quittiert den Interrupt im Laufwerk.
+        if self.read(register::STATUS) & (status::ERROR | status::DRIVE_FAULT) != 0
+        {
+            return Err(BlockError::Io);
+        }
+        Ok(())
+    }
+
+    /// Setzt Sektoranzahl und Adresse und startet `command`.
+    fn issue(&self, drive: Drive, lba: u64, count: usize, lba48: bool, command: u8)
+    {
+        // 0 steht für die Höchstzahl an Sektoren.
+        let count = if count == MAX_SECTORS_PER_COMMAND { 0 } else { count as u16 };
+        if lba48
+        {
+            self.select(0x40 | drive.select_bit());
+            self.write(register::SECTOR_COUNT, (count >> 8) as u8);
+            self.write(register::LBA_LOW, (lba >> 24) as u8);
+            self.write(register::LBA_MID, (lba >> 32) as u8);
+            self.write(register::LBA_HIGH, (lba >> 40) as u8);
+        }
+        else
+        {
+            self.select(0xE0 | drive.select_bit() | ((lba >> 24) as u8 & 0x0F));
+        }
+        self.write(register::SECTOR_COUNT, count as u8);
+        self.write(register::LBA_LOW, lba as u8);
+        self.write(register::LBA_MID, (lba >> 8) as u8);
+        self.write(register::LBA_HIGH, (lba >> 16) as u8);
+        self.write(register::COMMAND, command);
+    }
+
+    fn read_data(&self, sector: &mut [u8])
+    {
+        let mut port = Port::<u16>::new(self.io_base + register::DATA);
+        for word in sector.chunks_exact_mut(2)
+        {
+            word.copy_from_slice(&unsafe { port.read() }.to_le_bytes());
+        }
+    }
+
+    fn write_data(&self, sector: &[u8])
+    {
+        let mut port = Port::<u16>::new(self.io_base + register::DATA);
+        for word in sector.chunks_exact(2)
+        {
+            unsafe { port.write(u16::from_le_bytes([word[0], word[1]])) };
+        }
+    }
+
+    /// Sendet IDENTIFY an `drive` und gibt die 256 Antwortwörter zurück, falls
+    /// dort ein ATA-Laufwerk (kein ATAPI-Gerät) angeschlossen ist.
+    fn identify(&self, drive: Drive) -> Option<[u16; 256]>
+    {
+        let _guard = self.lock.lock();
+        self.select(0xA0 | drive.select_bit());
+        self.write(register::SECTOR_COUNT, 0);
+        self.write(register::LBA_LOW, 0);
+        self.write(register::LBA_MID, 0);
+        self.write(register::LBA_HIGH, 0);
+        self.write(register::COMMAND, command::IDENTIFY);
+
+        // 0 bzw. 0xFF: kein Laufwerk bzw. kein Controller an diesem Kanal.
+        let status = self.read(register::STATUS);
+        if status == 0 || status == 0xFF
+        {
+            return None;
+        }
+        self.wait_not_busy().ok()?;
+        // ATAPI-Geräte melden sich mit einer Signatur in LBA_MID/LBA_HIGH.
+        if self.read(register::LBA_MID) != 0 || self.read(register::LBA_HIGH) != 0
+        {
+            return None;
+        }
+        self.wait_data_request().ok()?;
+
+        let mut bytes = [0; SECTOR_SIZE];
+        self.read_data(&mut bytes);
+        self.read(register::STATUS);
+        self.discard_interrupts();
+
+        let mut words = [0; 256];
+        for (word, bytes) in words.iter_mut().zip(bytes.chunks_exact(2))
+        {
+            *word = u16::from_le_bytes([bytes[0], bytes[1]]);
+        }
+        Some(words)
+    }
+}
+
+/// Master oder Slave an einem Kanal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Drive
+{
+    Master,
+    Slave,
+}
+
+impl Drive
+{
+    fn select_bit(self) -> u8
+    {
+        match self
+        {
+            Drive::Master => 0,
+            Drive::Slave => 1 << 4,
+        }
+    }
+}
+
+/// ## AtaDrive
+///
+/// Ein per IDENTIFY erkanntes ATA-Laufwerk. Die Namen lauten `ata0` bis
+/// `ata3`: primärer Master, primärer Slave, sekundärer Master, sekundärer Slave.
+pub struct AtaDrive
+{
+    name: String,
+    channel: &'static Channel,
+    drive: Drive,
+    sectors: u64,
+    lba48: bool,
+    model: String,
+}
+
+impl AtaDrive
+{
+    fn new(index: usize, channel: &'static Channel, drive: Drive, identify: &[u16; 256]) -> AtaDrive
+    {
+        let lba48 = identify[83] & (1 << 10) != 0;
+        let sectors = if lba48
+        {
+            identify[100..104].iter().rev().fold(0, |sectors, &word| sectors << 16 | u64::from(word))
+        }
+        else
+        {
+            u64::from(identify[60]) | u64::from(identify[61]) << 16
+        };
+
+        // Die Modellbezeichnung steht mit vertauschten Bytes in den Wörtern 27–46.
+        let model: String = identify[27..47].iter().flat_map(|word| word.to_be_bytes()).map(char::from).collect();
+
+        AtaDrive { name: format!("ata{}", index), channel, drive, sectors, lba48, model: String::from(model.trim()) }
+    }
+
+    /// Modellbezeichnung laut IDENTIFY.
+    pub fn model(&self) -> &str
+    {
+        &self.model
+    }
+
+    /// Master oder Slave.
+    pub fn drive(&self) -> Drive
+    {
+        self.drive
+    }
+
+    /// Gibt an, ob das Laufwerk LBA48 unterstützt.
+    pub fn supports_lba48(&self) -> bool
+    {
+        self.lba48
+    }
+
+    /// Überträgt `sectors` Sektoren ab `lba` in Befehlen zu höchstens 256
+    /// Sektoren. `transfer_sector` liest bzw. schreibt den Sektor mit dem
+    /// übergebenen Index über das Datenregister.
+    fn transfer<F>(&self, lba: u64, sectors: usize, write: bool, mut transfer_sector: F) -> Result<(), BlockError>
+    where
+        F: FnMut(&Channel, usize),
+    {
+        let _guard = self.channel.lock.lock();
+        let mut done = 0;
+        while done < sectors
+        {
+            let count = (sectors - done).min(MAX_SECTORS_PER_COMMAND);
+            let start = lba + done as u64;
+            let lba48 = start + count as u64 > LBA28_LIMIT;
+            if lba48 && !self.lba48
+            {
+                return Err(BlockError::OutOfRange);
+            }
+            let command = match (write, lba48)
+            {
+                (false, false) => command::READ_SECTORS,
+                (false, true) => command::READ_SECTORS_EXT,
+                (true, false) => command::WRITE_SECTORS,
+                (true, true) => command::WRITE_SECTORS_EXT,
+            };
+
+            self.channel.wait_not_busy()?;
+            self.channel.discard_interrupts();
+            self.channel.issue(self.drive, start, count, lba48, command);
+            for index in done..done + count
+            {
+                if write
+                {
+                    self.channel.wait_data_request()?;
+                    transfer_sector(self.channel, index);
+                    self.channel.wait_interrupt()?;
+                }
+                else
+                {
+                    self.channel.wait_interrupt()?;
+                    self.channel.wait_data_request()?;
+                    transfer_sector(self.channel, index);
+                }
+            }
+            done += count;
+        }
+        Ok(())
+    }
+}
+
+impl BlockDevice for AtaDrive
+{
+    fn name(&self) -> &str
+    {
+        &self.name
+    }
+
+    fn sector_size(&self) -> usize
+    {
+        SECTOR_SIZE
+    }
+
+    fn sector_count(&self) -> u64
+    {
+        self.sectors
+    }
+
+    fn read_sectors(&self, lba: u64, buffer: &mut [u8]) -> Result<(), BlockError>
+    {
+        let count = check_range(self, lba, buffer.len())? as usize;
+        self.transfer(lba, count, false, |channel, index|
+        {
+            channel.read_data(&mut buffer[index * SECTOR_SIZE..(index + 1) * SECTOR_SIZE]);
+        })
+    }
+
+    fn write_sectors(&self, lba: u64, buffer: &[u8]) -> Result<(), BlockError>
+    {
+        let count = check_range(self, lba, buffer.len())? as usize;
+        self.transfer(lba, count, true, |channel, index|
+        {
+            channel.write_data(&buffer[index * SECTOR_SIZE..(index + 1) * SECTOR_SIZE]);
+        })
+    }
+
+    fn flush(&self) -> Result<(), BlockError>
+    {
+        let _guard = self.channel.lock.lock();
+        self.channel.wait_not_busy()?;
+        self.channel.discard_interrupts();
+        self.channel.select(0xE0 | self.drive.select_bit());
+        self.channel.write(register::COMMAND, if self.lba48 { command::CACHE_FLUSH_EXT } else { command::CACHE_FLUSH });
+        self.channel.wait_interrupt()
+    }
+}
+
+/// Quittiert einen Interrupt von Kanal `channel` und weckt den wartenden
+/// Befehl. Wird von den Handlern für IRQ 14 und 15 aufgerufen.
+pub(crate) fn handle_interrupt(channel: usize)
+{
+    let channel = &CHANNELS[channel];
+    channel.read(register::STATUS);
+    channel.interrupts.release();
+}
+
+/// ## Laufwerke erkennen
+///
+/// Schaltet IRQ 14 und 15 frei, sendet IDENTIFY an alle vier möglichen
+/// Laufwerke und meldet die gefundenen ATA-Laufwerke bei [crate::block]
+/// an. ATAPI-Geräte (z. B. CD-Laufwerke) werden übersprungen.
+pub fn init() -> Vec<Arc<AtaDrive>>
+{
+    let mut drives = Vec::new();
+    for (number, channel) in CHANNELS.iter().enumerate()
+    {
+        crate::interrupts::unmask_irq(channel.irq);
+        for (position, drive) in [Drive::Master, Drive::Slave].into_iter().enumerate()
+        {
+            if let Some(identify) = channel.identify(drive)
+            {
+                let drive = Arc::new(AtaDrive::new(number * 2 + position, channel, drive, &identify));
+                super::register(drive.clone());
+                drives.push(drive);
+            }
+        }
+    }
+    drives
+}
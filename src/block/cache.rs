@@ -0,0 +1,399 @@
+//! Puffercache für Blockgeräte.
+//!
+//! Dateisysteme lesen oft dieselben Sektoren (Superblock, FAT, Bitmaps,
+//! Verzeichnisse) und schreiben sie in kleinen Stücken. Statt jedes Mal das
+//! Gerät anzusprechen, hält der Cache die zuletzt benutzten Sektoren aller
+//! Geräte im Speicher:
+//!
+//! - **Schlüssel** ist das Paar aus Gerät und Sektornummer.
+//! - **LRU**: Ist der Cache voll, wird der am längsten nicht benutzte Sektor
+//!   verdrängt.
+//! - **Write-back**: Schreibzugriffe ändern nur den Cache und markieren den
+//!   Sektor als verändert (dirty). Geschrieben wird erst bei
+//!   [BlockDevice::flush], beim Verdrängen eines veränderten Sektors oder wenn
+//!   mehr als die Hälfte des Caches verändert ist.
+//!
+//! Fehlende und zurückzuschreibende Sektoren laufen über eine [RequestQueue],
+//! sodass benachbarte Sektoren in einem einzigen Gerätezugriff landen.
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::queue::RequestQueue;
+use super::{BlockDevice, BlockError, check_range};
+use crate::sync::Mutex;
+
+/// Anzahl der Sektoren, die der Cache standardmäßig hält (128 KiB bei
+/// 512-Byte-Sektoren).
+pub const DEFAULT_CAPACITY: usize = 256;
+
+/// Gerät (Adresse des zugrunde liegenden Geräts) und Sektor.
+type Key = (usize, u64);
+
+/// Ein zwischengespeicherter Sektor.
+struct Buffer
+{
+    data: Vec<u8>,
+    dirty: bool,
+    /// Zeitpunkt der letzten Benutzung, Schlüssel in [BufferCache::lru].
+    stamp: u64,
+}
+
+/// Zähler für [stats].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats
+{
+    /// Im Cache gefundene Sektoren.
+    pub hits: u64,
+    /// Vom Gerät gelesene Sektoren.
+    pub misses: u64,
+    /// Auf das Gerät zurückgeschriebene Sektoren.
+    pub writebacks: u64,
+    /// Zugriffe auf die Geräte nach dem Zusammenfassen.
+    pub requests: u64,
+    /// Aktuell zwischengespeicherte Sektoren.
+    pub cached: usize,
+    /// Davon verändert.
+    pub dirty: usize,
+}
+
+/// Ein Gerät mit Sektoren im Cache.
+struct Device
+{
+    device: Arc<dyn BlockDevice>,
+    /// Anzahl der [CachedDevice] über diesem Gerät.
+    users: usize,
+}
+
+/// Der Cache aller Geräte.
+struct BufferCache
+{
+    capacity: usize,
+    buffers: BTreeMap<Key, Buffer>,
+    /// Schlüssel nach Zeitpunkt der letzten Benutzung, ältester zuerst.
+    lru: BTreeMap<u64, Key>,
+    clock: u64,
+    /// Die Geräte, für die Sektoren im Cache liegen können.
+    devices: BTreeMap<usize, Device>,
+    stats: CacheStats,
+}
+
+static CACHE: Mutex<BufferCache> = Mutex::new(BufferCache
+{
+    capacity: DEFAULT_CAPACITY,
+    buffers: BTreeMap::new(),
+    lru: BTreeMap::new(),
+    clock: 0,
+    devices: BTreeMap::new(),
+    stats: CacheStats { hits: 0, misses: 0, writebacks: 0, requests: 0, cached: 0, dirty: 0 },
+});
+
+impl BufferCache
+{
+    /// Markiert `key` als zuletzt benutzt.
+    fn touch(&mut self, key: Key)
+    {
+        self.clock += 1;
+        if let Some(buffer) = self.buffers.get_mut(&key)
+        {
+            self.lru.remove(&buffer.stamp);
+            buffer.stamp = self.clock;
+            self.lru.insert(self.clock, key);
+        }
+    }
+
+    /// Legt `data` unter `key` ab und verdrängt dafür bei Bedarf alte Sektoren.
+    fn insert(&mut self, key: Key, data: Vec<u8>, dirty: bool) -> Result<(), BlockError>
+    {
+        if let Some(buffer) = self.buffers.get_mut(&key)
+        {
+            buffer.data = data;
+            if dirty && !buffer.dirty
+            {
+                buffer.dirty = true;
+                self.stats.dirty += 1;
+            }
+            self.touch(key);
+            return Ok(());
+        }
+
+        while self.buffers.len() >= self.capacity
+        {
+            self.evict()?;
+        }
+        self.clock += 1;
+        self.buffers.insert(key, Buffer { data, dirty, stamp: self.clock });
+        self.lru.insert(self.clock, key);
+        self.stats.dirty += usize::from(dirty);
+        Ok(())
+    }
+
+    /// Verdrängt den ältesten Sektor. Ist er verändert, werden vorher alle
+    /// veränderten Sektoren seines Geräts gemeinsam zurückgeschrieben.
+    fn evict(&mut self) -> Result<(), BlockError>
+    {
+        let Some((&stamp, &key)) = self.lru.first_key_value()
+        else
+        {
+            return Ok(());
+        };
+        if self.buffers[&key].dirty
+        {
+            self.write_back(Some(key.0))?;
+        }
+        self.lru.remove(&stamp);
+        self.buffers.remove(&key);
+        Ok(())
+    }
+
+    /// Schreibt alle veränderten Sektoren von `device` (oder aller Geräte)
+    /// zurück.
+    fn write_back(&mut self, device: Option<usize>) -> Result<(), BlockError>
+    {
+        let ids: Vec<usize> = match device
+        {
+            Some(id) => Vec::from([id]),
+            None => self.devices.keys().copied().collect(),
+        };
+        for id in ids
+        {
+            let target = self.devices[&id].device.clone();
+            let mut queue = RequestQueue::new(target.sector_size());
+            let keys: Vec<Key> = self.buffers.range((id, 0)..=(id, u64::MAX)).filter(|(_, buffer)| buffer.dirty).map(|(&key, _)| key).collect();
+            for &(_, lba) in &keys
+            {
+                queue.write(lba, self.buffers[&(id, lba)].data.clone());
+            }
+            queue.submit(target.as_ref())?;
+            self.stats.requests += queue.dispatched();
+            self.stats.writebacks += keys.len() as u64;
+            self.stats.dirty -= keys.len();
+            for key in keys
+            {
+                if let Some(buffer) = self.buffers.get_mut(&key)
+                {
+                    buffer.dirty = false;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, id: usize, lba: u64, buffer: &mut [u8]) -> Result<(), BlockError>
+    {
+        let device = self.devices[&id].device.clone();
+        let sector_size = device.sector_size();
+        let mut queue = RequestQueue::new(sector_size);
+        for (index, sector) in buffer.chunks_exact_mut(sector_size).enumerate()
+        {
+            let key = (id, lba + index as u64);
+            match self.buffers.get(&key)
+            {
+                Some(cached) =>
+                {
+                    sector.copy_from_slice(&cached.data);
+                    self.stats.hits += 1;
+                    self.touch(key);
+                }
+                None => queue.read(key.1, 1),
+            }
+        }
+        if queue.is_empty()
+        {
+            return Ok(());
+        }
+
+        let requests = queue.submit(device.as_ref())?;
+        self.stats.requests += queue.dispatched();
+        for request in requests
+        {
+            let offset = (request.lba - lba) as usize * sector_size;
+            buffer[offset..offset + sector_size].copy_from_slice(&request.data);
+            self.stats.misses += 1;
+            self.insert((id, request.lba), request.data, false)?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, id: usize, lba: u64, data: &[u8]) -> Result<(), BlockError>
+    {
+        let sector_size = self.devices[&id].device.sector_size();
+        for (index, sector) in data.chunks_exact(sector_size).enumerate()
+        {
+            self.insert((id, lba + index as u64), sector.to_vec(), true)?;
+        }
+        if self.stats.dirty > self.capacity / 2
+        {
+            self.write_back(None)?;
+        }
+        Ok(())
+    }
+}
+
+/// ## CachedDevice
+///
+/// Ein Blockgerät, dessen Zugriffe über den gemeinsamen Puffercache laufen.
+/// Mehrere `CachedDevice` über demselben Gerät teilen sich dessen Sektoren im
+/// Cache und sehen daher dieselben Daten. Beim Freigeben werden die
+/// veränderten Sektoren zurückgeschrieben.
+pub struct CachedDevice
+{
+    device: Arc<dyn BlockDevice>,
+    id: usize,
+}
+
+impl CachedDevice
+{
+    /// Schaltet den Cache vor `device`.
+    pub fn new(device: Arc<dyn BlockDevice>) -> CachedDevice
+    {
+        let id = Arc::as_ptr(&device) as *const () as usize;
+        CACHE.lock().devices.entry(id).or_insert_with(|| Device { device: device.clone(), users: 0 }).users += 1;
+        CachedDevice { device, id }
+    }
+
+    /// Das Gerät hinter dem Cache.
+    pub fn device(&self) -> &Arc<dyn BlockDevice>
+    {
+        &self.device
+    }
+}
+
+impl BlockDevice for CachedDevice
+{
+    fn name(&self) -> &str
+    {
+        self.device.name()
+    }
+
+    fn sector_size(&self) -> usize
+    {
+        self.device.sector_size()
+    }
+
+    fn sector_count(&self) -> u64
+    {
+        self.device.sector_count()
+    }
+
+    fn read_sectors(&self, lba: u64, buffer: &mut [u8]) -> Result<(), BlockError>
+    {
+        check_range(self, lba, buffer.len())?;
+        CACHE.lock().read(self.id, lba, buffer)
+    }
+
+    fn write_sectors(&self, lba: u64, buffer: &[u8]) -> Result<(), BlockError>
+    {
+        check_range(self, lba, buffer.len())?;
+        CACHE.lock().write(self.id, lba, buffer)
+    }
+
+    fn flush(&self) -> Result<(), BlockError>
+    {
+        CACHE.lock().write_back(Some(self.id))?;
+        self.device.flush()
+    }
+}
+
+impl Drop for CachedDevice
+{
+    /// Schreibt die veränderten Sektoren zurück. Der letzte Nutzer eines
+    /// Geräts entfernt außerdem dessen Sektoren aus dem Cache.
+    fn drop(&mut self)
+    {
+        let mut cache = CACHE.lock();
+        let _ = cache.write_back(Some(self.id));
+        let device = cache.devices.get_mut(&self.id).unwrap();
+        device.users -= 1;
+        if device.users > 0
+        {
+            return;
+        }
+        cache.devices.remove(&self.id);
+        let keys: Vec<Key> = cache.buffers.range((self.id, 0)..=(self.id, u64::MAX)).map(|(&key, _)| key).collect();
+        for key in keys
+        {
+            let buffer = cache.buffers.remove(&key).unwrap();
+            cache.lru.remove(&buffer.stamp);
+            cache.stats.dirty -= usize::from(buffer.dirty);
+        }
+    }
+}
+
+/// Schreibt die veränderten Sektoren aller Geräte zurück.
+pub fn flush_all() -> Result<(), BlockError>
+{
+    let devices: Vec<Arc<dyn BlockDevice>> =
+    {
+        let mut cache = CACHE.lock();
+        cache.write_back(None)?;
+        cache.devices.values().map(|entry| entry.device.clone()).collect()
+    };
+    devices.iter().try_for_each(|device| device.flush())
+}
+
+/// Setzt die Größe des Caches in Sektoren (mindestens 1) und verdrängt
+/// überzählige Sektoren.
+pub fn set_capacity(capacity: usize) -> Result<(), BlockError>
+{
+    let mut cache = CACHE.lock();
+    cache.capacity = capacity.max(1);
+    while cache.buffers.len() > cache.capacity
+    {
+        cache.evict()?;
+    }
+    Ok(())
+}
+
+/// Zähler des Caches seit dem Start.
+pub fn stats() -> CacheStats
+{
+    let cache = CACHE.lock();
+    CacheStats { cached: cache.buffers.len(), ..cache.stats }
+}
+
+#[test_case]
+fn test_hits_and_merged_misses()
+{
+    let disk: Arc<dyn BlockDevice> = Arc::new(super::RamDisk::new(64));
+    disk.write_sectors(8, &[7; 2048]).unwrap();
+    let cached = CachedDevice::new(disk.clone());
+    let before = stats();
+
+    let mut buffer = [0; 2048];
+    cached.read_sectors(8, &mut buffer).unwrap();
+    assert!(buffer.iter().all(|&byte| byte == 7));
+    cached.read_sectors(9, &mut buffer[..1024]).unwrap();
+    let after = stats();
+    assert_eq!(after.misses - before.misses, 4);
+    assert_eq!(after.hits - before.hits, 2);
+    // Vier fehlende Sektoren in einem einzigen Zugriff.
+    assert_eq!(after.requests - before.requests, 1);
+}
+
+#[test_case]
+fn test_write_back_and_eviction()
+{
+    let disk: Arc<dyn BlockDevice> = Arc::new(super::RamDisk::new(DEFAULT_CAPACITY + 256));
+    let cached = CachedDevice::new(disk.clone());
+    cached.write_sectors(1, &[1; 1024]).unwrap();
+
+    // Noch nicht auf dem Gerät, aber für alle Nutzer des Caches sichtbar.
+    let mut sector = [0; 512];
+    disk.read_sectors(1, &mut sector).unwrap();
+    assert_eq!(sector, [0; 512]);
+    CachedDevice::new(disk.clone()).read_sectors(2, &mut sector).unwrap();
+    assert_eq!(sector, [1; 512]);
+
+    cached.flush().unwrap();
+    disk.read_sectors(2, &mut sector).unwrap();
+    assert_eq!(sector, [1; 512]);
+
+    // Verdrängte veränderte Sektoren gehen nicht verloren.
+    cached.write_sectors(100, &[5; 512]).unwrap();
+    let mut buffer = alloc::vec![0; DEFAULT_CAPACITY * 512];
+    cached.read_sectors(200, &mut buffer).unwrap();
+    disk.read_sectors(100, &mut sector).unwrap();
+    assert_eq!(sector, [5; 512]);
+}
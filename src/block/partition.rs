@@ -0,0 +1,135 @@
+//! Partitionen nach dem Master Boot Record (MBR).
+//!
+//! Der erste Sektor eines partitionierten Datenträgers enthält ab Byte 446
+//! vier Einträge zu je 16 Bytes und endet mit der Signatur `0x55 0xAA`:
+//!
+//! | Offset | Größe | Inhalt |
+//! |--------|-------|--------|
+//! | 0 | 1 | Bootfähig (`0x80`) |
+//! | 4 | 1 | Partitionstyp, `0` für unbenutzt |
+//! | 8 | 4 | Erster Sektor (LBA) |
+//! | 12 | 4 | Anzahl der Sektoren |
+//!
+//! Erweiterte Partitionen werden nicht durchlaufen.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::{BlockDevice, BlockError, check_range};
+
+/// Partitionstyp eines Eintrags für eine erweiterte Partition (CHS bzw. LBA).
+const EXTENDED_TYPES: [u8; 2] = [0x05, 0x0F];
+
+/// ## Partition
+///
+/// Ein Ausschnitt eines Blockgeräts, selbst wieder ein [BlockDevice]. Sektor 0
+/// der Partition ist Sektor `start` des Geräts. Der Name hängt `p` und die
+/// Nummer im MBR an den Gerätenamen an, z. B. `ata2p1`.
+pub struct Partition
+{
+    name: String,
+    device: Arc<dyn BlockDevice>,
+    start: u64,
+    sectors: u64,
+    kind: u8,
+}
+
+impl Partition
+{
+    /// Partitionstyp aus dem MBR, z. B. `0x0C` für FAT32 oder `0x83` für Linux.
+    pub fn kind(&self) -> u8
+    {
+        self.kind
+    }
+
+    /// Erster Sektor auf dem zugrunde liegenden Gerät.
+    pub fn start(&self) -> u64
+    {
+        self.start
+    }
+}
+
+impl BlockDevice for Partition
+{
+    fn name(&self) -> &str
+    {
+        &self.name
+    }
+
+    fn sector_size(&self) -> usize
+    {
+        self.device.sector_size()
+    }
+
+    fn sector_count(&self) -> u64
+    {
+        self.sectors
+    }
+
+    fn read_sectors(&self, lba: u64, buffer: &mut [u8]) -> Result<(), BlockError>
+    {
+        check_range(self, lba, buffer.len())?;
+        self.device.read_sectors(self.start + lba, buffer)
+    }
+
+    fn write_sectors(&self, lba: u64, buffer: &[u8]) -> Result<(), BlockError>
+    {
+        check_range(self, lba, buffer.len())?;
+        self.device.write_sectors(self.start + lba, buffer)
+    }
+
+    fn flush(&self) -> Result<(), BlockError>
+    {
+        self.device.flush()
+    }
+}
+
+/// ## MBR einlesen
+///
+/// Liest die primären Partitionen von `device`. Fehlt die Signatur, ist das
+/// Ergebnis leer. Einträge, die über das Ende des Geräts hinausreichen, werden
+/// übersprungen.
+pub fn read_mbr(device: &Arc<dyn BlockDevice>) -> Result<Vec<Arc<Partition>>, BlockError>
+{
+    let mut sector = vec![0; device.sector_size()];
+    device.read_sectors(0, &mut sector)?;
+    if sector[510..512] != [0x55, 0xAA]
+    {
+        return Ok(Vec::new());
+    }
+
+    let mut partitions = Vec::new();
+    for (index, entry) in sector[446..510].chunks_exact(16).enumerate()
+    {
+        let kind = entry[4];
+        let start = u64::from(u32::from_le_bytes(entry[8..12].try_into().unwrap()));
+        let sectors = u64::from(u32::from_le_bytes(entry[12..16].try_into().unwrap()));
+        if kind == 0 || EXTENDED_TYPES.contains(&kind) || sectors == 0 || start + sectors > device.sector_count()
+        {
+            continue;
+        }
+        partitions.push(Arc::new(Partition
+        {
+            name: format!("{}p{}", device.name(), index + 1),
+            device: device.clone(),
+            start,
+            sectors,
+            kind,
+        }));
+    }
+    Ok(partitions)
+}
+
+/// Liest die Partitionen von `device` und meldet sie bei [crate::block] an.
+pub fn scan(device: &Arc<dyn BlockDevice>) -> Result<Vec<Arc<Partition>>, BlockError>
+{
+    let partitions = read_mbr(device)?;
+    for partition in &partitions
+    {
+        super::register(partition.clone());
+    }
+    Ok(partitions)
+}
@@ -0,0 +1,190 @@
+//! Warteschlange für Zugriffe auf ein Blockgerät.
+//!
+//! Anfragen werden zunächst nur gesammelt. [RequestQueue::submit] sortiert sie
+//! nach Sektor (wie ein Aufzug, der die Stockwerke der Reihe nach anfährt) und
+//! fasst direkt aneinander grenzende Anfragen gleicher Richtung zu einem
+//! einzigen Gerätezugriff zusammen:
+//!
+//! ```text
+//! read 10..12, read 14..16, read 12..14  -->  read 10..16
+//! ```
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::{BlockDevice, BlockError};
+
+/// Größte Anzahl an Sektoren, die zu einem Gerätezugriff zusammengefasst werden.
+pub const MAX_MERGED_SECTORS: usize = 128;
+
+/// Richtung einer Anfrage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction
+{
+    Read,
+    Write,
+}
+
+/// ## Request
+///
+/// Eine Anfrage über zusammenhängende Sektoren ab `lba`. Bei Leseanfragen
+/// enthält `data` nach [RequestQueue::submit] die gelesenen Daten.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request
+{
+    pub direction: Direction,
+    pub lba: u64,
+    pub data: Vec<u8>,
+}
+
+/// ## RequestQueue
+///
+/// Sammelt Anfragen an ein Gerät und führt sie gebündelt aus. Die Anfragen
+/// einer Warteschlange dürfen sich nicht überlappen, da ihre Reihenfolge beim
+/// Sortieren verloren geht.
+pub struct RequestQueue
+{
+    sector_size: usize,
+    requests: Vec<Request>,
+    /// Anzahl der bisher ausgeführten Gerätezugriffe.
+    dispatched: u64,
+}
+
+impl RequestQueue
+{
+    /// Eine leere Warteschlange für ein Gerät mit Sektoren zu `sector_size` Bytes.
+    pub fn new(sector_size: usize) -> RequestQueue
+    {
+        RequestQueue { sector_size, requests: Vec::new(), dispatched: 0 }
+    }
+
+    /// Reiht das Lesen von `count` Sektoren ab `lba` ein.
+    pub fn read(&mut self, lba: u64, count: usize)
+    {
+        self.requests.push(Request { direction: Direction::Read, lba, data: vec![0; count * self.sector_size] });
+    }
+
+    /// Reiht das Schreiben von `data` ab `lba` ein.
+    pub fn write(&mut self, lba: u64, data: Vec<u8>)
+    {
+        self.requests.push(Request { direction: Direction::Write, lba, data });
+    }
+
+    /// Anzahl der wartenden Anfragen.
+    pub fn len(&self) -> usize
+    {
+        self.requests.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.requests.is_empty()
+    }
+
+    /// Anzahl der bisher an das Gerät gestellten Zugriffe.
+    pub fn dispatched(&self) -> u64
+    {
+        self.dispatched
+    }
+
+    /// Führt alle wartenden Anfragen auf `device` aus und gibt sie nach Sektor
+    /// sortiert zurück. Beim ersten Fehler bricht die Ausführung ab; die
+    /// restlichen Anfragen werden verworfen.
+    pub fn submit(&mut self, device: &dyn BlockDevice) -> Result<Vec<Request>, BlockError>
+    {
+        let mut requests = core::mem::take(&mut self.requests);
+        requests.sort_by_key(|request| request.lba);
+
+        let mut start = 0;
+        while start < requests.len()
+        {
+            let end = self.merge_end(&requests, start);
+            let batch = &mut requests[start..end];
+            let lba = batch[0].lba;
+            match batch[0].direction
+            {
+                Direction::Read =>
+                {
+                    let mut buffer = vec![0; batch.iter().map(|request| request.data.len()).sum()];
+                    device.read_sectors(lba, &mut buffer)?;
+                    let mut offset = 0;
+                    for request in batch
+                    {
+                        let length = request.data.len();
+                        request.data.copy_from_slice(&buffer[offset..offset + length]);
+                        offset += length;
+                    }
+                }
+                Direction::Write if batch.len() == 1 => device.write_sectors(lba, &batch[0].data)?,
+                Direction::Write => device.write_sectors(lba, &batch.iter().flat_map(|request| request.data.iter().copied()).collect::<Vec<u8>>())?,
+            }
+            self.dispatched += 1;
+            start = end;
+        }
+        Ok(requests)
+    }
+
+    /// Ende der Folge von Anfragen ab `start`, die sich zu einem Zugriff
+    /// zusammenfassen lassen.
+    fn merge_end(&self, requests: &[Request], start: usize) -> usize
+    {
+        let sectors = |request: &Request| request.data.len() / self.sector_size;
+        let mut end = start + 1;
+        let mut total = sectors(&requests[start]);
+        while let Some(next) = requests.get(end)
+        {
+            let previous = &requests[end - 1];
+            if next.direction != previous.direction
+                || next.lba != previous.lba + sectors(previous) as u64
+                || total + sectors(next) > MAX_MERGED_SECTORS
+            {
+                break;
+            }
+            total += sectors(next);
+            end += 1;
+        }
+        end
+    }
+}
+
+#[test_case]
+fn test_adjacent_requests_are_merged()
+{
+    let disk = super::RamDisk::new(64);
+    let mut queue = RequestQueue::new(512);
+    queue.write(4, vec![4; 1024]);
+    queue.write(2, vec![2; 1024]);
+    queue.write(10, vec![10; 512]);
+    assert_eq!(queue.len(), 3);
+    queue.submit(&disk).unwrap();
+    assert!(queue.is_empty());
+    // 2..6 in einem Zugriff, 10 getrennt.
+    assert_eq!(queue.dispatched(), 2);
+
+    queue.read(10, 1);
+    queue.read(2, 2);
+    queue.read(4, 2);
+    let requests = queue.submit(&disk).unwrap();
+    assert_eq!(queue.dispatched(), 4);
+    let lbas: Vec<u64> = requests.iter().map(|request| request.lba).collect();
+    assert_eq!(lbas, [2, 4, 10]);
+    assert!(requests[0].data.iter().all(|&byte| byte == 2));
+    assert!(requests[1].data.iter().all(|&byte| byte == 4));
+    assert!(requests[2].data.iter().all(|&byte| byte == 10));
+}
+
+#[test_case]
+fn test_merge_limits()
+{
+    let disk = super::RamDisk::new(512);
+    let mut queue = RequestQueue::new(512);
+    // Richtungswechsel und die Höchstgröße beenden eine Zusammenfassung.
+    queue.read(0, 1);
+    queue.write(1, vec![0; 512]);
+    for lba in 2..2 + MAX_MERGED_SECTORS as u64 + 1
+    {
+        queue.read(lba, 1);
+    }
+    queue.submit(&disk).unwrap();
+    assert_eq!(queue.dispatched(), 4);
+}
@@ -0,0 +1,62 @@
+//! Blockgerät im Arbeitsspeicher.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::{BlockDevice, BlockError, check_range};
+use crate::sync::RwLock;
+
+/// Sektorgröße einer [RamDisk].
+const SECTOR_SIZE: usize = 512;
+
+/// ## RamDisk
+///
+/// Ein Blockgerät mit 512-Byte-Sektoren, dessen Inhalt im Heap liegt und beim
+/// Freigeben verloren geht. Nützlich für Tests und als Zwischenspeicher.
+pub struct RamDisk
+{
+    data: RwLock<Vec<u8>>,
+}
+
+impl RamDisk
+{
+    /// Eine mit Nullen gefüllte RAM-Disk aus `sectors` Sektoren.
+    pub fn new(sectors: usize) -> RamDisk
+    {
+        RamDisk { data: RwLock::new(vec![0; sectors * SECTOR_SIZE]) }
+    }
+}
+
+impl BlockDevice for RamDisk
+{
+    fn name(&self) -> &str
+    {
+        "ram"
+    }
+
+    fn sector_size(&self) -> usize
+    {
+        SECTOR_SIZE
+    }
+
+    fn sector_count(&self) -> u64
+    {
+        (self.data.read().len() / SECTOR_SIZE) as u64
+    }
+
+    fn read_sectors(&self, lba: u64, buffer: &mut [u8]) -> Result<(), BlockError>
+    {
+        check_range(self, lba, buffer.len())?;
+        let start = lba as usize * SECTOR_SIZE;
+        buffer.copy_from_slice(&self.data.read()[start..start + buffer.len()]);
+        Ok(())
+    }
+
+    fn write_sectors(&self, lba: u64, buffer: &[u8]) -> Result<(), BlockError>
+    {
+        check_range(self, lba, buffer.len())?;
+        let start = lba as usize * SECTOR_SIZE;
+        self.data.write()[start..start + buffer.len()].copy_from_slice(buffer);
+        Ok(())
+    }
+}
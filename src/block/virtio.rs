@@ -0,0 +1,465 @@
+//! Treiber für virtio-blk.
+//!
+//! Jede Anfrage ist eine Kette aus drei Puffern:
+//!
+//! | Puffer | Größe | Richtung | Inhalt |
+//! |--------|-------|----------|--------|
+//! | Kopf | 16 | Gerät liest | Art (`IN`, `OUT`, `FLUSH`), reserviert, erster Sektor |
+//! | Daten | n · 512 | je nach Art | gelesene bzw. zu schreibende Sektoren (entfällt bei `FLUSH`) |
+//! | Status | 1 | Gerät schreibt | `OK`, `IOERR` oder `UNSUPP` |
+//!
+//! Alle drei liegen in einem eigenen [DmaBuffer] je Anfrage. Anfragen laufen
+//! **asynchron**: [VirtioBlk::submit_read] und [VirtioBlk::submit_write]
+//! stellen sie nur in die Warteschlange und geben ein [Pending] zurück, auf
+//! das später gewartet wird. Große Zugriffe über [BlockDevice] werden so in
+//! mehrere gleichzeitig laufende Anfragen aufgeteilt.
+//!
+//! Mit MSI-X meldet das Gerät fertige Anfragen über einen eigenen Vektor und
+//! der Wartende schläft über [crate::sync::wait]; ohne MSI-X oder bei
+//! gesperrten Interrupts wird der benutzte Ring abgefragt.
+
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use x86_64::instructions::interrupts;
+
+use super::{BlockDevice, BlockError, check_range};
+use crate::interrupts::vector::Handler;
+use crate::memory::dma::DmaBuffer;
+use crate::pci::{self, DeviceId, PciDevice, PciDriver, PciError};
+use crate::sync::{IrqSpinlock, Mutex, Once};
+use crate::virtio::{Buffer, DeviceType, Transport, VENDOR_ID, VirtQueue};
+
+/// Sektorgröße, in der virtio-blk immer adressiert.
+pub const SECTOR_SIZE: usize = 512;
+
+/// Höchste Anzahl Sektoren pro Anfrage.
+const MAX_SECTORS_PER_REQUEST: usize = 128;
+
+/// Höchste Anzahl Einträge der Warteschlange.
+const QUEUE_SIZE: u16 = 128;
+
+/// Lage von Kopf, Status und Daten im [DmaBuffer] einer Anfrage.
+const HEADER_SIZE: u32 = 16;
+const STATUS_OFFSET: usize = 16;
+const DATA_OFFSET: usize = 512;
+
+/// Anzahl der Abfragen bei gesperrten Interrupts, bevor eine Anfrage als
+/// [BlockError::Timeout] gilt.
+const POLL_LIMIT: usize = 10_000_000;
+
+/// Wartezeit auf eine Anfrage bei aktiven Interrupts.
+const TIMEOUT_MS: u64 = 5000;
+
+/// Merkmale des Geräts.
+mod feature
+{
+    /// Das Gerät ist schreibgeschützt.
+    pub const RO: u64 = 1 << 5;
+    /// Das Gerät kennt `FLUSH`.
+    pub const FLUSH: u64 = 1 << 9;
+}
+
+/// Arten von Anfragen.
+mod request
+{
+    pub const IN: u32 = 0;
+    pub const OUT: u32 = 1;
+    pub const FLUSH: u32 = 4;
+}
+
+/// Werte des Statusbytes.
+mod status
+{
+    pub const OK: u8 = 0;
+}
+
+/// Offsets in der gerätespezifischen Konfiguration.
+mod config
+{
+    /// Kapazität in 512-Byte-Sektoren.
+    pub const CAPACITY: u16 = 0;
+}
+
+/// Die Warteschlange und ihre laufenden Anfragen.
+struct Queue
+{
+    transport: Transport,
+    queue: VirtQueue,
+    /// Laufende Anfragen: Kopf der Kette → Kennung der Anfrage.
+    in_flight: BTreeMap<u16, u64>,
+    /// Fertige Anfragen, auf die noch niemand gewartet hat.
+    completed: BTreeSet<u64>,
+    next_token: u64,
+}
+
+impl Queue
+{
+    /// Übernimmt alle fertigen Ketten aus dem benutzten Ring.
+    fn reap(&mut self)
+    {
+        while let Some((head, _)) = self.queue.pop_used()
+        {
+            if let Some(token) = self.in_flight.remove(&head)
+            {
+                self.completed.insert(token);
+            }
+        }
+    }
+}
+
+/// ## VirtioBlk
+///
+/// Eine virtio-Festplatte. Die Namen lauten `vda`, `vdb` usw. in der
+/// Reihenfolge der Erkennung.
+pub struct VirtioBlk
+{
+    name: String,
+    sectors: u64,
+    read_only: bool,
+    flush: bool,
+    msix: bool,
+    /// Anzahl der empfangenen Interrupts.
+    interrupts: Arc<AtomicU64>,
+    queue: Mutex<Queue>,
+}
+
+impl VirtioBlk
+{
+    /// Handelt die Merkmale aus und richtet die Warteschlange ein.
+    fn new(index: usize, device: &Arc<PciDevice>) -> Result<VirtioBlk, PciError>
+    {
+        let mut transport = Transport::new(device)?;
+        let features = transport.negotiate(feature::RO | feature::FLUSH)?;
+        let interrupts = Arc::new(AtomicU64::new(0));
+        let msix = transport.enable_msix();
+        let handler = msix.then(||
+        {
+            let interrupts = interrupts.clone();
+            Box::new(move || { interrupts.fetch_add(1, Ordering::Relaxed); }) as Handler
+        });
+        let queue = transport.setup_queue(0, QUEUE_SIZE, handler)?;
+        let sectors = transport.config_read_u64(config::CAPACITY);
+        transport.finish();
+
+        Ok(VirtioBlk
+        {
+            name: format!("vd{}", char::from(b'a' + index as u8)),
+            sectors,
+            read_only: features & feature::RO != 0,
+            flush: features & feature::FLUSH != 0,
+            msix,
+            interrupts,
+            queue: Mutex::new(Queue { transport, queue, in_flight: BTreeMap::new(), completed: BTreeSet::new(), next_token: 0 }),
+        })
+    }
+
+    /// Gibt an, ob das Gerät schreibgeschützt ist.
+    pub fn is_read_only(&self) -> bool
+    {
+        self.read_only
+    }
+
+    /// Gibt an, ob das Gerät fertige Anfragen über MSI-X meldet.
+    pub fn uses_msix(&self) -> bool
+    {
+        self.msix
+    }
+
+    /// Anzahl der bisher empfangenen Interrupts.
+    pub fn interrupt_count(&self) -> u64
+    {
+        self.interrupts.load(Ordering::Relaxed)
+    }
+
+    /// Wartet kurz auf das nächste Ereignis des Geräts. Gibt
+    /// [BlockError::Timeout] zurück, wenn seit `started` (in Ticks) bzw. nach
+    /// `polls` Abfragen zu viel Zeit vergangen ist.
+    fn wait_event(&self, started: u64, polls: &mut usize) -> Result<(), BlockError>
+    {
+        if interrupts::are_enabled()
+        {
+            if crate::time::ticks() - started > crate::time::ms_to_ticks(TIMEOUT_MS)
+            {
+                return Err(BlockError::Timeout);
+            }
+            if self.msix
+            {
+                crate::sync::wait();
+                return Ok(());
+            }
+        }
+        else
+        {
+            *polls += 1;
+            if *polls > POLL_LIMIT
+            {
+                return Err(BlockError::Timeout);
+            }
+        }
+        core::hint::spin_loop();
+        Ok(())
+    }
+
+    /// Stellt eine Anfrage mit `len` Datenbytes in die Warteschlange. Ist sie
+    /// voll, wird gewartet, bis andere Anfragen fertig sind.
+    fn submit(&self, kind: u32, lba: u64, len: usize, data: Option<&[u8]>) -> Result<Pending<'_>, BlockError>
+    {
+        let buffer = DmaBuffer::new(DATA_OFFSET + len).ok_or(BlockError::Io)?;
+        buffer.write(0, kind);
+        buffer.write(8, lba);
+        buffer.write(STATUS_OFFSET, u8::MAX);
+        if let Some(data) = data
+        {
+            buffer.write_bytes(DATA_OFFSET, data);
+        }
+
+        let start = buffer.physical_address();
+        let mut chain = Vec::with_capacity(3);
+        chain.push(Buffer { address: start, len: HEADER_SIZE, writable: false });
+        if len > 0
+        {
+            chain.push(Buffer { address: start + DATA_OFFSET as u64, len: len as u32, writable: kind == request::IN });
+        }
+        chain.push(Buffer { address: start + STATUS_OFFSET as u64, len: 1, writable: true });
+
+        let started = crate::time::ticks();
+        let mut polls = 0;
+        let token = loop
+        {
+            {
+                let mut queue = self.queue.lock();
+                queue.reap();
+                if let Some(head) = queue.queue.add(&chain)
+                {
+                    let token = queue.next_token;
+                    queue.next_token += 1;
+                    queue.in_flight.insert(head, token);
+                    queue.transport.notify(&queue.queue);
+                    break token;
+                }
+            }
+            self.wait_event(started, &mut polls)?;
+        };
+        Ok(Pending { disk: self, token, buffer: Some(buffer), done: false })
+    }
+
+    /// Startet das Lesen von `sectors` Sektoren ab `lba`, höchstens 128.
+    pub fn submit_read(&self, lba: u64, sectors: usize) -> Result<Pending<'_>, BlockError>
+    {
+        assert!(sectors <= MAX_SECTORS_PER_REQUEST, "virtio-blk request too large");
+        check_range(self, lba, sectors * SECTOR_SIZE)?;
+        self.submit(request::IN, lba, sectors * SECTOR_SIZE, None)
+    }
+
+    /// Startet das Schreiben von `data` ab `lba`, höchstens 128 Sektoren.
+    pub fn submit_write(&self, lba: u64, data: &[u8]) -> Result<Pending<'_>, BlockError>
+    {
+        assert!(data.len() <= MAX_SECTORS_PER_REQUEST * SECTOR_SIZE, "virtio-blk request too large");
+        check_range(self, lba, data.len())?;
+        if self.read_only
+        {
+            return Err(BlockError::ReadOnly);
+        }
+        self.submit(request::OUT, lba, data.len(), Some(data))
+    }
+}
+
+impl BlockDevice for VirtioBlk
+{
+    fn name(&self) -> &str
+    {
+        &self.name
+    }
+
+    fn sector_size(&self) -> usize
+    {
+        SECTOR_SIZE
+    }
+
+    fn sector_count(&self) -> u64
+    {
+        self.sectors
+    }
+
+    fn read_sectors(&self, lba: u64, buffer: &mut [u8]) -> Result<(), BlockError>
+    {
+        check_range(self, lba, buffer.len())?;
+        let chunk = MAX_SECTORS_PER_REQUEST * SECTOR_SIZE;
+        let pending: Vec<Pending> = (0..buffer.len().div_ceil(chunk))
+            .map(|index| self.submit_read(lba + (index * MAX_SECTORS_PER_REQUEST) as u64, (buffer.len() - index * chunk).min(chunk) / SECTOR_SIZE))
+            .collect::<Result<_, _>>()?;
+        for (request, part) in pending.into_iter().zip(buffer.chunks_mut(chunk))
+        {
+            request.read_into(part)?;
+        }
+        Ok(())
+    }
+
+    fn write_sectors(&self, lba: u64, buffer: &[u8]) -> Result<(), BlockError>
+    {
+        check_range(self, lba, buffer.len())?;
+        let pending: Vec<Pending> = buffer
+            .chunks(MAX_SECTORS_PER_REQUEST * SECTOR_SIZE)
+            .enumerate()
+            .map(|(index, part)| self.submit_write(lba + (index * MAX_SECTORS_PER_REQUEST) as u64, part))
+            .collect::<Result<_, _>>()?;
+        pending.into_iter().try_for_each(Pending::wait)
+    }
+
+    fn flush(&self) -> Result<(), BlockError>
+    {
+        if !self.flush
+        {
+            return Ok(());
+        }
+        self.submit(request::FLUSH, 0, 0, None)?.wait()
+    }
+}
+
+/// ## Pending
+///
+/// Eine laufende Anfrage. Wird sie freigegeben, ohne dass jemand gewartet
+/// hat, wartet [Drop] auf sie, denn bis dahin darf das Gerät ihren Puffer
+/// noch beschreiben.
+pub struct Pending<'a>
+{
+    disk: &'a VirtioBlk,
+    token: u64,
+    buffer: Option<DmaBuffer>,
+    done: bool,
+}
+
+impl Pending<'_>
+{
+    /// Gibt an, ob das Gerät die Anfrage bearbeitet hat.
+    pub fn is_complete(&self) -> bool
+    {
+        let mut queue = self.disk.queue.lock();
+        queue.reap();
+        self.done || queue.completed.contains(&self.token)
+    }
+
+    /// Wartet auf die Anfrage und prüft ihren Status.
+    fn complete(&mut self) -> Result<&DmaBuffer, BlockError>
+    {
+        if !self.done
+        {
+            let started = crate::time::ticks();
+            let mut polls = 0;
+            loop
+            {
+                {
+                    let mut queue = self.disk.queue.lock();
+                    queue.reap();
+                    if queue.completed.remove(&self.token)
+                    {
+                        break;
+                    }
+                }
+                if let Err(error) = self.disk.wait_event(started, &mut polls)
+                {
+                    self.done = true;
+                    self.disk.queue.lock().in_flight.retain(|_, token| *token != self.token);
+                    // Das Gerät könnte den Puffer noch beschreiben; er wird
+                    // daher nie wieder vergeben.
+                    core::mem::forget(self.buffer.take());
+                    return Err(error);
+                }
+            }
+            self.done = true;
+        }
+        match &self.buffer
+        {
+            Some(buffer) if buffer.read::<u8>(STATUS_OFFSET) == status::OK => Ok(buffer),
+            _ => Err(BlockError::Io),
+        }
+    }
+
+    /// Wartet, bis das Gerät die Anfrage bearbeitet hat.
+    pub fn wait(mut self) -> Result<(), BlockError>
+    {
+        self.complete().map(|_| ())
+    }
+
+    /// Wartet auf eine Leseanfrage und kopiert die gelesenen Sektoren nach
+    /// `buffer`, das genau so lang sein muss wie die Anfrage.
+    pub fn read_into(mut self, buffer: &mut [u8]) -> Result<(), BlockError>
+    {
+        self.complete()?.read_bytes(DATA_OFFSET, buffer);
+        Ok(())
+    }
+}
+
+impl Drop for Pending<'_>
+{
+    fn drop(&mut self)
+    {
+        if !self.done
+        {
+            let _ = self.complete();
+        }
+    }
+}
+
+/// Alle erkannten virtio-Festplatten.
+static DISKS: IrqSpinlock<Vec<Arc<VirtioBlk>>> = IrqSpinlock::new(Vec::new());
+
+/// Nummer der nächsten Festplatte für ihren Namen.
+static NEXT_INDEX: AtomicUsize = AtomicUsize::new(0);
+
+/// ## VirtioBlkDriver
+///
+/// Übernimmt virtio-blk-Geräte am PCI-Bus und meldet sie bei [crate::block]
+/// an.
+struct VirtioBlkDriver;
+
+impl PciDriver for VirtioBlkDriver
+{
+    fn name(&self) -> &str
+    {
+        "virtio-blk"
+    }
+
+    fn ids(&self) -> &[DeviceId]
+    {
+        const IDS: [DeviceId; 2] = [
+            DeviceId::Device { vendor: VENDOR_ID, device: DeviceType::Block.transitional_id() },
+            DeviceId::Device { vendor: VENDOR_ID, device: DeviceType::Block.modern_id() },
+        ];
+        &IDS
+    }
+
+    fn probe(&self, device: &Arc<PciDevice>) -> Result<(), PciError>
+    {
+        let disk = Arc::new(VirtioBlk::new(NEXT_INDEX.load(Ordering::Relaxed), device)?);
+        NEXT_INDEX.fetch_add(1, Ordering::Relaxed);
+        super::register(disk.clone());
+        DISKS.lock().push(disk);
+        Ok(())
+    }
+}
+
+/// ## Treiber anmelden
+///
+/// Meldet den Treiber bei [crate::pci] an. Er übernimmt alle bereits
+/// gefundenen und alle später von [pci::init] gefundenen virtio-blk-Geräte.
+/// Weitere Aufrufe haben keine Wirkung.
+pub fn register_driver()
+{
+    static DRIVER: Once<()> = Once::new();
+    DRIVER.call_once(||
+    {
+        pci::register_driver(Arc::new(VirtioBlkDriver));
+    });
+}
+
+/// Die bisher erkannten Festplatten, in der Reihenfolge ihrer Namen.
+pub fn disks() -> Vec<Arc<VirtioBlk>>
+{
+    DISKS.lock().clone()
+}
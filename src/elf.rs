@@ -0,0 +1,513 @@
+//! # Modul: elf
+//!
+//! Dieses Modul lädt **statisch gelinkte ELF64-Programme** in einen eigenen
+//! Adressraum und startet sie im Ring 3.
+//!
+//! # Ablauf
+//!
+//! ```text
+//! ElfFile::parse() --> load() --> LoadedProgram::run() --iretq--> Programm
+//!        |               |
+//!   Header prüfen   PT_LOAD-Segmente abbilden, User-Stack mit argv/envp/auxv anlegen
+//! ```
+//!
+//! # Einschränkungen
+//!
+//! - Nur `ET_EXEC` für x86_64, Little Endian, keine dynamischen Programme
+//! - Alle Segmente müssen zwischen [USER_SPACE_START] und [USER_SPACE_END] liegen,
+//!   da die übrigen Bereiche zum Kernel gehören
+//! - Teilen sich zwei Segmente eine Seite, bekommt sie die Rechte beider. Würde
+//!   die Seite dadurch beschreibbar und ausführbar, obwohl keines der Segmente
+//!   das verlangt, wird das Programm abgelehnt
+//! - Die 16 Bytes für `AT_RANDOM` stammen aus `rdrand`. Ohne `rdrand` (z. B.
+//!   mit dem Standard-CPU-Modell von QEMU) wird der Zeitstempelzähler
+//!   verwendet; diese Bytes sind dann vorhersagbar und **nicht** zufällig
+//!
+//! # Aufbau des initialen Stacks
+//!
+//! Wie von der System V ABI vorgegeben, zeigt `rsp` beim Start auf `argc`:
+//!
+//! ```text
+//! rsp --> argc
+//!         argv[0] .. argv[argc - 1], NULL
+//!         envp[0] .. envp[envc - 1], NULL
+//!         auxv (Typ, Wert)-Paare, AT_NULL
+//!         ... Zeichenketten und 16 Zufallsbytes für AT_RANDOM
+//! USER_STACK_TOP
+//! ```
+
+use x86_64::VirtAddr;
+use x86_64::structures::paging::{Page, PageTableFlags};
+
+use crate::memory::{AddressSpace, PAGE_SIZE, USER_SPACE_END, USER_SPACE_START};
+use crate::usermode::{USER_STACK_PAGES, USER_STACK_TOP};
+
+const ELF_MAGIC: [u8; 4] = *b"\x7fELF";
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const EV_CURRENT: u8 = 1;
+const ET_EXEC: u16 = 2;
+const EM_X86_64: u16 = 0x3e;
+
+/// Segmenttyp für Segmente, die in den Speicher geladen werden.
+pub const PT_LOAD: u32 = 1;
+
+/// Segment ist ausführbar.
+pub const PF_X: u32 = 1;
+/// Segment ist beschreibbar.
+pub const PF_W: u32 = 2;
+
+const AT_NULL: u64 = 0;
+const AT_PHDR: u64 = 3;
+const AT_PHENT: u64 = 4;
+const AT_PHNUM: u64 = 5;
+const AT_PAGESZ: u64 = 6;
+const AT_ENTRY: u64 = 9;
+const AT_RANDOM: u64 = 25;
+
+/// Anzahl der (Typ, Wert)-Paare im Auxiliary Vector inklusive `AT_NULL`.
+const AUXV_ENTRIES: usize = 7;
+
+/// Fehler beim Prüfen oder Laden eines ELF-Programms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElfError
+{
+    /// Die Datei ist kürzer als ein ELF-Header.
+    TooShort,
+    /// Die ersten vier Bytes sind nicht `\x7fELF`.
+    BadMagic,
+    /// Keine 64-Bit-, Little-Endian-Datei der aktuellen Version.
+    UnsupportedFormat,
+    /// Kein ausführbares Programm (`ET_EXEC`) für x86_64.
+    NotExecutable,
+    /// Die Programm-Header liegen (teilweise) außerhalb der Datei.
+    BadProgramHeaders,
+    /// Ein Segment ist inkonsistent (z. B. `p_filesz > p_memsz`) oder liegt außerhalb der Datei.
+    BadSegment,
+    /// Ein Segment oder der Einsprungpunkt liegt nicht im User-Bereich.
+    NotInUserSpace,
+    /// Zwei Segmente teilen sich eine Seite, die dadurch beschreibbar und
+    /// ausführbar würde.
+    ConflictingSegments,
+    /// Für Seitentabellen oder Segmente war kein Speicher mehr frei.
+    OutOfMemory,
+    /// Argumente und Umgebung passen nicht auf den User-Stack.
+    ArgumentsTooLarge,
+}
+
+/// ELF64-Dateiheader.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct ElfHeader
+{
+    pub e_ident: [u8; 16],
+    pub e_type: u16,
+    pub e_machine: u16,
+    pub e_version: u32,
+    pub e_entry: u64,
+    pub e_phoff: u64,
+    pub e_shoff: u64,
+    pub e_flags: u32,
+    pub e_ehsize: u16,
+    pub e_phentsize: u16,
+    pub e_phnum: u16,
+    pub e_shentsize: u16,
+    pub e_shnum: u16,
+    pub e_shstrndx: u16,
+}
+
+/// ELF64-Programm-Header, beschreibt ein Segment.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct ProgramHeader
+{
+    pub p_type: u32,
+    pub p_flags: u32,
+    pub p_offset: u64,
+    pub p_vaddr: u64,
+    pub p_paddr: u64,
+    pub p_filesz: u64,
+    pub p_memsz: u64,
+    pub p_align: u64,
+}
+
+/// Liest eine `repr(C)`-Struktur aus `data` ab `offset`, ohne Ausrichtung vorauszusetzen.
+fn read_struct<T: Copy>(data: &[u8], offset: u64) -> Option<T>
+{
+    let offset = usize::try_from(offset).ok()?;
+    let end = offset.checked_add(core::mem::size_of::<T>())?;
+    let bytes = data.get(offset..end)?;
+    // SAFETY: Die Länge wurde geprüft und T besteht nur aus Ganzzahlen.
+    Some(unsafe { core::ptr::read_unaligned(bytes.as_ptr() as *const T) })
+}
+
+/// ## ElfFile
+///
+/// Eine geprüfte ELF-Datei. [parse](ElfFile::parse) stellt sicher, dass der
+/// Header gültig ist und alle Programm-Header und Segmente innerhalb der Datei liegen.
+pub struct ElfFile<'a>
+{
+    data: &'a [u8],
+    header: ElfHeader,
+}
+
+impl<'a> ElfFile<'a>
+{
+    /// Prüft `data` und gibt bei Erfolg eine [ElfFile] zurück.
+    pub fn parse(data: &'a [u8]) -> Result<ElfFile<'a>, ElfError>
+    {
+        let header: ElfHeader = read_struct(data, 0).ok_or(ElfError::TooShort)?;
+        let ident = &header.e_ident;
+
+        if ident[..4] != ELF_MAGIC
+        {
+            return Err(ElfError::BadMagic);
+        }
+        if ident[4] != ELFCLASS64 || ident[5] != ELFDATA2LSB || ident[6] != EV_CURRENT
+        {
+            return Err(ElfError::UnsupportedFormat);
+        }
+        if header.e_type != ET_EXEC || header.e_machine != EM_X86_64
+        {
+            return Err(ElfError::NotExecutable);
+        }
+        if usize::from(header.e_phentsize) != core::mem::size_of::<ProgramHeader>()
+        {
+            return Err(ElfError::BadProgramHeaders);
+        }
+        let table_size = u64::from(header.e_phnum) * u64::from(header.e_phentsize);
+        let table_end = header.e_phoff.checked_add(table_size).ok_or(ElfError::BadProgramHeaders)?;
+        if table_end > data.len() as u64
+        {
+            return Err(ElfError::BadProgramHeaders);
+        }
+
+        let file = ElfFile { data, header };
+        for segment in file.program_headers().filter(|ph| ph.p_type == PT_LOAD)
+        {
+            file.check_segment(&segment)?;
+        }
+        file.check_shared_pages()?;
+        if !(USER_SPACE_START..USER_SPACE_END).contains(&header.e_entry)
+        {
+            return Err(ElfError::NotInUserSpace);
+        }
+        Ok(file)
+    }
+
+    /// Prüft ein `PT_LOAD`-Segment auf Konsistenz und Lage im User-Bereich.
+    fn check_segment(&self, segment: &ProgramHeader) -> Result<(), ElfError>
+    {
+        let file_end = segment.p_offset.checked_add(segment.p_filesz).ok_or(ElfError::BadSegment)?;
+        if segment.p_filesz > segment.p_memsz || file_end > self.data.len() as u64
+        {
+            return Err(ElfError::BadSegment);
+        }
+        let mem_end = segment.p_vaddr.checked_add(segment.p_memsz).ok_or(ElfError::BadSegment)?;
+        if segment.p_vaddr < USER_SPACE_START || mem_end > USER_SPACE_END
+        {
+            return Err(ElfError::NotInUserSpace);
+        }
+        Ok(())
+    }
+
+    /// Prüft, ob zwei `PT_LOAD`-Segmente sich eine Seite teilen, deren
+    /// zusammengeführte Rechte (siehe [load]) `W` und `X` ergäben, ohne dass
+    /// beide Segmente genau diese Rechte haben.
+    fn check_shared_pages(&self) -> Result<(), ElfError>
+    {
+        let pages = |ph: &ProgramHeader| (ph.p_vaddr / PAGE_SIZE, (ph.p_vaddr + ph.p_memsz - 1) / PAGE_SIZE);
+        let segments = || self.program_headers().filter(|ph| ph.p_type == PT_LOAD && ph.p_memsz > 0);
+        for (index, a) in segments().enumerate()
+        {
+            for b in segments().skip(index + 1)
+            {
+                let ((a_first, a_last), (b_first, b_last)) = (pages(&a), pages(&b));
+                let rights = |ph: &ProgramHeader| ph.p_flags & (PF_W | PF_X);
+                if a_first <= b_last && b_first <= a_last && rights(&a) | rights(&b) == PF_W | PF_X && rights(&a) != rights(&b)
+                {
+                    return Err(ElfError::ConflictingSegments);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Der Dateiheader.
+    pub fn header(&self) -> &ElfHeader
+    {
+        &self.header
+    }
+
+    /// Virtuelle Adresse des Einsprungpunkts.
+    pub fn entry(&self) -> VirtAddr
+    {
+        VirtAddr::new(self.header.e_entry)
+    }
+
+    /// Iterator über alle Programm-Header.
+    pub fn program_headers(&self) -> impl Iterator<Item = ProgramHeader> + '_
+    {
+        let size = u64::from(self.header.e_phentsize);
+        (0..u64::from(self.header.e_phnum))
+            .filter_map(move |i| read_struct(self.data, self.header.e_phoff + i * size))
+    }
+
+    /// Virtuelle Adresse der Programm-Header, falls sie Teil eines geladenen Segments sind.
+    fn program_headers_vaddr(&self) -> Option<u64>
+    {
+        let phoff = self.header.e_phoff;
+        self.program_headers()
+            .filter(|ph| ph.p_type == PT_LOAD)
+            .find(|ph| (ph.p_offset..ph.p_offset + ph.p_filesz).contains(&phoff))
+            .map(|ph| ph.p_vaddr + (phoff - ph.p_offset))
+    }
+}
+
+/// ## LoadedProgram
+///
+/// Ein in seinen eigenen Adressraum geladenes Programm, bereit zum Start.
+#[derive(Debug)]
+pub struct LoadedProgram
+{
+    pub address_space: AddressSpace,
+    pub entry: VirtAddr,
+    pub stack_pointer: VirtAddr,
+}
+
+impl LoadedProgram
+{
+    /// Wechselt in den Adressraum des Programms, führt es im Ring 3 aus und
+    /// kehrt nach dem Systemaufruf `exit` mit dessen Exit-Code zurück.
+    ///
+    /// # Safety
+    ///
+    /// Wie bei [run_user_mode](crate::usermode::run_user_mode).
+    pub unsafe fn run(&self) -> i64
+    {
+        unsafe
+        {
+            self.address_space.activate();
+            let code = crate::usermode::run_user_mode(self.entry, self.stack_pointer);
+            AddressSpace::kernel().activate();
+            code
+        }
+    }
+}
+
+/// ## Programm laden
+///
+/// Prüft `data`, legt einen neuen [AddressSpace] an, bildet alle
+/// `PT_LOAD`-Segmente mit passenden Rechten ab und baut den User-Stack mit
+/// `argv`, `envp` und dem Auxiliary Vector auf.
+///
+/// | Segment-Flag | Seitentabellen-Flag |
+/// |--------------|---------------------|
+/// | `PF_W` | `WRITABLE` |
+/// | kein `PF_X` | `NO_EXECUTE` |
+pub fn load(data: &[u8], argv: &[&str], envp: &[&str]) -> Result<LoadedProgram, ElfError>
+{
+    let file = ElfFile::parse(data)?;
+    let mut address_space = AddressSpace::new().map_err(|_| ElfError::OutOfMemory)?;
+
+    for segment in file.program_headers().filter(|ph| ph.p_type == PT_LOAD && ph.p_memsz > 0)
+    {
+        load_segment(&mut address_space, data, &segment)?;
+    }
+
+    let stack_pointer = setup_stack(&mut address_space, &file, argv, envp)?;
+
+    Ok(LoadedProgram { address_space, entry: file.entry(), stack_pointer })
+}
+
+/// Bildet ein Segment ab und kopiert dessen Inhalt aus der Datei. Der Bereich
+/// zwischen `p_filesz` und `p_memsz` (z. B. `.bss`) bleibt genullt.
+fn load_segment(address_space: &mut AddressSpace, data: &[u8], segment: &ProgramHeader) -> Result<(), ElfError>
+{
+    let mut flags = PageTableFlags::empty();
+    if segment.p_flags & PF_W != 0
+    {
+        flags |= PageTableFlags::WRITABLE;
+    }
+    if segment.p_flags & PF_X == 0
+    {
+        flags |= PageTableFlags::NO_EXECUTE;
+    }
+
+    let start = VirtAddr::new(segment.p_vaddr);
+    let first = Page::containing_address(start);
+    let last = Page::containing_address(start + (segment.p_memsz - 1));
+    for page in Page::range_inclusive(first, last)
+    {
+        address_space.map_user_page(page, flags).map_err(|_| ElfError::OutOfMemory)?;
+    }
+
+    let contents = &data[segment.p_offset as usize..(segment.p_offset + segment.p_filesz) as usize];
+    if !address_space.write(start, contents)
+    {
+        return Err(ElfError::BadSegment);
+    }
+    Ok(())
+}
+
+/// Legt den User-Stack an und schreibt argc, argv, envp, auxv und die
+/// Zeichenketten. Gibt den initialen Stackzeiger zurück.
+fn setup_stack(address_space: &mut AddressSpace, file: &ElfFile, argv: &[&str], envp: &[&str])
+    -> Result<VirtAddr, ElfError>
+{
+    let top = USER_STACK_TOP;
+    let stack_size = USER_STACK_PAGES * PAGE_SIZE;
+    let bottom = top - stack_size;
+
+    let string_size: u64 = 16 + argv.iter().chain(envp).map(|s| s.len() as u64 + 1).sum::<u64>();
+    let words = 1 + (argv.len() + 1) + (envp.len() + 1) + 2 * AUXV_ENTRIES;
+    let strings_start = top - string_size;
+    let sp = (strings_start - words as u64 * 8) & !0xf;
+    if string_size + words as u64 * 8 + 16 > stack_size
+    {
+        return Err(ElfError::ArgumentsTooLarge);
+    }
+
+    for page in Page::range_inclusive(
+        Page::containing_address(VirtAddr::new(bottom)),
+        Page::containing_address(VirtAddr::new(top - 1)),
+    )
+    {
+        address_space
+            .map_user_page(page, PageTableFlags::WRITABLE | PageTableFlags::NO_EXECUTE)
+            .map_err(|_| ElfError::OutOfMemory)?;
+    }
+
+    // Zeichenketten: zuerst die Zufallsbytes, dann argv und envp.
+    let random_addr = strings_start;
+    address_space.write(VirtAddr::new(random_addr), &random_bytes());
+
+    let mut string_cursor = random_addr + 16;
+    let mut word_cursor = sp;
+    let mut push_word = |address_space: &mut AddressSpace, value: u64|
+    {
+        address_space.write(VirtAddr::new(word_cursor), &value.to_le_bytes());
+        word_cursor += 8;
+    };
+
+    push_word(address_space, argv.len() as u64);
+    for list in [argv, envp]
+    {
+        for string in list
+        {
+            address_space.write(VirtAddr::new(string_cursor), string.as_bytes());
+            address_space.write(VirtAddr::new(string_cursor + string.len() as u64), &[0]);
+            push_word(address_space, string_cursor);
+            string_cursor += string.len() as u64 + 1;
+        }
+        push_word(address_space, 0);
+    }
+
+    let auxv: [(u64, u64); AUXV_ENTRIES] =
+    [
+        (AT_PHDR, file.program_headers_vaddr().unwrap_or(0)),
+        (AT_PHENT, u64::from(file.header().e_phentsize)),
+        (AT_PHNUM, u64::from(file.header().e_phnum)),
+        (AT_PAGESZ, PAGE_SIZE),
+        (AT_ENTRY, file.entry().as_u64()),
+        (AT_RANDOM, random_addr),
+        (AT_NULL, 0),
+    ];
+    for (key, value) in auxv
+    {
+        push_word(address_space, key);
+        push_word(address_space, value);
+    }
+
+    Ok(VirtAddr::new(sp))
+}
+
+/// 16 Bytes für `AT_RANDOM`: aus `rdrand`, falls die CPU es kann, sonst aus
+/// dem Zeitstempelzähler. Letztere sind **nicht** zufällig, sondern nur von
+/// Start zu Start verschieden.
+fn random_bytes() -> [u8; 16]
+{
+    use x86_64::instructions::random::RdRand;
+
+    let words = RdRand::new()
+        .and_then(|rdrand| Some([rdrand.get_u64()?, rdrand.get_u64()?]))
+        .unwrap_or_else(|| unsafe { [core::arch::x86_64::_rdtsc(), core::arch::x86_64::_rdtsc()] });
+    let mut bytes = [0; 16];
+    bytes[..8].copy_from_slice(&words[0].to_le_bytes());
+    bytes[8..].copy_from_slice(&words[1].to_le_bytes());
+    bytes
+}
+
+/// Minimaler gültiger ELF-Header (ohne Programm-Header) für die Tests.
+#[cfg(test)]
+fn test_header() -> [u8; 64]
+{
+    let mut header = [0u8; 64];
+    header[..4].copy_from_slice(&ELF_MAGIC);
+    header[4] = ELFCLASS64;
+    header[5] = ELFDATA2LSB;
+    header[6] = EV_CURRENT;
+    header[16..18].copy_from_slice(&ET_EXEC.to_le_bytes());
+    header[18..20].copy_from_slice(&EM_X86_64.to_le_bytes());
+    header[24..32].copy_from_slice(&(USER_SPACE_START + 0x1000).to_le_bytes());
+    header[32..40].copy_from_slice(&64u64.to_le_bytes());
+    header[54..56].copy_from_slice(&56u16.to_le_bytes());
+    header
+}
+
+#[test_case]
+fn test_parse_rejects_malformed_headers()
+{
+    assert_eq!(ElfFile::parse(&[0x7f, b'E']).err(), Some(ElfError::TooShort));
+    assert!(ElfFile::parse(&test_header()).is_ok());
+
+    let mut header = test_header();
+    header[0] = 0;
+    assert_eq!(ElfFile::parse(&header).err(), Some(ElfError::BadMagic));
+
+    let mut header = test_header();
+    header[4] = 1;
+    assert_eq!(ElfFile::parse(&header).err(), Some(ElfError::UnsupportedFormat));
+
+    let mut header = test_header();
+    header[18] = 0x03;
+    assert_eq!(ElfFile::parse(&header).err(), Some(ElfError::NotExecutable));
+
+    let mut header = test_header();
+    header[56] = 1;
+    assert_eq!(ElfFile::parse(&header).err(), Some(ElfError::BadProgramHeaders));
+
+    let mut header = test_header();
+    header[24..32].copy_from_slice(&0x20_0000u64.to_le_bytes());
+    assert_eq!(ElfFile::parse(&header).err(), Some(ElfError::NotInUserSpace));
+}
+
+/// [test_header] mit zwei leeren `PT_LOAD`-Segmenten (`flags`, Adresse).
+#[cfg(test)]
+fn test_with_segments(segments: [(u32, u64); 2]) -> [u8; 64 + 2 * 56]
+{
+    let mut file = [0u8; 64 + 2 * 56];
+    file[..64].copy_from_slice(&test_header());
+    file[56..58].copy_from_slice(&2u16.to_le_bytes());
+    for (index, (flags, vaddr)) in segments.into_iter().enumerate()
+    {
+        let ph = &mut file[64 + index * 56..64 + (index + 1) * 56];
+        ph[0..4].copy_from_slice(&PT_LOAD.to_le_bytes());
+        ph[4..8].copy_from_slice(&flags.to_le_bytes());
+        ph[16..24].copy_from_slice(&vaddr.to_le_bytes());
+        ph[40..48].copy_from_slice(&0x100u64.to_le_bytes());
+    }
+    file
+}
+
+#[test_case]
+fn test_parse_rejects_writable_code_pages()
+{
+    let text = USER_SPACE_START + 0x1000;
+    // Code und Daten auf derselben Seite ergäben eine W+X-Seite.
+    let file = test_with_segments([(PF_X, text), (PF_W, text + 0x800)]);
+    assert_eq!(ElfFile::parse(&file).err(), Some(ElfError::ConflictingSegments));
+    // Auf getrennten Seiten oder mit verträglichen Rechten ist das erlaubt.
+    assert!(ElfFile::parse(&test_with_segments([(PF_X, text), (PF_W, text + 0x1000)])).is_ok());
+    assert!(ElfFile::parse(&test_with_segments([(PF_X, text), (0, text + 0x800)])).is_ok());
+}
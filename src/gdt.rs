@@ -4,7 +4,8 @@
 //!
 //! Die GDT ist eine zentrale Struktur im x86_64-System, die Speichersegmente verwaltet.
 //! Sie definiert unter anderem:
-//! - Das **Kernel-Code-Segment**
+//! - Das **Kernel-Code-** und **Kernel-Daten-Segment**
+//! - Das **User-Code-** und **User-Daten-Segment** für Ring 3
 //! - Das **Task State Segment (TSS)** für Interrupt-Stack-Handling
 //!
 //! ## Übersicht
@@ -12,7 +13,12 @@
 //! - **Globale Initialisierung:** Die GDT wird einmalig über [`lazy_static`] erstellt.
 //! - **Segmente:**  
 //!   - Kernel-Code-Segment → für die CPU-Ausführung des Kernelcodes  
+//!   - Kernel-Daten-Segment → wird in SS/DS/ES geladen  
+//!   - User-Daten- und User-Code-Segment (DPL 3) → für Programme im Ring 3  
 //!   - TSS-Segment → ermöglicht eigene Stacks für bestimmte Interrupts (z. B. Double Fault)
+//!     und den Kernel-Stack beim Wechsel von Ring 3 nach Ring 0
+//! - **Reihenfolge:** Die Segmente liegen in der Reihenfolge, die `sysret` erwartet
+//!   (User-Daten direkt vor User-Code)
 //! - **Sicherheit:**  
 //!   - Das Laden von Segmenten (CS, TSS) ist `unsafe`, da ein falscher Wert zu einem **Triple Fault** führen kann
 //!   - Der TSS-Stack wird als `static mut` definiert, um global verfügbar zu sein
@@ -20,10 +26,13 @@
 //! ## Enthaltene Komponenten
 //!
 //! - [`GDT`]: statische Referenz auf die GDT und die Selektoren  
-//! - [`Selectors`]: enthält die Code- und TSS-Selektoren  
+//! - [`Selectors`]: enthält die Code-, Daten- und TSS-Selektoren  
+//! - [`selectors()`]: Zugriff auf die Selektoren, z. B. für den Sprung in den User Mode  
+//! - [`set_kernel_stack()`]: Setzt den Kernel-Stack für den Wechsel aus Ring 3, z. B. pro Prozess  
 //! - [`init()`]: Initialisiert die GDT und lädt die Segmente in die CPU
 //! - [`TSS`]: Task State Segment, das die Interrupt-Stacks enthält
 
+use core::cell::UnsafeCell;
 use x86_64::VirtAddr;
 use x86_64::structures::tss::TaskStateSegment;
 use lazy_static::lazy_static;
@@ -32,6 +41,16 @@ use x86_64::structures::gdt::SegmentSelector;
 
 pub const DOUBLE_FAULT_IST_INDEX: u16 = 0;
 
+/// Größe des Kernel-Stacks, auf den die CPU beim Wechsel aus Ring 3 wechselt.
+pub const PRIVILEGE_STACK_SIZE: usize = 4096 * 5;
+
+/// Hülle um das [TaskStateSegment], damit `privilege_stack_table[0]` beim
+/// Prozesswechsel angepasst werden kann. Die CPU liest das TSS direkt aus dem Speicher.
+struct TssCell(UnsafeCell<TaskStateSegment>);
+
+// SAFETY: Geschrieben wird nur in [set_kernel_stack] mit deaktivierten Interrupts.
+unsafe impl Sync for TssCell {}
+
 
 lazy_static!
 {
@@ -39,6 +58,8 @@ lazy_static!
     ///
     /// Dieser TaskStateSegment definiert den Interrupt-Stack für kritische Ausnahmen,
     /// insbesondere für **Double Faults**.  
+    /// Außerdem enthält er in `privilege_stack_table[0]` den Kernel-Stack, auf den
+    /// die CPU wechselt, wenn ein Interrupt oder eine Exception im **Ring 3** auftritt.
     /// 
     /// Dabei wird:
     /// - ein separater Stack-Bereich von 4096 * 5 Bytes reserviert,
@@ -62,7 +83,7 @@ lazy_static!
     /// auch im Fehlerfall korrekt reagieren.
     ///
     /// [`interrupt_stack_table`]: x86_64::structures::tss::TaskStateSegment::interrupt_stack_table
-    static ref TSS: TaskStateSegment =
+    static ref TSS: TssCell =
     {
         let mut tss = TaskStateSegment::new();
         tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] =
@@ -74,7 +95,14 @@ lazy_static!
             let stack_end = stack_start + STACK_SIZE;
             stack_end
         };
-        tss
+        tss.privilege_stack_table[0] =
+        {
+            static mut STACK: [u8; PRIVILEGE_STACK_SIZE] = [0; PRIVILEGE_STACK_SIZE];
+
+            let stack_start = VirtAddr::from_ptr(&raw const STACK);
+            stack_start + PRIVILEGE_STACK_SIZE
+        };
+        TssCell(UnsafeCell::new(tss))
     };
 }
 
@@ -95,6 +123,8 @@ lazy_static!
     /// Diese Definition:
     /// - erstellt eine neue [GlobalDescriptorTable],
     /// - fügt einen **Kernel Code Segment Descriptor** hinzu,
+    /// - fügt einen **Kernel Data Segment Descriptor** hinzu,
+    /// - fügt die **User Data** und **User Code Segment Descriptors** (DPL 3) hinzu,
     /// - fügt einen **TSS Descriptor** hinzu, der auf das globale [TSS] verweist.
     ///
     /// # Sicherheit
@@ -109,8 +139,12 @@ lazy_static!
     {
         let mut gdt = GlobalDescriptorTable::new();
         let code_selector = gdt.add_entry(Descriptor::kernel_code_segment());
-        let tss_selector = gdt.add_entry(Descriptor::tss_segment(&TSS));
-        (gdt, Selectors {code_selector, tss_selector})
+        let data_selector = gdt.add_entry(Descriptor::kernel_data_segment());
+        let user_data_selector = gdt.add_entry(Descriptor::user_data_segment());
+        let user_code_selector = gdt.add_entry(Descriptor::user_code_segment());
+        // SAFETY: Das TSS liegt in einer statischen Variable und bleibt dauerhaft gültig.
+        let tss_selector = gdt.add_entry(unsafe { Descriptor::tss_segment_unchecked(TSS.0.get()) });
+        (gdt, Selectors {code_selector, data_selector, user_data_selector, user_code_selector, tss_selector})
     };
 }
 
@@ -120,11 +154,41 @@ lazy_static!
 /// Einträge der Global Descriptor Table zu adressieren.
 /// 
 /// - [code_selector]: Verweist auf das Kernel-Code-Segment.
+/// - [data_selector]: Verweist auf das Kernel-Daten-Segment.
+/// - [user_data_selector]: Verweist auf das User-Daten-Segment (RPL 3).
+/// - [user_code_selector]: Verweist auf das User-Code-Segment (RPL 3).
 /// - [tss_selector]: Verweist auf das Task-State-Segment.
-struct Selectors
+pub struct Selectors
+{
+    pub code_selector: SegmentSelector,
+    pub data_selector: SegmentSelector,
+    pub user_data_selector: SegmentSelector,
+    pub user_code_selector: SegmentSelector,
+    pub tss_selector: SegmentSelector,
+}
+
+/// Gibt das obere Ende des Kernel-Stacks zurück, auf den die CPU beim
+/// Wechsel aus dem Ring 3 umschaltet (`TSS.privilege_stack_table[0]`).
+pub fn kernel_stack_top() -> VirtAddr
+{
+    x86_64::instructions::interrupts::without_interrupts(|| unsafe { (*TSS.0.get()).privilege_stack_table[0] })
+}
+
+/// Setzt den Kernel-Stack, auf den die CPU bei einem Interrupt im Ring 3
+/// wechselt. Wird beim Prozesswechsel mit dem Kernel-Stack des neuen Prozesses
+/// aufgerufen.
+pub fn set_kernel_stack(top: VirtAddr)
+{
+    x86_64::instructions::interrupts::without_interrupts(|| unsafe
+    {
+        (*TSS.0.get()).privilege_stack_table[0] = top;
+    });
+}
+
+/// Gibt die Selektoren der globalen GDT zurück.
+pub fn selectors() -> &'static Selectors
 {
-    code_selector: SegmentSelector,
-    tss_selector: SegmentSelector,
+    &GDT.1
 }
 
 /// Initialisiert und lädt die globale GDT.
@@ -132,7 +196,8 @@ struct Selectors
 /// Diese Funktion:
 /// 1. lädt die GDT mittels lgdt,
 /// 2. aktualisiert das [CS] (Code Segment Register),
-/// 3. lädt das Task-State-Segment (TSS) in die CPU.
+/// 3. lädt das Kernel-Daten-Segment in SS, DS und ES,
+/// 4. lädt das Task-State-Segment (TSS) in die CPU.
 ///
 /// # Sicherheit
 ///
@@ -145,12 +210,15 @@ struct Selectors
 pub fn init()
 {
     use x86_64::instructions::tables::load_tss;
-    use x86_64::instructions::segmentation::{CS, Segment};
+    use x86_64::instructions::segmentation::{CS, DS, ES, SS, Segment};
 
     GDT.0.load();
     unsafe
     {
         CS::set_reg(GDT.1.code_selector);
+        SS::set_reg(GDT.1.data_selector);
+        DS::set_reg(GDT.1.data_selector);
+        ES::set_reg(GDT.1.data_selector);
         load_tss(GDT.1.tss_selector);
     }
 }
\ No newline at end of file
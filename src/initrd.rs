@@ -0,0 +1,66 @@
+//! # Modul: initrd
+//!
+//! Dieses Modul stellt die **initiale Ramdisk** bereit: ein USTAR-Archiv, das
+//! mit dem Kernel ausgeliefert und beim Start als schreibgeschütztes
+//! [TarFs] unter [MOUNT_POINT] eingehängt wird. So lassen sich Programme und
+//! Konfiguration laden, bevor es einen Treiber für Datenträger gibt.
+//!
+//! # Aufbau des Archivs
+//!
+//! Der Inhalt liegt im Verzeichnis `initrd/root/` des Repositorys und wird mit
+//! `initrd/build.sh` zu `initrd/initrd.tar` gepackt. Das Archiv wird über
+//! `include_bytes!` fest in das Kernel-Image eingebunden, da der Bootloader
+//! keine zusätzlichen Module nachladen kann.
+//!
+//! ```text
+//! /initrd/bin/hello   Testprogramm, beendet sich mit Exit-Code 26
+//! /initrd/etc/motd    Begrüßungstext
+//! /initrd/etc/issue   symbolischer Link auf motd
+//! ```
+
+use alloc::sync::Arc;
+
+use crate::vfs::{self, TarFs, VfsError, VfsResult};
+
+/// Verzeichnis, unter dem die initrd eingehängt wird.
+pub const MOUNT_POINT: &str = "/initrd";
+
+/// Das eingebundene USTAR-Archiv.
+pub static ARCHIVE: &[u8] = include_bytes!("../initrd/initrd.tar");
+
+/// ## initrd einhängen
+///
+/// Liest [ARCHIVE] ein und hängt es unter [MOUNT_POINT] ein. Setzt eine
+/// initialisierte Speicherverwaltung voraus.
+pub fn init() -> VfsResult<()>
+{
+    mount(ARCHIVE)
+}
+
+/// Hängt ein beliebiges USTAR-Archiv unter [MOUNT_POINT] ein, z. B. eines,
+/// das auf anderem Weg in den Speicher gelangt ist.
+pub fn mount(archive: &'static [u8]) -> VfsResult<()>
+{
+    let fs = TarFs::new(archive)?;
+    match vfs::mkdir(MOUNT_POINT)
+    {
+        Ok(()) | Err(VfsError::AlreadyExists) => {}
+        Err(error) => return Err(error),
+    }
+    vfs::mount(MOUNT_POINT, Arc::new(fs))
+}
+
+#[test_case]
+fn test_initrd_contents()
+{
+    init().unwrap();
+    assert_eq!(init(), Err(VfsError::Busy));
+
+    assert_eq!(vfs::readlink("/initrd/etc/issue").as_deref(), Ok("motd"));
+    assert_eq!(vfs::read_to_end("/initrd/etc/issue"), vfs::read_to_end("/initrd/etc/motd"));
+    assert_eq!(vfs::lstat("/initrd/etc/issue").map(|metadata| metadata.file_type), Ok(vfs::FileType::Symlink));
+
+    let program = vfs::read_to_end("/initrd/bin/hello").unwrap();
+    assert!(crate::elf::ElfFile::parse(&program).is_ok());
+    assert_eq!(vfs::mkdir("/initrd/tmp"), Err(VfsError::ReadOnly));
+}
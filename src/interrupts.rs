@@ -7,13 +7,24 @@
 //! Enthält Handler für:
 //! - Breakpoints
 //! - Double Faults (mit separatem Stack aus dem TSS)
+//! - General Protection Faults (z. B. privilegierte Befehle im User Mode)
+//! - Page Faults, einschließlich Copy-on-Write (siehe [crate::memory])
+//! - Systemaufrufe über `int 0x80` (siehe [crate::syscall])
+//! - Abschluss von ATA-Befehlen über IRQ 14/15 (siehe [crate::block::ata])
+//! - Geteilte IRQ-Leitungen für PCI-Geräte ohne MSI (siehe [irq])
+//! - Dynamisch vergebene Vektoren für Message Signaled Interrupts (siehe [vector])
+//!   und den Local APIC, der sie annimmt (siehe [apic])
 
 use x86_64::{structures::idt::{InterruptDescriptorTable, InterruptStackFrame}};
-use crate::{print, println};
+use crate::println;
 use lazy_static::lazy_static;
 use crate::gdt;
-use spin;
+use crate::sync::IrqSpinlock;
 use pic8259::ChainedPics;
+
+pub mod apic;
+pub mod irq;
+pub mod vector;
 use x86_64::structures::idt::PageFaultErrorCode;
 use crate::hlt_loop;
 
@@ -30,6 +41,8 @@ pub enum InterruptIndex
 {
     Timer = PIC_1_OFFSET,
     Keyboard,
+    PrimaryAta = PIC_1_OFFSET + 14,
+    SecondaryAta,
 }
 
 impl InterruptIndex
@@ -45,6 +58,33 @@ impl InterruptIndex
     }
 }
 
+/// Trägt für jede der angegebenen geteilten Leitungen den passenden
+/// Einstiegspunkt in die IDT ein.
+macro_rules! set_irq_handlers
+{
+    ($idt:ident; $($irq:literal)*) =>
+    {
+        const _: () = assert!([$($irq),*].len() == (irq::LAST_SHARED - irq::FIRST_SHARED + 1) as usize);
+        $(
+            $idt[usize::from(PIC_1_OFFSET) + $irq].set_handler_fn(irq::interrupt_handler::<$irq>);
+        )*
+    };
+}
+
+/// Trägt für jeden der angegebenen Abstände zu [vector::FIRST_VECTOR] den
+/// passenden Einstiegspunkt in die IDT ein.
+macro_rules! set_vector_handlers
+{
+    ($idt:ident; $($offset:literal)*) =>
+    {
+        const _: () = assert!([$($offset),*].len() == vector::VECTOR_COUNT);
+        $(
+            $idt[usize::from(vector::FIRST_VECTOR) + $offset]
+                .set_handler_fn(vector::interrupt_handler::<{ vector::FIRST_VECTOR + $offset }>);
+        )*
+    };
+}
+
 lazy_static! 
 {
     /// Globale Instanz der Interrupt Descriptor Table.
@@ -55,6 +95,8 @@ lazy_static!
     /// Die IDT enthält aktuell Einträge für:
     /// - Breakpoint Exceptions (int3)
     /// - Double Faults (mit dedizierten Stack aus der GDT)
+    /// - Exceptions, die User-Programme auslösen können (#DE, #UD, #NP, #SS,
+    ///   #GP, #PF, #MF, #AC, #XM); aus dem Ring 3 beenden sie nur den Prozess
     /// 
     /// [`lazy_static!`]: https://docs.rs/lazy_static/latest/lazy_static/
     static ref IDT: InterruptDescriptorTable = 
@@ -68,7 +110,28 @@ lazy_static!
         }
         idt[InterruptIndex::Timer.as_usize()].set_handler_fn(timer_interrupt_handler);
         idt[InterruptIndex::Keyboard.as_usize()].set_handler_fn(keyboard_interrupt_handler);
+        idt[InterruptIndex::PrimaryAta.as_usize()].set_handler_fn(primary_ata_interrupt_handler);
+        idt[InterruptIndex::SecondaryAta.as_usize()].set_handler_fn(secondary_ata_interrupt_handler);
+        set_irq_handlers!(idt; 3 4 5 6 7 8 9 10 11 12 13);
         idt.page_fault.set_handler_fn(page_fault_handler);
+        idt.general_protection_fault.set_handler_fn(general_protection_fault_handler);
+        idt.divide_error.set_handler_fn(divide_error_handler);
+        idt.invalid_opcode.set_handler_fn(invalid_opcode_handler);
+        idt.segment_not_present.set_handler_fn(segment_not_present_handler);
+        idt.stack_segment_fault.set_handler_fn(stack_segment_fault_handler);
+        idt.x87_floating_point.set_handler_fn(x87_floating_point_handler);
+        idt.alignment_check.set_handler_fn(alignment_check_handler);
+        idt.simd_floating_point.set_handler_fn(simd_floating_point_handler);
+        set_vector_handlers!(idt;
+            0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24 25 26 27 28 29 30 31
+            32 33 34 35 36 37 38 39 40 41 42 43 44 45 46 47 48 49 50 51 52 53 54 55 56 57 58 59 60 61 62 63);
+        idt[usize::from(apic::SPURIOUS_VECTOR)].set_handler_fn(spurious_interrupt_handler);
+        unsafe
+        {
+            idt[crate::syscall::INT80_VECTOR]
+                .set_handler_addr(crate::syscall::int80_entry_addr())
+                .set_privilege_level(x86_64::PrivilegeLevel::Ring3);
+        }
         
         idt
     };
@@ -116,6 +179,8 @@ extern "x86-interrupt" fn double_fault_handler(
 
 /// # Handler für Timer Interrupts
 /// 
+/// Zählt den Tick für [crate::time].
+/// 
 /// Die `notify_end_of_interrupt()`-Funktion bestimmt ob er erste oder zweite PIC
 /// einen Interrupt gesendet hat und benutzt dann die `command` und `data` Ports
 /// um ein `EOI`(End of Interrupt)-Signal zu senden zum jeweiligen Controller.  
@@ -129,7 +194,7 @@ extern "x86-interrupt" fn double_fault_handler(
 /// das System aufhängt.
 extern "x86-interrupt" fn timer_interrupt_handler(_stack_frame: InterruptStackFrame)
 {
-    print!(".");
+    crate::time::tick();
 
     unsafe
     {
@@ -139,64 +204,76 @@ extern "x86-interrupt" fn timer_interrupt_handler(_stack_frame: InterruptStackFr
 
 /// # Handler für Keyboard Interrupts
 /// 
-/// Erstellt ein Keyboard Objekt, welches von einem `Mutex` gesichert wird.
-/// 
-/// Initialisiert:
-/// - US Keyboard
-/// - Scancode Set 1
-/// - HandleControl als ignoriert
-/// - Port 0x60 als I/O
-/// 
-/// Wenn diese Funktion aktiv ist wird sie mit einem `lock()` versehen, um weitere Interrupts
-/// zu verhindern, bis der Keyboard Interrupt abgearbeitet ist. 
-/// Der Port 0x60 wird genutzt, da er der I/O - Port des PS/2 Controllers ist.
-/// 
-/// Bei jedem Keyboard Interrupt wird der Handler aufgerufen und für die Dauer mit dem Mutex gesichert.
-/// Dann wird der scancode vom Keyboard Controller gelesen und der Wert als Parameter in die `add_byte()`
-/// Methode eingefügt, welche den Scancode in ein Option<KeyEvent> "übersetzt". Das `KeyEvent` stellt fest
-/// welcher Key gedrückt wurde und ob es ein Drücken oder Loslassen war. Um dieses KeyEvent
-/// zu interpretieren, wird es an die `process_keyevent()` Methode weitergegeben, welche das KeyEvent
-/// in einen Character umändert wenn möglich
+/// Liest den Scancode vom Port 0x60, dem I/O-Port des PS/2-Controllers, und
+/// übergibt ihn an [crate::keyboard::handle_scancode]. Dort wird er dekodiert
+/// und die fertige Taste eingereiht, bis sie z. B. die Shell abholt.
 extern "x86-interrupt" fn keyboard_interrupt_handler(_stack_frame: InterruptStackFrame)
 {
-    // use x86_64::instructions::interrupts;
-    use pc_keyboard::{layouts, DecodedKey, HandleControl, Keyboard, ScancodeSet1};
-    use spin::Mutex;
     use x86_64::instructions::port::Port;
 
-    lazy_static!
+    let mut port = Port::new(0x60);
+    let scancode: u8 = unsafe { port.read() };
+    crate::keyboard::handle_scancode(scancode);
+
+    unsafe
     {
-        static ref KEYBOARD: Mutex<Keyboard<layouts::Us104Key, ScancodeSet1>> = 
-            Mutex::new(Keyboard::new(ScancodeSet1::new(), layouts::Us104Key, HandleControl::Ignore)
-            );
+        PICS.lock().notify_end_of_interrupt(InterruptIndex::Keyboard.as_u8());
     }
+}
 
-    let mut keyboard = KEYBOARD.lock();
-    let mut port = Port::new(0x60);
-
-    let scancode: u8 = unsafe { port.read() };
-    if let Ok(Some(key_event)) = keyboard.add_byte(scancode) 
+/// # Handler für ATA Interrupts
+///
+/// Die Kanäle des ATA-Controllers melden über IRQ 14 (primär) und IRQ 15
+/// (sekundär), dass ein Sektor bereitsteht oder ein Befehl abgeschlossen ist.
+/// Die Auswertung übernimmt [crate::block::ata].
+extern "x86-interrupt" fn primary_ata_interrupt_handler(_stack_frame: InterruptStackFrame)
+{
+    crate::block::ata::handle_interrupt(0);
+    unsafe
     {
-        if let Some(key) = keyboard.process_keyevent(key_event)
-        {
-            match key
-            {
-                DecodedKey::Unicode(character) => print!("{}", character),
-                DecodedKey::RawKey(key) => print!("{:?}", key),
-            }
-        }    
+        PICS.lock().notify_end_of_interrupt(InterruptIndex::PrimaryAta.as_u8());
     }
+}
 
+/// Siehe [primary_ata_interrupt_handler].
+extern "x86-interrupt" fn secondary_ata_interrupt_handler(_stack_frame: InterruptStackFrame)
+{
+    crate::block::ata::handle_interrupt(1);
     unsafe
     {
-        PICS.lock().notify_end_of_interrupt(InterruptIndex::Keyboard.as_u8());
+        PICS.lock().notify_end_of_interrupt(InterruptIndex::SecondaryAta.as_u8());
     }
 }
 
+/// Handler für Spurious Interrupts des Local APIC. Sie werden ohne EOI
+/// verworfen.
+extern "x86-interrupt" fn spurious_interrupt_handler(_stack_frame: InterruptStackFrame)
+{
+}
+
+/// Handler für Page Faults
+///
+/// Schreibzugriffe auf Copy-on-Write-Seiten werden über
+/// [handle_cow_fault](crate::memory::handle_cow_fault) aufgelöst; der Befehl
+/// wird danach wiederholt. Alle anderen Page Faults aus dem Ring 3 – und
+/// Copy-on-Write-Seiten, für deren Kopie der Speicher fehlt – beenden den
+/// Prozess (siehe [kill_faulting_program]); im Kernel halten sie das System an.
 extern "x86-interrupt" fn page_fault_handler(stack_frame: InterruptStackFrame, error_code: PageFaultErrorCode)
 {
     use x86_64::registers::control::Cr2;
 
+    let cow = PageFaultErrorCode::PROTECTION_VIOLATION | PageFaultErrorCode::CAUSED_BY_WRITE;
+    if error_code.contains(cow) && matches!(crate::memory::handle_cow_fault(Cr2::read()), Ok(true))
+    {
+        return;
+    }
+
+    if stack_frame.code_segment & 0b11 == 3
+    {
+        println!("PAGE FAULT in process {} at {:?} ({:?})", crate::process::current_pid(), Cr2::read(), error_code);
+        kill_faulting_program();
+    }
+
     println!("EXCEPTION: PAGE FAULT");
     println!("ACCESSED ADDRESS: {:?}", Cr2::read());
     println!("ERROR CODE: {:?}", error_code);
@@ -204,6 +281,93 @@ extern "x86-interrupt" fn page_fault_handler(stack_frame: InterruptStackFrame, e
     hlt_loop();
 }
 
+/// # Handler für General Protection Faults
+///
+/// Eine #GP tritt u. a. auf, wenn Code im **Ring 3** einen privilegierten Befehl
+/// (z. B. `hlt` oder `cli`) ausführt oder einen ungültigen Selektor lädt.
+///
+/// Die Privilegstufe des unterbrochenen Codes steht in den unteren zwei Bits des
+/// gesicherten Code-Segments. Kommt der Fehler aus dem Ring 3, wird nur der
+/// Prozess beendet (siehe [kill_faulting_program]); im Kernel wird der Fehler
+/// ausgegeben und das System angehalten.
+extern "x86-interrupt" fn general_protection_fault_handler(stack_frame: InterruptStackFrame, error_code: u64)
+{
+    let ring = stack_frame.code_segment & 0b11;
+    if ring == 3
+    {
+        println!("GENERAL PROTECTION FAULT in process {} at {:?}", crate::process::current_pid(), stack_frame.instruction_pointer);
+        kill_faulting_program();
+    }
+
+    println!("EXCEPTION: GENERAL PROTECTION FAULT (ring {})", ring);
+    println!("ERROR CODE: {:#x}", error_code);
+    println!("{:#?}", stack_frame);
+    hlt_loop();
+}
+
+/// Erzeugt einen Handler für eine Exception, die ein User-Programm auslösen
+/// kann. Kommt sie aus dem Ring 3, wird nur der Prozess beendet (siehe
+/// [kill_faulting_program]); im Kernel wird sie ausgegeben und das System
+/// angehalten.
+macro_rules! user_fault_handler
+{
+    ($handler:ident, $name:literal) =>
+    {
+        extern "x86-interrupt" fn $handler(stack_frame: InterruptStackFrame)
+        {
+            fatal_exception($name, &stack_frame, None);
+        }
+    };
+    ($handler:ident, $name:literal, error_code) =>
+    {
+        extern "x86-interrupt" fn $handler(stack_frame: InterruptStackFrame, error_code: u64)
+        {
+            fatal_exception($name, &stack_frame, Some(error_code));
+        }
+    };
+}
+
+user_fault_handler!(divide_error_handler, "DIVIDE ERROR");
+user_fault_handler!(invalid_opcode_handler, "INVALID OPCODE");
+user_fault_handler!(segment_not_present_handler, "SEGMENT NOT PRESENT", error_code);
+user_fault_handler!(stack_segment_fault_handler, "STACK SEGMENT FAULT", error_code);
+user_fault_handler!(x87_floating_point_handler, "x87 FLOATING POINT");
+user_fault_handler!(alignment_check_handler, "ALIGNMENT CHECK", error_code);
+user_fault_handler!(simd_floating_point_handler, "SIMD FLOATING POINT");
+
+/// Gemeinsamer Teil der Handler aus [user_fault_handler].
+fn fatal_exception(name: &str, stack_frame: &InterruptStackFrame, error_code: Option<u64>) -> !
+{
+    if stack_frame.code_segment & 0b11 == 3
+    {
+        println!("{} in process {} at {:?}", name, crate::process::current_pid(), stack_frame.instruction_pointer);
+        kill_faulting_program();
+    }
+
+    println!("EXCEPTION: {}", name);
+    if let Some(error_code) = error_code
+    {
+        println!("ERROR CODE: {:#x}", error_code);
+    }
+    println!("{:#?}", stack_frame);
+    hlt_loop();
+}
+
+/// Beendet das User-Programm, das im Ring 3 eine Exception ausgelöst hat, mit
+/// [FAULT_EXIT_CODE](crate::process::FAULT_EXIT_CODE). Lief es direkt im
+/// Kernel-Prozess, kehrt stattdessen
+/// [run_user_mode](crate::usermode::run_user_mode) mit diesem Code zurück.
+fn kill_faulting_program() -> !
+{
+    use crate::process::{self, FAULT_EXIT_CODE, Pid};
+
+    if process::current_pid() == Pid::KERNEL
+    {
+        unsafe { crate::usermode::return_from_user_mode(FAULT_EXIT_CODE) }
+    }
+    process::exit(FAULT_EXIT_CODE)
+}
+
 /// # Offset für die PICs
 /// 
 /// [ChainedPics] repräsentiert das PIC-Layout.
@@ -212,13 +376,38 @@ extern "x86-interrupt" fn page_fault_handler(stack_frame: InterruptStackFrame, e
 /// 
 /// # Sicherheit
 /// 
-/// Durch das Mutex auf die `Chained Pics` Struktur, ist es möglich einen sicheren 
-/// veränderbaren Zugriff über die `lock` Methode zu bekommen.
+/// Durch das [IrqSpinlock] auf die `Chained Pics` Struktur, ist es möglich einen sicheren 
+/// veränderbaren Zugriff über die `lock` Methode zu bekommen, auch aus Interrupt-Handlern.
 /// 
 /// Die `ChainedPics::new()` Methode ist unsafe, da durch ein falsch gesetztes Offset 
 /// undefiniertes Verhalten verursacht werden kann.
-pub static PICS: spin::Mutex<ChainedPics> = 
-    spin::Mutex::new(unsafe { ChainedPics::new(PIC_1_OFFSET, PIC_2_OFFSET) });
+pub static PICS: IrqSpinlock<ChainedPics> = 
+    IrqSpinlock::new(unsafe { ChainedPics::new(PIC_1_OFFSET, PIC_2_OFFSET) });
+
+/// # IRQ freischalten
+///
+/// Löscht das Maskenbit der IRQ-Leitung `irq` (0–15). Für Leitungen des
+/// zweiten PICs wird zusätzlich die Kaskade (IRQ 2) freigeschaltet.
+pub fn unmask_irq(irq: u8)
+{
+    assert!(irq < 16, "IRQ {} does not exist", irq);
+
+    let mut pics = PICS.lock();
+    unsafe
+    {
+        let [mut master, mut slave] = pics.read_masks();
+        if irq < 8
+        {
+            master &= !(1 << irq);
+        }
+        else
+        {
+            master &= !(1 << 2);
+            slave &= !(1 << (irq - 8));
+        }
+        pics.write_masks(master, slave);
+    }
+}
 
 
 /// # Breakpoint Test
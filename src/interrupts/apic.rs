@@ -0,0 +1,109 @@
+//! Local APIC der CPU.
+//!
+//! Interrupts der 8259 PICs erreichen die CPU über den Eingang LINT0 des
+//! Local APIC, den das BIOS dafür im „Virtual Wire“-Modus betreibt. Message
+//! Signaled Interrupts (siehe [crate::pci::msi]) dagegen sind Schreibzugriffe
+//! des Geräts an die Adresse `0xFEE0_0000 | APIC-ID << 12`, die der Local APIC
+//! direkt annimmt. Ihr Ende wird daher nicht dem PIC, sondern über das
+//! EOI-Register des Local APIC gemeldet.
+//!
+//! Die Register liegen ab der Basisadresse aus dem MSR `IA32_APIC_BASE` im
+//! Speicher, jeweils 16 Byte voneinander entfernt:
+//!
+//! | Offset | Register |
+//! |--------|----------|
+//! | `0x020` | ID (Bits 24–31) |
+//! | `0x0B0` | End of Interrupt |
+//! | `0x0F0` | Spurious Interrupt Vector, Bit 8: APIC eingeschaltet |
+//! | `0x300` | Interrupt Command (unterer Teil) |
+
+use x86_64::registers::model_specific::Msr;
+use x86_64::{PhysAddr, VirtAddr};
+
+use crate::memory;
+use crate::sync::Once;
+
+/// MSR mit der physischen Basisadresse des Local APIC.
+const IA32_APIC_BASE: u32 = 0x1B;
+
+/// Bit in `IA32_APIC_BASE`: Der APIC ist eingeschaltet.
+const APIC_GLOBAL_ENABLE: u64 = 1 << 11;
+
+/// Vektor für Spurious Interrupts; er braucht kein EOI.
+pub const SPURIOUS_VECTOR: u8 = 0xFF;
+
+mod register
+{
+    pub const ID: u64 = 0x020;
+    pub const EOI: u64 = 0x0B0;
+    pub const SPURIOUS: u64 = 0x0F0;
+    pub const INTERRUPT_COMMAND: u64 = 0x300;
+}
+
+/// Bit im Spurious-Register: Der APIC nimmt Interrupts an.
+const SOFTWARE_ENABLE: u32 = 1 << 8;
+
+/// Ziel „nur diese CPU“ im Interrupt Command Register.
+const DESTINATION_SELF: u32 = 1 << 18;
+
+/// Bit im Interrupt Command Register: Die Nachricht ist noch unterwegs.
+const DELIVERY_PENDING: u32 = 1 << 12;
+
+/// Virtuelle Adresse der Register.
+static BASE: Once<VirtAddr> = Once::new();
+
+/// Bildet die Register ab und schaltet den Local APIC ein. Die Einstellungen
+/// von LINT0 bleiben erhalten, sodass die PICs weiter funktionieren.
+pub fn init()
+{
+    BASE.call_once(||
+    {
+        let mut msr = Msr::new(IA32_APIC_BASE);
+        let value = unsafe { msr.read() };
+        unsafe { msr.write(value | APIC_GLOBAL_ENABLE) };
+        let base = memory::map_mmio(PhysAddr::new(value & 0xF_FFFF_F000), 4096).expect("failed to map the local APIC");
+        let spurious = unsafe { read(base, register::SPURIOUS) };
+        unsafe { write(base, register::SPURIOUS, spurious | SOFTWARE_ENABLE | u32::from(SPURIOUS_VECTOR)) };
+        base
+    });
+}
+
+unsafe fn read(base: VirtAddr, register: u64) -> u32
+{
+    unsafe { (base + register).as_ptr::<u32>().read_volatile() }
+}
+
+unsafe fn write(base: VirtAddr, register: u64, value: u32)
+{
+    unsafe { (base + register).as_mut_ptr::<u32>().write_volatile(value) };
+}
+
+fn base() -> VirtAddr
+{
+    *BASE.get().expect("apic::init has not been called")
+}
+
+/// ID des Local APIC dieser CPU, Ziel für MSI-Nachrichten.
+pub fn id() -> u8
+{
+    (unsafe { read(base(), register::ID) } >> 24) as u8
+}
+
+/// Meldet dem Local APIC das Ende des gerade bearbeiteten Interrupts.
+pub fn end_of_interrupt()
+{
+    unsafe { write(base(), register::EOI, 0) };
+}
+
+/// Löst den Interrupt `vector` auf dieser CPU aus, als käme er von einem Gerät.
+pub fn send_self(vector: u8)
+{
+    unsafe
+    {
+        write(base(), register::INTERRUPT_COMMAND, DESTINATION_SELF | u32::from(vector));
+        while read(base(), register::INTERRUPT_COMMAND) & DELIVERY_PENDING != 0
+        {
+            core::hint::spin_loop();
+        }
+    }
+}
@@ -0,0 +1,102 @@
+//! Gemeinsam genutzte IRQ-Leitungen der PICs.
+//!
+//! PCI-Geräte ohne MSI melden sich über die Leitung, die das BIOS in ihr
+//! Register `INTERRUPT_LINE` geschrieben hat, und teilen sie sich oft mit
+//! anderen Geräten. Für die Leitungen [FIRST_SHARED] bis [LAST_SHARED] gibt es
+//! daher eine Liste von Handlern, die bei jedem Interrupt der Leitung alle
+//! aufgerufen werden; jeder prüft selbst, ob sein Gerät gemeint ist.
+//!
+//! Die übrigen Leitungen (Timer, Tastatur, Kaskade und ATA) haben feste
+//! Handler in [super].
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use x86_64::structures::idt::InterruptStackFrame;
+
+use super::vector::Handler;
+use super::{PIC_1_OFFSET, PICS};
+use crate::sync::IrqSpinlock;
+
+/// Erste Leitung mit gemeinsam genutzten Handlern.
+pub const FIRST_SHARED: u8 = 3;
+
+/// Letzte Leitung mit gemeinsam genutzten Handlern.
+pub const LAST_SHARED: u8 = 13;
+
+/// Die Handler jeder Leitung mit ihrer Kennung.
+static HANDLERS: [IrqSpinlock<Vec<(usize, Handler)>>; 16] = [const { IrqSpinlock::new(Vec::new()) }; 16];
+
+/// Kennung des nächsten Handlers.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// ## IrqHandler
+///
+/// Ein an einer Leitung angemeldeter Handler. Wird er freigegeben, läuft der
+/// Handler nicht mehr.
+#[derive(Debug)]
+pub struct IrqHandler
+{
+    irq: u8,
+    id: usize,
+}
+
+impl IrqHandler
+{
+    /// Die Leitung des Handlers.
+    pub fn irq(&self) -> u8
+    {
+        self.irq
+    }
+}
+
+impl Drop for IrqHandler
+{
+    fn drop(&mut self)
+    {
+        HANDLERS[usize::from(self.irq)].lock().retain(|(id, _)| *id != self.id);
+    }
+}
+
+/// Meldet `handler` an der Leitung `irq` an und schaltet sie frei. Gibt
+/// `None` zurück, wenn die Leitung nicht geteilt werden kann.
+pub fn register(irq: u8, handler: Handler) -> Option<IrqHandler>
+{
+    if !(FIRST_SHARED..=LAST_SHARED).contains(&irq)
+    {
+        return None;
+    }
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    HANDLERS[usize::from(irq)].lock().push((id, handler));
+    super::unmask_irq(irq);
+    Some(IrqHandler { irq, id })
+}
+
+/// Gemeinsamer Einstiegspunkt der geteilten Leitungen.
+pub(super) extern "x86-interrupt" fn interrupt_handler<const IRQ: u8>(_stack_frame: InterruptStackFrame)
+{
+    for (_, handler) in HANDLERS[usize::from(IRQ)].lock().iter()
+    {
+        handler();
+    }
+    unsafe
+    {
+        PICS.lock().notify_end_of_interrupt(PIC_1_OFFSET + IRQ);
+    }
+}
+
+#[test_case]
+fn test_register_and_release()
+{
+    use alloc::boxed::Box;
+
+    assert!(register(0, Box::new(|| ())).is_none());
+    assert!(register(14, Box::new(|| ())).is_none());
+
+    let first = register(11, Box::new(|| ())).unwrap();
+    let second = register(11, Box::new(|| ())).unwrap();
+    let count = || HANDLERS[11].lock().len();
+    let before = count();
+    drop(first);
+    assert_eq!(count(), before - 1);
+    assert_eq!(second.irq(), 11);
+}
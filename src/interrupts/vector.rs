@@ -0,0 +1,106 @@
+//! Dynamisch vergebene Interrupt-Vektoren.
+//!
+//! Die Vektoren [FIRST_VECTOR] bis [FIRST_VECTOR] + [VECTOR_COUNT] - 1 sind für
+//! Geräte mit Message Signaled Interrupts reserviert. Jeder hat in der IDT
+//! einen eigenen Einstiegspunkt, der den mit [allocate] hinterlegten Handler
+//! aufruft und das Ende des Interrupts dem Local APIC meldet.
+//!
+//! Ein [Vector] gehört seinem Besitzer; beim Freigeben wird der Handler
+//! entfernt und der Vektor wieder vergeben.
+
+use alloc::boxed::Box;
+use x86_64::structures::idt::InterruptStackFrame;
+
+use super::apic;
+use crate::sync::IrqSpinlock;
+
+/// Erster dynamisch vergebener Vektor, direkt hinter denen der PICs.
+pub const FIRST_VECTOR: u8 = 0x30;
+
+/// Anzahl der dynamisch vergebenen Vektoren.
+pub const VECTOR_COUNT: usize = 64;
+
+/// Handler eines Vektors. Er läuft mit gesperrten Interrupts und darf daher
+/// weder schlafen noch Speicher anfordern.
+pub type Handler = Box<dyn Fn() + Send + Sync>;
+
+/// Die Handler der vergebenen Vektoren.
+static HANDLERS: [IrqSpinlock<Option<Handler>>; VECTOR_COUNT] = [const { IrqSpinlock::new(None) }; VECTOR_COUNT];
+
+/// ## Vector
+///
+/// Ein vergebener Interrupt-Vektor. Wird er freigegeben, läuft sein Handler
+/// nicht mehr.
+#[derive(Debug)]
+pub struct Vector
+{
+    number: u8,
+}
+
+impl Vector
+{
+    /// Nummer des Vektors in der IDT.
+    pub fn number(&self) -> u8
+    {
+        self.number
+    }
+}
+
+impl Drop for Vector
+{
+    fn drop(&mut self)
+    {
+        *HANDLERS[usize::from(self.number - FIRST_VECTOR)].lock() = None;
+    }
+}
+
+/// Vergibt einen freien Vektor, der `handler` aufruft. Schaltet dazu beim
+/// ersten Aufruf den [Local APIC](apic) ein. Gibt `None` zurück, wenn alle
+/// Vektoren belegt sind.
+pub fn allocate(handler: Handler) -> Option<Vector>
+{
+    apic::init();
+    let mut handler = Some(handler);
+    for (index, slot) in HANDLERS.iter().enumerate()
+    {
+        let mut slot = slot.lock();
+        if slot.is_none()
+        {
+            *slot = handler.take();
+            return Some(Vector { number: FIRST_VECTOR + index as u8 });
+        }
+    }
+    None
+}
+
+/// Gemeinsamer Einstiegspunkt aller dynamischen Vektoren.
+pub(super) extern "x86-interrupt" fn interrupt_handler<const VECTOR: u8>(_stack_frame: InterruptStackFrame)
+{
+    if let Some(handler) = HANDLERS[usize::from(VECTOR - FIRST_VECTOR)].lock().as_ref()
+    {
+        handler();
+    }
+    apic::end_of_interrupt();
+}
+
+#[test_case]
+fn test_self_interrupt_reaches_handler()
+{
+    use alloc::sync::Arc;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    let count = Arc::new(AtomicUsize::new(0));
+    let counter = count.clone();
+    let vector = allocate(Box::new(move || { counter.fetch_add(1, Ordering::Relaxed); })).expect("no free vector");
+    assert!(vector.number() >= FIRST_VECTOR);
+
+    apic::send_self(vector.number());
+    apic::send_self(vector.number());
+    assert_eq!(count.load(Ordering::Relaxed), 2);
+
+    // Ein freigegebener Vektor wird wieder vergeben.
+    let number = vector.number();
+    drop(vector);
+    let again = allocate(Box::new(|| ())).unwrap();
+    assert_eq!(again.number(), number);
+}
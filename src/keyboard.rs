@@ -0,0 +1,167 @@
+//! # Modul: keyboard
+//!
+//! Dieses Modul übersetzt die Scancodes der PS/2-Tastatur in [Key]s und
+//! sammelt sie in einer Warteschlange, bis sie jemand abholt, z. B. die
+//! [Shell](crate::shell).
+//!
+//! # Aufbau
+//!
+//! | Komponente | Aufgabe |
+//! |------------|---------|
+//! | [handle_scancode] | Vom Interrupt-Handler aufgerufen, dekodiert und reiht ein |
+//! | [read], [try_read] | Holen die älteste Taste ab |
+//! | [push] | Reiht eine Taste von anderer Stelle ein |
+//!
+//! Die Warteschlange fasst [QUEUE_SIZE] Tasten und braucht keinen Heap, da
+//! sie im Interrupt-Handler gefüllt wird. Ist sie voll, gehen weitere Tasten
+//! verloren.
+
+use pc_keyboard::{DecodedKey, HandleControl, KeyCode, Keyboard, ScancodeSet1, layouts};
+
+use crate::sync::{IrqSpinlock, Lazy};
+
+/// Anzahl der Tasten, die auf Abholung warten können.
+pub const QUEUE_SIZE: usize = 64;
+
+/// Eine gedrückte Taste.
+///
+/// Mit Strg gedrückte Buchstaben kommen als Steuerzeichen an, z. B. Strg+C
+/// als `Char('\u{3}')`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key
+{
+    Char(char),
+    Enter,
+    Backspace,
+    Delete,
+    Tab,
+    Escape,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+}
+
+impl Key
+{
+    /// Übersetzt eine Taste von `pc_keyboard`; Umschalttasten und andere
+    /// ohne Bedeutung ergeben `None`.
+    fn from_decoded(key: DecodedKey) -> Option<Key>
+    {
+        Some(match key
+        {
+            DecodedKey::Unicode('\n') => Key::Enter,
+            DecodedKey::Unicode('\u{8}') => Key::Backspace,
+            DecodedKey::Unicode('\t') => Key::Tab,
+            DecodedKey::Unicode('\u{1b}') => Key::Escape,
+            DecodedKey::Unicode('\u{7f}') => Key::Delete,
+            DecodedKey::Unicode(character) => Key::Char(character),
+            DecodedKey::RawKey(KeyCode::ArrowLeft) => Key::Left,
+            DecodedKey::RawKey(KeyCode::ArrowRight) => Key::Right,
+            DecodedKey::RawKey(KeyCode::ArrowUp) => Key::Up,
+            DecodedKey::RawKey(KeyCode::ArrowDown) => Key::Down,
+            DecodedKey::RawKey(KeyCode::Home) => Key::Home,
+            DecodedKey::RawKey(KeyCode::End) => Key::End,
+            DecodedKey::RawKey(KeyCode::Delete) => Key::Delete,
+            DecodedKey::RawKey(_) => return None,
+        })
+    }
+}
+
+/// Ringpuffer fester Größe.
+struct Queue
+{
+    keys: [Key; QUEUE_SIZE],
+    head: usize,
+    len: usize,
+}
+
+impl Queue
+{
+    fn push(&mut self, key: Key) -> bool
+    {
+        if self.len == QUEUE_SIZE
+        {
+            return false;
+        }
+        self.keys[(self.head + self.len) % QUEUE_SIZE] = key;
+        self.len += 1;
+        true
+    }
+
+    fn pop(&mut self) -> Option<Key>
+    {
+        if self.len == 0
+        {
+            return None;
+        }
+        let key = self.keys[self.head];
+        self.head = (self.head + 1) % QUEUE_SIZE;
+        self.len -= 1;
+        Some(key)
+    }
+}
+
+/// Zustand des Dekoders (Umschalttasten, Präfix `0xE0`).
+static KEYBOARD: Lazy<IrqSpinlock<Keyboard<layouts::Us104Key, ScancodeSet1>>> = Lazy::new(||
+    IrqSpinlock::new(Keyboard::new(ScancodeSet1::new(), layouts::Us104Key, HandleControl::MapLettersToUnicode))
+);
+
+/// Tasten, die noch niemand abgeholt hat.
+static QUEUE: IrqSpinlock<Queue> = IrqSpinlock::new(Queue { keys: [Key::Enter; QUEUE_SIZE], head: 0, len: 0 });
+
+/// Dekodiert einen Scancode und reiht eine vollständige Taste ein. Wird vom
+/// Interrupt-Handler der Tastatur aufgerufen.
+pub fn handle_scancode(scancode: u8)
+{
+    let mut keyboard = KEYBOARD.lock();
+    if let Ok(Some(event)) = keyboard.add_byte(scancode)
+        && let Some(key) = keyboard.process_keyevent(event).and_then(Key::from_decoded)
+    {
+        push(key);
+    }
+}
+
+/// Reiht `key` ein, als wäre die Taste gedrückt worden. Gibt `false`
+/// zurück, wenn die Warteschlange voll ist.
+pub fn push(key: Key) -> bool
+{
+    QUEUE.lock().push(key)
+}
+
+/// Holt die älteste Taste ab, ohne zu warten.
+pub fn try_read() -> Option<Key>
+{
+    QUEUE.lock().pop()
+}
+
+/// Wartet auf die nächste Taste.
+pub fn read() -> Key
+{
+    loop
+    {
+        if let Some(key) = try_read()
+        {
+            return key;
+        }
+        crate::sync::wait();
+    }
+}
+
+#[test_case]
+fn test_queue()
+{
+    let mut queue = Queue { keys: [Key::Enter; QUEUE_SIZE], head: QUEUE_SIZE - 1, len: 0 };
+    assert!(queue.push(Key::Char('a')));
+    assert!(queue.push(Key::Up));
+    assert_eq!(queue.pop(), Some(Key::Char('a')));
+    assert_eq!(queue.pop(), Some(Key::Up));
+    assert_eq!(queue.pop(), None);
+    for _ in 0..QUEUE_SIZE
+    {
+        assert!(queue.push(Key::Tab));
+    }
+    assert!(!queue.push(Key::Tab));
+}
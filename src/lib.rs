@@ -21,9 +21,24 @@
 //! | [vga_buffer] | Textausgabe direkt im VGA-Speicher |
 //! | [interrupts] | Verwaltung und Behandlung von CPU-Interrupts |
 //! | [gdt] | Aufbau der Global Descriptor Table |
-//!
-//! Weitere Funktionen wie Paging, Speicherverwaltung oder Multitasking
-//! können später ergänzt werden.
+//! | [sync] | Synchronisationsprimitive (Spinlocks, Mutex, Semaphore, ...) |
+//! | [memory] | Seitentabellen und Vergabe physischer Frames |
+//! | [usermode] | Sprung in den Ring 3 (User Mode) |
+//! | [syscall] | Systemaufrufe über `syscall`/`sysret` und `int 0x80` |
+//! | [time] | Timer-Ticks, Laufzeit und Wartezeiten |
+//! | [elf] | Laden und Starten von ELF64-Programmen |
+//! | [allocator] | Kernel-Heap für `alloc` (`Box`, `Vec`, ...) |
+//! | [process] | Prozesse mit eigenem Adressraum, `spawn`, `exit` und `wait` |
+//! | [vfs] | Virtuelles Dateisystem mit Mountpunkten, Dateideskriptoren und tmpfs |
+//! | [initrd] | Eingebundenes USTAR-Archiv, eingehängt unter `/initrd` |
+//! | [block] | Blockgeräte und ATA-Treiber im PIO-Modus |
+//! | [acpi] | Suche nach ACPI-Tabellen der Firmware |
+//! | [pci] | Erkennung der PCI-Geräte und Zuordnung zu Treibern |
+//! | [virtio] | Transport und Warteschlangen paravirtualisierter Geräte |
+//! | [net] | Netzwerkgeräte, Treiber und TCP/IP-Stapel |
+//! | [keyboard] | Dekodierung der PS/2-Tastatur und Tastenpuffer |
+//! | [shell] | Kernel-Shell mit Zeileneditor und Befehlen |
+//! | [logger] | Logger für das `log`-Crate mit Filtern, Zielen und `dmesg` |
 //!
 //! # Testumgebung
 //!
@@ -39,11 +54,30 @@
 #![reexport_test_harness_main = "test_main"]
 #![feature(abi_x86_interrupt)]
 
+extern crate alloc;
 
 pub mod serial;
 pub mod vga_buffer;
 pub mod interrupts;
 pub mod gdt;
+pub mod sync;
+pub mod memory;
+pub mod usermode;
+pub mod syscall;
+pub mod time;
+pub mod elf;
+pub mod allocator;
+pub mod process;
+pub mod vfs;
+pub mod initrd;
+pub mod block;
+pub mod acpi;
+pub mod pci;
+pub mod virtio;
+pub mod net;
+pub mod keyboard;
+pub mod shell;
+pub mod logger;
 
 use core::panic::PanicInfo;
 // use crate::interrupts::PIC_1_OFFSET;
@@ -106,15 +140,19 @@ pub fn test_panic_handler(info: &PanicInfo) -> !
 }
 
 
+#[cfg(test)]
+bootloader::entry_point!(test_kernel_main);
+
 /// ### Test Entry Point (nur bei #[cfg(test)])
 ///
 /// Definiert den Einstiegspunkt für Testausführungen.
-/// Dieser ersetzt den normalen Kernelstart (_start) während Tests.
+/// Dieser ersetzt den normalen Kernelstart (_start) während Tests und richtet
+/// zusätzlich die Speicherverwaltung samt Heap ein.
 #[cfg(test)]
-#[unsafe(no_mangle)]
-pub extern "C" fn _start() -> !
+fn test_kernel_main(boot_info: &'static bootloader::BootInfo) -> !
 {
     init();
+    unsafe { memory::init(boot_info) };
     test_main();
     hlt_loop();
 }
@@ -164,12 +202,14 @@ pub fn exit_qemu(exit_code: QemuExitCode)
 ///
 /// Führt grundlegende Setup-Schritte aus:
 /// - Initialisiert die [Global Descriptor Table](crate::gdt)
+/// - Richtet die [Systemaufrufe](crate::syscall) ein
 /// - Initialisiert die [Interrupt Descriptor Table](crate::interrupts)
 /// - Initialisiert die 8259 PIC
 /// - Aktiviert Interrupts in der CPU Konfiguration
 pub fn init()
 {
     gdt::init();
+    syscall::init();
     interrupts::init_idt();
     unsafe { interrupts::PICS.lock().initialize() };
     x86_64::instructions::interrupts::enable();
@@ -0,0 +1,261 @@
+//! # Modul: logger
+//!
+//! Dieses Modul verbindet die Makros des `log`-Crates (`log::info!`,
+//! `log::warn!`, ...) mit dem Kernel. Jede Meldung erhält einen Zeitstempel,
+//! wird nach Level und Modul gefiltert und dann an alle angemeldeten
+//! [Sink]s verteilt.
+//!
+//! # Aufbau
+//!
+//! | Komponente | Aufgabe |
+//! |------------|---------|
+//! | [init] | Setzt den Logger und meldet die Standardziele an |
+//! | [set_level], [set_module_level] | Filter zur Laufzeit |
+//! | [add_sink], [remove_sink], [set_sink_level] | Ziele mit eigenem Mindestlevel |
+//! | [sink] | Bildschirm, serielle Schnittstelle und Ringpuffer |
+//! | [dmesg], [clear_dmesg] | Lesen die letzten Meldungen aus dem Ringpuffer |
+//!
+//! # Beispiel
+//!
+//! ```ignore
+//! logger::set_module_level("simple_os::net", LevelFilter::Debug);
+//! log::debug!("{} Bytes empfangen", len);
+//! ```
+//!
+//! Meldungen werden mit dem Heap formatiert; vor [init] und damit vor
+//! [crate::memory::init] verwirft `log` sie.
+
+pub mod sink;
+
+pub use sink::{RingBuffer, SerialSink, Sink, VgaSink};
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use crate::sync::{IrqSpinlock, Once};
+
+/// Anzahl der Meldungen im Ringpuffer für [dmesg].
+pub const DMESG_SIZE: usize = 256;
+
+/// ## LogEntry
+///
+/// Eine formatierte Meldung.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEntry
+{
+    /// Laufzeit seit dem Start.
+    pub timestamp_ms: u64,
+    pub level: Level,
+    /// Meist der Modulpfad, z. B. `simple_os::net::dhcp`.
+    pub target: String,
+    pub message: String,
+}
+
+impl fmt::Display for LogEntry
+{
+    /// Eine Zeile wie `[    1.250] INFO  simple_os::net::dhcp: ...`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "[{:5}.{:03}] {:5} {}: {}", self.timestamp_ms / 1000, self.timestamp_ms % 1000, self.level, self.target, self.message)
+    }
+}
+
+/// Level für alle Meldungen und Ausnahmen für einzelne Module.
+struct Filter
+{
+    default: LevelFilter,
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl Filter
+{
+    /// Das Level des längsten Moduls, in dem `target` liegt.
+    fn level(&self, target: &str) -> LevelFilter
+    {
+        self.modules.iter()
+            .filter(|(module, _)| target.strip_prefix(module.as_str()).is_some_and(|rest| rest.is_empty() || rest.starts_with("::")))
+            .max_by_key(|(module, _)| module.len())
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    /// Das höchste Level überhaupt; darüber verwirft schon `log` selbst.
+    fn max(&self) -> LevelFilter
+    {
+        self.modules.iter().map(|(_, level)| *level).fold(self.default, Ord::max)
+    }
+}
+
+static FILTER: IrqSpinlock<Filter> = IrqSpinlock::new(Filter { default: LevelFilter::Info, modules: Vec::new() });
+
+/// Ein angemeldetes Ziel.
+struct SinkEntry
+{
+    name: &'static str,
+    level: LevelFilter,
+    sink: Arc<dyn Sink>,
+}
+
+static SINKS: IrqSpinlock<Vec<SinkEntry>> = IrqSpinlock::new(Vec::new());
+
+/// Die letzten Meldungen.
+static DMESG: Once<Arc<RingBuffer>> = Once::new();
+
+/// Der Logger für `log::set_logger`.
+struct KernelLogger;
+
+static LOGGER: KernelLogger = KernelLogger;
+
+impl Log for KernelLogger
+{
+    fn enabled(&self, metadata: &Metadata) -> bool
+    {
+        metadata.level() <= FILTER.lock().level(metadata.target())
+    }
+
+    fn log(&self, record: &Record)
+    {
+        if !self.enabled(record.metadata())
+        {
+            return;
+        }
+        let entry = LogEntry
+        {
+            timestamp_ms: crate::time::uptime_ms(),
+            level: record.level(),
+            target: String::from(record.target()),
+            message: alloc::format!("{}", record.args()),
+        };
+        // Die Ziele laufen ohne Sperre, damit sie selbst Sperren nehmen können.
+        let sinks: Vec<Arc<dyn Sink>> = SINKS.lock().iter().filter(|sink| entry.level <= sink.level).map(|sink| sink.sink.clone()).collect();
+        for sink in sinks
+        {
+            sink.write(&entry);
+        }
+    }
+
+    fn flush(&self)
+    {
+    }
+}
+
+/// ## Initialisierung
+///
+/// Setzt den Logger und meldet beim ersten Aufruf die Ziele `"vga"` (ab
+/// [Level::Info]), `"serial"` und `"dmesg"` (alle Meldungen) an. Braucht den
+/// Heap.
+pub fn init()
+{
+    DMESG.call_once(||
+    {
+        let dmesg = Arc::new(RingBuffer::new(DMESG_SIZE));
+        add_sink("vga", LevelFilter::Info, Arc::new(VgaSink));
+        add_sink("serial", LevelFilter::Trace, Arc::new(SerialSink));
+        add_sink("dmesg", LevelFilter::Trace, dmesg.clone());
+        // Schlägt nur fehl, wenn schon ein anderer Logger gesetzt ist.
+        let _ = log::set_logger(&LOGGER);
+        log::set_max_level(FILTER.lock().max());
+        dmesg
+    });
+}
+
+/// Setzt das Level für alle Module ohne eigenes.
+pub fn set_level(level: LevelFilter)
+{
+    let mut filter = FILTER.lock();
+    filter.default = level;
+    log::set_max_level(filter.max());
+}
+
+/// Setzt das Level für `module` und alle Module darunter, z. B.
+/// `"simple_os::net"`.
+pub fn set_module_level(module: &str, level: LevelFilter)
+{
+    let mut filter = FILTER.lock();
+    match filter.modules.iter_mut().find(|(name, _)| name == module)
+    {
+        Some((_, current)) => *current = level,
+        None => filter.modules.push((String::from(module), level)),
+    }
+    log::set_max_level(filter.max());
+}
+
+/// Entfernt das eigene Level von `module`.
+pub fn reset_module_level(module: &str) -> bool
+{
+    let mut filter = FILTER.lock();
+    let before = filter.modules.len();
+    filter.modules.retain(|(name, _)| name != module);
+    log::set_max_level(filter.max());
+    filter.modules.len() != before
+}
+
+/// Das Level, das für Meldungen aus `target` gilt.
+pub fn level(target: &str) -> LevelFilter
+{
+    FILTER.lock().level(target)
+}
+
+/// Meldet ein Ziel an, das Meldungen bis `level` erhält. Gibt `false` zurück,
+/// wenn der Name vergeben ist.
+pub fn add_sink(name: &'static str, level: LevelFilter, sink: Arc<dyn Sink>) -> bool
+{
+    let mut sinks = SINKS.lock();
+    if sinks.iter().any(|entry| entry.name == name)
+    {
+        return false;
+    }
+    sinks.push(SinkEntry { name, level, sink });
+    true
+}
+
+/// Meldet ein Ziel ab.
+pub fn remove_sink(name: &str) -> bool
+{
+    let mut sinks = SINKS.lock();
+    let before = sinks.len();
+    sinks.retain(|entry| entry.name != name);
+    sinks.len() != before
+}
+
+/// Ändert das Mindestlevel eines Ziels.
+pub fn set_sink_level(name: &str, level: LevelFilter) -> bool
+{
+    SINKS.lock().iter_mut().find(|entry| entry.name == name).map(|entry| entry.level = level).is_some()
+}
+
+/// Die letzten [DMESG_SIZE] Meldungen, die älteste zuerst; leer vor [init].
+pub fn dmesg() -> Vec<LogEntry>
+{
+    DMESG.get().map(|dmesg| dmesg.entries()).unwrap_or_default()
+}
+
+/// Leert den Ringpuffer von [dmesg].
+pub fn clear_dmesg()
+{
+    if let Some(dmesg) = DMESG.get()
+    {
+        dmesg.clear();
+    }
+}
+
+#[test_case]
+fn test_filter()
+{
+    let filter = Filter
+    {
+        default: LevelFilter::Warn,
+        modules: alloc::vec![(String::from("simple_os::net"), LevelFilter::Debug), (String::from("simple_os::net::tcp"), LevelFilter::Off)],
+    };
+    assert_eq!(filter.level("simple_os::block"), LevelFilter::Warn);
+    assert_eq!(filter.level("simple_os::net"), LevelFilter::Debug);
+    assert_eq!(filter.level("simple_os::net::udp"), LevelFilter::Debug);
+    assert_eq!(filter.level("simple_os::network"), LevelFilter::Warn);
+    assert_eq!(filter.level("simple_os::net::tcp"), LevelFilter::Off);
+    assert_eq!(filter.max(), LevelFilter::Debug);
+
+    let entry = LogEntry { timestamp_ms: 1250, level: Level::Info, target: String::from("test"), message: String::from("hello") };
+    assert_eq!(alloc::format!("{}", entry), "[    1.250] INFO  test: hello");
+}
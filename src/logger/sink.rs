@@ -0,0 +1,105 @@
+//! Ziele für Logmeldungen.
+//!
+//! | Ziel | Ausgabe |
+//! |------|---------|
+//! | [VgaSink] | Bildschirm über [crate::println!] |
+//! | [SerialSink] | Port des Kanals [Channel::Log] |
+//! | [RingBuffer] | Die letzten Meldungen im Speicher, siehe [super::dmesg] |
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+use super::LogEntry;
+use crate::serial::Channel;
+use crate::sync::IrqSpinlock;
+
+/// ## Sink
+///
+/// Ein Ziel für Logmeldungen. Wird unter keiner Sperre des Loggers
+/// aufgerufen, darf aber selbst nichts loggen, sonst ruft es sich endlos
+/// wieder auf.
+pub trait Sink: Send + Sync
+{
+    fn write(&self, entry: &LogEntry);
+}
+
+/// Schreibt jede Meldung als Zeile auf den VGA-Bildschirm.
+#[derive(Debug, Default)]
+pub struct VgaSink;
+
+impl Sink for VgaSink
+{
+    fn write(&self, entry: &LogEntry)
+    {
+        crate::println!("{}", entry);
+    }
+}
+
+/// Schreibt jede Meldung als Zeile auf den Port von [Channel::Log].
+#[derive(Debug, Default)]
+pub struct SerialSink;
+
+impl Sink for SerialSink
+{
+    fn write(&self, entry: &LogEntry)
+    {
+        crate::serial_println_to!(Channel::Log, "{}", entry);
+    }
+}
+
+/// ## RingBuffer
+///
+/// Behält die letzten `capacity` Meldungen; ältere fallen heraus.
+pub struct RingBuffer
+{
+    capacity: usize,
+    entries: IrqSpinlock<VecDeque<LogEntry>>,
+}
+
+impl RingBuffer
+{
+    pub const fn new(capacity: usize) -> RingBuffer
+    {
+        RingBuffer { capacity, entries: IrqSpinlock::new(VecDeque::new()) }
+    }
+
+    /// Alle Meldungen, die älteste zuerst.
+    pub fn entries(&self) -> Vec<LogEntry>
+    {
+        self.entries.lock().iter().cloned().collect()
+    }
+
+    pub fn clear(&self)
+    {
+        self.entries.lock().clear();
+    }
+}
+
+impl Sink for RingBuffer
+{
+    fn write(&self, entry: &LogEntry)
+    {
+        let mut entries = self.entries.lock();
+        if entries.len() == self.capacity
+        {
+            entries.pop_front();
+        }
+        entries.push_back(entry.clone());
+    }
+}
+
+#[test_case]
+fn test_ring_buffer()
+{
+    use alloc::string::String;
+
+    let ring = RingBuffer::new(2);
+    for (timestamp_ms, message) in [(1, "a"), (2, "b"), (3, "c")]
+    {
+        ring.write(&LogEntry { timestamp_ms, level: log::Level::Info, target: String::from("test"), message: String::from(message) });
+    }
+    let messages: Vec<_> = ring.entries().into_iter().map(|entry| entry.message).collect();
+    assert_eq!(messages, ["b", "c"]);
+    ring.clear();
+    assert!(ring.entries().is_empty());
+}
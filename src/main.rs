@@ -19,21 +19,30 @@
 //! In Bare-Metal-Umgebungen (z. B. bei Betriebssystem-Kernen) existiert
 //! **kein Betriebssystem**, das eine main()-Funktion aufruft oder
 //! Panics handhabt.  
-//! Deshalb definiert dieses Modul den tatsächlichen Einstiegspunkt _start
-//! und eigene Mechanismen für Panic-Handling und Testunterstützung.
+//! Deshalb definiert dieses Modul über [entry_point!] den tatsächlichen
+//! Einstiegspunkt und eigene Mechanismen für Panic-Handling und Testunterstützung.
 #![no_std]
 #![no_main]
 #![feature(custom_test_frameworks)]
 #![test_runner(simple_os::test_runner)]
 #![reexport_test_harness_main = "test_main"]
 
+extern crate alloc;
+
+use alloc::sync::Arc;
+use bootloader::{BootInfo, entry_point};
 use core::panic::PanicInfo;
-use simple_os::println;
+use simple_os::{print, println};
+
+entry_point!(kernel_main);
 
 /// Einstiegspunkt des Betriebssystems.
 ///
-/// Diese Funktion entspricht dem **Kernel-Einstiegspunkt** (_start),
+/// Diese Funktion entspricht dem **Kernel-Einstiegspunkt**,
 /// der vom Bootloader nach dem Laden des Kernels aufgerufen wird.
+/// Das Makro [entry_point!] erzeugt daraus das eigentliche Symbol _start
+/// und prüft dabei die Signatur, sodass der Bootloader die [BootInfo]
+/// typsicher übergeben kann.
 ///
 /// Sie ersetzt in einem Betriebssystem den üblichen Einstiegspunkt main().
 /// Der Rückgabetyp [!] bedeutet, dass diese Funktion **niemals zurückkehren darf**.
@@ -42,25 +51,14 @@ use simple_os::println;
 /// Innerhalb dieser Funktion wird:
 /// - eine Begrüßungsnachricht auf die Konsole ausgegeben,
 /// - die **Hardware- und Interrupt-Initialisierung** über [simple_os::init()] durchgeführt,
+/// - die **Speicherverwaltung** über [simple_os::memory::init()] eingerichtet,
 /// - optional (#[cfg(test)]) die **Testsuite** aufgerufen,
 /// - und anschließend in eine **Endlosschleife** übergegangen.
 ///
-/// # ABI
-///
-/// Es wird das **C-ABI** (extern "C") verwendet.  
-/// Dieses garantiert ein wohldefiniertes, plattformkonstantes Speicherlayout,  
-/// sodass der Bootloader die Funktion zuverlässig aufrufen kann.
-///
-/// # Sicherheit
-///
-/// Die Funktion ist mit #[no_mangle] gekennzeichnet, damit der Compiler
-/// den Symbolnamen _start **nicht verändert**.  
-/// Der Bootloader erwartet diesen genauen Funktionsnamen, um den Kernel zu starten.
-///
 /// # Ablauf
 ///
 /// ```text
-/// Bootloader --> _start() --> simple_os::init() --> Endlosschleife
+/// Bootloader --> _start() --> kernel_main() --> simple_os::init() --> Endlosschleife
 /// ```
 ///
 /// # Beispielausgabe
@@ -71,17 +69,57 @@ use simple_os::println;
 /// ```
 ///
 /// [!]: https://doc.rust-lang.org/std/primitive.never.html
-#[unsafe(no_mangle)]
-pub extern "C" fn _start() -> !
+fn kernel_main(boot_info: &'static BootInfo) -> !
 {
     println!("Hello World {}", "!");
 
     simple_os::init();
+    unsafe { simple_os::memory::init(boot_info) };
+    simple_os::logger::init();
+
+    let kernel_space = simple_os::memory::AddressSpace::kernel();
+    log::info!("level 4 page table at {:?}", kernel_space.level_4_frame().start_address());
+    log::info!("PCID enabled: {}", simple_os::memory::pcid_enabled());
 
-    use x86_64::registers::control::Cr3;
+    simple_os::initrd::init().expect("failed to mount the initrd");
+    if let Ok(motd) = simple_os::vfs::read_to_end("/initrd/etc/motd")
+    {
+        print!("{}", core::str::from_utf8(&motd).unwrap_or(""));
+    }
 
-    let (level_4_page_table, _) = Cr3::read();
-    println!("Level 4 page table at: {:?}", level_4_page_table.start_address());
+    simple_os::block::virtio::register_driver();
+    simple_os::net::virtio::init();
+    simple_os::net::e1000::init();
+    for device in simple_os::pci::init()
+    {
+        log::info!("pci {}", device);
+    }
+    for disk in simple_os::block::virtio::disks()
+    {
+        use simple_os::block::BlockDevice;
+        log::info!("{}: virtio ({} KiB)", disk.name(), disk.capacity() / 1024);
+    }
+    simple_os::net::init();
+    for device in simple_os::net::devices()
+    {
+        log::info!("{}: {} (MTU {})", device.name(), device.mac_address(), device.mtu());
+    }
+    match simple_os::net::dhcp::configure("eth0")
+    {
+        Ok(config) => log::info!("eth0: {}", config),
+        Err(error) => log::warn!("eth0: no DHCP lease ({:?})", error),
+    }
+
+    for drive in simple_os::block::ata::init()
+    {
+        use simple_os::block::BlockDevice;
+        log::info!("{}: {} ({} KiB)", drive.name(), drive.model(), drive.capacity() / 1024);
+        let device: Arc<dyn BlockDevice> = drive;
+        for partition in simple_os::block::partition::scan(&device).unwrap_or_default()
+        {
+            log::info!("{}: type {:#04x} at sector {}", partition.name(), partition.kind(), partition.start());
+        }
+    }
 
     // let ptr = 0x2051b4 as *mut u8;
     // unsafe { let x = *ptr; }
@@ -94,7 +132,15 @@ pub extern "C" fn _start() -> !
     test_main();
 
     println!("It did not crash!");
-    simple_os::hlt_loop();
+    for port in simple_os::serial::init()
+    {
+        if let Some(config) = simple_os::serial::config(*port)
+        {
+            log::info!("{}: {:#x} ({})", port, port.base(), config);
+        }
+    }
+    let mut input = simple_os::shell::ConsoleInput::new();
+    simple_os::shell::run(&mut simple_os::shell::Console, || input.read());
 }
 
 /// Panic-Handler für das Betriebssystem.
@@ -0,0 +1,972 @@
+//! # Modul: memory
+//!
+//! Dieses Modul verwaltet den **physischen Speicher** und die **Seitentabellen**.
+//!
+//! Der Bootloader bildet mit dem Feature `map_physical_memory` den gesamten
+//! physischen Speicher ab einem festen Offset in den virtuellen Adressraum ab.
+//! Dadurch kann der Kernel jede Seitentabelle (die ja über physische Adressen
+//! verkettet sind) direkt lesen und verändern.
+//!
+//! # Aufbau
+//!
+//! | Komponente | Aufgabe |
+//! |------------|---------|
+//! | [init] | Speichert Offset und Speicherkarte aus der [BootInfo] |
+//! | [active_mapper] | [OffsetPageTable] für den aktuell aktiven Adressraum |
+//! | [BootInfoFrameAllocator] | Vergibt freie physische Frames aus der Speicherkarte |
+//! | [frame_allocator] | Zugriff auf den globalen Frame-Allocator |
+//! | [map_mmio] | Bildet Register von Geräten ungecacht ab |
+//! | [dma] | Physisch zusammenhängende Puffer für Geräte |
+//! | [free_frame] | Gibt einen Frame an den Frame-Allocator zurück |
+//! | [AddressSpace] | Eigener Adressraum (Level-4-Tabelle) für User-Programme |
+//! | [handle_cow_fault] | Kopiert beim Schreibzugriff eine Copy-on-Write-Seite |
+//! | [check_user_range], [write_user] | Prüfen Zeiger aus Systemaufrufen gegen die Seitentabellen |
+//!
+//! # Aufteilung des virtuellen Adressraums
+//!
+//! | Level-4-Einträge | Bereich | Inhalt |
+//! |------------------|---------|--------|
+//! | 0 | `0x0` – `0x80_0000_0000` | Kernel-Image, VGA-Puffer (vom Bootloader) |
+//! | 1 – 255 | [USER_SPACE_START] – [USER_SPACE_END] | User-Programme |
+//! | 256 – 511 | obere Hälfte | Physischer Speicher, Kernel-Heap, Kernel-Stack, Boot-Info |
+//!
+//! Die Kernel-Einträge werden in jeden neuen [AddressSpace] übernommen, sodass
+//! der Kernel nach einem Wechsel von CR3 weiterläuft.
+//!
+//! # PCID
+//!
+//! Unterstützt die CPU **Process-Context Identifiers**, bekommt jeder
+//! [AddressSpace] eine eigene PCID. Beim Wechsel von CR3 bleiben die
+//! TLB-Einträge der anderen Adressräume dann erhalten, statt jedes Mal
+//! verworfen zu werden. Der Kernel verwendet PCID 0.
+//!
+//! # Copy-on-Write
+//!
+//! [AddressSpace::fork] kopiert keine Seiten, sondern bildet dieselben Frames in
+//! beiden Adressräumen ab. Beschreibbare Seiten werden dabei schreibgeschützt
+//! und mit [COW] markiert; jeder Frame hat einen Referenzzähler
+//! ([frame_ref_count]). Erst der erste Schreibzugriff löst eine Page Fault aus,
+//! in der [handle_cow_fault] den Frame kopiert – oder, wenn nur noch ein
+//! Adressraum ihn verwendet, die Seite einfach wieder beschreibbar macht.
+//!
+//! Damit auch Schreibzugriffe des Kernels auf User-Seiten (z. B. Ergebnisse von
+//! Systemaufrufen) diesen Mechanismus auslösen, setzt [init] das Bit CR0.WP.
+
+pub mod dma;
+
+use bootloader::BootInfo;
+use bootloader::bootinfo::{MemoryMap, MemoryRegionType};
+use alloc::collections::BTreeMap;
+use core::sync::atomic::{AtomicBool, Ordering};
+use x86_64::instructions::tlb::{self, Pcid};
+use x86_64::registers::control::{Cr0, Cr0Flags, Cr3, Cr4, Cr4Flags};
+use x86_64::structures::paging::mapper::{MapToError, MappedFrame, Translate, TranslateResult};
+use x86_64::structures::paging::page_table::PageTableEntry;
+use x86_64::structures::paging::{
+    FrameAllocator, FrameDeallocator, Mapper, OffsetPageTable, Page, PageTable, PageTableFlags, PageTableIndex,
+    PhysFrame, Size4KiB,
+};
+use x86_64::{PhysAddr, VirtAddr};
+
+use crate::sync::{IrqSpinlock, IrqSpinlockGuard, Once};
+
+/// Erste Adresse, an der User-Programme abgebildet werden dürfen (Level-4-Eintrag 1).
+pub const USER_SPACE_START: u64 = 0x0000_0080_0000_0000;
+
+/// Erste Adresse hinter der unteren Hälfte des Adressraums.
+pub const USER_SPACE_END: u64 = 0x0000_8000_0000_0000;
+
+/// Größe einer Seite in Bytes.
+pub const PAGE_SIZE: u64 = 4096;
+
+/// Markiert eine schreibgeschützte Seite als Copy-on-Write. Bit 9 ist für das
+/// Betriebssystem frei und wird von der CPU ignoriert.
+pub const COW: PageTableFlags = PageTableFlags::BIT_9;
+
+/// Virtuelle Adresse, ab der der physische Speicher abgebildet ist.
+static PHYSICAL_MEMORY_OFFSET: Once<VirtAddr> = Once::new();
+
+/// Level-4-Tabelle des Kernels, wie sie der Bootloader angelegt hat.
+static KERNEL_LEVEL_4_FRAME: Once<PhysFrame> = Once::new();
+
+/// Globaler Frame-Allocator, wird in [init] angelegt.
+static FRAME_ALLOCATOR: Once<IrqSpinlock<BootInfoFrameAllocator>> = Once::new();
+
+/// Referenzzähler der Frames, die von mehreren Adressräumen verwendet werden.
+/// Frames ohne Eintrag haben genau einen Besitzer.
+static FRAME_REFS: IrqSpinlock<BTreeMap<PhysFrame, usize>> = IrqSpinlock::new(BTreeMap::new());
+
+/// Gibt an, ob CR4.PCIDE gesetzt ist.
+static PCID_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Anzahl der verfügbaren PCIDs (12 Bit).
+const PCID_COUNT: usize = 4096;
+
+/// Belegte PCIDs als Bitmap; PCID 0 gehört dem Kernel.
+static PCIDS: IrqSpinlock<[u64; PCID_COUNT / 64]> = IrqSpinlock::new(
+{
+    let mut bitmap = [0; PCID_COUNT / 64];
+    bitmap[0] = 1;
+    bitmap
+});
+
+/// ## Initialisierung der Speicherverwaltung
+///
+/// Übernimmt den Offset des physischen Speichers und die Speicherkarte aus der
+/// [BootInfo] des Bootloaders.
+///
+/// # Safety
+///
+/// Der Aufrufer muss garantieren, dass der Bootloader den kompletten physischen
+/// Speicher ab `boot_info.physical_memory_offset` abgebildet hat und dass die
+/// als `Usable` markierten Bereiche tatsächlich frei sind.
+///
+/// Anschließend wird der [Kernel-Heap](crate::allocator) angelegt, CR0.WP
+/// gesetzt und, falls die CPU es unterstützt, PCID aktiviert.
+pub unsafe fn init(boot_info: &'static BootInfo)
+{
+    PHYSICAL_MEMORY_OFFSET.call_once(|| VirtAddr::new(boot_info.physical_memory_offset));
+    KERNEL_LEVEL_4_FRAME.call_once(|| Cr3::read().0);
+    FRAME_ALLOCATOR.call_once(|| IrqSpinlock::new(BootInfoFrameAllocator::new(&boot_info.memory_map)));
+    crate::allocator::init_heap().expect("heap initialization failed");
+    unsafe { Cr0::update(|flags| flags.insert(Cr0Flags::WRITE_PROTECT)) };
+    enable_pcid();
+}
+
+/// Aktiviert CR4.PCIDE, wenn CPUID.01H:ECX Bit 17 gesetzt ist.
+///
+/// Das ist nur erlaubt, solange in CR3 PCID 0 steht – was nach dem Bootloader
+/// der Fall ist.
+fn enable_pcid()
+{
+    let supported = core::arch::x86_64::__cpuid(1).ecx & (1 << 17) != 0;
+    if supported && !pcid_enabled()
+    {
+        unsafe { Cr4::update(|flags| flags.insert(Cr4Flags::PCID)) };
+        PCID_ENABLED.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Gibt an, ob Adressräume mit eigenen PCIDs betrieben werden.
+pub fn pcid_enabled() -> bool
+{
+    PCID_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Reserviert eine freie PCID oder gibt `None` zurück, wenn PCID nicht aktiv ist
+/// oder alle vergeben sind.
+fn allocate_pcid() -> Option<Pcid>
+{
+    if !pcid_enabled()
+    {
+        return None;
+    }
+    let mut bitmap = PCIDS.lock();
+    let (index, word) = bitmap.iter_mut().enumerate().find(|(_, word)| **word != u64::MAX)?;
+    let bit = word.trailing_ones() as usize;
+    *word |= 1 << bit;
+    Pcid::new((index * 64 + bit) as u16).ok()
+}
+
+/// Gibt eine mit [allocate_pcid] reservierte PCID wieder frei.
+fn free_pcid(pcid: Pcid)
+{
+    let value = usize::from(pcid.value());
+    PCIDS.lock()[value / 64] &= !(1 << (value % 64));
+}
+
+/// Gibt den Offset zurück, ab dem der physische Speicher abgebildet ist.
+pub fn physical_memory_offset() -> VirtAddr
+{
+    *PHYSICAL_MEMORY_OFFSET.get().expect("memory::init has not been called")
+}
+
+/// Rechnet eine physische Adresse in die zugehörige virtuelle Adresse um.
+pub fn phys_to_virt(addr: PhysAddr) -> VirtAddr
+{
+    physical_memory_offset() + addr.as_u64()
+}
+
+/// Bildet den physischen Bereich `start..start + size` ungecacht an seiner
+/// Adresse im abgebildeten physischen Speicher ab und gibt diese zurück.
+///
+/// Der Bootloader bildet den physischen Speicher nur bis zur höchsten Adresse
+/// der Speicherkarte ab; Register von Geräten (MMIO) liegen oft darüber.
+/// Bereits abgebildete Seiten bleiben unverändert.
+pub fn map_mmio(start: PhysAddr, size: u64) -> Result<VirtAddr, MapToError<Size4KiB>>
+{
+    let first = PhysFrame::<Size4KiB>::containing_address(start);
+    let last = PhysFrame::containing_address(start + size.max(1) - 1u64);
+    let level_4_frame = *KERNEL_LEVEL_4_FRAME.get().expect("memory::init has not been called");
+    let mut mapper = unsafe { mapper_for(level_4_frame) };
+    let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_CACHE | PageTableFlags::NO_EXECUTE;
+
+    for frame in PhysFrame::range_inclusive(first, last)
+    {
+        let page = Page::containing_address(phys_to_virt(frame.start_address()));
+        if mapper.translate_addr(page.start_address()).is_some()
+        {
+            continue;
+        }
+        let mut allocator = frame_allocator();
+        unsafe { mapper.map_to(page, frame, flags, &mut *allocator)?.flush() };
+    }
+    Ok(phys_to_virt(start))
+}
+
+/// Gibt den gesperrten globalen Frame-Allocator zurück.
+///
+/// Solange das Guard lebt, sind Interrupts deaktiviert.
+pub fn frame_allocator() -> IrqSpinlockGuard<'static, BootInfoFrameAllocator>
+{
+    FRAME_ALLOCATOR.get().expect("memory::init has not been called").lock()
+}
+
+/// Gibt `frame` an den globalen Frame-Allocator zurück.
+///
+/// # Safety
+///
+/// Der Frame muss vom Frame-Allocator stammen und darf nirgends mehr
+/// abgebildet oder in Verwendung sein.
+pub unsafe fn free_frame(frame: PhysFrame)
+{
+    unsafe { frame_allocator().deallocate_frame(frame) };
+}
+
+/// Anzahl der Adressräume, in denen der vergebene Frame `frame` abgebildet ist.
+pub fn frame_ref_count(frame: PhysFrame) -> usize
+{
+    FRAME_REFS.lock().get(&frame).copied().unwrap_or(1)
+}
+
+/// Erhöht den Referenzzähler von `frame`, weil ein weiterer Adressraum ihn abbildet.
+fn share_frame(frame: PhysFrame)
+{
+    *FRAME_REFS.lock().entry(frame).or_insert(1) += 1;
+}
+
+/// Verringert den Referenzzähler von `frame` und gibt ihn frei, wenn ihn kein
+/// Adressraum mehr verwendet.
+///
+/// # Safety
+///
+/// Der Aufrufer muss seine Abbildung von `frame` bereits entfernt haben.
+unsafe fn release_frame(frame: PhysFrame)
+{
+    {
+        let mut refs = FRAME_REFS.lock();
+        if let Some(count) = refs.get_mut(&frame)
+        {
+            *count -= 1;
+            if *count == 1
+            {
+                refs.remove(&frame);
+            }
+            return;
+        }
+    }
+    unsafe { free_frame(frame) };
+}
+
+/// Anzahl der aktuell vergebenen Frames.
+pub fn allocated_frames() -> usize
+{
+    frame_allocator().allocated
+}
+
+/// Erstellt eine [OffsetPageTable] für den aktuell aktiven Adressraum (CR3).
+///
+/// # Safety
+///
+/// Es darf nicht gleichzeitig eine zweite Instanz für dieselbe Level-4-Tabelle
+/// verwendet werden, da sonst zwei `&mut`-Referenzen auf dieselbe Tabelle existieren.
+pub unsafe fn active_mapper() -> OffsetPageTable<'static>
+{
+    let (level_4_frame, _) = Cr3::read();
+    unsafe { mapper_for(level_4_frame) }
+}
+
+/// Erstellt eine [OffsetPageTable] für die Level-4-Tabelle in `level_4_frame`.
+///
+/// # Safety
+///
+/// Wie bei [active_mapper]; zusätzlich muss `level_4_frame` eine gültige
+/// Level-4-Tabelle enthalten.
+pub unsafe fn mapper_for(level_4_frame: PhysFrame) -> OffsetPageTable<'static>
+{
+    let table = phys_to_virt(level_4_frame.start_address()).as_mut_ptr::<PageTable>();
+    unsafe { OffsetPageTable::new(&mut *table, physical_memory_offset()) }
+}
+
+/// ## BootInfoFrameAllocator
+///
+/// Vergibt physische Frames aus allen Bereichen, die der Bootloader in der
+/// Speicherkarte als `Usable` markiert hat.
+///
+/// `next` zählt die bereits vergebenen Frames; bei jeder Anfrage wird der
+/// nächste freie Frame aus der Speicherkarte gesucht.
+///
+/// Zurückgegebene Frames landen in einer Freiliste, die direkt in den freien
+/// Frames gespeichert ist: Die ersten acht Bytes eines freien Frames enthalten
+/// die physische Adresse des nächsten. Sie werden vor der Speicherkarte vergeben.
+pub struct BootInfoFrameAllocator
+{
+    memory_map: &'static MemoryMap,
+    next: usize,
+    free_list: Option<PhysFrame>,
+    allocated: usize,
+}
+
+impl BootInfoFrameAllocator
+{
+    fn new(memory_map: &'static MemoryMap) -> BootInfoFrameAllocator
+    {
+        BootInfoFrameAllocator { memory_map, next: 0, free_list: None, allocated: 0 }
+    }
+
+    /// Zeiger auf das Verkettungsfeld eines freien Frames.
+    fn link(frame: PhysFrame) -> *mut u64
+    {
+        phys_to_virt(frame.start_address()).as_mut_ptr()
+    }
+
+    /// Iterator über alle nutzbaren Frames der Speicherkarte.
+    fn usable_frames(&self) -> impl Iterator<Item = PhysFrame>
+    {
+        self.memory_map
+            .iter()
+            .filter(|region| region.region_type == MemoryRegionType::Usable)
+            .map(|region| region.range.start_addr()..region.range.end_addr())
+            .flat_map(|range| range.step_by(4096))
+            .map(|addr| PhysFrame::containing_address(PhysAddr::new(addr)))
+    }
+
+    /// Vergibt `count` physisch aufeinanderfolgende Frames aus der
+    /// Speicherkarte und gibt den ersten zurück. Frames, die dafür übersprungen
+    /// werden, landen in der Freiliste.
+    pub fn allocate_contiguous(&mut self, count: usize) -> Option<PhysFrame>
+    {
+        let mut run: Option<(usize, PhysFrame)> = None;
+        let mut length = 0;
+        for (index, frame) in self.usable_frames().enumerate().skip(self.next)
+        {
+            match run
+            {
+                Some((_, first)) if frame == first + length as u64 => length += 1,
+                _ =>
+                {
+                    run = Some((index, frame));
+                    length = 1;
+                }
+            }
+            if length == count
+            {
+                break;
+            }
+        }
+        let (first_index, first) = run.filter(|_| length == count && count > 0)?;
+
+        for index in self.next..first_index
+        {
+            let skipped = self.usable_frames().nth(index).unwrap();
+            self.allocated += 1;
+            unsafe { self.deallocate_frame(skipped) };
+        }
+        self.next = first_index + count;
+        self.allocated += count;
+        Some(first)
+    }
+}
+
+unsafe impl FrameAllocator<Size4KiB> for BootInfoFrameAllocator
+{
+    fn allocate_frame(&mut self) -> Option<PhysFrame>
+    {
+        let frame = match self.free_list
+        {
+            Some(frame) =>
+            {
+                let next = unsafe { Self::link(frame).read() };
+                self.free_list = (next != u64::MAX).then(|| PhysFrame::containing_address(PhysAddr::new(next)));
+                Some(frame)
+            }
+            None =>
+            {
+                let frame = self.usable_frames().nth(self.next);
+                self.next += 1;
+                frame
+            }
+        };
+        self.allocated += usize::from(frame.is_some());
+        frame
+    }
+}
+
+impl FrameDeallocator<Size4KiB> for BootInfoFrameAllocator
+{
+    unsafe fn deallocate_frame(&mut self, frame: PhysFrame)
+    {
+        let next = self.free_list.map_or(u64::MAX, |frame| frame.start_address().as_u64());
+        unsafe { Self::link(frame).write(next) };
+        self.free_list = Some(frame);
+        self.allocated -= 1;
+    }
+}
+
+/// Gibt an, ob der Level-4-Eintrag `index` zum Kernel gehört und daher in
+/// jeden Adressraum übernommen wird.
+fn is_kernel_entry(index: usize) -> bool
+{
+    index == 0 || index >= 256
+}
+
+/// ## AddressSpace
+///
+/// Ein eigener virtueller Adressraum, repräsentiert durch eine eigene
+/// Level-4-Tabelle.
+///
+/// Die Kernel-Einträge (siehe Modulbeschreibung) verweisen auf dieselben
+/// Tabellen wie beim Kernel; die User-Hälfte ist anfangs leer. Seiten werden mit
+/// [map_user_page](AddressSpace::map_user_page) abgebildet und können über
+/// [write](AddressSpace::write) beschrieben werden, ohne dass der Adressraum
+/// aktiv sein muss – der Zugriff erfolgt über die Abbildung des physischen Speichers.
+///
+/// Beim Verwerfen werden alle Frames der User-Hälfte samt Seitentabellen an den
+/// Frame-Allocator zurückgegeben. Der Adressraum darf dann nicht mehr aktiv sein.
+#[derive(Debug)]
+pub struct AddressSpace
+{
+    level_4_frame: PhysFrame,
+    pcid: Option<Pcid>,
+    /// Der TLB kann für diese PCID veraltete Einträge enthalten, die beim
+    /// nächsten [activate](AddressSpace::activate) verworfen werden müssen.
+    stale: AtomicBool,
+}
+
+impl AddressSpace
+{
+    /// Legt einen neuen Adressraum mit leerer User-Hälfte an.
+    pub fn new() -> Result<AddressSpace, MapToError<Size4KiB>>
+    {
+        let frame = frame_allocator().allocate_frame().ok_or(MapToError::FrameAllocationFailed)?;
+        let kernel_frame = *KERNEL_LEVEL_4_FRAME.get().expect("memory::init has not been called");
+
+        // SAFETY: Beide Frames enthalten Level-4-Tabellen und sind über den
+        // Offset abgebildet; der neue Frame wurde gerade exklusiv allokiert.
+        let (table, kernel_table) = unsafe
+        {
+            (
+                &mut *phys_to_virt(frame.start_address()).as_mut_ptr::<PageTable>(),
+                &*phys_to_virt(kernel_frame.start_address()).as_ptr::<PageTable>(),
+            )
+        };
+        table.zero();
+        for (index, entry) in kernel_table.iter().enumerate()
+        {
+            if is_kernel_entry(index)
+            {
+                table[index] = entry.clone();
+            }
+        }
+
+        Ok(AddressSpace { level_4_frame: frame, pcid: allocate_pcid(), stale: AtomicBool::new(true) })
+    }
+
+    /// Der Adressraum des Kernels.
+    pub fn kernel() -> AddressSpace
+    {
+        AddressSpace
+        {
+            level_4_frame: *KERNEL_LEVEL_4_FRAME.get().expect("memory::init has not been called"),
+            pcid: None,
+            stale: AtomicBool::new(false),
+        }
+    }
+
+    /// Gibt an, ob dies der Adressraum des Kernels ist.
+    pub fn is_kernel(&self) -> bool
+    {
+        KERNEL_LEVEL_4_FRAME.get() == Some(&self.level_4_frame)
+    }
+
+    /// Gibt an, ob dieser Adressraum gerade in CR3 geladen ist.
+    pub fn is_active(&self) -> bool
+    {
+        Cr3::read().0 == self.level_4_frame
+    }
+
+    /// Der Frame mit der Level-4-Tabelle dieses Adressraums.
+    pub fn level_4_frame(&self) -> PhysFrame
+    {
+        self.level_4_frame
+    }
+
+    /// Erstellt einen Mapper für diesen Adressraum.
+    ///
+    /// # Safety
+    ///
+    /// Wie bei [mapper_for]: Es darf keine zweite Instanz gleichzeitig existieren.
+    pub unsafe fn mapper(&mut self) -> OffsetPageTable<'static>
+    {
+        unsafe { mapper_for(self.level_4_frame) }
+    }
+
+    /// Lädt die Level-4-Tabelle dieses Adressraums nach CR3.
+    ///
+    /// Mit PCID wird Bit 63 gesetzt, sodass die TLB-Einträge dieser PCID erhalten
+    /// bleiben – außer der Adressraum wurde seit dem letzten Wechsel verändert.
+    ///
+    /// # Safety
+    ///
+    /// Der aktuell ausgeführte Code und Stack müssen in den Kernel-Einträgen liegen.
+    pub unsafe fn activate(&self)
+    {
+        let (current, flags) = Cr3::read();
+        if current == self.level_4_frame
+        {
+            return;
+        }
+
+        let stale = self.stale.swap(false, Ordering::Relaxed);
+        if pcid_enabled()
+        {
+            const NO_FLUSH: u64 = 1 << 63;
+            let pcid = self.pcid.map_or(0, |pcid| u64::from(pcid.value()));
+            let mut value = self.level_4_frame.start_address().as_u64() | pcid;
+            if !stale && self.pcid.is_some() || self.is_kernel()
+            {
+                value |= NO_FLUSH;
+            }
+            unsafe { core::arch::asm!("mov cr3, {}", in(reg) value, options(nostack, preserves_flags)) };
+        }
+        else
+        {
+            unsafe { Cr3::write(self.level_4_frame, flags) };
+        }
+    }
+
+    /// Sorgt dafür, dass der TLB keine veralteten Einträge für `page` enthält.
+    ///
+    /// Ist der Adressraum aktiv, wird die Seite sofort invalidiert, sonst beim
+    /// nächsten [activate](AddressSpace::activate) die ganze PCID.
+    pub fn flush_page(&self, page: Page)
+    {
+        if self.is_active()
+        {
+            x86_64::instructions::tlb::flush(page.start_address());
+        }
+        else
+        {
+            self.stale.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Bildet `page` auf einen frischen, genullten Frame ab.
+    ///
+    /// Ist die Seite bereits abgebildet, werden nur die Flags erweitert. So
+    /// können sich mehrere ELF-Segmente eine Seite teilen.
+    ///
+    /// # Panics
+    ///
+    /// Wenn `page` nicht zwischen [USER_SPACE_START] und [USER_SPACE_END] liegt.
+    pub fn map_user_page(&mut self, page: Page, flags: PageTableFlags) -> Result<(), MapToError<Size4KiB>>
+    {
+        assert!(
+            (USER_SPACE_START..USER_SPACE_END).contains(&page.start_address().as_u64()),
+            "page {:?} is outside of the user space", page
+        );
+
+        let flags = flags | PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE;
+        let parent_flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE;
+        let mut mapper = unsafe { self.mapper() };
+
+        if let TranslateResult::Mapped { flags: old_flags, .. } = mapper.translate(page.start_address())
+        {
+            let mut merged = (old_flags | flags) & !PageTableFlags::NO_EXECUTE;
+            if old_flags.contains(PageTableFlags::NO_EXECUTE) && flags.contains(PageTableFlags::NO_EXECUTE)
+            {
+                merged |= PageTableFlags::NO_EXECUTE;
+            }
+            unsafe { mapper.update_flags(page, merged).map_err(|_| MapToError::ParentEntryHugePage)?.ignore() };
+            self.flush_page(page);
+            return Ok(());
+        }
+
+        let mut allocator = frame_allocator();
+        let frame = allocator.allocate_frame().ok_or(MapToError::FrameAllocationFailed)?;
+        unsafe
+        {
+            core::ptr::write_bytes(phys_to_virt(frame.start_address()).as_mut_ptr::<u8>(), 0, PAGE_SIZE as usize);
+            mapper.map_to_with_table_flags(page, frame, flags, parent_flags, &mut *allocator)?.ignore();
+        }
+        Ok(())
+    }
+
+    /// Frame und Flags, auf die `page` abgebildet ist.
+    pub fn translate(&self, page: Page) -> Option<(PhysFrame, PageTableFlags)>
+    {
+        let mapper = unsafe { mapper_for(self.level_4_frame) };
+        match mapper.translate(page.start_address())
+        {
+            TranslateResult::Mapped { frame: MappedFrame::Size4KiB(frame), flags, .. } => Some((frame, flags)),
+            _ => None,
+        }
+    }
+
+    /// ## Adressraum kopieren (fork)
+    ///
+    /// Legt einen neuen Adressraum an, der dieselben User-Seiten abbildet.
+    /// Beschreibbare Seiten werden in beiden Adressräumen schreibgeschützt und
+    /// mit [COW] markiert; kopiert wird erst beim ersten Schreibzugriff.
+    pub fn fork(&mut self) -> Result<AddressSpace, MapToError<Size4KiB>>
+    {
+        let mut child = AddressSpace::new()?;
+        let mut child_mapper = unsafe { child.mapper() };
+        let parent_flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE;
+
+        let mut result = Ok(());
+        unsafe
+        {
+            for_each_user_entry(self.level_4_frame, |page, entry|
+            {
+                if result.is_err()
+                {
+                    return;
+                }
+                let Ok(frame) = entry.frame() else { return };
+                let mut flags = entry.flags();
+                if flags.contains(PageTableFlags::WRITABLE)
+                {
+                    flags.remove(PageTableFlags::WRITABLE);
+                    flags.insert(COW);
+                    entry.set_flags(flags);
+                }
+
+                let mut allocator = frame_allocator();
+                match child_mapper.map_to_with_table_flags(page, frame, flags, parent_flags, &mut *allocator)
+                {
+                    Ok(flush) =>
+                    {
+                        flush.ignore();
+                        drop(allocator);
+                        share_frame(frame);
+                    }
+                    Err(error) => result = Err(error),
+                }
+            });
+        }
+
+        // Die eigenen Seiten sind jetzt schreibgeschützt.
+        if self.is_active()
+        {
+            tlb::flush_all();
+        }
+        else
+        {
+            self.stale.store(true, Ordering::Relaxed);
+        }
+
+        result.map(|()| child)
+    }
+
+    /// Kopiert ab der virtuellen Adresse `addr` dieses Adressraums nach `buffer`.
+    ///
+    /// Gibt `false` zurück, wenn eine der Seiten nicht abgebildet ist.
+    pub fn read(&self, addr: VirtAddr, buffer: &mut [u8]) -> bool
+    {
+        let mapper = unsafe { mapper_for(self.level_4_frame) };
+        let mut read = 0;
+        while read < buffer.len()
+        {
+            let current = addr + read as u64;
+            let Some(phys) = mapper.translate_addr(current) else { return false };
+            let chunk = ((PAGE_SIZE - current.as_u64() % PAGE_SIZE) as usize).min(buffer.len() - read);
+            unsafe
+            {
+                core::ptr::copy_nonoverlapping(phys_to_virt(phys).as_ptr::<u8>(), buffer[read..].as_mut_ptr(), chunk);
+            }
+            read += chunk;
+        }
+        true
+    }
+
+    /// Kopiert `bytes` an die virtuelle Adresse `addr` dieses Adressraums.
+    ///
+    /// Copy-on-Write-Seiten werden dabei wie bei einem Schreibzugriff aus dem
+    /// Programm zuerst kopiert. Gibt `false` zurück, wenn eine der Seiten nicht
+    /// abgebildet ist oder der Speicher für eine Kopie fehlt.
+    pub fn write(&mut self, addr: VirtAddr, bytes: &[u8]) -> bool
+    {
+        let mut mapper = unsafe { self.mapper() };
+        let mut written = 0;
+        while written < bytes.len()
+        {
+            let current = addr + written as u64;
+            let page = Page::containing_address(current);
+            match unsafe { resolve_cow(&mut mapper, page) }
+            {
+                Ok(true) => self.flush_page(page),
+                Ok(false) => {}
+                Err(_) => return false,
+            }
+            let Some(phys) = mapper.translate_addr(current) else { return false };
+            let chunk = ((PAGE_SIZE - current.as_u64() % PAGE_SIZE) as usize).min(bytes.len() - written);
+            unsafe
+            {
+                core::ptr::copy_nonoverlapping(
+                    bytes[written..].as_ptr(),
+                    phys_to_virt(phys).as_mut_ptr::<u8>(),
+                    chunk,
+                );
+            }
+            written += chunk;
+        }
+        true
+    }
+}
+
+impl Drop for AddressSpace
+{
+    /// Gibt alle Frames und Seitentabellen der User-Hälfte frei. Der Adressraum
+    /// des Kernels wird nie freigegeben.
+    fn drop(&mut self)
+    {
+        if self.is_kernel()
+        {
+            return;
+        }
+        assert!(!self.is_active(), "dropping the active address space");
+
+        // SAFETY: Die Tabelle gehört exklusiv diesem Adressraum.
+        let level_4 = unsafe { &mut *phys_to_virt(self.level_4_frame.start_address()).as_mut_ptr::<PageTable>() };
+        for (index, entry) in level_4.iter_mut().enumerate()
+        {
+            if !is_kernel_entry(index) && entry.flags().contains(PageTableFlags::PRESENT)
+            {
+                unsafe { free_table(entry.frame().expect("huge page in level 4"), 3) };
+                entry.set_unused();
+            }
+        }
+        unsafe { free_frame(self.level_4_frame) };
+
+        if let Some(pcid) = self.pcid
+        {
+            free_pcid(pcid);
+        }
+    }
+}
+
+/// Gibt die Seitentabelle in `frame` der Ebene `level` und alles, worauf sie
+/// verweist, frei. Abgebildete Frames werden nur freigegeben, wenn kein anderer
+/// Adressraum sie mehr verwendet.
+///
+/// # Safety
+///
+/// Die Tabelle darf nur von einem einzigen, nicht aktiven Adressraum verwendet werden.
+unsafe fn free_table(frame: PhysFrame, level: u8)
+{
+    let table = unsafe { &*phys_to_virt(frame.start_address()).as_ptr::<PageTable>() };
+    for entry in table.iter().filter(|entry| entry.flags().contains(PageTableFlags::PRESENT))
+    {
+        let Ok(child) = entry.frame() else { continue };
+        if level == 1
+        {
+            unsafe { release_frame(child) };
+        }
+        else
+        {
+            unsafe { free_table(child, level - 1) };
+        }
+    }
+    unsafe { free_frame(frame) };
+}
+
+/// Ruft `f` für jeden vorhandenen Level-1-Eintrag der User-Hälfte auf.
+///
+/// # Safety
+///
+/// `level_4_frame` muss eine gültige Level-4-Tabelle enthalten, und niemand
+/// sonst darf ihre Tabellen währenddessen verändern.
+unsafe fn for_each_user_entry(level_4_frame: PhysFrame, mut f: impl FnMut(Page, &mut PageTableEntry))
+{
+    let table = |frame: PhysFrame| unsafe { &mut *phys_to_virt(frame.start_address()).as_mut_ptr::<PageTable>() };
+    let present = |entry: &&mut PageTableEntry| entry.flags().contains(PageTableFlags::PRESENT);
+
+    for (i4, e4) in table(level_4_frame).iter_mut().enumerate().filter(|(i, _)| !is_kernel_entry(*i))
+    {
+        let Ok(level_3) = e4.frame() else { continue };
+        for (i3, e3) in table(level_3).iter_mut().enumerate()
+        {
+            let Ok(level_2) = e3.frame() else { continue };
+            for (i2, e2) in table(level_2).iter_mut().enumerate()
+            {
+                let Ok(level_1) = e2.frame() else { continue };
+                for (i1, e1) in table(level_1).iter_mut().enumerate()
+                {
+                    if present(&e1)
+                    {
+                        let index = |i: usize| PageTableIndex::new(i as u16);
+                        f(Page::from_page_table_indices(index(i4), index(i3), index(i2), index(i1)), e1);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Löst den Copy-on-Write-Schutz von `page` auf: Verwendet nur noch dieser
+/// Adressraum den Frame, wird die Seite wieder beschreibbar, sonst wird der
+/// Frame kopiert. Gibt zurück, ob `page` eine Copy-on-Write-Seite war, oder
+/// [MapToError::FrameAllocationFailed], wenn für die Kopie kein Frame frei ist;
+/// die Seite bleibt dann unverändert.
+///
+/// Den TLB-Eintrag muss der Aufrufer invalidieren.
+///
+/// # Safety
+///
+/// `mapper` muss zu einem gültigen Adressraum gehören.
+unsafe fn resolve_cow(mapper: &mut OffsetPageTable, page: Page) -> Result<bool, MapToError<Size4KiB>>
+{
+    let TranslateResult::Mapped { frame: MappedFrame::Size4KiB(frame), flags, .. } = mapper.translate(page.start_address())
+    else
+    {
+        return Ok(false);
+    };
+    if !flags.contains(COW)
+    {
+        return Ok(false);
+    }
+
+    let flags = (flags - COW) | PageTableFlags::WRITABLE;
+    if frame_ref_count(frame) == 1
+    {
+        unsafe { mapper.update_flags(page, flags).expect("page vanished").ignore() };
+        return Ok(true);
+    }
+
+    let copy = frame_allocator().allocate_frame().ok_or(MapToError::FrameAllocationFailed)?;
+    unsafe
+    {
+        core::ptr::copy_nonoverlapping(
+            phys_to_virt(frame.start_address()).as_ptr::<u8>(),
+            phys_to_virt(copy.start_address()).as_mut_ptr::<u8>(),
+            PAGE_SIZE as usize,
+        );
+        mapper.unmap(page).expect("page vanished").1.ignore();
+        mapper.map_to(page, copy, flags, &mut *frame_allocator()).expect("page table vanished").ignore();
+        release_frame(frame);
+    }
+    Ok(true)
+}
+
+/// ## Copy-on-Write-Fault behandeln
+///
+/// Wird von der Page Fault aufgerufen, wenn auf eine schreibgeschützte Seite
+/// geschrieben wurde. Handelt es sich um eine [COW]-Seite des aktiven
+/// Adressraums, wird sie beschreibbar gemacht und `true` zurückgegeben; der
+/// Schreibzugriff kann dann wiederholt werden. Fehlt der Speicher für die
+/// Kopie, muss der Aufrufer den Prozess beenden.
+pub fn handle_cow_fault(addr: VirtAddr) -> Result<bool, MapToError<Size4KiB>>
+{
+    if !(USER_SPACE_START..USER_SPACE_END).contains(&addr.as_u64())
+    {
+        return Ok(false);
+    }
+
+    let page = Page::containing_address(addr);
+    let mut mapper = unsafe { active_mapper() };
+    let resolved = unsafe { resolve_cow(&mut mapper, page)? };
+    if resolved
+    {
+        tlb::flush(page.start_address());
+    }
+    Ok(resolved)
+}
+
+/// Zugriff, den der Kernel auf einen Puffer im User-Space braucht.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserAccess
+{
+    Read,
+    /// Schreiben; Copy-on-Write-Seiten zählen als beschreibbar.
+    Write,
+}
+
+/// ## User-Puffer prüfen
+///
+/// Gibt an, ob `addr..addr + len` vollständig zwischen [USER_SPACE_START] und
+/// [USER_SPACE_END] liegt und jede Seite davon im aktiven Adressraum mit
+/// `PRESENT | USER_ACCESSIBLE` (und bei [UserAccess::Write] beschreibbar)
+/// abgebildet ist. In einem Systemaufruf ist das der Adressraum des
+/// aufrufenden Prozesses.
+pub fn check_user_range(addr: u64, len: u64, access: UserAccess) -> bool
+{
+    let Some(end) = addr.checked_add(len) else { return false };
+    if addr < USER_SPACE_START || end > USER_SPACE_END
+    {
+        return false;
+    }
+    if len == 0
+    {
+        return true;
+    }
+
+    let mapper = unsafe { active_mapper() };
+    let first = Page::<Size4KiB>::containing_address(VirtAddr::new(addr));
+    let last = Page::<Size4KiB>::containing_address(VirtAddr::new(end - 1));
+    Page::range_inclusive(first, last).all(|page| match mapper.translate(page.start_address())
+    {
+        TranslateResult::Mapped { flags, .. } =>
+        {
+            flags.contains(PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE)
+                && (access == UserAccess::Read || flags.intersects(PageTableFlags::WRITABLE | COW))
+        }
+        _ => false,
+    })
+}
+
+/// ## In einen User-Puffer schreiben
+///
+/// Kopiert `bytes` nach `addr` im aktiven Adressraum, nachdem
+/// [check_user_range] den Bereich als beschreibbar bestätigt hat.
+/// Copy-on-Write-Seiten werden vorher aufgelöst. Gibt `false` zurück, wenn
+/// der Bereich ungültig ist oder der Speicher für eine Kopie fehlt; es wird
+/// dann nichts geschrieben.
+pub fn write_user(addr: u64, bytes: &[u8]) -> bool
+{
+    if !check_user_range(addr, bytes.len() as u64, UserAccess::Write)
+    {
+        return false;
+    }
+    if bytes.is_empty()
+    {
+        return true;
+    }
+
+    let mut mapper = unsafe { active_mapper() };
+    let first = Page::<Size4KiB>::containing_address(VirtAddr::new(addr));
+    let last = Page::<Size4KiB>::containing_address(VirtAddr::new(addr + bytes.len() as u64 - 1));
+    for page in Page::range_inclusive(first, last)
+    {
+        match unsafe { resolve_cow(&mut mapper, page) }
+        {
+            Ok(true) => tlb::flush(page.start_address()),
+            Ok(false) => {}
+            Err(_) => return false,
+        }
+    }
+
+    let mut written = 0;
+    while written < bytes.len()
+    {
+        let current = VirtAddr::new(addr + written as u64);
+        let Some(phys) = mapper.translate_addr(current) else { return false };
+        let chunk = ((PAGE_SIZE - current.as_u64() % PAGE_SIZE) as usize).min(bytes.len() - written);
+        unsafe
+        {
+            core::ptr::copy_nonoverlapping(bytes[written..].as_ptr(), phys_to_virt(phys).as_mut_ptr::<u8>(), chunk);
+        }
+        written += chunk;
+    }
+    true
+}
@@ -0,0 +1,127 @@
+//! Puffer für Direct Memory Access.
+//!
+//! Geräte wie virtio oder Netzwerkkarten lesen und schreiben den Speicher
+//! selbst und kennen dabei nur physische Adressen. Puffer aus dem Kernel-Heap
+//! eignen sich dafür nicht: Ihre Seiten liegen physisch verstreut. Ein
+//! [DmaBuffer] besteht dagegen aus aufeinanderfolgenden Frames und wird über
+//! die Abbildung des physischen Speichers angesprochen.
+
+use x86_64::PhysAddr;
+use x86_64::structures::paging::{FrameDeallocator, PhysFrame};
+
+use super::{PAGE_SIZE, frame_allocator, phys_to_virt};
+
+/// ## DmaBuffer
+///
+/// Ein mit Nullen gefüllter, physisch zusammenhängender Speicherbereich. Da
+/// das Gerät jederzeit darauf zugreifen kann, erfolgen alle Zugriffe
+/// `volatile` und auch über `&self`.
+#[derive(Debug)]
+pub struct DmaBuffer
+{
+    start: PhysFrame,
+    frames: usize,
+    len: usize,
+}
+
+impl DmaBuffer
+{
+    /// Ein Puffer mit mindestens `len` Bytes, beginnend an einer Seitengrenze.
+    /// Gibt `None` zurück, wenn kein ausreichend großer zusammenhängender
+    /// Bereich frei ist.
+    pub fn new(len: usize) -> Option<DmaBuffer>
+    {
+        let frames = (len as u64).div_ceil(PAGE_SIZE).max(1) as usize;
+        let start = frame_allocator().allocate_contiguous(frames)?;
+        let buffer = DmaBuffer { start, frames, len };
+        unsafe { core::ptr::write_bytes(buffer.pointer(0), 0, frames * PAGE_SIZE as usize) };
+        Some(buffer)
+    }
+
+    /// Physische Adresse des ersten Bytes, wie sie dem Gerät übergeben wird.
+    pub fn physical_address(&self) -> PhysAddr
+    {
+        self.start.start_address()
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.len == 0
+    }
+
+    fn pointer(&self, offset: usize) -> *mut u8
+    {
+        unsafe { phys_to_virt(self.start.start_address()).as_mut_ptr::<u8>().add(offset) }
+    }
+
+    /// Liest einen Wert an `offset`.
+    ///
+    /// # Panics
+    ///
+    /// Wenn der Wert nicht vollständig im Puffer liegt.
+    pub fn read<T: Copy>(&self, offset: usize) -> T
+    {
+        assert!(offset + size_of::<T>() <= self.len, "DMA read at {} is out of bounds", offset);
+        unsafe { self.pointer(offset).cast::<T>().read_volatile() }
+    }
+
+    /// Schreibt `value` an `offset`.
+    ///
+    /// # Panics
+    ///
+    /// Wenn der Wert nicht vollständig im Puffer liegt.
+    pub fn write<T: Copy>(&self, offset: usize, value: T)
+    {
+        assert!(offset + size_of::<T>() <= self.len, "DMA write at {} is out of bounds", offset);
+        unsafe { self.pointer(offset).cast::<T>().write_volatile(value) };
+    }
+
+    /// Kopiert Bytes ab `offset` nach `buffer`.
+    pub fn read_bytes(&self, offset: usize, buffer: &mut [u8])
+    {
+        assert!(offset + buffer.len() <= self.len, "DMA read at {} is out of bounds", offset);
+        unsafe { core::ptr::copy_nonoverlapping(self.pointer(offset), buffer.as_mut_ptr(), buffer.len()) };
+    }
+
+    /// Kopiert `bytes` an `offset`.
+    pub fn write_bytes(&self, offset: usize, bytes: &[u8])
+    {
+        assert!(offset + bytes.len() <= self.len, "DMA write at {} is out of bounds", offset);
+        unsafe { core::ptr::copy_nonoverlapping(bytes.as_ptr(), self.pointer(offset), bytes.len()) };
+    }
+}
+
+impl Drop for DmaBuffer
+{
+    fn drop(&mut self)
+    {
+        let mut allocator = frame_allocator();
+        for frame in PhysFrame::range(self.start, self.start + self.frames as u64)
+        {
+            unsafe { allocator.deallocate_frame(frame) };
+        }
+    }
+}
+
+#[test_case]
+fn test_allocation_and_release()
+{
+    let before = super::allocated_frames();
+    let buffer = DmaBuffer::new(3 * PAGE_SIZE as usize + 1).expect("no contiguous memory");
+    assert_eq!(super::allocated_frames(), before + 4);
+    assert!(buffer.physical_address().is_aligned(PAGE_SIZE));
+    assert_eq!(buffer.read::<u64>(3 * PAGE_SIZE as usize - 8), 0);
+
+    buffer.write(4096, 0xDEAD_BEEFu32);
+    let mut bytes = [0; 4];
+    buffer.read_bytes(4096, &mut bytes);
+    assert_eq!(u32::from_le_bytes(bytes), 0xDEAD_BEEF);
+
+    drop(buffer);
+    assert_eq!(super::allocated_frames(), before);
+}
@@ -0,0 +1,210 @@
+//! # Modul: net
+//!
+//! Dieses Modul stellt die Schnittstelle für **Netzwerkgeräte** bereit:
+//! Geräte, die ganze Ethernet-Frames senden und empfangen. Darauf baut ein
+//! kleiner **TCP/IP-Stapel** mit Sockets nach dem Vorbild von `std::net` auf.
+//!
+//! # Aufbau
+//!
+//! | Komponente | Aufgabe |
+//! |------------|---------|
+//! | [NetDevice] | Gemeinsame Schnittstelle aller Netzwerkgeräte |
+//! | [MacAddress] | Hardware-Adresse eines Geräts |
+//! | [register], [get], [devices] | Liste der erkannten Geräte mit Namen |
+//! | [virtio] | Treiber für virtio-net über PCI |
+//! | [e1000] | Treiber für Intel-Karten der Reihe 8254x |
+//! | [loopback] | Gerät `lo`, das jeden Frame an sich selbst schickt |
+//! | [stack] | Schnittstellen, Routing und Antrieb des Stapels über [poll] |
+//! | [ethernet], [arp], [ipv4] | Frames, Adressauflösung und IP-Pakete |
+//! | [icmp] | Echo-Antworten und [ping] |
+//! | [udp], [tcp] | [UdpSocket], [TcpListener] und [TcpStream] |
+//! | [dhcp] | Automatische Adressvergabe |
+//!
+//! [init] meldet `lo` mit `127.0.0.1/8` an. Andere Geräte erhalten ihre
+//! Adresse über [configure] oder [dhcp::configure].
+//!
+//! Ein Frame beginnt mit dem Ethernet-Kopf (Ziel, Quelle, Typ; 14 Bytes) und
+//! endet vor der Prüfsumme, die das Gerät selbst anhängt bzw. entfernt.
+
+pub mod arp;
+pub mod dhcp;
+pub mod e1000;
+pub mod ethernet;
+pub mod icmp;
+pub mod ipv4;
+pub mod loopback;
+pub mod stack;
+pub mod tcp;
+pub mod udp;
+pub mod virtio;
+
+pub use icmp::ping;
+pub use ipv4::Ipv4Config;
+pub use stack::{config, configure, init, poll};
+pub use tcp::{TcpListener, TcpStream};
+pub use udp::UdpSocket;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::sync::IrqSpinlock;
+
+/// Größe des Ethernet-Kopfs: Ziel, Quelle und Typ.
+pub const ETHERNET_HEADER_SIZE: usize = 14;
+
+/// Übliche MTU im Ethernet.
+pub const DEFAULT_MTU: usize = 1500;
+
+/// Fehler beim Senden eines Frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetError
+{
+    /// Der Frame ist kürzer als der Ethernet-Kopf oder länger als MTU und Kopf.
+    BadFrameSize,
+    /// Die Verbindung ist getrennt.
+    LinkDown,
+    /// Alle Sendepuffer sind belegt; später erneut versuchen.
+    QueueFull,
+    /// Das Gerät hat einen Fehler gemeldet.
+    Io,
+}
+
+/// Fehler von Sockets und Stapel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SocketError
+{
+    /// Der Port ist bereits belegt.
+    AddressInUse,
+    /// Die Adresse gehört keiner Schnittstelle.
+    AddressNotAvailable,
+    /// Die Verbindung besteht nicht (mehr) oder ist in dieser Richtung
+    /// geschlossen.
+    NotConnected,
+    /// Die Gegenseite hat den Verbindungsaufbau abgelehnt.
+    ConnectionRefused,
+    /// Die Gegenseite hat die Verbindung abgebrochen.
+    ConnectionReset,
+    /// Die Gegenseite antwortet nicht.
+    TimedOut,
+    /// Es gibt keinen Weg zum Ziel.
+    Unreachable,
+    /// Ein Gerät dieses Namens gibt es nicht.
+    NoDevice,
+    /// Die Daten passen nicht in ein Paket.
+    MessageTooLarge,
+    /// Ungültige Adresse oder ungültiger Port.
+    InvalidInput,
+    /// Das Gerät hat das Senden verweigert.
+    Device(NetError),
+}
+
+/// ## MacAddress
+///
+/// Eine 48-Bit-Hardware-Adresse, angezeigt als `52:54:00:12:34:56`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct MacAddress(pub [u8; 6]);
+
+impl MacAddress
+{
+    /// Die Adresse aller Geräte im Netz.
+    pub const BROADCAST: MacAddress = MacAddress([0xFF; 6]);
+
+    /// Gibt an, ob die Adresse an eine Gruppe statt an ein Gerät gerichtet ist.
+    pub fn is_multicast(&self) -> bool
+    {
+        self.0[0] & 1 != 0
+    }
+}
+
+impl fmt::Display for MacAddress
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        let [a, b, c, d, e, g] = self.0;
+        write!(f, "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}", a, b, c, d, e, g)
+    }
+}
+
+/// ## NetDevice
+///
+/// Ein Gerät, das Ethernet-Frames austauscht. [receive](NetDevice::receive)
+/// wartet nicht: Sind keine Frames angekommen, gibt es `None` zurück.
+pub trait NetDevice: Send + Sync
+{
+    /// Name des Geräts, z. B. `"eth0"`.
+    fn name(&self) -> &str;
+
+    /// Hardware-Adresse des Geräts.
+    fn mac_address(&self) -> MacAddress;
+
+    /// Größte Nutzlast eines Frames ohne Ethernet-Kopf.
+    fn mtu(&self) -> usize;
+
+    /// Gibt an, ob eine Verbindung besteht.
+    fn link_up(&self) -> bool;
+
+    /// Sendet `frame` samt Ethernet-Kopf.
+    fn send(&self, frame: &[u8]) -> Result<(), NetError>;
+
+    /// Holt den ältesten empfangenen Frame ab.
+    fn receive(&self) -> Option<Vec<u8>>;
+}
+
+/// Prüft die Länge eines zu sendenden Frames. Für Treiber gedacht.
+pub fn check_frame(device: &dyn NetDevice, frame: &[u8]) -> Result<(), NetError>
+{
+    if frame.len() < ETHERNET_HEADER_SIZE || frame.len() > ETHERNET_HEADER_SIZE + device.mtu()
+    {
+        return Err(NetError::BadFrameSize);
+    }
+    if !device.link_up()
+    {
+        return Err(NetError::LinkDown);
+    }
+    Ok(())
+}
+
+/// Alle angemeldeten Netzwerkgeräte.
+static DEVICES: IrqSpinlock<Vec<Arc<dyn NetDevice>>> = IrqSpinlock::new(Vec::new());
+
+/// Nummer des nächsten Ethernet-Geräts.
+static NEXT_ETHERNET: AtomicUsize = AtomicUsize::new(0);
+
+/// Vergibt den nächsten freien Namen `eth0`, `eth1` usw. Für Treiber gedacht.
+pub fn next_name() -> String
+{
+    format!("eth{}", NEXT_ETHERNET.fetch_add(1, Ordering::Relaxed))
+}
+
+/// Meldet ein erkanntes Gerät an. Ein Gerät mit gleichem Namen wird ersetzt.
+pub fn register(device: Arc<dyn NetDevice>)
+{
+    let mut devices = DEVICES.lock();
+    devices.retain(|existing| existing.name() != device.name());
+    devices.push(device);
+}
+
+/// Das Gerät mit dem Namen `name`.
+pub fn get(name: &str) -> Option<Arc<dyn NetDevice>>
+{
+    DEVICES.lock().iter().find(|device| device.name() == name).cloned()
+}
+
+/// Alle angemeldeten Geräte in der Reihenfolge ihrer Anmeldung.
+pub fn devices() -> Vec<Arc<dyn NetDevice>>
+{
+    DEVICES.lock().clone()
+}
+
+#[test_case]
+fn test_mac_address()
+{
+    let mac = MacAddress([0x52, 0x54, 0x00, 0x12, 0x34, 0x56]);
+    assert_eq!(format!("{}", mac), "52:54:00:12:34:56");
+    assert!(!mac.is_multicast());
+    assert!(MacAddress::BROADCAST.is_multicast());
+}
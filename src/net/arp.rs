@@ -0,0 +1,216 @@
+//! Address Resolution Protocol.
+//!
+//! Bevor ein IPv4-Paket im lokalen Netz verschickt werden kann, muss die MAC
+//! des nächsten Empfängers bekannt sein. Dazu fragt eine ARP-Anfrage an alle
+//! Geräte, wer die Adresse hat; der Besitzer antwortet direkt. Antworten
+//! landen für [ENTRY_LIFETIME_MS] im [ArpCache]. Pakete, deren Ziel noch
+//! unbekannt ist, wartet der Cache ab und gibt sie mit der Antwort frei.
+//!
+//! | Offset | Größe | Inhalt |
+//! |--------|-------|--------|
+//! | 0 | 2 | Hardwaretyp (1 = Ethernet) |
+//! | 2 | 2 | Protokolltyp (`0x0800` = IPv4) |
+//! | 4 | 2 | Adresslängen (6 und 4) |
+//! | 6 | 2 | Operation: 1 Anfrage, 2 Antwort |
+//! | 8 | 10 | MAC und IPv4-Adresse des Absenders |
+//! | 18 | 10 | MAC und IPv4-Adresse des Ziels |
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::net::Ipv4Addr;
+
+use super::MacAddress;
+use super::ethernet::TYPE_IPV4;
+use super::ipv4::Packet;
+
+pub const OPERATION_REQUEST: u16 = 1;
+pub const OPERATION_REPLY: u16 = 2;
+
+/// Länge eines ARP-Pakets für Ethernet und IPv4.
+const PACKET_SIZE: usize = 28;
+
+/// Gültigkeit eines Eintrags.
+pub const ENTRY_LIFETIME_MS: u64 = 60_000;
+
+/// Abstand zwischen zwei Anfragen nach derselben Adresse.
+const RETRY_MS: u64 = 1000;
+
+/// Anzahl der Anfragen, bevor wartende Pakete verworfen werden.
+const MAX_REQUESTS: u32 = 3;
+
+/// Höchste Anzahl wartender Pakete je Adresse.
+const MAX_WAITING: usize = 16;
+
+/// Ein ARP-Paket für Ethernet und IPv4.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArpPacket
+{
+    pub operation: u16,
+    pub sender_mac: MacAddress,
+    pub sender_ip: Ipv4Addr,
+    pub target_mac: MacAddress,
+    pub target_ip: Ipv4Addr,
+}
+
+impl ArpPacket
+{
+    /// Zerlegt ein Paket; andere Hardware- oder Protokolltypen ergeben `None`.
+    pub fn parse(data: &[u8]) -> Option<ArpPacket>
+    {
+        if data.len() < PACKET_SIZE || data[0..6] != [0, 1, 0x08, 0x00, 6, 4]
+        {
+            return None;
+        }
+        let mac = |offset: usize| MacAddress(data[offset..offset + 6].try_into().unwrap());
+        let ip = |offset: usize| Ipv4Addr::new(data[offset], data[offset + 1], data[offset + 2], data[offset + 3]);
+        Some(ArpPacket
+        {
+            operation: u16::from_be_bytes([data[6], data[7]]),
+            sender_mac: mac(8),
+            sender_ip: ip(14),
+            target_mac: mac(18),
+            target_ip: ip(24),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8>
+    {
+        let mut data = Vec::with_capacity(PACKET_SIZE);
+        data.extend_from_slice(&[0, 1]);
+        data.extend_from_slice(&TYPE_IPV4.to_be_bytes());
+        data.extend_from_slice(&[6, 4]);
+        data.extend_from_slice(&self.operation.to_be_bytes());
+        data.extend_from_slice(&self.sender_mac.0);
+        data.extend_from_slice(&self.sender_ip.octets());
+        data.extend_from_slice(&self.target_mac.0);
+        data.extend_from_slice(&self.target_ip.octets());
+        data
+    }
+}
+
+/// Eine Adresse, nach der gefragt wird, mit ihren wartenden Paketen.
+#[derive(Debug)]
+struct Waiting
+{
+    packets: Vec<Packet>,
+    requests: u32,
+    last_request: u64,
+}
+
+/// ## ArpCache
+///
+/// Bekannte Zuordnungen einer Schnittstelle und die Pakete, die auf eine
+/// Antwort warten. Alle Zeiten in Millisekunden seit dem Start.
+#[derive(Debug, Default)]
+pub struct ArpCache
+{
+    entries: BTreeMap<Ipv4Addr, (MacAddress, u64)>,
+    waiting: BTreeMap<Ipv4Addr, Waiting>,
+}
+
+impl ArpCache
+{
+    pub const fn new() -> ArpCache
+    {
+        ArpCache { entries: BTreeMap::new(), waiting: BTreeMap::new() }
+    }
+
+    /// Die MAC zu `address`, falls bekannt und nicht abgelaufen.
+    pub fn lookup(&self, address: Ipv4Addr, now: u64) -> Option<MacAddress>
+    {
+        self.entries.get(&address).filter(|(_, expires)| *expires > now).map(|(mac, _)| *mac)
+    }
+
+    /// Trägt eine Zuordnung ein und gibt die Pakete zurück, die auf sie
+    /// gewartet haben.
+    pub fn insert(&mut self, address: Ipv4Addr, mac: MacAddress, now: u64) -> Vec<Packet>
+    {
+        self.entries.insert(address, (mac, now + ENTRY_LIFETIME_MS));
+        self.waiting.remove(&address).map(|waiting| waiting.packets).unwrap_or_default()
+    }
+
+    /// Lässt `packet` auf die Antwort für `address` warten. Gibt `true`
+    /// zurück, wenn eine neue Anfrage gesendet werden soll.
+    pub fn wait(&mut self, address: Ipv4Addr, packet: Packet, now: u64) -> bool
+    {
+        let waiting = self.waiting.entry(address).or_insert(Waiting { packets: Vec::new(), requests: 0, last_request: 0 });
+        if waiting.packets.len() < MAX_WAITING
+        {
+            waiting.packets.push(packet);
+        }
+        if waiting.requests == 0
+        {
+            waiting.requests = 1;
+            waiting.last_request = now;
+            return true;
+        }
+        false
+    }
+
+    /// Entfernt abgelaufene Einträge und Pakete, auf deren Adresse niemand
+    /// antwortet. Gibt die Adressen zurück, nach denen erneut gefragt werden
+    /// soll.
+    pub fn expire(&mut self, now: u64) -> Vec<Ipv4Addr>
+    {
+        self.entries.retain(|_, (_, expires)| *expires > now);
+        self.waiting.retain(|_, waiting| waiting.requests < MAX_REQUESTS || now < waiting.last_request + RETRY_MS);
+        let mut retry = Vec::new();
+        for (address, waiting) in self.waiting.iter_mut()
+        {
+            if now >= waiting.last_request + RETRY_MS
+            {
+                waiting.requests += 1;
+                waiting.last_request = now;
+                retry.push(*address);
+            }
+        }
+        retry
+    }
+
+    /// Alle gültigen Einträge.
+    pub fn entries(&self, now: u64) -> Vec<(Ipv4Addr, MacAddress)>
+    {
+        self.entries.iter().filter(|(_, (_, expires))| *expires > now).map(|(address, (mac, _))| (*address, *mac)).collect()
+    }
+}
+
+#[test_case]
+fn test_packet_round_trip()
+{
+    let packet = ArpPacket
+    {
+        operation: OPERATION_REQUEST,
+        sender_mac: MacAddress([0x52, 0x54, 0, 0x12, 0x34, 0x56]),
+        sender_ip: Ipv4Addr::new(10, 0, 2, 15),
+        target_mac: MacAddress::default(),
+        target_ip: Ipv4Addr::new(10, 0, 2, 2),
+    };
+    assert_eq!(ArpPacket::parse(&packet.to_bytes()), Some(packet));
+    assert_eq!(ArpPacket::parse(&packet.to_bytes()[..27]), None);
+}
+
+#[test_case]
+fn test_cache_waiting_and_expiry()
+{
+    let address = Ipv4Addr::new(10, 0, 2, 2);
+    let mac = MacAddress([2, 0, 0, 0, 0, 1]);
+    let packet = Packet { source: Ipv4Addr::UNSPECIFIED, destination: address, protocol: 17, payload: Vec::new(), interface: None };
+    let mut cache = ArpCache::new();
+
+    assert!(cache.wait(address, packet.clone(), 0));
+    assert!(!cache.wait(address, packet.clone(), 10));
+    assert!(cache.expire(500).is_empty());
+    assert_eq!(cache.expire(1000), [address]);
+    assert_eq!(cache.insert(address, mac, 1100).len(), 2);
+    assert_eq!(cache.lookup(address, 1100), Some(mac));
+    assert_eq!(cache.lookup(address, 1100 + ENTRY_LIFETIME_MS), None);
+
+    // Ohne Antwort werden die Pakete nach der letzten Anfrage verworfen.
+    let other = Ipv4Addr::new(10, 0, 2, 3);
+    cache.wait(other, packet, 0);
+    assert_eq!(cache.expire(1000), [other]);
+    assert_eq!(cache.expire(2000), [other]);
+    assert!(cache.expire(3000).is_empty());
+    assert!(cache.waiting.is_empty());
+    assert_eq!(cache.entries(1100), [(address, mac)]);
+}
@@ -0,0 +1,238 @@
+//! DHCP-Client.
+//!
+//! [configure] holt für ein Gerät eine Adresse in vier Schritten: DISCOVER an
+//! alle, OFFER eines Servers, REQUEST der angebotenen Adresse und ACK. Das
+//! Ergebnis samt Netzmaske und Gateway wird sofort gesetzt. Die Lease wird
+//! nicht verlängert.
+//!
+//! Eine Nachricht besteht aus dem festen BOOTP-Teil (236 Bytes), dem Magic
+//! Cookie `99.130.83.99` und den Optionen als Typ, Länge und Wert.
+
+use alloc::vec::Vec;
+use core::net::{Ipv4Addr, SocketAddrV4};
+
+use super::ipv4::Ipv4Config;
+use super::udp::UdpSocket;
+use super::{MacAddress, SocketError, stack};
+
+const SERVER_PORT: u16 = 67;
+const CLIENT_PORT: u16 = 68;
+
+/// Länge des BOOTP-Teils ohne Optionen.
+const FIXED_SIZE: usize = 236;
+
+const MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+
+/// Opcodes des BOOTP-Teils.
+const BOOT_REQUEST: u8 = 1;
+const BOOT_REPLY: u8 = 2;
+
+/// Antworten bitte per Broadcast, da noch keine Adresse vorliegt.
+const FLAG_BROADCAST: u16 = 0x8000;
+
+/// Optionen.
+mod option
+{
+    pub const PAD: u8 = 0;
+    pub const SUBNET_MASK: u8 = 1;
+    pub const ROUTER: u8 = 3;
+    pub const REQUESTED_ADDRESS: u8 = 50;
+    pub const MESSAGE_TYPE: u8 = 53;
+    pub const SERVER_ID: u8 = 54;
+    pub const PARAMETER_REQUEST: u8 = 55;
+    pub const END: u8 = 255;
+}
+
+/// Werte der Option [option::MESSAGE_TYPE].
+mod message_type
+{
+    pub const DISCOVER: u8 = 1;
+    pub const OFFER: u8 = 2;
+    pub const REQUEST: u8 = 3;
+    pub const ACK: u8 = 5;
+    pub const NAK: u8 = 6;
+}
+
+/// Wartezeit auf jede Antwort.
+const RESPONSE_TIMEOUT_MS: u64 = 2000;
+
+/// Versuche, bevor [configure] aufgibt.
+const ATTEMPTS: usize = 4;
+
+/// Eine DHCP-Nachricht mit den Feldern, die der Client braucht.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Message
+{
+    operation: u8,
+    transaction: u32,
+    your_address: Ipv4Addr,
+    client_mac: MacAddress,
+    options: Vec<(u8, Vec<u8>)>,
+}
+
+impl Message
+{
+    fn request(transaction: u32, client_mac: MacAddress, kind: u8) -> Message
+    {
+        Message
+        {
+            operation: BOOT_REQUEST,
+            transaction,
+            your_address: Ipv4Addr::UNSPECIFIED,
+            client_mac,
+            options: alloc::vec![(option::MESSAGE_TYPE, alloc::vec![kind]), (option::PARAMETER_REQUEST, alloc::vec![option::SUBNET_MASK, option::ROUTER])],
+        }
+    }
+
+    fn parse(data: &[u8]) -> Option<Message>
+    {
+        if data.len() < FIXED_SIZE + MAGIC_COOKIE.len() || data[FIXED_SIZE..FIXED_SIZE + 4] != MAGIC_COOKIE || data[1] != 1 || data[2] != 6
+        {
+            return None;
+        }
+        let mut options = Vec::new();
+        let mut rest = &data[FIXED_SIZE + 4..];
+        while let [code, tail @ ..] = rest
+        {
+            match *code
+            {
+                option::END => break,
+                option::PAD => rest = tail,
+                code =>
+                {
+                    let (&len, tail) = tail.split_first()?;
+                    let value = tail.get(..usize::from(len))?;
+                    options.push((code, value.to_vec()));
+                    rest = &tail[usize::from(len)..];
+                }
+            }
+        }
+        Some(Message
+        {
+            operation: data[0],
+            transaction: u32::from_be_bytes(data[4..8].try_into().unwrap()),
+            your_address: Ipv4Addr::new(data[16], data[17], data[18], data[19]),
+            client_mac: MacAddress(data[28..34].try_into().unwrap()),
+            options,
+        })
+    }
+
+    fn to_bytes(&self) -> Vec<u8>
+    {
+        let mut data = alloc::vec![0; FIXED_SIZE];
+        data[..4].copy_from_slice(&[self.operation, 1, 6, 0]);
+        data[4..8].copy_from_slice(&self.transaction.to_be_bytes());
+        data[10..12].copy_from_slice(&FLAG_BROADCAST.to_be_bytes());
+        data[16..20].copy_from_slice(&self.your_address.octets());
+        data[28..34].copy_from_slice(&self.client_mac.0);
+        data.extend_from_slice(&MAGIC_COOKIE);
+        for (code, value) in &self.options
+        {
+            data.push(*code);
+            data.push(value.len() as u8);
+            data.extend_from_slice(value);
+        }
+        data.push(option::END);
+        data
+    }
+
+    fn option(&self, code: u8) -> Option<&[u8]>
+    {
+        self.options.iter().find(|(other, _)| *other == code).map(|(_, value)| value.as_slice())
+    }
+
+    fn address_option(&self, code: u8) -> Option<Ipv4Addr>
+    {
+        let value: [u8; 4] = self.option(code)?.get(..4)?.try_into().ok()?;
+        Some(Ipv4Addr::from(value))
+    }
+
+    fn message_type(&self) -> Option<u8>
+    {
+        self.option(option::MESSAGE_TYPE)?.first().copied()
+    }
+}
+
+/// Wartet auf eine Antwort zu `request` mit einem der Typen `kinds`.
+fn receive(socket: &UdpSocket, request: &Message, kinds: &[u8]) -> Result<Message, SocketError>
+{
+    let mut buffer = [0; 1024];
+    loop
+    {
+        let (len, _) = socket.recv_from(&mut buffer)?;
+        let Some(reply) = Message::parse(&buffer[..len])
+        else
+        {
+            continue;
+        };
+        if reply.operation == BOOT_REPLY
+            && reply.transaction == request.transaction
+            && reply.client_mac == request.client_mac
+            && reply.message_type().is_some_and(|kind| kinds.contains(&kind))
+        {
+            return Ok(reply);
+        }
+    }
+}
+
+/// Holt per DHCP eine Adresse für das Gerät `name`, setzt sie und gibt sie
+/// zurück.
+pub fn configure(name: &str) -> Result<Ipv4Config, SocketError>
+{
+    let mac = super::get(name).ok_or(SocketError::NoDevice)?.mac_address();
+    let mut socket = UdpSocket::bind(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, CLIENT_PORT))?;
+    socket.bind_device(name)?;
+    socket.set_read_timeout(Some(RESPONSE_TIMEOUT_MS));
+    let server = SocketAddrV4::new(Ipv4Addr::BROADCAST, SERVER_PORT);
+    let [_, _, a, b, c, d] = mac.0;
+    let transaction = u32::from_be_bytes([a, b, c, d]) ^ stack::now() as u32;
+
+    for attempt in 0..ATTEMPTS as u32
+    {
+        let transaction = transaction.wrapping_add(attempt);
+        let discover = Message::request(transaction, mac, message_type::DISCOVER);
+        socket.send_to(&discover.to_bytes(), server)?;
+        let offer = match receive(&socket, &discover, &[message_type::OFFER])
+        {
+            Ok(offer) => offer,
+            Err(SocketError::TimedOut) => continue,
+            Err(error) => return Err(error),
+        };
+
+        let mut request = Message::request(transaction, mac, message_type::REQUEST);
+        request.options.push((option::REQUESTED_ADDRESS, offer.your_address.octets().to_vec()));
+        if let Some(id) = offer.option(option::SERVER_ID)
+        {
+            request.options.push((option::SERVER_ID, id.to_vec()));
+        }
+        socket.send_to(&request.to_bytes(), server)?;
+        let ack = match receive(&socket, &request, &[message_type::ACK, message_type::NAK])
+        {
+            Ok(ack) if ack.message_type() == Some(message_type::ACK) => ack,
+            Ok(_) | Err(SocketError::TimedOut) => continue,
+            Err(error) => return Err(error),
+        };
+
+        // Ohne Netzmaske gilt ein /24-Netz.
+        let prefix_len = ack.address_option(option::SUBNET_MASK).map_or(24, |mask| mask.to_bits().leading_ones() as u8);
+        let config = Ipv4Config::new(ack.your_address, prefix_len, ack.address_option(option::ROUTER));
+        stack::configure(name, Some(config))?;
+        return Ok(config);
+    }
+    Err(SocketError::TimedOut)
+}
+
+#[test_case]
+fn test_message_round_trip()
+{
+    let mac = MacAddress([0x52, 0x54, 0, 0x12, 0x34, 0x56]);
+    let mut message = Message::request(0x1234_5678, mac, message_type::DISCOVER);
+    message.options.push((option::ROUTER, alloc::vec![10, 0, 2, 2]));
+    let data = message.to_bytes();
+    assert_eq!(data.len(), FIXED_SIZE + 4 + 3 + 4 + 6 + 1);
+    let parsed = Message::parse(&data).unwrap();
+    assert_eq!(parsed, message);
+    assert_eq!(parsed.message_type(), Some(message_type::DISCOVER));
+    assert_eq!(parsed.address_option(option::ROUTER), Some(Ipv4Addr::new(10, 0, 2, 2)));
+    assert_eq!(Message::parse(&data[..FIXED_SIZE]), None);
+}
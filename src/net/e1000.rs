@@ -0,0 +1,559 @@
+//! Treiber für Intel-Netzwerkkarten der Reihe 8254x (e1000).
+//!
+//! Die Karte wird über Register im Speicher-BAR 0 gesteuert. Gesendet und
+//! empfangen wird über zwei Ringe aus Deskriptoren zu 16 Bytes, die jeweils
+//! auf einen Puffer von 2 KiB zeigen:
+//!
+//! | Ring | Kopf (`RDH`/`TDH`) | Ende (`RDT`/`TDT`) |
+//! |------|--------------------|--------------------|
+//! | Empfang | nächster Deskriptor, den die Karte füllt | letzter Deskriptor, den die Karte füllen darf |
+//! | Senden | nächster Deskriptor, den die Karte sendet | hinter dem letzten zu sendenden Deskriptor |
+//!
+//! Die Karte setzt in bearbeiteten Deskriptoren das Bit `DD` (Descriptor
+//! Done). Der Treiber holt empfangene Frames in Ringreihenfolge ab und gibt
+//! ihre Deskriptoren durch Weiterschieben von `RDT` sofort zurück; Sendeplätze
+//! werden beim nächsten Senden wieder frei, sobald ihr `DD` gesetzt ist.
+//!
+//! Interrupts kommen über MSI oder eine geteilte IRQ-Leitung (siehe
+//! [crate::interrupts::irq]). Das Lesen von `ICR` liefert die Ursachen und
+//! quittiert sie zugleich.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+use x86_64::{PhysAddr, VirtAddr};
+
+use super::{DEFAULT_MTU, MacAddress, NetDevice, NetError, check_frame};
+use crate::interrupts::PIC_1_OFFSET;
+use crate::interrupts::irq::{self, IrqHandler};
+use crate::interrupts::vector::Handler;
+use crate::memory::{self, dma::DmaBuffer};
+use crate::pci::msi::Msi;
+use crate::pci::{self, Bar, DeviceId, PciDevice, PciDriver, PciError};
+use crate::sync::{IrqSpinlock, Once};
+
+/// Hersteller Intel.
+const VENDOR_INTEL: u16 = 0x8086;
+
+/// Register relativ zu BAR 0.
+mod register
+{
+    pub const CTRL: u32 = 0x0000;
+    pub const STATUS: u32 = 0x0008;
+    pub const EERD: u32 = 0x0014;
+    pub const ICR: u32 = 0x00C0;
+    pub const IMS: u32 = 0x00D0;
+    pub const IMC: u32 = 0x00D8;
+    pub const RCTL: u32 = 0x0100;
+    pub const TCTL: u32 = 0x0400;
+    pub const TIPG: u32 = 0x0410;
+    pub const RDBAL: u32 = 0x2800;
+    pub const RDBAH: u32 = 0x2804;
+    pub const RDLEN: u32 = 0x2808;
+    pub const RDH: u32 = 0x2810;
+    pub const RDT: u32 = 0x2818;
+    pub const TDBAL: u32 = 0x3800;
+    pub const TDBAH: u32 = 0x3804;
+    pub const TDLEN: u32 = 0x3808;
+    pub const TDH: u32 = 0x3810;
+    pub const TDT: u32 = 0x3818;
+    pub const MTA: u32 = 0x5200;
+    pub const RAL: u32 = 0x5400;
+    pub const RAH: u32 = 0x5404;
+}
+
+/// Bits in `CTRL`.
+mod ctrl
+{
+    pub const LRST: u32 = 1 << 3;
+    pub const ASDE: u32 = 1 << 5;
+    pub const SLU: u32 = 1 << 6;
+    pub const ILOS: u32 = 1 << 7;
+    pub const RST: u32 = 1 << 26;
+    pub const PHY_RST: u32 = 1 << 31;
+}
+
+/// Bit 1 in `STATUS`: Verbindung besteht.
+const STATUS_LU: u32 = 1 << 1;
+
+/// Bits in `EERD`.
+const EERD_START: u32 = 1 << 0;
+const EERD_DONE: u32 = 1 << 4;
+
+/// Bit 31 in `RAH`: Adresse gültig.
+const RAH_AV: u32 = 1 << 31;
+
+/// Bits in `RCTL`; die Puffergröße 2048 ist die Voreinstellung.
+mod rctl
+{
+    pub const EN: u32 = 1 << 1;
+    pub const BAM: u32 = 1 << 15;
+    pub const SECRC: u32 = 1 << 26;
+}
+
+/// Bits in `TCTL`.
+mod tctl
+{
+    pub const EN: u32 = 1 << 1;
+    pub const PSP: u32 = 1 << 3;
+    pub const CT: u32 = 0x10 << 4;
+    pub const COLD: u32 = 0x40 << 12;
+}
+
+/// Übliche Abstände zwischen Frames für `TIPG`.
+const TIPG_DEFAULT: u32 = 0x0060_200A;
+
+/// Interrupt-Ursachen in `ICR`, `IMS` und `IMC`.
+pub mod cause
+{
+    /// Ein Sendedeskriptor wurde zurückgeschrieben.
+    pub const TXDW: u32 = 1 << 0;
+    /// Der Verbindungsstatus hat sich geändert.
+    pub const LSC: u32 = 1 << 2;
+    /// Kaum noch freie Empfangsdeskriptoren.
+    pub const RXDMT0: u32 = 1 << 4;
+    /// Frames gingen verloren, weil kein Empfangsdeskriptor frei war.
+    pub const RXO: u32 = 1 << 6;
+    /// Ein Frame wurde empfangen.
+    pub const RXT0: u32 = 1 << 7;
+}
+
+/// Bits im Status eines Deskriptors.
+const DESC_DD: u8 = 1 << 0;
+
+/// Befehlsbits eines Sendedeskriptors: Ende des Frames, Prüfsumme anhängen,
+/// Status zurückschreiben.
+const CMD_EOP: u8 = 1 << 0;
+const CMD_IFCS: u8 = 1 << 1;
+const CMD_RS: u8 = 1 << 3;
+
+/// Anzahl der Deskriptoren je Ring; `RDLEN` und `TDLEN` müssen ein
+/// Vielfaches von 128 Bytes sein.
+const RING_SIZE: usize = 32;
+const DESCRIPTOR_SIZE: usize = 16;
+
+/// Größe eines Puffers.
+const BUFFER_SIZE: usize = 2048;
+
+/// Anzahl der Abfragen, bis ein Zurücksetzen oder Lesen des EEPROMs
+/// abgeschlossen sein muss.
+const POLL_LIMIT: usize = 100_000;
+
+/// Zugriff auf die Register im abgebildeten BAR 0.
+#[derive(Debug, Clone, Copy)]
+struct Registers
+{
+    base: VirtAddr,
+}
+
+impl Registers
+{
+    fn read(&self, register: u32) -> u32
+    {
+        unsafe { (self.base + u64::from(register)).as_ptr::<u32>().read_volatile() }
+    }
+
+    fn write(&self, register: u32, value: u32)
+    {
+        unsafe { (self.base + u64::from(register)).as_mut_ptr::<u32>().write_volatile(value) }
+    }
+
+    /// Setzt die Karte zurück und sperrt alle Interrupts.
+    fn reset(&self)
+    {
+        self.write(register::IMC, u32::MAX);
+        self.write(register::CTRL, self.read(register::CTRL) | ctrl::RST);
+        for _ in 0..POLL_LIMIT
+        {
+            if self.read(register::CTRL) & ctrl::RST == 0
+            {
+                break;
+            }
+            core::hint::spin_loop();
+        }
+        self.write(register::IMC, u32::MAX);
+        self.read(register::ICR);
+    }
+
+    /// Liest das Wort `address` aus dem EEPROM.
+    fn read_eeprom(&self, address: u8) -> Option<u16>
+    {
+        self.write(register::EERD, u32::from(address) << 8 | EERD_START);
+        for _ in 0..POLL_LIMIT
+        {
+            let value = self.read(register::EERD);
+            if value & EERD_DONE != 0
+            {
+                return Some((value >> 16) as u16);
+            }
+            core::hint::spin_loop();
+        }
+        None
+    }
+
+    /// Die MAC-Adresse aus den Wörtern 0–2 des EEPROMs oder, falls es keines
+    /// gibt, aus dem ersten Empfangsadressregister.
+    fn read_mac(&self) -> MacAddress
+    {
+        let mut mac = [0; 6];
+        let words = [0, 1, 2].map(|address| self.read_eeprom(address));
+        if words.iter().all(Option::is_some)
+        {
+            for (index, word) in words.into_iter().flatten().enumerate()
+            {
+                mac[2 * index..2 * index + 2].copy_from_slice(&word.to_le_bytes());
+            }
+        }
+        else
+        {
+            mac[..4].copy_from_slice(&self.read(register::RAL).to_le_bytes());
+            mac[4..].copy_from_slice(&self.read(register::RAH).to_le_bytes()[..2]);
+        }
+        MacAddress(mac)
+    }
+}
+
+/// Ein Ring aus Deskriptoren mit seinen Puffern.
+struct Ring
+{
+    descriptors: DmaBuffer,
+    buffers: DmaBuffer,
+}
+
+impl Ring
+{
+    /// Legt den Ring an und trägt in jeden Deskriptor seinen Puffer ein.
+    fn new() -> Option<Ring>
+    {
+        let ring = Ring { descriptors: DmaBuffer::new(RING_SIZE * DESCRIPTOR_SIZE)?, buffers: DmaBuffer::new(RING_SIZE * BUFFER_SIZE)? };
+        for index in 0..RING_SIZE
+        {
+            ring.descriptors.write(index * DESCRIPTOR_SIZE, ring.buffer_address(index).as_u64());
+        }
+        Some(ring)
+    }
+
+    fn buffer_address(&self, index: usize) -> PhysAddr
+    {
+        self.buffers.physical_address() + (index * BUFFER_SIZE) as u64
+    }
+
+    /// Das Statusbyte des Deskriptors `index`.
+    fn status(&self, index: usize) -> u8
+    {
+        self.descriptors.read(index * DESCRIPTOR_SIZE + 12)
+    }
+
+    /// Trägt Basis und Länge des Rings in die Register `low`, `high` und
+    /// `length` ein.
+    fn program(&self, registers: &Registers, [low, high, length]: [u32; 3])
+    {
+        let address = self.descriptors.physical_address().as_u64();
+        registers.write(low, address as u32);
+        registers.write(high, (address >> 32) as u32);
+        registers.write(length, (RING_SIZE * DESCRIPTOR_SIZE) as u32);
+    }
+}
+
+/// Die beiden Ringe und ihre Positionen.
+struct Rings
+{
+    receive: Ring,
+    transmit: Ring,
+    /// Nächster Empfangsdeskriptor, den die Karte füllt.
+    next_receive: usize,
+    /// Nächster freier Sendedeskriptor (entspricht `TDT`).
+    transmit_tail: usize,
+    /// Ältester noch nicht versendeter Sendedeskriptor.
+    transmit_clean: usize,
+}
+
+/// Zähler der Interrupt-Ursachen.
+#[derive(Debug, Default)]
+struct Counters
+{
+    interrupts: AtomicU64,
+    link_changes: AtomicU64,
+    overruns: AtomicU64,
+}
+
+/// Wie die Karte ihre Interrupts meldet.
+enum Interrupt
+{
+    Msi(Msi),
+    Line(IrqHandler),
+    None,
+}
+
+/// ## E1000
+///
+/// Eine Netzwerkkarte der Reihe 8254x.
+pub struct E1000
+{
+    name: String,
+    registers: Registers,
+    mac: MacAddress,
+    rings: IrqSpinlock<Rings>,
+    counters: Arc<Counters>,
+    interrupt: Interrupt,
+}
+
+impl E1000
+{
+    /// Setzt die Karte zurück, liest die MAC-Adresse und richtet beide Ringe
+    /// und die Interrupts ein.
+    fn new(device: &Arc<PciDevice>) -> Result<E1000, PciError>
+    {
+        let Some(Bar::Memory { address, size, .. }) = device.bars[0]
+        else
+        {
+            return Err(PciError::BadBar);
+        };
+        let base = memory::map_mmio(PhysAddr::new(address), size).map_err(|_| PciError::BadBar)?;
+        device.enable(true);
+        let registers = Registers { base };
+        registers.reset();
+
+        let control = registers.read(register::CTRL) & !(ctrl::LRST | ctrl::PHY_RST | ctrl::ILOS);
+        registers.write(register::CTRL, control | ctrl::SLU | ctrl::ASDE);
+
+        let mac = registers.read_mac();
+        let [a, b, c, d, e, f] = mac.0;
+        registers.write(register::RAL, u32::from_le_bytes([a, b, c, d]));
+        registers.write(register::RAH, u32::from(u16::from_le_bytes([e, f])) | RAH_AV);
+        for index in 0..128
+        {
+            registers.write(register::MTA + 4 * index, 0);
+        }
+
+        let receive = Ring::new().ok_or(PciError::Io)?;
+        receive.program(&registers, [register::RDBAL, register::RDBAH, register::RDLEN]);
+        registers.write(register::RDH, 0);
+        registers.write(register::RDT, (RING_SIZE - 1) as u32);
+        registers.write(register::RCTL, rctl::EN | rctl::BAM | rctl::SECRC);
+
+        let transmit = Ring::new().ok_or(PciError::Io)?;
+        transmit.program(&registers, [register::TDBAL, register::TDBAH, register::TDLEN]);
+        registers.write(register::TDH, 0);
+        registers.write(register::TDT, 0);
+        registers.write(register::TIPG, TIPG_DEFAULT);
+        registers.write(register::TCTL, tctl::EN | tctl::PSP | tctl::CT | tctl::COLD);
+
+        let counters = Arc::new(Counters::default());
+        let interrupt = Self::setup_interrupt(device, registers, &counters);
+        if !matches!(interrupt, Interrupt::None)
+        {
+            registers.write(register::IMS, cause::TXDW | cause::LSC | cause::RXDMT0 | cause::RXO | cause::RXT0);
+        }
+
+        Ok(E1000
+        {
+            name: super::next_name(),
+            registers,
+            mac,
+            rings: IrqSpinlock::new(Rings { receive, transmit, next_receive: 0, transmit_tail: 0, transmit_clean: 0 }),
+            counters,
+            interrupt,
+        })
+    }
+
+    /// Meldet den Handler über MSI oder, ohne MSI, an der IRQ-Leitung der
+    /// Karte an. Ohne beides wird nur abgefragt.
+    fn setup_interrupt(device: &Arc<PciDevice>, registers: Registers, counters: &Arc<Counters>) -> Interrupt
+    {
+        let handler = ||
+        {
+            let counters = counters.clone();
+            Box::new(move || handle_interrupt(registers, &counters)) as Handler
+        };
+        if let Ok(msi) = Msi::enable(device, handler())
+        {
+            return Interrupt::Msi(msi);
+        }
+        if device.interrupt_pin == 0
+        {
+            return Interrupt::None;
+        }
+        irq::register(device.interrupt_line, handler()).map_or(Interrupt::None, Interrupt::Line)
+    }
+
+    /// Anzahl der bisher empfangenen Interrupts der Karte.
+    pub fn interrupt_count(&self) -> u64
+    {
+        self.counters.interrupts.load(Ordering::Relaxed)
+    }
+
+    /// Anzahl der gemeldeten Änderungen des Verbindungsstatus.
+    pub fn link_changes(&self) -> u64
+    {
+        self.counters.link_changes.load(Ordering::Relaxed)
+    }
+
+    /// Anzahl der Überläufe des Empfangsrings.
+    pub fn overruns(&self) -> u64
+    {
+        self.counters.overruns.load(Ordering::Relaxed)
+    }
+
+    /// Der Vektor, auf dem die Karte ihre Interrupts meldet. Ohne MSI und
+    /// IRQ-Leitung gibt es keinen; dann wird nur abgefragt.
+    pub fn interrupt_vector(&self) -> Option<u8>
+    {
+        match &self.interrupt
+        {
+            Interrupt::Msi(msi) => Some(msi.vector()),
+            Interrupt::Line(line) => Some(PIC_1_OFFSET + line.irq()),
+            Interrupt::None => None,
+        }
+    }
+}
+
+/// Liest und quittiert die Ursachen eines Interrupts. Auf einer geteilten
+/// Leitung kann der Interrupt auch von einem anderen Gerät stammen; dann ist
+/// `ICR` leer.
+fn handle_interrupt(registers: Registers, counters: &Counters)
+{
+    let causes = registers.read(register::ICR);
+    if causes == 0
+    {
+        return;
+    }
+    counters.interrupts.fetch_add(1, Ordering::Relaxed);
+    if causes & cause::LSC != 0
+    {
+        counters.link_changes.fetch_add(1, Ordering::Relaxed);
+    }
+    if causes & cause::RXO != 0
+    {
+        counters.overruns.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl NetDevice for E1000
+{
+    fn name(&self) -> &str
+    {
+        &self.name
+    }
+
+    fn mac_address(&self) -> MacAddress
+    {
+        self.mac
+    }
+
+    fn mtu(&self) -> usize
+    {
+        DEFAULT_MTU
+    }
+
+    fn link_up(&self) -> bool
+    {
+        self.registers.read(register::STATUS) & STATUS_LU != 0
+    }
+
+    fn send(&self, frame: &[u8]) -> Result<(), NetError>
+    {
+        check_frame(self, frame)?;
+        let mut rings = self.rings.lock();
+        while rings.transmit_clean != rings.transmit_tail && rings.transmit.status(rings.transmit_clean) & DESC_DD != 0
+        {
+            rings.transmit_clean = (rings.transmit_clean + 1) % RING_SIZE;
+        }
+        let index = rings.transmit_tail;
+        let next = (index + 1) % RING_SIZE;
+        if next == rings.transmit_clean
+        {
+            return Err(NetError::QueueFull);
+        }
+
+        let ring = &rings.transmit;
+        ring.buffers.write_bytes(index * BUFFER_SIZE, frame);
+        let offset = index * DESCRIPTOR_SIZE;
+        ring.descriptors.write(offset + 8, frame.len() as u16);
+        ring.descriptors.write(offset + 11, CMD_EOP | CMD_IFCS | CMD_RS);
+        ring.descriptors.write(offset + 12, 0u8);
+        rings.transmit_tail = next;
+        self.registers.write(register::TDT, next as u32);
+        Ok(())
+    }
+
+    fn receive(&self) -> Option<Vec<u8>>
+    {
+        let mut rings = self.rings.lock();
+        let index = rings.next_receive;
+        if rings.receive.status(index) & DESC_DD == 0
+        {
+            return None;
+        }
+        let offset = index * DESCRIPTOR_SIZE;
+        let len = usize::from(rings.receive.descriptors.read::<u16>(offset + 8)).min(BUFFER_SIZE);
+        let mut frame = vec![0; len];
+        rings.receive.buffers.read_bytes(index * BUFFER_SIZE, &mut frame);
+
+        // Den Deskriptor an die Karte zurückgeben.
+        rings.receive.descriptors.write(offset + 12, 0u8);
+        rings.next_receive = (index + 1) % RING_SIZE;
+        self.registers.write(register::RDT, index as u32);
+        Some(frame)
+    }
+}
+
+impl Drop for E1000
+{
+    fn drop(&mut self)
+    {
+        self.registers.reset();
+    }
+}
+
+/// Alle erkannten e1000-Karten.
+static DEVICES: IrqSpinlock<Vec<Arc<E1000>>> = IrqSpinlock::new(Vec::new());
+
+/// ## E1000Driver
+///
+/// Übernimmt die von QEMU nachgebildeten Karten 82540EM und 82545EM und
+/// meldet sie bei [crate::net] an.
+struct E1000Driver;
+
+impl PciDriver for E1000Driver
+{
+    fn name(&self) -> &str
+    {
+        "e1000"
+    }
+
+    fn ids(&self) -> &[DeviceId]
+    {
+        const IDS: [DeviceId; 2] = [
+            DeviceId::Device { vendor: VENDOR_INTEL, device: 0x100E },
+            DeviceId::Device { vendor: VENDOR_INTEL, device: 0x100F },
+        ];
+        &IDS
+    }
+
+    fn probe(&self, device: &Arc<PciDevice>) -> Result<(), PciError>
+    {
+        let device = Arc::new(E1000::new(device)?);
+        super::register(device.clone());
+        DEVICES.lock().push(device);
+        Ok(())
+    }
+}
+
+/// ## Netzwerkkarten erkennen
+///
+/// Meldet beim ersten Aufruf den Treiber bei [crate::pci] an. Er übernimmt
+/// alle bereits gefundenen und alle später von [pci::init] gefundenen
+/// e1000-Karten. Gibt die bisher erkannten Karten zurück.
+pub fn init() -> Vec<Arc<E1000>>
+{
+    static DRIVER: Once<()> = Once::new();
+    DRIVER.call_once(||
+    {
+        pci::register_driver(Arc::new(E1000Driver));
+    });
+    DEVICES.lock().clone()
+}
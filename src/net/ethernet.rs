@@ -0,0 +1,63 @@
+//! Ethernet-Frames.
+//!
+//! | Offset | Größe | Inhalt |
+//! |--------|-------|--------|
+//! | 0 | 6 | Ziel-MAC |
+//! | 6 | 6 | Quell-MAC |
+//! | 12 | 2 | Typ der Nutzlast (Big Endian) |
+//! | 14 | … | Nutzlast |
+
+use alloc::vec::Vec;
+
+use super::{ETHERNET_HEADER_SIZE, MacAddress};
+
+/// Nutzlast IPv4.
+pub const TYPE_IPV4: u16 = 0x0800;
+
+/// Nutzlast ARP.
+pub const TYPE_ARP: u16 = 0x0806;
+
+/// Kleinste Länge eines Frames ohne Prüfsumme; kürzere werden aufgefüllt.
+const MIN_FRAME_SIZE: usize = 60;
+
+/// Ein empfangener Frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame<'a>
+{
+    pub destination: MacAddress,
+    pub source: MacAddress,
+    pub kind: u16,
+    pub payload: &'a [u8],
+}
+
+impl Frame<'_>
+{
+    /// Zerlegt einen Frame; zu kurze ergeben `None`.
+    pub fn parse(frame: &[u8]) -> Option<Frame<'_>>
+    {
+        if frame.len() < ETHERNET_HEADER_SIZE
+        {
+            return None;
+        }
+        let mac = |offset: usize| MacAddress(frame[offset..offset + 6].try_into().unwrap());
+        Some(Frame
+        {
+            destination: mac(0),
+            source: mac(6),
+            kind: u16::from_be_bytes([frame[12], frame[13]]),
+            payload: &frame[ETHERNET_HEADER_SIZE..],
+        })
+    }
+}
+
+/// Setzt einen Frame zusammen und füllt ihn auf die Mindestlänge auf.
+pub fn build(destination: MacAddress, source: MacAddress, kind: u16, payload: &[u8]) -> Vec<u8>
+{
+    let mut frame = Vec::with_capacity((ETHERNET_HEADER_SIZE + payload.len()).max(MIN_FRAME_SIZE));
+    frame.extend_from_slice(&destination.0);
+    frame.extend_from_slice(&source.0);
+    frame.extend_from_slice(&kind.to_be_bytes());
+    frame.extend_from_slice(payload);
+    frame.resize(frame.len().max(MIN_FRAME_SIZE), 0);
+    frame
+}
@@ -0,0 +1,174 @@
+//! Internet Control Message Protocol.
+//!
+//! Unterstützt wird nur Echo („Ping“): Anfragen an eigene Adressen
+//! beantwortet der Stapel selbst, [ping] sendet eine Anfrage und wartet auf
+//! die Antwort.
+//!
+//! | Offset | Größe | Inhalt |
+//! |--------|-------|--------|
+//! | 0 | 1 | Typ: 8 Anfrage, 0 Antwort |
+//! | 1 | 1 | Code (0) |
+//! | 2 | 2 | Prüfsumme über die ganze Nachricht |
+//! | 4 | 2 | Kennung |
+//! | 6 | 2 | Folgenummer |
+//! | 8 | … | Daten, in der Antwort unverändert |
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+use core::net::Ipv4Addr;
+
+use super::ipv4::{self, Packet, protocol};
+use super::{SocketError, stack};
+
+pub const TYPE_ECHO_REPLY: u8 = 0;
+pub const TYPE_ECHO_REQUEST: u8 = 8;
+
+/// Länge des Kopfs einer Echo-Nachricht.
+const HEADER_SIZE: usize = 8;
+
+/// Kennung aller Anfragen dieses Systems.
+const IDENTIFIER: u16 = 0x534F;
+
+/// Daten einer Anfrage.
+const PAYLOAD: &[u8] = b"abcdefghijklmnopqrstuvwabcdefghi";
+
+/// Eine Echo-Nachricht.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Echo<'a>
+{
+    pub kind: u8,
+    pub identifier: u16,
+    pub sequence: u16,
+    pub data: &'a [u8],
+}
+
+impl Echo<'_>
+{
+    /// Zerlegt eine Echo-Nachricht; andere Typen und falsche Prüfsummen
+    /// ergeben `None`.
+    pub fn parse(data: &[u8]) -> Option<Echo<'_>>
+    {
+        if data.len() < HEADER_SIZE || data[1] != 0 || ipv4::checksum(0, data) != 0
+        {
+            return None;
+        }
+        if data[0] != TYPE_ECHO_REQUEST && data[0] != TYPE_ECHO_REPLY
+        {
+            return None;
+        }
+        Some(Echo
+        {
+            kind: data[0],
+            identifier: u16::from_be_bytes([data[4], data[5]]),
+            sequence: u16::from_be_bytes([data[6], data[7]]),
+            data: &data[HEADER_SIZE..],
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8>
+    {
+        let mut data = Vec::with_capacity(HEADER_SIZE + self.data.len());
+        data.extend_from_slice(&[self.kind, 0, 0, 0]);
+        data.extend_from_slice(&self.identifier.to_be_bytes());
+        data.extend_from_slice(&self.sequence.to_be_bytes());
+        data.extend_from_slice(self.data);
+        let sum = ipv4::checksum(0, &data);
+        data[2..4].copy_from_slice(&sum.to_be_bytes());
+        data
+    }
+}
+
+/// Laufende Anfragen von [ping] und die Ankunftszeit ihrer Antworten.
+#[derive(Debug, Default)]
+pub(super) struct Pings
+{
+    next_sequence: u16,
+    outstanding: BTreeSet<u16>,
+    replies: BTreeMap<u16, u64>,
+}
+
+impl Pings
+{
+    pub(super) const fn new() -> Pings
+    {
+        Pings { next_sequence: 0, outstanding: BTreeSet::new(), replies: BTreeMap::new() }
+    }
+
+    /// Beantwortet Anfragen und vermerkt Antworten auf eigene Anfragen.
+    pub(super) fn receive(&mut self, packet: &Packet, now: u64, out: &mut Vec<Packet>)
+    {
+        let Some(echo) = Echo::parse(&packet.payload)
+        else
+        {
+            return;
+        };
+        if echo.kind == TYPE_ECHO_REQUEST
+        {
+            // Anfragen an Broadcast-Adressen bleiben unbeantwortet.
+            if packet.destination == Ipv4Addr::BROADCAST || packet.source.is_unspecified()
+            {
+                return;
+            }
+            out.push(Packet
+            {
+                source: packet.destination,
+                destination: packet.source,
+                protocol: protocol::ICMP,
+                payload: Echo { kind: TYPE_ECHO_REPLY, ..echo }.to_bytes(),
+                interface: None,
+            });
+        }
+        else if echo.identifier == IDENTIFIER && self.outstanding.remove(&echo.sequence)
+        {
+            self.replies.insert(echo.sequence, now);
+        }
+    }
+}
+
+/// Sendet eine Echo-Anfrage an `destination` und gibt die Zeit bis zur
+/// Antwort in Millisekunden zurück. Wegen des Timer-Takts ist sie auf etwa
+/// 55 ms genau.
+pub fn ping(destination: Ipv4Addr, timeout_ms: u64) -> Result<u64, SocketError>
+{
+    let (sequence, start) =
+    {
+        let mut stack = stack::lock();
+        let source = stack.source_address(destination, None)?;
+        let sequence = stack.pings.next_sequence;
+        stack.pings.next_sequence = sequence.wrapping_add(1);
+        stack.pings.outstanding.insert(sequence);
+        let request = Echo { kind: TYPE_ECHO_REQUEST, identifier: IDENTIFIER, sequence, data: PAYLOAD };
+        let packet = Packet { source, destination, protocol: protocol::ICMP, payload: request.to_bytes(), interface: None };
+        if let Err(error) = stack.transmit(packet)
+        {
+            stack.pings.outstanding.remove(&sequence);
+            return Err(error);
+        }
+        (sequence, stack::now())
+    };
+    let result = stack::wait_for(Some(timeout_ms), |stack| stack.pings.replies.remove(&sequence).map(|received| Ok(received - start)));
+    if result.is_err()
+    {
+        stack::lock().pings.outstanding.remove(&sequence);
+    }
+    result
+}
+
+#[test_case]
+fn test_echo_reply()
+{
+    let request = Echo { kind: TYPE_ECHO_REQUEST, identifier: 1, sequence: 2, data: b"ping" };
+    let packet = Packet
+    {
+        source: Ipv4Addr::new(10, 0, 2, 2),
+        destination: Ipv4Addr::new(10, 0, 2, 15),
+        protocol: protocol::ICMP,
+        payload: request.to_bytes(),
+        interface: Some(1),
+    };
+    let mut out = Vec::new();
+    Pings::new().receive(&packet, 0, &mut out);
+    assert_eq!(out.len(), 1);
+    assert_eq!(out[0].destination, packet.source);
+    assert_eq!(Echo::parse(&out[0].payload), Some(Echo { kind: TYPE_ECHO_REPLY, ..request }));
+}
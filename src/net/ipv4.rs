@@ -0,0 +1,217 @@
+//! IPv4.
+//!
+//! Der Kopf ohne Optionen ist 20 Bytes lang:
+//!
+//! | Offset | Größe | Inhalt |
+//! |--------|-------|--------|
+//! | 0 | 1 | Version (4) und Kopflänge in 32-Bit-Wörtern |
+//! | 2 | 2 | Gesamtlänge |
+//! | 4 | 2 | Kennung |
+//! | 6 | 2 | Flags und Fragment-Offset |
+//! | 8 | 1 | Lebensdauer (TTL) |
+//! | 9 | 1 | Protokoll: 1 ICMP, 6 TCP, 17 UDP |
+//! | 10 | 2 | Prüfsumme des Kopfs |
+//! | 12 | 4 | Quelladresse |
+//! | 16 | 4 | Zieladresse |
+//!
+//! Fragmente werden nicht zusammengesetzt, sondern verworfen; gesendet wird
+//! stets mit gesetztem „Don't Fragment“.
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::net::Ipv4Addr;
+
+/// Protokollnummern.
+pub mod protocol
+{
+    pub const ICMP: u8 = 1;
+    pub const TCP: u8 = 6;
+    pub const UDP: u8 = 17;
+}
+
+/// Länge des Kopfs ohne Optionen.
+pub const HEADER_SIZE: usize = 20;
+
+/// Lebensdauer gesendeter Pakete.
+const DEFAULT_TTL: u8 = 64;
+
+/// Flag „Don't Fragment“.
+const DONT_FRAGMENT: u16 = 0x4000;
+
+/// Flag „More Fragments“ und Fragment-Offset.
+const FRAGMENT_MASK: u16 = 0x3FFF;
+
+/// ## Ipv4Config
+///
+/// Statische Konfiguration einer Schnittstelle: Adresse, Länge des
+/// Netzpräfixes und optional ein Gateway für alle anderen Netze.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ipv4Config
+{
+    pub address: Ipv4Addr,
+    pub prefix_len: u8,
+    pub gateway: Option<Ipv4Addr>,
+}
+
+impl Ipv4Config
+{
+    pub const fn new(address: Ipv4Addr, prefix_len: u8, gateway: Option<Ipv4Addr>) -> Ipv4Config
+    {
+        Ipv4Config { address, prefix_len, gateway }
+    }
+
+    /// Die Netzmaske, z. B. `255.255.255.0` für das Präfix 24.
+    pub fn netmask(&self) -> Ipv4Addr
+    {
+        Ipv4Addr::from_bits(u32::MAX.checked_shl(32 - u32::from(self.prefix_len)).unwrap_or(0))
+    }
+
+    /// Gibt an, ob `address` im selben Netz liegt.
+    pub fn contains(&self, address: Ipv4Addr) -> bool
+    {
+        let mask = self.netmask().to_bits();
+        address.to_bits() & mask == self.address.to_bits() & mask
+    }
+
+    /// Die Broadcast-Adresse des Netzes.
+    pub fn broadcast(&self) -> Ipv4Addr
+    {
+        Ipv4Addr::from_bits(self.address.to_bits() | !self.netmask().to_bits())
+    }
+}
+
+impl fmt::Display for Ipv4Config
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{}/{}", self.address, self.prefix_len)?;
+        if let Some(gateway) = self.gateway
+        {
+            write!(f, " via {}", gateway)?;
+        }
+        Ok(())
+    }
+}
+
+/// Ein IPv4-Paket ohne Kopf. `interface` legt die Schnittstelle fest, über
+/// die gesendet wird; sonst wird sie anhand der Zieladresse gewählt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packet
+{
+    pub source: Ipv4Addr,
+    pub destination: Ipv4Addr,
+    pub protocol: u8,
+    pub payload: Vec<u8>,
+    pub interface: Option<usize>,
+}
+
+/// Internet-Prüfsumme: Einerkomplement der Einerkomplementsumme aller
+/// 16-Bit-Wörter, beginnend mit der Teilsumme `initial`.
+pub fn checksum(initial: u32, data: &[u8]) -> u16
+{
+    let mut sum = initial;
+    let mut words = data.chunks_exact(2);
+    for word in &mut words
+    {
+        sum += u32::from(u16::from_be_bytes([word[0], word[1]]));
+    }
+    if let [last] = words.remainder()
+    {
+        sum += u32::from(*last) << 8;
+    }
+    while sum > 0xFFFF
+    {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Teilsumme des Pseudokopfs, über den TCP und UDP ihre Prüfsumme bilden.
+pub fn pseudo_header_sum(source: Ipv4Addr, destination: Ipv4Addr, protocol: u8, len: usize) -> u32
+{
+    let [a, b, c, d] = source.octets();
+    let [e, f, g, h] = destination.octets();
+    [u16::from_be_bytes([a, b]), u16::from_be_bytes([c, d]), u16::from_be_bytes([e, f]), u16::from_be_bytes([g, h]), u16::from(protocol), len as u16]
+        .into_iter()
+        .map(u32::from)
+        .sum()
+}
+
+/// Zerlegt ein empfangenes Paket. Pakete mit falscher Prüfsumme, Fragmente
+/// und andere Versionen ergeben `None`.
+pub fn parse(data: &[u8]) -> Option<Packet>
+{
+    if data.len() < HEADER_SIZE || data[0] >> 4 != 4
+    {
+        return None;
+    }
+    let header_len = usize::from(data[0] & 0xF) * 4;
+    let total_len = usize::from(u16::from_be_bytes([data[2], data[3]]));
+    if header_len < HEADER_SIZE || total_len < header_len || total_len > data.len() || checksum(0, &data[..header_len]) != 0
+    {
+        return None;
+    }
+    if u16::from_be_bytes([data[6], data[7]]) & FRAGMENT_MASK != 0
+    {
+        return None;
+    }
+    let address = |offset: usize| Ipv4Addr::new(data[offset], data[offset + 1], data[offset + 2], data[offset + 3]);
+    Some(Packet
+    {
+        source: address(12),
+        destination: address(16),
+        protocol: data[9],
+        payload: data[header_len..total_len].to_vec(),
+        interface: None,
+    })
+}
+
+/// Setzt Kopf und Nutzlast eines Pakets zusammen.
+pub fn build(packet: &Packet, identification: u16) -> Vec<u8>
+{
+    let total_len = HEADER_SIZE + packet.payload.len();
+    let mut data = Vec::with_capacity(total_len);
+    data.extend_from_slice(&[0x45, 0]);
+    data.extend_from_slice(&(total_len as u16).to_be_bytes());
+    data.extend_from_slice(&identification.to_be_bytes());
+    data.extend_from_slice(&DONT_FRAGMENT.to_be_bytes());
+    data.extend_from_slice(&[DEFAULT_TTL, packet.protocol, 0, 0]);
+    data.extend_from_slice(&packet.source.octets());
+    data.extend_from_slice(&packet.destination.octets());
+    let sum = checksum(0, &data);
+    data[10..12].copy_from_slice(&sum.to_be_bytes());
+    data.extend_from_slice(&packet.payload);
+    data
+}
+
+#[test_case]
+fn test_checksum()
+{
+    // Beispielkopf aus RFC 1071 bzw. gängigen Mitschnitten.
+    let header = [0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00, 0xC0, 0xA8, 0x00, 0x01, 0xC0, 0xA8, 0x00, 0xC7];
+    assert_eq!(checksum(0, &header), 0xB861);
+    assert_eq!(checksum(0, &[0xFF]), 0x00FF);
+}
+
+#[test_case]
+fn test_build_and_parse()
+{
+    let packet = Packet
+    {
+        source: Ipv4Addr::new(10, 0, 2, 15),
+        destination: Ipv4Addr::new(10, 0, 2, 2),
+        protocol: protocol::UDP,
+        payload: alloc::vec![1, 2, 3],
+        interface: None,
+    };
+    let mut data = build(&packet, 7);
+    assert_eq!(data.len(), HEADER_SIZE + 3);
+    assert_eq!(parse(&data), Some(packet));
+    data[8] ^= 1;
+    assert_eq!(parse(&data), None);
+
+    let config = Ipv4Config::new(Ipv4Addr::new(10, 0, 2, 15), 24, None);
+    assert_eq!(config.netmask(), Ipv4Addr::new(255, 255, 255, 0));
+    assert_eq!(config.broadcast(), Ipv4Addr::new(10, 0, 2, 255));
+    assert!(config.contains(Ipv4Addr::new(10, 0, 2, 2)) && !config.contains(Ipv4Addr::new(10, 0, 3, 2)));
+}
@@ -0,0 +1,90 @@
+//! Loopback-Gerät.
+//!
+//! Jeder gesendete Frame landet unverändert in der eigenen Empfangsschlange.
+//! So lassen sich Protokolle und Sockets ohne Netzwerkkarte betreiben und
+//! testen.
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+use super::{DEFAULT_MTU, MacAddress, NetDevice, NetError, check_frame};
+use crate::sync::IrqSpinlock;
+
+/// Höchste Anzahl nicht abgeholter Frames.
+const QUEUE_LIMIT: usize = 256;
+
+/// ## Loopback
+///
+/// Das Gerät `lo` ohne Hardware-Adresse.
+pub struct Loopback
+{
+    queue: IrqSpinlock<VecDeque<Vec<u8>>>,
+}
+
+impl Loopback
+{
+    pub const fn new() -> Loopback
+    {
+        Loopback { queue: IrqSpinlock::new(VecDeque::new()) }
+    }
+}
+
+impl Default for Loopback
+{
+    fn default() -> Loopback
+    {
+        Loopback::new()
+    }
+}
+
+impl NetDevice for Loopback
+{
+    fn name(&self) -> &str
+    {
+        "lo"
+    }
+
+    fn mac_address(&self) -> MacAddress
+    {
+        MacAddress::default()
+    }
+
+    fn mtu(&self) -> usize
+    {
+        DEFAULT_MTU
+    }
+
+    fn link_up(&self) -> bool
+    {
+        true
+    }
+
+    fn send(&self, frame: &[u8]) -> Result<(), NetError>
+    {
+        check_frame(self, frame)?;
+        let mut queue = self.queue.lock();
+        if queue.len() >= QUEUE_LIMIT
+        {
+            return Err(NetError::QueueFull);
+        }
+        queue.push_back(frame.to_vec());
+        Ok(())
+    }
+
+    fn receive(&self) -> Option<Vec<u8>>
+    {
+        self.queue.lock().pop_front()
+    }
+}
+
+#[test_case]
+fn test_loopback()
+{
+    let device = Loopback::new();
+    assert_eq!(device.send(&[0; 10]), Err(NetError::BadFrameSize));
+    device.send(&[1; 60]).unwrap();
+    device.send(&[2; 60]).unwrap();
+    assert_eq!(device.receive(), Some(alloc::vec![1; 60]));
+    assert_eq!(device.receive(), Some(alloc::vec![2; 60]));
+    assert_eq!(device.receive(), None);
+}
@@ -0,0 +1,449 @@
+//! Kern des Netzwerkstapels.
+//!
+//! Der Stapel kennt seine **Schnittstellen**: Netzwerkgeräte mit optionaler
+//! [Ipv4Config] und eigenem [ArpCache]. Er läuft nicht von selbst, sondern
+//! wird durch [poll] angetrieben, das alle empfangenen Frames auswertet und
+//! die Zeitgeber von ARP und TCP bedient. Blockierende Socket-Operationen
+//! rufen [poll] selbst auf, solange sie warten.
+//!
+//! Pakete werden so geleitet:
+//!
+//! 1. an eigene Adressen und `127.0.0.0/8` über die Loopback-Schnittstelle
+//! 2. in ein direkt angeschlossenes Netz an die Zieladresse selbst
+//! 3. sonst an das Gateway der ersten Schnittstelle, die eines hat
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::net::Ipv4Addr;
+
+use super::arp::{self, ArpCache, ArpPacket};
+use super::ethernet::{self, Frame};
+use super::ipv4::{self, Ipv4Config, Packet, protocol};
+use super::loopback::Loopback;
+use super::{MacAddress, NetDevice, NetError, SocketError, icmp, tcp, udp};
+use crate::sync::{Mutex, MutexGuard, Once};
+
+/// Höchste Anzahl an Durchgängen je [poll]; jeder wertet alle bis dahin
+/// empfangenen Frames aus.
+const MAX_ROUNDS: usize = 16;
+
+/// Höchste Anzahl an Frames je Schnittstelle und Durchgang.
+const FRAMES_PER_ROUND: usize = 64;
+
+/// Ein Netzwerkgerät im Stapel.
+struct Interface
+{
+    device: Arc<dyn NetDevice>,
+    loopback: bool,
+    config: Option<Ipv4Config>,
+    arp: ArpCache,
+}
+
+/// ## Stack
+///
+/// Schnittstellen und Sockets aller Protokolle.
+pub(super) struct Stack
+{
+    interfaces: Vec<Interface>,
+    pub(super) udp: udp::Sockets,
+    pub(super) tcp: tcp::Sockets,
+    pub(super) pings: icmp::Pings,
+    identification: u16,
+}
+
+/// Der einzige Stapel.
+static STACK: Mutex<Stack> = Mutex::new(Stack::new());
+
+/// Sperrt den Stapel.
+pub(super) fn lock() -> MutexGuard<'static, Stack>
+{
+    STACK.lock()
+}
+
+/// Millisekunden seit dem Start; Grundlage aller Zeitgeber.
+pub(super) fn now() -> u64
+{
+    crate::time::uptime_ms()
+}
+
+impl Stack
+{
+    const fn new() -> Stack
+    {
+        Stack { interfaces: Vec::new(), udp: udp::Sockets::new(), tcp: tcp::Sockets::new(), pings: icmp::Pings::new(), identification: 0 }
+    }
+
+    /// Index der Schnittstelle des Geräts `name`. Angemeldete Geräte werden
+    /// beim ersten Zugriff in den Stapel aufgenommen.
+    pub(super) fn interface(&mut self, name: &str) -> Option<usize>
+    {
+        if let Some(index) = self.interfaces.iter().position(|interface| interface.device.name() == name)
+        {
+            return Some(index);
+        }
+        let device = super::get(name)?;
+        self.interfaces.push(Interface { device, loopback: false, config: None, arp: ArpCache::new() });
+        Some(self.interfaces.len() - 1)
+    }
+
+    fn loopback(&self) -> Option<usize>
+    {
+        self.interfaces.iter().position(|interface| interface.loopback)
+    }
+
+    /// Gibt an, ob `address` zu einer Schnittstelle gehört.
+    pub(super) fn is_local(&self, address: Ipv4Addr) -> bool
+    {
+        (address.is_loopback() && self.loopback().is_some())
+            || self.interfaces.iter().any(|interface| interface.config.is_some_and(|config| config.address == address))
+    }
+
+    /// Schnittstelle und nächster Empfänger für `destination`.
+    fn route(&self, destination: Ipv4Addr, interface: Option<usize>) -> Option<(usize, Ipv4Addr)>
+    {
+        if let Some(index) = interface
+        {
+            return Some((index, destination));
+        }
+        if self.is_local(destination)
+        {
+            return self.loopback().map(|index| (index, destination));
+        }
+        let configured = || self.interfaces.iter().enumerate().filter(|(_, interface)| !interface.loopback).filter_map(|(index, interface)| Some((index, interface.config?)));
+        if destination == Ipv4Addr::BROADCAST
+        {
+            return configured().next().map(|(index, _)| (index, destination));
+        }
+        if let Some((index, _)) = configured().find(|(_, config)| config.contains(destination))
+        {
+            return Some((index, destination));
+        }
+        configured().find_map(|(index, config)| Some((index, config.gateway?)))
+    }
+
+    /// Die Absenderadresse für Pakete an `destination`: die Adresse der
+    /// Schnittstelle, über die sie gehen, bzw. `0.0.0.0`, solange diese noch
+    /// keine hat.
+    pub(super) fn source_address(&self, destination: Ipv4Addr, interface: Option<usize>) -> Result<Ipv4Addr, SocketError>
+    {
+        let (index, _) = self.route(destination, interface).ok_or(SocketError::Unreachable)?;
+        if self.interfaces[index].loopback && !destination.is_loopback()
+        {
+            return Ok(destination);
+        }
+        Ok(self.interfaces[index].config.map_or(Ipv4Addr::UNSPECIFIED, |config| config.address))
+    }
+
+    /// Sendet ein Paket. Ist die MAC des nächsten Empfängers unbekannt, wartet
+    /// es im [ArpCache] auf dessen Antwort.
+    pub(super) fn transmit(&mut self, packet: Packet) -> Result<(), SocketError>
+    {
+        let (index, next_hop) = self.route(packet.destination, packet.interface).ok_or(SocketError::Unreachable)?;
+        let now = now();
+        let interface = &mut self.interfaces[index];
+        if ipv4::HEADER_SIZE + packet.payload.len() > interface.device.mtu()
+        {
+            return Err(SocketError::MessageTooLarge);
+        }
+        let destination = if interface.loopback
+        {
+            MacAddress::default()
+        }
+        else if next_hop == Ipv4Addr::BROADCAST || interface.config.is_some_and(|config| config.broadcast() == next_hop)
+        {
+            MacAddress::BROADCAST
+        }
+        else if let Some(mac) = interface.arp.lookup(next_hop, now)
+        {
+            mac
+        }
+        else
+        {
+            if interface.arp.wait(next_hop, packet, now)
+            {
+                Self::send_arp(interface, arp::OPERATION_REQUEST, MacAddress::default(), next_hop)?;
+            }
+            return Ok(());
+        };
+
+        self.identification = self.identification.wrapping_add(1);
+        let data = ipv4::build(&packet, self.identification);
+        Self::send_frame(&self.interfaces[index], destination, ethernet::TYPE_IPV4, &data)
+    }
+
+    fn send_frame(interface: &Interface, destination: MacAddress, kind: u16, payload: &[u8]) -> Result<(), SocketError>
+    {
+        let frame = ethernet::build(destination, interface.device.mac_address(), kind, payload);
+        interface.device.send(&frame).map_err(|error| match error
+        {
+            NetError::BadFrameSize => SocketError::MessageTooLarge,
+            NetError::LinkDown => SocketError::Unreachable,
+            error => SocketError::Device(error),
+        })
+    }
+
+    /// Sendet eine ARP-Anfrage nach `target` oder eine Antwort an `target`.
+    fn send_arp(interface: &Interface, operation: u16, target_mac: MacAddress, target_ip: Ipv4Addr) -> Result<(), SocketError>
+    {
+        let packet = ArpPacket
+        {
+            operation,
+            sender_mac: interface.device.mac_address(),
+            sender_ip: interface.config.map_or(Ipv4Addr::UNSPECIFIED, |config| config.address),
+            target_mac,
+            target_ip,
+        };
+        let destination = if operation == arp::OPERATION_REQUEST { MacAddress::BROADCAST } else { target_mac };
+        Self::send_frame(interface, destination, ethernet::TYPE_ARP, &packet.to_bytes())
+    }
+
+    /// Wertet alle empfangenen Frames aus und bedient die Zeitgeber.
+    pub(super) fn poll(&mut self)
+    {
+        let now = now();
+        for _ in 0..MAX_ROUNDS
+        {
+            let mut received = false;
+            for index in 0..self.interfaces.len()
+            {
+                for _ in 0..FRAMES_PER_ROUND
+                {
+                    let Some(frame) = self.interfaces[index].device.receive()
+                    else
+                    {
+                        break;
+                    };
+                    received = true;
+                    self.receive_frame(index, &frame, now);
+                }
+            }
+            if !received
+            {
+                break;
+            }
+        }
+
+        for interface in &mut self.interfaces
+        {
+            for address in interface.arp.expire(now)
+            {
+                let _ = Self::send_arp(interface, arp::OPERATION_REQUEST, MacAddress::default(), address);
+            }
+        }
+        let mut out = Vec::new();
+        self.tcp.poll(now, &mut out);
+        self.transmit_all(out);
+    }
+
+    /// Sendet, was die TCP-Verbindungen nach einer Operation des Benutzers
+    /// zu senden haben.
+    pub(super) fn flush(&mut self)
+    {
+        let mut out = Vec::new();
+        self.tcp.output(now(), &mut out);
+        self.transmit_all(out);
+    }
+
+    /// Sendet alle Pakete; Fehler gelten als Verlust unterwegs.
+    fn transmit_all(&mut self, packets: Vec<Packet>)
+    {
+        for packet in packets
+        {
+            let _ = self.transmit(packet);
+        }
+    }
+
+    fn receive_frame(&mut self, index: usize, data: &[u8], now: u64)
+    {
+        let Some(frame) = Frame::parse(data)
+        else
+        {
+            return;
+        };
+        let interface = &self.interfaces[index];
+        if !interface.loopback && frame.destination != interface.device.mac_address() && frame.destination != MacAddress::BROADCAST
+        {
+            return;
+        }
+        match frame.kind
+        {
+            ethernet::TYPE_ARP =>
+            {
+                if let Some(packet) = ArpPacket::parse(frame.payload)
+                {
+                    self.receive_arp(index, packet, now);
+                }
+            }
+            ethernet::TYPE_IPV4 =>
+            {
+                if let Some(packet) = ipv4::parse(frame.payload)
+                {
+                    self.receive_packet(index, packet, now);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Merkt sich die Adresse des Absenders und beantwortet Anfragen nach der
+    /// eigenen Adresse.
+    fn receive_arp(&mut self, index: usize, packet: ArpPacket, now: u64)
+    {
+        let interface = &mut self.interfaces[index];
+        let Some(config) = interface.config
+        else
+        {
+            return;
+        };
+        if packet.sender_ip.is_unspecified() || !config.contains(packet.sender_ip)
+        {
+            return;
+        }
+        let waiting = interface.arp.insert(packet.sender_ip, packet.sender_mac, now);
+        if packet.operation == arp::OPERATION_REQUEST && packet.target_ip == config.address
+        {
+            let _ = Self::send_arp(interface, arp::OPERATION_REPLY, packet.sender_mac, packet.sender_ip);
+        }
+        self.transmit_all(waiting);
+    }
+
+    fn receive_packet(&mut self, index: usize, mut packet: Packet, now: u64)
+    {
+        let interface = &self.interfaces[index];
+        let accepted = interface.loopback
+            || packet.destination == Ipv4Addr::BROADCAST
+            || match interface.config
+            {
+                Some(config) => packet.destination == config.address || packet.destination == config.broadcast(),
+                // Ohne Adresse nur UDP, damit DHCP eine bekommen kann.
+                None => packet.protocol == protocol::UDP,
+            };
+        if !accepted
+        {
+            return;
+        }
+        packet.interface = Some(index);
+
+        let mut out = Vec::new();
+        match packet.protocol
+        {
+            protocol::ICMP => self.pings.receive(&packet, now, &mut out),
+            protocol::UDP => self.udp.receive(&packet),
+            protocol::TCP => self.tcp.receive(&packet, now, &mut out),
+            _ => (),
+        }
+        self.transmit_all(out);
+    }
+}
+
+/// Ruft [poll] auf, bis `condition` ein Ergebnis liefert, höchstens aber
+/// `timeout_ms` Millisekunden lang; danach gilt [SocketError::TimedOut].
+/// Zwischen zwei Versuchen schläft der Aufrufer über [crate::sync::wait].
+pub(super) fn wait_for<T>(timeout_ms: Option<u64>, mut condition: impl FnMut(&mut Stack) -> Option<Result<T, SocketError>>) -> Result<T, SocketError>
+{
+    let deadline = timeout_ms.map(|timeout| now() + timeout);
+    loop
+    {
+        {
+            let mut stack = lock();
+            stack.poll();
+            if let Some(result) = condition(&mut stack)
+            {
+                stack.flush();
+                return result;
+            }
+        }
+        if deadline.is_some_and(|deadline| now() >= deadline)
+        {
+            return Err(SocketError::TimedOut);
+        }
+        crate::sync::wait();
+    }
+}
+
+/// Sucht ab `*next` den nächsten freien Port im Bereich für kurzlebige Ports
+/// (49152–65535).
+pub(super) fn ephemeral_port(next: &mut u16, in_use: impl Fn(u16) -> bool) -> Option<u16>
+{
+    const FIRST: u16 = 49152;
+    for _ in FIRST..=u16::MAX
+    {
+        let port = (*next).max(FIRST);
+        *next = port.checked_add(1).unwrap_or(FIRST);
+        if !in_use(port)
+        {
+            return Some(port);
+        }
+    }
+    None
+}
+
+/// ## Initialisierung
+///
+/// Meldet beim ersten Aufruf die Loopback-Schnittstelle `lo` mit
+/// `127.0.0.1/8` an.
+pub fn init()
+{
+    static LOOPBACK: Once<()> = Once::new();
+    LOOPBACK.call_once(||
+    {
+        let device = Arc::new(Loopback::new());
+        super::register(device.clone());
+        lock().interfaces.push(Interface
+        {
+            device,
+            loopback: true,
+            config: Some(Ipv4Config::new(Ipv4Addr::LOCALHOST, 8, None)),
+            arp: ArpCache::new(),
+        });
+    });
+}
+
+/// Wertet alle empfangenen Frames aus und bedient die Zeitgeber. Wer keine
+/// blockierenden Sockets nutzt, muss dies regelmäßig aufrufen.
+pub fn poll()
+{
+    lock().poll();
+}
+
+/// Setzt die Adresse des Geräts `name` oder entfernt sie mit `None`.
+pub fn configure(name: &str, config: Option<Ipv4Config>) -> Result<(), SocketError>
+{
+    let mut stack = lock();
+    let index = stack.interface(name).ok_or(SocketError::NoDevice)?;
+    stack.interfaces[index].config = config;
+    Ok(())
+}
+
+/// Die Adresse des Geräts `name`, falls gesetzt.
+pub fn config(name: &str) -> Option<Ipv4Config>
+{
+    let stack = lock();
+    stack.interfaces.iter().find(|interface| interface.device.name() == name)?.config
+}
+
+/// Namen und Adressen aller Schnittstellen im Stapel.
+pub fn interfaces() -> Vec<(String, Option<Ipv4Config>)>
+{
+    lock().interfaces.iter().map(|interface| (String::from(interface.device.name()), interface.config)).collect()
+}
+
+/// Die bekannten Zuordnungen im ARP-Cache des Geräts `name`.
+pub fn arp_entries(name: &str) -> Vec<(Ipv4Addr, MacAddress)>
+{
+    let stack = lock();
+    stack.interfaces.iter().find(|interface| interface.device.name() == name).map(|interface| interface.arp.entries(now())).unwrap_or_default()
+}
+
+#[test_case]
+fn test_ephemeral_ports()
+{
+    let mut next = 0;
+    assert_eq!(ephemeral_port(&mut next, |_| false), Some(49152));
+    assert_eq!(ephemeral_port(&mut next, |port| port == 49153), Some(49154));
+    let mut next = u16::MAX;
+    assert_eq!(ephemeral_port(&mut next, |_| false), Some(u16::MAX));
+    assert_eq!(next, 49152);
+    assert_eq!(ephemeral_port(&mut next, |_| true), None);
+}
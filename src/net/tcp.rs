@@ -0,0 +1,1066 @@
+//! Transmission Control Protocol.
+//!
+//! Der Kopf ohne Optionen ist 20 Bytes lang:
+//!
+//! | Offset | Größe | Inhalt |
+//! |--------|-------|--------|
+//! | 0 | 4 | Quell- und Zielport |
+//! | 4 | 4 | Folgenummer des ersten Bytes |
+//! | 8 | 4 | Bestätigungsnummer: nächstes erwartetes Byte |
+//! | 12 | 2 | Kopflänge in 32-Bit-Wörtern und Flags |
+//! | 14 | 2 | Empfangsfenster |
+//! | 16 | 2 | Prüfsumme über Pseudokopf, Kopf und Daten |
+//! | 18 | 2 | Urgent-Zeiger (nicht unterstützt) |
+//!
+//! Die Umsetzung ist bewusst einfach gehalten:
+//!
+//! - Segmente außerhalb der Reihenfolge werden verworfen und mit einer
+//!   doppelten Bestätigung beantwortet.
+//! - Nach Ablauf der Wartezeit wird alles Unbestätigte erneut gesendet
+//!   (Go-Back-N); die Wartezeit verdoppelt sich mit jedem Versuch.
+//! - Es gibt keine Überlastkontrolle, nur das Fenster des Empfängers.
+//!
+//! [TcpListener] und [TcpStream] folgen `std::net`. Blockierende Aufrufe
+//! treiben den Stapel selbst an.
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::vec::Vec;
+use core::net::{Ipv4Addr, SocketAddrV4};
+
+use super::ipv4::{self, Packet, protocol};
+use super::{DEFAULT_MTU, SocketError, stack};
+
+/// Flags im Kopf.
+pub mod flags
+{
+    pub const FIN: u8 = 0x01;
+    pub const SYN: u8 = 0x02;
+    pub const RST: u8 = 0x04;
+    pub const PSH: u8 = 0x08;
+    pub const ACK: u8 = 0x10;
+}
+
+/// Länge des Kopfs ohne Optionen.
+pub const HEADER_SIZE: usize = 20;
+
+/// Größe von Sende- und Empfangspuffer jeder Verbindung.
+pub const BUFFER_SIZE: usize = 16 * 1024;
+
+/// Größte Segmentlänge, wenn die Gegenseite keine angibt.
+const DEFAULT_MSS: usize = 536;
+
+/// Größte Segmentlänge, die dieser Stapel annimmt.
+const LOCAL_MSS: usize = DEFAULT_MTU - ipv4::HEADER_SIZE - HEADER_SIZE;
+
+/// Erste und längste Wartezeit vor einer Wiederholung.
+const INITIAL_RTO_MS: u64 = 250;
+const MAX_RTO_MS: u64 = 8000;
+
+/// Wiederholungen, bevor eine Verbindung als verloren gilt.
+const MAX_RETRIES: u32 = 8;
+
+/// Wiederholungen von SYN, bevor der Aufbau scheitert.
+const MAX_SYN_RETRIES: u32 = 4;
+
+/// Verweildauer in [TcpState::TimeWait].
+const TIME_WAIT_MS: u64 = 2000;
+
+/// Höchste Anzahl noch nicht angenommener Verbindungen je Listener.
+const BACKLOG: usize = 16;
+
+/// `a < b` im Folgenummernraum modulo 2^32.
+fn seq_lt(a: u32, b: u32) -> bool
+{
+    (a.wrapping_sub(b) as i32) < 0
+}
+
+/// `a <= b` im Folgenummernraum modulo 2^32.
+fn seq_le(a: u32, b: u32) -> bool
+{
+    (a.wrapping_sub(b) as i32) <= 0
+}
+
+/// Ein TCP-Segment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment
+{
+    pub source_port: u16,
+    pub destination_port: u16,
+    pub sequence: u32,
+    pub acknowledgment: u32,
+    pub flags: u8,
+    pub window: u16,
+    /// Option „Maximum Segment Size“, nur mit SYN.
+    pub mss: Option<u16>,
+    pub payload: Vec<u8>,
+}
+
+impl Segment
+{
+    /// Zerlegt das Segment in `packet`; falsche Längen und Prüfsummen ergeben
+    /// `None`.
+    pub fn parse(packet: &Packet) -> Option<Segment>
+    {
+        let data = &packet.payload;
+        if data.len() < HEADER_SIZE
+        {
+            return None;
+        }
+        let header_len = usize::from(data[12] >> 4) * 4;
+        if header_len < HEADER_SIZE || header_len > data.len()
+        {
+            return None;
+        }
+        if ipv4::checksum(ipv4::pseudo_header_sum(packet.source, packet.destination, protocol::TCP, data.len()), data) != 0
+        {
+            return None;
+        }
+
+        let mut mss = None;
+        let mut offset = HEADER_SIZE;
+        while offset < header_len
+        {
+            match data[offset]
+            {
+                0 => break,
+                1 => offset += 1,
+                kind =>
+                {
+                    let len = usize::from(*data.get(offset + 1)?);
+                    if len < 2 || offset + len > header_len
+                    {
+                        break;
+                    }
+                    if kind == 2 && len == 4
+                    {
+                        mss = Some(u16::from_be_bytes([data[offset + 2], data[offset + 3]]));
+                    }
+                    offset += len;
+                }
+            }
+        }
+
+        let word = |offset: usize| u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap());
+        Some(Segment
+        {
+            source_port: u16::from_be_bytes([data[0], data[1]]),
+            destination_port: u16::from_be_bytes([data[2], data[3]]),
+            sequence: word(4),
+            acknowledgment: word(8),
+            flags: data[13] & 0x1F,
+            window: u16::from_be_bytes([data[14], data[15]]),
+            mss,
+            payload: data[header_len..].to_vec(),
+        })
+    }
+
+    /// Setzt das Segment für ein Paket von `source` an `destination`
+    /// zusammen.
+    pub fn to_bytes(&self, source: Ipv4Addr, destination: Ipv4Addr) -> Vec<u8>
+    {
+        let header_len = HEADER_SIZE + if self.mss.is_some() { 4 } else { 0 };
+        let mut data = Vec::with_capacity(header_len + self.payload.len());
+        data.extend_from_slice(&self.source_port.to_be_bytes());
+        data.extend_from_slice(&self.destination_port.to_be_bytes());
+        data.extend_from_slice(&self.sequence.to_be_bytes());
+        data.extend_from_slice(&self.acknowledgment.to_be_bytes());
+        data.extend_from_slice(&[(header_len / 4) as u8 * 16, self.flags]);
+        data.extend_from_slice(&self.window.to_be_bytes());
+        data.extend_from_slice(&[0; 4]);
+        if let Some(mss) = self.mss
+        {
+            data.extend_from_slice(&[2, 4]);
+            data.extend_from_slice(&mss.to_be_bytes());
+        }
+        data.extend_from_slice(&self.payload);
+        let sum = ipv4::checksum(ipv4::pseudo_header_sum(source, destination, protocol::TCP, data.len()), &data);
+        data[16..18].copy_from_slice(&sum.to_be_bytes());
+        data
+    }
+
+    /// Länge im Folgenummernraum: Daten und je eins für SYN und FIN.
+    pub fn len(&self) -> u32
+    {
+        self.payload.len() as u32 + u32::from(self.flags & flags::SYN != 0) + u32::from(self.flags & flags::FIN != 0)
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.len() == 0
+    }
+}
+
+/// Zustand einer Verbindung nach RFC 793; `Listen` gibt es nur als
+/// [TcpListener].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TcpState
+{
+    SynSent,
+    SynReceived,
+    Established,
+    FinWait1,
+    FinWait2,
+    CloseWait,
+    Closing,
+    LastAck,
+    TimeWait,
+    Closed,
+}
+
+/// Eine Verbindung.
+#[derive(Debug)]
+struct Connection
+{
+    local: SocketAddrV4,
+    remote: SocketAddrV4,
+    state: TcpState,
+    /// Erste eigene Folgenummer, die des SYN.
+    iss: u32,
+    /// Ältestes unbestätigtes und nächstes zu sendendes Byte.
+    snd_una: u32,
+    snd_nxt: u32,
+    /// Fenster der Gegenseite.
+    snd_wnd: u32,
+    /// Unbestätigte und ungesendete Daten ab `snd_una`.
+    send_buffer: VecDeque<u8>,
+    /// Der Benutzer hat die Senderichtung geschlossen; FIN folgt den Daten.
+    fin_queued: bool,
+    fin_sent: bool,
+    /// Nächstes erwartetes Byte der Gegenseite.
+    rcv_nxt: u32,
+    receive_buffer: VecDeque<u8>,
+    fin_received: bool,
+    /// Größte Segmentlänge beim Senden.
+    mss: usize,
+    rto: u64,
+    retransmit_at: Option<u64>,
+    retries: u32,
+    time_wait_until: u64,
+    error: Option<SocketError>,
+    ack_pending: bool,
+    /// Fenster in der zuletzt gesendeten Bestätigung.
+    advertised_window: u32,
+    /// Kein [TcpStream] hält die Verbindung; geschlossen wird sie entfernt.
+    orphaned: bool,
+}
+
+impl Connection
+{
+    fn new(local: SocketAddrV4, remote: SocketAddrV4, state: TcpState, iss: u32, now: u64) -> Connection
+    {
+        Connection
+        {
+            local,
+            remote,
+            state,
+            iss,
+            snd_una: iss,
+            snd_nxt: iss.wrapping_add(1),
+            snd_wnd: 0,
+            send_buffer: VecDeque::new(),
+            fin_queued: false,
+            fin_sent: false,
+            rcv_nxt: 0,
+            receive_buffer: VecDeque::new(),
+            fin_received: false,
+            mss: DEFAULT_MSS,
+            rto: INITIAL_RTO_MS,
+            retransmit_at: Some(now + INITIAL_RTO_MS),
+            retries: 0,
+            time_wait_until: 0,
+            error: None,
+            ack_pending: false,
+            advertised_window: 0,
+            orphaned: false,
+        }
+    }
+
+    fn receive_window(&self) -> u32
+    {
+        (BUFFER_SIZE - self.receive_buffer.len()) as u32
+    }
+
+    /// Sendet ein Segment; außer im ersten SYN trägt jedes eine Bestätigung.
+    fn emit(&mut self, flags: u8, sequence: u32, payload: &[u8], out: &mut Vec<Packet>)
+    {
+        let flags = if self.state == TcpState::SynSent { flags } else { flags | flags::ACK };
+        let segment = Segment
+        {
+            source_port: self.local.port(),
+            destination_port: self.remote.port(),
+            sequence,
+            acknowledgment: if flags & flags::ACK != 0 { self.rcv_nxt } else { 0 },
+            flags,
+            window: self.receive_window().min(u32::from(u16::MAX)) as u16,
+            mss: (flags & flags::SYN != 0).then_some(LOCAL_MSS as u16),
+            payload: payload.to_vec(),
+        };
+        self.ack_pending = false;
+        self.advertised_window = u32::from(segment.window);
+        out.push(Packet
+        {
+            source: *self.local.ip(),
+            destination: *self.remote.ip(),
+            protocol: protocol::TCP,
+            payload: segment.to_bytes(*self.local.ip(), *self.remote.ip()),
+            interface: None,
+        });
+    }
+
+    fn send_syn(&mut self, out: &mut Vec<Packet>)
+    {
+        self.emit(flags::SYN, self.iss, &[], out);
+    }
+
+    /// Sendet Daten `[offset, offset + len)` des Sendepuffers.
+    fn send_data(&mut self, offset: usize, len: usize, out: &mut Vec<Packet>)
+    {
+        let data: Vec<u8> = self.send_buffer.range(offset..offset + len).copied().collect();
+        self.emit(flags::PSH, self.snd_una.wrapping_add(offset as u32), &data, out);
+    }
+
+    /// Beendet die Verbindung ohne weiteres Segment.
+    fn abort(&mut self, error: SocketError)
+    {
+        self.state = TcpState::Closed;
+        self.error = Some(error);
+        self.retransmit_at = None;
+        self.send_buffer.clear();
+    }
+
+    /// Bricht die Verbindung mit RST ab.
+    fn reset(&mut self, out: &mut Vec<Packet>)
+    {
+        if !matches!(self.state, TcpState::SynSent | TcpState::TimeWait | TcpState::Closed)
+        {
+            self.emit(flags::RST, self.snd_nxt, &[], out);
+        }
+        self.state = TcpState::Closed;
+        self.retransmit_at = None;
+        self.send_buffer.clear();
+    }
+
+    /// Sendet neue Daten im Rahmen des Fensters, danach FIN und ausstehende
+    /// Bestätigungen.
+    fn output(&mut self, now: u64, out: &mut Vec<Packet>)
+    {
+        if matches!(self.state, TcpState::Established | TcpState::CloseWait)
+        {
+            loop
+            {
+                let sent = self.snd_nxt.wrapping_sub(self.snd_una) as usize;
+                let window = (self.snd_wnd as usize).saturating_sub(sent);
+                let len = (self.send_buffer.len() - sent).min(window).min(self.mss);
+                if len == 0
+                {
+                    break;
+                }
+                self.send_data(sent, len, out);
+                self.snd_nxt = self.snd_nxt.wrapping_add(len as u32);
+            }
+            if self.fin_queued && self.snd_nxt.wrapping_sub(self.snd_una) as usize == self.send_buffer.len()
+            {
+                self.emit(flags::FIN, self.snd_nxt, &[], out);
+                self.snd_nxt = self.snd_nxt.wrapping_add(1);
+                self.fin_sent = true;
+                self.state = if self.state == TcpState::Established { TcpState::FinWait1 } else { TcpState::LastAck };
+            }
+            // Auch bei geschlossenem Fenster läuft der Zeitgeber: Nach Ablauf
+            // fragt ein einzelnes Byte, ob es sich wieder geöffnet hat.
+            if (self.snd_nxt != self.snd_una || !self.send_buffer.is_empty()) && self.retransmit_at.is_none()
+            {
+                self.retransmit_at = Some(now + self.rto);
+            }
+        }
+        if self.ack_pending && !matches!(self.state, TcpState::SynSent | TcpState::Closed)
+        {
+            self.emit(0, self.snd_nxt, &[], out);
+        }
+    }
+
+    /// Bedient die Zeitgeber für Wiederholungen und [TcpState::TimeWait].
+    fn on_timer(&mut self, now: u64, out: &mut Vec<Packet>)
+    {
+        if self.state == TcpState::TimeWait
+        {
+            if now >= self.time_wait_until
+            {
+                self.state = TcpState::Closed;
+            }
+            return;
+        }
+        let Some(at) = self.retransmit_at
+        else
+        {
+            return;
+        };
+        if now < at
+        {
+            return;
+        }
+        self.retries += 1;
+        let limit = if matches!(self.state, TcpState::SynSent | TcpState::SynReceived) { MAX_SYN_RETRIES } else { MAX_RETRIES };
+        if self.retries > limit
+        {
+            self.abort(SocketError::TimedOut);
+            return;
+        }
+        self.rto = (self.rto * 2).min(MAX_RTO_MS);
+        self.retransmit_at = Some(now + self.rto);
+
+        if matches!(self.state, TcpState::SynSent | TcpState::SynReceived)
+        {
+            self.send_syn(out);
+            return;
+        }
+        let in_flight = self.snd_nxt.wrapping_sub(self.snd_una) as usize - usize::from(self.fin_sent);
+        if in_flight == 0 && !self.fin_sent && !self.send_buffer.is_empty()
+        {
+            // Fensterprobe
+            self.send_data(0, 1, out);
+            self.snd_nxt = self.snd_nxt.wrapping_add(1);
+            return;
+        }
+        let mut offset = 0;
+        while offset < in_flight
+        {
+            let len = (in_flight - offset).min(self.mss);
+            self.send_data(offset, len, out);
+            offset += len;
+        }
+        if self.fin_sent
+        {
+            self.emit(flags::FIN, self.snd_nxt.wrapping_sub(1), &[], out);
+        }
+    }
+
+    /// Verarbeitet ein Segment der Gegenseite.
+    fn receive(&mut self, segment: Segment, now: u64, out: &mut Vec<Packet>)
+    {
+        let has = |flag: u8| segment.flags & flag != 0;
+        match self.state
+        {
+            TcpState::Closed => return,
+            TcpState::SynSent =>
+            {
+                let ack_valid = segment.acknowledgment == self.iss.wrapping_add(1);
+                if has(flags::ACK) && !ack_valid
+                {
+                    if !has(flags::RST)
+                    {
+                        out.push(reset_for(&segment, self.local, self.remote));
+                    }
+                    return;
+                }
+                if has(flags::RST)
+                {
+                    if has(flags::ACK)
+                    {
+                        self.abort(SocketError::ConnectionRefused);
+                    }
+                    return;
+                }
+                if has(flags::SYN)
+                {
+                    self.rcv_nxt = segment.sequence.wrapping_add(1);
+                    self.snd_wnd = u32::from(segment.window);
+                    self.mss = segment.mss.map_or(DEFAULT_MSS, usize::from).min(LOCAL_MSS);
+                    if has(flags::ACK)
+                    {
+                        self.established();
+                        self.ack_pending = true;
+                    }
+                    else
+                    {
+                        // Gleichzeitiges Öffnen beider Seiten
+                        self.state = TcpState::SynReceived;
+                        self.send_syn(out);
+                    }
+                }
+                return;
+            }
+            _ => (),
+        }
+
+        if has(flags::RST)
+        {
+            if seq_le(self.rcv_nxt, segment.sequence) && seq_lt(segment.sequence, self.rcv_nxt.wrapping_add(self.receive_window().max(1)))
+            {
+                self.abort(SocketError::ConnectionReset);
+            }
+            return;
+        }
+        if self.state == TcpState::SynReceived && has(flags::SYN)
+        {
+            // Unser SYN-ACK ist verloren gegangen.
+            self.send_syn(out);
+            return;
+        }
+        let len = segment.len();
+        if len > 0 && seq_le(segment.sequence.wrapping_add(len), self.rcv_nxt)
+        {
+            // Bereits empfangen; die Bestätigung ging wohl verloren.
+            self.ack_pending = true;
+            return;
+        }
+        if has(flags::SYN)
+        {
+            self.reset(out);
+            self.error = Some(SocketError::ConnectionReset);
+            return;
+        }
+        if !has(flags::ACK)
+        {
+            return;
+        }
+
+        let ack = segment.acknowledgment;
+        if self.state == TcpState::SynReceived
+        {
+            if ack != self.iss.wrapping_add(1)
+            {
+                out.push(reset_for(&segment, self.local, self.remote));
+                return;
+            }
+            self.established();
+        }
+        if seq_lt(self.snd_nxt, ack)
+        {
+            // Bestätigt Ungesendetes
+            self.ack_pending = true;
+            return;
+        }
+        if seq_le(self.snd_una, ack)
+        {
+            self.snd_wnd = u32::from(segment.window);
+        }
+        if seq_lt(self.snd_una, ack)
+        {
+            let acked = ack.wrapping_sub(self.snd_una) as usize;
+            self.send_buffer.drain(..acked.min(self.send_buffer.len()));
+            self.snd_una = ack;
+            self.retries = 0;
+            self.rto = INITIAL_RTO_MS;
+            self.retransmit_at = (self.snd_una != self.snd_nxt).then_some(now + self.rto);
+        }
+        if self.fin_sent && self.snd_una == self.snd_nxt
+        {
+            match self.state
+            {
+                TcpState::FinWait1 => self.state = TcpState::FinWait2,
+                TcpState::Closing => self.enter_time_wait(now),
+                TcpState::LastAck =>
+                {
+                    self.state = TcpState::Closed;
+                    return;
+                }
+                _ => (),
+            }
+        }
+
+        if !matches!(self.state, TcpState::Established | TcpState::FinWait1 | TcpState::FinWait2) || len == 0
+        {
+            return;
+        }
+        if seq_lt(self.rcv_nxt, segment.sequence)
+        {
+            // Außerhalb der Reihenfolge: doppelte Bestätigung
+            self.ack_pending = true;
+            return;
+        }
+        let skip = self.rcv_nxt.wrapping_sub(segment.sequence) as usize;
+        let data = segment.payload.get(skip..).unwrap_or_default();
+        let take = data.len().min(self.receive_window() as usize);
+        self.receive_buffer.extend(&data[..take]);
+        self.rcv_nxt = self.rcv_nxt.wrapping_add(take as u32);
+        self.ack_pending = true;
+        if has(flags::FIN) && take == data.len()
+        {
+            self.rcv_nxt = self.rcv_nxt.wrapping_add(1);
+            self.fin_received = true;
+            match self.state
+            {
+                TcpState::Established => self.state = TcpState::CloseWait,
+                TcpState::FinWait1 => self.state = TcpState::Closing,
+                _ => self.enter_time_wait(now),
+            }
+        }
+    }
+
+    fn established(&mut self)
+    {
+        self.state = TcpState::Established;
+        self.snd_una = self.iss.wrapping_add(1);
+        self.retransmit_at = None;
+        self.retries = 0;
+        self.rto = INITIAL_RTO_MS;
+    }
+
+    fn enter_time_wait(&mut self, now: u64)
+    {
+        self.state = TcpState::TimeWait;
+        self.time_wait_until = now + TIME_WAIT_MS;
+        self.retransmit_at = None;
+    }
+
+    /// Holt empfangene Daten ab. Hat sich das Fenster dadurch merklich
+    /// geöffnet, erfährt die Gegenseite es mit der nächsten Ausgabe.
+    fn read(&mut self, buffer: &mut [u8]) -> usize
+    {
+        let len = buffer.len().min(self.receive_buffer.len());
+        for (target, byte) in buffer.iter_mut().zip(self.receive_buffer.drain(..len))
+        {
+            *target = byte;
+        }
+        if self.receive_window() >= self.advertised_window + self.mss as u32
+        {
+            self.ack_pending = true;
+        }
+        len
+    }
+}
+
+/// RST als Antwort auf ein Segment ohne passende Verbindung.
+fn reset_for(segment: &Segment, local: SocketAddrV4, remote: SocketAddrV4) -> Packet
+{
+    let (sequence, acknowledgment, flags) = if segment.flags & flags::ACK != 0
+    {
+        (segment.acknowledgment, 0, flags::RST)
+    }
+    else
+    {
+        (0, segment.sequence.wrapping_add(segment.len()), flags::RST | flags::ACK)
+    };
+    let reset = Segment { source_port: local.port(), destination_port: remote.port(), sequence, acknowledgment, flags, window: 0, mss: None, payload: Vec::new() };
+    Packet { source: *local.ip(), destination: *remote.ip(), protocol: protocol::TCP, payload: reset.to_bytes(*local.ip(), *remote.ip()), interface: None }
+}
+
+/// Ein Port im Zustand `Listen` mit den Verbindungen, die noch nicht
+/// angenommen wurden.
+#[derive(Debug)]
+struct Listener
+{
+    address: Ipv4Addr,
+    backlog: VecDeque<usize>,
+}
+
+/// Alle Verbindungen und Listener.
+#[derive(Debug, Default)]
+pub(super) struct Sockets
+{
+    connections: BTreeMap<usize, Connection>,
+    listeners: BTreeMap<u16, Listener>,
+    next_id: usize,
+    next_ephemeral: u16,
+    next_iss: u32,
+}
+
+impl Sockets
+{
+    pub(super) const fn new() -> Sockets
+    {
+        Sockets { connections: BTreeMap::new(), listeners: BTreeMap::new(), next_id: 0, next_ephemeral: 0, next_iss: 0 }
+    }
+
+    fn insert(&mut self, connection: Connection) -> usize
+    {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.connections.insert(id, connection);
+        id
+    }
+
+    /// Eine schwer vorhersagbare erste Folgenummer.
+    fn iss(&mut self, now: u64) -> u32
+    {
+        self.next_iss = self.next_iss.wrapping_add(0x0001_0000).wrapping_add((now as u32).wrapping_mul(250));
+        self.next_iss
+    }
+
+    /// Ordnet ein empfangenes Segment seiner Verbindung oder einem Listener
+    /// zu; sonst wird es mit RST beantwortet.
+    pub(super) fn receive(&mut self, packet: &Packet, now: u64, out: &mut Vec<Packet>)
+    {
+        let Some(segment) = Segment::parse(packet)
+        else
+        {
+            return;
+        };
+        let local = SocketAddrV4::new(packet.destination, segment.destination_port);
+        let remote = SocketAddrV4::new(packet.source, segment.source_port);
+
+        if let Some(connection) = self.connections.values_mut().find(|connection| connection.state != TcpState::Closed && connection.local == local && connection.remote == remote)
+        {
+            connection.receive(segment, now, out);
+            connection.output(now, out);
+            return;
+        }
+
+        if segment.flags & (flags::SYN | flags::ACK | flags::RST) == flags::SYN
+            && let Some(listener) = self.listeners.get(&local.port())
+            && (listener.address.is_unspecified() || listener.address == *local.ip())
+            && listener.backlog.len() < BACKLOG
+        {
+            let iss = self.iss(now);
+            let mut connection = Connection::new(local, remote, TcpState::SynReceived, iss, now);
+            connection.rcv_nxt = segment.sequence.wrapping_add(1);
+            connection.snd_wnd = u32::from(segment.window);
+            connection.mss = segment.mss.map_or(DEFAULT_MSS, usize::from).min(LOCAL_MSS);
+            // Bis zur Annahme gehört die Verbindung niemandem.
+            connection.orphaned = true;
+            connection.send_syn(out);
+            let id = self.insert(connection);
+            self.listeners.get_mut(&local.port()).unwrap().backlog.push_back(id);
+            return;
+        }
+
+        if segment.flags & flags::RST == 0
+        {
+            out.push(reset_for(&segment, local, remote));
+        }
+    }
+
+    /// Bedient die Zeitgeber und entfernt beendete Verbindungen.
+    pub(super) fn poll(&mut self, now: u64, out: &mut Vec<Packet>)
+    {
+        for connection in self.connections.values_mut()
+        {
+            connection.on_timer(now, out);
+        }
+        self.output(now, out);
+        self.connections.retain(|_, connection| !(connection.orphaned && connection.state == TcpState::Closed));
+        for listener in self.listeners.values_mut()
+        {
+            listener.backlog.retain(|id| self.connections.contains_key(id));
+        }
+    }
+
+    /// Sendet, was die Verbindungen zu senden haben.
+    pub(super) fn output(&mut self, now: u64, out: &mut Vec<Packet>)
+    {
+        for connection in self.connections.values_mut()
+        {
+            connection.output(now, out);
+        }
+    }
+}
+
+/// ## TcpListener
+///
+/// Nimmt Verbindungen an einem Port an. Beim Drop werden noch nicht
+/// angenommene Verbindungen abgebrochen.
+#[derive(Debug)]
+pub struct TcpListener
+{
+    local: SocketAddrV4,
+    timeout: Option<u64>,
+}
+
+impl TcpListener
+{
+    /// Öffnet `address` für eingehende Verbindungen. Port 0 wählt einen
+    /// freien Port, Adresse `0.0.0.0` nimmt an allen eigenen Adressen an.
+    pub fn bind(address: SocketAddrV4) -> Result<TcpListener, SocketError>
+    {
+        let mut stack = stack::lock();
+        if !address.ip().is_unspecified() && !stack.is_local(*address.ip())
+        {
+            return Err(SocketError::AddressNotAvailable);
+        }
+        let sockets = &mut stack.tcp;
+        let port = match address.port()
+        {
+            0 =>
+            {
+                let (listeners, connections) = (&sockets.listeners, &sockets.connections);
+                stack::ephemeral_port(&mut sockets.next_ephemeral, |port| listeners.contains_key(&port) || connections.values().any(|connection| connection.local.port() == port)).ok_or(SocketError::AddressInUse)?
+            }
+            port if sockets.listeners.contains_key(&port) => return Err(SocketError::AddressInUse),
+            port => port,
+        };
+        sockets.listeners.insert(port, Listener { address: *address.ip(), backlog: VecDeque::new() });
+        Ok(TcpListener { local: SocketAddrV4::new(*address.ip(), port), timeout: None })
+    }
+
+    pub fn local_addr(&self) -> SocketAddrV4
+    {
+        self.local
+    }
+
+    /// Legt fest, wie lange [accept](TcpListener::accept) wartet; `None`
+    /// wartet unbegrenzt.
+    pub fn set_timeout(&mut self, timeout_ms: Option<u64>)
+    {
+        self.timeout = timeout_ms;
+    }
+
+    /// Wartet auf die nächste aufgebaute Verbindung.
+    pub fn accept(&self) -> Result<(TcpStream, SocketAddrV4), SocketError>
+    {
+        let port = self.local.port();
+        stack::wait_for(self.timeout, |stack|
+        {
+            let sockets = &mut stack.tcp;
+            let backlog = &mut sockets.listeners.get_mut(&port)?.backlog;
+            let position = backlog.iter().position(|id| sockets.connections.get(id).is_some_and(|connection| connection.state != TcpState::SynReceived))?;
+            let id = backlog.remove(position)?;
+            let connection = sockets.connections.get_mut(&id)?;
+            connection.orphaned = false;
+            let stream = TcpStream { id, local: connection.local, peer: connection.remote, read_timeout: None };
+            Some(Ok((stream, connection.remote)))
+        })
+    }
+}
+
+impl Drop for TcpListener
+{
+    fn drop(&mut self)
+    {
+        let mut stack = stack::lock();
+        let mut out = Vec::new();
+        if let Some(listener) = stack.tcp.listeners.remove(&self.local.port())
+        {
+            for id in listener.backlog
+            {
+                if let Some(mut connection) = stack.tcp.connections.remove(&id)
+                {
+                    connection.reset(&mut out);
+                }
+            }
+        }
+        for packet in out
+        {
+            let _ = stack.transmit(packet);
+        }
+    }
+}
+
+/// ## TcpStream
+///
+/// Eine Verbindung nach dem Vorbild von `std::net::TcpStream`. Beim Drop
+/// wird sie geordnet geschlossen; liegen noch ungelesene Daten vor, wird sie
+/// mit RST abgebrochen.
+#[derive(Debug)]
+pub struct TcpStream
+{
+    id: usize,
+    local: SocketAddrV4,
+    peer: SocketAddrV4,
+    read_timeout: Option<u64>,
+}
+
+impl TcpStream
+{
+    /// Baut eine Verbindung zu `address` auf.
+    pub fn connect(address: SocketAddrV4) -> Result<TcpStream, SocketError>
+    {
+        if address.ip().is_unspecified() || address.port() == 0
+        {
+            return Err(SocketError::InvalidInput);
+        }
+        // Der Stream entsteht vor dem Senden, damit sein Drop bei Fehlern
+        // aufräumt – erst nachdem die Sperre wieder frei ist.
+        let (stream, sent) =
+        {
+            let mut stack = stack::lock();
+            let source = stack.source_address(*address.ip(), None)?;
+            let sockets = &mut stack.tcp;
+            let (listeners, connections) = (&sockets.listeners, &sockets.connections);
+            let port = stack::ephemeral_port(&mut sockets.next_ephemeral, |port| listeners.contains_key(&port) || connections.values().any(|connection| connection.local.port() == port)).ok_or(SocketError::AddressInUse)?;
+            let local = SocketAddrV4::new(source, port);
+            let now = stack::now();
+            let iss = sockets.iss(now);
+            let mut connection = Connection::new(local, address, TcpState::SynSent, iss, now);
+            let mut out = Vec::new();
+            connection.send_syn(&mut out);
+            let id = sockets.insert(connection);
+            let sent = out.into_iter().try_for_each(|packet| stack.transmit(packet));
+            (TcpStream { id, local, peer: address, read_timeout: None }, sent)
+        };
+        sent?;
+        stream.wait(None, |connection| match connection.state
+        {
+            TcpState::SynSent | TcpState::SynReceived => None,
+            TcpState::Closed => Some(Err(connection.error.unwrap_or(SocketError::ConnectionRefused))),
+            _ => Some(Ok(())),
+        })?;
+        Ok(stream)
+    }
+
+    /// Wartet mit [stack::wait_for], bis `condition` für die Verbindung ein
+    /// Ergebnis liefert.
+    fn wait<T>(&self, timeout_ms: Option<u64>, mut condition: impl FnMut(&mut Connection) -> Option<Result<T, SocketError>>) -> Result<T, SocketError>
+    {
+        stack::wait_for(timeout_ms, |stack| match stack.tcp.connections.get_mut(&self.id)
+        {
+            Some(connection) => condition(connection),
+            None => Some(Err(SocketError::NotConnected)),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddrV4
+    {
+        self.local
+    }
+
+    pub fn peer_addr(&self) -> SocketAddrV4
+    {
+        self.peer
+    }
+
+    /// Der aktuelle Zustand der Verbindung.
+    pub fn state(&self) -> TcpState
+    {
+        stack::lock().tcp.connections.get(&self.id).map_or(TcpState::Closed, |connection| connection.state)
+    }
+
+    /// Legt fest, wie lange [read](TcpStream::read) wartet; `None` wartet
+    /// unbegrenzt.
+    pub fn set_read_timeout(&mut self, timeout_ms: Option<u64>)
+    {
+        self.read_timeout = timeout_ms;
+    }
+
+    /// Wartet auf Daten und kopiert höchstens `buffer.len()` Bytes. 0 heißt,
+    /// dass die Gegenseite nichts mehr senden wird.
+    pub fn read(&self, buffer: &mut [u8]) -> Result<usize, SocketError>
+    {
+        if buffer.is_empty()
+        {
+            return Ok(0);
+        }
+        self.wait(self.read_timeout, |connection|
+        {
+            if !connection.receive_buffer.is_empty()
+            {
+                return Some(Ok(connection.read(buffer)));
+            }
+            if connection.fin_received
+            {
+                return Some(Ok(0));
+            }
+            if let Some(error) = connection.error
+            {
+                return Some(Err(error));
+            }
+            (connection.state == TcpState::Closed).then_some(Err(SocketError::NotConnected))
+        })
+    }
+
+    /// Reiht so viel von `data` zum Senden ein, wie in den Sendepuffer passt,
+    /// und wartet nur, solange dieser voll ist.
+    pub fn write(&self, data: &[u8]) -> Result<usize, SocketError>
+    {
+        if data.is_empty()
+        {
+            return Ok(0);
+        }
+        self.wait(None, |connection|
+        {
+            if let Some(error) = connection.error
+            {
+                return Some(Err(error));
+            }
+            if connection.fin_queued || !matches!(connection.state, TcpState::Established | TcpState::CloseWait)
+            {
+                return Some(Err(SocketError::NotConnected));
+            }
+            let len = data.len().min(BUFFER_SIZE - connection.send_buffer.len());
+            if len == 0
+            {
+                return None;
+            }
+            connection.send_buffer.extend(&data[..len]);
+            Some(Ok(len))
+        })
+    }
+
+    pub fn write_all(&self, mut data: &[u8]) -> Result<(), SocketError>
+    {
+        while !data.is_empty()
+        {
+            let len = self.write(data)?;
+            data = &data[len..];
+        }
+        Ok(())
+    }
+
+    /// Wartet, bis die Gegenseite alle Daten bestätigt hat.
+    pub fn flush(&self) -> Result<(), SocketError>
+    {
+        self.wait(None, |connection|
+        {
+            if let Some(error) = connection.error
+            {
+                return Some(Err(error));
+            }
+            connection.send_buffer.is_empty().then_some(Ok(()))
+        })
+    }
+
+    /// Schließt die Senderichtung: Nach den eingereihten Daten folgt FIN.
+    /// Lesen bleibt möglich.
+    pub fn shutdown(&self) -> Result<(), SocketError>
+    {
+        let mut stack = stack::lock();
+        let connection = stack.tcp.connections.get_mut(&self.id).ok_or(SocketError::NotConnected)?;
+        if !matches!(connection.state, TcpState::Established | TcpState::CloseWait)
+        {
+            return Err(SocketError::NotConnected);
+        }
+        connection.fin_queued = true;
+        stack.flush();
+        Ok(())
+    }
+}
+
+impl Drop for TcpStream
+{
+    fn drop(&mut self)
+    {
+        let mut stack = stack::lock();
+        let mut out = Vec::new();
+        if let Some(connection) = stack.tcp.connections.get_mut(&self.id)
+        {
+            connection.orphaned = true;
+            match connection.state
+            {
+                TcpState::SynSent | TcpState::SynReceived => connection.reset(&mut out),
+                TcpState::Established | TcpState::CloseWait if !connection.receive_buffer.is_empty() => connection.reset(&mut out),
+                TcpState::Established | TcpState::CloseWait => connection.fin_queued = true,
+                _ => (),
+            }
+        }
+        for packet in out
+        {
+            let _ = stack.transmit(packet);
+        }
+        stack.poll();
+    }
+}
+
+#[test_case]
+fn test_segment_round_trip()
+{
+    let source = Ipv4Addr::new(10, 0, 2, 15);
+    let destination = Ipv4Addr::new(10, 0, 2, 2);
+    let segment = Segment
+    {
+        source_port: 49152,
+        destination_port: 80,
+        sequence: 0xFFFF_FFFF,
+        acknowledgment: 0,
+        flags: flags::SYN,
+        window: 1024,
+        mss: Some(1460),
+        payload: Vec::new(),
+    };
+    let mut packet = Packet { source, destination, protocol: protocol::TCP, payload: segment.to_bytes(source, destination), interface: None };
+    assert_eq!(Segment::parse(&packet), Some(segment.clone()));
+    assert_eq!(segment.len(), 1);
+    packet.payload[4] ^= 1;
+    assert_eq!(Segment::parse(&packet), None);
+
+    assert!(seq_lt(0xFFFF_FFFF, 0) && seq_le(5, 5) && !seq_lt(5, 5));
+    assert!(seq_lt(0x7FFF_FFFF, 0x8000_0000) && !seq_lt(1, 0xFFFF_FFF0));
+}
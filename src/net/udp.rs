@@ -0,0 +1,260 @@
+//! User Datagram Protocol.
+//!
+//! | Offset | Größe | Inhalt |
+//! |--------|-------|--------|
+//! | 0 | 2 | Quellport |
+//! | 2 | 2 | Zielport |
+//! | 4 | 2 | Länge samt Kopf |
+//! | 6 | 2 | Prüfsumme über Pseudokopf, Kopf und Daten; 0 = keine |
+//!
+//! Ein [UdpSocket] ist an einen Port gebunden und sammelt die Datagramme, die
+//! dort ankommen, bis zu [QUEUE_LIMIT] Stück; weitere werden verworfen.
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::vec::Vec;
+use core::net::{Ipv4Addr, SocketAddrV4};
+
+use super::ipv4::{self, Packet, protocol};
+use super::{SocketError, stack};
+
+/// Länge des Kopfs.
+pub const HEADER_SIZE: usize = 8;
+
+/// Höchste Anzahl nicht abgeholter Datagramme je Socket.
+pub const QUEUE_LIMIT: usize = 64;
+
+/// Ein Datagramm.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Datagram<'a>
+{
+    pub source_port: u16,
+    pub destination_port: u16,
+    pub data: &'a [u8],
+}
+
+impl Datagram<'_>
+{
+    /// Zerlegt das Datagramm in `packet`; falsche Längen und Prüfsummen
+    /// ergeben `None`.
+    pub fn parse(packet: &Packet) -> Option<Datagram<'_>>
+    {
+        let data = &packet.payload;
+        if data.len() < HEADER_SIZE
+        {
+            return None;
+        }
+        let len = usize::from(u16::from_be_bytes([data[4], data[5]]));
+        if len < HEADER_SIZE || len > data.len()
+        {
+            return None;
+        }
+        let sum = u16::from_be_bytes([data[6], data[7]]);
+        if sum != 0 && ipv4::checksum(ipv4::pseudo_header_sum(packet.source, packet.destination, protocol::UDP, len), &data[..len]) != 0
+        {
+            return None;
+        }
+        Some(Datagram
+        {
+            source_port: u16::from_be_bytes([data[0], data[1]]),
+            destination_port: u16::from_be_bytes([data[2], data[3]]),
+            data: &data[HEADER_SIZE..len],
+        })
+    }
+
+    /// Setzt das Datagramm für ein Paket von `source` an `destination`
+    /// zusammen.
+    pub fn to_bytes(&self, source: Ipv4Addr, destination: Ipv4Addr) -> Vec<u8>
+    {
+        let len = HEADER_SIZE + self.data.len();
+        let mut data = Vec::with_capacity(len);
+        data.extend_from_slice(&self.source_port.to_be_bytes());
+        data.extend_from_slice(&self.destination_port.to_be_bytes());
+        data.extend_from_slice(&(len as u16).to_be_bytes());
+        data.extend_from_slice(&[0, 0]);
+        data.extend_from_slice(self.data);
+        // Eine berechnete 0 wird als 0xFFFF übertragen, da 0 „keine“ bedeutet.
+        let sum = match ipv4::checksum(ipv4::pseudo_header_sum(source, destination, protocol::UDP, len), &data)
+        {
+            0 => 0xFFFF,
+            sum => sum,
+        };
+        data[6..8].copy_from_slice(&sum.to_be_bytes());
+        data
+    }
+}
+
+/// Ein gebundener Port.
+#[derive(Debug)]
+struct Binding
+{
+    address: Ipv4Addr,
+    interface: Option<usize>,
+    queue: VecDeque<(SocketAddrV4, Vec<u8>)>,
+}
+
+/// Alle gebundenen Ports.
+#[derive(Debug, Default)]
+pub(super) struct Sockets
+{
+    bindings: BTreeMap<u16, Binding>,
+    next_ephemeral: u16,
+}
+
+impl Sockets
+{
+    pub(super) const fn new() -> Sockets
+    {
+        Sockets { bindings: BTreeMap::new(), next_ephemeral: 0 }
+    }
+
+    /// Reiht ein empfangenes Datagramm beim passenden Socket ein.
+    pub(super) fn receive(&mut self, packet: &Packet)
+    {
+        let Some(datagram) = Datagram::parse(packet)
+        else
+        {
+            return;
+        };
+        let Some(binding) = self.bindings.get_mut(&datagram.destination_port)
+        else
+        {
+            return;
+        };
+        if !binding.address.is_unspecified() && binding.address != packet.destination
+        {
+            return;
+        }
+        if binding.interface.is_some_and(|interface| packet.interface != Some(interface))
+        {
+            return;
+        }
+        if binding.queue.len() < QUEUE_LIMIT
+        {
+            binding.queue.push_back((SocketAddrV4::new(packet.source, datagram.source_port), datagram.data.to_vec()));
+        }
+    }
+}
+
+/// ## UdpSocket
+///
+/// Ein an einen Port gebundener UDP-Socket nach dem Vorbild von
+/// `std::net::UdpSocket`. Beim Drop wird der Port wieder frei.
+#[derive(Debug)]
+pub struct UdpSocket
+{
+    local: SocketAddrV4,
+    read_timeout: Option<u64>,
+}
+
+impl UdpSocket
+{
+    /// Bindet einen Socket an `address`. Port 0 wählt einen freien Port,
+    /// Adresse `0.0.0.0` empfängt an allen eigenen Adressen.
+    pub fn bind(address: SocketAddrV4) -> Result<UdpSocket, SocketError>
+    {
+        let mut stack = stack::lock();
+        if !address.ip().is_unspecified() && !stack.is_local(*address.ip())
+        {
+            return Err(SocketError::AddressNotAvailable);
+        }
+        let sockets = &mut stack.udp;
+        let port = match address.port()
+        {
+            0 => stack::ephemeral_port(&mut sockets.next_ephemeral, |port| sockets.bindings.contains_key(&port)).ok_or(SocketError::AddressInUse)?,
+            port if sockets.bindings.contains_key(&port) => return Err(SocketError::AddressInUse),
+            port => port,
+        };
+        sockets.bindings.insert(port, Binding { address: *address.ip(), interface: None, queue: VecDeque::new() });
+        Ok(UdpSocket { local: SocketAddrV4::new(*address.ip(), port), read_timeout: None })
+    }
+
+    /// Beschränkt Empfang und Versand auf das Gerät `name`. Nötig, solange
+    /// dieses noch keine Adresse hat, etwa für DHCP.
+    pub fn bind_device(&self, name: &str) -> Result<(), SocketError>
+    {
+        let mut stack = stack::lock();
+        let interface = stack.interface(name).ok_or(SocketError::NoDevice)?;
+        if let Some(binding) = stack.udp.bindings.get_mut(&self.local.port())
+        {
+            binding.interface = Some(interface);
+        }
+        Ok(())
+    }
+
+    pub fn local_addr(&self) -> SocketAddrV4
+    {
+        self.local
+    }
+
+    /// Legt fest, wie lange [recv_from](UdpSocket::recv_from) wartet;
+    /// `None` wartet unbegrenzt.
+    pub fn set_read_timeout(&mut self, timeout_ms: Option<u64>)
+    {
+        self.read_timeout = timeout_ms;
+    }
+
+    /// Sendet `data` als ein Datagramm an `destination` und gibt die Anzahl
+    /// gesendeter Bytes zurück.
+    pub fn send_to(&self, data: &[u8], destination: SocketAddrV4) -> Result<usize, SocketError>
+    {
+        if destination.port() == 0
+        {
+            return Err(SocketError::InvalidInput);
+        }
+        let mut stack = stack::lock();
+        let interface = stack.udp.bindings.get(&self.local.port()).and_then(|binding| binding.interface);
+        let source = match *self.local.ip()
+        {
+            address if address.is_unspecified() => stack.source_address(*destination.ip(), interface)?,
+            address => address,
+        };
+        let datagram = Datagram { source_port: self.local.port(), destination_port: destination.port(), data };
+        let packet = Packet
+        {
+            source,
+            destination: *destination.ip(),
+            protocol: protocol::UDP,
+            payload: datagram.to_bytes(source, *destination.ip()),
+            interface,
+        };
+        stack.transmit(packet)?;
+        Ok(data.len())
+    }
+
+    /// Wartet auf ein Datagramm und kopiert es nach `buffer`. Gibt die Länge
+    /// und den Absender zurück; was nicht in `buffer` passt, geht verloren.
+    pub fn recv_from(&self, buffer: &mut [u8]) -> Result<(usize, SocketAddrV4), SocketError>
+    {
+        let port = self.local.port();
+        stack::wait_for(self.read_timeout, |stack|
+        {
+            let (source, data) = stack.udp.bindings.get_mut(&port)?.queue.pop_front()?;
+            let len = data.len().min(buffer.len());
+            buffer[..len].copy_from_slice(&data[..len]);
+            Some(Ok((len, source)))
+        })
+    }
+}
+
+impl Drop for UdpSocket
+{
+    fn drop(&mut self)
+    {
+        stack::lock().udp.bindings.remove(&self.local.port());
+    }
+}
+
+#[test_case]
+fn test_datagram_round_trip()
+{
+    let source = Ipv4Addr::new(10, 0, 2, 15);
+    let destination = Ipv4Addr::new(10, 0, 2, 2);
+    let datagram = Datagram { source_port: 68, destination_port: 67, data: b"hello" };
+    let mut packet = Packet { source, destination, protocol: protocol::UDP, payload: datagram.to_bytes(source, destination), interface: None };
+    assert_eq!(Datagram::parse(&packet), Some(datagram));
+
+    packet.payload[8] ^= 1;
+    assert_eq!(Datagram::parse(&packet), None);
+    packet.payload[6..8].fill(0);
+    assert_eq!(Datagram::parse(&packet).map(|datagram| datagram.data), Some(&b"iello"[..]));
+}
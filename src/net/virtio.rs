@@ -0,0 +1,310 @@
+//! Treiber für virtio-net.
+//!
+//! Das Gerät hat zwei Warteschlangen: 0 zum Empfangen und 1 zum Senden. Vor
+//! jedem Frame steht ein Kopf mit Angaben zu Prüfsumme und Segmentierung, 10
+//! Bytes bei legacy- und 12 Bytes bei modernen Geräten. Da der Treiber beides
+//! nicht nutzt, sendet er ihn mit Nullen und ignoriert ihn beim Empfang.
+//!
+//! Sende- und Empfangspuffer liegen in je einem [DmaBuffer] mit Plätzen zu
+//! 2 KiB; jeder Platz wird als Kette aus Kopf und Frame übergeben:
+//!
+//! - **Empfang**: Alle Plätze stehen anfangs in der Warteschlange. Holt
+//!   [NetDevice::receive] einen Frame ab, wird sein Platz sofort wieder
+//!   eingestellt.
+//! - **Senden**: [NetDevice::send] nimmt einen freien Platz. Vom Gerät
+//!   versendete Frames geben ihre Plätze beim nächsten Senden wieder frei.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use super::{DEFAULT_MTU, ETHERNET_HEADER_SIZE, MacAddress, NetDevice, NetError, check_frame};
+use crate::interrupts::vector::Handler;
+use crate::memory::dma::DmaBuffer;
+use crate::pci::{self, DeviceId, PciDevice, PciDriver, PciError};
+use crate::sync::{IrqSpinlock, Once};
+use crate::virtio::{Buffer, DeviceType, Transport, VENDOR_ID, VirtQueue};
+
+const RECEIVE_QUEUE: u16 = 0;
+const TRANSMIT_QUEUE: u16 = 1;
+
+/// Höchste Anzahl Einträge je Warteschlange.
+const QUEUE_SIZE: u16 = 64;
+
+/// Größe eines Pufferplatzes und Lage des Frames darin.
+const SLOT_SIZE: usize = 2048;
+const FRAME_OFFSET: usize = 64;
+const FRAME_CAPACITY: usize = SLOT_SIZE - FRAME_OFFSET;
+
+/// Plätze je Richtung; jeder belegt zwei Deskriptoren.
+const SLOTS: usize = QUEUE_SIZE as usize / 2;
+
+/// Merkmale des Geräts.
+mod feature
+{
+    /// Die Konfiguration enthält die MTU.
+    pub const MTU: u64 = 1 << 3;
+    /// Die Konfiguration enthält die MAC-Adresse.
+    pub const MAC: u64 = 1 << 5;
+    /// Die Konfiguration enthält den Verbindungsstatus.
+    pub const STATUS: u64 = 1 << 16;
+}
+
+/// Offsets in der gerätespezifischen Konfiguration.
+mod config
+{
+    pub const MAC: u16 = 0;
+    pub const STATUS: u16 = 6;
+    pub const MTU: u16 = 10;
+}
+
+/// Bit 0 des Verbindungsstatus: Verbindung besteht.
+const LINK_UP: u16 = 1;
+
+/// Die Warteschlangen und ihre Pufferplätze.
+struct Queues
+{
+    transport: Transport,
+    header_size: u32,
+    receive: VirtQueue,
+    transmit: VirtQueue,
+    receive_buffers: DmaBuffer,
+    transmit_buffers: DmaBuffer,
+    /// Eingestellte Plätze: Kopf der Kette → Platz.
+    receiving: BTreeMap<u16, usize>,
+    transmitting: BTreeMap<u16, usize>,
+    transmit_free: Vec<usize>,
+}
+
+impl Queues
+{
+    /// Die Kette aus Kopf und Frame für einen Platz in `buffers`.
+    fn chain(&self, buffers: &DmaBuffer, slot: usize, frame_len: usize, writable: bool) -> [Buffer; 2]
+    {
+        let start = buffers.physical_address() + (slot * SLOT_SIZE) as u64;
+        [
+            Buffer { address: start, len: self.header_size, writable },
+            Buffer { address: start + FRAME_OFFSET as u64, len: frame_len as u32, writable },
+        ]
+    }
+
+    /// Stellt einen Empfangsplatz in die Warteschlange.
+    fn post_receive(&mut self, slot: usize)
+    {
+        let chain = self.chain(&self.receive_buffers, slot, FRAME_CAPACITY, true);
+        let head = self.receive.add(&chain).expect("virtio-net receive queue overflow");
+        self.receiving.insert(head, slot);
+    }
+
+    /// Gibt die Plätze versendeter Frames frei.
+    fn reap_transmitted(&mut self)
+    {
+        while let Some((head, _)) = self.transmit.pop_used()
+        {
+            if let Some(slot) = self.transmitting.remove(&head)
+            {
+                self.transmit_free.push(slot);
+            }
+        }
+    }
+}
+
+/// ## VirtioNet
+///
+/// Eine virtio-Netzwerkkarte.
+pub struct VirtioNet
+{
+    name: String,
+    mac: MacAddress,
+    mtu: usize,
+    status: bool,
+    msix: bool,
+    /// Anzahl der empfangenen Interrupts beider Warteschlangen.
+    interrupts: Arc<AtomicU64>,
+    queues: IrqSpinlock<Queues>,
+}
+
+impl VirtioNet
+{
+    /// Handelt die Merkmale aus, richtet beide Warteschlangen ein und stellt
+    /// alle Empfangsplätze bereit.
+    fn new(device: &Arc<PciDevice>) -> Result<VirtioNet, PciError>
+    {
+        let mut transport = Transport::new(device)?;
+        let features = transport.negotiate(feature::MTU | feature::MAC | feature::STATUS)?;
+        let interrupts = Arc::new(AtomicU64::new(0));
+        let msix = transport.enable_msix();
+        let handler = ||
+        {
+            msix.then(||
+            {
+                let interrupts = interrupts.clone();
+                Box::new(move || { interrupts.fetch_add(1, Ordering::Relaxed); }) as Handler
+            })
+        };
+        let receive = transport.setup_queue(RECEIVE_QUEUE, QUEUE_SIZE, handler())?;
+        let transmit = transport.setup_queue(TRANSMIT_QUEUE, QUEUE_SIZE, handler())?;
+
+        let mac = if features & feature::MAC != 0
+        {
+            MacAddress(core::array::from_fn(|index| transport.config_read_u8(config::MAC + index as u16)))
+        }
+        else
+        {
+            // Lokal verwaltete Adresse aus der Lage am PCI-Bus.
+            MacAddress([0x02, 0, 0, device.address.bus, device.address.device, device.address.function])
+        };
+        let mtu = if features & feature::MTU != 0 { usize::from(transport.config_read_u16(config::MTU)) } else { DEFAULT_MTU };
+
+        let slots = SLOTS.min(usize::from(receive.size().min(transmit.size())) / 2);
+        let buffers = || DmaBuffer::new(slots * SLOT_SIZE).ok_or(PciError::Io);
+        let mut queues = Queues
+        {
+            header_size: if transport.is_modern() { 12 } else { 10 },
+            receive_buffers: buffers()?,
+            transmit_buffers: buffers()?,
+            transport,
+            receive,
+            transmit,
+            receiving: BTreeMap::new(),
+            transmitting: BTreeMap::new(),
+            transmit_free: (0..slots).collect(),
+        };
+        for slot in 0..slots
+        {
+            queues.post_receive(slot);
+        }
+        queues.transport.finish();
+        queues.transport.notify(&queues.receive);
+
+        Ok(VirtioNet
+        {
+            name: super::next_name(),
+            mac,
+            mtu: mtu.min(FRAME_CAPACITY - ETHERNET_HEADER_SIZE),
+            status: features & feature::STATUS != 0,
+            msix,
+            interrupts,
+            queues: IrqSpinlock::new(queues),
+        })
+    }
+
+    /// Gibt an, ob das Gerät über MSI-X meldet.
+    pub fn uses_msix(&self) -> bool
+    {
+        self.msix
+    }
+
+    /// Anzahl der bisher empfangenen Interrupts.
+    pub fn interrupt_count(&self) -> u64
+    {
+        self.interrupts.load(Ordering::Relaxed)
+    }
+}
+
+impl NetDevice for VirtioNet
+{
+    fn name(&self) -> &str
+    {
+        &self.name
+    }
+
+    fn mac_address(&self) -> MacAddress
+    {
+        self.mac
+    }
+
+    fn mtu(&self) -> usize
+    {
+        self.mtu
+    }
+
+    fn link_up(&self) -> bool
+    {
+        // Ohne Statusmerkmal gilt die Verbindung als immer vorhanden.
+        !self.status || self.queues.lock().transport.config_read_u16(config::STATUS) & LINK_UP != 0
+    }
+
+    fn send(&self, frame: &[u8]) -> Result<(), NetError>
+    {
+        check_frame(self, frame)?;
+        let mut queues = self.queues.lock();
+        queues.reap_transmitted();
+        let slot = queues.transmit_free.pop().ok_or(NetError::QueueFull)?;
+
+        let offset = slot * SLOT_SIZE;
+        queues.transmit_buffers.write_bytes(offset, &[0; 12][..queues.header_size as usize]);
+        queues.transmit_buffers.write_bytes(offset + FRAME_OFFSET, frame);
+        let chain = queues.chain(&queues.transmit_buffers, slot, frame.len(), false);
+        let head = queues.transmit.add(&chain).expect("virtio-net transmit queue overflow");
+        queues.transmitting.insert(head, slot);
+        queues.transport.notify(&queues.transmit);
+        Ok(())
+    }
+
+    fn receive(&self) -> Option<Vec<u8>>
+    {
+        let mut queues = self.queues.lock();
+        let (head, len) = queues.receive.pop_used()?;
+        let slot = queues.receiving.remove(&head)?;
+        let len = (len as usize).saturating_sub(queues.header_size as usize).min(FRAME_CAPACITY);
+        let mut frame = vec![0; len];
+        queues.receive_buffers.read_bytes(slot * SLOT_SIZE + FRAME_OFFSET, &mut frame);
+
+        queues.post_receive(slot);
+        queues.transport.notify(&queues.receive);
+        Some(frame)
+    }
+}
+
+/// Alle erkannten virtio-Netzwerkkarten.
+static DEVICES: IrqSpinlock<Vec<Arc<VirtioNet>>> = IrqSpinlock::new(Vec::new());
+
+/// ## VirtioNetDriver
+///
+/// Übernimmt virtio-net-Geräte am PCI-Bus und meldet sie bei [crate::net] an.
+struct VirtioNetDriver;
+
+impl PciDriver for VirtioNetDriver
+{
+    fn name(&self) -> &str
+    {
+        "virtio-net"
+    }
+
+    fn ids(&self) -> &[DeviceId]
+    {
+        const IDS: [DeviceId; 2] = [
+            DeviceId::Device { vendor: VENDOR_ID, device: DeviceType::Network.transitional_id() },
+            DeviceId::Device { vendor: VENDOR_ID, device: DeviceType::Network.modern_id() },
+        ];
+        &IDS
+    }
+
+    fn probe(&self, device: &Arc<PciDevice>) -> Result<(), PciError>
+    {
+        let device = Arc::new(VirtioNet::new(device)?);
+        super::register(device.clone());
+        DEVICES.lock().push(device);
+        Ok(())
+    }
+}
+
+/// ## Netzwerkkarten erkennen
+///
+/// Meldet beim ersten Aufruf den Treiber bei [crate::pci] an. Er übernimmt
+/// alle bereits gefundenen und alle später von [pci::init] gefundenen
+/// virtio-net-Geräte. Gibt die bisher erkannten Karten zurück.
+pub fn init() -> Vec<Arc<VirtioNet>>
+{
+    static DRIVER: Once<()> = Once::new();
+    DRIVER.call_once(||
+    {
+        pci::register_driver(Arc::new(VirtioNetDriver));
+    });
+    DEVICES.lock().clone()
+}
@@ -0,0 +1,268 @@
+//! # Modul: pci
+//!
+//! Erkennung der Geräte am **PCI-Bus** und Zuordnung zu ihren Treibern.
+//!
+//! Jedes Gerät ist über Bus (0–255), Gerät (0–31) und Funktion (0–7)
+//! adressiert und beschreibt sich selbst in seinem Konfigurationsraum:
+//! Hersteller- und Gerätekennung, Klasse, die Adressbereiche seiner Register
+//! (BARs), Erweiterungen (Capabilities) und die Interrupt-Leitung.
+//!
+//! # Aufbau
+//!
+//! | Komponente | Aufgabe |
+//! |------------|---------|
+//! | [config] | Lesen und Schreiben des Konfigurationsraums (Ports oder ECAM) |
+//! | [PciDevice] | Header einer Funktion mit BARs und Capabilities |
+//! | [init], [devices], [find] | Einmaliges Durchsuchen aller Busse |
+//! | [PciDriver], [register_driver] | Treiber, die sich für Kennungen oder Klassen melden |
+//! | [msi] | Message Signaled Interrupts (MSI und MSI-X) mit eigenen Vektoren |
+//!
+//! # Treiber
+//!
+//! Ein Treiber nennt über [PciDriver::ids] die Geräte, die er unterstützt, und
+//! wird mit [register_driver] angemeldet. Für jedes passende Gerät, das noch
+//! keinen Treiber hat, wird [PciDriver::probe] aufgerufen – für bereits
+//! gefundene Geräte sofort, sonst bei [init]. Gibt `probe` einen Fehler zurück,
+//! bleibt das Gerät für andere Treiber frei.
+
+pub mod config;
+mod device;
+pub mod msi;
+
+pub use device::{Bar, Capability, PciDevice, capability, command, offset};
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::sync::{IrqSpinlock, Once};
+
+/// Adresse einer PCI-Funktion, angezeigt als `Bus:Gerät.Funktion`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PciAddress
+{
+    pub bus: u8,
+    pub device: u8,
+    pub function: u8,
+}
+
+impl PciAddress
+{
+    pub const fn new(bus: u8, device: u8, function: u8) -> PciAddress
+    {
+        PciAddress { bus, device, function }
+    }
+}
+
+impl fmt::Display for PciAddress
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{:02x}:{:02x}.{}", self.bus, self.device, self.function)
+    }
+}
+
+/// Fehler beim Übernehmen eines Geräts durch einen Treiber.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PciError
+{
+    /// Das Gerät oder eine benötigte Eigenschaft wird nicht unterstützt.
+    Unsupported,
+    /// Ein BAR fehlt oder ließ sich nicht abbilden.
+    BadBar,
+    /// Alle dynamischen Interrupt-Vektoren sind vergeben.
+    NoVectors,
+    /// Das Gerät hat einen Fehler gemeldet oder nicht geantwortet.
+    Io,
+}
+
+/// Woran ein Treiber seine Geräte erkennt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceId
+{
+    /// Hersteller- und Gerätekennung.
+    Device { vendor: u16, device: u16 },
+    /// Klasse und Unterklasse, z. B. `0x01, 0x01` für IDE-Controller.
+    Class { class: u8, subclass: u8 },
+}
+
+impl DeviceId
+{
+    pub fn matches(&self, device: &PciDevice) -> bool
+    {
+        match *self
+        {
+            DeviceId::Device { vendor, device: id } => device.vendor_id == vendor && device.device_id == id,
+            DeviceId::Class { class, subclass } => device.class == class && device.subclass == subclass,
+        }
+    }
+}
+
+/// ## PciDriver
+///
+/// Schnittstelle der Treiber für PCI-Geräte.
+pub trait PciDriver: Send + Sync
+{
+    /// Name des Treibers, z. B. `"virtio-blk"`.
+    fn name(&self) -> &str;
+
+    /// Die unterstützten Geräte.
+    fn ids(&self) -> &[DeviceId];
+
+    /// Übernimmt `device`. Bei einem Fehler bleibt das Gerät frei.
+    fn probe(&self, device: &Arc<PciDevice>) -> Result<(), PciError>;
+}
+
+/// Alle Funktionen, nach Adresse sortiert; wird von [init] gefüllt.
+static DEVICES: Once<Vec<Arc<PciDevice>>> = Once::new();
+
+/// Angemeldete Treiber in der Reihenfolge ihrer Anmeldung.
+static DRIVERS: IrqSpinlock<Vec<Arc<dyn PciDriver>>> = IrqSpinlock::new(Vec::new());
+
+/// Treiber, die ein Gerät übernommen haben.
+static BOUND: IrqSpinlock<BTreeMap<PciAddress, Arc<dyn PciDriver>>> = IrqSpinlock::new(BTreeMap::new());
+
+/// Sucht alle Funktionen auf allen Bussen. Funktionen 1–7 werden nur
+/// abgefragt, wenn Funktion 0 im Header-Typ weitere Funktionen ankündigt.
+fn scan() -> Vec<Arc<PciDevice>>
+{
+    let mut devices = Vec::new();
+    for bus in 0..=255
+    {
+        for slot in 0..32
+        {
+            let address = PciAddress::new(bus, slot, 0);
+            if config::read_u16(address, offset::VENDOR_ID) == 0xFFFF
+            {
+                continue;
+            }
+            let functions = if config::read_u8(address, offset::HEADER_TYPE) & 0x80 != 0 { 8 } else { 1 };
+            devices.extend((0..functions).filter_map(|function| PciDevice::probe(PciAddress::new(bus, slot, function))).map(Arc::new));
+        }
+    }
+    devices
+}
+
+/// ## Initialisierung
+///
+/// Richtet den Zugriff auf den Konfigurationsraum ein, durchsucht beim ersten
+/// Aufruf alle Busse und bietet die gefundenen Geräte den angemeldeten
+/// Treibern an. Gibt alle Geräte zurück.
+pub fn init() -> &'static [Arc<PciDevice>]
+{
+    let mut first = false;
+    let devices = DEVICES.call_once(||
+    {
+        first = true;
+        config::init();
+        scan()
+    });
+    if first
+    {
+        let drivers = DRIVERS.lock().clone();
+        for driver in drivers
+        {
+            bind(&driver, devices);
+        }
+    }
+    devices
+}
+
+/// Die von [init] gefundenen Geräte, leer vor dem ersten Aufruf.
+pub fn devices() -> &'static [Arc<PciDevice>]
+{
+    DEVICES.get().map(Vec::as_slice).unwrap_or_default()
+}
+
+/// Das erste Gerät mit Hersteller `vendor` und Kennung `device`.
+pub fn find(vendor: u16, device: u16) -> Option<Arc<PciDevice>>
+{
+    devices().iter().find(|candidate| DeviceId::Device { vendor, device }.matches(candidate)).cloned()
+}
+
+/// Meldet `driver` an und bietet ihm alle bereits gefundenen, freien Geräte
+/// an. Gibt die Anzahl der übernommenen Geräte zurück.
+pub fn register_driver(driver: Arc<dyn PciDriver>) -> usize
+{
+    DRIVERS.lock().push(driver.clone());
+    bind(&driver, devices())
+}
+
+/// Name des Treibers, der das Gerät an `address` übernommen hat.
+pub fn driver_name(address: PciAddress) -> Option<String>
+{
+    BOUND.lock().get(&address).map(|driver| driver.name().into())
+}
+
+/// Ruft [PciDriver::probe] für alle passenden, freien Geräte auf.
+fn bind(driver: &Arc<dyn PciDriver>, devices: &[Arc<PciDevice>]) -> usize
+{
+    let mut bound = 0;
+    for device in devices.iter().filter(|device| driver.ids().iter().any(|id| id.matches(device)))
+    {
+        if BOUND.lock().contains_key(&device.address)
+        {
+            continue;
+        }
+        // Ohne Sperre, da probe schlafen oder selbst auf PCI zugreifen kann.
+        if driver.probe(device).is_ok()
+        {
+            BOUND.lock().insert(device.address, driver.clone());
+            bound += 1;
+        }
+    }
+    bound
+}
+
+#[test_case]
+fn test_host_bridge_is_found()
+{
+    let devices = init();
+    let host = devices.iter().find(|device| device.address == PciAddress::new(0, 0, 0)).expect("no device at 00:00.0");
+    assert_eq!((host.class, host.subclass), (0x06, 0x00));
+    assert!(devices.windows(2).all(|pair| pair[0].address < pair[1].address));
+}
+
+#[test_case]
+fn test_ide_controller_bars()
+{
+    // Der IDE-Controller (PIIX) der Standardmaschine von QEMU nutzt für seine
+    // Kanäle die festen Ports; nur der Bus-Master-Bereich liegt in BAR 4.
+    let ide = init().iter().find(|device| DeviceId::Class { class: 0x01, subclass: 0x01 }.matches(device));
+    if let Some(ide) = ide
+    {
+        assert!(matches!(ide.bars[4], Some(Bar::Io { size: 16, .. })));
+    }
+}
+
+#[test_case]
+fn test_driver_matching()
+{
+    struct Bridges;
+
+    impl PciDriver for Bridges
+    {
+        fn name(&self) -> &str
+        {
+            "test-host-bridge"
+        }
+
+        fn ids(&self) -> &[DeviceId]
+        {
+            &[DeviceId::Class { class: 0x06, subclass: 0x00 }]
+        }
+
+        fn probe(&self, device: &Arc<PciDevice>) -> Result<(), PciError>
+        {
+            if device.address.function == 0 { Ok(()) } else { Err(PciError::Unsupported) }
+        }
+    }
+
+    init();
+    assert!(register_driver(Arc::new(Bridges)) >= 1);
+    assert_eq!(driver_name(PciAddress::new(0, 0, 0)).as_deref(), Some("test-host-bridge"));
+    // Bereits übernommene Geräte werden nicht erneut angeboten.
+    assert_eq!(register_driver(Arc::new(Bridges)), 0);
+}
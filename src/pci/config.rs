@@ -0,0 +1,152 @@
+//! Zugriff auf den Konfigurationsraum von PCI-Funktionen.
+//!
+//! Jede Funktion hat 256 Bytes (PCI Express: 4 KiB) Konfigurationsraum. Es gibt
+//! zwei Wege dorthin:
+//!
+//! - **Ports `0xCF8`/`0xCFC`**: Die Adresse des Doppelworts wird nach `0xCF8`
+//!   geschrieben, danach wird es über `0xCFC` gelesen oder geschrieben. Das
+//!   funktioniert auf jedem PC, erreicht aber nur die ersten 256 Bytes.
+//!
+//!   | Bit | Inhalt |
+//!   |-----|--------|
+//!   | 31 | Freigabe |
+//!   | 16–23 | Bus |
+//!   | 11–15 | Gerät |
+//!   | 8–10 | Funktion |
+//!   | 2–7 | Doppelwort im Konfigurationsraum |
+//!
+//! - **ECAM** (Enhanced Configuration Access Mechanism): Der gesamte
+//!   Konfigurationsraum liegt im Speicher, 4 KiB pro Funktion ab
+//!   `Basis + (Bus << 20 | Gerät << 15 | Funktion << 12)`. Die Basis steht in der
+//!   ACPI-Tabelle `MCFG`.
+//!
+//! [init] richtet ECAM ein, sofern die Firmware es anbietet; Busse außerhalb
+//! des ECAM-Bereichs werden weiter über die Ports angesprochen.
+
+use x86_64::instructions::port::Port;
+use x86_64::{PhysAddr, VirtAddr};
+
+use super::PciAddress;
+use crate::acpi;
+use crate::memory;
+use crate::sync::{IrqSpinlock, Once};
+
+const CONFIG_ADDRESS: u16 = 0xCF8;
+const CONFIG_DATA: u16 = 0xCFC;
+
+/// Offset des ersten Eintrags hinter Kopf und reserviertem Feld der `MCFG`.
+const MCFG_ENTRIES: usize = acpi::HEADER_SIZE + 8;
+
+/// Größe eines Eintrags der `MCFG`.
+const MCFG_ENTRY_SIZE: usize = 16;
+
+/// Speicherbereich für den Konfigurationsraum der Busse `start_bus..=end_bus`.
+#[derive(Debug, Clone, Copy)]
+pub struct Ecam
+{
+    pub base: PhysAddr,
+    pub start_bus: u8,
+    pub end_bus: u8,
+    /// Virtuelle Adresse von `base`.
+    virtual_base: VirtAddr,
+}
+
+/// Schützt das Paar aus Adress- und Datenport.
+static PORTS: IrqSpinlock<(Port<u32>, Port<u32>)> = IrqSpinlock::new((Port::new(CONFIG_ADDRESS), Port::new(CONFIG_DATA)));
+
+/// ECAM-Bereich von Segment 0, falls vorhanden.
+static ECAM: Once<Option<Ecam>> = Once::new();
+
+/// Sucht den ECAM-Bereich in der ACPI-Tabelle `MCFG` und bildet ihn ab.
+pub fn init() -> Option<Ecam>
+{
+    *ECAM.call_once(||
+    {
+        let mcfg = acpi::find_table(b"MCFG")?;
+        let entry = mcfg.get(MCFG_ENTRIES..)?.chunks_exact(MCFG_ENTRY_SIZE).find(|entry| entry[8..10] == [0, 0])?;
+        let base = PhysAddr::new(u64::from_le_bytes(entry[..8].try_into().unwrap()));
+        let (start_bus, end_bus) = (entry[10], entry[11]);
+        let size = (u64::from(end_bus - start_bus) + 1) << 20;
+        let virtual_base = memory::map_mmio(base, size).ok()?;
+        Some(Ecam { base, start_bus, end_bus, virtual_base })
+    })
+}
+
+/// Der ECAM-Bereich, wenn [init] einen gefunden hat.
+pub fn ecam() -> Option<Ecam>
+{
+    ECAM.get().copied().flatten()
+}
+
+/// Adresse des Doppelworts `offset` im Konfigurationsraum von `address`, wenn
+/// es über ECAM erreichbar ist.
+fn ecam_pointer(address: PciAddress, offset: u16) -> Option<*mut u32>
+{
+    let ecam = ecam().filter(|ecam| (ecam.start_bus..=ecam.end_bus).contains(&address.bus))?;
+    let function = u64::from(address.bus - ecam.start_bus) << 20 | u64::from(address.device) << 15 | u64::from(address.function) << 12;
+    Some((ecam.virtual_base + function + u64::from(offset & !3)).as_mut_ptr())
+}
+
+/// Wert für [CONFIG_ADDRESS].
+fn port_address(address: PciAddress, offset: u16) -> u32
+{
+    assert!(offset < 256, "offset {:#x} needs ECAM", offset);
+    1 << 31 | u32::from(address.bus) << 16 | u32::from(address.device) << 11 | u32::from(address.function) << 8 | u32::from(offset & 0xFC)
+}
+
+/// Liest das Doppelwort, in dem `offset` liegt.
+pub fn read_u32(address: PciAddress, offset: u16) -> u32
+{
+    if let Some(pointer) = ecam_pointer(address, offset)
+    {
+        return unsafe { pointer.read_volatile() };
+    }
+    let mut ports = PORTS.lock();
+    unsafe
+    {
+        ports.0.write(port_address(address, offset));
+        ports.1.read()
+    }
+}
+
+/// Schreibt das Doppelwort, in dem `offset` liegt.
+pub fn write_u32(address: PciAddress, offset: u16, value: u32)
+{
+    if let Some(pointer) = ecam_pointer(address, offset)
+    {
+        unsafe { pointer.write_volatile(value) };
+        return;
+    }
+    let mut ports = PORTS.lock();
+    unsafe
+    {
+        ports.0.write(port_address(address, offset));
+        ports.1.write(value);
+    }
+}
+
+pub fn read_u16(address: PciAddress, offset: u16) -> u16
+{
+    (read_u32(address, offset) >> ((offset & 2) * 8)) as u16
+}
+
+pub fn read_u8(address: PciAddress, offset: u16) -> u8
+{
+    (read_u32(address, offset) >> ((offset & 3) * 8)) as u8
+}
+
+/// Schreibt ein Wort. Die übrigen Bytes des Doppelworts werden dazu gelesen und
+/// unverändert zurückgeschrieben.
+pub fn write_u16(address: PciAddress, offset: u16, value: u16)
+{
+    let shift = (offset & 2) * 8;
+    let old = read_u32(address, offset) & !(0xFFFF << shift);
+    write_u32(address, offset, old | u32::from(value) << shift);
+}
+
+pub fn write_u8(address: PciAddress, offset: u16, value: u8)
+{
+    let shift = (offset & 3) * 8;
+    let old = read_u32(address, offset) & !(0xFF << shift);
+    write_u32(address, offset, old | u32::from(value) << shift);
+}
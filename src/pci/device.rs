@@ -0,0 +1,344 @@
+//! Beschreibung einer PCI-Funktion aus ihrem Konfigurationsraum.
+//!
+//! Die ersten 64 Bytes (Header) haben einen festen Aufbau; die wichtigsten
+//! Felder für Header-Typ 0 (Geräte) und 1 (PCI-zu-PCI-Brücken):
+//!
+//! | Offset | Größe | Inhalt |
+//! |--------|-------|--------|
+//! | `0x00` | 2 | Hersteller (`0xFFFF`: keine Funktion vorhanden) |
+//! | `0x02` | 2 | Gerät |
+//! | `0x04` | 2 | Befehlsregister |
+//! | `0x06` | 2 | Statusregister |
+//! | `0x08` | 4 | Revision, Programmierschnittstelle, Unterklasse, Klasse |
+//! | `0x0E` | 1 | Header-Typ, Bit 7: weitere Funktionen |
+//! | `0x10` | 24 | Base Address Registers (Typ 1: nur zwei) |
+//! | `0x2C` | 4 | Subsystem-Hersteller und -Gerät (nur Typ 0) |
+//! | `0x34` | 1 | Erste Capability |
+//! | `0x3C` | 1 | Interrupt-Leitung am PIC |
+//! | `0x3D` | 1 | Interrupt-Pin (1 = INTA# … 4 = INTD#, 0 = keiner) |
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::{PciAddress, config};
+
+/// Offsets im Header.
+pub mod offset
+{
+    pub const VENDOR_ID: u16 = 0x00;
+    pub const DEVICE_ID: u16 = 0x02;
+    pub const COMMAND: u16 = 0x04;
+    pub const STATUS: u16 = 0x06;
+    pub const CLASS: u16 = 0x08;
+    pub const HEADER_TYPE: u16 = 0x0E;
+    pub const BAR0: u16 = 0x10;
+    pub const SECONDARY_BUS: u16 = 0x19;
+    pub const SUBSYSTEM: u16 = 0x2C;
+    pub const CAPABILITIES: u16 = 0x34;
+    pub const INTERRUPT_LINE: u16 = 0x3C;
+    pub const INTERRUPT_PIN: u16 = 0x3D;
+}
+
+/// Bits des Befehlsregisters.
+pub mod command
+{
+    pub const IO_SPACE: u16 = 1 << 0;
+    pub const MEMORY_SPACE: u16 = 1 << 1;
+    pub const BUS_MASTER: u16 = 1 << 2;
+    pub const INTERRUPT_DISABLE: u16 = 1 << 10;
+}
+
+/// Kennungen von Capabilities.
+pub mod capability
+{
+    pub const POWER_MANAGEMENT: u8 = 0x01;
+    pub const MSI: u8 = 0x05;
+    pub const VENDOR_SPECIFIC: u8 = 0x09;
+    pub const PCI_EXPRESS: u8 = 0x10;
+    pub const MSI_X: u8 = 0x11;
+}
+
+/// Statusbit: Die Funktion hat eine Liste von Capabilities.
+const STATUS_CAPABILITIES: u16 = 1 << 4;
+
+/// Höchste Anzahl an Capabilities, bevor eine Liste als Schleife gilt.
+const MAX_CAPABILITIES: usize = 48;
+
+/// ## Bar
+///
+/// Ein Base Address Register: ein Bereich im Speicher- oder I/O-Adressraum,
+/// über den die Register des Geräts erreichbar sind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bar
+{
+    Memory
+    {
+        address: u64,
+        size: u64,
+        prefetchable: bool,
+        /// Die Adresse belegt auch das folgende Register (64 Bit).
+        wide: bool,
+    },
+    Io
+    {
+        port: u16,
+        size: u16,
+    },
+}
+
+impl Bar
+{
+    /// Physische Adresse bzw. erster Port.
+    pub fn address(&self) -> u64
+    {
+        match *self
+        {
+            Bar::Memory { address, .. } => address,
+            Bar::Io { port, .. } => u64::from(port),
+        }
+    }
+
+    /// Größe des Bereichs in Bytes.
+    pub fn size(&self) -> u64
+    {
+        match *self
+        {
+            Bar::Memory { size, .. } => size,
+            Bar::Io { size, .. } => u64::from(size),
+        }
+    }
+}
+
+/// Eine Capability: eine Erweiterung des Konfigurationsraums (z. B. MSI), die
+/// ab `offset` liegt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capability
+{
+    pub id: u8,
+    pub offset: u8,
+}
+
+/// ## PciDevice
+///
+/// Eine gefundene PCI-Funktion mit den Feldern ihres Headers. Die BARs sind
+/// nach Index geordnet; der obere Teil einer 64-Bit-Adresse bleibt `None`.
+#[derive(Debug, Clone)]
+pub struct PciDevice
+{
+    pub address: PciAddress,
+    pub vendor_id: u16,
+    pub device_id: u16,
+    pub class: u8,
+    pub subclass: u8,
+    pub prog_if: u8,
+    pub revision: u8,
+    /// Header-Typ ohne das Bit für weitere Funktionen.
+    pub header_type: u8,
+    pub subsystem_vendor_id: u16,
+    pub subsystem_id: u16,
+    pub bars: [Option<Bar>; 6],
+    pub interrupt_line: u8,
+    pub interrupt_pin: u8,
+    pub capabilities: Vec<Capability>,
+}
+
+impl PciDevice
+{
+    /// Liest den Header der Funktion an `address`, sofern sie existiert.
+    pub fn probe(address: PciAddress) -> Option<PciDevice>
+    {
+        let vendor_id = config::read_u16(address, offset::VENDOR_ID);
+        if vendor_id == 0xFFFF
+        {
+            return None;
+        }
+        let [revision, prog_if, subclass, class] = config::read_u32(address, offset::CLASS).to_le_bytes();
+        let header_type = config::read_u8(address, offset::HEADER_TYPE) & 0x7F;
+        let (subsystem_vendor_id, subsystem_id) = match header_type
+        {
+            0 =>
+            {
+                let subsystem = config::read_u32(address, offset::SUBSYSTEM);
+                (subsystem as u16, (subsystem >> 16) as u16)
+            }
+            _ => (0, 0),
+        };
+
+        let mut device = PciDevice
+        {
+            address,
+            vendor_id,
+            device_id: config::read_u16(address, offset::DEVICE_ID),
+            class,
+            subclass,
+            prog_if,
+            revision,
+            header_type,
+            subsystem_vendor_id,
+            subsystem_id,
+            bars: [None; 6],
+            interrupt_line: config::read_u8(address, offset::INTERRUPT_LINE),
+            interrupt_pin: config::read_u8(address, offset::INTERRUPT_PIN),
+            capabilities: Vec::new(),
+        };
+        device.read_bars();
+        device.read_capabilities();
+        Some(device)
+    }
+
+    /// Anzahl der BARs für den Header-Typ.
+    fn bar_count(&self) -> usize
+    {
+        match self.header_type
+        {
+            0 => 6,
+            1 => 2,
+            _ => 0,
+        }
+    }
+
+    /// Liest alle BARs und ermittelt ihre Größe: Nach dem Schreiben von lauter
+    /// Einsen bleiben nur die Adressbits gesetzt, die das Gerät dekodiert.
+    /// Währenddessen sind Speicher- und I/O-Zugriffe des Geräts abgeschaltet.
+    fn read_bars(&mut self)
+    {
+        let command = self.command();
+        self.set_command(command & !(command::IO_SPACE | command::MEMORY_SPACE));
+
+        let count = self.bar_count();
+        let mut index = 0;
+        while index < count
+        {
+            let register = offset::BAR0 + 4 * index as u16;
+            let (value, mask) = self.probe_register(register);
+            if value & 1 == 1
+            {
+                let mask = (mask & 0xFFFC) as u16;
+                if mask != 0
+                {
+                    self.bars[index] = Some(Bar::Io { port: (value & 0xFFFC) as u16, size: (!mask).wrapping_add(1) });
+                }
+            }
+            else
+            {
+                let wide = (value >> 1) & 0x3 == 0x2 && index + 1 < count;
+                let (high, high_mask) = if wide { self.probe_register(register + 4) } else { (0, u32::MAX) };
+                if mask & !0xF != 0 || (wide && high_mask != 0)
+                {
+                    let address = u64::from(high) << 32 | u64::from(value & !0xF);
+                    let mask = u64::from(high_mask) << 32 | u64::from(mask & !0xF);
+                    let prefetchable = value & 0x8 != 0;
+                    self.bars[index] = Some(Bar::Memory { address, size: (!mask).wrapping_add(1), prefetchable, wide });
+                }
+                if wide
+                {
+                    index += 1;
+                }
+            }
+            index += 1;
+        }
+
+        self.set_command(command);
+    }
+
+    /// Liest ein BAR und die Maske seiner beschreibbaren Bits und stellt den
+    /// alten Wert wieder her.
+    fn probe_register(&self, register: u16) -> (u32, u32)
+    {
+        let value = config::read_u32(self.address, register);
+        config::write_u32(self.address, register, u32::MAX);
+        let mask = config::read_u32(self.address, register);
+        config::write_u32(self.address, register, value);
+        (value, mask)
+    }
+
+    /// Folgt der verketteten Liste der Capabilities ab [offset::CAPABILITIES].
+    fn read_capabilities(&mut self)
+    {
+        if config::read_u16(self.address, offset::STATUS) & STATUS_CAPABILITIES == 0 || self.header_type > 1
+        {
+            return;
+        }
+        let mut next = config::read_u8(self.address, offset::CAPABILITIES) & !0x3;
+        while next != 0 && self.capabilities.len() < MAX_CAPABILITIES
+        {
+            let header = config::read_u16(self.address, u16::from(next));
+            self.capabilities.push(Capability { id: header as u8, offset: next });
+            next = (header >> 8) as u8 & !0x3;
+        }
+    }
+
+    /// Offset der ersten Capability mit der Kennung `id`.
+    pub fn capability(&self, id: u8) -> Option<u8>
+    {
+        self.capabilities.iter().find(|capability| capability.id == id).map(|capability| capability.offset)
+    }
+
+    pub fn command(&self) -> u16
+    {
+        config::read_u16(self.address, offset::COMMAND)
+    }
+
+    pub fn set_command(&self, value: u16)
+    {
+        config::write_u16(self.address, offset::COMMAND, value);
+    }
+
+    /// Schaltet die Dekodierung der BARs und, mit `bus_master`, DMA des Geräts ein.
+    pub fn enable(&self, bus_master: bool)
+    {
+        let mut command = self.command() | command::IO_SPACE | command::MEMORY_SPACE;
+        if bus_master
+        {
+            command |= command::BUS_MASTER;
+        }
+        self.set_command(command);
+    }
+
+    /// Gibt an, ob es sich um eine PCI-zu-PCI-Brücke handelt.
+    pub fn is_bridge(&self) -> bool
+    {
+        self.header_type == 1
+    }
+
+    /// Bezeichnung der Klasse, z. B. `"IDE controller"`.
+    pub fn class_name(&self) -> &'static str
+    {
+        match (self.class, self.subclass)
+        {
+            (0x01, 0x01) => "IDE controller",
+            (0x01, 0x06) => "SATA controller",
+            (0x01, 0x08) => "NVMe controller",
+            (0x01, _) => "mass storage controller",
+            (0x02, 0x00) => "Ethernet controller",
+            (0x02, _) => "network controller",
+            (0x03, 0x00) => "VGA controller",
+            (0x03, _) => "display controller",
+            (0x04, _) => "multimedia controller",
+            (0x05, _) => "memory controller",
+            (0x06, 0x00) => "host bridge",
+            (0x06, 0x01) => "ISA bridge",
+            (0x06, 0x04) => "PCI bridge",
+            (0x06, _) => "bridge",
+            (0x07, _) => "communication controller",
+            (0x08, _) => "system peripheral",
+            (0x0C, 0x03) => "USB controller",
+            (0x0C, 0x05) => "SMBus controller",
+            (0x0C, _) => "serial bus controller",
+            (0xFF, _) => "unassigned class",
+            _ => "unknown device",
+        }
+    }
+}
+
+impl fmt::Display for PciDevice
+{
+    /// Eine Zeile wie `00:01.1 [8086:7010] IDE controller (01:01.80)`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(
+            f, "{} [{:04x}:{:04x}] {} ({:02x}:{:02x}.{:02x})",
+            self.address, self.vendor_id, self.device_id, self.class_name(), self.class, self.subclass, self.prog_if
+        )
+    }
+}
@@ -0,0 +1,244 @@
+//! Message Signaled Interrupts für PCI-Geräte.
+//!
+//! Statt eine mit anderen Geräten geteilte Interrupt-Leitung zu ziehen,
+//! schreibt das Gerät eine Nachricht an eine Adresse, die der Local APIC
+//! annimmt (siehe [crate::interrupts::apic]). Der Wert der Nachricht ist der
+//! Vektor in der IDT; jeder Vektor gehört damit genau einem Gerät – bei MSI-X
+//! sogar einer einzelnen Warteschlange des Geräts.
+//!
+//! **MSI** (Capability `0x05`) liegt im Konfigurationsraum:
+//!
+//! | Offset | Größe | Inhalt |
+//! |--------|-------|--------|
+//! | 2 | 2 | Steuerung: Bit 0 an, Bit 7 64-Bit-Adresse, Bit 8 Maskierbar |
+//! | 4 | 4 | Adresse |
+//! | 8 | 4 | oberer Teil der Adresse (nur mit 64 Bit) |
+//! | 8 bzw. 12 | 2 | Nachricht |
+//!
+//! **MSI-X** (Capability `0x11`) hat eine Tabelle mit bis zu 2048 Einträgen in
+//! einem BAR des Geräts:
+//!
+//! | Offset | Größe | Inhalt |
+//! |--------|-------|--------|
+//! | 2 | 2 | Steuerung: Bits 0–10 Tabellengröße - 1, Bit 14 alle maskieren, Bit 15 an |
+//! | 4 | 4 | Offset der Tabelle, Bits 0–2: Index des BARs |
+//!
+//! Jeder Eintrag der Tabelle ist 16 Bytes groß: Adresse (64 Bit), Nachricht und
+//! Steuerung mit Bit 0 zum Maskieren.
+//!
+//! Solange MSI oder MSI-X eingeschaltet sind, ist der klassische Interrupt
+//! (INTx) des Geräts abgeschaltet.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use x86_64::{PhysAddr, VirtAddr};
+
+use super::{Bar, PciDevice, PciError, capability, command, config};
+use crate::interrupts::apic;
+use crate::interrupts::vector::{self, Handler, Vector};
+use crate::memory;
+
+/// Feste obere Bits der Adresse einer MSI-Nachricht.
+const MESSAGE_ADDRESS: u32 = 0xFEE0_0000;
+
+const MSI_ENABLE: u16 = 1 << 0;
+const MSI_MULTIPLE_ENABLE: u16 = 0x7 << 4;
+const MSI_64_BIT: u16 = 1 << 7;
+
+const MSIX_TABLE_SIZE: u16 = 0x7FF;
+const MSIX_FUNCTION_MASK: u16 = 1 << 14;
+const MSIX_ENABLE: u16 = 1 << 15;
+
+/// Größe eines Eintrags der MSI-X-Tabelle.
+const MSIX_ENTRY_SIZE: u64 = 16;
+
+/// Bit 0 der Steuerung eines Tabelleneintrags: Eintrag maskiert.
+const MSIX_ENTRY_MASKED: u32 = 1 << 0;
+
+/// Adresse, an die das Gerät seine Nachrichten schreibt: der Local APIC dieser
+/// CPU.
+fn message_address() -> u32
+{
+    MESSAGE_ADDRESS | u32::from(apic::id()) << 12
+}
+
+/// Schaltet den klassischen Interrupt (INTx) des Geräts ab oder wieder ein.
+fn set_intx_disabled(device: &PciDevice, disabled: bool)
+{
+    let command = device.command() & !command::INTERRUPT_DISABLE;
+    device.set_command(command | if disabled { command::INTERRUPT_DISABLE } else { 0 });
+}
+
+/// ## Msi
+///
+/// Ein Gerät mit eingeschaltetem MSI und einem eigenen Vektor. Beim Freigeben
+/// wird MSI wieder abgeschaltet.
+pub struct Msi
+{
+    device: Arc<PciDevice>,
+    offset: u16,
+    vector: Vector,
+}
+
+impl Msi
+{
+    /// Vergibt einen Vektor für `handler` und schaltet MSI ein. Geräte ohne MSI
+    /// ergeben [PciError::Unsupported], fehlende Vektoren [PciError::NoVectors].
+    pub fn enable(device: &Arc<PciDevice>, handler: Handler) -> Result<Msi, PciError>
+    {
+        let offset = u16::from(device.capability(capability::MSI).ok_or(PciError::Unsupported)?);
+        let vector = vector::allocate(handler).ok_or(PciError::NoVectors)?;
+        let address = device.address;
+
+        let control = config::read_u16(address, offset + 2) & !(MSI_ENABLE | MSI_MULTIPLE_ENABLE);
+        config::write_u16(address, offset + 2, control);
+        config::write_u32(address, offset + 4, message_address());
+        let data = if control & MSI_64_BIT != 0
+        {
+            config::write_u32(address, offset + 8, 0);
+            offset + 12
+        }
+        else
+        {
+            offset + 8
+        };
+        config::write_u16(address, data, u16::from(vector.number()));
+        set_intx_disabled(device, true);
+        config::write_u16(address, offset + 2, control | MSI_ENABLE);
+
+        Ok(Msi { device: device.clone(), offset, vector })
+    }
+
+    /// Der Vektor, auf dem das Gerät seine Interrupts meldet.
+    pub fn vector(&self) -> u8
+    {
+        self.vector.number()
+    }
+}
+
+impl Drop for Msi
+{
+    fn drop(&mut self)
+    {
+        let control = config::read_u16(self.device.address, self.offset + 2);
+        config::write_u16(self.device.address, self.offset + 2, control & !MSI_ENABLE);
+        set_intx_disabled(&self.device, false);
+    }
+}
+
+/// ## MsiX
+///
+/// Ein Gerät mit eingeschaltetem MSI-X. Alle Einträge beginnen maskiert; erst
+/// [MsiX::set_handler] vergibt einen Vektor und gibt den Eintrag frei. Beim
+/// Freigeben wird MSI-X wieder abgeschaltet.
+pub struct MsiX
+{
+    device: Arc<PciDevice>,
+    offset: u16,
+    table: VirtAddr,
+    vectors: Vec<Option<Vector>>,
+}
+
+impl MsiX
+{
+    /// Bildet die Tabelle ab, maskiert alle Einträge und schaltet MSI-X ein.
+    pub fn enable(device: &Arc<PciDevice>) -> Result<MsiX, PciError>
+    {
+        let offset = u16::from(device.capability(capability::MSI_X).ok_or(PciError::Unsupported)?);
+        let address = device.address;
+        let control = config::read_u16(address, offset + 2);
+        let size = usize::from(control & MSIX_TABLE_SIZE) + 1;
+
+        let location = config::read_u32(address, offset + 4);
+        let Some(Bar::Memory { address: bar, .. }) = device.bars.get(location as usize & 0x7).copied().flatten()
+        else
+        {
+            return Err(PciError::BadBar);
+        };
+        let start = PhysAddr::new(bar + u64::from(location & !0x7));
+        let table = memory::map_mmio(start, size as u64 * MSIX_ENTRY_SIZE).map_err(|_| PciError::BadBar)?;
+        device.enable(false);
+
+        // Einträge erst bei maskierter Funktion verändern.
+        config::write_u16(address, offset + 2, control | MSIX_ENABLE | MSIX_FUNCTION_MASK);
+        let msix = MsiX { device: device.clone(), offset, table, vectors: (0..size).map(|_| None).collect() };
+        for entry in 0..size
+        {
+            msix.write_entry(entry, 3, MSIX_ENTRY_MASKED);
+        }
+        set_intx_disabled(device, true);
+        config::write_u16(address, offset + 2, (control | MSIX_ENABLE) & !MSIX_FUNCTION_MASK);
+        Ok(msix)
+    }
+
+    /// Anzahl der Einträge in der Tabelle.
+    pub fn table_size(&self) -> usize
+    {
+        self.vectors.len()
+    }
+
+    /// Schreibt das Doppelwort `index` (0–3) des Eintrags `entry`.
+    fn write_entry(&self, entry: usize, index: u64, value: u32)
+    {
+        let pointer = (self.table + entry as u64 * MSIX_ENTRY_SIZE + index * 4).as_mut_ptr::<u32>();
+        unsafe { pointer.write_volatile(value) };
+    }
+
+    /// Vergibt einen Vektor für `handler`, trägt ihn in den Eintrag `entry` ein
+    /// und gibt den Eintrag frei. Ein zuvor vergebener Vektor des Eintrags wird
+    /// freigegeben. Gibt die Nummer des Vektors zurück.
+    pub fn set_handler(&mut self, entry: usize, handler: Handler) -> Result<u8, PciError>
+    {
+        if entry >= self.table_size()
+        {
+            return Err(PciError::Unsupported);
+        }
+        let vector = vector::allocate(handler).ok_or(PciError::NoVectors)?;
+        let number = vector.number();
+        self.mask(entry, true);
+        self.write_entry(entry, 0, message_address());
+        self.write_entry(entry, 1, 0);
+        self.write_entry(entry, 2, u32::from(number));
+        self.vectors[entry] = Some(vector);
+        self.mask(entry, false);
+        Ok(number)
+    }
+
+    /// Maskiert den Eintrag `entry` oder gibt ihn frei. Nachrichten eines
+    /// maskierten Eintrags hält das Gerät zurück.
+    pub fn mask(&self, entry: usize, masked: bool)
+    {
+        self.write_entry(entry, 3, if masked { MSIX_ENTRY_MASKED } else { 0 });
+    }
+
+    /// Der Vektor des Eintrags `entry`, falls vergeben.
+    pub fn vector(&self, entry: usize) -> Option<u8>
+    {
+        self.vectors.get(entry)?.as_ref().map(Vector::number)
+    }
+}
+
+impl Drop for MsiX
+{
+    fn drop(&mut self)
+    {
+        for entry in 0..self.table_size()
+        {
+            self.mask(entry, true);
+        }
+        let control = config::read_u16(self.device.address, self.offset + 2);
+        config::write_u16(self.device.address, self.offset + 2, control & !MSIX_ENABLE);
+        set_intx_disabled(&self.device, false);
+    }
+}
+
+#[test_case]
+fn test_devices_without_capability()
+{
+    let host = super::init().iter().find(|device| device.capability(capability::MSI).is_none() && device.capability(capability::MSI_X).is_none());
+    if let Some(host) = host
+    {
+        assert_eq!(Msi::enable(host, alloc::boxed::Box::new(|| ())).err(), Some(PciError::Unsupported));
+        assert_eq!(MsiX::enable(host).err(), Some(PciError::Unsupported));
+    }
+}
@@ -0,0 +1,491 @@
+//! # Modul: process
+//!
+//! Dieses Modul verwaltet **Prozesse**: Programme mit eigenem Adressraum,
+//! eigenem Kernel-Stack und eigener Prozess-ID.
+//!
+//! # Aufbau
+//!
+//! | Komponente | Aufgabe |
+//! |------------|---------|
+//! | [Pid], [PidAllocator] | Vergabe eindeutiger Prozess-IDs |
+//! | [spawn] | Lädt ein ELF-Programm in einen neuen Prozess |
+//! | [fork] | Kopiert den aktuellen Prozess mit Copy-on-Write-Adressraum |
+//! | [exit] | Beendet den aktuellen Prozess, er wird zum Zombie |
+//! | [wait] | Wartet auf ein Kind und räumt den Zombie ab |
+//! | [yield_now] | Gibt die CPU an den nächsten bereiten Prozess ab |
+//!
+//! # Lebenszyklus
+//!
+//! ```text
+//! spawn(), fork() --> Ready <--> Running --exit()--> Zombie --wait() des Elternprozesses--> entfernt
+//!                          |   ^
+//!                 wait()   v   |  Kind beendet
+//!                         Waiting
+//! ```
+//!
+//! Ein beendeter Prozess bleibt als **Zombie** in der Prozesstabelle, bis sein
+//! Elternprozess den Exit-Code mit [wait] abholt. Erst dann werden Adressraum,
+//! Kernel-Stack und PID freigegeben – vorher läuft der Kernel womöglich noch
+//! auf genau diesem Kernel-Stack. Kinder eines beendeten Prozesses werden vom
+//! Kernel ([Pid::KERNEL]) übernommen, aber nicht an [wait] übergeben: Sind sie
+//! beendet, räumt der Scheduler sie ab, sobald ein anderer Prozess läuft.
+//!
+//! # Scheduling
+//!
+//! Prozesse wechseln **kooperativ**: bei `yield`, beim Warten auf ein Kind, bei
+//! `exit` und wenn ein schlafendes Primitiv aus [crate::sync] warten muss. Bei
+//! jedem Wechsel werden CR3 (mit PCID, siehe [crate::memory]), der Kernel-Stack
+//! im TSS und der Stack für `syscall` auf den neuen Prozess umgestellt.
+//!
+//! Der Kernel selbst ist der Prozess mit [Pid::KERNEL]; er verwendet den
+//! Adressraum des Kernels und läuft auf dem Stack des Bootloaders.
+
+mod context;
+mod pid;
+
+pub use context::KERNEL_STACK_SIZE;
+pub use pid::{MAX_PIDS, Pid, PidAllocator};
+
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
+use alloc::sync::Arc;
+use x86_64::instructions::interrupts;
+
+use crate::elf::{self, ElfError};
+use crate::memory::AddressSpace;
+use crate::sync::{IrqSpinlock, Once};
+use crate::usermode::UserContext;
+use crate::vfs::FileTable;
+use context::{KernelStack, switch_context};
+
+/// Zustand eines Prozesses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State
+{
+    /// Wartet in der Warteschlange darauf, die CPU zu bekommen.
+    Ready,
+    /// Läuft gerade.
+    Running,
+    /// Wartet in [wait] auf ein Kind.
+    Waiting,
+    /// Beendet mit Exit-Code, wartet auf [wait] des Elternprozesses.
+    Zombie(i64),
+}
+
+/// Fehler beim Starten eines Prozesses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpawnError
+{
+    /// Das Programm konnte nicht geladen werden.
+    Elf(ElfError),
+    /// Alle Prozess-IDs sind vergeben.
+    TooManyProcesses,
+    /// Für Seitentabellen war kein Speicher mehr frei.
+    OutOfMemory,
+}
+
+impl From<ElfError> for SpawnError
+{
+    fn from(error: ElfError) -> SpawnError
+    {
+        SpawnError::Elf(error)
+    }
+}
+
+/// Fehler beim Warten auf ein Kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitError
+{
+    /// Der aktuelle Prozess hat kein (passendes) Kind.
+    NoChildren,
+}
+
+/// ## Process
+///
+/// Eintrag der Prozesstabelle.
+struct Process
+{
+    pid: Pid,
+    parent: Pid,
+    name: String,
+    state: State,
+    address_space: AddressSpace,
+    kernel_stack: KernelStack,
+    /// Gesicherter Stackzeiger, solange der Prozess nicht läuft.
+    saved_rsp: u64,
+    /// Registerzustand, mit dem der Prozess zum ersten Mal in den Ring 3 springt.
+    user_context: UserContext,
+    /// Geöffnete Dateien, siehe [crate::vfs].
+    files: Arc<IrqSpinlock<FileTable>>,
+    /// Der Elternprozess ist beendet; als Zombie wird der Prozess ohne [wait]
+    /// abgeräumt (siehe [ProcessTable::reap_orphans]).
+    orphaned: bool,
+}
+
+/// ## ProcessTable
+///
+/// Alle Prozesse samt Warteschlange der bereiten Prozesse. Die Einträge liegen
+/// in einer `Box`, damit [switch_context] einen festen Zeiger auf
+/// `saved_rsp` bekommt.
+struct ProcessTable
+{
+    processes: BTreeMap<Pid, Box<Process>>,
+    ready: VecDeque<Pid>,
+    current: Pid,
+    pids: PidAllocator,
+}
+
+/// Globale Prozesstabelle, wird beim ersten Zugriff mit dem Kernel-Prozess angelegt.
+static TABLE: Once<IrqSpinlock<ProcessTable>> = Once::new();
+
+/// Gibt die Prozesstabelle zurück und legt sie bei Bedarf an.
+///
+/// Setzt eine initialisierte Speicherverwaltung voraus.
+fn table() -> &'static IrqSpinlock<ProcessTable>
+{
+    TABLE.call_once(|| IrqSpinlock::new(ProcessTable::new()))
+}
+
+impl ProcessTable
+{
+    fn new() -> ProcessTable
+    {
+        let kernel = Process
+        {
+            pid: Pid::KERNEL,
+            parent: Pid::KERNEL,
+            name: String::from("kernel"),
+            state: State::Running,
+            address_space: AddressSpace::kernel(),
+            kernel_stack: KernelStack::boot(crate::gdt::kernel_stack_top()),
+            saved_rsp: 0,
+            user_context: UserContext::default(),
+            files: Arc::new(IrqSpinlock::new(FileTable::new())),
+            orphaned: false,
+        };
+
+        let mut processes = BTreeMap::new();
+        processes.insert(Pid::KERNEL, Box::new(kernel));
+        ProcessTable { processes, ready: VecDeque::new(), current: Pid::KERNEL, pids: PidAllocator::new() }
+    }
+
+    fn current_mut(&mut self) -> &mut Process
+    {
+        let current = self.current;
+        self.processes.get_mut(&current).expect("current process is not in the table")
+    }
+
+    /// Reiht einen wartenden Prozess wieder ein.
+    fn wake(&mut self, pid: Pid)
+    {
+        if let Some(process) = self.processes.get_mut(&pid)
+            && process.state == State::Waiting
+        {
+            process.state = State::Ready;
+            self.ready.push_back(pid);
+        }
+    }
+
+    /// Räumt beendete Waisen ab. Der aktuelle Prozess bleibt stehen, da der
+    /// Kernel womöglich noch auf seinem Kernel-Stack läuft.
+    fn reap_orphans(&mut self)
+    {
+        let current = self.current;
+        let pids = &mut self.pids;
+        self.processes.retain(|&pid, process|
+        {
+            let reap = process.orphaned && pid != current && matches!(process.state, State::Zombie(_));
+            if reap
+            {
+                pids.free(pid);
+            }
+            !reap
+        });
+    }
+
+    /// Wählt den nächsten bereiten Prozess, stellt CR3 und die Kernel-Stacks
+    /// auf ihn um und gibt die Argumente für [switch_context] zurück.
+    ///
+    /// Gibt `None` zurück, wenn kein anderer Prozess bereit ist.
+    fn switch_target(&mut self) -> Option<(*mut u64, u64)>
+    {
+        let current = self.current;
+        let next = loop
+        {
+            let pid = self.ready.pop_front()?;
+            if self.processes.get(&pid).is_some_and(|process| process.state == State::Ready)
+            {
+                break pid;
+            }
+        };
+
+        if next == current
+        {
+            self.current_mut().state = State::Running;
+            return None;
+        }
+        if self.current_mut().state == State::Running
+        {
+            self.current_mut().state = State::Ready;
+            self.ready.push_back(current);
+        }
+
+        let next_process = self.processes.get_mut(&next).expect("ready process is not in the table");
+        next_process.state = State::Running;
+        unsafe { next_process.address_space.activate() };
+        crate::gdt::set_kernel_stack(next_process.kernel_stack.top());
+        crate::syscall::set_kernel_stack(next_process.kernel_stack.top());
+        let new = next_process.saved_rsp;
+
+        self.current = next;
+        let old = &mut self.processes.get_mut(&current).expect("current process is not in the table").saved_rsp;
+        Some((old as *mut u64, new))
+    }
+}
+
+/// ## Scheduler
+///
+/// Wechselt zum nächsten bereiten Prozess. Läuft der aktuelle Prozess nicht
+/// mehr (er wartet oder ist beendet) und ist niemand bereit, wird mit `hlt`
+/// auf einen Interrupt gewartet.
+///
+/// Gibt zurück, ob zu einem anderen Prozess gewechselt wurde.
+fn schedule() -> bool
+{
+    interrupts::without_interrupts(||
+    {
+        let (old, new) = loop
+        {
+            let mut table = table().lock();
+            if let Some(target) = table.switch_target()
+            {
+                break target;
+            }
+            if table.current_mut().state == State::Running
+            {
+                return false;
+            }
+            drop(table);
+            interrupts::enable_and_hlt();
+            interrupts::disable();
+        };
+
+        // SAFETY: `new` stammt aus der Prozesstabelle, Interrupts sind aus und
+        // kein Lock ist mehr gehalten.
+        unsafe { switch_context(old, new) };
+        table().lock().reap_orphans();
+        true
+    })
+}
+
+/// Erster Code eines neuen Prozesses nach dem ersten Kontextwechsel: springt
+/// in den User Mode.
+extern "C" fn process_entry() -> !
+{
+    let context =
+    {
+        let mut table = table().lock();
+        table.reap_orphans();
+        table.current_mut().user_context.clone()
+    };
+    unsafe { crate::usermode::resume_user_mode(&context) }
+}
+
+/// Trägt einen neuen Prozess als Kind des aktuellen Prozesses in die Tabelle
+/// ein und reiht ihn in die Warteschlange ein. Das Kind erbt eine Kopie der
+/// Dateitabelle.
+fn insert_process(name: String, address_space: AddressSpace, user_context: UserContext) -> Result<Pid, SpawnError>
+{
+    let (kernel_stack, saved_rsp) = KernelStack::new(process_entry);
+
+    interrupts::without_interrupts(||
+    {
+        let mut table = table().lock();
+        let pid = table.pids.allocate().ok_or(SpawnError::TooManyProcesses)?;
+        let files = table.current_mut().files.lock().clone();
+        let process = Process
+        {
+            pid,
+            parent: table.current,
+            name,
+            state: State::Ready,
+            address_space,
+            kernel_stack,
+            saved_rsp,
+            user_context,
+            files: Arc::new(IrqSpinlock::new(files)),
+            orphaned: false,
+        };
+        table.processes.insert(pid, Box::new(process));
+        table.ready.push_back(pid);
+        Ok(pid)
+    })
+}
+
+/// ## Prozess starten
+///
+/// Lädt das ELF-Programm `program` mit `argv` und `envp` in einen neuen
+/// Adressraum und reiht den Prozess als Kind des aktuellen Prozesses in die
+/// Warteschlange ein. Er läuft, sobald der aktuelle Prozess die CPU abgibt.
+pub fn spawn(name: &str, program: &[u8], argv: &[&str], envp: &[&str]) -> Result<Pid, SpawnError>
+{
+    let loaded = elf::load(program, argv, envp)?;
+    let context = UserContext::new(loaded.entry, loaded.stack_pointer);
+    insert_process(String::from(name), loaded.address_space, context)
+}
+
+/// ## Prozess kopieren (fork)
+///
+/// Erzeugt ein Kind des aktuellen Prozesses mit einer Copy-on-Write-Kopie
+/// seines Adressraums (siehe [AddressSpace::fork]). Das Kind springt mit
+/// `context` in den Ring 3 – für `fork` ist das der Zustand nach dem
+/// Systemaufruf mit Ergebnis 0.
+///
+/// # Panics
+///
+/// Wenn der Kernel-Prozess sich kopieren will.
+pub fn fork(context: UserContext) -> Result<Pid, SpawnError>
+{
+    let (name, address_space) = interrupts::without_interrupts(||
+    {
+        let mut table = table().lock();
+        let process = table.current_mut();
+        assert!(process.pid != Pid::KERNEL, "the kernel process cannot fork");
+        let address_space = process.address_space.fork().map_err(|_| SpawnError::OutOfMemory)?;
+        Ok::<_, SpawnError>((process.name.clone(), address_space))
+    })?;
+    insert_process(name, address_space, context)
+}
+
+/// Exit-Code eines Prozesses, der wegen einer Exception im Ring 3 beendet
+/// wurde; wie bei einer Unix-Shell nach `SIGSEGV` (128 + 11).
+pub const FAULT_EXIT_CODE: i64 = 139;
+
+/// ## Prozess beenden
+///
+/// Macht den aktuellen Prozess zum Zombie mit Exit-Code `code`, weckt den
+/// Elternprozess und übergibt die eigenen Kinder als Waisen an den Kernel.
+/// Bereits beendete Kinder werden sofort abgeräumt.
+///
+/// # Panics
+///
+/// Wenn der Kernel selbst beendet werden soll.
+pub fn exit(code: i64) -> !
+{
+    interrupts::disable();
+    {
+        let mut table = table().lock();
+        let current = table.current;
+        assert!(current != Pid::KERNEL, "the kernel process cannot exit");
+
+        let process = table.current_mut();
+        process.state = State::Zombie(code);
+        let parent = process.parent;
+
+        for child in table.processes.values_mut().filter(|process| process.parent == current)
+        {
+            child.parent = Pid::KERNEL;
+            child.orphaned = true;
+        }
+        table.reap_orphans();
+        table.wake(parent);
+    }
+
+    schedule();
+    unreachable!("a zombie process was scheduled again");
+}
+
+/// ## Auf ein Kind warten
+///
+/// Wartet, bis das Kind `pid` (oder bei `None` ein beliebiges Kind) beendet
+/// ist, räumt den Zombie ab und gibt dessen PID und Exit-Code zurück.
+pub fn wait(pid: Option<Pid>) -> Result<(Pid, i64), WaitError>
+{
+    loop
+    {
+        let result = interrupts::without_interrupts(||
+        {
+            let mut table = table().lock();
+            let current = table.current;
+            let mut has_child = false;
+            let mut zombie = None;
+            for process in table.processes.values()
+            {
+                if process.parent != current
+                    || process.pid == current
+                    || process.orphaned
+                    || pid.is_some_and(|pid| pid != process.pid)
+                {
+                    continue;
+                }
+                has_child = true;
+                if let State::Zombie(code) = process.state
+                {
+                    zombie = Some((process.pid, code));
+                    break;
+                }
+            }
+
+            if let Some((child, code)) = zombie
+            {
+                // Gibt Adressraum und Kernel-Stack frei.
+                drop(table.processes.remove(&child));
+                table.pids.free(child);
+                return Some(Ok((child, code)));
+            }
+            if !has_child
+            {
+                return Some(Err(WaitError::NoChildren));
+            }
+            table.current_mut().state = State::Waiting;
+            None
+        });
+
+        if let Some(result) = result
+        {
+            return result;
+        }
+        schedule();
+    }
+}
+
+/// ## CPU abgeben
+///
+/// Wechselt zum nächsten bereiten Prozess, falls es einen gibt. Gibt zurück,
+/// ob ein anderer Prozess lief.
+pub fn yield_now() -> bool
+{
+    TABLE.get().is_some() && schedule()
+}
+
+/// ID des aktuell laufenden Prozesses.
+pub fn current_pid() -> Pid
+{
+    TABLE.get().map_or(Pid::KERNEL, |table| table.lock().current)
+}
+
+/// Dateitabelle des aktuell laufenden Prozesses.
+pub fn files() -> Arc<IrqSpinlock<FileTable>>
+{
+    let table = table().lock();
+    table.processes.get(&table.current).expect("current process is not in the table").files.clone()
+}
+
+/// Zustand des Prozesses `pid`, falls er existiert.
+pub fn state(pid: Pid) -> Option<State>
+{
+    table().lock().processes.get(&pid).map(|process| process.state)
+}
+
+/// Name des Prozesses `pid`, falls er existiert.
+pub fn name(pid: Pid) -> Option<String>
+{
+    table().lock().processes.get(&pid).map(|process| process.name.clone())
+}
+
+/// Anzahl der Prozesse in der Tabelle, einschließlich Kernel und Zombies.
+pub fn count() -> usize
+{
+    table().lock().processes.len()
+}
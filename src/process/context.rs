@@ -0,0 +1,90 @@
+//! Kontextwechsel zwischen Kernel-Stacks.
+
+use alloc::boxed::Box;
+use alloc::vec;
+use x86_64::VirtAddr;
+
+/// Größe des Kernel-Stacks eines Prozesses.
+pub const KERNEL_STACK_SIZE: usize = 4096 * 4;
+
+/// Anzahl der callee-saved Register, die [switch_context] sichert.
+const SAVED_REGISTERS: usize = 6;
+
+/// ## KernelStack
+///
+/// Eigener Kernel-Stack eines Prozesses. Auf ihm laufen Systemaufrufe und
+/// Interrupts, während der Prozess aktiv ist, und auf ihm liegt sein
+/// gesicherter Kontext, während er nicht läuft.
+///
+/// Der Kernel-Prozess besitzt keinen eigenen Speicher, sondern verwendet den
+/// Stack aus dem TSS ([KernelStack::boot]).
+pub struct KernelStack
+{
+    memory: Option<Box<[u64]>>,
+    top: u64,
+}
+
+impl KernelStack
+{
+    /// Legt einen Stack an, dessen erster Kontextwechsel in `entry` springt.
+    ///
+    /// Gibt den Stack und den Stackzeiger für [switch_context] zurück.
+    pub fn new(entry: extern "C" fn() -> !) -> (KernelStack, u64)
+    {
+        let mut memory = vec![0u64; KERNEL_STACK_SIZE / 8].into_boxed_slice();
+        let top = memory.as_ptr_range().end as u64 & !0xf;
+        let top_index = ((top - memory.as_ptr() as u64) / 8) as usize;
+
+        // Oben: Füllwort, damit `entry` wie nach einem `call` ausgerichtet startet,
+        // darunter die Rücksprungadresse und die genullten Register.
+        memory[top_index - 2] = entry as usize as u64;
+        let stack_pointer = memory[top_index - 2 - SAVED_REGISTERS..].as_ptr() as u64;
+
+        (KernelStack { memory: Some(memory), top }, stack_pointer)
+    }
+
+    /// Der bereits bestehende Stack mit oberem Ende `top`.
+    pub fn boot(top: VirtAddr) -> KernelStack
+    {
+        KernelStack { memory: None, top: top.as_u64() }
+    }
+
+    /// Oberes Ende des Stacks, 16-Byte-ausgerichtet.
+    pub fn top(&self) -> VirtAddr
+    {
+        debug_assert!(self.memory.as_ref().is_none_or(|memory| memory.as_ptr_range().end as u64 >= self.top));
+        VirtAddr::new(self.top)
+    }
+}
+
+/// ## Kontextwechsel
+///
+/// Sichert die callee-saved Register auf dem aktuellen Stack, speichert den
+/// Stackzeiger in `*old` und setzt die Ausführung auf dem Stack `new` fort.
+/// Kehrt erst zurück, wenn wieder zu `*old` gewechselt wird.
+///
+/// # Safety
+///
+/// `new` muss ein mit [switch_context] gesicherter oder von [KernelStack::new]
+/// vorbereiteter Stackzeiger sein. Interrupts müssen deaktiviert sein.
+#[unsafe(naked)]
+pub unsafe extern "C" fn switch_context(old: *mut u64, new: u64)
+{
+    core::arch::naked_asm!(
+        "push rbx",
+        "push rbp",
+        "push r12",
+        "push r13",
+        "push r14",
+        "push r15",
+        "mov [rdi], rsp",
+        "mov rsp, rsi",
+        "pop r15",
+        "pop r14",
+        "pop r13",
+        "pop r12",
+        "pop rbp",
+        "pop rbx",
+        "ret",
+    );
+}
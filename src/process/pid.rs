@@ -0,0 +1,118 @@
+//! Vergabe von Prozess-IDs.
+
+use core::fmt;
+
+/// Höchste Anzahl gleichzeitig vergebener Prozess-IDs.
+pub const MAX_PIDS: usize = 4096;
+
+/// ## Pid
+///
+/// Eindeutige ID eines Prozesses. [KERNEL_PID](Pid::KERNEL) gehört dem Kernel selbst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Pid(u64);
+
+impl Pid
+{
+    /// Der Kernel, der beim Start läuft und verwaiste Prozesse übernimmt.
+    pub const KERNEL: Pid = Pid(0);
+
+    /// Die ID zu einer Zahl, z. B. aus einem Systemaufruf.
+    pub fn from_u64(pid: u64) -> Pid
+    {
+        Pid(pid)
+    }
+
+    /// Die ID als Zahl, z. B. als Ergebnis von `getpid`.
+    pub fn as_u64(self) -> u64
+    {
+        self.0
+    }
+}
+
+impl fmt::Display for Pid
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// ## PidAllocator
+///
+/// Vergibt IDs aus einer Bitmap. Wie unter Unix werden IDs fortlaufend vergeben
+/// und erst nach einem Überlauf wiederverwendet, damit eine gerade freigegebene
+/// ID nicht sofort einem neuen Prozess gehört.
+pub struct PidAllocator
+{
+    used: [u64; MAX_PIDS / 64],
+    next: usize,
+}
+
+impl PidAllocator
+{
+    /// Erstellt einen Allocator, in dem nur [Pid::KERNEL] belegt ist.
+    pub const fn new() -> PidAllocator
+    {
+        let mut used = [0; MAX_PIDS / 64];
+        used[0] = 1;
+        PidAllocator { used, next: 1 }
+    }
+
+    /// Reserviert die nächste freie ID.
+    pub fn allocate(&mut self) -> Option<Pid>
+    {
+        for offset in 0..MAX_PIDS
+        {
+            let pid = (self.next + offset) % MAX_PIDS;
+            if self.used[pid / 64] & (1 << (pid % 64)) == 0
+            {
+                self.used[pid / 64] |= 1 << (pid % 64);
+                self.next = (pid + 1) % MAX_PIDS;
+                return Some(Pid(pid as u64));
+            }
+        }
+        None
+    }
+
+    /// Gibt `pid` wieder frei.
+    pub fn free(&mut self, pid: Pid)
+    {
+        let pid = pid.0 as usize;
+        debug_assert!(pid != 0, "the kernel pid cannot be freed");
+        self.used[pid / 64] &= !(1 << (pid % 64));
+    }
+}
+
+impl Default for PidAllocator
+{
+    fn default() -> PidAllocator
+    {
+        PidAllocator::new()
+    }
+}
+
+#[test_case]
+fn test_pid_allocator_does_not_reuse_immediately()
+{
+    let mut allocator = PidAllocator::new();
+    let first = allocator.allocate().unwrap();
+    let second = allocator.allocate().unwrap();
+    assert_eq!((first.as_u64(), second.as_u64()), (1, 2));
+
+    allocator.free(first);
+    assert_eq!(allocator.allocate().unwrap().as_u64(), 3);
+}
+
+#[test_case]
+fn test_pid_allocator_wraps_and_exhausts()
+{
+    let mut allocator = PidAllocator::new();
+    for _ in 1..MAX_PIDS
+    {
+        allocator.allocate().unwrap();
+    }
+    assert!(allocator.allocate().is_none());
+
+    allocator.free(Pid(7));
+    assert_eq!(allocator.allocate(), Some(Pid(7)));
+}
@@ -1,84 +1,370 @@
 //! # Modul: serial
-//! 
-//! Dieses Modul stellt die serielle Schnittstelle bereit, um Ausgaben vom Kernel
-//! (z. B. Logmeldungen oder Testergebnisse) an den Host zu senden.
 //!
-//! Es implementiert eine einfache, thread-sichere [SerialPort]-Instanz, die über
-//! [serial_print!] und [serial_println!] angesprochen werden kann.
+//! Dieses Modul stellt die seriellen Schnittstellen **COM1** bis **COM4**
+//! bereit, um Ausgaben vom Kernel (z. B. Logmeldungen oder Testergebnisse)
+//! an den Host zu senden und Eingaben von dort zu empfangen.
 //!
 //! # Aufbau
-//! 
+//!
 //! | Komponente | Aufgabe |
 //! |-------------|----------|
-//! | [SERIAL1] | Globale, mutexgeschützte Instanz des UART-Ports |
-//! | [serial_print!] / [serial_println!] | Eigene Makros zum Schreiben über die serielle Schnittstelle |
+//! | [uart] | Treiber für den 16550 mit einstellbarer Baudrate, Parität, Stoppbits und FIFO |
+//! | [ComPort] | Die vier Standard-Schnittstellen mit Basisadresse und IRQ |
+//! | [detect], [configure] | Erkennung per Loopback-Selbsttest und Einstellungen je Port |
+//! | [Channel], [route] | Ordnet Konsole, Log und Debugger je einer Schnittstelle zu |
+//! | [init] | Erkennt die Ports und schaltet den Empfang über IRQ 3/4 ein |
+//! | [read], [try_read] | Holen empfangene Bytes ab |
+//! | [serial_print!] / [serial_println!] | Eigene Makros zum Schreiben über **COM1** |
+//! | [serial_print_to!] / [serial_println_to!] | Dasselbe für einen Port oder Kanal nach Wahl |
 //!
 //! # Hintergrund
-//! 
+//!
 //! Da in einem Bare-Metal-Umfeld keine Standardbibliothek (std) zur Verfügung steht,
 //! können normale Print-Makros (println!, eprintln!, etc.) nicht verwendet werden.
 //! Stattdessen werden die Ausgaben direkt an die UART-Schnittstelle (0x3F8) gesendet,
 //! welche typischerweise als **COM1** genutzt wird.
+//!
+//! **COM1** wird beim ersten Zugriff ohne Prüfung eingerichtet, damit Ausgaben
+//! schon vor [init] funktionieren. Die übrigen Ports stehen erst nach
+//! [detect] bereit; Ausgaben an fehlende Ports gehen verloren.
+//!
+//! # Empfang
+//!
+//! Die Bausteine melden empfangene Bytes über IRQ 4 (**COM1**, **COM3**)
+//! bzw. IRQ 3 (**COM2**, **COM4**). Der Handler leert dann den FIFO des
+//! Bausteins in einen Puffer mit [INPUT_SIZE] Bytes je Port, aus dem [read]
+//! und [try_read] lesen. So lässt sich der Kernel mit `-serial stdio` auch
+//! ohne Bildschirm bedienen, z. B. über die [Shell](crate::shell).
+
+pub mod uart;
+
+pub use uart::{DataBits, FifoTrigger, Parity, StopBits, Uart, UartConfig};
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+use crate::interrupts::irq::{self, IrqHandler};
+use crate::sync::{IrqSpinlock, Once};
 
-use uart_16550::SerialPort;
-use spin::Mutex;
-use lazy_static::lazy_static;
+/// Anzahl der Bytes je Port, die auf Abholung warten können.
+pub const INPUT_SIZE: usize = 256;
 
-lazy_static!
+/// Fehler beim Zugriff auf eine serielle Schnittstelle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerialError
 {
-    /// ### Globale serielle Schnittstelle
-    ///
-    /// Initialisiert den ersten UART-Port (0x3F8, typischerweise **COM1**)
-    /// und schützt ihn durch einen [Mutex], um konkurrierende Zugriffe zu verhindern.
-    ///
-    /// Der Port wird **lazy** initialisiert, d. h. erst beim ersten Zugriff während
-    /// der Laufzeit, was Ressourcen spart und Initialisierungsprobleme vermeidet.
-    pub static ref SERIAL1: Mutex<SerialPort> = 
+    /// An der Adresse antwortet kein Baustein.
+    NotPresent,
+    /// Der Baustein hat das Byte im Loopback nicht zurückgegeben.
+    SelfTestFailed,
+    /// Die Baudrate teilt [uart::MAX_BAUD_RATE] nicht ganzzahlig.
+    InvalidBaudRate,
+}
+
+/// ## ComPort
+///
+/// Die vier Standard-Schnittstellen des PCs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ComPort
+{
+    Com1,
+    Com2,
+    Com3,
+    Com4,
+}
+
+impl ComPort
+{
+    pub const ALL: [ComPort; 4] = [ComPort::Com1, ComPort::Com2, ComPort::Com3, ComPort::Com4];
+
+    const fn index(self) -> usize
     {
-        let mut serial_port = unsafe { SerialPort::new(0x3F8) };
-        serial_port.init();
-        Mutex::new(serial_port)
-    };
+        self as usize
+    }
+
+    /// I/O-Basisadresse.
+    pub const fn base(self) -> u16
+    {
+        match self
+        {
+            ComPort::Com1 => 0x3F8,
+            ComPort::Com2 => 0x2F8,
+            ComPort::Com3 => 0x3E8,
+            ComPort::Com4 => 0x2E8,
+        }
+    }
+
+    /// IRQ-Leitung am PIC; **COM3** und **COM4** teilen sie sich mit
+    /// **COM1** und **COM2**.
+    pub const fn irq(self) -> u8
+    {
+        match self
+        {
+            ComPort::Com1 | ComPort::Com3 => 4,
+            ComPort::Com2 | ComPort::Com4 => 3,
+        }
+    }
+}
+
+impl fmt::Display for ComPort
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "COM{}", self.index() + 1)
+    }
+}
+
+/// Schreibt auf den Port; fehlt er, geht die Ausgabe verloren.
+impl fmt::Write for ComPort
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result
+    {
+        with_uart(*self, |uart| uart.write_str(s)).unwrap_or(Ok(()))
+    }
+}
+
+/// ## Channel
+///
+/// Verwendungszwecke, die sich je auf einen eigenen Port legen lassen.
+/// Anfangs liegen alle auf **COM1**.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel
+{
+    /// Ein- und Ausgabe der [Shell](crate::shell).
+    Console,
+    /// Logmeldungen des Kernels.
+    Log,
+    /// Verbindung zu einem Debugger.
+    Debug,
+}
+
+/// Der Port, auf dem der Kanal gerade liegt.
+impl From<Channel> for ComPort
+{
+    fn from(channel: Channel) -> ComPort
+    {
+        ROUTES.lock()[channel as usize]
+    }
+}
+
+/// Zustand eines Ports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Presence
+{
+    /// Noch nicht geprüft.
+    Unknown,
+    Present,
+    Absent,
+}
+
+struct Slot
+{
+    uart: Uart,
+    presence: Presence,
+}
+
+/// Die Bausteine der vier Ports.
+static PORTS: [IrqSpinlock<Slot>; 4] =
+{
+    const fn slot(port: ComPort) -> IrqSpinlock<Slot>
+    {
+        IrqSpinlock::new(Slot { uart: unsafe { Uart::new(port.base()) }, presence: Presence::Unknown })
+    }
+    [slot(ComPort::Com1), slot(ComPort::Com2), slot(ComPort::Com3), slot(ComPort::Com4)]
+};
+
+/// Ports der Kanäle in der Reihenfolge von [Channel].
+static ROUTES: IrqSpinlock<[ComPort; 3]> = IrqSpinlock::new([ComPort::Com1; 3]);
+
+/// Ringpuffer für empfangene Bytes.
+struct Input
+{
+    bytes: [u8; INPUT_SIZE],
+    head: usize,
+    len: usize,
+}
+
+impl Input
+{
+    const fn new() -> Input
+    {
+        Input { bytes: [0; INPUT_SIZE], head: 0, len: 0 }
+    }
+
+    /// Hängt `byte` an; ist der Puffer voll, geht es verloren.
+    fn push(&mut self, byte: u8) -> bool
+    {
+        if self.len == INPUT_SIZE
+        {
+            return false;
+        }
+        self.bytes[(self.head + self.len) % INPUT_SIZE] = byte;
+        self.len += 1;
+        true
+    }
+
+    fn pop(&mut self) -> Option<u8>
+    {
+        if self.len == 0
+        {
+            return None;
+        }
+        let byte = self.bytes[self.head];
+        self.head = (self.head + 1) % INPUT_SIZE;
+        self.len -= 1;
+        Some(byte)
+    }
+}
+
+/// Empfangene, noch nicht abgeholte Bytes je Port.
+static INPUT: [IrqSpinlock<Input>; 4] = [const { IrqSpinlock::new(Input::new()) }; 4];
+
+/// Die von [detect] gefundenen Ports.
+static DETECTED: Once<Vec<ComPort>> = Once::new();
+
+/// Die angemeldeten Handler für den Empfang.
+static RECEIVERS: Once<Vec<IrqHandler>> = Once::new();
+
+/// Ruft `f` mit dem Baustein von `port` auf, falls er vorhanden ist.
+/// **COM1** wird beim ersten Zugriff ungeprüft eingerichtet.
+///
+/// Das [IrqSpinlock] deaktiviert die Interrupts, solange der Port gesperrt ist,
+/// und stellt den vorherigen Zustand danach wieder her, damit es nicht zu einem
+/// Deadlock mit dem Empfangs-Handler kommt.
+fn with_uart<R>(port: ComPort, f: impl FnOnce(&mut Uart) -> R) -> Option<R>
+{
+    let mut slot = PORTS[port.index()].lock();
+    if slot.presence == Presence::Unknown && port == ComPort::Com1
+    {
+        let config = slot.uart.config();
+        slot.uart.configure(&config).ok()?;
+        slot.presence = Presence::Present;
+    }
+    (slot.presence == Presence::Present).then(|| f(&mut slot.uart))
+}
+
+/// ## Erkennung
+///
+/// Prüft beim ersten Aufruf alle vier Ports mit [Uart::self_test] und
+/// richtet die gefundenen mit [UartConfig::DEFAULT] ein. Gibt die
+/// vorhandenen Ports zurück.
+pub fn detect() -> &'static [ComPort]
+{
+    DETECTED.call_once(||
+    {
+        ComPort::ALL.into_iter().filter(|port|
+        {
+            let mut slot = PORTS[port.index()].lock();
+            let present = slot.uart.self_test().is_ok();
+            slot.presence = if present { Presence::Present } else { Presence::Absent };
+            present
+        })
+        .collect()
+    })
+}
+
+/// Stellt `port` neu ein.
+pub fn configure(port: ComPort, config: &UartConfig) -> Result<(), SerialError>
+{
+    with_uart(port, |uart| uart.configure(config)).unwrap_or(Err(SerialError::NotPresent))
+}
+
+/// Die aktuellen Einstellungen von `port`, falls vorhanden.
+pub fn config(port: ComPort) -> Option<UartConfig>
+{
+    with_uart(port, |uart| uart.config())
+}
+
+/// Legt `channel` auf `port`. Der Port muss vorhanden sein.
+pub fn route(channel: Channel, port: ComPort) -> Result<(), SerialError>
+{
+    with_uart(port, |_| ()).ok_or(SerialError::NotPresent)?;
+    ROUTES.lock()[channel as usize] = port;
+    Ok(())
+}
+
+/// ## Initialisierung
+///
+/// Erkennt die Ports mit [detect] und meldet für jeden einen Handler an
+/// seiner IRQ-Leitung an. Braucht den Heap und ist daher erst nach
+/// [crate::memory::init] möglich. Gibt die vorhandenen Ports zurück.
+pub fn init() -> &'static [ComPort]
+{
+    let ports = detect();
+    RECEIVERS.call_once(|| ports.iter().filter_map(|&port| irq::register(port.irq(), Box::new(move || receive(port)))).collect());
+    // Was vor der Anmeldung ankam, hat womöglich keinen Interrupt mehr zur Folge.
+    for &port in ports
+    {
+        receive(port);
+    }
+    ports
+}
+
+/// Leert den FIFO des Bausteins in [INPUT].
+fn receive(port: ComPort)
+{
+    with_uart(port, |uart|
+    {
+        let mut input = INPUT[port.index()].lock();
+        while let Some(byte) = uart.try_receive()
+        {
+            input.push(byte);
+        }
+    });
+}
+
+/// Holt das älteste empfangene Byte von `port` ab, ohne zu warten.
+pub fn try_read(port: impl Into<ComPort>) -> Option<u8>
+{
+    INPUT[port.into().index()].lock().pop()
+}
+
+/// Wartet auf das nächste empfangene Byte von `port`. Setzt [init] voraus.
+pub fn read(port: impl Into<ComPort>) -> u8
+{
+    let port = port.into();
+    loop
+    {
+        if let Some(byte) = try_read(port)
+        {
+            return byte;
+        }
+        crate::sync::wait();
+    }
 }
 
 #[doc(hidden)]
 /// ## Hilfsfunktion
-/// 
-/// Interne Hilfsfunktion, die Formatierungsargumente (fmt::Arguments) an den
-/// globalen [SERIAL1]-Port weiterleitet.
+///
+/// Interne Hilfsfunktion, die Formatierungsargumente (fmt::Arguments) an
+/// einen Port weiterleitet. Der Port bleibt für die ganze Ausgabe gesperrt,
+/// sodass sich Zeilen verschiedener Kontexte nicht mischen.
 ///
 /// Sollte **nicht direkt** verwendet werden – stattdessen die Makros
-/// [serial_print!] oder [serial_println!] nutzen.
-/// 
-/// ### Deadlock
-/// 
-/// Interrupts werden am Anfang der Funktion deaktiviert und
-/// nachdem sie fertig ist wieder aktiviert, damit es nicht zu einem
-/// Deadlock kommt.
-pub fn _print(args: ::core::fmt::Arguments)
-{
-    use core::fmt::Write;
-    use x86_64::instructions::interrupts;
-
-    interrupts::without_interrupts(||
-    {
-        SERIAL1
-            .lock()
-            .write_fmt(args)
-            .expect("Printing to serial failed");
-    });
+/// [serial_print!], [serial_println!], [serial_print_to!] oder
+/// [serial_println_to!] nutzen.
+pub fn _print_to(port: impl Into<ComPort>, args: fmt::Arguments)
+{
+    with_uart(port.into(), |uart| uart.write_fmt(args).expect("Printing to serial failed"));
+}
+
+#[doc(hidden)]
+/// Wie [_print_to] für **COM1**.
+pub fn _print(args: fmt::Arguments)
+{
+    _print_to(ComPort::Com1, args);
 }
 
 /// ### serial_print!
 ///
-/// Gibt formatierten Text über die serielle Schnittstelle aus.
+/// Gibt formatierten Text über **COM1** aus.
 ///
 /// Funktioniert ähnlich wie das Standard-Makro [print!], verwendet jedoch
 /// den UART-Port statt der Standardausgabe.
 #[macro_export]
-macro_rules! serial_print 
+macro_rules! serial_print
 {
-    ($($arg:tt)*) => 
+    ($($arg:tt)*) =>
     {
         $crate::serial::_print(format_args!($($arg)*));
     };
@@ -86,19 +372,76 @@ macro_rules! serial_print
 
 /// ### serial_println!
 ///
-/// Gibt eine formatierte Zeile über die serielle Schnittstelle aus.
+/// Gibt eine formatierte Zeile über **COM1** aus.
 ///
 /// Entspricht funktional dem Standard-Makro [println!], ist aber für
 /// Bare-Metal-Umgebungen implementiert.
 ///
 /// Unterstützt drei Varianten:
-/// 1. Ohne Argumente – gibt nur einen Zeilenumbruch aus  
-/// 2. Mit Formatstring  
+/// 1. Ohne Argumente – gibt nur einen Zeilenumbruch aus
+/// 2. Mit Formatstring
 /// 3. Mit Formatstring und Argumenten
 #[macro_export]
-macro_rules! serial_println 
+macro_rules! serial_println
 {
     () => ($crate::serial_print!("\n"));
     ($fmt:expr) => ($crate::serial_print!(concat!($fmt, "\n")));
     ($fmt:expr, $($arg:tt)*) => ($crate::serial_print!(concat!($fmt, "\n"), $($arg)*));
 }
+
+/// ### serial_print_to!
+///
+/// Wie [serial_print!], aber für einen [ComPort] oder einen [Channel] als
+/// erstes Argument:
+///
+/// ```ignore
+/// serial_print_to!(ComPort::Com2, "x = {}", x);
+/// serial_print_to!(Channel::Log, "boot");
+/// ```
+#[macro_export]
+macro_rules! serial_print_to
+{
+    ($port:expr, $($arg:tt)*) =>
+    {
+        $crate::serial::_print_to($port, format_args!($($arg)*));
+    };
+}
+
+/// ### serial_println_to!
+///
+/// Wie [serial_println!], aber für einen [ComPort] oder einen [Channel] als
+/// erstes Argument.
+#[macro_export]
+macro_rules! serial_println_to
+{
+    ($port:expr) => ($crate::serial_print_to!($port, "\n"));
+    ($port:expr, $fmt:expr) => ($crate::serial_print_to!($port, concat!($fmt, "\n")));
+    ($port:expr, $fmt:expr, $($arg:tt)*) => ($crate::serial_print_to!($port, concat!($fmt, "\n"), $($arg)*));
+}
+
+#[test_case]
+fn test_input_buffer()
+{
+    let mut input = Input { bytes: [0; INPUT_SIZE], head: INPUT_SIZE - 1, len: 0 };
+    assert!(input.push(b'a'));
+    assert!(input.push(b'b'));
+    assert_eq!(input.pop(), Some(b'a'));
+    assert_eq!(input.pop(), Some(b'b'));
+    assert_eq!(input.pop(), None);
+    for _ in 0..INPUT_SIZE
+    {
+        assert!(input.push(b'x'));
+    }
+    assert!(!input.push(b'x'));
+}
+
+#[test_case]
+fn test_detect_and_route()
+{
+    // QEMU stellt mit `-serial stdio` nur COM1 bereit.
+    assert_eq!(detect().first(), Some(&ComPort::Com1));
+    assert_eq!(config(ComPort::Com1), Some(UartConfig::DEFAULT));
+    assert_eq!(route(Channel::Log, ComPort::Com4), Err(SerialError::NotPresent));
+    assert_eq!(ComPort::from(Channel::Log), ComPort::Com1);
+    assert_eq!(alloc::format!("{} IRQ {}", ComPort::Com2, ComPort::Com2.irq()), "COM2 IRQ 3");
+}
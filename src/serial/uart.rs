@@ -0,0 +1,329 @@
+//! Treiber für den UART-Baustein 16550.
+//!
+//! Ein [Uart] belegt acht I/O-Ports ab seiner Basisadresse. Die Register
+//! (relativ zur Basis) sind:
+//!
+//! | Offset | Lesen | Schreiben |
+//! |--------|-------|-----------|
+//! | 0 | Empfangene Daten | Zu sendende Daten (bei DLAB: Teiler, unteres Byte) |
+//! | 1 | Freigegebene Interrupts | dto. (bei DLAB: Teiler, oberes Byte) |
+//! | 2 | Interrupt-Kennung | FIFO-Steuerung |
+//! | 3 | Leitungssteuerung | dto. (Bit 7: DLAB) |
+//! | 4 | Modemsteuerung | dto. (Bit 4: Loopback) |
+//! | 5 | Leitungsstatus | – |
+//! | 7 | Scratch-Register | dto. |
+
+use core::fmt;
+use x86_64::instructions::port::Port;
+
+use super::SerialError;
+
+/// Takt des Bausteins geteilt durch 16: die höchste Baudrate.
+pub const MAX_BAUD_RATE: u32 = 115_200;
+
+mod register
+{
+    pub const DATA: u16 = 0;
+    pub const INTERRUPT_ENABLE: u16 = 1;
+    pub const FIFO_CONTROL: u16 = 2;
+    pub const LINE_CONTROL: u16 = 3;
+    pub const MODEM_CONTROL: u16 = 4;
+    pub const LINE_STATUS: u16 = 5;
+    pub const SCRATCH: u16 = 7;
+}
+
+mod line_status
+{
+    /// Ein empfangenes Byte liegt bereit.
+    pub const DATA_READY: u8 = 0x01;
+    /// Das Senderegister ist leer.
+    pub const TRANSMIT_EMPTY: u8 = 0x20;
+}
+
+/// Anzahl der Datenbits je Zeichen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataBits
+{
+    Five = 0,
+    Six = 1,
+    Seven = 2,
+    Eight = 3,
+}
+
+/// Paritätsbit je Zeichen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parity
+{
+    None = 0b000,
+    Odd = 0b001,
+    Even = 0b011,
+    /// Immer 1.
+    Mark = 0b101,
+    /// Immer 0.
+    Space = 0b111,
+}
+
+/// Anzahl der Stoppbits. Bei fünf Datenbits wird aus [StopBits::Two]
+/// eineinhalb.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopBits
+{
+    One,
+    Two,
+}
+
+/// Füllstand des Empfangs-FIFOs, ab dem der Baustein einen Interrupt auslöst.
+/// Kleinere Schwellen antworten schneller, größere sparen Interrupts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FifoTrigger
+{
+    /// Ohne FIFO: jedes Byte einzeln.
+    Disabled,
+    Bytes1,
+    Bytes4,
+    Bytes8,
+    Bytes14,
+}
+
+/// ## UartConfig
+///
+/// Einstellungen einer Schnittstelle. Der Standard ist 38400 Baud, 8N1 mit
+/// einer FIFO-Schwelle von 14 Bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UartConfig
+{
+    /// Muss [MAX_BAUD_RATE] ganzzahlig teilen.
+    pub baud_rate: u32,
+    pub data_bits: DataBits,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    pub fifo: FifoTrigger,
+}
+
+impl Default for UartConfig
+{
+    fn default() -> UartConfig
+    {
+        UartConfig::DEFAULT
+    }
+}
+
+impl UartConfig
+{
+    pub const DEFAULT: UartConfig = UartConfig
+    {
+        baud_rate: 38_400,
+        data_bits: DataBits::Eight,
+        parity: Parity::None,
+        stop_bits: StopBits::One,
+        fifo: FifoTrigger::Bytes14,
+    };
+
+    /// Teiler des Bausteintakts für die Baudrate.
+    fn divisor(&self) -> Result<u16, SerialError>
+    {
+        if self.baud_rate == 0 || !MAX_BAUD_RATE.is_multiple_of(self.baud_rate)
+        {
+            return Err(SerialError::InvalidBaudRate);
+        }
+        u16::try_from(MAX_BAUD_RATE / self.baud_rate).map_err(|_| SerialError::InvalidBaudRate)
+    }
+
+    /// Wert des Registers für die Leitungssteuerung (ohne DLAB).
+    fn line_control(&self) -> u8
+    {
+        let stop_bits = match self.stop_bits
+        {
+            StopBits::One => 0,
+            StopBits::Two => 1 << 2,
+        };
+        self.data_bits as u8 | stop_bits | (self.parity as u8) << 3
+    }
+
+    /// Wert des Registers für die FIFO-Steuerung; leert beide FIFOs.
+    fn fifo_control(&self) -> u8
+    {
+        let trigger = match self.fifo
+        {
+            FifoTrigger::Disabled => return 0,
+            FifoTrigger::Bytes1 => 0,
+            FifoTrigger::Bytes4 => 1,
+            FifoTrigger::Bytes8 => 2,
+            FifoTrigger::Bytes14 => 3,
+        };
+        0x07 | trigger << 6
+    }
+}
+
+impl fmt::Display for UartConfig
+{
+    /// Kurzform wie `38400 8N1`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        let parity = match self.parity
+        {
+            Parity::None => 'N',
+            Parity::Odd => 'O',
+            Parity::Even => 'E',
+            Parity::Mark => 'M',
+            Parity::Space => 'S',
+        };
+        let stop_bits = match self.stop_bits
+        {
+            StopBits::One => 1,
+            StopBits::Two => 2,
+        };
+        write!(f, "{} {}{}{}", self.baud_rate, self.data_bits as u8 + 5, parity, stop_bits)
+    }
+}
+
+/// ## Uart
+///
+/// Ein 16550 an einer festen I/O-Basisadresse.
+#[derive(Debug)]
+pub struct Uart
+{
+    base: u16,
+    config: UartConfig,
+}
+
+impl Uart
+{
+    /// Ein Baustein an `base`, noch ohne Einstellungen.
+    ///
+    /// # Safety
+    ///
+    /// An `base` muss ein 16550 liegen oder nichts; niemand sonst darf die
+    /// Ports benutzen.
+    pub const unsafe fn new(base: u16) -> Uart
+    {
+        Uart { base, config: UartConfig::DEFAULT }
+    }
+
+    fn read(&self, register: u16) -> u8
+    {
+        unsafe { Port::new(self.base + register).read() }
+    }
+
+    fn write(&mut self, register: u16, value: u8)
+    {
+        unsafe { Port::new(self.base + register).write(value) }
+    }
+
+    pub fn base(&self) -> u16
+    {
+        self.base
+    }
+
+    pub fn config(&self) -> UartConfig
+    {
+        self.config
+    }
+
+    /// ## Selbsttest
+    ///
+    /// Prüft zuerst das Scratch-Register, dann im Loopback-Modus, ob ein
+    /// gesendetes Byte zurückkommt. Ohne Baustein liest der Bus `0xFF`, daher
+    /// schlagen beide Proben dann fehl. Danach ist der Baustein mit der
+    /// bisherigen Einstellung in Betrieb.
+    pub fn self_test(&mut self) -> Result<(), SerialError>
+    {
+        self.write(register::SCRATCH, 0x5A);
+        if self.read(register::SCRATCH) != 0x5A
+        {
+            return Err(SerialError::NotPresent);
+        }
+        // Ohne gültigen Teiler sendet der Baustein auch im Loopback nichts.
+        let config = self.config;
+        self.configure(&config)?;
+        self.write(register::INTERRUPT_ENABLE, 0x00);
+        // Loopback mit RTS, OUT1 und OUT2.
+        self.write(register::MODEM_CONTROL, 0x1E);
+        // Alte Bytes aus dem Empfang werfen.
+        while self.read(register::LINE_STATUS) & line_status::DATA_READY != 0
+        {
+            self.read(register::DATA);
+        }
+        self.write(register::DATA, 0xAE);
+        let mut echoed = None;
+        for _ in 0..100_000
+        {
+            if self.read(register::LINE_STATUS) & line_status::DATA_READY != 0
+            {
+                echoed = Some(self.read(register::DATA));
+                break;
+            }
+            core::hint::spin_loop();
+        }
+        self.configure(&config)?;
+        match echoed
+        {
+            Some(0xAE) => Ok(()),
+            _ => Err(SerialError::SelfTestFailed),
+        }
+    }
+
+    /// Stellt Baudrate, Zeichenformat und FIFO ein und schaltet den
+    /// Empfangs-Interrupt frei. Die Leitung zum PIC (OUT2) ist danach aktiv.
+    pub fn configure(&mut self, config: &UartConfig) -> Result<(), SerialError>
+    {
+        let [low, high] = config.divisor()?.to_le_bytes();
+        self.write(register::INTERRUPT_ENABLE, 0x00);
+        self.write(register::LINE_CONTROL, 0x80);
+        self.write(register::DATA, low);
+        self.write(register::INTERRUPT_ENABLE, high);
+        self.write(register::LINE_CONTROL, config.line_control());
+        self.write(register::FIFO_CONTROL, config.fifo_control());
+        // DTR, RTS und OUT2.
+        self.write(register::MODEM_CONTROL, 0x0B);
+        self.write(register::INTERRUPT_ENABLE, 0x01);
+        self.config = *config;
+        Ok(())
+    }
+
+    /// Sendet ein Byte, sobald das Senderegister frei ist.
+    pub fn send(&mut self, byte: u8)
+    {
+        while self.read(register::LINE_STATUS) & line_status::TRANSMIT_EMPTY == 0
+        {
+            core::hint::spin_loop();
+        }
+        self.write(register::DATA, byte);
+    }
+
+    /// Holt ein empfangenes Byte ab, falls eines bereitliegt.
+    pub fn try_receive(&mut self) -> Option<u8>
+    {
+        (self.read(register::LINE_STATUS) & line_status::DATA_READY != 0).then(|| self.read(register::DATA))
+    }
+}
+
+impl fmt::Write for Uart
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result
+    {
+        for byte in s.bytes()
+        {
+            self.send(byte);
+        }
+        Ok(())
+    }
+}
+
+#[test_case]
+fn test_config_registers()
+{
+    let config = UartConfig::default();
+    assert_eq!(config.divisor(), Ok(3));
+    assert_eq!(config.line_control(), 0x03);
+    assert_eq!(config.fifo_control(), 0xC7);
+
+    let config = UartConfig { baud_rate: 9600, data_bits: DataBits::Seven, parity: Parity::Even, stop_bits: StopBits::Two, fifo: FifoTrigger::Bytes4 };
+    assert_eq!(config.divisor(), Ok(12));
+    assert_eq!(config.line_control(), 0b0001_1110);
+    assert_eq!(config.fifo_control(), 0x47);
+    assert_eq!(alloc::format!("{}", config), "9600 7E2");
+
+    assert_eq!(UartConfig { baud_rate: 1, ..config }.divisor(), Err(SerialError::InvalidBaudRate));
+    assert_eq!(UartConfig { baud_rate: 7000, ..config }.divisor(), Err(SerialError::InvalidBaudRate));
+}
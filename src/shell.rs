@@ -0,0 +1,259 @@
+//! # Modul: shell
+//!
+//! Dieses Modul stellt eine einfache **Kernel-Shell** bereit: Sie liest Zeilen
+//! mit dem [LineEditor], zerlegt sie an Leerzeichen und führt den passenden
+//! Befehl aus.
+//!
+//! # Aufbau
+//!
+//! | Komponente | Aufgabe |
+//! |------------|---------|
+//! | [Terminal] | Ausgabe samt Bewegung der Schreibmarke, z. B. [VgaTerminal] |
+//! | [console] | Bildschirm und serielle Schnittstelle zugleich, auch ohne Bildschirm bedienbar |
+//! | [line] | Zeileneditor mit Historie und Vervollständigung |
+//! | [builtins] | Eingebaute Befehle wie `help`, `meminfo` oder `reboot` |
+//! | [register] | Meldet weitere Befehle aus anderen Modulen an |
+//! | [run] | Die Eingabeschleife |
+//!
+//! # Beispiel
+//!
+//! ```ignore
+//! fn hello(terminal: &mut dyn Terminal, args: &[&str]) -> Result<(), CommandError>
+//! {
+//!     writeln!(terminal, "Hallo, {}!", args.get(1).unwrap_or(&"Welt"))?;
+//!     Ok(())
+//! }
+//!
+//! shell::register("hello", "Begrüßt jemanden", hello);
+//! ```
+
+pub mod builtins;
+pub mod console;
+pub mod line;
+
+pub use console::{Console, ConsoleInput, SerialTerminal};
+pub use line::LineEditor;
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::keyboard::Key;
+use crate::sync::{Lazy, Mutex};
+use crate::vga_buffer::{BUFFER_WIDTH, WRITER};
+
+/// Eingabeaufforderung vor jeder Zeile.
+pub const PROMPT: &str = "> ";
+
+/// ## Terminal
+///
+/// Ein zeilenorientiertes Ausgabegerät, auf dem der [LineEditor] die
+/// Schreibmarke innerhalb der aktuellen Zeile bewegen kann.
+pub trait Terminal: fmt::Write
+{
+    /// Anzahl der Zeichen je Zeile, falls begrenzt. Längere Eingaben nimmt
+    /// der [LineEditor] nicht an.
+    fn width(&self) -> Option<usize>;
+
+    /// Bewegt die Schreibmarke um `n` Zeichen nach links.
+    fn cursor_left(&mut self, n: usize);
+
+    /// Bewegt die Schreibmarke um `n` Zeichen nach rechts.
+    fn cursor_right(&mut self, n: usize);
+
+    /// Löscht die Zeile ab der Schreibmarke.
+    fn erase_to_end(&mut self);
+
+    /// Löscht den ganzen Bildschirm.
+    fn clear(&mut self);
+}
+
+/// Fehler eines Befehls.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandError
+{
+    /// Falsche Anzahl oder Form der Argumente.
+    InvalidArguments,
+    /// Der Befehl ist mit dieser Meldung gescheitert.
+    Failed(String),
+    /// Die Ausgabe auf das Terminal ist gescheitert.
+    Output,
+}
+
+impl From<fmt::Error> for CommandError
+{
+    fn from(_: fmt::Error) -> CommandError
+    {
+        CommandError::Output
+    }
+}
+
+/// Ein Befehl erhält das Terminal und alle Wörter der Zeile; `args[0]` ist
+/// sein eigener Name.
+pub type Handler = fn(&mut dyn Terminal, &[&str]) -> Result<(), CommandError>;
+
+/// Ein angemeldeter Befehl.
+#[derive(Debug, Clone, Copy)]
+pub struct Command
+{
+    pub name: &'static str,
+    /// Einzeilige Beschreibung für `help`.
+    pub help: &'static str,
+    pub handler: Handler,
+}
+
+/// Alle Befehle nach Namen, beginnend mit den [builtins].
+static COMMANDS: Lazy<Mutex<BTreeMap<&'static str, Command>>> = Lazy::new(||
+{
+    let commands = builtins::COMMANDS.iter().map(|command| (command.name, *command)).collect();
+    Mutex::new(commands)
+});
+
+/// Meldet einen Befehl an. Gibt `false` zurück, wenn der Name vergeben ist.
+pub fn register(name: &'static str, help: &'static str, handler: Handler) -> bool
+{
+    let mut commands = COMMANDS.lock();
+    if commands.contains_key(name) || name.is_empty() || name.contains(char::is_whitespace)
+    {
+        return false;
+    }
+    commands.insert(name, Command { name, help, handler });
+    true
+}
+
+/// Meldet einen Befehl ab.
+pub fn unregister(name: &str) -> bool
+{
+    COMMANDS.lock().remove(name).is_some()
+}
+
+/// Alle Befehle, nach Namen sortiert.
+pub fn commands() -> Vec<Command>
+{
+    COMMANDS.lock().values().copied().collect()
+}
+
+/// Namen aller Befehle, die mit `prefix` beginnen.
+pub fn complete(prefix: &str) -> Vec<String>
+{
+    COMMANDS.lock().keys().filter(|name| name.starts_with(prefix)).map(|name| String::from(*name)).collect()
+}
+
+/// Führt eine Zeile aus und gibt Fehler als Meldung zurück.
+pub fn execute(terminal: &mut dyn Terminal, line: &str) -> Result<(), String>
+{
+    let args: Vec<&str> = line.split_whitespace().collect();
+    let Some(&name) = args.first()
+    else
+    {
+        return Ok(());
+    };
+    // Die Sperre wird vor dem Aufruf frei, damit Befehle selbst welche
+    // anmelden können.
+    let command = COMMANDS.lock().get(name).copied().ok_or_else(|| alloc::format!("{}: command not found", name))?;
+    (command.handler)(terminal, &args).map_err(|error| match error
+    {
+        CommandError::InvalidArguments => alloc::format!("{}: invalid arguments (see `help {}`)", name, name),
+        CommandError::Failed(message) => alloc::format!("{}: {}", name, message),
+        CommandError::Output => alloc::format!("{}: output failed", name),
+    })
+}
+
+/// ## Eingabeschleife
+///
+/// Liest mit `read_key` Zeile für Zeile und führt sie aus. Kehrt nicht
+/// zurück; `reboot` startet den Rechner neu.
+pub fn run(terminal: &mut dyn Terminal, mut read_key: impl FnMut() -> Key) -> !
+{
+    let mut editor = LineEditor::new();
+    loop
+    {
+        let _ = terminal.write_str(PROMPT);
+        let line = loop
+        {
+            if let Some(line) = editor.handle(read_key(), terminal, PROMPT, &complete)
+            {
+                break line;
+            }
+        };
+        if let Err(message) = execute(terminal, &line)
+        {
+            let _ = writeln!(terminal, "{}", message);
+        }
+    }
+}
+
+/// ## VgaTerminal
+///
+/// Der VGA-Textbildschirm als [Terminal]. Eingaben bleiben in der untersten
+/// Zeile; daher begrenzt [BUFFER_WIDTH] ihre Länge.
+#[derive(Debug, Default)]
+pub struct VgaTerminal;
+
+impl fmt::Write for VgaTerminal
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result
+    {
+        WRITER.lock().write_string(s);
+        Ok(())
+    }
+}
+
+impl Terminal for VgaTerminal
+{
+    fn width(&self) -> Option<usize>
+    {
+        Some(BUFFER_WIDTH)
+    }
+
+    fn cursor_left(&mut self, n: usize)
+    {
+        let mut writer = WRITER.lock();
+        let column = writer.column().saturating_sub(n);
+        writer.set_column(column);
+    }
+
+    fn cursor_right(&mut self, n: usize)
+    {
+        let mut writer = WRITER.lock();
+        let column = writer.column() + n;
+        writer.set_column(column);
+    }
+
+    fn erase_to_end(&mut self)
+    {
+        WRITER.lock().clear_to_end();
+    }
+
+    fn clear(&mut self)
+    {
+        WRITER.lock().clear_screen();
+    }
+}
+
+#[test_case]
+fn test_register_and_execute()
+{
+    fn greet(terminal: &mut dyn Terminal, args: &[&str]) -> Result<(), CommandError>
+    {
+        match args
+        {
+            [_, name] => Ok(write!(terminal, "hi {}", name)?),
+            _ => Err(CommandError::InvalidArguments),
+        }
+    }
+
+    assert!(register("test-greet", "test", greet));
+    assert!(!register("test-greet", "test", greet));
+    assert!(!register("help", "test", greet));
+    assert_eq!(complete("test-g"), ["test-greet"]);
+
+    let mut output = line::Recorder::default();
+    assert_eq!(execute(&mut output, "  test-greet   you "), Ok(()));
+    assert_eq!(output.line(), "hi you");
+    assert_eq!(execute(&mut output, "test-greet"), Err(String::from("test-greet: invalid arguments (see `help test-greet`)")));
+    assert_eq!(execute(&mut output, "nonexistent"), Err(String::from("nonexistent: command not found")));
+    assert!(unregister("test-greet"));
+    assert!(complete("test-g").is_empty());
+}
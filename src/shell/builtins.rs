@@ -0,0 +1,201 @@
+//! Eingebaute Befehle.
+//!
+//! | Befehl | Wirkung |
+//! |--------|---------|
+//! | `help [befehl]` | Listet alle Befehle oder beschreibt einen |
+//! | `clear` | Löscht den Bildschirm |
+//! | `echo [text...]` | Gibt die Argumente aus |
+//! | `uptime` | Laufzeit seit dem Start |
+//! | `meminfo` | Belegung von Heap und physischem Speicher |
+//! | `lspci` | PCI-Geräte samt Treiber |
+//! | `dmesg [-c]` | Die letzten Logmeldungen, mit `-c` danach gelöscht |
+//! | `loglevel [modul] level` | Setzt den Logfilter, für ein Modul mit `reset` zurück |
+//! | `reboot` | Startet den Rechner neu |
+
+use log::LevelFilter;
+use x86_64::instructions::port::Port;
+
+use super::{Command, CommandError, Terminal};
+use crate::{allocator, logger, memory, pci, time};
+
+/// Alle eingebauten Befehle.
+pub static COMMANDS: [Command; 9] = [
+    Command { name: "help", help: "help [command] - list commands or describe one", handler: help },
+    Command { name: "clear", help: "clear - clear the screen", handler: clear },
+    Command { name: "echo", help: "echo [text...] - print the arguments", handler: echo },
+    Command { name: "uptime", help: "uptime - time since boot", handler: uptime },
+    Command { name: "meminfo", help: "meminfo - heap and physical memory usage", handler: meminfo },
+    Command { name: "lspci", help: "lspci - list PCI devices and their drivers", handler: lspci },
+    Command { name: "dmesg", help: "dmesg [-c] - show kernel log messages, -c clears them afterwards", handler: dmesg },
+    Command { name: "loglevel", help: "loglevel [module] off|error|warn|info|debug|trace|reset - set the log filter", handler: loglevel },
+    Command { name: "reboot", help: "reboot - restart the machine", handler: reboot_command },
+];
+
+fn help(terminal: &mut dyn Terminal, args: &[&str]) -> Result<(), CommandError>
+{
+    let commands = super::commands();
+    match args
+    {
+        [_] =>
+        {
+            for command in &commands
+            {
+                writeln!(terminal, "  {}", command.help)?;
+            }
+        }
+        [_, name] =>
+        {
+            let command = commands.iter().find(|command| command.name == *name)
+                .ok_or_else(|| CommandError::Failed(alloc::format!("no command named `{}`", name)))?;
+            writeln!(terminal, "{}", command.help)?;
+        }
+        _ => return Err(CommandError::InvalidArguments),
+    }
+    Ok(())
+}
+
+fn clear(terminal: &mut dyn Terminal, args: &[&str]) -> Result<(), CommandError>
+{
+    if args.len() != 1
+    {
+        return Err(CommandError::InvalidArguments);
+    }
+    terminal.clear();
+    Ok(())
+}
+
+fn echo(terminal: &mut dyn Terminal, args: &[&str]) -> Result<(), CommandError>
+{
+    writeln!(terminal, "{}", args[1..].join(" "))?;
+    Ok(())
+}
+
+fn uptime(terminal: &mut dyn Terminal, args: &[&str]) -> Result<(), CommandError>
+{
+    if args.len() != 1
+    {
+        return Err(CommandError::InvalidArguments);
+    }
+    let seconds = time::uptime_ms() / 1000;
+    writeln!(terminal, "up {}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)?;
+    Ok(())
+}
+
+fn meminfo(terminal: &mut dyn Terminal, args: &[&str]) -> Result<(), CommandError>
+{
+    if args.len() != 1
+    {
+        return Err(CommandError::InvalidArguments);
+    }
+    let used = allocator::used();
+    let size = allocator::HEAP_SIZE as usize;
+    writeln!(terminal, "heap:   {} KiB of {} KiB used ({}%)", used / 1024, size / 1024, used * 100 / size)?;
+    writeln!(terminal, "frames: {} allocated ({} KiB)", memory::allocated_frames(), memory::allocated_frames() * 4)?;
+    Ok(())
+}
+
+fn lspci(terminal: &mut dyn Terminal, args: &[&str]) -> Result<(), CommandError>
+{
+    if args.len() != 1
+    {
+        return Err(CommandError::InvalidArguments);
+    }
+    for device in pci::devices()
+    {
+        match pci::driver_name(device.address)
+        {
+            Some(driver) => writeln!(terminal, "{} -> {}", device, driver)?,
+            None => writeln!(terminal, "{}", device)?,
+        }
+    }
+    Ok(())
+}
+
+fn dmesg(terminal: &mut dyn Terminal, args: &[&str]) -> Result<(), CommandError>
+{
+    let clear = match args
+    {
+        [_] => false,
+        [_, "-c"] => true,
+        _ => return Err(CommandError::InvalidArguments),
+    };
+    for entry in logger::dmesg()
+    {
+        writeln!(terminal, "{}", entry)?;
+    }
+    if clear
+    {
+        logger::clear_dmesg();
+    }
+    Ok(())
+}
+
+fn loglevel(terminal: &mut dyn Terminal, args: &[&str]) -> Result<(), CommandError>
+{
+    let parse = |level: &str| level.parse::<LevelFilter>().map_err(|_| CommandError::InvalidArguments);
+    match args
+    {
+        [_] => writeln!(terminal, "{}", logger::level(""))?,
+        [_, level] => logger::set_level(parse(level)?),
+        [_, module, "reset"] =>
+        {
+            if !logger::reset_module_level(module)
+            {
+                return Err(CommandError::Failed(alloc::format!("no filter for `{}`", module)));
+            }
+        }
+        [_, module, level] => logger::set_module_level(module, parse(level)?),
+        _ => return Err(CommandError::InvalidArguments),
+    }
+    Ok(())
+}
+
+fn reboot_command(terminal: &mut dyn Terminal, args: &[&str]) -> Result<(), CommandError>
+{
+    if args.len() != 1
+    {
+        return Err(CommandError::InvalidArguments);
+    }
+    writeln!(terminal, "rebooting...")?;
+    reboot()
+}
+
+/// ## Neustart
+///
+/// Versucht nacheinander den Reset über den Tastatur-Controller (Befehl
+/// `0xFE` an Port `0x64`), über das Reset-Register `0xCF9` und zuletzt einen
+/// Triple Fault mit leerer IDT.
+pub fn reboot() -> !
+{
+    x86_64::instructions::interrupts::disable();
+    unsafe
+    {
+        let mut status: Port<u8> = Port::new(0x64);
+        // Warten, bis der Controller Befehle annimmt (Eingabepuffer leer).
+        for _ in 0..100_000
+        {
+            if status.read() & 0x02 == 0
+            {
+                break;
+            }
+            core::hint::spin_loop();
+        }
+        status.write(0xFE);
+        for _ in 0..100_000
+        {
+            core::hint::spin_loop();
+        }
+
+        // Vollständiger Reset über den Chipsatz.
+        Port::<u8>::new(0xCF9).write(0x06);
+        for _ in 0..100_000
+        {
+            core::hint::spin_loop();
+        }
+
+        let idt = x86_64::structures::DescriptorTablePointer { limit: 0, base: x86_64::VirtAddr::zero() };
+        x86_64::instructions::tables::lidt(&idt);
+        x86_64::instructions::interrupts::int3();
+    }
+    crate::hlt_loop();
+}
@@ -0,0 +1,297 @@
+//! Konsole über Bildschirm und serielle Schnittstelle.
+//!
+//! Die [Console] zeigt die Shell zugleich auf dem VGA-Bildschirm und auf dem
+//! Port des Kanals [Channel::Console] (anfangs **COM1**); [ConsoleInput] nimmt Tasten von der Tastatur und Bytes von der
+//! seriellen Schnittstelle an. Mit `-serial stdio` lässt sich der Kernel so
+//! ohne Bildschirm aus einem Skript bedienen.
+//!
+//! Auf der seriellen Seite steuern ANSI-Sequenzen die Schreibmarke, und der
+//! [KeyDecoder] übersetzt die Sequenzen, die ein Terminal für Pfeil- und
+//! Sondertasten sendet, zurück in [Key]s.
+
+use core::fmt::{self, Write};
+
+use super::{Terminal, VgaTerminal};
+use crate::keyboard::{self, Key};
+use crate::serial::{self, Channel, ComPort};
+use crate::vga_buffer::BUFFER_WIDTH;
+
+/// ## SerialTerminal
+///
+/// Der Port von [Channel::Console] als [Terminal]. Die Breite des Terminals
+/// am anderen Ende ist unbekannt.
+#[derive(Debug, Default)]
+pub struct SerialTerminal;
+
+impl fmt::Write for SerialTerminal
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result
+    {
+        // Terminals im Rohmodus brauchen den Wagenrücklauf ausdrücklich.
+        let mut port = ComPort::from(Channel::Console);
+        for (index, line) in s.split('\n').enumerate()
+        {
+            if index > 0
+            {
+                port.write_str("\r\n")?;
+            }
+            port.write_str(line)?;
+        }
+        Ok(())
+    }
+}
+
+impl Terminal for SerialTerminal
+{
+    fn width(&self) -> Option<usize>
+    {
+        None
+    }
+
+    fn cursor_left(&mut self, n: usize)
+    {
+        if n > 0
+        {
+            let _ = write!(self, "\x1b[{}D", n);
+        }
+    }
+
+    fn cursor_right(&mut self, n: usize)
+    {
+        if n > 0
+        {
+            let _ = write!(self, "\x1b[{}C", n);
+        }
+    }
+
+    fn erase_to_end(&mut self)
+    {
+        let _ = self.write_str("\x1b[K");
+    }
+
+    fn clear(&mut self)
+    {
+        let _ = self.write_str("\x1b[2J\x1b[H");
+    }
+}
+
+/// ## Console
+///
+/// Gibt alles auf [VgaTerminal] und [SerialTerminal] aus. Die Zeilen sind so
+/// lang wie auf dem Bildschirm, damit beide Seiten gleich aussehen.
+#[derive(Debug, Default)]
+pub struct Console;
+
+impl fmt::Write for Console
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result
+    {
+        VgaTerminal.write_str(s)?;
+        SerialTerminal.write_str(s)
+    }
+}
+
+impl Terminal for Console
+{
+    fn width(&self) -> Option<usize>
+    {
+        Some(BUFFER_WIDTH)
+    }
+
+    fn cursor_left(&mut self, n: usize)
+    {
+        VgaTerminal.cursor_left(n);
+        SerialTerminal.cursor_left(n);
+    }
+
+    fn cursor_right(&mut self, n: usize)
+    {
+        VgaTerminal.cursor_right(n);
+        SerialTerminal.cursor_right(n);
+    }
+
+    fn erase_to_end(&mut self)
+    {
+        VgaTerminal.erase_to_end();
+        SerialTerminal.erase_to_end();
+    }
+
+    fn clear(&mut self)
+    {
+        VgaTerminal.clear();
+        SerialTerminal.clear();
+    }
+}
+
+/// Zustand des [KeyDecoder]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum State
+{
+    #[default]
+    Ground,
+    /// Nach `ESC`.
+    Escape,
+    /// Nach `ESC [`, mit dem ersten Zahlenparameter und ob er schon
+    /// abgeschlossen ist.
+    Csi(u8, bool),
+    /// Nach `ESC O`.
+    Ss3,
+}
+
+/// ## KeyDecoder
+///
+/// Übersetzt Bytes eines Terminals in [Key]s:
+///
+/// | Bytes | Taste |
+/// |-------|-------|
+/// | `\r`, `\n`, `\r\n` | [Key::Enter] |
+/// | `0x7F`, `0x08` | [Key::Backspace] |
+/// | `ESC [ A` … `ESC [ D`, auch `ESC O A` … | Pfeiltasten |
+/// | `ESC [ H`, `ESC [ 1 ~`, `ESC [ F`, `ESC [ 4 ~` | Pos1 und Ende |
+/// | `ESC [ 3 ~` | [Key::Delete] |
+///
+/// Andere Steuerzeichen kommen wie bei der Tastatur als [Key::Char] an,
+/// unbekannte Sequenzen und Bytes außerhalb von ASCII werden verworfen.
+#[derive(Debug, Default)]
+pub struct KeyDecoder
+{
+    state: State,
+    /// Das letzte Byte war `\r`; ein folgendes `\n` gehört dazu.
+    after_cr: bool,
+}
+
+impl KeyDecoder
+{
+    pub const fn new() -> KeyDecoder
+    {
+        KeyDecoder { state: State::Ground, after_cr: false }
+    }
+
+    /// Verarbeitet ein Byte und gibt die Taste zurück, falls sie vollständig ist.
+    pub fn feed(&mut self, byte: u8) -> Option<Key>
+    {
+        let after_cr = core::mem::replace(&mut self.after_cr, byte == b'\r');
+        match (self.state, byte)
+        {
+            (State::Ground, 0x1b) =>
+            {
+                self.state = State::Escape;
+                None
+            }
+            (State::Ground, b'\n') if after_cr => None,
+            (State::Ground, b'\r' | b'\n') => Some(Key::Enter),
+            (State::Ground, 0x7f | 0x08) => Some(Key::Backspace),
+            (State::Ground, b'\t') => Some(Key::Tab),
+            (State::Ground, 0x00..=0x7e) => Some(Key::Char(char::from(byte))),
+            (State::Ground, _) => None,
+            (State::Escape, b'[') =>
+            {
+                self.state = State::Csi(0, false);
+                None
+            }
+            (State::Escape, b'O') =>
+            {
+                self.state = State::Ss3;
+                None
+            }
+            (State::Escape, 0x1b) => Some(Key::Escape),
+            (State::Escape, _) =>
+            {
+                self.state = State::Ground;
+                Some(Key::Escape)
+            }
+            (State::Csi(parameter, false), b'0'..=b'9') =>
+            {
+                self.state = State::Csi(parameter.saturating_mul(10).saturating_add(byte - b'0'), false);
+                None
+            }
+            // Weitere Parameter (z. B. Umschalttasten) werden überlesen.
+            (State::Csi(parameter, _), b'0'..=b'?') =>
+            {
+                self.state = State::Csi(parameter, true);
+                None
+            }
+            (State::Csi(parameter, _), _) =>
+            {
+                self.state = State::Ground;
+                match (byte, parameter)
+                {
+                    (b'~', 1 | 7) => Some(Key::Home),
+                    (b'~', 3) => Some(Key::Delete),
+                    (b'~', 4 | 8) => Some(Key::End),
+                    (b'~', _) => None,
+                    _ => Self::cursor_key(byte),
+                }
+            }
+            (State::Ss3, _) =>
+            {
+                self.state = State::Ground;
+                Self::cursor_key(byte)
+            }
+        }
+    }
+
+    /// Schlussbyte einer Sequenz für Pfeiltasten, Pos1 und Ende.
+    fn cursor_key(byte: u8) -> Option<Key>
+    {
+        match byte
+        {
+            b'A' => Some(Key::Up),
+            b'B' => Some(Key::Down),
+            b'C' => Some(Key::Right),
+            b'D' => Some(Key::Left),
+            b'H' => Some(Key::Home),
+            b'F' => Some(Key::End),
+            _ => None,
+        }
+    }
+}
+
+/// ## ConsoleInput
+///
+/// Wartet auf die nächste Taste von der Tastatur oder der seriellen
+/// Schnittstelle. Letztere muss mit [serial::init] eingeschaltet sein.
+#[derive(Debug, Default)]
+pub struct ConsoleInput
+{
+    decoder: KeyDecoder,
+}
+
+impl ConsoleInput
+{
+    pub const fn new() -> ConsoleInput
+    {
+        ConsoleInput { decoder: KeyDecoder::new() }
+    }
+
+    pub fn read(&mut self) -> Key
+    {
+        loop
+        {
+            if let Some(key) = keyboard::try_read()
+            {
+                return key;
+            }
+            while let Some(byte) = serial::try_read(Channel::Console)
+            {
+                if let Some(key) = self.decoder.feed(byte)
+                {
+                    return key;
+                }
+            }
+            crate::sync::wait();
+        }
+    }
+}
+
+#[test_case]
+fn test_key_decoder()
+{
+    let mut decoder = KeyDecoder::new();
+    let mut decode = |bytes: &[u8]| bytes.iter().filter_map(|byte| decoder.feed(*byte)).collect::<alloc::vec::Vec<_>>();
+
+    assert_eq!(decode(b"ls\r\n\n\x7f\x03"), [Key::Char('l'), Key::Char('s'), Key::Enter, Key::Enter, Key::Backspace, Key::Char('\u{3}')]);
+    assert_eq!(decode(b"\x1b[A\x1b[B\x1bOC\x1b[D"), [Key::Up, Key::Down, Key::Right, Key::Left]);
+    assert_eq!(decode(b"\x1b[1~\x1b[3~\x1b[4~\x1b[H\x1b[1;5F\x1b[1;5~"), [Key::Home, Key::Delete, Key::End, Key::Home, Key::End, Key::Home]);
+    assert_eq!(decode(b"\x1b[15~\x1bxa\xc3\xa4"), [Key::Escape, Key::Char('a')]);
+}
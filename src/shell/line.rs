@@ -0,0 +1,397 @@
+//! Zeileneditor.
+//!
+//! Der [LineEditor] verarbeitet Taste für Taste und hält das Terminal dabei
+//! auf dem Stand der Eingabe:
+//!
+//! | Taste | Wirkung |
+//! |-------|---------|
+//! | ←, →, Pos1, Ende | Schreibmarke bewegen |
+//! | Rücktaste, Entf | Zeichen vor bzw. unter der Schreibmarke löschen |
+//! | ↑, ↓ | Durch frühere Zeilen blättern |
+//! | Tab | Befehlsnamen vervollständigen |
+//! | Strg+C | Zeile verwerfen |
+//! | Strg+L | Bildschirm löschen |
+
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::Terminal;
+use crate::keyboard::Key;
+
+/// Anzahl der Zeilen in der Historie.
+pub const HISTORY_SIZE: usize = 32;
+
+/// Größte Länge einer Zeile auf Terminals ohne feste Breite.
+const MAX_LINE: usize = 256;
+
+/// ## LineEditor
+///
+/// Eingabezeile samt Historie. Ausgaben an das Terminal sind nur Darstellung;
+/// Fehler dabei werden ignoriert.
+#[derive(Debug, Default)]
+pub struct LineEditor
+{
+    buffer: Vec<char>,
+    cursor: usize,
+    history: VecDeque<String>,
+    /// Angezeigter Eintrag der Historie; `None` ist die eigene Eingabe.
+    browsing: Option<usize>,
+    /// Die eigene Eingabe, während in der Historie geblättert wird.
+    draft: Vec<char>,
+}
+
+impl LineEditor
+{
+    pub const fn new() -> LineEditor
+    {
+        LineEditor { buffer: Vec::new(), cursor: 0, history: VecDeque::new(), browsing: None, draft: Vec::new() }
+    }
+
+    /// Frühere Zeilen, die älteste zuerst.
+    pub fn history(&self) -> impl Iterator<Item = &str>
+    {
+        self.history.iter().map(String::as_str)
+    }
+
+    /// Verarbeitet eine Taste. Mit Enter ist die Zeile fertig und wird
+    /// zurückgegeben; Strg+C ergibt eine leere Zeile. `prompt` steht bereits
+    /// vor der Eingabe, `complete` liefert die Kandidaten für Tab.
+    pub fn handle(&mut self, key: Key, terminal: &mut dyn Terminal, prompt: &str, complete: &dyn Fn(&str) -> Vec<String>) -> Option<String>
+    {
+        match key
+        {
+            Key::Enter =>
+            {
+                let _ = terminal.write_str("\n");
+                let line: String = self.buffer.iter().collect();
+                if !line.trim().is_empty() && self.history.back() != Some(&line)
+                {
+                    if self.history.len() == HISTORY_SIZE
+                    {
+                        self.history.pop_front();
+                    }
+                    self.history.push_back(line.clone());
+                }
+                self.reset();
+                return Some(line);
+            }
+            Key::Char('\u{3}') =>
+            {
+                let _ = terminal.write_str("^C\n");
+                self.reset();
+                return Some(String::new());
+            }
+            Key::Char('\u{c}') =>
+            {
+                terminal.clear();
+                let _ = terminal.write_str(prompt);
+                self.redraw_from(0, terminal);
+            }
+            Key::Char(character) if !character.is_control() =>
+            {
+                let mut text = [0; 4];
+                self.insert(character.encode_utf8(&mut text), terminal, prompt);
+            }
+            Key::Backspace if self.cursor > 0 =>
+            {
+                self.cursor -= 1;
+                self.buffer.remove(self.cursor);
+                terminal.cursor_left(1);
+                self.redraw_from(self.cursor, terminal);
+            }
+            Key::Delete if self.cursor < self.buffer.len() =>
+            {
+                self.buffer.remove(self.cursor);
+                self.redraw_from(self.cursor, terminal);
+            }
+            Key::Left if self.cursor > 0 =>
+            {
+                self.cursor -= 1;
+                terminal.cursor_left(1);
+            }
+            Key::Right if self.cursor < self.buffer.len() =>
+            {
+                self.cursor += 1;
+                terminal.cursor_right(1);
+            }
+            Key::Home =>
+            {
+                terminal.cursor_left(self.cursor);
+                self.cursor = 0;
+            }
+            Key::End =>
+            {
+                terminal.cursor_right(self.buffer.len() - self.cursor);
+                self.cursor = self.buffer.len();
+            }
+            Key::Up => self.browse_back(terminal),
+            Key::Down => self.browse_forward(terminal),
+            Key::Tab => self.complete(terminal, prompt, complete),
+            _ => (),
+        }
+        None
+    }
+
+    fn reset(&mut self)
+    {
+        self.buffer.clear();
+        self.cursor = 0;
+        self.browsing = None;
+        self.draft.clear();
+    }
+
+    /// Höchste Länge der Eingabe hinter `prompt`.
+    fn limit(terminal: &dyn Terminal, prompt: &str) -> usize
+    {
+        // Die Schreibmarke braucht hinter dem letzten Zeichen noch Platz.
+        terminal.width().map_or(MAX_LINE, |width| width.saturating_sub(prompt.chars().count() + 1))
+    }
+
+    /// Fügt `text` an der Schreibmarke ein, soweit die Zeile Platz hat.
+    fn insert(&mut self, text: &str, terminal: &mut dyn Terminal, prompt: &str)
+    {
+        let free = Self::limit(terminal, prompt).saturating_sub(self.buffer.len());
+        let start = self.cursor;
+        for character in text.chars().take(free)
+        {
+            self.buffer.insert(self.cursor, character);
+            self.cursor += 1;
+        }
+        self.redraw_from(start, terminal);
+        terminal.cursor_right(self.cursor - start);
+    }
+
+    /// Schreibt die Eingabe ab `start` neu, wobei die Schreibmarke vorher an
+    /// `start` steht. Danach steht sie wieder dort bzw. am Cursor.
+    fn redraw_from(&mut self, start: usize, terminal: &mut dyn Terminal)
+    {
+        let tail: String = self.buffer[start..].iter().collect();
+        let _ = terminal.write_str(&tail);
+        terminal.erase_to_end();
+        terminal.cursor_left(self.buffer.len() - start);
+        if start == 0
+        {
+            terminal.cursor_right(self.cursor);
+        }
+    }
+
+    /// Ersetzt die ganze Eingabe durch `line`.
+    fn replace(&mut self, line: Vec<char>, terminal: &mut dyn Terminal)
+    {
+        terminal.cursor_left(self.cursor);
+        self.buffer = line;
+        self.cursor = self.buffer.len();
+        self.redraw_from(0, terminal);
+    }
+
+    fn browse_back(&mut self, terminal: &mut dyn Terminal)
+    {
+        let index = match self.browsing
+        {
+            None if !self.history.is_empty() =>
+            {
+                self.draft = self.buffer.clone();
+                self.history.len() - 1
+            }
+            Some(index) if index > 0 => index - 1,
+            _ => return,
+        };
+        self.browsing = Some(index);
+        self.replace(self.history[index].chars().collect(), terminal);
+    }
+
+    fn browse_forward(&mut self, terminal: &mut dyn Terminal)
+    {
+        let Some(index) = self.browsing
+        else
+        {
+            return;
+        };
+        if index + 1 < self.history.len()
+        {
+            self.browsing = Some(index + 1);
+            self.replace(self.history[index + 1].chars().collect(), terminal);
+        }
+        else
+        {
+            self.browsing = None;
+            let draft = core::mem::take(&mut self.draft);
+            self.replace(draft, terminal);
+        }
+    }
+
+    /// Vervollständigt das erste Wort. Bei mehreren Kandidaten wird bis zum
+    /// gemeinsamen Anfang ergänzt oder, wenn das nichts bringt, die Liste
+    /// angezeigt.
+    fn complete(&mut self, terminal: &mut dyn Terminal, prompt: &str, complete: &dyn Fn(&str) -> Vec<String>)
+    {
+        let word: String = self.buffer[..self.cursor].iter().collect();
+        if word.contains(char::is_whitespace)
+        {
+            return;
+        }
+        let candidates = complete(&word);
+        let Some(first) = candidates.first()
+        else
+        {
+            return;
+        };
+        if candidates.len() == 1
+        {
+            let mut rest = String::from(&first[word.len()..]);
+            rest.push(' ');
+            self.insert(&rest, terminal, prompt);
+            return;
+        }
+        let common = candidates.iter().fold(first.len(), |len, candidate|
+        {
+            first.bytes().zip(candidate.bytes()).take(len).take_while(|(a, b)| a == b).count()
+        });
+        if common > word.len()
+        {
+            self.insert(&first[word.len()..common], terminal, prompt);
+            return;
+        }
+        let _ = terminal.write_str("\n");
+        let _ = terminal.write_str(&candidates.join("  "));
+        let _ = terminal.write_str("\n");
+        let _ = terminal.write_str(prompt);
+        self.redraw_from(0, terminal);
+    }
+}
+
+/// Ein [Terminal] im Speicher, das nachbildet, was auf dem Bildschirm stünde.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub(super) struct Recorder
+{
+    lines: Vec<String>,
+    current: Vec<char>,
+    column: usize,
+}
+
+#[cfg(test)]
+impl Recorder
+{
+    /// Die aktuelle Zeile.
+    pub(super) fn line(&self) -> String
+    {
+        self.current.iter().collect()
+    }
+}
+
+#[cfg(test)]
+impl core::fmt::Write for Recorder
+{
+    fn write_str(&mut self, s: &str) -> core::fmt::Result
+    {
+        for character in s.chars()
+        {
+            if character == '\n'
+            {
+                self.lines.push(self.line());
+                self.current.clear();
+                self.column = 0;
+                continue;
+            }
+            if self.column < self.current.len()
+            {
+                self.current[self.column] = character;
+            }
+            else
+            {
+                self.current.push(character);
+            }
+            self.column += 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl Terminal for Recorder
+{
+    fn width(&self) -> Option<usize>
+    {
+        Some(20)
+    }
+
+    fn cursor_left(&mut self, n: usize)
+    {
+        self.column = self.column.saturating_sub(n);
+    }
+
+    fn cursor_right(&mut self, n: usize)
+    {
+        self.column = (self.column + n).min(self.current.len());
+    }
+
+    fn erase_to_end(&mut self)
+    {
+        self.current.truncate(self.column);
+    }
+
+    fn clear(&mut self)
+    {
+        self.lines.clear();
+        self.current.clear();
+        self.column = 0;
+    }
+}
+
+#[cfg(test)]
+fn type_keys(editor: &mut LineEditor, terminal: &mut Recorder, keys: &[Key]) -> Option<String>
+{
+    let complete = |prefix: &str| ["help", "hello", "halt"].iter().filter(|name| name.starts_with(prefix)).map(|name| String::from(*name)).collect();
+    keys.iter().fold(None, |_, key| editor.handle(*key, terminal, "> ", &complete))
+}
+
+#[test_case]
+fn test_editing()
+{
+    let mut editor = LineEditor::new();
+    let mut terminal = Recorder::default();
+    let _ = core::fmt::Write::write_str(&mut terminal, "> ");
+
+    let keys = [Key::Char('e'), Key::Char('h'), Key::Char('o'), Key::Left, Key::Left, Key::Char('c'), Key::End, Key::Backspace, Key::Char('o')];
+    assert_eq!(type_keys(&mut editor, &mut terminal, &keys), None);
+    assert_eq!(terminal.line(), "> echo");
+    assert_eq!(terminal.column, 6);
+
+    let keys = [Key::Home, Key::Delete, Key::Char('E'), Key::Right, Key::Right, Key::Right, Key::Char('!')];
+    type_keys(&mut editor, &mut terminal, &keys);
+    assert_eq!((terminal.line().as_str(), terminal.column), ("> Echo!", 7));
+
+    // Die Zeile hat höchstens 20 - 2 - 1 Zeichen.
+    let keys = [Key::Char('x'); 20];
+    type_keys(&mut editor, &mut terminal, &keys);
+    assert_eq!(terminal.line().len(), 19);
+    assert_eq!(type_keys(&mut editor, &mut terminal, &[Key::Enter]).unwrap().len(), 17);
+    assert_eq!(terminal.line(), "");
+}
+
+#[test_case]
+fn test_history_and_completion()
+{
+    let mut editor = LineEditor::new();
+    let mut terminal = Recorder::default();
+    type_keys(&mut editor, &mut terminal, &[Key::Char('a'), Key::Enter, Key::Char('b'), Key::Enter, Key::Char('b'), Key::Enter]);
+    assert_eq!(editor.history().collect::<Vec<_>>(), ["a", "b"]);
+
+    type_keys(&mut editor, &mut terminal, &[Key::Char('c'), Key::Up, Key::Up]);
+    assert_eq!(terminal.line(), "a");
+    type_keys(&mut editor, &mut terminal, &[Key::Up, Key::Down]);
+    assert_eq!(terminal.line(), "b");
+    type_keys(&mut editor, &mut terminal, &[Key::Down]);
+    assert_eq!((terminal.line().as_str(), terminal.column), ("c", 1));
+
+    // „h“ ergänzt nichts und zeigt die Kandidaten, „he“ bis „hel“, „help“ ganz.
+    type_keys(&mut editor, &mut terminal, &[Key::Backspace, Key::Char('h'), Key::Tab]);
+    assert_eq!(terminal.lines.last().map(String::as_str), Some("help  hello  halt"));
+    assert_eq!(terminal.line(), "> h");
+    type_keys(&mut editor, &mut terminal, &[Key::Char('e'), Key::Tab]);
+    assert_eq!(terminal.line(), "> hel");
+    assert_eq!(type_keys(&mut editor, &mut terminal, &[Key::Char('p'), Key::Tab, Key::Enter]), Some(String::from("help ")));
+    assert_eq!(type_keys(&mut editor, &mut terminal, &[Key::Char('x'), Key::Char('\u{3}')]), Some(String::new()));
+}
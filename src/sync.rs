@@ -0,0 +1,72 @@
+//! # Modul: sync
+//!
+//! Dieses Modul stellt die **Synchronisationsprimitive** des Kernels bereit.
+//!
+//! Bisher wurde für jede globale Struktur ein einfacher [spin::Mutex] verwendet.
+//! Dieser weiß jedoch nichts über Interrupts: Hält der normale Kernelcode das Lock
+//! und ein Interrupt-Handler versucht es ebenfalls zu bekommen, hängt das System.
+//! Deshalb mussten Funktionen wie `_print` die Interrupts manuell deaktivieren.
+//!
+//! # Aufbau
+//!
+//! | Komponente | Aufgabe |
+//! |------------|---------|
+//! | [IrqSpinlock] | Spinlock, das das Interrupt-Flag sichert und wiederherstellt |
+//! | [TicketLock] | Faires Spinlock, das Anfragen in Ankunftsreihenfolge bedient |
+//! | [RwLock] | Beliebig viele Leser oder genau ein Schreiber |
+//! | [Mutex] | Schlafendes Mutex, das beim Warten die CPU abgibt |
+//! | [Semaphore] | Zählende Semaphore |
+//! | [Condvar] | Bedingungsvariable in Kombination mit [Mutex] |
+//! | [Once], [Lazy] | Einmalige Initialisierung, Ersatz für `lazy_static!` |
+//!
+//! # Deadlock-Erkennung
+//!
+//! In Debug-Builds (`debug_assertions`) merkt sich jedes Spinlock die Stelle im
+//! Quellcode, an der es zuletzt gesperrt wurde. Wartet ein Aufrufer zu lange oder
+//! wird ein belegtes Lock bei deaktivierten Interrupts angefordert (auf einem
+//! Einkernsystem kann es dann nie mehr freigegeben werden), wird eine Panic mit
+//! der Position des Besitzers ausgelöst. In Release-Builds entfällt dieser Aufwand.
+
+mod condvar;
+mod deadlock;
+mod mutex;
+mod once;
+mod rwlock;
+mod semaphore;
+mod spinlock;
+mod ticket;
+
+pub use condvar::Condvar;
+pub use mutex::{Mutex, MutexGuard};
+pub use once::{Lazy, Once};
+pub use rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+pub use semaphore::{Semaphore, SemaphorePermit};
+pub use spinlock::{IrqSpinlock, IrqSpinlockGuard};
+pub use ticket::{TicketLock, TicketLockGuard};
+
+/// Lässt den aktuellen Kontext warten, bis sich ein Zustand ändern könnte.
+///
+/// Wird von den schlafenden Primitiven ([Mutex], [Semaphore], [Condvar])
+/// aufgerufen, wenn sie nicht sofort fortfahren können.
+///
+/// Sind Interrupts aktiv, wird die CPU mit `hlt` bis zum nächsten Interrupt
+/// schlafen gelegt, statt sinnlos Zyklen zu verbrauchen. Da nur ein Interrupt
+/// (oder ein anderer Kontext) den Zustand ändern kann, geht dabei nichts verloren.
+/// Gibt es andere bereite Prozesse, bekommen stattdessen diese die CPU.
+/// Bei deaktivierten Interrupts bleibt nur aktives Warten.
+pub fn wait()
+{
+    use x86_64::instructions::interrupts;
+
+    if interrupts::are_enabled()
+    {
+        if !crate::process::yield_now()
+        {
+            x86_64::instructions::hlt();
+        }
+    }
+    else
+    {
+        core::hint::spin_loop();
+    }
+}
@@ -0,0 +1,103 @@
+//! Bedingungsvariable.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use super::{Mutex, MutexGuard};
+
+/// ## Condvar
+///
+/// Lässt einen Aufrufer schlafen, bis ein anderer Kontext eine Bedingung
+/// signalisiert. Wird immer zusammen mit einem [Mutex] verwendet, das die
+/// geprüfte Bedingung schützt.
+///
+/// Intern zählt die Condvar wartende Aufrufer (`waiters`) und ausstehende
+/// Weckrufe (`wakeups`). [notify_one](Condvar::notify_one) vergibt einen
+/// Weckruf, [notify_all](Condvar::notify_all) einen für jeden Wartenden.
+///
+/// Wie üblich können **unechte Aufwachvorgänge** nicht ausgeschlossen werden;
+/// die Bedingung sollte daher in einer Schleife geprüft oder
+/// [wait_while](Condvar::wait_while) verwendet werden.
+pub struct Condvar
+{
+    waiters: AtomicUsize,
+    wakeups: AtomicUsize,
+}
+
+impl Condvar
+{
+    /// Erstellt eine neue Bedingungsvariable ohne Wartende.
+    pub const fn new() -> Condvar
+    {
+        Condvar
+        {
+            waiters: AtomicUsize::new(0),
+            wakeups: AtomicUsize::new(0),
+        }
+    }
+
+    /// Gibt das Mutex frei, schläft bis zu einem Weckruf und sperrt es wieder.
+    #[track_caller]
+    pub fn wait<'a, T: ?Sized>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T>
+    {
+        let mutex: &'a Mutex<T> = guard.mutex();
+
+        // Vor dem Freigeben registrieren, damit kein Weckruf verloren geht.
+        self.waiters.fetch_add(1, Ordering::Relaxed);
+        drop(guard);
+
+        while self.wakeups
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |n| n.checked_sub(1))
+            .is_err()
+        {
+            super::wait();
+        }
+
+        mutex.lock()
+    }
+
+    /// Wartet, solange `condition` für die geschützten Daten `true` liefert.
+    #[track_caller]
+    pub fn wait_while<'a, T: ?Sized, F>(&self, mut guard: MutexGuard<'a, T>, mut condition: F) -> MutexGuard<'a, T>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        while condition(&mut *guard)
+        {
+            guard = self.wait(guard);
+        }
+        guard
+    }
+
+    /// Weckt einen Wartenden auf.
+    pub fn notify_one(&self)
+    {
+        let _ = self.waiters.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+            .map(|_| self.wakeups.fetch_add(1, Ordering::Release));
+    }
+
+    /// Weckt alle aktuell Wartenden auf.
+    pub fn notify_all(&self)
+    {
+        let waiters = self.waiters.swap(0, Ordering::Relaxed);
+        self.wakeups.fetch_add(waiters, Ordering::Release);
+    }
+}
+
+impl Default for Condvar
+{
+    fn default() -> Condvar
+    {
+        Condvar::new()
+    }
+}
+
+/// Ein Weckruf ohne Wartende darf nicht gespeichert werden, sonst würde ein
+/// späterer Aufruf von `wait` sofort zurückkehren.
+#[test_case]
+fn test_condvar_notify_without_waiters_is_lost()
+{
+    let condvar = Condvar::new();
+    condvar.notify_one();
+    condvar.notify_all();
+    assert_eq!(condvar.wakeups.load(Ordering::Relaxed), 0);
+}
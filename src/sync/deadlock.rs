@@ -0,0 +1,70 @@
+//! Deadlock-Erkennung für die Spinlocks in Debug-Builds.
+//!
+//! In Release-Builds ist [Detector] ein leerer Typ, dessen Methoden vom
+//! Compiler vollständig entfernt werden.
+
+use core::panic::Location;
+
+/// Anzahl an Wartezyklen, nach denen ein Spinlock als verklemmt gilt.
+#[cfg(debug_assertions)]
+const SPIN_LIMIT: usize = 1 << 28;
+
+/// Merkt sich den aktuellen Besitzer eines Locks.
+pub(super) struct Detector
+{
+    #[cfg(debug_assertions)]
+    owner: core::sync::atomic::AtomicPtr<Location<'static>>,
+}
+
+impl Detector
+{
+    pub const fn new() -> Detector
+    {
+        Detector
+        {
+            #[cfg(debug_assertions)]
+            owner: core::sync::atomic::AtomicPtr::new(core::ptr::null_mut()),
+        }
+    }
+
+    /// Trägt `location` als neuen Besitzer ein.
+    #[inline]
+    pub fn acquired(&self, location: &'static Location<'static>)
+    {
+        #[cfg(debug_assertions)]
+        self.owner.store(location as *const _ as *mut _, core::sync::atomic::Ordering::Relaxed);
+        #[cfg(not(debug_assertions))]
+        let _ = location;
+    }
+
+    /// Löscht den Besitzer wieder.
+    #[inline]
+    pub fn released(&self)
+    {
+        #[cfg(debug_assertions)]
+        self.owner.store(core::ptr::null_mut(), core::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Wird bei jedem Wartezyklus aufgerufen.
+    ///
+    /// `irq_was_enabled` gibt an, ob beim Aufruf von `lock()` Interrupts aktiv
+    /// waren. Waren sie es nicht, kann auf einem Einkernsystem niemand mehr das
+    /// Lock freigeben und es wird sofort eine Panic ausgelöst.
+    #[inline]
+    pub fn check(&self, spins: usize, irq_was_enabled: bool, name: &str)
+    {
+        #[cfg(debug_assertions)]
+        if !irq_was_enabled || spins > SPIN_LIMIT
+        {
+            let owner = self.owner.load(core::sync::atomic::Ordering::Relaxed);
+            // SAFETY: Es werden nur `&'static Location`-Referenzen gespeichert.
+            match unsafe { owner.as_ref() }
+            {
+                Some(location) => panic!("deadlock detected: {} held since {}", name, location),
+                None => panic!("deadlock detected: {} held by unknown owner", name),
+            }
+        }
+        #[cfg(not(debug_assertions))]
+        let _ = (spins, irq_was_enabled, name);
+    }
+}
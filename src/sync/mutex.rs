@@ -0,0 +1,151 @@
+//! Schlafendes Mutex.
+
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::panic::Location;
+use core::sync::atomic::{AtomicBool, Ordering};
+use x86_64::instructions::interrupts;
+
+use super::deadlock::Detector;
+
+/// ## Mutex
+///
+/// Gegenseitiger Ausschluss für längere kritische Abschnitte.
+///
+/// Anders als ein Spinlock verbrennt ein wartender Aufrufer keine Rechenzeit,
+/// sondern gibt die CPU über [wait](super::wait) ab, bis ein Interrupt den
+/// Zustand geändert haben könnte.
+///
+/// Darf **nicht** aus Interrupt-Handlern verwendet werden, da dort nicht
+/// geschlafen werden kann – dafür ist [IrqSpinlock](super::IrqSpinlock) gedacht.
+pub struct Mutex<T: ?Sized>
+{
+    locked: AtomicBool,
+    detector: Detector,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+
+impl<T> Mutex<T>
+{
+    /// Erstellt ein neues, freies Mutex.
+    pub const fn new(data: T) -> Mutex<T>
+    {
+        Mutex
+        {
+            locked: AtomicBool::new(false),
+            detector: Detector::new(),
+            data: UnsafeCell::new(data),
+        }
+    }
+}
+
+impl<T: ?Sized> Mutex<T>
+{
+    /// Sperrt das Mutex und schläft, solange es belegt ist.
+    #[track_caller]
+    pub fn lock(&self) -> MutexGuard<'_, T>
+    {
+        loop
+        {
+            if let Some(guard) = self.try_lock()
+            {
+                return guard;
+            }
+            // Schlafen ist nur sinnvoll, wenn ein Interrupt den Besitzer wieder
+            // zum Zug kommen lassen kann.
+            self.detector.check(0, interrupts::are_enabled(), core::any::type_name::<Self>());
+            super::wait();
+        }
+    }
+
+    /// Sperrt das Mutex, falls es frei ist.
+    #[track_caller]
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>>
+    {
+        self.locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_|
+            {
+                self.detector.acquired(Location::caller());
+                MutexGuard { mutex: self }
+            })
+    }
+
+    /// Gibt an, ob das Mutex gerade gehalten wird.
+    pub fn is_locked(&self) -> bool
+    {
+        self.locked.load(Ordering::Relaxed)
+    }
+
+    /// Zugriff ohne Sperren, möglich da `&mut self` exklusiv ist.
+    pub fn get_mut(&mut self) -> &mut T
+    {
+        self.data.get_mut()
+    }
+}
+
+/// Guard eines [Mutex].
+pub struct MutexGuard<'a, T: ?Sized>
+{
+    mutex: &'a Mutex<T>,
+}
+
+impl<'a, T: ?Sized> MutexGuard<'a, T>
+{
+    /// Gibt das zugehörige Mutex zurück, z. B. für [Condvar](super::Condvar).
+    pub(super) fn mutex(&self) -> &'a Mutex<T>
+    {
+        self.mutex
+    }
+}
+
+impl<T: ?Sized> Deref for MutexGuard<'_, T>
+{
+    type Target = T;
+
+    fn deref(&self) -> &T
+    {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for MutexGuard<'_, T>
+{
+    fn deref_mut(&mut self) -> &mut T
+    {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for MutexGuard<'_, T>
+{
+    fn drop(&mut self)
+    {
+        self.mutex.detector.released();
+        self.mutex.locked.store(false, Ordering::Release);
+    }
+}
+
+/// Solange das Guard lebt, scheitert jeder weitere Sperrversuch; danach ist das
+/// Mutex wieder frei und die Änderung sichtbar.
+#[test_case]
+fn test_mutex_try_lock_while_held()
+{
+    let mutex = Mutex::new(0);
+    {
+        let mut guard = mutex.lock();
+        assert!(mutex.is_locked());
+        assert!(mutex.try_lock().is_none());
+        *guard += 1;
+    }
+    assert!(!mutex.is_locked());
+
+    let guard = mutex.try_lock().expect("mutex still locked after the guard was dropped");
+    assert_eq!(*guard, 1);
+    drop(guard);
+    assert_eq!(*mutex.lock(), 1);
+}
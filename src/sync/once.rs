@@ -0,0 +1,183 @@
+//! Einmalige Initialisierung als Ersatz für `lazy_static!`.
+
+use core::cell::{Cell, UnsafeCell};
+use core::mem::MaybeUninit;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicU8, Ordering};
+use x86_64::instructions::interrupts;
+
+use super::deadlock::Detector;
+
+const INCOMPLETE: u8 = 0;
+const RUNNING: u8 = 1;
+const COMPLETE: u8 = 2;
+
+/// ## Once
+///
+/// Speicher für einen Wert, der **genau einmal** beim ersten Zugriff erzeugt wird.
+///
+/// Ruft ein zweiter Aufrufer [call_once](Once::call_once) auf, während die
+/// Initialisierung noch läuft, wartet er, bis sie abgeschlossen ist. Ruft die
+/// Initialisierung sich selbst (indirekt) wieder auf, wird dies in Debug-Builds
+/// als Deadlock erkannt.
+pub struct Once<T>
+{
+    state: AtomicU8,
+    detector: Detector,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+unsafe impl<T: Send + Sync> Sync for Once<T> {}
+unsafe impl<T: Send> Send for Once<T> {}
+
+impl<T> Once<T>
+{
+    /// Erstellt einen noch nicht initialisierten Speicher.
+    pub const fn new() -> Once<T>
+    {
+        Once
+        {
+            state: AtomicU8::new(INCOMPLETE),
+            detector: Detector::new(),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Gibt den Wert zurück und erzeugt ihn beim ersten Aufruf mit `init`.
+    #[track_caller]
+    pub fn call_once<F: FnOnce() -> T>(&self, init: F) -> &T
+    {
+        if self.state
+            .compare_exchange(INCOMPLETE, RUNNING, Ordering::Acquire, Ordering::Acquire)
+            .is_ok()
+        {
+            self.detector.acquired(core::panic::Location::caller());
+            unsafe { (*self.value.get()).write(init()) };
+            self.detector.released();
+            self.state.store(COMPLETE, Ordering::Release);
+        }
+        else
+        {
+            let irq_enabled = interrupts::are_enabled();
+            let mut spins = 0;
+            while self.state.load(Ordering::Acquire) == RUNNING
+            {
+                spins += 1;
+                self.detector.check(spins, irq_enabled, core::any::type_name::<Self>());
+                core::hint::spin_loop();
+            }
+        }
+
+        // SAFETY: Der Zustand ist jetzt COMPLETE, der Wert also geschrieben.
+        unsafe { (*self.value.get()).assume_init_ref() }
+    }
+
+    /// Gibt den Wert zurück, falls er bereits initialisiert wurde.
+    pub fn get(&self) -> Option<&T>
+    {
+        match self.state.load(Ordering::Acquire)
+        {
+            COMPLETE => Some(unsafe { (*self.value.get()).assume_init_ref() }),
+            _ => None,
+        }
+    }
+
+    /// Gibt an, ob die Initialisierung abgeschlossen ist.
+    pub fn is_completed(&self) -> bool
+    {
+        self.state.load(Ordering::Acquire) == COMPLETE
+    }
+}
+
+impl<T> Default for Once<T>
+{
+    fn default() -> Once<T>
+    {
+        Once::new()
+    }
+}
+
+impl<T> Drop for Once<T>
+{
+    fn drop(&mut self)
+    {
+        if *self.state.get_mut() == COMPLETE
+        {
+            unsafe { self.value.get_mut().assume_init_drop() };
+        }
+    }
+}
+
+/// ## Lazy
+///
+/// Wert, der beim ersten Zugriff über [Deref] durch die übergebene Funktion
+/// erzeugt wird. Ersetzt `lazy_static!` ohne Makro:
+///
+/// ```ignore
+/// static WRITER: Lazy<IrqSpinlock<Writer>> = Lazy::new(|| IrqSpinlock::new(Writer::new()));
+/// ```
+pub struct Lazy<T, F = fn() -> T>
+{
+    once: Once<T>,
+    init: Cell<Option<F>>,
+}
+
+unsafe impl<T: Send + Sync, F: Send> Sync for Lazy<T, F> {}
+
+impl<T, F> Lazy<T, F>
+{
+    /// Erstellt einen Lazy-Wert, der beim ersten Zugriff `init` aufruft.
+    pub const fn new(init: F) -> Lazy<T, F>
+    {
+        Lazy
+        {
+            once: Once::new(),
+            init: Cell::new(Some(init)),
+        }
+    }
+}
+
+impl<T, F: FnOnce() -> T> Lazy<T, F>
+{
+    /// Erzwingt die Initialisierung und gibt den Wert zurück.
+    #[track_caller]
+    pub fn force(this: &Lazy<T, F>) -> &T
+    {
+        this.once.call_once(||
+        {
+            match this.init.take()
+            {
+                Some(init) => init(),
+                None => panic!("Lazy instance has previously been poisoned"),
+            }
+        })
+    }
+}
+
+impl<T, F: FnOnce() -> T> Deref for Lazy<T, F>
+{
+    type Target = T;
+
+    #[track_caller]
+    fn deref(&self) -> &T
+    {
+        Lazy::force(self)
+    }
+}
+
+#[test_case]
+fn test_lazy_initializes_once()
+{
+    use core::sync::atomic::AtomicUsize;
+
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+    static VALUE: Lazy<usize> = Lazy::new(||
+    {
+        CALLS.fetch_add(1, Ordering::Relaxed);
+        42
+    });
+
+    assert_eq!(*VALUE, 42);
+    assert_eq!(*VALUE, 42);
+    assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+}
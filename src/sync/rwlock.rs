@@ -0,0 +1,193 @@
+//! Reader-Writer-Lock.
+
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::panic::Location;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use x86_64::instructions::interrupts;
+
+use super::deadlock::Detector;
+
+/// Gesetzt, solange ein Schreiber das Lock hält.
+const WRITER: usize = 1 << (usize::BITS - 1);
+
+/// ## RwLock
+///
+/// Erlaubt entweder **beliebig viele gleichzeitige Leser** oder **genau einen
+/// Schreiber**. Geeignet für Daten, die oft gelesen und selten verändert werden
+/// (z. B. Tabellen, die nur beim Start befüllt werden).
+///
+/// Der Zustand wird in einem einzigen Zähler gespeichert: Das oberste Bit
+/// markiert einen Schreiber, die restlichen Bits zählen die aktiven Leser.
+pub struct RwLock<T: ?Sized>
+{
+    state: AtomicUsize,
+    detector: Detector,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+impl<T> RwLock<T>
+{
+    /// Erstellt ein neues, freies Lock.
+    pub const fn new(data: T) -> RwLock<T>
+    {
+        RwLock
+        {
+            state: AtomicUsize::new(0),
+            detector: Detector::new(),
+            data: UnsafeCell::new(data),
+        }
+    }
+}
+
+impl<T: ?Sized> RwLock<T>
+{
+    /// Wartet, bis kein Schreiber mehr aktiv ist, und registriert einen Leser.
+    #[track_caller]
+    pub fn read(&self) -> RwLockReadGuard<'_, T>
+    {
+        let irq_enabled = interrupts::are_enabled();
+        let mut spins = 0;
+        loop
+        {
+            if let Some(guard) = self.try_read()
+            {
+                return guard;
+            }
+            spins += 1;
+            self.detector.check(spins, irq_enabled, core::any::type_name::<Self>());
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Registriert einen Leser, falls gerade kein Schreiber aktiv ist.
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>>
+    {
+        let state = self.state.load(Ordering::Relaxed);
+        if state & WRITER != 0
+        {
+            return None;
+        }
+        self.state
+            .compare_exchange(state, state + 1, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| RwLockReadGuard { lock: self })
+    }
+
+    /// Wartet, bis weder Leser noch Schreiber aktiv sind.
+    #[track_caller]
+    pub fn write(&self) -> RwLockWriteGuard<'_, T>
+    {
+        let irq_enabled = interrupts::are_enabled();
+        let mut spins = 0;
+        loop
+        {
+            if let Some(guard) = self.try_write()
+            {
+                return guard;
+            }
+            spins += 1;
+            self.detector.check(spins, irq_enabled, core::any::type_name::<Self>());
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Sperrt exklusiv, falls das Lock gerade vollständig frei ist.
+    #[track_caller]
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>>
+    {
+        self.state
+            .compare_exchange(0, WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_|
+            {
+                self.detector.acquired(Location::caller());
+                RwLockWriteGuard { lock: self }
+            })
+    }
+
+    /// Anzahl der aktuell aktiven Leser.
+    pub fn reader_count(&self) -> usize
+    {
+        self.state.load(Ordering::Relaxed) & !WRITER
+    }
+}
+
+/// Lesender Guard eines [RwLock].
+pub struct RwLockReadGuard<'a, T: ?Sized>
+{
+    lock: &'a RwLock<T>,
+}
+
+impl<T: ?Sized> Deref for RwLockReadGuard<'_, T>
+{
+    type Target = T;
+
+    fn deref(&self) -> &T
+    {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockReadGuard<'_, T>
+{
+    fn drop(&mut self)
+    {
+        self.lock.state.fetch_sub(1, Ordering::Release);
+    }
+}
+
+/// Schreibender Guard eines [RwLock].
+pub struct RwLockWriteGuard<'a, T: ?Sized>
+{
+    lock: &'a RwLock<T>,
+}
+
+impl<T: ?Sized> Deref for RwLockWriteGuard<'_, T>
+{
+    type Target = T;
+
+    fn deref(&self) -> &T
+    {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for RwLockWriteGuard<'_, T>
+{
+    fn deref_mut(&mut self) -> &mut T
+    {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockWriteGuard<'_, T>
+{
+    fn drop(&mut self)
+    {
+        self.lock.detector.released();
+        self.lock.state.store(0, Ordering::Release);
+    }
+}
+
+#[test_case]
+fn test_rwlock_readers_exclude_writer()
+{
+    let lock = RwLock::new(1);
+    {
+        let a = lock.read();
+        let b = lock.read();
+        assert_eq!(*a + *b, 2);
+        assert_eq!(lock.reader_count(), 2);
+        assert!(lock.try_write().is_none());
+    }
+    {
+        let mut w = lock.write();
+        *w = 7;
+        assert!(lock.try_read().is_none());
+    }
+    assert_eq!(*lock.read(), 7);
+}
@@ -0,0 +1,100 @@
+//! Zählende Semaphore.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// ## Semaphore
+///
+/// Verwaltet eine feste Anzahl an **Erlaubnissen** (Permits). [acquire](Semaphore::acquire)
+/// nimmt eine Erlaubnis und schläft, solange keine verfügbar ist;
+/// [release](Semaphore::release) gibt eine zurück.
+///
+/// Eine Semaphore mit Startwert 0 eignet sich zum Signalisieren zwischen einem
+/// Interrupt-Handler (der `release` aufruft) und wartendem Kernelcode.
+pub struct Semaphore
+{
+    permits: AtomicUsize,
+}
+
+impl Semaphore
+{
+    /// Erstellt eine Semaphore mit `permits` verfügbaren Erlaubnissen.
+    pub const fn new(permits: usize) -> Semaphore
+    {
+        Semaphore { permits: AtomicUsize::new(permits) }
+    }
+
+    /// Nimmt eine Erlaubnis und schläft, bis eine verfügbar ist.
+    ///
+    /// Die Erlaubnis wird automatisch zurückgegeben, wenn das [SemaphorePermit]
+    /// gedroppt wird.
+    pub fn acquire(&self) -> SemaphorePermit<'_>
+    {
+        loop
+        {
+            if let Some(permit) = self.try_acquire()
+            {
+                return permit;
+            }
+            super::wait();
+        }
+    }
+
+    /// Nimmt eine Erlaubnis, falls eine verfügbar ist.
+    pub fn try_acquire(&self) -> Option<SemaphorePermit<'_>>
+    {
+        self.permits
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |n| n.checked_sub(1))
+            .ok()
+            .map(|_| SemaphorePermit { semaphore: self })
+    }
+
+    /// Gibt eine Erlaubnis zurück, ohne dass vorher eine genommen wurde.
+    ///
+    /// Darf auch aus Interrupt-Handlern aufgerufen werden.
+    pub fn release(&self)
+    {
+        self.permits.fetch_add(1, Ordering::Release);
+    }
+
+    /// Anzahl der aktuell verfügbaren Erlaubnisse.
+    pub fn available(&self) -> usize
+    {
+        self.permits.load(Ordering::Relaxed)
+    }
+}
+
+/// Eine gehaltene Erlaubnis einer [Semaphore].
+pub struct SemaphorePermit<'a>
+{
+    semaphore: &'a Semaphore,
+}
+
+impl SemaphorePermit<'_>
+{
+    /// Verbraucht die Erlaubnis dauerhaft, statt sie beim Droppen zurückzugeben.
+    pub fn forget(self)
+    {
+        core::mem::forget(self);
+    }
+}
+
+impl Drop for SemaphorePermit<'_>
+{
+    fn drop(&mut self)
+    {
+        self.semaphore.release();
+    }
+}
+
+#[test_case]
+fn test_semaphore_counts_permits()
+{
+    let semaphore = Semaphore::new(2);
+    let a = semaphore.acquire();
+    let b = semaphore.acquire();
+    assert!(semaphore.try_acquire().is_none());
+    drop(a);
+    assert_eq!(semaphore.available(), 1);
+    b.forget();
+    assert_eq!(semaphore.available(), 1);
+}
@@ -0,0 +1,186 @@
+//! Interrupt-sicheres Spinlock.
+
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::panic::Location;
+use core::sync::atomic::{AtomicBool, Ordering};
+use x86_64::instructions::interrupts;
+
+use super::deadlock::Detector;
+
+/// ## IrqSpinlock
+///
+/// Spinlock, das vor dem Sperren die Interrupts deaktiviert und beim Freigeben
+/// den **vorherigen Zustand** des Interrupt-Flags wiederherstellt.
+///
+/// Damit kann dieselbe Struktur gefahrlos sowohl vom normalen Kernelcode als
+/// auch aus Interrupt-Handlern verwendet werden: Solange das Lock gehalten wird,
+/// kann kein Interrupt auftreten, der das Lock erneut anfordert.
+///
+/// Da das ursprüngliche Flag gesichert wird, funktioniert auch verschachteltes
+/// Sperren verschiedener Locks korrekt – erst das äußerste Guard aktiviert die
+/// Interrupts wieder.
+pub struct IrqSpinlock<T: ?Sized>
+{
+    locked: AtomicBool,
+    detector: Detector,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Sync for IrqSpinlock<T> {}
+unsafe impl<T: ?Sized + Send> Send for IrqSpinlock<T> {}
+
+impl<T> IrqSpinlock<T>
+{
+    /// Erstellt ein neues, freies Lock.
+    pub const fn new(data: T) -> IrqSpinlock<T>
+    {
+        IrqSpinlock
+        {
+            locked: AtomicBool::new(false),
+            detector: Detector::new(),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Gibt die geschützten Daten zurück.
+    pub fn into_inner(self) -> T
+    {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> IrqSpinlock<T>
+{
+    /// Deaktiviert die Interrupts und wartet, bis das Lock frei ist.
+    ///
+    /// Das zurückgegebene Guard gibt das Lock beim Droppen frei und stellt
+    /// das Interrupt-Flag wieder her.
+    #[track_caller]
+    pub fn lock(&self) -> IrqSpinlockGuard<'_, T>
+    {
+        let irq_was_enabled = interrupts::are_enabled();
+        interrupts::disable();
+
+        let mut spins = 0;
+        while self.locked.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err()
+        {
+            while self.locked.load(Ordering::Relaxed)
+            {
+                spins += 1;
+                self.detector.check(spins, irq_was_enabled, core::any::type_name::<Self>());
+                core::hint::spin_loop();
+            }
+        }
+        self.detector.acquired(Location::caller());
+
+        IrqSpinlockGuard { lock: self, irq_was_enabled }
+    }
+
+    /// Versucht das Lock ohne Warten zu bekommen.
+    #[track_caller]
+    pub fn try_lock(&self) -> Option<IrqSpinlockGuard<'_, T>>
+    {
+        let irq_was_enabled = interrupts::are_enabled();
+        interrupts::disable();
+
+        if self.locked.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_ok()
+        {
+            self.detector.acquired(Location::caller());
+            Some(IrqSpinlockGuard { lock: self, irq_was_enabled })
+        }
+        else
+        {
+            if irq_was_enabled
+            {
+                interrupts::enable();
+            }
+            None
+        }
+    }
+
+    /// Gibt an, ob das Lock gerade gehalten wird.
+    pub fn is_locked(&self) -> bool
+    {
+        self.locked.load(Ordering::Relaxed)
+    }
+
+    /// Gibt das Lock gewaltsam frei.
+    ///
+    /// # Safety
+    ///
+    /// Darf nur verwendet werden, wenn der Besitzer nie wieder auf die Daten
+    /// zugreift, z. B. im Panic-Handler, um noch Ausgaben machen zu können.
+    pub unsafe fn force_unlock(&self)
+    {
+        self.detector.released();
+        self.locked.store(false, Ordering::Release);
+    }
+
+    /// Zugriff ohne Sperren, möglich da `&mut self` exklusiv ist.
+    pub fn get_mut(&mut self) -> &mut T
+    {
+        self.data.get_mut()
+    }
+}
+
+/// Guard eines [IrqSpinlock].
+pub struct IrqSpinlockGuard<'a, T: ?Sized>
+{
+    lock: &'a IrqSpinlock<T>,
+    irq_was_enabled: bool,
+}
+
+impl<T: ?Sized> Deref for IrqSpinlockGuard<'_, T>
+{
+    type Target = T;
+
+    fn deref(&self) -> &T
+    {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for IrqSpinlockGuard<'_, T>
+{
+    fn deref_mut(&mut self) -> &mut T
+    {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for IrqSpinlockGuard<'_, T>
+{
+    fn drop(&mut self)
+    {
+        self.lock.detector.released();
+        self.lock.locked.store(false, Ordering::Release);
+        if self.irq_was_enabled
+        {
+            interrupts::enable();
+        }
+    }
+}
+
+/// Prüft, dass das Guard den vorherigen Zustand des Interrupt-Flags
+/// wiederherstellt, auch bei verschachtelten Locks.
+#[test_case]
+fn test_irq_spinlock_restores_interrupt_flag()
+{
+    let outer = IrqSpinlock::new(0);
+    let inner = IrqSpinlock::new(0);
+
+    assert!(interrupts::are_enabled());
+    {
+        let mut a = outer.lock();
+        assert!(!interrupts::are_enabled());
+        {
+            let mut b = inner.lock();
+            *b += 1;
+        }
+        assert!(!interrupts::are_enabled());
+        *a += 1;
+    }
+    assert!(interrupts::are_enabled());
+    assert!(outer.try_lock().is_some());
+}
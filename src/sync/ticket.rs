@@ -0,0 +1,137 @@
+//! Faires Ticket-Lock.
+
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::panic::Location;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use x86_64::instructions::interrupts;
+
+use super::deadlock::Detector;
+
+/// ## TicketLock
+///
+/// Spinlock, das wie eine Warteschlange beim Amt funktioniert: Jeder Aufrufer
+/// zieht eine Nummer (`next_ticket`) und wartet, bis diese aufgerufen wird
+/// (`now_serving`).
+///
+/// Im Gegensatz zu einem einfachen Spinlock kann so kein Wartender verhungern,
+/// da das Lock strikt in Ankunftsreihenfolge vergeben wird.
+///
+/// Das TicketLock verändert das Interrupt-Flag **nicht**. Wird es auch aus
+/// Interrupt-Handlern verwendet, muss stattdessen [IrqSpinlock](super::IrqSpinlock)
+/// benutzt werden.
+pub struct TicketLock<T: ?Sized>
+{
+    next_ticket: AtomicUsize,
+    now_serving: AtomicUsize,
+    detector: Detector,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Sync for TicketLock<T> {}
+unsafe impl<T: ?Sized + Send> Send for TicketLock<T> {}
+
+impl<T> TicketLock<T>
+{
+    /// Erstellt ein neues, freies Lock.
+    pub const fn new(data: T) -> TicketLock<T>
+    {
+        TicketLock
+        {
+            next_ticket: AtomicUsize::new(0),
+            now_serving: AtomicUsize::new(0),
+            detector: Detector::new(),
+            data: UnsafeCell::new(data),
+        }
+    }
+}
+
+impl<T: ?Sized> TicketLock<T>
+{
+    /// Zieht ein Ticket und wartet, bis es an der Reihe ist.
+    #[track_caller]
+    pub fn lock(&self) -> TicketLockGuard<'_, T>
+    {
+        let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+        let irq_enabled = interrupts::are_enabled();
+
+        let mut spins = 0;
+        while self.now_serving.load(Ordering::Acquire) != ticket
+        {
+            spins += 1;
+            self.detector.check(spins, irq_enabled, core::any::type_name::<Self>());
+            core::hint::spin_loop();
+        }
+        self.detector.acquired(Location::caller());
+
+        TicketLockGuard { lock: self }
+    }
+
+    /// Bekommt das Lock nur, wenn gerade niemand wartet oder es hält.
+    #[track_caller]
+    pub fn try_lock(&self) -> Option<TicketLockGuard<'_, T>>
+    {
+        let ticket = self.now_serving.load(Ordering::Acquire);
+        self.next_ticket
+            .compare_exchange(ticket, ticket + 1, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_|
+            {
+                self.detector.acquired(Location::caller());
+                TicketLockGuard { lock: self }
+            })
+    }
+
+    /// Gibt an, ob das Lock gerade gehalten wird.
+    pub fn is_locked(&self) -> bool
+    {
+        self.next_ticket.load(Ordering::Relaxed) != self.now_serving.load(Ordering::Relaxed)
+    }
+}
+
+/// Guard eines [TicketLock].
+pub struct TicketLockGuard<'a, T: ?Sized>
+{
+    lock: &'a TicketLock<T>,
+}
+
+impl<T: ?Sized> Deref for TicketLockGuard<'_, T>
+{
+    type Target = T;
+
+    fn deref(&self) -> &T
+    {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for TicketLockGuard<'_, T>
+{
+    fn deref_mut(&mut self) -> &mut T
+    {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for TicketLockGuard<'_, T>
+{
+    fn drop(&mut self)
+    {
+        self.lock.detector.released();
+        self.lock.now_serving.fetch_add(1, Ordering::Release);
+    }
+}
+
+#[test_case]
+fn test_ticket_lock_try_lock()
+{
+    let lock = TicketLock::new(5);
+    {
+        let mut guard = lock.lock();
+        *guard += 1;
+        assert!(lock.is_locked());
+        assert!(lock.try_lock().is_none());
+    }
+    assert!(!lock.is_locked());
+    assert_eq!(*lock.try_lock().unwrap(), 6);
+}
@@ -0,0 +1,465 @@
+//! # Modul: syscall
+//!
+//! Dieses Modul implementiert die **Systemaufruf-Schnittstelle** zwischen
+//! Programmen im Ring 3 und dem Kernel.
+//!
+//! Es gibt zwei Eintrittswege, die beide im selben Dispatcher landen:
+//!
+//! | Weg | Ablauf |
+//! |-----|--------|
+//! | `syscall`/`sysret` | Schneller Weg über die MSRs STAR, LSTAR und SFMASK |
+//! | `int 0x80` | Klassischer Software-Interrupt über ein Gate mit DPL 3 in der IDT |
+//!
+//! # Aufrufkonvention
+//!
+//! Wie bei Linux steht die Nummer des Aufrufs in `rax`, die Argumente in
+//! `rdi`, `rsi`, `rdx`, `r10`, `r8` und `r9`. Das Ergebnis wird in `rax`
+//! zurückgegeben; negative Werte sind Fehlercodes ([SyscallError]).
+//! `syscall` überschreibt zusätzlich `rcx` und `r11`, alle anderen Register
+//! bleiben erhalten.
+//!
+//! # Systemaufrufe
+//!
+//! | Nummer | Name | Argumente | Ergebnis |
+//! |--------|------|-----------|----------|
+//! | 0 | `write` | fd, Puffer, Länge | Anzahl geschriebener Bytes |
+//! | 1 | `exit` | Exit-Code | kehrt nicht zurück |
+//! | 2 | `getpid` | – | ID des aktuellen Prozesses |
+//! | 3 | `sleep` | Millisekunden | 0 |
+//! | 4 | `yield` | – | 0 |
+//! | 5 | `fork` | – | PID des Kindes bzw. 0 im Kind |
+//! | 6 | `wait` | PID (0 = beliebiges Kind), Zeiger auf Exit-Code oder 0 | PID des beendeten Kindes |
+//!
+//! Jeder Prozess hat einen eigenen Kernel-Stack; beim Prozesswechsel stellt
+//! [crate::process] über [set_kernel_stack] um, wohin `syscall_entry` wechselt.
+
+use x86_64::VirtAddr;
+use x86_64::registers::model_specific::{Efer, EferFlags, LStar, SFMask, Star};
+use x86_64::registers::rflags::RFlags;
+
+use crate::gdt;
+use crate::memory::{self, UserAccess};
+use crate::process::{self, Pid, SpawnError, WaitError};
+use crate::usermode::UserContext;
+
+/// Interrupt-Vektor des klassischen Systemaufruf-Gates.
+pub const INT80_VECTOR: usize = 0x80;
+
+/// Nummern der Systemaufrufe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u64)]
+pub enum Syscall
+{
+    Write = 0,
+    Exit = 1,
+    Getpid = 2,
+    Sleep = 3,
+    Yield = 4,
+    Fork = 5,
+    Wait = 6,
+}
+
+/// Fehlercodes, die als negative Werte in `rax` zurückgegeben werden.
+///
+/// Die Werte entsprechen den gleichnamigen `errno`-Werten von Linux.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i64)]
+pub enum SyscallError
+{
+    BadFileDescriptor = 9,
+    NoChild = 10,
+    TryAgain = 11,
+    OutOfMemory = 12,
+    Fault = 14,
+    InvalidArgument = 22,
+    NoSys = 38,
+}
+
+/// Ergebnis eines Systemaufrufs.
+pub type SyscallResult = Result<u64, SyscallError>;
+
+/// Register, die beim Eintritt auf den Kernel-Stack gesichert werden.
+///
+/// Die Reihenfolge entspricht umgekehrt den `push`-Befehlen der Einsprungpunkte.
+/// Beide Einsprungpunkte legen den vollständigen Registerzustand des Programms
+/// ab, damit `fork` ihn für das Kind kopieren kann. Der Dispatcher schreibt das
+/// Ergebnis in [rax](SyscallFrame::rax), das beim Rücksprung wiederhergestellt wird.
+#[derive(Debug, Clone)]
+#[repr(C)]
+pub struct SyscallFrame
+{
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub rbp: u64,
+    pub rbx: u64,
+    pub r9: u64,
+    pub r8: u64,
+    pub r10: u64,
+    pub rdx: u64,
+    pub rsi: u64,
+    pub rdi: u64,
+    pub rax: u64,
+    pub r11: u64,
+    pub rcx: u64,
+    /// RFLAGS des Programms.
+    pub user_rflags: u64,
+    /// Adresse des Befehls nach dem Systemaufruf.
+    pub user_rip: u64,
+    /// Stackzeiger des Programms.
+    pub user_rsp: u64,
+}
+
+impl SyscallFrame
+{
+    /// Die sechs Argumente in der Reihenfolge der Aufrufkonvention.
+    pub fn args(&self) -> [u64; 6]
+    {
+        [self.rdi, self.rsi, self.rdx, self.r10, self.r8, self.r9]
+    }
+
+    /// Der Registerzustand, mit dem das Programm nach dem Systemaufruf
+    /// fortgesetzt wird, wobei `rax` das Ergebnis `result` enthält.
+    pub fn user_context(&self, result: u64) -> UserContext
+    {
+        let selectors = gdt::selectors();
+        UserContext
+        {
+            r15: self.r15,
+            r14: self.r14,
+            r13: self.r13,
+            r12: self.r12,
+            rbp: self.rbp,
+            rbx: self.rbx,
+            r9: self.r9,
+            r8: self.r8,
+            r10: self.r10,
+            rdx: self.rdx,
+            rsi: self.rsi,
+            rdi: self.rdi,
+            rax: result,
+            r11: self.r11,
+            rcx: self.rcx,
+            rip: self.user_rip,
+            cs: u64::from(selectors.user_code_selector.0),
+            rflags: self.user_rflags,
+            rsp: self.user_rsp,
+            ss: u64::from(selectors.user_data_selector.0),
+        }
+    }
+}
+
+/// Signatur eines Systemaufruf-Handlers.
+type SyscallHandler = fn(&SyscallFrame) -> SyscallResult;
+
+/// Dispatch-Tabelle, indiziert mit der Nummer des Systemaufrufs.
+static SYSCALL_TABLE: [SyscallHandler; 7] =
+[
+    sys_write,
+    sys_exit,
+    sys_getpid,
+    sys_sleep,
+    sys_yield,
+    sys_fork,
+    sys_wait,
+];
+
+/// Oberes Ende des Kernel-Stacks, auf den `syscall_entry` wechselt.
+static mut KERNEL_STACK_TOP: u64 = 0;
+
+/// Zwischenspeicher für den User-Stackzeiger während des Stackwechsels.
+static mut USER_RSP_SCRATCH: u64 = 0;
+
+/// ## Initialisierung
+///
+/// Programmiert die MSRs für `syscall`/`sysret`:
+///
+/// - **EFER.SCE** aktiviert die Befehle `syscall` und `sysret`,
+/// - **STAR** enthält die Segment-Selektoren für Kernel und User,
+/// - **LSTAR** enthält die Adresse von `syscall_entry`,
+/// - **SFMASK** löscht beim Eintritt das Interrupt-Flag, damit der Stackwechsel
+///   nicht unterbrochen werden kann.
+///
+/// Muss nach [gdt::init] aufgerufen werden.
+pub fn init()
+{
+    let selectors = gdt::selectors();
+
+    unsafe
+    {
+        KERNEL_STACK_TOP = gdt::kernel_stack_top().as_u64();
+        Efer::update(|flags| flags.insert(EferFlags::SYSTEM_CALL_EXTENSIONS));
+    }
+    Star::write(
+        selectors.user_code_selector,
+        selectors.user_data_selector,
+        selectors.code_selector,
+        selectors.data_selector,
+    ).expect("invalid GDT layout for syscall/sysret");
+    LStar::write(VirtAddr::from_ptr(syscall_entry as *const ()));
+    SFMask::write(RFlags::INTERRUPT_FLAG | RFlags::DIRECTION_FLAG | RFlags::TRAP_FLAG);
+}
+
+/// Setzt den Kernel-Stack, auf den `syscall_entry` wechselt.
+pub(crate) fn set_kernel_stack(top: VirtAddr)
+{
+    x86_64::instructions::interrupts::without_interrupts(|| unsafe { KERNEL_STACK_TOP = top.as_u64() });
+}
+
+/// Adresse des `int 0x80`-Einsprungpunkts für die IDT.
+pub(crate) fn int80_entry_addr() -> VirtAddr
+{
+    VirtAddr::from_ptr(int80_entry as *const ())
+}
+
+/// ## Einsprungpunkt für `syscall`
+///
+/// Die CPU springt hierher mit der Rücksprungadresse in `rcx`, den alten RFLAGS
+/// in `r11` und weiterhin dem **User-Stack** in `rsp`. Deshalb wird zuerst auf
+/// den Kernel-Stack gewechselt, dann werden die Register gesichert und der
+/// Dispatcher mit Interrupts aufgerufen.
+///
+/// `rcx` und `r11` landen zweimal im [SyscallFrame]: als Rücksprungadresse und
+/// RFLAGS sowie als normale Register.
+#[unsafe(naked)]
+extern "C" fn syscall_entry()
+{
+    core::arch::naked_asm!(
+        "mov [rip + {user_rsp}], rsp",
+        "mov rsp, [rip + {kernel_rsp}]",
+        "and rsp, -16",
+        "push qword ptr [rip + {user_rsp}]",
+        "push rcx",
+        "push r11",
+        "push rcx",
+        "push r11",
+        "push rax",
+        "push rdi",
+        "push rsi",
+        "push rdx",
+        "push r10",
+        "push r8",
+        "push r9",
+        "push rbx",
+        "push rbp",
+        "push r12",
+        "push r13",
+        "push r14",
+        "push r15",
+        "mov rdi, rsp",
+        "sti",
+        "call {dispatch}",
+        "cli",
+        "pop r15",
+        "pop r14",
+        "pop r13",
+        "pop r12",
+        "pop rbp",
+        "pop rbx",
+        "pop r9",
+        "pop r8",
+        "pop r10",
+        "pop rdx",
+        "pop rsi",
+        "pop rdi",
+        "pop rax",
+        "pop r11",
+        "pop rcx",
+        "add rsp, 16",
+        "pop rsp",
+        "sysretq",
+        user_rsp = sym USER_RSP_SCRATCH,
+        kernel_rsp = sym KERNEL_STACK_TOP,
+        dispatch = sym syscall_dispatch,
+    );
+}
+
+/// ## Einsprungpunkt für `int 0x80`
+///
+/// Die CPU hat bereits über das TSS auf den Kernel-Stack gewechselt und einen
+/// Interrupt-Stack-Frame abgelegt. Es wird derselbe [SyscallFrame] wie bei
+/// `syscall_entry` aufgebaut, sodass derselbe Dispatcher verwendet werden kann;
+/// `rsp`, `rip` und RFLAGS werden dafür aus dem Interrupt-Stack-Frame kopiert.
+#[unsafe(naked)]
+extern "C" fn int80_entry()
+{
+    core::arch::naked_asm!(
+        // Ausrichtung: Der Interrupt-Stack-Frame ist fünf Worte groß.
+        "sub rsp, 8",
+        "push qword ptr [rsp + 32]",
+        "push qword ptr [rsp + 16]",
+        "push qword ptr [rsp + 40]",
+        "push rcx",
+        "push r11",
+        "push rax",
+        "push rdi",
+        "push rsi",
+        "push rdx",
+        "push r10",
+        "push r8",
+        "push r9",
+        "push rbx",
+        "push rbp",
+        "push r12",
+        "push r13",
+        "push r14",
+        "push r15",
+        "mov rdi, rsp",
+        "sti",
+        "call {dispatch}",
+        "cli",
+        "pop r15",
+        "pop r14",
+        "pop r13",
+        "pop r12",
+        "pop rbp",
+        "pop rbx",
+        "pop r9",
+        "pop r8",
+        "pop r10",
+        "pop rdx",
+        "pop rsi",
+        "pop rdi",
+        "pop rax",
+        "pop r11",
+        "pop rcx",
+        "add rsp, 32",
+        "iretq",
+        dispatch = sym syscall_dispatch,
+    );
+}
+
+/// ## Dispatcher
+///
+/// Sucht den Handler zur Nummer in `rax` und schreibt dessen Ergebnis zurück
+/// nach `rax`. Fehler werden als negative Fehlercodes kodiert.
+extern "C" fn syscall_dispatch(frame: &mut SyscallFrame)
+{
+    let result = match SYSCALL_TABLE.get(frame.rax as usize)
+    {
+        Some(handler) => handler(frame),
+        None => Err(SyscallError::NoSys),
+    };
+
+    frame.rax = match result
+    {
+        Ok(value) => value,
+        Err(error) => (-(error as i64)) as u64,
+    };
+}
+
+/// Prüft mit [memory::check_user_range], dass der Bereich `addr..addr + len`
+/// im User-Space liegt und lesbar abgebildet ist, und gibt ihn als Slice zurück.
+fn user_slice(addr: u64, len: u64) -> Result<&'static [u8], SyscallError>
+{
+    if !memory::check_user_range(addr, len, UserAccess::Read)
+    {
+        return Err(SyscallError::Fault);
+    }
+    Ok(unsafe { core::slice::from_raw_parts(addr as *const u8, len as usize) })
+}
+
+/// `write(fd, buf, len)`: fd 1 schreibt auf den VGA-Bildschirm, fd 2 auf die
+/// serielle Schnittstelle.
+fn sys_write(frame: &SyscallFrame) -> SyscallResult
+{
+    let [fd, buf, len, ..] = frame.args();
+    let bytes = user_slice(buf, len)?;
+    let text = core::str::from_utf8(bytes).map_err(|_| SyscallError::InvalidArgument)?;
+
+    match fd
+    {
+        1 => { crate::print!("{}", text); }
+        2 => { crate::serial_print!("{}", text); }
+        _ => return Err(SyscallError::BadFileDescriptor),
+    }
+    Ok(len)
+}
+
+/// `exit(code)`: beendet den aktuellen Prozess. Läuft das Programm direkt im
+/// Kernel-Prozess, kehrt der Aufruf stattdessen zum Aufrufer von
+/// [run_user_mode](crate::usermode::run_user_mode) zurück.
+fn sys_exit(frame: &SyscallFrame) -> SyscallResult
+{
+    let code = frame.rdi as i64;
+    if process::current_pid() == Pid::KERNEL
+    {
+        unsafe { crate::usermode::return_from_user_mode(code) }
+    }
+    process::exit(code)
+}
+
+/// `getpid()`: ID des aktuellen Prozesses.
+fn sys_getpid(_frame: &SyscallFrame) -> SyscallResult
+{
+    Ok(process::current_pid().as_u64())
+}
+
+/// `sleep(ms)`: schläft mindestens die angegebene Zeit. Andere Prozesse laufen
+/// währenddessen weiter.
+fn sys_sleep(frame: &SyscallFrame) -> SyscallResult
+{
+    crate::time::sleep_ms(frame.rdi);
+    Ok(0)
+}
+
+/// `yield()`: gibt die CPU ab. Ohne weitere Prozesse kehrt der Aufruf sofort zurück.
+fn sys_yield(_frame: &SyscallFrame) -> SyscallResult
+{
+    process::yield_now();
+    Ok(0)
+}
+
+/// `fork()`: erzeugt eine Kopie des aktuellen Prozesses mit Copy-on-Write-
+/// Adressraum. Das Kind setzt nach dem Systemaufruf mit Ergebnis 0 fort.
+fn sys_fork(frame: &SyscallFrame) -> SyscallResult
+{
+    match process::fork(frame.user_context(0))
+    {
+        Ok(pid) => Ok(pid.as_u64()),
+        Err(SpawnError::TooManyProcesses) => Err(SyscallError::TryAgain),
+        Err(SpawnError::OutOfMemory) => Err(SyscallError::OutOfMemory),
+        Err(SpawnError::Elf(_)) => Err(SyscallError::InvalidArgument),
+    }
+}
+
+/// `wait(pid, status)`: wartet auf das Kind `pid` (bei 0 auf ein beliebiges)
+/// und schreibt dessen Exit-Code nach `status`, falls der Zeiger nicht 0 ist.
+/// `status` muss auf eine beschreibbare User-Seite zeigen, sonst `EFAULT`.
+fn sys_wait(frame: &SyscallFrame) -> SyscallResult
+{
+    let [pid, status, ..] = frame.args();
+    if status != 0 && !memory::check_user_range(status, 8, UserAccess::Write)
+    {
+        return Err(SyscallError::Fault);
+    }
+
+    let filter = (pid != 0).then(|| Pid::from_u64(pid));
+    let (child, code) = process::wait(filter).map_err(|WaitError::NoChildren| SyscallError::NoChild)?;
+    // Copy-on-Write-Seiten werden dabei vorher kopiert, nicht erst über die Page Fault.
+    if status != 0 && !memory::write_user(status, &code.to_le_bytes())
+    {
+        return Err(SyscallError::Fault);
+    }
+    Ok(child.as_u64())
+}
+
+#[test_case]
+fn test_user_slice_rejects_kernel_addresses()
+{
+    use crate::memory::{USER_SPACE_END, USER_SPACE_START};
+
+    assert_eq!(user_slice(0, 4).err(), Some(SyscallError::Fault));
+    assert_eq!(user_slice(USER_SPACE_END - 2, 4).err(), Some(SyscallError::Fault));
+    assert_eq!(user_slice(u64::MAX, 2).err(), Some(SyscallError::Fault));
+    // Kernel-Image und VGA-Puffer liegen unterhalb von USER_SPACE_START.
+    assert_eq!(user_slice(0x1000, 4).err(), Some(SyscallError::Fault));
+    assert_eq!(user_slice(0xb8000, 4).err(), Some(SyscallError::Fault));
+    // Im Adressraum des Kernels ist in der User-Hälfte nichts abgebildet.
+    assert_eq!(user_slice(USER_SPACE_START, 4).err(), Some(SyscallError::Fault));
+
+    // Ebenso für den Exit-Code von `wait`.
+    assert!(!memory::write_user(0x1000, &[0; 8]));
+    assert!(!memory::write_user(USER_SPACE_START, &[0; 8]));
+}
@@ -0,0 +1,71 @@
+//! # Modul: time
+//!
+//! Zeitmessung über den **Programmable Interval Timer (PIT)**.
+//!
+//! Der PIT läuft mit seiner Standardeinstellung (Teiler 65536), löst also etwa
+//! 18,2 Timer-Interrupts pro Sekunde aus. Der Timer-Handler in [crate::interrupts]
+//! ruft bei jedem Interrupt [tick] auf; daraus werden Laufzeit und Wartezeiten
+//! berechnet.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Eingangsfrequenz des PIT in Hz.
+pub const PIT_FREQUENCY: u64 = 1_193_182;
+
+/// Teiler, mit dem der PIT nach dem Start betrieben wird.
+pub const PIT_DIVISOR: u64 = 65536;
+
+/// Anzahl der Timer-Interrupts seit dem Start.
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+/// Zählt einen Timer-Interrupt. Wird nur vom Timer-Handler aufgerufen.
+pub(crate) fn tick()
+{
+    TICKS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Anzahl der Timer-Interrupts seit dem Start.
+pub fn ticks() -> u64
+{
+    TICKS.load(Ordering::Relaxed)
+}
+
+/// Rechnet Ticks in Millisekunden um.
+pub fn ticks_to_ms(ticks: u64) -> u64
+{
+    ticks * PIT_DIVISOR * 1000 / PIT_FREQUENCY
+}
+
+/// Rechnet Millisekunden in Ticks um (aufgerundet). Sehr große Werte, etwa
+/// aus `sleep` im User-Space, werden auf die längste darstellbare Dauer begrenzt.
+pub fn ms_to_ticks(ms: u64) -> u64
+{
+    ms.saturating_mul(PIT_FREQUENCY).div_ceil(PIT_DIVISOR * 1000)
+}
+
+/// Laufzeit seit dem Start in Millisekunden.
+pub fn uptime_ms() -> u64
+{
+    ticks_to_ms(ticks())
+}
+
+/// Schläft mindestens `ms` Millisekunden.
+///
+/// Wartet mit `hlt` auf Timer-Interrupts, setzt also aktive Interrupts voraus.
+pub fn sleep_ms(ms: u64)
+{
+    let target = ticks().saturating_add(ms_to_ticks(ms));
+    while ticks() < target
+    {
+        crate::sync::wait();
+    }
+}
+
+#[test_case]
+fn test_tick_conversion_round_trip()
+{
+    assert_eq!(ms_to_ticks(0), 0);
+    assert_eq!(ms_to_ticks(1), 1);
+    assert!(ticks_to_ms(ms_to_ticks(1000)) >= 1000);
+    assert_eq!(ms_to_ticks(u64::MAX), u64::MAX.div_ceil(PIT_DIVISOR * 1000));
+}
@@ -0,0 +1,322 @@
+//! # Modul: usermode
+//!
+//! Dieses Modul ermöglicht das Ausführen von Code im **Ring 3** (User Mode).
+//!
+//! Im User Mode sind privilegierte Befehle wie `hlt`, `cli` oder das Schreiben
+//! von Kontrollregistern verboten; ein Versuch löst eine **General Protection
+//! Fault (#GP)** aus, die wieder im Kernel landet. Außerdem darf nur auf Seiten
+//! zugegriffen werden, die mit [USER_ACCESSIBLE] markiert sind.
+//!
+//! # Ablauf
+//!
+//! ```text
+//! map_user_stack() --> make_user_accessible() --> enter_user_mode() --iretq--> Ring 3
+//! ```
+//!
+//! Prozesse starten über [resume_user_mode] mit einem vollständigen [UserContext].
+//!
+//! Soll der Kernel nach dem Programm weiterlaufen, wird stattdessen
+//! [run_user_mode] verwendet. Der Systemaufruf `exit` kehrt dann über
+//! [return_from_user_mode] mit dem Exit-Code zum Aufrufer zurück.
+//!
+//! Tritt im Ring 3 ein Interrupt oder eine Exception auf, wechselt die CPU
+//! automatisch auf den Kernel-Stack aus `TSS.privilege_stack_table[0]`
+//! (siehe [crate::gdt]).
+//!
+//! [USER_ACCESSIBLE]: x86_64::structures::paging::PageTableFlags::USER_ACCESSIBLE
+
+use x86_64::VirtAddr;
+use x86_64::structures::paging::mapper::{MapToError, FlagUpdateError};
+use x86_64::structures::paging::{FrameAllocator, Mapper, OffsetPageTable, Page, PageTableFlags, Size4KiB};
+
+use crate::gdt;
+
+/// Oberes Ende des Standard-User-Stacks.
+pub const USER_STACK_TOP: u64 = 0x0000_7fff_0000_0000;
+
+/// Anzahl der Seiten des Standard-User-Stacks.
+pub const USER_STACK_PAGES: u64 = 4;
+
+/// RFLAGS beim Eintritt in den User Mode: Bit 1 ist reserviert und immer
+/// gesetzt, Bit 9 (IF) aktiviert Interrupts.
+const USER_RFLAGS: u64 = 0x202;
+
+/// ## User-Stack anlegen
+///
+/// Bildet `pages` Seiten unterhalb von `stack_top` auf frisch allokierte Frames
+/// ab, beschreibbar und für Ring 3 zugänglich.
+///
+/// Gibt das obere Ende des Stacks zurück, das direkt als `rsp` verwendet werden kann.
+pub fn map_user_stack(
+    mapper: &mut OffsetPageTable,
+    frame_allocator: &mut impl FrameAllocator<Size4KiB>,
+    stack_top: VirtAddr,
+    pages: u64,
+) -> Result<VirtAddr, MapToError<Size4KiB>>
+{
+    let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE
+        | PageTableFlags::USER_ACCESSIBLE | PageTableFlags::NO_EXECUTE;
+    let parent_flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE;
+
+    let top_page = Page::<Size4KiB>::containing_address(stack_top - 1u64);
+    for page in Page::range_inclusive(top_page - (pages - 1), top_page)
+    {
+        let frame = frame_allocator.allocate_frame().ok_or(MapToError::FrameAllocationFailed)?;
+        unsafe
+        {
+            mapper.map_to_with_table_flags(page, frame, flags, parent_flags, frame_allocator)?.flush();
+        }
+    }
+    Ok(stack_top)
+}
+
+/// ## Kernel-Seiten für Ring 3 freigeben
+///
+/// Setzt das [USER_ACCESSIBLE]-Flag für alle Seiten im Bereich
+/// `start..start + len` sowie für alle übergeordneten Tabelleneinträge.
+/// Damit kann eine im Kernel gelinkte Funktion im User Mode ausgeführt werden.
+///
+/// # Safety
+///
+/// Die betroffenen Seiten werden für Ring 3 lesbar (und ggf. beschreibbar).
+/// Der Aufrufer muss sicherstellen, dass dort keine schützenswerten Daten liegen.
+///
+/// [USER_ACCESSIBLE]: x86_64::structures::paging::PageTableFlags::USER_ACCESSIBLE
+pub unsafe fn make_user_accessible(mapper: &mut OffsetPageTable, start: VirtAddr, len: u64)
+    -> Result<(), FlagUpdateError>
+{
+    use x86_64::structures::paging::mapper::Translate;
+    use x86_64::structures::paging::mapper::TranslateResult;
+
+    let first = Page::<Size4KiB>::containing_address(start);
+    let last = Page::<Size4KiB>::containing_address(start + len.max(1) - 1u64);
+    for page in Page::range_inclusive(first, last)
+    {
+        let flags = match mapper.translate(page.start_address())
+        {
+            TranslateResult::Mapped { flags, .. } => flags,
+            _ => return Err(FlagUpdateError::PageNotMapped),
+        };
+        let parent_flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE;
+        unsafe
+        {
+            mapper.set_flags_p4_entry(page, parent_flags)?.ignore();
+            mapper.set_flags_p3_entry(page, parent_flags)?.ignore();
+            mapper.set_flags_p2_entry(page, parent_flags)?.ignore();
+            mapper.update_flags(page, flags | PageTableFlags::USER_ACCESSIBLE)?.flush();
+        }
+    }
+    Ok(())
+}
+
+/// ## Sprung in den User Mode
+///
+/// Baut auf dem aktuellen Stack einen Interrupt-Stack-Frame auf und führt
+/// `iretq` aus. Die CPU lädt dabei:
+///
+/// | Wert | Quelle |
+/// |------|--------|
+/// | `SS` | User-Daten-Selektor (RPL 3) |
+/// | `RSP` | `stack_top` |
+/// | `RFLAGS` | `0x202` (Interrupts aktiv) |
+/// | `CS` | User-Code-Selektor (RPL 3) |
+/// | `RIP` | `entry` |
+///
+/// Da `CS` die Privilegstufe 3 hat, läuft der Code danach im Ring 3.
+///
+/// # Safety
+///
+/// `entry` und der Stack unter `stack_top` müssen für Ring 3 abgebildet sein,
+/// und `TSS.privilege_stack_table[0]` muss auf einen gültigen Kernel-Stack zeigen.
+pub unsafe fn enter_user_mode(entry: VirtAddr, stack_top: VirtAddr) -> !
+{
+    let selectors = gdt::selectors();
+    let code = u64::from(selectors.user_code_selector.0);
+    let data = u64::from(selectors.user_data_selector.0);
+
+    unsafe
+    {
+        core::arch::asm!(
+            "mov ds, {data:x}",
+            "mov es, {data:x}",
+            "push {data}",
+            "push {stack}",
+            "push {rflags}",
+            "push {code}",
+            "push {entry}",
+            "iretq",
+            data = in(reg) data,
+            stack = in(reg) stack_top.as_u64(),
+            rflags = in(reg) USER_RFLAGS,
+            code = in(reg) code,
+            entry = in(reg) entry.as_u64(),
+            options(noreturn),
+        );
+    }
+}
+
+/// ## UserContext
+///
+/// Vollständiger Registerzustand eines Programms im Ring 3. Die letzten fünf
+/// Felder bilden den Interrupt-Stack-Frame, den `iretq` erwartet.
+#[derive(Debug, Clone, Default)]
+#[repr(C)]
+pub struct UserContext
+{
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub rbp: u64,
+    pub rbx: u64,
+    pub r9: u64,
+    pub r8: u64,
+    pub r10: u64,
+    pub rdx: u64,
+    pub rsi: u64,
+    pub rdi: u64,
+    pub rax: u64,
+    pub r11: u64,
+    pub rcx: u64,
+    pub rip: u64,
+    pub cs: u64,
+    pub rflags: u64,
+    pub rsp: u64,
+    pub ss: u64,
+}
+
+impl UserContext
+{
+    /// Zustand beim Programmstart: alle Register 0, Interrupts aktiv.
+    pub fn new(entry: VirtAddr, stack_top: VirtAddr) -> UserContext
+    {
+        let selectors = gdt::selectors();
+        UserContext
+        {
+            rip: entry.as_u64(),
+            cs: u64::from(selectors.user_code_selector.0),
+            rflags: USER_RFLAGS,
+            rsp: stack_top.as_u64(),
+            ss: u64::from(selectors.user_data_selector.0),
+            ..UserContext::default()
+        }
+    }
+}
+
+/// ## Programm fortsetzen
+///
+/// Lädt alle Register aus `context` und springt mit `iretq` in den Ring 3.
+/// Wird für neue Prozesse und für das Kind nach `fork` verwendet.
+///
+/// # Safety
+///
+/// Wie bei [enter_user_mode]; `context` muss in den aktiven Adressraum passen.
+pub unsafe fn resume_user_mode(context: &UserContext) -> !
+{
+    unsafe { resume_user_mode_inner(context) }
+}
+
+#[unsafe(naked)]
+unsafe extern "C" fn resume_user_mode_inner(context: *const UserContext) -> !
+{
+    core::arch::naked_asm!(
+        "mov rsp, rdi",
+        "pop r15",
+        "pop r14",
+        "pop r13",
+        "pop r12",
+        "pop rbp",
+        "pop rbx",
+        "pop r9",
+        "pop r8",
+        "pop r10",
+        "pop rdx",
+        "pop rsi",
+        "pop rdi",
+        "pop rax",
+        "pop r11",
+        "pop rcx",
+        "iretq",
+    );
+}
+
+/// Kernel-Stackzeiger von [run_user_mode], zu dem `exit` zurückkehrt.
+static mut KERNEL_RETURN_RSP: u64 = 0;
+
+/// ## User-Programm ausführen und auf `exit` warten
+///
+/// Wie [enter_user_mode], sichert aber vorher RFLAGS, die callee-saved Register
+/// und den Stackzeiger des Kernels. Ruft das Programm den Systemaufruf `exit` auf,
+/// stellt [return_from_user_mode] diesen Zustand wieder her, sodass
+/// `run_user_mode` scheinbar normal mit dem Exit-Code zurückkehrt.
+///
+/// # Safety
+///
+/// Wie bei [enter_user_mode]. Außerdem darf immer nur ein User-Programm
+/// gleichzeitig über diese Funktion laufen.
+pub unsafe fn run_user_mode(entry: VirtAddr, stack_top: VirtAddr) -> i64
+{
+    let selectors = gdt::selectors();
+    let code = u64::from(selectors.user_code_selector.0);
+    let data = u64::from(selectors.user_data_selector.0);
+
+    unsafe { run_user_mode_inner(entry.as_u64(), stack_top.as_u64(), code, data) }
+}
+
+#[unsafe(naked)]
+unsafe extern "C" fn run_user_mode_inner(entry: u64, stack_top: u64, code: u64, data: u64) -> i64
+{
+    core::arch::naked_asm!(
+        "pushfq",
+        "push rbx",
+        "push rbp",
+        "push r12",
+        "push r13",
+        "push r14",
+        "push r15",
+        "mov [rip + {return_rsp}], rsp",
+        "push rcx",
+        "push rsi",
+        "push {rflags}",
+        "push rdx",
+        "push rdi",
+        "iretq",
+        return_rsp = sym KERNEL_RETURN_RSP,
+        rflags = const USER_RFLAGS,
+    );
+}
+
+/// ## Rückkehr aus dem User Mode
+///
+/// Verwirft den aktuellen Kernel-Stack, stellt den in [run_user_mode]
+/// gesicherten Zustand wieder her und lässt `run_user_mode` mit `code`
+/// zurückkehren. Dazu gehört RFLAGS: Kommt der Aufruf aus einem Exception-
+/// oder Syscall-Handler, sind Interrupts dort gesperrt, beim Aufrufer von
+/// `run_user_mode` aber womöglich nicht.
+///
+/// # Safety
+///
+/// Darf nur aus einem Systemaufruf oder einer Exception eines über
+/// [run_user_mode] gestarteten Programms aufgerufen werden.
+pub unsafe fn return_from_user_mode(code: i64) -> !
+{
+    unsafe { return_from_user_mode_inner(code) }
+}
+
+#[unsafe(naked)]
+unsafe extern "C" fn return_from_user_mode_inner(code: i64) -> !
+{
+    core::arch::naked_asm!(
+        "mov rsp, [rip + {return_rsp}]",
+        "mov rax, rdi",
+        "pop r15",
+        "pop r14",
+        "pop r13",
+        "pop r12",
+        "pop rbp",
+        "pop rbx",
+        "popfq",
+        "ret",
+        return_rsp = sym KERNEL_RETURN_RSP,
+    );
+}
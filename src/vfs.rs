@@ -0,0 +1,541 @@
+//! # Modul: vfs
+//!
+//! Dieses Modul stellt das **virtuelle Dateisystem (VFS)** bereit: eine
+//! einheitliche Schnittstelle, hinter der beliebige Dateisysteme eingehängt
+//! werden können.
+//!
+//! # Aufbau
+//!
+//! | Komponente | Aufgabe |
+//! |------------|---------|
+//! | [FileSystem] | Ein eingehängtes Dateisystem mit Wurzelverzeichnis |
+//! | [Inode] | Datei oder Verzeichnis innerhalb eines Dateisystems |
+//! | [File] | Geöffnete Datei mit eigener Position ([OpenFile]) |
+//! | [DirEntry] | Eintrag eines Verzeichnisses |
+//! | [FileTable] | Tabelle der geöffneten Dateien eines Prozesses |
+//! | [TmpFs] | Dateisystem im Arbeitsspeicher, standardmäßig unter `/` |
+//! | [TarFs] | Schreibgeschütztes Dateisystem aus einem USTAR-Archiv (initrd) |
+//! | [FatFs] | FAT12/16/32 mit langen Dateinamen auf einem Blockgerät |
+//! | [Ext2Fs] | ext2 mit Inodes und Unix-Zugriffsrechten auf einem Blockgerät |
+//!
+//! # Pfade und Mountpunkte
+//!
+//! Pfade sind immer absolut. `.` und `..` werden rein textuell aufgelöst
+//! (siehe [path]). Anschließend wird der längste passende Mountpunkt gesucht
+//! und der Rest des Pfads von der Wurzel dieses Dateisystems aus mit
+//! [Inode::lookup] durchlaufen.
+//!
+//! ```text
+//! /mnt/data/a.txt --> Mount "/mnt/data" --> root().lookup("a.txt")
+//! ```
+//!
+//! Trifft die Auflösung auf einen symbolischen Link, wird sein Ziel an Stelle
+//! des Links in den Pfad eingesetzt und von vorn begonnen, höchstens
+//! [MAX_SYMLINKS]-mal.
+//!
+//! # Dateideskriptoren
+//!
+//! [open] legt eine [OpenFile] in der [FileTable] des aktuellen Prozesses ab
+//! und gibt deren Index zurück. [read], [write], [seek], [fstat] und [close]
+//! arbeiten mit diesem Index. Kindprozesse erben die Tabelle; die geöffneten
+//! Dateien samt Position werden dabei geteilt, wie unter Unix.
+
+pub mod ext2;
+pub mod fat;
+mod file;
+mod mount;
+pub mod path;
+mod tarfs;
+mod tmpfs;
+
+pub use ext2::Ext2Fs;
+pub use fat::FatFs;
+pub use file::{FileTable, MAX_OPEN_FILES, OpenFile};
+pub use mount::MAX_SYMLINKS;
+pub use tarfs::TarFs;
+pub use tmpfs::TmpFs;
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::ops::BitOr;
+
+use crate::process;
+
+/// Index in der [FileTable] eines Prozesses.
+pub type Fd = usize;
+
+/// Fehler bei Dateisystem-Operationen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VfsError
+{
+    /// Datei oder Verzeichnis existiert nicht.
+    NotFound,
+    /// Ein Teil des Pfads ist kein Verzeichnis.
+    NotADirectory,
+    /// Die Operation ist auf einem Verzeichnis nicht möglich.
+    IsADirectory,
+    /// Der Eintrag existiert bereits.
+    AlreadyExists,
+    /// Das Verzeichnis ist nicht leer.
+    NotEmpty,
+    /// Der Pfad ist nicht absolut oder hat keinen Namen.
+    InvalidPath,
+    /// Der Dateideskriptor ist ungültig oder erlaubt die Operation nicht.
+    BadFileDescriptor,
+    /// Die Tabelle der geöffneten Dateien ist voll.
+    TooManyOpenFiles,
+    /// Der Pfad ist ein Mountpunkt oder das Dateisystem wird noch verwendet.
+    Busy,
+    /// Das Dateisystem ist schreibgeschützt.
+    ReadOnly,
+    /// Zu viele symbolische Links, vermutlich eine Schleife.
+    SymlinkLoop,
+    /// Die Daten des Dateisystems sind beschädigt.
+    Corrupt,
+    /// Auf dem Datenträger ist kein Platz mehr.
+    NoSpace,
+    /// Ungültiges Argument, z. B. eine negative Position.
+    InvalidArgument,
+    /// Fehler beim Zugriff auf das Gerät.
+    Io,
+    /// Das Dateisystem unterstützt die Operation nicht.
+    Unsupported,
+}
+
+/// Ergebnis einer Dateisystem-Operation.
+pub type VfsResult<T> = Result<T, VfsError>;
+
+/// Art eines Eintrags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType
+{
+    File,
+    Directory,
+    /// Symbolischer Link auf einen anderen Pfad.
+    Symlink,
+    /// Gerätedatei, z. B. eine serielle Schnittstelle.
+    CharDevice,
+}
+
+impl FileType
+{
+    /// Zugriffsrechte für Dateisysteme, die selbst keine speichern.
+    pub fn default_mode(self) -> u16
+    {
+        match self
+        {
+            FileType::Directory => 0o755,
+            FileType::Symlink => 0o777,
+            FileType::File | FileType::CharDevice => 0o644,
+        }
+    }
+}
+
+/// Informationen über eine Datei, wie sie [stat] zurückgibt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Metadata
+{
+    /// Nummer des Inodes, eindeutig innerhalb seines Dateisystems.
+    pub inode: u64,
+    pub file_type: FileType,
+    /// Größe in Bytes; bei Verzeichnissen die Anzahl der Einträge.
+    pub size: u64,
+    /// Zugriffsrechte im Unix-Format, z. B. `0o644`.
+    pub mode: u16,
+}
+
+/// ## DirEntry
+///
+/// Ein Eintrag eines Verzeichnisses, wie ihn [readdir] liefert.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry
+{
+    pub name: String,
+    pub inode: u64,
+    pub file_type: FileType,
+}
+
+/// Bezugspunkt für [seek].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekFrom
+{
+    Start(u64),
+    Current(i64),
+    End(i64),
+}
+
+/// Flags für [open], kombinierbar mit `|`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpenFlags(u32);
+
+impl OpenFlags
+{
+    /// Nur lesen.
+    pub const READ: OpenFlags = OpenFlags(1 << 0);
+    /// Nur schreiben.
+    pub const WRITE: OpenFlags = OpenFlags(1 << 1);
+    /// Lesen und schreiben.
+    pub const READ_WRITE: OpenFlags = OpenFlags(Self::READ.0 | Self::WRITE.0);
+    /// Datei anlegen, falls sie nicht existiert.
+    pub const CREATE: OpenFlags = OpenFlags(1 << 2);
+    /// Mit [CREATE](OpenFlags::CREATE): Fehler, falls die Datei schon existiert.
+    pub const EXCLUSIVE: OpenFlags = OpenFlags(1 << 3);
+    /// Datei beim Öffnen auf Länge 0 kürzen.
+    pub const TRUNCATE: OpenFlags = OpenFlags(1 << 4);
+    /// Jeder Schreibzugriff hängt an das Dateiende an.
+    pub const APPEND: OpenFlags = OpenFlags(1 << 5);
+
+    /// Gibt an, ob alle Flags aus `other` gesetzt sind.
+    pub fn contains(self, other: OpenFlags) -> bool
+    {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for OpenFlags
+{
+    type Output = OpenFlags;
+
+    fn bitor(self, other: OpenFlags) -> OpenFlags
+    {
+        OpenFlags(self.0 | other.0)
+    }
+}
+
+/// ## FileSystem
+///
+/// Ein Dateisystem, das mit [mount] eingehängt werden kann.
+pub trait FileSystem: Send + Sync
+{
+    /// Kurzer Name des Dateisystemtyps, z. B. `"tmpfs"`.
+    fn name(&self) -> &str;
+
+    /// Das Wurzelverzeichnis.
+    fn root(&self) -> Arc<dyn Inode>;
+
+    /// Schreibt zwischengespeicherte Änderungen auf den Datenträger.
+    fn sync(&self) -> VfsResult<()>
+    {
+        Ok(())
+    }
+}
+
+/// ## Inode
+///
+/// Eine Datei oder ein Verzeichnis. Alle Operationen sind positionslos; die
+/// aktuelle Position verwaltet [OpenFile]. Nicht unterstützte Operationen geben
+/// standardmäßig [VfsError::Unsupported] zurück.
+pub trait Inode: Send + Sync
+{
+    /// Nummer, Art und Größe.
+    fn metadata(&self) -> Metadata;
+
+    /// Liest ab `offset` nach `buffer` und gibt die Anzahl gelesener Bytes zurück
+    /// (0 am Dateiende).
+    fn read_at(&self, _offset: u64, _buffer: &mut [u8]) -> VfsResult<usize>
+    {
+        Err(VfsError::Unsupported)
+    }
+
+    /// Schreibt `buffer` ab `offset` und vergrößert die Datei bei Bedarf.
+    fn write_at(&self, _offset: u64, _buffer: &[u8]) -> VfsResult<usize>
+    {
+        Err(VfsError::Unsupported)
+    }
+
+    /// Setzt die Größe der Datei auf `size` Bytes.
+    fn truncate(&self, _size: u64) -> VfsResult<()>
+    {
+        Err(VfsError::Unsupported)
+    }
+
+    /// Sucht den Eintrag `name` in diesem Verzeichnis.
+    fn lookup(&self, _name: &str) -> VfsResult<Arc<dyn Inode>>
+    {
+        Err(VfsError::NotADirectory)
+    }
+
+    /// Legt in diesem Verzeichnis einen neuen Eintrag an.
+    fn create(&self, _name: &str, _file_type: FileType) -> VfsResult<Arc<dyn Inode>>
+    {
+        Err(VfsError::NotADirectory)
+    }
+
+    /// Entfernt den Eintrag `name`; Verzeichnisse müssen leer sein.
+    fn unlink(&self, _name: &str) -> VfsResult<()>
+    {
+        Err(VfsError::NotADirectory)
+    }
+
+    /// Alle Einträge dieses Verzeichnisses ohne `.` und `..`.
+    fn readdir(&self) -> VfsResult<Vec<DirEntry>>
+    {
+        Err(VfsError::NotADirectory)
+    }
+
+    /// Ziel dieses symbolischen Links.
+    fn readlink(&self) -> VfsResult<String>
+    {
+        Err(VfsError::InvalidArgument)
+    }
+
+    /// Setzt die Zugriffsrechte (die unteren 12 Bit von `mode`).
+    fn set_mode(&self, _mode: u16) -> VfsResult<()>
+    {
+        Err(VfsError::Unsupported)
+    }
+}
+
+/// ## File
+///
+/// Eine geöffnete Datei, wie sie in der [FileTable] steht. Für normale
+/// Dateien ist das eine [OpenFile]; Geräte können eigene Implementierungen
+/// bereitstellen.
+pub trait File: Send + Sync
+{
+    /// Liest ab der aktuellen Position und rückt sie vor.
+    fn read(&self, buffer: &mut [u8]) -> VfsResult<usize>;
+
+    /// Schreibt ab der aktuellen Position und rückt sie vor.
+    fn write(&self, buffer: &[u8]) -> VfsResult<usize>;
+
+    /// Setzt die Position und gibt die neue Position zurück.
+    fn seek(&self, _position: SeekFrom) -> VfsResult<u64>
+    {
+        Err(VfsError::Unsupported)
+    }
+
+    /// Informationen über die Datei.
+    fn stat(&self) -> VfsResult<Metadata>;
+}
+
+/// Löst `path` zu einem Inode auf und folgt dabei symbolischen Links.
+pub fn lookup(path: &str) -> VfsResult<Arc<dyn Inode>>
+{
+    mount::resolve(&path::normalize(path)?, true)
+}
+
+/// Löst das übergeordnete Verzeichnis von `path` auf und gibt es zusammen mit
+/// dem letzten Namen zurück.
+fn lookup_parent(path: &str) -> VfsResult<(Arc<dyn Inode>, String)>
+{
+    let mut components = path::normalize(path)?;
+    let name = components.pop().ok_or(VfsError::InvalidPath)?;
+    Ok((mount::resolve(&components, true)?, String::from(name)))
+}
+
+/// ## Datei öffnen
+///
+/// Öffnet `path` mit den Rechten aus `flags` und gibt den Dateideskriptor im
+/// aktuellen Prozess zurück. Verzeichnisse können nur lesend geöffnet werden.
+pub fn open(path: &str, flags: OpenFlags) -> VfsResult<Fd>
+{
+    let inode = match lookup(path)
+    {
+        Ok(_) if flags.contains(OpenFlags::CREATE | OpenFlags::EXCLUSIVE) => return Err(VfsError::AlreadyExists),
+        Ok(inode) => inode,
+        Err(VfsError::NotFound) if flags.contains(OpenFlags::CREATE) =>
+        {
+            let (parent, name) = lookup_parent(path)?;
+            parent.create(&name, FileType::File)?
+        }
+        Err(error) => return Err(error),
+    };
+
+    let file_type = inode.metadata().file_type;
+    if file_type == FileType::Directory && flags.contains(OpenFlags::WRITE)
+    {
+        return Err(VfsError::IsADirectory);
+    }
+    if file_type == FileType::File && flags.contains(OpenFlags::TRUNCATE | OpenFlags::WRITE)
+    {
+        inode.truncate(0)?;
+    }
+
+    process::files().lock().insert(Arc::new(OpenFile::new(inode, flags)))
+}
+
+/// Gibt die geöffnete Datei zu `fd` im aktuellen Prozess zurück.
+pub fn file(fd: Fd) -> VfsResult<Arc<dyn File>>
+{
+    process::files().lock().get(fd)
+}
+
+/// Liest aus `fd` nach `buffer`.
+pub fn read(fd: Fd, buffer: &mut [u8]) -> VfsResult<usize>
+{
+    file(fd)?.read(buffer)
+}
+
+/// Schreibt `buffer` nach `fd`.
+pub fn write(fd: Fd, buffer: &[u8]) -> VfsResult<usize>
+{
+    file(fd)?.write(buffer)
+}
+
+/// Setzt die Position von `fd`.
+pub fn seek(fd: Fd, position: SeekFrom) -> VfsResult<u64>
+{
+    file(fd)?.seek(position)
+}
+
+/// Schließt `fd`. Die Datei selbst wird erst geschlossen, wenn kein anderer
+/// Deskriptor (z. B. in einem Kindprozess) mehr auf sie verweist.
+pub fn close(fd: Fd) -> VfsResult<()>
+{
+    process::files().lock().remove(fd).map(drop)
+}
+
+/// Informationen über `path`.
+pub fn stat(path: &str) -> VfsResult<Metadata>
+{
+    Ok(lookup(path)?.metadata())
+}
+
+/// Informationen über `path`; ist `path` ein symbolischer Link, über den Link
+/// selbst.
+pub fn lstat(path: &str) -> VfsResult<Metadata>
+{
+    Ok(mount::resolve(&path::normalize(path)?, false)?.metadata())
+}
+
+/// Ziel des symbolischen Links `path`.
+pub fn readlink(path: &str) -> VfsResult<String>
+{
+    mount::resolve(&path::normalize(path)?, false)?.readlink()
+}
+
+/// Setzt die Zugriffsrechte von `path`, z. B. `chmod("/mnt/skript", 0o755)`.
+pub fn chmod(path: &str, mode: u16) -> VfsResult<()>
+{
+    lookup(path)?.set_mode(mode & 0o7777)
+}
+
+/// Liest die ganze Datei `path` in einen Puffer.
+pub fn read_to_end(path: &str) -> VfsResult<Vec<u8>>
+{
+    let inode = lookup(path)?;
+    let mut data = alloc::vec![0; inode.metadata().size as usize];
+    let count = inode.read_at(0, &mut data)?;
+    data.truncate(count);
+    Ok(data)
+}
+
+/// Informationen über die geöffnete Datei `fd`.
+pub fn fstat(fd: Fd) -> VfsResult<Metadata>
+{
+    file(fd)?.stat()
+}
+
+/// Einträge des Verzeichnisses `path`, nach Namen sortiert.
+pub fn readdir(path: &str) -> VfsResult<Vec<DirEntry>>
+{
+    let mut entries = lookup(path)?.readdir()?;
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(entries)
+}
+
+/// Legt das Verzeichnis `path` an.
+pub fn mkdir(path: &str) -> VfsResult<()>
+{
+    let (parent, name) = lookup_parent(path)?;
+    parent.create(&name, FileType::Directory).map(drop)
+}
+
+/// Entfernt die Datei oder das leere Verzeichnis `path`.
+pub fn unlink(path: &str) -> VfsResult<()>
+{
+    if mount::is_mount_point(&path::normalize(path)?)
+    {
+        return Err(VfsError::Busy);
+    }
+    let (parent, name) = lookup_parent(path)?;
+    parent.unlink(&name)
+}
+
+/// ## Dateisystem einhängen
+///
+/// Hängt `fs` am Verzeichnis `path` ein. Dessen bisheriger Inhalt ist danach
+/// verdeckt, bis das Dateisystem mit [unmount] wieder ausgehängt wird.
+pub fn mount(path: &str, fs: Arc<dyn FileSystem>) -> VfsResult<()>
+{
+    let components = path::normalize(path)?;
+    if mount::resolve(&components, true)?.metadata().file_type != FileType::Directory
+    {
+        return Err(VfsError::NotADirectory);
+    }
+    mount::add(&components, fs)
+}
+
+/// Hängt das Dateisystem am Mountpunkt `path` aus und gibt es zurück.
+pub fn unmount(path: &str) -> VfsResult<Arc<dyn FileSystem>>
+{
+    let fs = mount::remove(&path::normalize(path)?)?;
+    fs.sync()?;
+    Ok(fs)
+}
+
+#[test_case]
+fn test_open_write_seek_read()
+{
+    mkdir("/vfs-rw").unwrap();
+    let fd = open("/vfs-rw/file", OpenFlags::READ_WRITE | OpenFlags::CREATE).unwrap();
+    assert_eq!(write(fd, b"hello world"), Ok(11));
+    assert_eq!(seek(fd, SeekFrom::Start(6)), Ok(6));
+
+    let mut buffer = [0; 16];
+    assert_eq!(read(fd, &mut buffer), Ok(5));
+    assert_eq!(&buffer[..5], b"world");
+    assert_eq!(read(fd, &mut buffer), Ok(0));
+
+    assert_eq!(seek(fd, SeekFrom::End(-5)), Ok(6));
+    assert_eq!(seek(fd, SeekFrom::Current(-7)), Err(VfsError::InvalidArgument));
+    assert_eq!(fstat(fd).map(|metadata| metadata.size), Ok(11));
+
+    close(fd).unwrap();
+    assert_eq!(read(fd, &mut buffer), Err(VfsError::BadFileDescriptor));
+    assert_eq!(close(fd), Err(VfsError::BadFileDescriptor));
+}
+
+#[test_case]
+fn test_open_flags()
+{
+    mkdir("/vfs-flags").unwrap();
+    assert_eq!(open("/vfs-flags/missing", OpenFlags::READ), Err(VfsError::NotFound));
+    assert_eq!(open("/vfs-flags", OpenFlags::WRITE), Err(VfsError::IsADirectory));
+
+    let fd = open("/vfs-flags/log", OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::APPEND).unwrap();
+    write(fd, b"one ").unwrap();
+    seek(fd, SeekFrom::Start(0)).unwrap();
+    write(fd, b"two").unwrap();
+    assert_eq!(read(fd, &mut [0; 4]), Err(VfsError::BadFileDescriptor));
+    close(fd).unwrap();
+    assert_eq!(stat("/vfs-flags/log").map(|metadata| metadata.size), Ok(7));
+
+    assert_eq!(open("/vfs-flags/log", OpenFlags::CREATE | OpenFlags::EXCLUSIVE), Err(VfsError::AlreadyExists));
+    let fd = open("/vfs-flags/log", OpenFlags::WRITE | OpenFlags::TRUNCATE).unwrap();
+    close(fd).unwrap();
+    assert_eq!(stat("/vfs-flags/log").map(|metadata| metadata.size), Ok(0));
+}
+
+#[test_case]
+fn test_readdir_and_mount()
+{
+    mkdir("/vfs-mnt").unwrap();
+    mkdir("/vfs-mnt/b").unwrap();
+    close(open("/vfs-mnt/a", OpenFlags::CREATE).unwrap()).unwrap();
+    let names: Vec<String> = readdir("/vfs-mnt/./b/..").unwrap().into_iter().map(|entry| entry.name).collect();
+    assert_eq!(names, ["a", "b"]);
+
+    mount("/vfs-mnt/b", Arc::new(TmpFs::new())).unwrap();
+    assert_eq!(readdir("/vfs-mnt/b"), Ok(Vec::new()));
+    close(open("/vfs-mnt/b/inner", OpenFlags::CREATE).unwrap()).unwrap();
+    assert!(stat("/vfs-mnt/b/inner").is_ok());
+    assert_eq!(unlink("/vfs-mnt/b"), Err(VfsError::Busy));
+
+    unmount("/vfs-mnt/b").unwrap();
+    assert_eq!(stat("/vfs-mnt/b/inner"), Err(VfsError::NotFound));
+    unlink("/vfs-mnt/b").unwrap();
+    assert_eq!(unlink("/vfs-mnt"), Err(VfsError::NotEmpty));
+}
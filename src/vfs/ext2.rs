@@ -0,0 +1,663 @@
+//! Treiber für das Second Extended Filesystem (ext2).
+//!
+//! Ein ext2-Datenträger ist in **Blockgruppen** gleicher Größe aufgeteilt. Der
+//! Superblock liegt immer bei Byte 1024 und beschreibt die Geometrie; direkt
+//! dahinter folgt die Tabelle der Gruppendeskriptoren. Jede Gruppe hat eine
+//! Bitmap für ihre Blöcke, eine für ihre Inodes und eine Inode-Tabelle:
+//!
+//! ```text
+//! | Boot | Superblock | Deskriptoren | Block-Bitmap | Inode-Bitmap | Inode-Tabelle | Daten ... |
+//! |      |<------------------------------ Gruppe 0 ---------------------------------------->| Gruppe 1 ...
+//! ```
+//!
+//! Jede Datei hat genau einen **Inode** mit Art, Zugriffsrechten, Größe und
+//! Blockzuordnung (siehe [inode]). Verzeichnisse sind Dateien, die Namen auf
+//! Inode-Nummern abbilden (siehe [dir]); ein Inode kann unter mehreren Namen
+//! erreichbar sein (harte Links). Die Wurzel ist immer Inode 2.
+//!
+//! Unterstützt werden Blockgrößen von 1 bis 4 KiB, die Merkmale `filetype`,
+//! `sparse_super` und `large_file` sowie schnelle symbolische Links. Ein
+//! Hash-Index (`dir_index`) wird beim Lesen ignoriert und beim Ändern eines
+//! Verzeichnisses entfernt. Ohne Echtzeituhr bleiben alle Zeitstempel
+//! unverändert bzw. 0.
+
+mod dir;
+mod inode;
+mod volume;
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::{DirEntry, FileSystem, FileType, Inode, Metadata, VfsError, VfsResult};
+use crate::block::{BlockDevice, CachedDevice};
+use crate::sync::{Mutex, MutexGuard};
+use inode::{BlockMap, FLAG_INDEX, MODE_DIRECTORY, MODE_FILE, RawInode};
+use volume::{Counters, INCOMPAT_FILETYPE, RO_COMPAT_LARGE_FILE, Volume};
+
+/// Inode-Nummer des Wurzelverzeichnisses.
+const ROOT_INODE: u32 = 2;
+
+/// Größte Anzahl harter Links auf einen Inode.
+const MAX_LINKS: u16 = 32000;
+
+/// Veränderlicher Zustand des Dateisystems.
+struct State
+{
+    counters: Counters,
+    /// Alle lebenden Inodes nach Nummer.
+    inodes: BTreeMap<u32, Weak<Ext2Inode>>,
+}
+
+/// Gemeinsame Daten aller Inodes eines Dateisystems.
+struct Shared
+{
+    volume: Volume,
+    /// Serialisiert alle Zugriffe auf das Dateisystem.
+    state: Mutex<State>,
+}
+
+/// ## Ext2Fs
+///
+/// Ein ext2-Dateisystem auf einem [BlockDevice], lesend und schreibend. Alle
+/// Änderungen gehen sofort an den Puffercache und spätestens mit
+/// [FileSystem::sync] auf das Gerät; ein Dateisystem mit unbekannten
+/// Merkmalen, die nur das Schreiben betreffen, wird schreibgeschützt
+/// eingehängt.
+pub struct Ext2Fs
+{
+    shared: Arc<Shared>,
+    root: Arc<Ext2Inode>,
+}
+
+impl Ext2Fs
+{
+    /// Liest den Superblock von `device` und bereitet das Dateisystem vor.
+    pub fn new(device: Arc<dyn BlockDevice>) -> VfsResult<Ext2Fs>
+    {
+        let device: Arc<dyn BlockDevice> = Arc::new(CachedDevice::new(device));
+        let (volume, counters) = Volume::open(device)?;
+        let shared = Arc::new(Shared { volume, state: Mutex::new(State { counters, inodes: BTreeMap::new() }) });
+        let root = Ext2Inode::get(&shared, &mut shared.state.lock(), ROOT_INODE)?;
+        if root.file_type != FileType::Directory
+        {
+            return Err(VfsError::Corrupt);
+        }
+        Ok(Ext2Fs { shared, root })
+    }
+
+    /// Blockgröße in Bytes.
+    pub fn block_size(&self) -> usize
+    {
+        self.shared.volume.block_size
+    }
+
+    /// Bezeichnung des Datenträgers aus dem Superblock.
+    pub fn label(&self) -> &str
+    {
+        &self.shared.volume.label
+    }
+
+    /// Gibt an, ob das Dateisystem nur gelesen werden kann.
+    pub fn is_read_only(&self) -> bool
+    {
+        self.shared.volume.read_only
+    }
+
+    /// Anzahl der freien Blöcke.
+    pub fn free_blocks(&self) -> u32
+    {
+        self.shared.state.lock().counters.free_blocks()
+    }
+
+    /// Anzahl der freien Inodes.
+    pub fn free_inodes(&self) -> u32
+    {
+        self.shared.state.lock().counters.free_inodes()
+    }
+
+    /// Freie Blöcke und freie Inodes jeder Blockgruppe laut ihrem Deskriptor.
+    pub fn group_free_counts(&self) -> Vec<(u16, u16)>
+    {
+        let state = self.shared.state.lock();
+        (0..self.shared.volume.group_count()).map(|group| state.counters.group_free(group)).collect()
+    }
+}
+
+impl FileSystem for Ext2Fs
+{
+    fn name(&self) -> &str
+    {
+        "ext2"
+    }
+
+    fn root(&self) -> Arc<dyn Inode>
+    {
+        self.root.clone()
+    }
+
+    fn sync(&self) -> VfsResult<()>
+    {
+        let _state = self.shared.state.lock();
+        self.shared.volume.flush()
+    }
+}
+
+/// Datei, Verzeichnis oder symbolischer Link in einem [Ext2Fs].
+struct Ext2Inode
+{
+    shared: Arc<Shared>,
+    number: u32,
+    file_type: FileType,
+    raw: Mutex<RawInode>,
+}
+
+/// Ein Eintrag samt Nummer des Verzeichnisblocks, in dem er steht.
+struct Found
+{
+    entry: dir::Entry,
+    block_index: u64,
+}
+
+impl Ext2Inode
+{
+    /// Gibt den Inode `number` zurück, aus der Tabelle oder von der Platte.
+    fn get(shared: &Arc<Shared>, state: &mut State, number: u32) -> VfsResult<Arc<Ext2Inode>>
+    {
+        if let Some(inode) = state.inodes.get(&number).and_then(Weak::upgrade)
+        {
+            return Ok(inode);
+        }
+
+        let raw = shared.volume.read_inode(number)?;
+        if raw.links_count == 0
+        {
+            return Err(VfsError::Corrupt);
+        }
+        let inode = Arc::new(Ext2Inode { shared: shared.clone(), number, file_type: raw.file_type(), raw: Mutex::new(raw) });
+        state.inodes.retain(|_, inode| inode.strong_count() > 0);
+        state.inodes.insert(number, Arc::downgrade(&inode));
+        Ok(inode)
+    }
+
+    fn volume(&self) -> &Volume
+    {
+        &self.shared.volume
+    }
+
+    fn lock(&self) -> (MutexGuard<'_, State>, MutexGuard<'_, RawInode>)
+    {
+        let state = self.shared.state.lock();
+        (state, self.raw.lock())
+    }
+
+    fn block_map<'a>(&'a self, state: &'a mut State) -> BlockMap<'a>
+    {
+        BlockMap { volume: self.volume(), counters: &mut state.counters, goal: self.volume().inode_group(self.number) }
+    }
+
+    /// Liest `buffer.len()` Bytes ab `offset`; Löcher ergeben Nullen.
+    fn read_data(&self, state: &mut State, raw: &RawInode, offset: u64, buffer: &mut [u8]) -> VfsResult<()>
+    {
+        let block_size = self.volume().block_size as u64;
+        let map = self.block_map(state);
+        let mut block = vec![0; block_size as usize];
+        let mut done = 0;
+        while done < buffer.len()
+        {
+            let position = offset + done as u64;
+            let within = (position % block_size) as usize;
+            let count = (buffer.len() - done).min(block.len() - within);
+            match map.lookup(raw, position / block_size)?
+            {
+                0 => buffer[done..done + count].fill(0),
+                number =>
+                {
+                    self.volume().read_block(number, &mut block)?;
+                    buffer[done..done + count].copy_from_slice(&block[within..within + count]);
+                }
+            }
+            done += count;
+        }
+        Ok(())
+    }
+
+    /// Schreibt `data` ab `offset`, belegt fehlende Blöcke und vergrößert die
+    /// Datei bei Bedarf. Der Inode wird anschließend zurückgeschrieben.
+    fn write_data(&self, state: &mut State, raw: &mut RawInode, offset: u64, data: &[u8]) -> VfsResult<()>
+    {
+        let block_size = self.volume().block_size as u64;
+        let end = offset.checked_add(data.len() as u64).ok_or(VfsError::NoSpace)?;
+        if raw.is(MODE_FILE) && end > u64::from(i32::MAX as u32) && self.volume().features_ro_compat & RO_COMPAT_LARGE_FILE == 0
+        {
+            return Err(VfsError::NoSpace);
+        }
+
+        let mut block = vec![0; block_size as usize];
+        let mut done = 0;
+        let result = (|| -> VfsResult<()>
+        {
+            let mut map = self.block_map(state);
+            while done < data.len()
+            {
+                let position = offset + done as u64;
+                let within = (position % block_size) as usize;
+                let count = (data.len() - done).min(block.len() - within);
+                let number = map.map(raw, position / block_size)?;
+                if count < block.len()
+                {
+                    self.volume().read_block(number, &mut block)?;
+                }
+                block[within..within + count].copy_from_slice(&data[done..done + count]);
+                self.volume().write_block(number, &block)?;
+                done += count;
+            }
+            Ok(())
+        })();
+
+        // Auch bei einem Fehler (z. B. voller Platte) gehören die bereits
+        // belegten Blöcke zum Inode.
+        raw.size = raw.size.max(offset + done as u64);
+        self.volume().write_inode(self.number, raw)?;
+        result
+    }
+
+    /// Setzt die Dateigröße auf `size`. Blöcke hinter dem neuen Ende werden
+    /// freigegeben und der Rest des letzten Blocks genullt, damit eine spätere
+    /// Vergrößerung Nullen liest.
+    fn resize(&self, state: &mut State, raw: &mut RawInode, size: u64) -> VfsResult<()>
+    {
+        let block_size = self.volume().block_size as u64;
+        if size < raw.size
+        {
+            self.block_map(state).truncate(raw, size.div_ceil(block_size))?;
+            let within = (size % block_size) as usize;
+            let last = self.block_map(state).lookup(raw, size / block_size)?;
+            if within != 0 && last != 0
+            {
+                let mut block = vec![0; block_size as usize];
+                self.volume().read_block(last, &mut block)?;
+                block[within..].fill(0);
+                self.volume().write_block(last, &block)?;
+            }
+        }
+        raw.size = size;
+        self.volume().write_inode(self.number, raw)
+    }
+
+    /// Gibt Blöcke, Attributblock und schließlich den Inode selbst frei.
+    fn release(&self, state: &mut State, raw: &mut RawInode) -> VfsResult<()>
+    {
+        if raw.inline_target(self.volume().block_size).is_none()
+        {
+            self.block_map(state).truncate(raw, 0)?;
+        }
+        if raw.file_acl != 0
+        {
+            // Attributblöcke können von mehreren Inodes geteilt werden.
+            let mut block = vec![0; self.volume().block_size];
+            self.volume().read_block(raw.file_acl, &mut block)?;
+            let references = u32::from_le_bytes(block[4..8].try_into().unwrap());
+            if references > 1
+            {
+                block[4..8].copy_from_slice(&(references - 1).to_le_bytes());
+                self.volume().write_block(raw.file_acl, &block)?;
+            }
+            else
+            {
+                self.volume().free_block(&mut state.counters, raw.file_acl)?;
+            }
+        }
+        self.volume().free_inode(&mut state.counters, self.number, raw.is(MODE_DIRECTORY))?;
+        *raw = RawInode::new(raw.mode);
+        Ok(())
+    }
+
+    /// Alle Blöcke dieses Verzeichnisses samt Gerätenummer.
+    fn directory_blocks(&self, state: &mut State, raw: &RawInode) -> VfsResult<Vec<(u64, u32, Vec<u8>)>>
+    {
+        if self.file_type != FileType::Directory
+        {
+            return Err(VfsError::NotADirectory);
+        }
+        let block_size = self.volume().block_size;
+        let map = self.block_map(state);
+        let mut blocks = Vec::new();
+        for index in 0..raw.size.div_ceil(block_size as u64)
+        {
+            let number = map.lookup(raw, index)?;
+            if number == 0
+            {
+                return Err(VfsError::Corrupt);
+            }
+            let mut block = vec![0; block_size];
+            self.volume().read_block(number, &mut block)?;
+            blocks.push((index, number, block));
+        }
+        Ok(blocks)
+    }
+
+    /// Sucht den Eintrag `name` in diesem Verzeichnis.
+    fn find(&self, state: &mut State, raw: &RawInode, name: &str) -> VfsResult<Option<Found>>
+    {
+        for (block_index, _, block) in self.directory_blocks(state, raw)?
+        {
+            if let Some(entry) = dir::parse(&block)?.into_iter().find(|entry| entry.name == name)
+            {
+                return Ok(Some(Found { entry, block_index }));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Art des Eintrags für das Verzeichnis, wenn das Merkmal `filetype` aktiv ist.
+    fn entry_kind(&self, file_type: FileType) -> u8
+    {
+        if self.volume().features_incompat & INCOMPAT_FILETYPE == 0
+        {
+            return dir::TYPE_UNKNOWN;
+        }
+        match file_type
+        {
+            FileType::Directory => dir::TYPE_DIRECTORY,
+            FileType::Symlink => dir::TYPE_SYMLINK,
+            _ => dir::TYPE_FILE,
+        }
+    }
+
+    /// Trägt `name` mit dem Inode `number` in dieses Verzeichnis ein und hängt
+    /// dazu bei Bedarf einen Block an.
+    fn add_entry(&self, state: &mut State, raw: &mut RawInode, name: &str, number: u32, kind: u8) -> VfsResult<()>
+    {
+        // Ein vorhandener Hash-Index passt nach der Änderung nicht mehr.
+        raw.flags &= !FLAG_INDEX;
+        for (_, device_block, mut block) in self.directory_blocks(state, raw)?
+        {
+            if dir::insert(&mut block, name, number, kind)?
+            {
+                self.volume().write_block(device_block, &block)?;
+                return self.volume().write_inode(self.number, raw);
+            }
+        }
+
+        let block_size = self.volume().block_size;
+        let index = raw.size / block_size as u64;
+        let device_block = self.block_map(state).map(raw, index)?;
+        let mut block = dir::empty_block(block_size);
+        dir::insert(&mut block, name, number, kind)?;
+        self.volume().write_block(device_block, &block)?;
+        raw.size += block_size as u64;
+        self.volume().write_inode(self.number, raw)
+    }
+
+    /// Entfernt den Eintrag `found` aus diesem Verzeichnis.
+    fn remove_entry(&self, state: &mut State, raw: &mut RawInode, found: &Found) -> VfsResult<()>
+    {
+        let device_block = self.block_map(state).lookup(raw, found.block_index)?;
+        let mut block = vec![0; self.volume().block_size];
+        self.volume().read_block(device_block, &mut block)?;
+        dir::remove(&mut block, found.entry.offset)?;
+        self.volume().write_block(device_block, &block)?;
+        raw.flags &= !FLAG_INDEX;
+        self.volume().write_inode(self.number, raw)
+    }
+
+    /// Gibt an, ob dieses Verzeichnis außer `.` und `..` keine Einträge hat.
+    fn is_empty_directory(&self, state: &mut State, raw: &RawInode) -> VfsResult<bool>
+    {
+        for (_, _, block) in self.directory_blocks(state, raw)?
+        {
+            if dir::parse(&block)?.iter().any(|entry| entry.name != "." && entry.name != "..")
+            {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Prüft, ob dieser Inode geändert werden darf. Gelöschte, aber noch
+    /// geöffnete Inodes ergeben [VfsError::NotFound].
+    fn check_writable(&self, raw: &RawInode) -> VfsResult<()>
+    {
+        if self.volume().read_only
+        {
+            return Err(VfsError::ReadOnly);
+        }
+        if raw.links_count == 0
+        {
+            return Err(VfsError::NotFound);
+        }
+        Ok(())
+    }
+}
+
+impl Inode for Ext2Inode
+{
+    fn metadata(&self) -> Metadata
+    {
+        let (mut state, raw) = self.lock();
+        let size = match self.file_type
+        {
+            FileType::Directory => self.directory_blocks(&mut state, &raw).map_or(0, |blocks|
+            {
+                blocks.iter().filter_map(|(_, _, block)| dir::parse(block).ok()).map(|entries| entries.len()).sum::<usize>() as u64 - 2
+            }),
+            _ => raw.size,
+        };
+        Metadata { inode: u64::from(self.number), file_type: self.file_type, size, mode: raw.permissions() }
+    }
+
+    fn read_at(&self, offset: u64, buffer: &mut [u8]) -> VfsResult<usize>
+    {
+        if self.file_type == FileType::Directory
+        {
+            return Err(VfsError::IsADirectory);
+        }
+        let (mut state, raw) = self.lock();
+        if offset >= raw.size
+        {
+            return Ok(0);
+        }
+        let count = buffer.len().min((raw.size - offset) as usize);
+        if let Some(target) = raw.inline_target(self.volume().block_size)
+        {
+            buffer[..count].copy_from_slice(&target[offset as usize..offset as usize + count]);
+            return Ok(count);
+        }
+        self.read_data(&mut state, &raw, offset, &mut buffer[..count])?;
+        Ok(count)
+    }
+
+    fn write_at(&self, offset: u64, buffer: &[u8]) -> VfsResult<usize>
+    {
+        if self.file_type != FileType::File
+        {
+            return Err(if self.file_type == FileType::Directory { VfsError::IsADirectory } else { VfsError::InvalidArgument });
+        }
+        let (mut state, mut raw) = self.lock();
+        self.check_writable(&raw)?;
+        self.write_data(&mut state, &mut raw, offset, buffer)?;
+        Ok(buffer.len())
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult<()>
+    {
+        if self.file_type != FileType::File
+        {
+            return Err(if self.file_type == FileType::Directory { VfsError::IsADirectory } else { VfsError::InvalidArgument });
+        }
+        let (mut state, mut raw) = self.lock();
+        self.check_writable(&raw)?;
+        self.resize(&mut state, &mut raw, size)
+    }
+
+    fn lookup(&self, name: &str) -> VfsResult<Arc<dyn Inode>>
+    {
+        let (mut state, raw) = self.lock();
+        let found = self.find(&mut state, &raw, name)?.ok_or(VfsError::NotFound)?;
+        drop(raw);
+        Ok(Ext2Inode::get(&self.shared, &mut state, found.entry.inode)?)
+    }
+
+    fn create(&self, name: &str, file_type: FileType) -> VfsResult<Arc<dyn Inode>>
+    {
+        if !dir::is_valid_name(name)
+        {
+            return Err(VfsError::InvalidPath);
+        }
+        let mode = match file_type
+        {
+            FileType::File => MODE_FILE,
+            FileType::Directory => MODE_DIRECTORY,
+            _ => return Err(VfsError::Unsupported),
+        } | file_type.default_mode();
+
+        let (mut state, mut raw) = self.lock();
+        self.check_writable(&raw)?;
+        if self.find(&mut state, &raw, name)?.is_some()
+        {
+            return Err(VfsError::AlreadyExists);
+        }
+        if file_type == FileType::Directory && raw.links_count >= MAX_LINKS
+        {
+            return Err(VfsError::NoSpace);
+        }
+
+        let directory = file_type == FileType::Directory;
+        let goal = self.volume().inode_group(self.number);
+        let number = self.volume().allocate_inode(&mut state.counters, goal, directory)?;
+        let mut child = RawInode::new(mode);
+        child.links_count = 1;
+        let child_node = Ext2Inode { shared: self.shared.clone(), number, file_type, raw: Mutex::new(child.clone()) };
+
+        let result = (|| -> VfsResult<()>
+        {
+            if directory
+            {
+                child.links_count = 2;
+                let block_size = self.volume().block_size;
+                let block = child_node.block_map(&mut state).map(&mut child, 0)?;
+                let kind = self.entry_kind(FileType::Directory);
+                self.volume().write_block(block, &dir::new_directory(block_size, number, self.number, kind))?;
+                child.size = block_size as u64;
+            }
+            self.volume().write_inode(number, &child)?;
+            self.add_entry(&mut state, &mut raw, name, number, self.entry_kind(file_type))?;
+            if directory
+            {
+                raw.links_count += 1;
+                self.volume().write_inode(self.number, &raw)?;
+            }
+            Ok(())
+        })();
+
+        if let Err(error) = result
+        {
+            child_node.release(&mut state, &mut child)?;
+            return Err(error);
+        }
+
+        *child_node.raw.lock() = child;
+        let inode = Arc::new(child_node);
+        state.inodes.retain(|_, inode| inode.strong_count() > 0);
+        state.inodes.insert(number, Arc::downgrade(&inode));
+        Ok(inode)
+    }
+
+    fn unlink(&self, name: &str) -> VfsResult<()>
+    {
+        if name == "." || name == ".."
+        {
+            return Err(VfsError::InvalidArgument);
+        }
+        let (mut state, mut raw) = self.lock();
+        self.check_writable(&raw)?;
+        let found = self.find(&mut state, &raw, name)?.ok_or(VfsError::NotFound)?;
+        let child = Ext2Inode::get(&self.shared, &mut state, found.entry.inode)?;
+        let mut child_raw = child.raw.lock();
+
+        let directory = child.file_type == FileType::Directory;
+        if directory && !child.is_empty_directory(&mut state, &child_raw)?
+        {
+            return Err(VfsError::NotEmpty);
+        }
+
+        self.remove_entry(&mut state, &mut raw, &found)?;
+        if directory
+        {
+            // `..` des Kindes verweist nicht mehr auf dieses Verzeichnis.
+            raw.links_count -= 1;
+            self.volume().write_inode(self.number, &raw)?;
+            child_raw.links_count = 0;
+        }
+        else
+        {
+            child_raw.links_count -= 1;
+        }
+
+        if child_raw.links_count == 0
+        {
+            // Noch geöffnete Inodes zeigen danach auf eine leere Datei.
+            child.release(&mut state, &mut child_raw)?;
+            state.inodes.remove(&child.number);
+        }
+        else
+        {
+            self.volume().write_inode(child.number, &child_raw)?;
+        }
+        Ok(())
+    }
+
+    fn readdir(&self) -> VfsResult<Vec<DirEntry>>
+    {
+        let (mut state, raw) = self.lock();
+        let mut entries = Vec::new();
+        for (_, _, block) in self.directory_blocks(&mut state, &raw)?
+        {
+            for entry in dir::parse(&block)?
+            {
+                if entry.name == "." || entry.name == ".."
+                {
+                    continue;
+                }
+                let file_type = match entry.file_type()
+                {
+                    Some(file_type) => file_type,
+                    None => self.volume().read_inode(entry.inode)?.file_type(),
+                };
+                entries.push(DirEntry { name: entry.name, inode: u64::from(entry.inode), file_type });
+            }
+        }
+        Ok(entries)
+    }
+
+    fn readlink(&self) -> VfsResult<String>
+    {
+        if self.file_type != FileType::Symlink
+        {
+            return Err(VfsError::InvalidArgument);
+        }
+        let (mut state, raw) = self.lock();
+        let target = match raw.inline_target(self.volume().block_size)
+        {
+            Some(target) => target,
+            None =>
+            {
+                let mut target = vec![0; usize::try_from(raw.size).map_err(|_| VfsError::Corrupt)?];
+                self.read_data(&mut state, &raw, 0, &mut target)?;
+                target
+            }
+        };
+        String::from_utf8(target).map_err(|_| VfsError::Corrupt)
+    }
+
+    fn set_mode(&self, mode: u16) -> VfsResult<()>
+    {
+        let (_state, mut raw) = self.lock();
+        self.check_writable(&raw)?;
+        raw.mode = (raw.mode & 0xF000) | (mode & 0o7777);
+        self.volume().write_inode(self.number, &raw)
+    }
+}
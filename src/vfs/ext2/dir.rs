@@ -0,0 +1,231 @@
+//! Verzeichniseinträge von ext2.
+//!
+//! Ein Verzeichnis ist eine Datei aus Blöcken; jeder Block ist lückenlos in
+//! Einträge variabler Länge aufgeteilt, die nie über eine Blockgrenze reichen:
+//!
+//! | Offset | Größe | Inhalt |
+//! |--------|-------|--------|
+//! | 0 | 4 | Inode, 0 für einen freien Eintrag |
+//! | 4 | 2 | Länge des Eintrags bis zum nächsten (`rec_len`) |
+//! | 6 | 1 | Länge des Namens |
+//! | 7 | 1 | Art der Datei (nur mit dem Merkmal `filetype`) |
+//! | 8 | n | Name, ohne abschließendes NUL |
+//!
+//! Gelöschte Einträge werden dem vorherigen Eintrag im selben Block
+//! zugeschlagen; der letzte Eintrag reicht immer bis zum Ende des Blocks.
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::vfs::{FileType, VfsError, VfsResult};
+
+/// Werte für die Art der Datei im Eintrag.
+pub const TYPE_UNKNOWN: u8 = 0;
+pub const TYPE_FILE: u8 = 1;
+pub const TYPE_DIRECTORY: u8 = 2;
+const TYPE_CHAR_DEVICE: u8 = 3;
+pub const TYPE_SYMLINK: u8 = 7;
+
+/// Länge des festen Teils eines Eintrags.
+const HEADER_SIZE: usize = 8;
+
+/// Längster erlaubter Name in Bytes.
+pub const MAX_NAME_LENGTH: usize = 255;
+
+/// Ein belegter Eintrag eines Verzeichnisblocks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry
+{
+    pub inode: u32,
+    pub name: String,
+    /// Art der Datei laut Eintrag, [TYPE_UNKNOWN] ohne das Merkmal `filetype`.
+    pub kind: u8,
+    /// Position des Eintrags im Block.
+    pub offset: usize,
+}
+
+impl Entry
+{
+    /// Art der Datei für das VFS, falls der Eintrag sie enthält.
+    pub fn file_type(&self) -> Option<FileType>
+    {
+        match self.kind
+        {
+            TYPE_DIRECTORY => Some(FileType::Directory),
+            TYPE_SYMLINK => Some(FileType::Symlink),
+            TYPE_CHAR_DEVICE => Some(FileType::CharDevice),
+            TYPE_UNKNOWN => None,
+            _ => Some(FileType::File),
+        }
+    }
+}
+
+/// Platzbedarf eines Eintrags mit einem Namen der Länge `name_length`.
+fn record_length(name_length: usize) -> usize
+{
+    (HEADER_SIZE + name_length).next_multiple_of(4)
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> usize
+{
+    usize::from(u16::from_le_bytes([bytes[offset], bytes[offset + 1]]))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32
+{
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// Durchläuft die Einträge eines Blocks als `(Offset, rec_len)`. Ungültige
+/// Längen ergeben [VfsError::Corrupt].
+fn records(block: &[u8]) -> VfsResult<Vec<(usize, usize)>>
+{
+    let mut records = Vec::new();
+    let mut offset = 0;
+    while offset < block.len()
+    {
+        if offset + HEADER_SIZE > block.len()
+        {
+            return Err(VfsError::Corrupt);
+        }
+        let length = u16_at(block, offset + 4);
+        if length < HEADER_SIZE || !length.is_multiple_of(4) || offset + length > block.len() || record_length(usize::from(block[offset + 6])) > length
+        {
+            return Err(VfsError::Corrupt);
+        }
+        records.push((offset, length));
+        offset += length;
+    }
+    Ok(records)
+}
+
+/// Alle belegten Einträge eines Verzeichnisblocks, einschließlich `.` und `..`.
+pub fn parse(block: &[u8]) -> VfsResult<Vec<Entry>>
+{
+    Ok(records(block)?
+        .into_iter()
+        .filter(|&(offset, _)| u32_at(block, offset) != 0)
+        .map(|(offset, _)|
+        {
+            let name = &block[offset + HEADER_SIZE..offset + HEADER_SIZE + usize::from(block[offset + 6])];
+            Entry { inode: u32_at(block, offset), name: String::from_utf8_lossy(name).into_owned(), kind: block[offset + 7], offset }
+        })
+        .collect())
+}
+
+/// Schreibt einen Eintrag an `offset` mit der Länge `length`.
+fn write_entry(block: &mut [u8], offset: usize, length: usize, inode: u32, name: &[u8], kind: u8)
+{
+    block[offset..offset + 4].copy_from_slice(&inode.to_le_bytes());
+    block[offset + 4..offset + 6].copy_from_slice(&(length as u16).to_le_bytes());
+    block[offset + 6] = name.len() as u8;
+    block[offset + 7] = kind;
+    block[offset + HEADER_SIZE..offset + HEADER_SIZE + name.len()].copy_from_slice(name);
+}
+
+/// Trägt `name` mit dem Inode `inode` in den Block ein, sofern dort Platz ist.
+/// Ein bestehender Eintrag wird dazu geteilt.
+pub fn insert(block: &mut [u8], name: &str, inode: u32, kind: u8) -> VfsResult<bool>
+{
+    let needed = record_length(name.len());
+    for (offset, length) in records(block)?
+    {
+        let used = if u32_at(block, offset) == 0 { 0 } else { record_length(usize::from(block[offset + 6])) };
+        if length - used < needed
+        {
+            continue;
+        }
+        if used == 0
+        {
+            write_entry(block, offset, length, inode, name.as_bytes(), kind);
+        }
+        else
+        {
+            block[offset + 4..offset + 6].copy_from_slice(&(used as u16).to_le_bytes());
+            write_entry(block, offset + used, length - used, inode, name.as_bytes(), kind);
+        }
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+/// Entfernt den Eintrag an `offset`. Er wird dem vorherigen Eintrag
+/// zugeschlagen oder, als erster des Blocks, als frei markiert.
+pub fn remove(block: &mut [u8], offset: usize) -> VfsResult<()>
+{
+    let records = records(block)?;
+    let index = records.iter().position(|&(start, _)| start == offset).ok_or(VfsError::Corrupt)?;
+    match index
+    {
+        0 => block[offset..offset + 4].fill(0),
+        _ =>
+        {
+            let (previous, previous_length) = records[index - 1];
+            let length = previous_length + records[index].1;
+            block[previous + 4..previous + 6].copy_from_slice(&(length as u16).to_le_bytes());
+        }
+    }
+    Ok(())
+}
+
+/// Ein leerer Verzeichnisblock der Größe `size`.
+pub fn empty_block(size: usize) -> Vec<u8>
+{
+    let mut block = vec![0; size];
+    block[4..6].copy_from_slice(&(size as u16).to_le_bytes());
+    block
+}
+
+/// Erster Block eines neuen Verzeichnisses mit `.` und `..`.
+pub fn new_directory(size: usize, inode: u32, parent: u32, kind: u8) -> Vec<u8>
+{
+    let mut block = vec![0; size];
+    let dot = record_length(1);
+    write_entry(&mut block, 0, dot, inode, b".", kind);
+    write_entry(&mut block, dot, size - dot, parent, b"..", kind);
+    block
+}
+
+/// Gibt an, ob `name` als Name eines Eintrags erlaubt ist.
+pub fn is_valid_name(name: &str) -> bool
+{
+    !name.is_empty() && name.len() <= MAX_NAME_LENGTH && name != "." && name != ".." && !name.contains(['/', '\0'])
+}
+
+#[test_case]
+fn test_insert_and_remove()
+{
+    let mut block = new_directory(1024, 12, 2, TYPE_DIRECTORY);
+    assert!(insert(&mut block, "hello.txt", 13, TYPE_FILE).unwrap());
+    assert!(insert(&mut block, "b", 14, TYPE_FILE).unwrap());
+
+    let entries = parse(&block).unwrap();
+    let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+    assert_eq!(names, [".", "..", "hello.txt", "b"]);
+    assert_eq!(entries[2].offset, 24);
+
+    remove(&mut block, entries[2].offset).unwrap();
+    let entries = parse(&block).unwrap();
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[2].inode, 14);
+    // Der freigewordene Platz wird wieder verwendet.
+    assert!(insert(&mut block, "c", 15, TYPE_FILE).unwrap());
+    assert_eq!(parse(&block).unwrap()[2].name, "c");
+}
+
+#[test_case]
+fn test_full_and_corrupt_blocks()
+{
+    let mut block = empty_block(1024);
+    assert!(parse(&block).unwrap().is_empty());
+    let name = "x".repeat(MAX_NAME_LENGTH);
+    for inode in 1..=3
+    {
+        assert!(insert(&mut block, &name, inode, TYPE_FILE).unwrap());
+    }
+    assert!(!insert(&mut block, &name, 4, TYPE_FILE).unwrap());
+
+    block[4] = 3;
+    assert_eq!(parse(&block), Err(VfsError::Corrupt));
+}
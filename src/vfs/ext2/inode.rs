@@ -0,0 +1,309 @@
+//! Inodes auf der Platte und ihre Blockzuordnung.
+//!
+//! Ein Inode verweist mit 15 Blocknummern auf seine Daten: die ersten 12
+//! direkt, die 13. auf einen Block voller Blocknummern (einfach indirekt), die
+//! 14. zweifach und die 15. dreifach indirekt. Eine Blocknummer 0 ist ein Loch
+//! und wird als Nullen gelesen.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::volume::{Counters, Volume};
+use crate::vfs::{FileType, VfsError, VfsResult};
+
+/// Maske für die Art der Datei in `mode`.
+const MODE_TYPE_MASK: u16 = 0xF000;
+pub const MODE_FILE: u16 = 0x8000;
+pub const MODE_DIRECTORY: u16 = 0x4000;
+pub const MODE_SYMLINK: u16 = 0xA000;
+const MODE_CHAR_DEVICE: u16 = 0x2000;
+
+/// Das Verzeichnis hat einen Hash-Index (`dir_index`).
+pub const FLAG_INDEX: u32 = 0x1000;
+
+/// Anzahl der direkten Blocknummern.
+const DIRECT_BLOCKS: usize = 12;
+
+/// ## RawInode
+///
+/// Die ersten 128 Bytes eines Inodes, wie sie in der Inode-Tabelle stehen.
+/// Felder, die der Treiber nicht auswertet (Zeitstempel, Eigentümer, ...),
+/// bleiben beim Zurückschreiben unverändert.
+#[derive(Clone)]
+pub struct RawInode
+{
+    bytes: [u8; 128],
+    pub mode: u16,
+    pub size: u64,
+    pub links_count: u16,
+    /// Belegter Platz in Einheiten von 512 Bytes, einschließlich der
+    /// Indirektionsblöcke.
+    pub sectors: u32,
+    pub flags: u32,
+    pub block: [u32; 15],
+    /// Block mit erweiterten Attributen, 0 wenn keiner.
+    pub file_acl: u32,
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16
+{
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32
+{
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+impl RawInode
+{
+    /// Ein leerer Inode der Art `mode`.
+    pub fn new(mode: u16) -> RawInode
+    {
+        let mut inode = RawInode::parse(&[0; 128]);
+        inode.mode = mode;
+        inode
+    }
+
+    /// Liest die Felder aus dem Eintrag der Inode-Tabelle.
+    pub fn parse(raw: &[u8]) -> RawInode
+    {
+        let bytes: [u8; 128] = raw[..128].try_into().unwrap();
+        let mode = u16_at(&bytes, 0);
+        // Bei Verzeichnissen steht an Stelle der oberen 32 Bit `i_dir_acl`.
+        let high = if mode & MODE_TYPE_MASK == MODE_FILE { u64::from(u32_at(&bytes, 108)) << 32 } else { 0 };
+        RawInode
+        {
+            mode,
+            size: u64::from(u32_at(&bytes, 4)) | high,
+            links_count: u16_at(&bytes, 26),
+            sectors: u32_at(&bytes, 28),
+            flags: u32_at(&bytes, 32),
+            block: core::array::from_fn(|index| u32_at(&bytes, 40 + 4 * index)),
+            file_acl: u32_at(&bytes, 104),
+            bytes,
+        }
+    }
+
+    /// Schreibt die Felder in den Eintrag `raw` der Inode-Tabelle.
+    pub fn write(&self, raw: &mut [u8])
+    {
+        let raw = &mut raw[..128];
+        raw.copy_from_slice(&self.bytes);
+        raw[0..2].copy_from_slice(&self.mode.to_le_bytes());
+        raw[4..8].copy_from_slice(&(self.size as u32).to_le_bytes());
+        raw[26..28].copy_from_slice(&self.links_count.to_le_bytes());
+        raw[28..32].copy_from_slice(&self.sectors.to_le_bytes());
+        raw[32..36].copy_from_slice(&self.flags.to_le_bytes());
+        for (index, block) in self.block.iter().enumerate()
+        {
+            raw[40 + 4 * index..44 + 4 * index].copy_from_slice(&block.to_le_bytes());
+        }
+        raw[104..108].copy_from_slice(&self.file_acl.to_le_bytes());
+        if self.is(MODE_FILE)
+        {
+            raw[108..112].copy_from_slice(&((self.size >> 32) as u32).to_le_bytes());
+        }
+    }
+
+    /// Gibt an, ob der Inode von der Art `kind` ist, z. B. [MODE_DIRECTORY].
+    pub fn is(&self, kind: u16) -> bool
+    {
+        self.mode & MODE_TYPE_MASK == kind
+    }
+
+    /// Art der Datei für das VFS. Block-Geräte, FIFOs und Sockets erscheinen
+    /// als normale Dateien.
+    pub fn file_type(&self) -> FileType
+    {
+        match self.mode & MODE_TYPE_MASK
+        {
+            MODE_DIRECTORY => FileType::Directory,
+            MODE_SYMLINK => FileType::Symlink,
+            MODE_CHAR_DEVICE => FileType::CharDevice,
+            _ => FileType::File,
+        }
+    }
+
+    /// Zugriffsrechte ohne die Art der Datei.
+    pub fn permissions(&self) -> u16
+    {
+        self.mode & !MODE_TYPE_MASK
+    }
+
+    /// Ziel eines kurzen symbolischen Links, das statt der Blocknummern im Inode
+    /// selbst steht; `None` bei Links mit eigenem Datenblock.
+    pub fn inline_target(&self, block_size: usize) -> Option<Vec<u8>>
+    {
+        let attribute_sectors = if self.file_acl != 0 { (block_size / 512) as u32 } else { 0 };
+        (self.is(MODE_SYMLINK) && self.sectors == attribute_sectors && self.size <= 60)
+            .then(|| self.bytes[40..40 + self.size as usize].to_vec())
+    }
+}
+
+/// ## BlockMap
+///
+/// Übersetzt Blocknummern innerhalb einer Datei in Blocknummern auf dem Gerät
+/// und legt fehlende Daten- und Indirektionsblöcke an.
+pub struct BlockMap<'a>
+{
+    pub volume: &'a Volume,
+    pub counters: &'a mut Counters,
+    /// Bevorzugte Gruppe für neue Blöcke, in der Regel die des Inodes.
+    pub goal: u32,
+}
+
+impl BlockMap<'_>
+{
+    /// Anzahl der Blocknummern in einem Indirektionsblock.
+    fn per_block(&self) -> u64
+    {
+        (self.volume.block_size / 4) as u64
+    }
+
+    /// Sektoren zu 512 Bytes pro Block, für [RawInode::sectors].
+    fn block_sectors(&self) -> u32
+    {
+        (self.volume.block_size / 512) as u32
+    }
+
+    /// Zerlegt den Dateiblock `index` in die Stelle in [RawInode::block] und die
+    /// Positionen in den folgenden Indirektionsblöcken.
+    fn path(&self, index: u64) -> VfsResult<(usize, Vec<usize>)>
+    {
+        let per_block = self.per_block();
+        if index < DIRECT_BLOCKS as u64
+        {
+            return Ok((index as usize, Vec::new()));
+        }
+        let mut rest = index - DIRECT_BLOCKS as u64;
+        let mut span = per_block;
+        for depth in 1..=3
+        {
+            if rest < span
+            {
+                let path = (0..depth).rev().map(|level| ((rest / per_block.pow(level)) % per_block) as usize).collect();
+                return Ok((DIRECT_BLOCKS + depth as usize - 1, path));
+            }
+            rest -= span;
+            span *= per_block;
+        }
+        Err(VfsError::NoSpace)
+    }
+
+    fn read_table(&self, block: u32) -> VfsResult<Vec<u8>>
+    {
+        let mut table = vec![0; self.volume.block_size];
+        self.volume.read_block(block, &mut table)?;
+        Ok(table)
+    }
+
+    /// Blocknummer des Dateiblocks `index` auf dem Gerät, 0 bei einem Loch.
+    pub fn lookup(&self, inode: &RawInode, index: u64) -> VfsResult<u32>
+    {
+        let (slot, path) = self.path(index)?;
+        let mut block = inode.block[slot];
+        for position in path
+        {
+            if block == 0
+            {
+                break;
+            }
+            block = u32_at(&self.read_table(block)?, 4 * position);
+        }
+        Ok(block)
+    }
+
+    /// Wie [BlockMap::lookup], belegt aber fehlende Blöcke. Neue Blöcke sind
+    /// genullt; der Aufrufer muss den Inode anschließend zurückschreiben.
+    pub fn map(&mut self, inode: &mut RawInode, index: u64) -> VfsResult<u32>
+    {
+        let (slot, path) = self.path(index)?;
+        if inode.block[slot] == 0
+        {
+            inode.block[slot] = self.volume.allocate_block(self.counters, self.goal)?;
+            inode.sectors += self.block_sectors();
+        }
+        let mut block = inode.block[slot];
+        for position in path
+        {
+            let mut table = self.read_table(block)?;
+            let mut next = u32_at(&table, 4 * position);
+            if next == 0
+            {
+                next = self.volume.allocate_block(self.counters, self.goal)?;
+                inode.sectors += self.block_sectors();
+                table[4 * position..4 * position + 4].copy_from_slice(&next.to_le_bytes());
+                self.volume.write_block(block, &table)?;
+            }
+            block = next;
+        }
+        Ok(block)
+    }
+
+    /// Gibt alle Dateiblöcke ab dem Index `keep` frei, ebenso
+    /// Indirektionsblöcke, die dadurch leer werden.
+    pub fn truncate(&mut self, inode: &mut RawInode, keep: u64) -> VfsResult<()>
+    {
+        for slot in keep.min(DIRECT_BLOCKS as u64) as usize..DIRECT_BLOCKS
+        {
+            if inode.block[slot] != 0
+            {
+                self.release(inode, inode.block[slot], 0, 0)?;
+                inode.block[slot] = 0;
+            }
+        }
+
+        let mut start = DIRECT_BLOCKS as u64;
+        let mut span = self.per_block();
+        for depth in 1..=3
+        {
+            let slot = DIRECT_BLOCKS + depth as usize - 1;
+            if inode.block[slot] != 0 && keep < start + span && self.release(inode, inode.block[slot], depth, keep.saturating_sub(start))?
+            {
+                inode.block[slot] = 0;
+            }
+            start += span;
+            span *= self.per_block();
+        }
+        Ok(())
+    }
+
+    /// Gibt im Teilbaum `block` der Tiefe `depth` alle Dateiblöcke ab dem
+    /// relativen Index `from` frei. Gibt zurück, ob `block` selbst freigegeben
+    /// wurde.
+    fn release(&mut self, inode: &mut RawInode, block: u32, depth: u32, from: u64) -> VfsResult<bool>
+    {
+        if depth > 0
+        {
+            let span = self.per_block().pow(depth - 1);
+            let mut table = self.read_table(block)?;
+            let mut changed = false;
+            for position in 0..self.per_block() as usize
+            {
+                let child = u32_at(&table, 4 * position);
+                let child_start = position as u64 * span;
+                if child == 0 || child_start + span <= from
+                {
+                    continue;
+                }
+                if self.release(inode, child, depth - 1, from.saturating_sub(child_start))?
+                {
+                    table[4 * position..4 * position + 4].fill(0);
+                    changed = true;
+                }
+            }
+            if table.iter().any(|&byte| byte != 0)
+            {
+                if changed
+                {
+                    self.volume.write_block(block, &table)?;
+                }
+                return Ok(false);
+            }
+        }
+        self.volume.free_block(self.counters, block)?;
+        inode.sectors = inode.sectors.saturating_sub(self.block_sectors());
+        Ok(true)
+    }
+}
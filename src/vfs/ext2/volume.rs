@@ -0,0 +1,465 @@
+//! Aufbau eines ext2-Datenträgers: Superblock, Gruppendeskriptoren, Bitmaps
+//! und Inode-Tabellen.
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::inode::RawInode;
+use crate::block::BlockDevice;
+use crate::vfs::{VfsError, VfsResult};
+
+/// Position und Größe des Superblocks, unabhängig von der Blockgröße.
+const SUPERBLOCK_OFFSET: u64 = 1024;
+const SUPERBLOCK_SIZE: usize = 1024;
+
+/// Kennung im Superblock.
+const MAGIC: u16 = 0xEF53;
+
+/// Größe eines Gruppendeskriptors.
+const DESCRIPTOR_SIZE: usize = 32;
+
+/// Verzeichniseinträge enthalten die Art der Datei.
+pub const INCOMPAT_FILETYPE: u32 = 0x0002;
+/// Sicherungskopien des Superblocks nur in einigen Gruppen.
+const RO_COMPAT_SPARSE_SUPER: u32 = 0x0001;
+/// Dateien dürfen größer als 2 GiB sein.
+pub const RO_COMPAT_LARGE_FILE: u32 = 0x0002;
+/// Nie verwendet, aber von alten Werkzeugen gesetzt.
+const RO_COMPAT_BTREE_DIR: u32 = 0x0004;
+
+/// Feste Lage der Bitmaps und der Inode-Tabelle einer Blockgruppe.
+struct Group
+{
+    block_bitmap: u32,
+    inode_bitmap: u32,
+    inode_table: u32,
+}
+
+/// ## Counters
+///
+/// Der veränderliche Teil der Metadaten: Superblock und Gruppendeskriptoren
+/// mit den Zählern freier Blöcke, freier Inodes und der Verzeichnisse. Liegt im
+/// Zustand des Dateisystems, damit Belegungen serialisiert sind.
+pub struct Counters
+{
+    superblock: Vec<u8>,
+    descriptors: Vec<u8>,
+}
+
+impl Counters
+{
+    fn field(&self, group: u32, offset: usize) -> u16
+    {
+        u16_at(&self.descriptors, group as usize * DESCRIPTOR_SIZE + offset)
+    }
+
+    /// Passt die Zähler der Gruppe `group` und des Superblocks an.
+    fn adjust(&mut self, group: u32, blocks: i32, inodes: i32, directories: i32)
+    {
+        for (offset, delta) in [(12, blocks), (14, inodes), (16, directories)]
+        {
+            let position = group as usize * DESCRIPTOR_SIZE + offset;
+            let value = u16_at(&self.descriptors, position).wrapping_add_signed(delta as i16);
+            self.descriptors[position..position + 2].copy_from_slice(&value.to_le_bytes());
+        }
+        for (offset, delta) in [(12, blocks), (16, inodes)]
+        {
+            let value = u32_at(&self.superblock, offset).wrapping_add_signed(delta);
+            self.superblock[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    /// Anzahl der freien Blöcke laut Superblock.
+    pub fn free_blocks(&self) -> u32
+    {
+        u32_at(&self.superblock, 12)
+    }
+
+    /// Anzahl der freien Inodes laut Superblock.
+    pub fn free_inodes(&self) -> u32
+    {
+        u32_at(&self.superblock, 16)
+    }
+
+    /// Freie Blöcke und freie Inodes der Gruppe `group` laut Deskriptor.
+    pub fn group_free(&self, group: u32) -> (u16, u16)
+    {
+        (self.field(group, 12), self.field(group, 14))
+    }
+}
+
+/// ## Volume
+///
+/// Die unveränderliche Geometrie eines eingehängten ext2-Dateisystems samt
+/// Zugriff auf das Gerät. Blöcke werden ab 0 gezählt, Inodes ab 1.
+pub struct Volume
+{
+    device: Arc<dyn BlockDevice>,
+    pub block_size: usize,
+    sectors_per_block: u64,
+    blocks_count: u32,
+    first_data_block: u32,
+    blocks_per_group: u32,
+    inodes_per_group: u32,
+    inode_size: usize,
+    /// Erster Inode, der nicht für das Dateisystem selbst reserviert ist.
+    first_inode: u32,
+    descriptor_block: u32,
+    groups: Vec<Group>,
+    pub features_incompat: u32,
+    pub features_ro_compat: u32,
+    /// Unbekannte Merkmale verbieten das Schreiben.
+    pub read_only: bool,
+    pub label: String,
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16
+{
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32
+{
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+impl Volume
+{
+    /// Liest Superblock und Gruppendeskriptoren von `device`. Ein fehlerhafter
+    /// Superblock ergibt [VfsError::Corrupt], unbekannte inkompatible Merkmale
+    /// (z. B. Extents oder ein Journal, das wiederhergestellt werden muss)
+    /// [VfsError::Unsupported].
+    pub fn open(device: Arc<dyn BlockDevice>) -> VfsResult<(Volume, Counters)>
+    {
+        let sector_size = device.sector_size();
+        if !SUPERBLOCK_SIZE.is_multiple_of(sector_size)
+        {
+            return Err(VfsError::Unsupported);
+        }
+        let mut superblock = vec![0; SUPERBLOCK_SIZE];
+        device.read_sectors(SUPERBLOCK_OFFSET / sector_size as u64, &mut superblock)?;
+
+        let inodes_count = u32_at(&superblock, 0);
+        let blocks_count = u32_at(&superblock, 4);
+        let first_data_block = u32_at(&superblock, 20);
+        let log_block_size = u32_at(&superblock, 24);
+        let blocks_per_group = u32_at(&superblock, 32);
+        let inodes_per_group = u32_at(&superblock, 40);
+        let revision = u32_at(&superblock, 76);
+        let (first_inode, inode_size) = match revision
+        {
+            0 => (11, 128),
+            _ => (u32_at(&superblock, 84), usize::from(u16_at(&superblock, 88))),
+        };
+        let (features_incompat, features_ro_compat) = match revision
+        {
+            0 => (0, 0),
+            _ => (u32_at(&superblock, 96), u32_at(&superblock, 100)),
+        };
+
+        if u16_at(&superblock, 56) != MAGIC
+            || blocks_per_group == 0
+            || inodes_per_group == 0
+            || first_data_block >= blocks_count
+            || !inode_size.is_power_of_two()
+            || inode_size < 128
+        {
+            return Err(VfsError::Corrupt);
+        }
+        // Größere Blöcke bräuchten eine andere Kodierung von `rec_len`.
+        if features_incompat & !INCOMPAT_FILETYPE != 0 || log_block_size > 2
+        {
+            return Err(VfsError::Unsupported);
+        }
+
+        let block_size = 1024 << log_block_size;
+        let group_count = (blocks_count - first_data_block).div_ceil(blocks_per_group);
+        if u64::from(group_count) * u64::from(inodes_per_group) < u64::from(inodes_count)
+            || u64::from(blocks_count) * block_size as u64 > device.capacity()
+            || inode_size > block_size
+            // Die Belegung einer Gruppe steht in je einem Bitmap-Block.
+            || blocks_per_group as usize > block_size * 8
+            || inodes_per_group as usize > block_size * 8
+        {
+            return Err(VfsError::Corrupt);
+        }
+
+        let mut volume = Volume
+        {
+            device,
+            block_size,
+            sectors_per_block: (block_size / sector_size) as u64,
+            blocks_count,
+            first_data_block,
+            blocks_per_group,
+            inodes_per_group,
+            inode_size,
+            first_inode,
+            descriptor_block: first_data_block + 1,
+            groups: Vec::new(),
+            features_incompat,
+            features_ro_compat,
+            read_only: features_ro_compat & !(RO_COMPAT_SPARSE_SUPER | RO_COMPAT_LARGE_FILE | RO_COMPAT_BTREE_DIR) != 0,
+            label: superblock[120..136].iter().take_while(|&&byte| byte != 0).map(|&byte| char::from(byte)).collect(),
+        };
+
+        let descriptor_blocks = (group_count as usize * DESCRIPTOR_SIZE).div_ceil(block_size);
+        let mut descriptors = vec![0; descriptor_blocks * block_size];
+        for (index, chunk) in descriptors.chunks_exact_mut(block_size).enumerate()
+        {
+            volume.read_block(volume.descriptor_block + index as u32, chunk)?;
+        }
+        for group in 0..group_count as usize
+        {
+            let raw = &descriptors[group * DESCRIPTOR_SIZE..(group + 1) * DESCRIPTOR_SIZE];
+            let group = Group { block_bitmap: u32_at(raw, 0), inode_bitmap: u32_at(raw, 4), inode_table: u32_at(raw, 8) };
+            if [group.block_bitmap, group.inode_bitmap, group.inode_table].iter().any(|&block| block >= blocks_count)
+            {
+                return Err(VfsError::Corrupt);
+            }
+            volume.groups.push(group);
+        }
+        Ok((volume, Counters { superblock, descriptors }))
+    }
+
+    /// Liest den Block `block` nach `buffer` (genau eine Blockgröße).
+    pub fn read_block(&self, block: u32, buffer: &mut [u8]) -> VfsResult<()>
+    {
+        if block >= self.blocks_count
+        {
+            return Err(VfsError::Corrupt);
+        }
+        self.device.read_sectors(u64::from(block) * self.sectors_per_block, buffer)?;
+        Ok(())
+    }
+
+    /// Schreibt `data` (genau eine Blockgröße) in den Block `block`.
+    pub fn write_block(&self, block: u32, data: &[u8]) -> VfsResult<()>
+    {
+        if block >= self.blocks_count
+        {
+            return Err(VfsError::Corrupt);
+        }
+        self.device.write_sectors(u64::from(block) * self.sectors_per_block, data)?;
+        Ok(())
+    }
+
+    /// Gruppe, zu der der Inode `number` gehört.
+    pub fn inode_group(&self, number: u32) -> u32
+    {
+        (number - 1) / self.inodes_per_group
+    }
+
+    /// Block und Offset des Inodes `number` in der Inode-Tabelle.
+    fn inode_location(&self, number: u32) -> VfsResult<(u32, usize)>
+    {
+        let group = self.groups.get(self.inode_group(number) as usize).filter(|_| number != 0).ok_or(VfsError::Corrupt)?;
+        let offset = ((number - 1) % self.inodes_per_group) as usize * self.inode_size;
+        Ok((group.inode_table + (offset / self.block_size) as u32, offset % self.block_size))
+    }
+
+    /// Liest den Inode `number`.
+    pub fn read_inode(&self, number: u32) -> VfsResult<RawInode>
+    {
+        let (block, offset) = self.inode_location(number)?;
+        let mut buffer = vec![0; self.block_size];
+        self.read_block(block, &mut buffer)?;
+        Ok(RawInode::parse(&buffer[offset..offset + self.inode_size]))
+    }
+
+    /// Schreibt die ersten 128 Bytes des Inodes `number`; erweiterte Felder
+    /// größerer Inodes bleiben erhalten.
+    pub fn write_inode(&self, number: u32, inode: &RawInode) -> VfsResult<()>
+    {
+        if self.read_only
+        {
+            return Err(VfsError::ReadOnly);
+        }
+        let (block, offset) = self.inode_location(number)?;
+        let mut buffer = vec![0; self.block_size];
+        self.read_block(block, &mut buffer)?;
+        inode.write(&mut buffer[offset..offset + self.inode_size]);
+        self.write_block(block, &buffer)
+    }
+
+    /// Schreibt den Deskriptor der Gruppe `group` und den Superblock.
+    fn write_counters(&self, counters: &Counters, group: u32) -> VfsResult<()>
+    {
+        let index = group as usize * DESCRIPTOR_SIZE / self.block_size;
+        let start = index * self.block_size;
+        self.write_block(self.descriptor_block + index as u32, &counters.descriptors[start..start + self.block_size])?;
+        let sector_size = self.device.sector_size() as u64;
+        self.device.write_sectors(SUPERBLOCK_OFFSET / sector_size, &counters.superblock)?;
+        Ok(())
+    }
+
+    /// Sucht im Bitmap-Block `bitmap` ein freies Bit unter `limit`, setzt es und
+    /// gibt seinen Index zurück.
+    fn claim_bit(&self, bitmap: u32, limit: u32, minimum: u32) -> VfsResult<Option<u32>>
+    {
+        let mut buffer = vec![0; self.block_size];
+        self.read_block(bitmap, &mut buffer)?;
+        let Some(bit) = (minimum..limit).find(|&bit| buffer[bit as usize / 8] & (1 << (bit % 8)) == 0)
+        else
+        {
+            return Ok(None);
+        };
+        buffer[bit as usize / 8] |= 1 << (bit % 8);
+        self.write_block(bitmap, &buffer)?;
+        Ok(Some(bit))
+    }
+
+    /// Löscht das Bit `bit` im Bitmap-Block `bitmap`. Ein bereits freies Bit
+    /// ergibt [VfsError::Corrupt].
+    fn release_bit(&self, bitmap: u32, bit: u32) -> VfsResult<()>
+    {
+        let mut buffer = vec![0; self.block_size];
+        self.read_block(bitmap, &mut buffer)?;
+        let mask = 1 << (bit % 8);
+        if buffer[bit as usize / 8] & mask == 0
+        {
+            return Err(VfsError::Corrupt);
+        }
+        buffer[bit as usize / 8] &= !mask;
+        self.write_block(bitmap, &buffer)
+    }
+
+    /// Gruppen beginnend bei `goal`, danach reihum alle übrigen.
+    fn groups_from(&self, goal: u32) -> impl Iterator<Item = u32>
+    {
+        let count = self.groups.len() as u32;
+        (0..count).map(move |index| (goal % count + index) % count)
+    }
+
+    /// Belegt einen freien Block, bevorzugt in der Gruppe `goal`, und füllt ihn
+    /// mit Nullen.
+    pub fn allocate_block(&self, counters: &mut Counters, goal: u32) -> VfsResult<u32>
+    {
+        if self.read_only
+        {
+            return Err(VfsError::ReadOnly);
+        }
+        for group in self.groups_from(goal)
+        {
+            if counters.field(group, 12) == 0
+            {
+                continue;
+            }
+            let start = self.first_data_block + group * self.blocks_per_group;
+            let limit = self.blocks_per_group.min(self.blocks_count - start);
+            if let Some(bit) = self.claim_bit(self.groups[group as usize].block_bitmap, limit, 0)?
+            {
+                counters.adjust(group, -1, 0, 0);
+                self.write_counters(counters, group)?;
+                let block = start + bit;
+                self.write_block(block, &vec![0; self.block_size])?;
+                return Ok(block);
+            }
+        }
+        Err(VfsError::NoSpace)
+    }
+
+    /// Gibt den Block `block` frei.
+    pub fn free_block(&self, counters: &mut Counters, block: u32) -> VfsResult<()>
+    {
+        if block < self.first_data_block || block >= self.blocks_count
+        {
+            return Err(VfsError::Corrupt);
+        }
+        let group = (block - self.first_data_block) / self.blocks_per_group;
+        self.release_bit(self.groups[group as usize].block_bitmap, (block - self.first_data_block) % self.blocks_per_group)?;
+        counters.adjust(group, 1, 0, 0);
+        self.write_counters(counters, group)
+    }
+
+    /// Belegt einen freien Inode, bevorzugt in der Gruppe `goal`. Der Inode wird
+    /// auf der Platte vollständig genullt.
+    pub fn allocate_inode(&self, counters: &mut Counters, goal: u32, directory: bool) -> VfsResult<u32>
+    {
+        if self.read_only
+        {
+            return Err(VfsError::ReadOnly);
+        }
+        for group in self.groups_from(goal)
+        {
+            if counters.field(group, 14) == 0
+            {
+                continue;
+            }
+            // Die reservierten Inodes liegen alle in Gruppe 0.
+            let minimum = if group == 0 { self.first_inode - 1 } else { 0 };
+            if let Some(bit) = self.claim_bit(self.groups[group as usize].inode_bitmap, self.inodes_per_group, minimum)?
+            {
+                counters.adjust(group, 0, -1, i32::from(directory));
+                self.write_counters(counters, group)?;
+                let number = group * self.inodes_per_group + bit + 1;
+                self.clear_inode(number)?;
+                return Ok(number);
+            }
+        }
+        Err(VfsError::NoSpace)
+    }
+
+    /// Gibt den Inode `number` frei und nullt ihn.
+    pub fn free_inode(&self, counters: &mut Counters, number: u32, directory: bool) -> VfsResult<()>
+    {
+        let group = self.inode_group(number);
+        self.release_bit(self.groups.get(group as usize).ok_or(VfsError::Corrupt)?.inode_bitmap, (number - 1) % self.inodes_per_group)?;
+        self.clear_inode(number)?;
+        counters.adjust(group, 0, 1, -i32::from(directory));
+        self.write_counters(counters, group)
+    }
+
+    /// Nullt den ganzen Eintrag des Inodes `number` in der Inode-Tabelle.
+    fn clear_inode(&self, number: u32) -> VfsResult<()>
+    {
+        let (block, offset) = self.inode_location(number)?;
+        let mut buffer = vec![0; self.block_size];
+        self.read_block(block, &mut buffer)?;
+        buffer[offset..offset + self.inode_size].fill(0);
+        self.write_block(block, &buffer)
+    }
+
+    /// Anzahl der Blockgruppen.
+    pub fn group_count(&self) -> u32
+    {
+        self.groups.len() as u32
+    }
+
+    /// Schreibt zwischengespeicherte Daten des Geräts auf die Platte.
+    pub fn flush(&self) -> VfsResult<()>
+    {
+        self.device.flush()?;
+        Ok(())
+    }
+}
+
+/// Kleines Gerät mit einem Superblock (1-KiB-Blöcke, eine Gruppe) mit
+/// `blocks_per_group` Blöcken und `inodes_per_group` Inodes pro Gruppe.
+#[cfg(test)]
+fn superblock_device(blocks_per_group: u32, inodes_per_group: u32) -> Arc<dyn BlockDevice>
+{
+    let mut superblock = vec![0; SUPERBLOCK_SIZE];
+    superblock[0..4].copy_from_slice(&inodes_per_group.to_le_bytes());
+    superblock[4..8].copy_from_slice(&64u32.to_le_bytes());
+    superblock[20..24].copy_from_slice(&1u32.to_le_bytes());
+    superblock[32..36].copy_from_slice(&blocks_per_group.to_le_bytes());
+    superblock[40..44].copy_from_slice(&inodes_per_group.to_le_bytes());
+    superblock[56..58].copy_from_slice(&MAGIC.to_le_bytes());
+
+    let device = Arc::new(crate::block::RamDisk::new(128));
+    device.write_sectors(SUPERBLOCK_OFFSET / 512, &superblock).unwrap();
+    device
+}
+
+#[test_case]
+fn test_group_larger_than_bitmap()
+{
+    // Eine Gruppe darf nicht mehr Blöcke oder Inodes haben, als ihr
+    // Bitmap-Block (8 Bits pro Byte) erfasst.
+    assert!(Volume::open(superblock_device(8 * 1024, 8 * 1024)).is_ok());
+    assert_eq!(Volume::open(superblock_device(8 * 1024 + 1, 16)).err(), Some(VfsError::Corrupt));
+    assert_eq!(Volume::open(superblock_device(8 * 1024, 8 * 1024 + 1)).err(), Some(VfsError::Corrupt));
+}
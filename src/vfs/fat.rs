@@ -0,0 +1,588 @@
+//! Treiber für FAT12, FAT16 und FAT32 mit langen Dateinamen (VFAT).
+//!
+//! Ein FAT-Datenträger besteht aus vier Bereichen:
+//!
+//! ```text
+//! | Bootsektor, reserviert | FAT 1 | FAT 2 | Wurzelverzeichnis (FAT12/16) | Cluster 2, 3, ... |
+//! ```
+//!
+//! Die **Dateizuordnungstabelle (FAT)** enthält für jeden Cluster den
+//! nächsten Cluster derselben Datei; eine Datei ist also eine verkettete Liste
+//! von Clustern, deren Anfang im Verzeichniseintrag steht. Ob 12, 16 oder 32
+//! Bit pro Eintrag verwendet werden, ergibt sich allein aus der Anzahl der
+//! Cluster (siehe [volume]).
+//!
+//! Verzeichnisse sind Dateien aus 32-Byte-Einträgen (siehe [dir]). Bei FAT12
+//! und FAT16 liegt das Wurzelverzeichnis in einem festen Bereich und kann
+//! nicht wachsen.
+//!
+//! # Inodes
+//!
+//! FAT kennt keine Inodes. Als Inode-Nummer dient die Position des kurzen
+//! Verzeichniseintrags auf dem Gerät; die Wurzel hat die Nummer 1. Damit zwei
+//! Zugriffe auf dieselbe Datei dieselbe Größe sehen, hält [FatFs] eine Tabelle
+//! aller lebenden Inodes.
+
+mod dir;
+mod volume;
+
+pub use volume::FatType;
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::{DirEntry, FileSystem, FileType, Inode, Metadata, VfsError, VfsResult};
+use crate::block::{BlockDevice, CachedDevice};
+use crate::sync::{Mutex, MutexGuard};
+use dir::{ATTR_ARCHIVE, ATTR_DIRECTORY, ENTRY_SIZE};
+use volume::{DirRegion, Volume};
+
+/// Inode-Nummer des Wurzelverzeichnisses.
+const ROOT_INODE: u64 = 1;
+
+/// Veränderlicher Zustand des Dateisystems.
+struct State
+{
+    /// Ab hier wird nach freien Clustern gesucht.
+    next_free: u32,
+    /// Alle lebenden Inodes nach Nummer.
+    inodes: BTreeMap<u64, Weak<FatInode>>,
+}
+
+/// Gemeinsame Daten aller Inodes eines Dateisystems.
+struct Shared
+{
+    volume: Volume,
+    /// Serialisiert alle Zugriffe auf das Dateisystem.
+    state: Mutex<State>,
+}
+
+/// ## FatFs
+///
+/// Ein FAT-Dateisystem auf einem [BlockDevice], lesend und schreibend.
+pub struct FatFs
+{
+    shared: Arc<Shared>,
+    root: Arc<FatInode>,
+}
+
+impl FatFs
+{
+    /// Liest den Bootsektor von `device` und bereitet das Dateisystem vor.
+    /// Zugriffe laufen über den Puffercache ([CachedDevice]). Ein ungültiger
+    /// Bootsektor ergibt [VfsError::Corrupt].
+    pub fn new(device: Arc<dyn BlockDevice>) -> VfsResult<FatFs>
+    {
+        let device: Arc<dyn BlockDevice> = Arc::new(CachedDevice::new(device));
+        let volume = Volume::open(device)?;
+        let root_cluster = volume.root_cluster;
+        let shared = Arc::new(Shared { volume, state: Mutex::new(State { next_free: 2, inodes: BTreeMap::new() }) });
+        let root = Arc::new(FatInode
+        {
+            shared: shared.clone(),
+            inode: ROOT_INODE,
+            entry_offset: None,
+            file_type: FileType::Directory,
+            node: Mutex::new(Node { first_cluster: root_cluster, size: 0, unlinked: false }),
+        });
+        Ok(FatFs { shared, root })
+    }
+
+    /// Variante des Dateisystems.
+    pub fn fat_type(&self) -> FatType
+    {
+        self.shared.volume.fat_type
+    }
+
+    /// Clustergröße in Bytes.
+    pub fn cluster_size(&self) -> usize
+    {
+        self.shared.volume.cluster_size
+    }
+
+    /// Bezeichnung des Datenträgers aus dem Wurzelverzeichnis, falls vorhanden.
+    pub fn label(&self) -> VfsResult<Option<String>>
+    {
+        let (_state, node) = self.root.lock();
+        let directory = self.root.directory(&node)?;
+        Ok(directory
+            .data
+            .chunks_exact(ENTRY_SIZE)
+            .take_while(|raw| raw[0] != 0)
+            .find(|raw| raw[0] != dir::DELETED && raw[11] & 0x3F == dir::ATTR_VOLUME_ID)
+            .map(|raw| String::from(raw[..11].iter().map(|&byte| char::from(byte)).collect::<String>().trim_end())))
+    }
+
+    /// Anzahl der freien Cluster.
+    pub fn free_clusters(&self) -> VfsResult<u32>
+    {
+        let _state = self.shared.state.lock();
+        self.shared.volume.free_clusters()
+    }
+}
+
+impl FileSystem for FatFs
+{
+    fn name(&self) -> &str
+    {
+        "fat"
+    }
+
+    fn root(&self) -> Arc<dyn Inode>
+    {
+        self.root.clone()
+    }
+
+    fn sync(&self) -> VfsResult<()>
+    {
+        let state = self.shared.state.lock();
+        self.shared.volume.sync(state.next_free)
+    }
+}
+
+/// Lage der Daten eines Inodes, wie sie im Verzeichniseintrag steht.
+struct Node
+{
+    first_cluster: u32,
+    size: u32,
+    /// Der Verzeichniseintrag wurde gelöscht, der Inode ist aber noch geöffnet.
+    /// `entry_offset` zeigt dann auf einen freien Eintrag, der nicht mehr
+    /// beschrieben werden darf.
+    unlinked: bool,
+}
+
+/// Datei oder Verzeichnis in einem [FatFs].
+struct FatInode
+{
+    shared: Arc<Shared>,
+    inode: u64,
+    /// Position des kurzen Verzeichniseintrags auf dem Gerät; `None` für die Wurzel.
+    entry_offset: Option<u64>,
+    file_type: FileType,
+    node: Mutex<Node>,
+}
+
+/// Inhalt eines Verzeichnisses samt Positionen seiner Abschnitte.
+struct Directory
+{
+    region: DirRegion,
+    data: Vec<u8>,
+    offsets: Vec<u64>,
+    chunk_size: usize,
+}
+
+impl Directory
+{
+    /// Geräte-Offset des Eintrags an Position `position` im Verzeichnis.
+    fn offset(&self, position: usize) -> u64
+    {
+        self.offsets[position / self.chunk_size] + (position % self.chunk_size) as u64
+    }
+}
+
+impl FatInode
+{
+    fn volume(&self) -> &Volume
+    {
+        &self.shared.volume
+    }
+
+    fn lock(&self) -> (MutexGuard<'_, State>, MutexGuard<'_, Node>)
+    {
+        let state = self.shared.state.lock();
+        (state, self.node.lock())
+    }
+
+    /// Prüft, ob der Verzeichniseintrag dieses Inodes noch existiert. Gelöschte,
+    /// aber noch geöffnete Inodes ergeben [VfsError::NotFound].
+    fn check_linked(node: &Node) -> VfsResult<()>
+    {
+        if node.unlinked
+        {
+            return Err(VfsError::NotFound);
+        }
+        Ok(())
+    }
+
+    /// Liest dieses Verzeichnis ein.
+    fn directory(&self, node: &Node) -> VfsResult<Directory>
+    {
+        if self.file_type != FileType::Directory
+        {
+            return Err(VfsError::NotADirectory);
+        }
+        FatInode::check_linked(node)?;
+        let region = if self.entry_offset.is_none() { self.volume().root_region() } else { DirRegion::Chain(node.first_cluster) };
+        let (data, offsets) = self.volume().read_directory(region)?;
+        Ok(Directory { region, data, offsets, chunk_size: self.volume().directory_chunk_size(region) })
+    }
+
+    /// Gibt den Inode zu `entry` zurück, aus der Tabelle oder neu angelegt.
+    fn child(&self, state: &mut State, directory: &Directory, entry: &dir::Entry) -> Arc<FatInode>
+    {
+        let offset = directory.offset(entry.position);
+        if let Some(inode) = state.inodes.get(&offset).and_then(Weak::upgrade)
+        {
+            return inode;
+        }
+
+        let inode = Arc::new(FatInode
+        {
+            shared: self.shared.clone(),
+            inode: offset,
+            entry_offset: Some(offset),
+            file_type: if entry.is_directory() { FileType::Directory } else { FileType::File },
+            node: Mutex::new(Node { first_cluster: entry.first_cluster, size: entry.size, unlinked: false }),
+        });
+        state.inodes.retain(|_, inode| inode.strong_count() > 0);
+        state.inodes.insert(offset, Arc::downgrade(&inode));
+        inode
+    }
+
+    /// Schreibt ersten Cluster und Größe in den Verzeichniseintrag.
+    fn update_entry(&self, node: &Node) -> VfsResult<()>
+    {
+        let Some(offset) = self.entry_offset
+        else
+        {
+            return Ok(());
+        };
+        let mut entry = [0; ENTRY_SIZE];
+        self.volume().read_bytes(offset, &mut entry)?;
+        let size = if self.file_type == FileType::Directory { 0 } else { node.size };
+        dir::set_location(&mut entry, node.first_cluster, size);
+        self.volume().write_bytes(offset, &entry)
+    }
+
+    /// Hängt Cluster an, bis die Kette `clusters` Cluster lang ist.
+    fn grow(&self, state: &mut State, node: &mut Node, chain: &mut Vec<u32>, clusters: usize) -> VfsResult<()>
+    {
+        while chain.len() < clusters
+        {
+            let cluster = self.volume().allocate(state.next_free, chain.last().copied())?;
+            state.next_free = cluster + 1;
+            if chain.is_empty()
+            {
+                node.first_cluster = cluster;
+            }
+            chain.push(cluster);
+        }
+        Ok(())
+    }
+
+    /// Schreibt `data` ab `offset` in die Datei und vergrößert sie bei Bedarf.
+    fn write_locked(&self, state: &mut State, node: &mut Node, offset: u64, data: &[u8]) -> VfsResult<usize>
+    {
+        let cluster_size = self.volume().cluster_size as u64;
+        let end = offset.checked_add(data.len() as u64).filter(|&end| end <= u64::from(u32::MAX)).ok_or(VfsError::NoSpace)?;
+
+        let mut chain = self.volume().chain(node.first_cluster)?;
+        let allocated = chain.len() as u64 * cluster_size;
+        self.grow(state, node, &mut chain, end.div_ceil(cluster_size) as usize)?;
+
+        // Zwischen altem Ende und `offset` müssen Nullen gelesen werden; neu
+        // belegte Cluster sind bereits genullt.
+        let gap = u64::from(node.size)..offset.min(allocated);
+        if !gap.is_empty()
+        {
+            self.transfer(&chain, gap.start, Transfer::Write(&vec![0; (gap.end - gap.start) as usize]))?;
+        }
+        self.transfer(&chain, offset, Transfer::Write(data))?;
+
+        node.size = node.size.max(end as u32);
+        self.update_entry(node)?;
+        Ok(data.len())
+    }
+
+    /// Liest oder schreibt ab dem Datei-Offset `offset` über die Cluster der Kette.
+    fn transfer(&self, chain: &[u32], offset: u64, mut transfer: Transfer<'_>) -> VfsResult<()>
+    {
+        let cluster_size = self.volume().cluster_size as u64;
+        let length = transfer.len();
+        let mut done = 0;
+        while done < length
+        {
+            let position = offset + done as u64;
+            let cluster = *chain.get((position / cluster_size) as usize).ok_or(VfsError::Corrupt)?;
+            let within = position % cluster_size;
+            let count = (length - done).min((cluster_size - within) as usize);
+            let device_offset = self.volume().cluster_offset(cluster) + within;
+            match &mut transfer
+            {
+                Transfer::Read(buffer) => self.volume().read_bytes(device_offset, &mut buffer[done..done + count])?,
+                Transfer::Write(data) => self.volume().write_bytes(device_offset, &data[done..done + count])?,
+            }
+            done += count;
+        }
+        Ok(())
+    }
+
+    /// Trägt den Namen `name` mit dem kurzen Eintrag `entry` in dieses
+    /// Verzeichnis ein und gibt die Position des kurzen Eintrags zurück.
+    fn add_entry(&self, state: &mut State, node: &mut Node, directory: &mut Directory, name: &str, attributes: u8, first_cluster: u32) -> VfsResult<usize>
+    {
+        let entries = dir::parse(&directory.data);
+        let (short_name, flags, long_entries) = match dir::exact_short_name(name)
+        {
+            Some((short_name, flags)) => (short_name, flags, Vec::new()),
+            None =>
+            {
+                let short_name = dir::generate_short_name(name, |candidate| entries.iter().any(|entry| &entry.short_name == candidate))
+                    .ok_or(VfsError::AlreadyExists)?;
+                (short_name, 0, dir::long_name_entries(name, &short_name))
+            }
+        };
+
+        let count = long_entries.len() + 1;
+        let position = match dir::find_free(&directory.data, count)
+        {
+            Some(position) => position,
+            None =>
+            {
+                // Das feste Wurzelverzeichnis kann nicht wachsen.
+                let DirRegion::Chain(_) = directory.region
+                else
+                {
+                    return Err(VfsError::NoSpace);
+                };
+                let mut chain = self.volume().chain(node.first_cluster)?;
+                let clusters = chain.len() + (count * ENTRY_SIZE).div_ceil(directory.chunk_size);
+                let old = chain.len();
+                self.grow(state, node, &mut chain, clusters)?;
+                for &cluster in &chain[old..]
+                {
+                    directory.offsets.push(self.volume().cluster_offset(cluster));
+                    directory.data.resize(directory.data.len() + directory.chunk_size, 0);
+                }
+                dir::find_free(&directory.data, count).ok_or(VfsError::NoSpace)?
+            }
+        };
+
+        let short = dir::short_entry(&short_name, flags, attributes, first_cluster, 0);
+        for (index, raw) in long_entries.iter().chain(core::iter::once(&short)).enumerate()
+        {
+            let slot = position + index * ENTRY_SIZE;
+            directory.data[slot..slot + ENTRY_SIZE].copy_from_slice(raw);
+            self.volume().write_bytes(directory.offset(slot), raw)?;
+        }
+        Ok(position + long_entries.len() * ENTRY_SIZE)
+    }
+
+    /// Legt einen Cluster für ein neues Verzeichnis mit `.` und `..` an.
+    fn new_directory_cluster(&self, state: &mut State, parent_cluster: u32) -> VfsResult<u32>
+    {
+        let cluster = self.volume().allocate(state.next_free, None)?;
+        state.next_free = cluster + 1;
+        let mut entries = [0; 2 * ENTRY_SIZE];
+        entries[..ENTRY_SIZE].copy_from_slice(&dir::short_entry(b".          ", 0, ATTR_DIRECTORY, cluster, 0));
+        entries[ENTRY_SIZE..].copy_from_slice(&dir::short_entry(b"..         ", 0, ATTR_DIRECTORY, parent_cluster, 0));
+        self.volume().write_bytes(self.volume().cluster_offset(cluster), &entries)?;
+        Ok(cluster)
+    }
+}
+
+/// Richtung für [FatInode::transfer].
+enum Transfer<'a>
+{
+    Read(&'a mut [u8]),
+    Write(&'a [u8]),
+}
+
+impl Transfer<'_>
+{
+    fn len(&self) -> usize
+    {
+        match self
+        {
+            Transfer::Read(buffer) => buffer.len(),
+            Transfer::Write(data) => data.len(),
+        }
+    }
+}
+
+impl Inode for FatInode
+{
+    fn metadata(&self) -> Metadata
+    {
+        let (_state, node) = self.lock();
+        let size = match self.file_type
+        {
+            FileType::Directory => self.directory(&node).map_or(0, |directory| dir::parse(&directory.data).len() as u64),
+            _ => u64::from(node.size),
+        };
+        Metadata { inode: self.inode, file_type: self.file_type, size, mode: self.file_type.default_mode() }
+    }
+
+    fn read_at(&self, offset: u64, buffer: &mut [u8]) -> VfsResult<usize>
+    {
+        if self.file_type == FileType::Directory
+        {
+            return Err(VfsError::IsADirectory);
+        }
+        let (_state, node) = self.lock();
+        let size = u64::from(node.size);
+        if offset >= size
+        {
+            return Ok(0);
+        }
+        let count = buffer.len().min((size - offset) as usize);
+        let chain = self.volume().chain(node.first_cluster)?;
+        self.transfer(&chain, offset, Transfer::Read(&mut buffer[..count]))?;
+        Ok(count)
+    }
+
+    fn write_at(&self, offset: u64, buffer: &[u8]) -> VfsResult<usize>
+    {
+        if self.file_type == FileType::Directory
+        {
+            return Err(VfsError::IsADirectory);
+        }
+        let (mut state, mut node) = self.lock();
+        FatInode::check_linked(&node)?;
+        self.write_locked(&mut state, &mut node, offset, buffer)
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult<()>
+    {
+        if self.file_type == FileType::Directory
+        {
+            return Err(VfsError::IsADirectory);
+        }
+        let size = u32::try_from(size).map_err(|_| VfsError::NoSpace)?;
+        let (mut state, mut node) = self.lock();
+        FatInode::check_linked(&node)?;
+        if size >= node.size
+        {
+            let zeros = vec![0; (size - node.size) as usize];
+            let end = u64::from(node.size);
+            self.write_locked(&mut state, &mut node, end, &zeros)?;
+            return Ok(());
+        }
+
+        let chain = self.volume().chain(node.first_cluster)?;
+        let keep = (size as usize).div_ceil(self.volume().cluster_size);
+        if keep == 0
+        {
+            if let Some(&first) = chain.first()
+            {
+                self.volume().free_chain(first)?;
+            }
+            node.first_cluster = 0;
+        }
+        else if let Some(&next) = chain.get(keep)
+        {
+            self.volume().set_entry(chain[keep - 1], volume::FatEntry::End)?;
+            self.volume().free_chain(next)?;
+        }
+        node.size = size;
+        self.update_entry(&node)
+    }
+
+    fn lookup(&self, name: &str) -> VfsResult<Arc<dyn Inode>>
+    {
+        let (mut state, node) = self.lock();
+        let directory = self.directory(&node)?;
+        let entry = dir::parse(&directory.data).into_iter().find(|entry| entry.matches(name)).ok_or(VfsError::NotFound)?;
+        Ok(self.child(&mut state, &directory, &entry))
+    }
+
+    fn create(&self, name: &str, file_type: FileType) -> VfsResult<Arc<dyn Inode>>
+    {
+        if !dir::is_valid_name(name)
+        {
+            return Err(VfsError::InvalidPath);
+        }
+        let attributes = match file_type
+        {
+            FileType::File => ATTR_ARCHIVE,
+            FileType::Directory => ATTR_DIRECTORY,
+            _ => return Err(VfsError::Unsupported),
+        };
+
+        let (mut state, mut node) = self.lock();
+        let mut directory = self.directory(&node)?;
+        if dir::parse(&directory.data).iter().any(|entry| entry.matches(name))
+        {
+            return Err(VfsError::AlreadyExists);
+        }
+
+        let first_cluster = if file_type == FileType::Directory
+        {
+            // `..` zeigt bei Unterverzeichnissen der Wurzel auf Cluster 0.
+            let parent = if self.entry_offset.is_none() { 0 } else { node.first_cluster };
+            self.new_directory_cluster(&mut state, parent)?
+        }
+        else
+        {
+            0
+        };
+
+        let position = match self.add_entry(&mut state, &mut node, &mut directory, name, attributes, first_cluster)
+        {
+            Ok(position) => position,
+            Err(error) =>
+            {
+                if first_cluster != 0
+                {
+                    self.volume().free_chain(first_cluster)?;
+                }
+                return Err(error);
+            }
+        };
+
+        let entry = dir::parse(&directory.data).into_iter().find(|entry| entry.position == position).ok_or(VfsError::Corrupt)?;
+        Ok(self.child(&mut state, &directory, &entry))
+    }
+
+    fn unlink(&self, name: &str) -> VfsResult<()>
+    {
+        let (mut state, node) = self.lock();
+        let directory = self.directory(&node)?;
+        let entry = dir::parse(&directory.data).into_iter().find(|entry| entry.matches(name)).ok_or(VfsError::NotFound)?;
+
+        if entry.is_directory()
+        {
+            let (data, _) = self.volume().read_directory(DirRegion::Chain(entry.first_cluster))?;
+            if !dir::parse(&data).is_empty()
+            {
+                return Err(VfsError::NotEmpty);
+            }
+        }
+
+        for position in (entry.first_position..=entry.position).step_by(ENTRY_SIZE)
+        {
+            self.volume().write_bytes(directory.offset(position), &[dir::DELETED])?;
+        }
+        if entry.first_cluster != 0
+        {
+            self.volume().free_chain(entry.first_cluster)?;
+        }
+
+        let offset = directory.offset(entry.position);
+        if let Some(inode) = state.inodes.remove(&offset).and_then(|inode| inode.upgrade())
+        {
+            // Noch geöffnete Inodes zeigen danach auf eine leere Datei, die
+            // sich nicht mehr ändern lässt.
+            *inode.node.lock() = Node { first_cluster: 0, size: 0, unlinked: true };
+        }
+        Ok(())
+    }
+
+    fn readdir(&self) -> VfsResult<Vec<DirEntry>>
+    {
+        let (_state, node) = self.lock();
+        let directory = self.directory(&node)?;
+        Ok(dir::parse(&directory.data)
+            .into_iter()
+            .map(|entry| DirEntry
+            {
+                inode: directory.offset(entry.position),
+                file_type: if entry.is_directory() { FileType::Directory } else { FileType::File },
+                name: entry.name,
+            })
+            .collect())
+    }
+}
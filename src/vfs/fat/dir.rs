@@ -0,0 +1,406 @@
+//! Verzeichniseinträge: kurze 8.3-Namen und lange Dateinamen (VFAT).
+//!
+//! Jeder Eintrag ist 32 Bytes groß. Ein langer Name wird in Stücken zu 13
+//! UTF-16-Zeichen in eigenen Einträgen (Attribut `0x0F`) direkt **vor** dem
+//! kurzen Eintrag abgelegt, das letzte Stück zuerst. Jedes Stück trägt eine
+//! Prüfsumme über den kurzen Namen, damit verwaiste Stücke erkannt werden.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Größe eines Verzeichniseintrags.
+pub const ENTRY_SIZE: usize = 32;
+
+/// Erstes Namensbyte eines gelöschten Eintrags.
+pub const DELETED: u8 = 0xE5;
+
+pub const ATTR_READ_ONLY: u8 = 0x01;
+pub const ATTR_HIDDEN: u8 = 0x02;
+pub const ATTR_SYSTEM: u8 = 0x04;
+pub const ATTR_VOLUME_ID: u8 = 0x08;
+pub const ATTR_DIRECTORY: u8 = 0x10;
+pub const ATTR_ARCHIVE: u8 = 0x20;
+pub const ATTR_LONG_NAME: u8 = ATTR_READ_ONLY | ATTR_HIDDEN | ATTR_SYSTEM | ATTR_VOLUME_ID;
+
+/// Bits im Byte 12, mit denen Windows NT Namen in Kleinbuchstaben markiert.
+const LOWERCASE_BASE: u8 = 0x08;
+const LOWERCASE_EXTENSION: u8 = 0x10;
+
+/// Zeichen pro Eintrag eines langen Namens und ihre Positionen im Eintrag.
+const LONG_NAME_CHARS: usize = 13;
+const LONG_NAME_OFFSETS: [usize; LONG_NAME_CHARS] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+
+/// Längster erlaubter Name in UTF-16-Zeichen.
+pub const MAX_NAME_LENGTH: usize = 255;
+
+/// Datum 1980-01-01, da es noch keine Uhrzeit gibt.
+const DEFAULT_DATE: u16 = (1 << 5) | 1;
+
+/// Ein gültiger Eintrag eines Verzeichnisses.
+#[derive(Debug, Clone)]
+pub struct Entry
+{
+    /// Langer Name, sonst der kurze Name in Anzeigeform.
+    pub name: String,
+    pub short_name: [u8; 11],
+    pub attributes: u8,
+    pub first_cluster: u32,
+    pub size: u32,
+    /// Position des kurzen Eintrags im Verzeichnis in Bytes.
+    pub position: usize,
+    /// Position des ersten zugehörigen Eintrags (langer Name oder kurzer Eintrag).
+    pub first_position: usize,
+}
+
+impl Entry
+{
+    pub fn is_directory(&self) -> bool
+    {
+        self.attributes & ATTR_DIRECTORY != 0
+    }
+
+    /// Vergleicht `name` ohne Beachtung der Groß- und Kleinschreibung mit dem
+    /// langen und dem kurzen Namen.
+    pub fn matches(&self, name: &str) -> bool
+    {
+        self.name.eq_ignore_ascii_case(name) || decode_short_name(&self.short_name, 0).eq_ignore_ascii_case(name)
+    }
+}
+
+/// Prüfsumme über einen kurzen Namen, wie sie in den Einträgen des langen
+/// Namens steht.
+pub fn checksum(short_name: &[u8; 11]) -> u8
+{
+    short_name.iter().fold(0u8, |sum, &byte| sum.rotate_right(1).wrapping_add(byte))
+}
+
+/// Wandelt einen kurzen Namen wie `README  TXT` in `README.TXT` um und
+/// beachtet dabei die Kleinschreibungs-Bits aus Byte 12.
+pub fn decode_short_name(short_name: &[u8; 11], flags: u8) -> String
+{
+    let convert = |bytes: &[u8], lowercase: bool| -> String
+    {
+        let mut part: String = bytes.iter().map(|&byte| char::from(byte)).collect();
+        part.truncate(part.trim_end_matches(' ').len());
+        if lowercase { part.to_ascii_lowercase() } else { part }
+    };
+
+    let mut base = short_name[..8].to_vec();
+    // 0x05 steht für ein erstes Zeichen 0xE5, das sonst "gelöscht" bedeuten würde.
+    if base[0] == 0x05
+    {
+        base[0] = DELETED;
+    }
+    let mut name = convert(&base, flags & LOWERCASE_BASE != 0);
+    let extension = convert(&short_name[8..], flags & LOWERCASE_EXTENSION != 0);
+    if !extension.is_empty()
+    {
+        name.push('.');
+        name.push_str(&extension);
+    }
+    name
+}
+
+/// Gibt an, ob `byte` in einem kurzen Namen vorkommen darf.
+fn is_short_name_char(byte: u8) -> bool
+{
+    byte.is_ascii_uppercase() || byte.is_ascii_digit() || b"!#$%&'()-@^_`{}~".contains(&byte) || byte >= 0x80
+}
+
+/// Gibt an, ob `name` als Dateiname erlaubt ist.
+pub fn is_valid_name(name: &str) -> bool
+{
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && name.encode_utf16().count() <= MAX_NAME_LENGTH
+        && !name.ends_with(['.', ' '])
+        && name.chars().all(|character| character >= ' ' && !"\"*/:<>?\\|".contains(character))
+}
+
+/// ## Kurzer Name ohne langen Namen
+///
+/// Lässt sich `name` direkt als 8.3-Name darstellen (ASCII, höchstens ein
+/// Punkt, Teile jeweils einheitlich groß oder klein geschrieben), gibt die
+/// Funktion den kurzen Namen und die Kleinschreibungs-Bits zurück.
+pub fn exact_short_name(name: &str) -> Option<([u8; 11], u8)>
+{
+    let (base, extension) = name.rsplit_once('.').unwrap_or((name, ""));
+    if base.is_empty() || base.len() > 8 || extension.len() > 3 || base.contains('.')
+    {
+        return None;
+    }
+
+    let mut short_name = [b' '; 11];
+    let mut flags = 0;
+    let (base_slots, extension_slots) = short_name.split_at_mut(8);
+    for (part, target, lowercase_flag) in [(base, base_slots, LOWERCASE_BASE), (extension, extension_slots, LOWERCASE_EXTENSION)]
+    {
+        let has_upper = part.bytes().any(|byte| byte.is_ascii_uppercase());
+        let has_lower = part.bytes().any(|byte| byte.is_ascii_lowercase());
+        if has_upper && has_lower
+        {
+            return None;
+        }
+        if has_lower
+        {
+            flags |= lowercase_flag;
+        }
+        for (slot, byte) in target.iter_mut().zip(part.bytes())
+        {
+            let byte = byte.to_ascii_uppercase();
+            if !is_short_name_char(byte) || byte >= 0x80
+            {
+                return None;
+            }
+            *slot = byte;
+        }
+    }
+    Some((short_name, flags))
+}
+
+/// ## Kurzen Namen erzeugen
+///
+/// Erzeugt für einen langen Namen einen eindeutigen kurzen Namen nach dem
+/// Muster `BASIS~N.EXT`: Großbuchstaben, ungültige Zeichen als `_`, Leerzeichen
+/// und zusätzliche Punkte entfernt. `exists` prüft, ob ein kurzer Name im
+/// Verzeichnis schon vergeben ist.
+pub fn generate_short_name(name: &str, exists: impl Fn(&[u8; 11]) -> bool) -> Option<[u8; 11]>
+{
+    let convert = |part: &str| -> Vec<u8>
+    {
+        part.chars()
+            .filter(|&character| character != ' ' && character != '.')
+            .map(|character|
+            {
+                let byte = if character.is_ascii() { character.to_ascii_uppercase() as u8 } else { b'_' };
+                if is_short_name_char(byte) && byte < 0x80 { byte } else { b'_' }
+            })
+            .collect()
+    };
+
+    let trimmed = name.trim_start_matches('.');
+    let (base, extension) = match trimmed.rsplit_once('.')
+    {
+        Some((base, extension)) if !base.is_empty() => (convert(base), convert(extension)),
+        _ => (convert(trimmed), Vec::new()),
+    };
+
+    let mut short_name = [b' '; 11];
+    for (slot, &byte) in short_name[8..].iter_mut().zip(&extension)
+    {
+        *slot = byte;
+    }
+
+    for number in 1..1_000_000u32
+    {
+        let mut suffix = Vec::from(b"~".as_slice());
+        suffix.extend_from_slice(alloc::format!("{}", number).as_bytes());
+        let base_length = base.len().min(8 - suffix.len());
+        short_name[..8].fill(b' ');
+        short_name[..base_length].copy_from_slice(&base[..base_length]);
+        short_name[base_length..base_length + suffix.len()].copy_from_slice(&suffix);
+        if !exists(&short_name)
+        {
+            return Some(short_name);
+        }
+    }
+    None
+}
+
+/// Baut einen kurzen Eintrag.
+pub fn short_entry(short_name: &[u8; 11], flags: u8, attributes: u8, first_cluster: u32, size: u32) -> [u8; ENTRY_SIZE]
+{
+    let mut entry = [0; ENTRY_SIZE];
+    entry[..11].copy_from_slice(short_name);
+    entry[11] = attributes;
+    entry[12] = flags;
+    for date_offset in [16, 18, 24]
+    {
+        entry[date_offset..date_offset + 2].copy_from_slice(&DEFAULT_DATE.to_le_bytes());
+    }
+    set_location(&mut entry, first_cluster, size);
+    entry
+}
+
+/// Setzt ersten Cluster und Größe in einem kurzen Eintrag.
+pub fn set_location(entry: &mut [u8], first_cluster: u32, size: u32)
+{
+    entry[20..22].copy_from_slice(&((first_cluster >> 16) as u16).to_le_bytes());
+    entry[26..28].copy_from_slice(&(first_cluster as u16).to_le_bytes());
+    entry[28..32].copy_from_slice(&size.to_le_bytes());
+}
+
+/// Baut die Einträge für den langen Namen `name` in der Reihenfolge, in der
+/// sie vor dem kurzen Eintrag stehen.
+pub fn long_name_entries(name: &str, short_name: &[u8; 11]) -> Vec<[u8; ENTRY_SIZE]>
+{
+    let characters: Vec<u16> = name.encode_utf16().collect();
+    let count = characters.len().div_ceil(LONG_NAME_CHARS);
+    let checksum = checksum(short_name);
+
+    let mut entries = Vec::with_capacity(count);
+    for index in (0..count).rev()
+    {
+        let mut entry = [0; ENTRY_SIZE];
+        entry[0] = (index + 1) as u8 | if index + 1 == count { 0x40 } else { 0 };
+        entry[11] = ATTR_LONG_NAME;
+        entry[13] = checksum;
+        for (slot, &offset) in LONG_NAME_OFFSETS.iter().enumerate()
+        {
+            // Nach dem Namen folgt ein Nullzeichen, der Rest ist mit 0xFFFF gefüllt.
+            let position = index * LONG_NAME_CHARS + slot;
+            let character = match position.cmp(&characters.len())
+            {
+                core::cmp::Ordering::Less => characters[position],
+                core::cmp::Ordering::Equal => 0,
+                core::cmp::Ordering::Greater => 0xFFFF,
+            };
+            entry[offset..offset + 2].copy_from_slice(&character.to_le_bytes());
+        }
+        entries.push(entry);
+    }
+    entries
+}
+
+/// ## Verzeichnis auswerten
+///
+/// Durchläuft den Inhalt eines Verzeichnisses bis zum Endmarker und setzt
+/// lange Namen zusammen. Gelöschte Einträge, Volumenbezeichnungen sowie `.`
+/// und `..` werden übersprungen.
+pub fn parse(data: &[u8]) -> Vec<Entry>
+{
+    let mut entries = Vec::new();
+    let mut long_name: Vec<u16> = Vec::new();
+    let mut long_start = 0;
+    let mut long_checksum = None;
+    let mut expected = 0;
+
+    for (index, raw) in data.chunks_exact(ENTRY_SIZE).enumerate()
+    {
+        let position = index * ENTRY_SIZE;
+        match raw[0]
+        {
+            0 => break,
+            DELETED =>
+            {
+                long_checksum = None;
+                continue;
+            }
+            _ => {}
+        }
+
+        if raw[11] & 0x3F == ATTR_LONG_NAME
+        {
+            let order = raw[0] & 0x1F;
+            if raw[0] & 0x40 != 0
+            {
+                long_name = alloc::vec![0; usize::from(order) * LONG_NAME_CHARS];
+                long_start = position;
+                long_checksum = Some(raw[13]);
+                expected = order;
+            }
+            if long_checksum != Some(raw[13]) || order != expected || order == 0
+            {
+                long_checksum = None;
+                continue;
+            }
+            let start = usize::from(order - 1) * LONG_NAME_CHARS;
+            for (slot, &offset) in LONG_NAME_OFFSETS.iter().enumerate()
+            {
+                long_name[start + slot] = u16::from_le_bytes([raw[offset], raw[offset + 1]]);
+            }
+            expected -= 1;
+            continue;
+        }
+
+        let short_name: [u8; 11] = raw[..11].try_into().unwrap();
+        let attributes = raw[11];
+        let valid_long_name = long_checksum == Some(checksum(&short_name)) && expected == 0;
+        let first_position = if valid_long_name { long_start } else { position };
+        let name = if valid_long_name
+        {
+            let length = long_name.iter().position(|&character| character == 0).unwrap_or(long_name.len());
+            char::decode_utf16(long_name[..length].iter().copied())
+                .map(|character| character.unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect()
+        }
+        else
+        {
+            decode_short_name(&short_name, raw[12])
+        };
+        long_checksum = None;
+
+        if attributes & ATTR_VOLUME_ID != 0 || short_name[0] == b'.'
+        {
+            continue;
+        }
+
+        let first_cluster = u32::from(u16::from_le_bytes([raw[20], raw[21]])) << 16 | u32::from(u16::from_le_bytes([raw[26], raw[27]]));
+        let size = u32::from_le_bytes(raw[28..32].try_into().unwrap());
+        entries.push(Entry { name, short_name, attributes, first_cluster, size, position, first_position });
+    }
+    entries
+}
+
+/// Sucht `count` aufeinanderfolgende freie Einträge und gibt die Position des
+/// ersten zurück. Hinter dem Endmarker ist alles frei.
+pub fn find_free(data: &[u8], count: usize) -> Option<usize>
+{
+    let mut run = 0;
+    for (index, raw) in data.chunks_exact(ENTRY_SIZE).enumerate()
+    {
+        if raw[0] == 0 || raw[0] == DELETED
+        {
+            run += 1;
+            if run == count
+            {
+                return Some((index + 1 - count) * ENTRY_SIZE);
+            }
+        }
+        else
+        {
+            run = 0;
+        }
+    }
+    None
+}
+
+#[test_case]
+fn test_short_names()
+{
+    assert_eq!(exact_short_name("README.TXT"), Some((*b"README  TXT", 0)));
+    assert_eq!(exact_short_name("readme.txt"), Some((*b"README  TXT", LOWERCASE_BASE | LOWERCASE_EXTENSION)));
+    assert_eq!(exact_short_name("ReadMe.txt"), None);
+    assert_eq!(exact_short_name("a.b.c"), None);
+    assert_eq!(exact_short_name("toolongname"), None);
+    assert_eq!(decode_short_name(b"README  TXT", LOWERCASE_EXTENSION), "README.txt");
+    assert_eq!(decode_short_name(b"MAKEFILE   ", 0), "MAKEFILE");
+
+    assert_eq!(generate_short_name("A long file.name.txt", |_| false), Some(*b"ALONGF~1TXT"));
+    assert_eq!(generate_short_name("ünïcode", |name| name == b"_N_COD~1   "), Some(*b"_N_COD~2   "));
+    assert!(!is_valid_name("a/b") && !is_valid_name("..") && !is_valid_name("x."));
+}
+
+#[test_case]
+fn test_long_name_round_trip()
+{
+    let name = "A rather long file name.text";
+    let short_name = generate_short_name(name, |_| false).unwrap();
+    let mut data = Vec::new();
+    for entry in long_name_entries(name, &short_name)
+    {
+        data.extend_from_slice(&entry);
+    }
+    data.extend_from_slice(&short_entry(&short_name, 0, ATTR_ARCHIVE, 5, 1234));
+    data.extend_from_slice(&[0; ENTRY_SIZE]);
+
+    let entries = parse(&data);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].name, name);
+    assert_eq!((entries[0].first_cluster, entries[0].size), (5, 1234));
+    assert_eq!((entries[0].first_position, entries[0].position), (0, 3 * ENTRY_SIZE));
+    assert!(entries[0].matches("a RATHER long file name.TEXT"));
+    assert_eq!(find_free(&data, 1), Some(4 * ENTRY_SIZE));
+    assert_eq!(find_free(&data, 2), None);
+}
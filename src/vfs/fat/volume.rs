@@ -0,0 +1,457 @@
+//! Aufbau eines FAT-Datenträgers: Bootsektor, Dateizuordnungstabellen und
+//! Cluster.
+
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::block::BlockDevice;
+use crate::vfs::{VfsError, VfsResult};
+
+/// Variante des Dateisystems, bestimmt durch die Anzahl der Cluster.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FatType
+{
+    Fat12,
+    Fat16,
+    Fat32,
+}
+
+/// Bedeutung eines Eintrags der Dateizuordnungstabelle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FatEntry
+{
+    Free,
+    /// Nächster Cluster der Kette.
+    Next(u32),
+    /// Letzter Cluster der Kette.
+    End,
+    /// Als defekt markierter Cluster.
+    Bad,
+}
+
+/// Wo ein Verzeichnis liegt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirRegion
+{
+    /// Das Wurzelverzeichnis von FAT12/16 in einem festen Bereich vor den Clustern.
+    FixedRoot,
+    /// Eine Clusterkette ab dem angegebenen Cluster.
+    Chain(u32),
+}
+
+/// Kennung am Anfang und vor dem Ende des FSInfo-Sektors von FAT32.
+const FSINFO_LEAD_SIGNATURE: u32 = 0x4161_5252;
+const FSINFO_STRUCT_SIGNATURE: u32 = 0x6141_7272;
+
+/// ## Volume
+///
+/// Die unveränderliche Geometrie eines eingehängten FAT-Dateisystems samt
+/// Zugriff auf das Gerät. Alle Positionen sind Byte-Offsets auf dem Gerät.
+pub struct Volume
+{
+    device: Arc<dyn BlockDevice>,
+    pub fat_type: FatType,
+    pub cluster_size: usize,
+    pub cluster_count: u32,
+    fat_offset: u64,
+    fat_size: u64,
+    fat_count: u64,
+    root_offset: u64,
+    root_size: usize,
+    data_offset: u64,
+    pub root_cluster: u32,
+    fsinfo_offset: Option<u64>,
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16
+{
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32
+{
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+impl Volume
+{
+    /// Liest den Bootsektor (BIOS Parameter Block) von `device` und berechnet
+    /// daraus die Lage von Tabellen, Wurzelverzeichnis und Clustern.
+    pub fn open(device: Arc<dyn BlockDevice>) -> VfsResult<Volume>
+    {
+        let mut boot = vec![0; device.sector_size().max(512)];
+        device.read_sectors(0, &mut boot[..device.sector_size()])?;
+
+        let bytes_per_sector = u64::from(u16_at(&boot, 11));
+        let sectors_per_cluster = u64::from(boot[13]);
+        let reserved_sectors = u64::from(u16_at(&boot, 14));
+        let fat_count = u64::from(boot[16]);
+        let root_entries = u64::from(u16_at(&boot, 17));
+        let total_sectors = match u16_at(&boot, 19)
+        {
+            0 => u64::from(u32_at(&boot, 32)),
+            total => u64::from(total),
+        };
+        let fat_sectors = match u16_at(&boot, 22)
+        {
+            0 => u64::from(u32_at(&boot, 36)),
+            size => u64::from(size),
+        };
+
+        if boot[510..512] != [0x55, 0xAA]
+            || !matches!(bytes_per_sector, 512 | 1024 | 2048 | 4096)
+            || bytes_per_sector % device.sector_size() as u64 != 0
+            || !sectors_per_cluster.is_power_of_two()
+            || reserved_sectors == 0
+            || fat_count == 0
+            || fat_sectors == 0
+        {
+            return Err(VfsError::Corrupt);
+        }
+
+        let root_sectors = (root_entries * 32).div_ceil(bytes_per_sector);
+        let data_start = reserved_sectors + fat_count * fat_sectors + root_sectors;
+        let cluster_count = total_sectors.checked_sub(data_start).ok_or(VfsError::Corrupt)? / sectors_per_cluster;
+        let fat_type = match cluster_count
+        {
+            0..4085 => FatType::Fat12,
+            4085..65525 => FatType::Fat16,
+            _ => FatType::Fat32,
+        };
+
+        let (root_cluster, fsinfo_offset) = if fat_type == FatType::Fat32
+        {
+            let fsinfo = u64::from(u16_at(&boot, 48));
+            (u32_at(&boot, 44), (fsinfo != 0 && fsinfo != 0xFFFF).then_some(fsinfo * bytes_per_sector))
+        }
+        else
+        {
+            (0, None)
+        };
+
+        let volume = Volume
+        {
+            device,
+            fat_type,
+            cluster_size: (sectors_per_cluster * bytes_per_sector) as usize,
+            cluster_count: cluster_count as u32,
+            fat_offset: reserved_sectors * bytes_per_sector,
+            fat_size: fat_sectors * bytes_per_sector,
+            fat_count,
+            root_offset: (reserved_sectors + fat_count * fat_sectors) * bytes_per_sector,
+            root_size: (root_entries * 32) as usize,
+            data_offset: data_start * bytes_per_sector,
+            root_cluster,
+            fsinfo_offset,
+        };
+        if total_sectors * bytes_per_sector > volume.device.capacity()
+            || (fat_type == FatType::Fat32 && !volume.is_cluster(root_cluster))
+        {
+            return Err(VfsError::Corrupt);
+        }
+        Ok(volume)
+    }
+
+    /// Das Wurzelverzeichnis.
+    pub fn root_region(&self) -> DirRegion
+    {
+        match self.fat_type
+        {
+            FatType::Fat32 => DirRegion::Chain(self.root_cluster),
+            _ => DirRegion::FixedRoot,
+        }
+    }
+
+    /// Gibt an, ob `cluster` ein gültiger Datencluster ist.
+    pub fn is_cluster(&self, cluster: u32) -> bool
+    {
+        (2..self.cluster_count + 2).contains(&cluster)
+    }
+
+    /// Byte-Offset des Clusters `cluster` auf dem Gerät.
+    pub fn cluster_offset(&self, cluster: u32) -> u64
+    {
+        self.data_offset + u64::from(cluster - 2) * self.cluster_size as u64
+    }
+
+    /// Liest `buffer.len()` Bytes ab dem Byte-Offset `offset`.
+    pub fn read_bytes(&self, offset: u64, buffer: &mut [u8]) -> VfsResult<()>
+    {
+        let sector_size = self.device.sector_size();
+        let mut sector = vec![0; sector_size];
+        let mut done = 0;
+        while done < buffer.len()
+        {
+            let position = offset + done as u64;
+            let lba = position / sector_size as u64;
+            let within = (position % sector_size as u64) as usize;
+            let remaining = buffer.len() - done;
+
+            if within == 0 && remaining >= sector_size
+            {
+                let length = remaining - remaining % sector_size;
+                self.device.read_sectors(lba, &mut buffer[done..done + length])?;
+                done += length;
+            }
+            else
+            {
+                let length = remaining.min(sector_size - within);
+                self.device.read_sectors(lba, &mut sector)?;
+                buffer[done..done + length].copy_from_slice(&sector[within..within + length]);
+                done += length;
+            }
+        }
+        Ok(())
+    }
+
+    /// Schreibt `data` ab dem Byte-Offset `offset`. Teilweise betroffene
+    /// Sektoren werden vorher gelesen.
+    pub fn write_bytes(&self, offset: u64, data: &[u8]) -> VfsResult<()>
+    {
+        let sector_size = self.device.sector_size();
+        let mut sector = vec![0; sector_size];
+        let mut done = 0;
+        while done < data.len()
+        {
+            let position = offset + done as u64;
+            let lba = position / sector_size as u64;
+            let within = (position % sector_size as u64) as usize;
+            let remaining = data.len() - done;
+
+            if within == 0 && remaining >= sector_size
+            {
+                let length = remaining - remaining % sector_size;
+                self.device.write_sectors(lba, &data[done..done + length])?;
+                done += length;
+            }
+            else
+            {
+                let length = remaining.min(sector_size - within);
+                self.device.read_sectors(lba, &mut sector)?;
+                sector[within..within + length].copy_from_slice(&data[done..done + length]);
+                self.device.write_sectors(lba, &sector)?;
+                done += length;
+            }
+        }
+        Ok(())
+    }
+
+    /// Offset des Eintrags für `cluster` in der ersten Tabelle.
+    fn entry_offset(&self, cluster: u32) -> u64
+    {
+        let cluster = u64::from(cluster);
+        self.fat_offset + match self.fat_type
+        {
+            FatType::Fat12 => cluster + cluster / 2,
+            FatType::Fat16 => cluster * 2,
+            FatType::Fat32 => cluster * 4,
+        }
+    }
+
+    /// Liest den Tabelleneintrag von `cluster`.
+    pub fn entry(&self, cluster: u32) -> VfsResult<FatEntry>
+    {
+        let mut bytes = [0; 4];
+        let offset = self.entry_offset(cluster);
+        let (value, bad) = match self.fat_type
+        {
+            FatType::Fat12 =>
+            {
+                self.read_bytes(offset, &mut bytes[..2])?;
+                let value = u32::from(u16::from_le_bytes([bytes[0], bytes[1]]));
+                (if cluster % 2 == 1 { value >> 4 } else { value & 0xFFF }, 0xFF7)
+            }
+            FatType::Fat16 =>
+            {
+                self.read_bytes(offset, &mut bytes[..2])?;
+                (u32::from(u16::from_le_bytes([bytes[0], bytes[1]])), 0xFFF7)
+            }
+            FatType::Fat32 =>
+            {
+                self.read_bytes(offset, &mut bytes)?;
+                (u32::from_le_bytes(bytes) & 0x0FFF_FFFF, 0x0FFF_FFF7)
+            }
+        };
+
+        match value
+        {
+            0 => Ok(FatEntry::Free),
+            value if value == bad => Ok(FatEntry::Bad),
+            value if value > bad => Ok(FatEntry::End),
+            value if self.is_cluster(value) => Ok(FatEntry::Next(value)),
+            _ => Err(VfsError::Corrupt),
+        }
+    }
+
+    /// Schreibt den Tabelleneintrag von `cluster` in alle Kopien der Tabelle.
+    pub fn set_entry(&self, cluster: u32, entry: FatEntry) -> VfsResult<()>
+    {
+        let value = match entry
+        {
+            FatEntry::Free => 0,
+            FatEntry::Next(next) => next,
+            FatEntry::End => 0x0FFF_FFFF,
+            FatEntry::Bad => 0x0FFF_FFF7,
+        };
+
+        for copy in 0..self.fat_count
+        {
+            let offset = self.entry_offset(cluster) + copy * self.fat_size;
+            let mut bytes = [0; 4];
+            match self.fat_type
+            {
+                FatType::Fat12 =>
+                {
+                    self.read_bytes(offset, &mut bytes[..2])?;
+                    let old = u16::from_le_bytes([bytes[0], bytes[1]]);
+                    let value = (value & 0xFFF) as u16;
+                    let new = if cluster % 2 == 1 { (old & 0x000F) | value << 4 } else { (old & 0xF000) | value };
+                    self.write_bytes(offset, &new.to_le_bytes())?;
+                }
+                FatType::Fat16 => self.write_bytes(offset, &(value as u16).to_le_bytes())?,
+                FatType::Fat32 =>
+                {
+                    // Die oberen vier Bits sind reserviert und bleiben erhalten.
+                    self.read_bytes(offset, &mut bytes)?;
+                    let new = (u32::from_le_bytes(bytes) & 0xF000_0000) | (value & 0x0FFF_FFFF);
+                    self.write_bytes(offset, &new.to_le_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Alle Cluster der Kette ab `first`. Eine Kette, die länger als das
+    /// Dateisystem ist, enthält eine Schleife und gilt als beschädigt.
+    pub fn chain(&self, first: u32) -> VfsResult<Vec<u32>>
+    {
+        let mut clusters = Vec::new();
+        let mut cluster = first;
+        while self.is_cluster(cluster)
+        {
+            if clusters.len() > self.cluster_count as usize
+            {
+                return Err(VfsError::Corrupt);
+            }
+            clusters.push(cluster);
+            cluster = match self.entry(cluster)?
+            {
+                FatEntry::Next(next) => next,
+                FatEntry::End => break,
+                FatEntry::Free | FatEntry::Bad => return Err(VfsError::Corrupt),
+            };
+        }
+        Ok(clusters)
+    }
+
+    /// ## Cluster belegen
+    ///
+    /// Sucht ab `hint` einen freien Cluster, markiert ihn als Kettenende,
+    /// füllt ihn mit Nullen und hängt ihn an `previous` an. Gibt den Cluster
+    /// zurück.
+    pub fn allocate(&self, hint: u32, previous: Option<u32>) -> VfsResult<u32>
+    {
+        let start = if self.is_cluster(hint) { hint } else { 2 };
+        let candidates = (start..self.cluster_count + 2).chain(2..start);
+        for cluster in candidates
+        {
+            if self.entry(cluster)? != FatEntry::Free
+            {
+                continue;
+            }
+            self.set_entry(cluster, FatEntry::End)?;
+            self.write_bytes(self.cluster_offset(cluster), &vec![0; self.cluster_size])?;
+            if let Some(previous) = previous
+            {
+                self.set_entry(previous, FatEntry::Next(cluster))?;
+            }
+            return Ok(cluster);
+        }
+        Err(VfsError::NoSpace)
+    }
+
+    /// Gibt alle Cluster der Kette ab `first` frei.
+    pub fn free_chain(&self, first: u32) -> VfsResult<()>
+    {
+        for cluster in self.chain(first)?
+        {
+            self.set_entry(cluster, FatEntry::Free)?;
+        }
+        Ok(())
+    }
+
+    /// Anzahl der freien Cluster.
+    pub fn free_clusters(&self) -> VfsResult<u32>
+    {
+        let mut free = 0;
+        for cluster in 2..self.cluster_count + 2
+        {
+            if self.entry(cluster)? == FatEntry::Free
+            {
+                free += 1;
+            }
+        }
+        Ok(free)
+    }
+
+    /// Liest den Inhalt eines Verzeichnisses und die Geräte-Offsets seiner
+    /// Abschnitte (ein Abschnitt pro Cluster bzw. einer für das feste
+    /// Wurzelverzeichnis).
+    pub fn read_directory(&self, region: DirRegion) -> VfsResult<(Vec<u8>, Vec<u64>)>
+    {
+        match region
+        {
+            DirRegion::FixedRoot =>
+            {
+                let mut data = vec![0; self.root_size];
+                self.read_bytes(self.root_offset, &mut data)?;
+                Ok((data, vec![self.root_offset]))
+            }
+            DirRegion::Chain(first) =>
+            {
+                let offsets: Vec<u64> = self.chain(first)?.into_iter().map(|cluster| self.cluster_offset(cluster)).collect();
+                let mut data = vec![0; offsets.len() * self.cluster_size];
+                for (chunk, &offset) in data.chunks_exact_mut(self.cluster_size).zip(&offsets)
+                {
+                    self.read_bytes(offset, chunk)?;
+                }
+                Ok((data, offsets))
+            }
+        }
+    }
+
+    /// Größe eines Abschnitts aus [read_directory](Volume::read_directory).
+    pub fn directory_chunk_size(&self, region: DirRegion) -> usize
+    {
+        match region
+        {
+            DirRegion::FixedRoot => self.root_size,
+            DirRegion::Chain(_) => self.cluster_size,
+        }
+    }
+
+    /// Aktualisiert bei FAT32 die Anzahl freier Cluster und den Suchhinweis im
+    /// FSInfo-Sektor und leert den Schreibcache des Geräts.
+    pub fn sync(&self, next_free: u32) -> VfsResult<()>
+    {
+        if let Some(offset) = self.fsinfo_offset
+        {
+            let mut signatures = [0; 4];
+            self.read_bytes(offset, &mut signatures)?;
+            if u32::from_le_bytes(signatures) == FSINFO_LEAD_SIGNATURE
+            {
+                self.read_bytes(offset + 484, &mut signatures)?;
+                if u32::from_le_bytes(signatures) == FSINFO_STRUCT_SIGNATURE
+                {
+                    let mut counts = [0; 8];
+                    counts[..4].copy_from_slice(&self.free_clusters()?.to_le_bytes());
+                    counts[4..].copy_from_slice(&next_free.to_le_bytes());
+                    self.write_bytes(offset + 488, &counts)?;
+                }
+            }
+        }
+        self.device.flush()?;
+        Ok(())
+    }
+}
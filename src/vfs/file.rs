@@ -0,0 +1,180 @@
+//! Geöffnete Dateien und Dateitabellen der Prozesse.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::{Fd, File, FileType, Inode, Metadata, OpenFlags, SeekFrom, VfsError, VfsResult};
+use crate::sync::Mutex;
+
+/// Höchste Anzahl gleichzeitig geöffneter Dateien pro Prozess.
+pub const MAX_OPEN_FILES: usize = 64;
+
+/// ## OpenFile
+///
+/// Ein geöffneter Inode mit Zugriffsrechten und aktueller Position.
+pub struct OpenFile
+{
+    inode: Arc<dyn Inode>,
+    flags: OpenFlags,
+    offset: Mutex<u64>,
+}
+
+impl OpenFile
+{
+    /// Öffnet `inode` mit den Rechten aus `flags` an Position 0.
+    pub fn new(inode: Arc<dyn Inode>, flags: OpenFlags) -> OpenFile
+    {
+        OpenFile { inode, flags, offset: Mutex::new(0) }
+    }
+
+    /// Der geöffnete Inode.
+    pub fn inode(&self) -> &Arc<dyn Inode>
+    {
+        &self.inode
+    }
+}
+
+impl File for OpenFile
+{
+    fn read(&self, buffer: &mut [u8]) -> VfsResult<usize>
+    {
+        if !self.flags.contains(OpenFlags::READ)
+        {
+            return Err(VfsError::BadFileDescriptor);
+        }
+        if self.inode.metadata().file_type == FileType::Directory
+        {
+            return Err(VfsError::IsADirectory);
+        }
+
+        let mut offset = self.offset.lock();
+        let count = self.inode.read_at(*offset, buffer)?;
+        *offset += count as u64;
+        Ok(count)
+    }
+
+    fn write(&self, buffer: &[u8]) -> VfsResult<usize>
+    {
+        if !self.flags.contains(OpenFlags::WRITE)
+        {
+            return Err(VfsError::BadFileDescriptor);
+        }
+
+        let mut offset = self.offset.lock();
+        if self.flags.contains(OpenFlags::APPEND)
+        {
+            *offset = self.inode.metadata().size;
+        }
+        let count = self.inode.write_at(*offset, buffer)?;
+        *offset += count as u64;
+        Ok(count)
+    }
+
+    fn seek(&self, position: SeekFrom) -> VfsResult<u64>
+    {
+        let mut offset = self.offset.lock();
+        let new = match position
+        {
+            SeekFrom::Start(position) => Some(position),
+            SeekFrom::Current(delta) => offset.checked_add_signed(delta),
+            SeekFrom::End(delta) => self.inode.metadata().size.checked_add_signed(delta),
+        };
+        *offset = new.ok_or(VfsError::InvalidArgument)?;
+        Ok(*offset)
+    }
+
+    fn stat(&self) -> VfsResult<Metadata>
+    {
+        Ok(self.inode.metadata())
+    }
+}
+
+/// ## FileTable
+///
+/// Die geöffneten Dateien eines Prozesses, indiziert über den [Fd]. Neue
+/// Dateien bekommen wie unter Unix den kleinsten freien Deskriptor.
+///
+/// Beim Kopieren der Tabelle (z. B. in [fork](crate::process::fork)) teilen
+/// sich beide Tabellen die geöffneten Dateien samt Position.
+#[derive(Clone, Default)]
+pub struct FileTable
+{
+    files: Vec<Option<Arc<dyn File>>>,
+}
+
+impl FileTable
+{
+    /// Erstellt eine leere Tabelle.
+    pub const fn new() -> FileTable
+    {
+        FileTable { files: Vec::new() }
+    }
+
+    /// Trägt `file` am kleinsten freien Deskriptor ein.
+    pub fn insert(&mut self, file: Arc<dyn File>) -> VfsResult<Fd>
+    {
+        if let Some(fd) = self.files.iter().position(Option::is_none)
+        {
+            self.files[fd] = Some(file);
+            return Ok(fd);
+        }
+        if self.files.len() >= MAX_OPEN_FILES
+        {
+            return Err(VfsError::TooManyOpenFiles);
+        }
+        self.files.push(Some(file));
+        Ok(self.files.len() - 1)
+    }
+
+    /// Die Datei hinter `fd`.
+    pub fn get(&self, fd: Fd) -> VfsResult<Arc<dyn File>>
+    {
+        self.files.get(fd).cloned().flatten().ok_or(VfsError::BadFileDescriptor)
+    }
+
+    /// Entfernt `fd` aus der Tabelle und gibt die Datei zurück.
+    pub fn remove(&mut self, fd: Fd) -> VfsResult<Arc<dyn File>>
+    {
+        let file = self.files.get_mut(fd).and_then(Option::take).ok_or(VfsError::BadFileDescriptor)?;
+        while self.files.last().is_some_and(Option::is_none)
+        {
+            self.files.pop();
+        }
+        Ok(file)
+    }
+
+    /// Anzahl der geöffneten Deskriptoren.
+    pub fn len(&self) -> usize
+    {
+        self.files.iter().filter(|file| file.is_some()).count()
+    }
+
+    /// Gibt an, ob kein Deskriptor geöffnet ist.
+    pub fn is_empty(&self) -> bool
+    {
+        self.len() == 0
+    }
+}
+
+#[test_case]
+fn test_file_table_lowest_free()
+{
+    use super::FileSystem;
+
+    let inode = super::TmpFs::new().root();
+    let mut table = FileTable::new();
+    let open = || -> Arc<dyn File> { Arc::new(OpenFile::new(inode.clone(), OpenFlags::READ)) };
+
+    assert_eq!(table.insert(open()), Ok(0));
+    assert_eq!(table.insert(open()), Ok(1));
+    assert_eq!(table.insert(open()), Ok(2));
+    assert!(table.remove(1).is_ok());
+    assert_eq!(table.insert(open()), Ok(1));
+    assert_eq!(table.len(), 3);
+    assert!(table.remove(7).is_err());
+
+    let copy = table.clone();
+    assert!(table.remove(0).is_ok());
+    assert!(copy.get(0).is_ok());
+    assert_eq!(table.get(0).err(), Some(VfsError::BadFileDescriptor));
+}
@@ -0,0 +1,146 @@
+//! Tabelle der eingehängten Dateisysteme.
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::path::{normalize, starts_with};
+use super::{FileSystem, FileType, Inode, TmpFs, VfsError, VfsResult};
+use crate::sync::{Once, RwLock};
+
+/// Ein eingehängtes Dateisystem samt normalisiertem Pfad seines Mountpunkts.
+struct Mount
+{
+    path: Vec<String>,
+    fs: Arc<dyn FileSystem>,
+}
+
+/// Alle Mounts; der erste Eintrag ist immer die Wurzel `/`.
+static MOUNTS: Once<RwLock<Vec<Mount>>> = Once::new();
+
+/// Gibt die Mount-Tabelle zurück und hängt beim ersten Zugriff ein leeres
+/// [TmpFs] als Wurzel ein.
+fn mounts() -> &'static RwLock<Vec<Mount>>
+{
+    MOUNTS.call_once(|| RwLock::new(alloc::vec![Mount { path: Vec::new(), fs: Arc::new(TmpFs::new()) }]))
+}
+
+impl Mount
+{
+    /// Gibt an, ob der Mountpunkt ein Anfangsstück von `components` ist.
+    fn covers<T: AsRef<str>>(&self, components: &[T]) -> bool
+    {
+        starts_with(components, &self.path)
+    }
+
+    /// Gibt an, ob der Mountpunkt genau `components` ist.
+    fn is_at<T: AsRef<str>>(&self, components: &[T]) -> bool
+    {
+        self.path.len() == components.len() && self.covers(components)
+    }
+}
+
+/// Höchste Anzahl symbolischer Links, denen eine Pfadauflösung folgt.
+pub const MAX_SYMLINKS: usize = 8;
+
+/// ## Pfad auflösen
+///
+/// Sucht den Mount mit dem längsten passenden Pfad und durchläuft von dessen
+/// Wurzel aus die restlichen Bestandteile. Symbolische Links werden durch ihr
+/// Ziel ersetzt; der letzte Bestandteil nur, wenn `follow_last` gesetzt ist.
+pub fn resolve(components: &[&str], follow_last: bool) -> VfsResult<Arc<dyn Inode>>
+{
+    let mut path: Vec<String> = components.iter().map(|&name| String::from(name)).collect();
+    let mut links = 0;
+
+    'restart: loop
+    {
+        let (depth, mut inode) =
+        {
+            let mounts = mounts().read();
+            let mount = mounts
+                .iter()
+                .filter(|mount| mount.covers(&path))
+                .max_by_key(|mount| mount.path.len())
+                .expect("the root file system is always mounted");
+            (mount.path.len(), mount.fs.root())
+        };
+
+        for index in depth..path.len()
+        {
+            if inode.metadata().file_type != FileType::Directory
+            {
+                return Err(VfsError::NotADirectory);
+            }
+            let next = inode.lookup(&path[index])?;
+            let is_last = index + 1 == path.len();
+            if next.metadata().file_type == FileType::Symlink && (follow_last || !is_last)
+            {
+                links += 1;
+                if links > MAX_SYMLINKS
+                {
+                    return Err(VfsError::SymlinkLoop);
+                }
+                path = substitute_link(&path, index, &next.readlink()?)?;
+                continue 'restart;
+            }
+            inode = next;
+        }
+        return Ok(inode);
+    }
+}
+
+/// Ersetzt `path[index]` durch das Linkziel `target`. Relative Ziele beziehen
+/// sich auf das Verzeichnis, in dem der Link liegt.
+fn substitute_link(path: &[String], index: usize, target: &str) -> VfsResult<Vec<String>>
+{
+    let mut joined = String::new();
+    if !target.starts_with('/')
+    {
+        for name in &path[..index]
+        {
+            joined.push('/');
+            joined.push_str(name);
+        }
+    }
+    joined.push('/');
+    joined.push_str(target);
+    for name in &path[index + 1..]
+    {
+        joined.push('/');
+        joined.push_str(name);
+    }
+    Ok(normalize(&joined)?.into_iter().map(String::from).collect())
+}
+
+/// Gibt an, ob an `components` ein Dateisystem eingehängt ist.
+pub fn is_mount_point(components: &[&str]) -> bool
+{
+    mounts().read().iter().any(|mount| mount.is_at(components))
+}
+
+/// Hängt `fs` an `components` ein.
+pub fn add(components: &[&str], fs: Arc<dyn FileSystem>) -> VfsResult<()>
+{
+    let mut mounts = mounts().write();
+    if mounts.iter().any(|mount| mount.is_at(components))
+    {
+        return Err(VfsError::Busy);
+    }
+    mounts.push(Mount { path: components.iter().map(|&name| String::from(name)).collect(), fs });
+    Ok(())
+}
+
+/// Hängt das Dateisystem an `components` aus. Die Wurzel und Mountpunkte, unter
+/// denen weitere Dateisysteme hängen, sind [VfsError::Busy].
+pub fn remove(components: &[&str]) -> VfsResult<Arc<dyn FileSystem>>
+{
+    let mut mounts = mounts().write();
+    let index = mounts.iter().position(|mount| mount.is_at(components)).ok_or(VfsError::InvalidArgument)?;
+    let nested = mounts.iter().any(|mount| mount.path.len() > components.len() && starts_with(&mount.path, components));
+    if components.is_empty() || nested
+    {
+        return Err(VfsError::Busy);
+    }
+    Ok(mounts.remove(index).fs)
+}
@@ -0,0 +1,66 @@
+//! Zerlegen und Normalisieren von Pfaden.
+
+use alloc::vec::Vec;
+
+use super::{VfsError, VfsResult};
+
+/// ## Pfad normalisieren
+///
+/// Zerlegt den absoluten Pfad `path` in seine Bestandteile. Leere Teile und `.`
+/// werden übersprungen, `..` entfernt den vorherigen Teil; oberhalb der Wurzel
+/// bleibt `..` wie unter Unix bei `/`.
+///
+/// ```text
+/// "/a//b/./c/../d" --> ["a", "b", "d"]
+/// ```
+///
+/// Relative Pfade gibt es mangels Arbeitsverzeichnis nicht; sie ergeben
+/// [VfsError::InvalidPath].
+pub fn normalize(path: &str) -> VfsResult<Vec<&str>>
+{
+    let Some(rest) = path.strip_prefix('/')
+    else
+    {
+        return Err(VfsError::InvalidPath);
+    };
+
+    let mut components = Vec::new();
+    for component in rest.split('/')
+    {
+        match component
+        {
+            "" | "." => {}
+            ".." =>
+            {
+                components.pop();
+            }
+            name => components.push(name),
+        }
+    }
+    Ok(components)
+}
+
+/// Gibt an, ob `prefix` ein Anfangsstück von `components` ist.
+pub fn starts_with<A: AsRef<str>, B: AsRef<str>>(components: &[A], prefix: &[B]) -> bool
+{
+    components.len() >= prefix.len() && components.iter().zip(prefix).all(|(a, b)| a.as_ref() == b.as_ref())
+}
+
+#[test_case]
+fn test_normalize()
+{
+    assert_eq!(normalize("/"), Ok(Vec::new()));
+    assert_eq!(normalize("/a//b/./c/../d/"), Ok(alloc::vec!["a", "b", "d"]));
+    assert_eq!(normalize("/../a/.."), Ok(Vec::new()));
+    assert_eq!(normalize("a/b"), Err(VfsError::InvalidPath));
+    assert_eq!(normalize(""), Err(VfsError::InvalidPath));
+}
+
+#[test_case]
+fn test_starts_with()
+{
+    assert!(starts_with(&["mnt", "data", "x"], &["mnt", "data"]));
+    assert!(starts_with::<_, &str>(&["mnt"], &[]));
+    assert!(!starts_with(&["mnt"], &["mnt", "data"]));
+    assert!(!starts_with(&["mntx"], &["mnt"]));
+}
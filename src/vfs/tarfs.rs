@@ -0,0 +1,375 @@
+//! Schreibgeschütztes Dateisystem aus einem USTAR-Archiv.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::{DirEntry, FileSystem, FileType, Inode, Metadata, VfsError, VfsResult};
+
+/// Größe eines Blocks im Archiv; Kopf und Daten beginnen immer an Blockgrenzen.
+const BLOCK_SIZE: usize = 512;
+
+/// ## TarFs
+///
+/// Ein schreibgeschütztes Dateisystem über einem USTAR-Archiv, z. B. der
+/// initrd (siehe [crate::initrd]). Das Archiv wird beim Erstellen einmal
+/// durchlaufen; Dateiinhalte werden nicht kopiert, sondern direkt aus dem
+/// Archiv gelesen.
+///
+/// Unterstützt werden normale Dateien, Verzeichnisse, symbolische Links und
+/// harte Links. Verzeichnisse, die nur als Teil eines Pfads vorkommen, werden
+/// angelegt; andere Eintragsarten (Geräte, pax-Köpfe, ...) werden übersprungen.
+pub struct TarFs
+{
+    root: Arc<TarInode>,
+}
+
+impl TarFs
+{
+    /// Liest das Archiv `archive` ein. Fehlerhafte Köpfe oder Einträge, die
+    /// über das Ende des Archivs hinausragen, ergeben [VfsError::Corrupt].
+    pub fn new(archive: &'static [u8]) -> VfsResult<TarFs>
+    {
+        let mut root = BTreeMap::new();
+        let mut offset = 0;
+        while offset + BLOCK_SIZE <= archive.len()
+        {
+            let header = &archive[offset..offset + BLOCK_SIZE];
+            if header.iter().all(|&byte| byte == 0)
+            {
+                break;
+            }
+            verify_header(header)?;
+
+            let size = usize::try_from(octal(&header[124..136])?).map_err(|_| VfsError::Corrupt)?;
+            let start = offset + BLOCK_SIZE;
+            let data = archive.get(start..start + size).ok_or(VfsError::Corrupt)?;
+            offset = start + size.div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+
+            let mut path = String::from(text(&header[345..500])?);
+            if !path.is_empty()
+            {
+                path.push('/');
+            }
+            path.push_str(text(&header[0..100])?);
+            let link = text(&header[157..257])?;
+
+            let node = match header[156]
+            {
+                b'0' | b'7' | 0 => Node::File(data),
+                b'5' => Node::Directory(BTreeMap::new()),
+                b'2' => Node::Symlink(String::from(link)),
+                b'1' => match find(&root, &components(link)?)
+                {
+                    Some(Node::File(data)) => Node::File(data),
+                    _ => return Err(VfsError::Corrupt),
+                },
+                _ => continue,
+            };
+            insert(&mut root, &components(&path)?, node)?;
+        }
+
+        let mut next_inode = 1;
+        Ok(TarFs { root: freeze(Node::Directory(root), &mut next_inode) })
+    }
+}
+
+impl FileSystem for TarFs
+{
+    fn name(&self) -> &str
+    {
+        "tarfs"
+    }
+
+    fn root(&self) -> Arc<dyn Inode>
+    {
+        self.root.clone()
+    }
+}
+
+/// Prüft Kennung und Prüfsumme eines Kopfes.
+fn verify_header(header: &[u8]) -> VfsResult<()>
+{
+    if &header[257..262] != b"ustar"
+    {
+        return Err(VfsError::Corrupt);
+    }
+    // Die Prüfsumme zählt ihr eigenes Feld als Leerzeichen.
+    let sum: u64 = header
+        .iter()
+        .enumerate()
+        .map(|(index, &byte)| if (148..156).contains(&index) { b' ' } else { byte } as u64)
+        .sum();
+    if octal(&header[148..156])? != sum
+    {
+        return Err(VfsError::Corrupt);
+    }
+    Ok(())
+}
+
+/// Liest eine Oktalzahl, die mit Leerzeichen oder NUL enden darf.
+fn octal(field: &[u8]) -> VfsResult<u64>
+{
+    let mut value: u64 = 0;
+    for &byte in field.iter().skip_while(|&&byte| byte == b' ')
+    {
+        match byte
+        {
+            b'0'..=b'7' => value = value.checked_mul(8).ok_or(VfsError::Corrupt)? + u64::from(byte - b'0'),
+            b' ' | 0 => break,
+            _ => return Err(VfsError::Corrupt),
+        }
+    }
+    Ok(value)
+}
+
+/// Liest eine mit NUL abgeschlossene (oder das Feld füllende) Zeichenkette.
+fn text(field: &[u8]) -> VfsResult<&str>
+{
+    let length = field.iter().position(|&byte| byte == 0).unwrap_or(field.len());
+    core::str::from_utf8(&field[..length]).map_err(|_| VfsError::Corrupt)
+}
+
+/// Zerlegt einen Pfad im Archiv, z. B. `./bin/init`, in seine Bestandteile.
+fn components(path: &str) -> VfsResult<Vec<&str>>
+{
+    let components: Vec<&str> = path.split('/').filter(|&name| !name.is_empty() && name != ".").collect();
+    if components.contains(&"..")
+    {
+        return Err(VfsError::Corrupt);
+    }
+    Ok(components)
+}
+
+/// Eintrag des Archivs, solange es noch eingelesen wird.
+enum Node
+{
+    File(&'static [u8]),
+    Directory(BTreeMap<String, Node>),
+    Symlink(String),
+}
+
+fn find<'a>(directory: &'a BTreeMap<String, Node>, components: &[&str]) -> Option<&'a Node>
+{
+    let (name, rest) = components.split_first()?;
+    match (directory.get(*name)?, rest.is_empty())
+    {
+        (node, true) => Some(node),
+        (Node::Directory(children), false) => find(children, rest),
+        _ => None,
+    }
+}
+
+/// Trägt `node` unter `components` ein und legt fehlende Verzeichnisse an. Ein
+/// späterer Eintrag ersetzt wie beim Entpacken einen früheren gleichen Namens;
+/// nur bestehende Verzeichnisse bleiben samt Inhalt erhalten.
+fn insert(directory: &mut BTreeMap<String, Node>, components: &[&str], node: Node) -> VfsResult<()>
+{
+    let Some((name, rest)) = components.split_first()
+    else
+    {
+        // Eintrag für die Wurzel selbst, z. B. `./`.
+        return Ok(());
+    };
+
+    if rest.is_empty()
+    {
+        if !matches!((&node, directory.get(*name)), (Node::Directory(_), Some(Node::Directory(_))))
+        {
+            directory.insert(String::from(*name), node);
+        }
+        return Ok(());
+    }
+
+    let child = directory.entry(String::from(*name)).or_insert_with(|| Node::Directory(BTreeMap::new()));
+    match child
+    {
+        Node::Directory(children) => insert(children, rest, node),
+        _ => Err(VfsError::Corrupt),
+    }
+}
+
+/// Wandelt den eingelesenen Baum in Inodes um und vergibt dabei die Nummern.
+fn freeze(node: Node, next_inode: &mut u64) -> Arc<TarInode>
+{
+    let inode = *next_inode;
+    *next_inode += 1;
+    let content = match node
+    {
+        Node::File(data) => Content::File(data),
+        Node::Symlink(target) => Content::Symlink(target),
+        Node::Directory(children) => Content::Directory(
+            children.into_iter().map(|(name, child)| (name, freeze(child, next_inode))).collect(),
+        ),
+    };
+    Arc::new(TarInode { inode, content })
+}
+
+/// Inhalt eines [TarInode].
+enum Content
+{
+    File(&'static [u8]),
+    Directory(BTreeMap<String, Arc<TarInode>>),
+    Symlink(String),
+}
+
+/// Datei, Verzeichnis oder symbolischer Link im [TarFs].
+struct TarInode
+{
+    inode: u64,
+    content: Content,
+}
+
+impl Inode for TarInode
+{
+    fn metadata(&self) -> Metadata
+    {
+        let (file_type, size) = match &self.content
+        {
+            Content::File(data) => (FileType::File, data.len()),
+            Content::Directory(entries) => (FileType::Directory, entries.len()),
+            Content::Symlink(target) => (FileType::Symlink, target.len()),
+        };
+        Metadata { inode: self.inode, file_type, size: size as u64, mode: file_type.default_mode() }
+    }
+
+    fn read_at(&self, offset: u64, buffer: &mut [u8]) -> VfsResult<usize>
+    {
+        let data = match &self.content
+        {
+            Content::File(data) => data,
+            Content::Directory(_) => return Err(VfsError::IsADirectory),
+            Content::Symlink(_) => return Err(VfsError::InvalidArgument),
+        };
+        let start = usize::try_from(offset).unwrap_or(usize::MAX).min(data.len());
+        let count = buffer.len().min(data.len() - start);
+        buffer[..count].copy_from_slice(&data[start..start + count]);
+        Ok(count)
+    }
+
+    fn write_at(&self, _offset: u64, _buffer: &[u8]) -> VfsResult<usize>
+    {
+        Err(VfsError::ReadOnly)
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult<()>
+    {
+        Err(VfsError::ReadOnly)
+    }
+
+    fn lookup(&self, name: &str) -> VfsResult<Arc<dyn Inode>>
+    {
+        match &self.content
+        {
+            Content::Directory(entries) => entries.get(name).map(|inode| inode.clone() as Arc<dyn Inode>).ok_or(VfsError::NotFound),
+            _ => Err(VfsError::NotADirectory),
+        }
+    }
+
+    fn create(&self, _name: &str, _file_type: FileType) -> VfsResult<Arc<dyn Inode>>
+    {
+        match &self.content
+        {
+            Content::Directory(_) => Err(VfsError::ReadOnly),
+            _ => Err(VfsError::NotADirectory),
+        }
+    }
+
+    fn unlink(&self, _name: &str) -> VfsResult<()>
+    {
+        match &self.content
+        {
+            Content::Directory(_) => Err(VfsError::ReadOnly),
+            _ => Err(VfsError::NotADirectory),
+        }
+    }
+
+    fn readdir(&self) -> VfsResult<Vec<DirEntry>>
+    {
+        match &self.content
+        {
+            Content::Directory(entries) => Ok(entries
+                .iter()
+                .map(|(name, inode)| DirEntry { name: name.clone(), inode: inode.inode, file_type: inode.metadata().file_type })
+                .collect()),
+            _ => Err(VfsError::NotADirectory),
+        }
+    }
+
+    fn readlink(&self) -> VfsResult<String>
+    {
+        match &self.content
+        {
+            Content::Symlink(target) => Ok(target.clone()),
+            _ => Err(VfsError::InvalidArgument),
+        }
+    }
+}
+
+/// Baut einen USTAR-Kopf für die Tests.
+#[cfg(test)]
+fn test_header(name: &str, kind: u8, link: &str, size: usize) -> [u8; BLOCK_SIZE]
+{
+    use core::fmt::Write;
+
+    struct Field<'a>(&'a mut [u8], usize);
+    impl Write for Field<'_>
+    {
+        fn write_str(&mut self, text: &str) -> core::fmt::Result
+        {
+            self.0[self.1..self.1 + text.len()].copy_from_slice(text.as_bytes());
+            self.1 += text.len();
+            Ok(())
+        }
+    }
+
+    let mut header = [0; BLOCK_SIZE];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    write!(Field(&mut header[124..136], 0), "{:011o}", size).unwrap();
+    header[156] = kind;
+    header[157..157 + link.len()].copy_from_slice(link.as_bytes());
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    header[148..156].fill(b' ');
+    let sum: u32 = header.iter().map(|&byte| byte as u32).sum();
+    write!(Field(&mut header[148..156], 0), "{:06o}\0", sum).unwrap();
+    header
+}
+
+#[test_case]
+fn test_tarfs_entries()
+{
+    let mut archive = Vec::new();
+    archive.extend_from_slice(&test_header("./etc/motd", b'0', "", 5));
+    archive.extend_from_slice(b"hello");
+    archive.resize(2 * BLOCK_SIZE, 0);
+    archive.extend_from_slice(&test_header("./etc/issue", b'2', "motd", 0));
+    archive.extend_from_slice(&test_header("./etc/copy", b'1', "./etc/motd", 0));
+    archive.extend_from_slice(&[0; 2 * BLOCK_SIZE]);
+
+    let fs = TarFs::new(archive.leak()).unwrap();
+    let etc = fs.root().lookup("etc").unwrap();
+    assert_eq!(etc.metadata().file_type, FileType::Directory);
+    let names: Vec<String> = etc.readdir().unwrap().into_iter().map(|entry| entry.name).collect();
+    assert_eq!(names, ["copy", "issue", "motd"]);
+
+    let mut buffer = [0; 8];
+    assert_eq!(etc.lookup("copy").unwrap().read_at(1, &mut buffer), Ok(4));
+    assert_eq!(&buffer[..4], b"ello");
+    assert_eq!(etc.lookup("issue").unwrap().readlink().as_deref(), Ok("motd"));
+    assert_eq!(etc.create("new", FileType::File).err(), Some(VfsError::ReadOnly));
+    assert_eq!(etc.lookup("motd").unwrap().write_at(0, b"x"), Err(VfsError::ReadOnly));
+}
+
+#[test_case]
+fn test_tarfs_corrupt()
+{
+    let mut header = test_header("file", b'0', "", 600);
+    assert!(TarFs::new(Vec::from(header).leak()).is_err());
+
+    header[0] = b'g';
+    let mut archive = Vec::from(header);
+    archive.resize(3 * BLOCK_SIZE, 0);
+    assert_eq!(TarFs::new(archive.leak()).err(), Some(VfsError::Corrupt));
+}
@@ -0,0 +1,248 @@
+//! Dateisystem im Arbeitsspeicher.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use super::{DirEntry, FileSystem, FileType, Inode, Metadata, VfsError, VfsResult};
+use crate::sync::RwLock;
+
+/// ## TmpFs
+///
+/// Ein Dateisystem, dessen Dateien und Verzeichnisse nur im Kernel-Heap
+/// existieren. Es dient als Wurzeldateisystem, solange kein Datenträger
+/// eingehängt ist.
+pub struct TmpFs
+{
+    root: Arc<TmpInode>,
+}
+
+impl TmpFs
+{
+    /// Erstellt ein leeres Dateisystem mit Wurzelverzeichnis.
+    pub fn new() -> TmpFs
+    {
+        let next_inode = Arc::new(AtomicU64::new(1));
+        TmpFs { root: TmpInode::new(&next_inode, FileType::Directory) }
+    }
+}
+
+impl Default for TmpFs
+{
+    fn default() -> TmpFs
+    {
+        TmpFs::new()
+    }
+}
+
+impl FileSystem for TmpFs
+{
+    fn name(&self) -> &str
+    {
+        "tmpfs"
+    }
+
+    fn root(&self) -> Arc<dyn Inode>
+    {
+        self.root.clone()
+    }
+}
+
+/// Inhalt eines [TmpInode].
+enum Content
+{
+    File(RwLock<Vec<u8>>),
+    Directory(RwLock<BTreeMap<String, Arc<TmpInode>>>),
+}
+
+/// Datei oder Verzeichnis im [TmpFs].
+struct TmpInode
+{
+    inode: u64,
+    /// Zähler für neue Inode-Nummern, geteilt im ganzen Dateisystem.
+    next_inode: Arc<AtomicU64>,
+    content: Content,
+}
+
+impl TmpInode
+{
+    fn new(next_inode: &Arc<AtomicU64>, file_type: FileType) -> Arc<TmpInode>
+    {
+        let content = match file_type
+        {
+            FileType::Directory => Content::Directory(RwLock::new(BTreeMap::new())),
+            _ => Content::File(RwLock::new(Vec::new())),
+        };
+        Arc::new(TmpInode
+        {
+            inode: next_inode.fetch_add(1, Ordering::Relaxed),
+            next_inode: next_inode.clone(),
+            content,
+        })
+    }
+
+    fn data(&self) -> VfsResult<&RwLock<Vec<u8>>>
+    {
+        match &self.content
+        {
+            Content::File(data) => Ok(data),
+            Content::Directory(_) => Err(VfsError::IsADirectory),
+        }
+    }
+
+    fn entries(&self) -> VfsResult<&RwLock<BTreeMap<String, Arc<TmpInode>>>>
+    {
+        match &self.content
+        {
+            Content::File(_) => Err(VfsError::NotADirectory),
+            Content::Directory(entries) => Ok(entries),
+        }
+    }
+}
+
+/// Ändert die Länge von `data` auf `size` und füllt mit Nullen auf. Passt die
+/// neue Länge nicht mehr in den Heap, ergibt das [VfsError::NoSpace] statt
+/// eines Absturzes.
+fn resize(data: &mut Vec<u8>, size: usize) -> VfsResult<()>
+{
+    data.try_reserve(size.saturating_sub(data.len())).map_err(|_| VfsError::NoSpace)?;
+    data.resize(size, 0);
+    Ok(())
+}
+
+impl Inode for TmpInode
+{
+    fn metadata(&self) -> Metadata
+    {
+        let (file_type, size) = match &self.content
+        {
+            Content::File(data) => (FileType::File, data.read().len()),
+            Content::Directory(entries) => (FileType::Directory, entries.read().len()),
+        };
+        Metadata { inode: self.inode, file_type, size: size as u64, mode: file_type.default_mode() }
+    }
+
+    fn read_at(&self, offset: u64, buffer: &mut [u8]) -> VfsResult<usize>
+    {
+        let data = self.data()?.read();
+        let start = usize::try_from(offset).unwrap_or(usize::MAX).min(data.len());
+        let count = buffer.len().min(data.len() - start);
+        buffer[..count].copy_from_slice(&data[start..start + count]);
+        Ok(count)
+    }
+
+    fn write_at(&self, offset: u64, buffer: &[u8]) -> VfsResult<usize>
+    {
+        let start = usize::try_from(offset).map_err(|_| VfsError::NoSpace)?;
+        let end = start.checked_add(buffer.len()).ok_or(VfsError::NoSpace)?;
+        let mut data = self.data()?.write();
+        if data.len() < end
+        {
+            resize(&mut data, end)?;
+        }
+        data[start..end].copy_from_slice(buffer);
+        Ok(buffer.len())
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult<()>
+    {
+        let size = usize::try_from(size).map_err(|_| VfsError::NoSpace)?;
+        resize(&mut self.data()?.write(), size)
+    }
+
+    fn lookup(&self, name: &str) -> VfsResult<Arc<dyn Inode>>
+    {
+        let entries = self.entries()?.read();
+        entries.get(name).map(|inode| inode.clone() as Arc<dyn Inode>).ok_or(VfsError::NotFound)
+    }
+
+    fn create(&self, name: &str, file_type: FileType) -> VfsResult<Arc<dyn Inode>>
+    {
+        if name.is_empty() || name == "." || name == ".." || name.contains('/')
+        {
+            return Err(VfsError::InvalidPath);
+        }
+        if !matches!(file_type, FileType::File | FileType::Directory)
+        {
+            return Err(VfsError::Unsupported);
+        }
+
+        let mut entries = self.entries()?.write();
+        if entries.contains_key(name)
+        {
+            return Err(VfsError::AlreadyExists);
+        }
+        let inode = TmpInode::new(&self.next_inode, file_type);
+        entries.insert(String::from(name), inode.clone());
+        Ok(inode)
+    }
+
+    fn unlink(&self, name: &str) -> VfsResult<()>
+    {
+        let mut entries = self.entries()?.write();
+        let inode = entries.get(name).ok_or(VfsError::NotFound)?;
+        if let Content::Directory(children) = &inode.content
+            && !children.read().is_empty()
+        {
+            return Err(VfsError::NotEmpty);
+        }
+        entries.remove(name);
+        Ok(())
+    }
+
+    fn readdir(&self) -> VfsResult<Vec<DirEntry>>
+    {
+        let entries = self.entries()?.read();
+        Ok(entries
+            .iter()
+            .map(|(name, inode)| DirEntry { name: name.clone(), inode: inode.inode, file_type: inode.metadata().file_type })
+            .collect())
+    }
+}
+
+#[test_case]
+fn test_tmpfs_files()
+{
+    let fs = TmpFs::new();
+    let root = fs.root();
+    let file = root.create("file", FileType::File).unwrap();
+    assert_eq!(root.create("file", FileType::Directory).err(), Some(VfsError::AlreadyExists));
+
+    assert_eq!(file.write_at(4, b"data"), Ok(4));
+    let mut buffer = [0xff; 10];
+    assert_eq!(file.read_at(0, &mut buffer), Ok(8));
+    assert_eq!(&buffer[..8], b"\0\0\0\0data");
+    assert_eq!(file.read_at(100, &mut buffer), Ok(0));
+
+    file.truncate(2).unwrap();
+    assert_eq!(file.metadata().size, 2);
+
+    // Größer als der Heap: der Aufrufer bekommt einen Fehler, der Kernel läuft weiter.
+    assert_eq!(file.write_at(1 << 40, b"x"), Err(VfsError::NoSpace));
+    assert_eq!(file.truncate(1 << 40), Err(VfsError::NoSpace));
+    assert_eq!(file.metadata().size, 2);
+    assert_eq!(file.lookup("x").err(), Some(VfsError::NotADirectory));
+}
+
+#[test_case]
+fn test_tmpfs_directories()
+{
+    let fs = TmpFs::new();
+    let root = fs.root();
+    let directory = root.create("dir", FileType::Directory).unwrap();
+    let file = directory.create("file", FileType::File).unwrap();
+    assert_ne!(directory.metadata().inode, file.metadata().inode);
+    assert_eq!(directory.read_at(0, &mut [0; 4]), Err(VfsError::IsADirectory));
+
+    let entries = root.readdir().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].name, "dir");
+    assert_eq!(entries[0].file_type, FileType::Directory);
+
+    assert_eq!(root.unlink("dir"), Err(VfsError::NotEmpty));
+    directory.unlink("file").unwrap();
+    root.unlink("dir").unwrap();
+    assert_eq!(root.lookup("dir").err(), Some(VfsError::NotFound));
+}
@@ -12,8 +12,11 @@
 //! |-------------|--------------|
 //! | [Color] | Enthält alle 16 VGA-Farben |
 //! | [Writer] | Schreibt Text in den VGA-Puffer |
-//! | [WRITER] | Globale, mutexgeschützte Writer-Instanz |
+//! | [WRITER] | Globale, durch ein [IrqSpinlock] geschützte Writer-Instanz |
 //! | [print!], [println!] | Makros für formatierte Textausgabe |
+//! | [cursor] | Blinkender Hardware-Cursor über den CRT-Controller |
+//! | [ansi] | Parser für ANSI-Escape-Sequenzen (Farben, Cursor, Löschen) |
+//! | [screen] | Positioniertes Schreiben, Rahmen und scrollende Fenster |
 //!
 //! # Hintergrund
 //!
@@ -23,10 +26,17 @@
 //! Da Bare-Metal-Umgebungen keine std::io-Funktionen bieten,
 //! müssen Ein- und Ausgaben direkt über Speicherzugriffe erfolgen.
 
-use spin::Mutex;
+pub mod ansi;
+pub mod cursor;
+pub mod screen;
+
+pub use screen::{BoxStyle, Rect, Window};
+
 use volatile::Volatile;
 use core::fmt;
-use lazy_static::lazy_static;
+use crate::sync::{IrqSpinlock, Lazy};
+use ansi::Action;
+use cursor::CursorShape;
 
 /// Repräsentiert die 16 verfügbaren VGA-Farben.
 ///
@@ -61,14 +71,34 @@ pub enum Color
 /// die oberen 4 Bits die Hintergrundfarbe.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(transparent)]
-struct ColorCode(u8);
+pub struct ColorCode(u8);
 
 impl ColorCode 
 {
-    fn new(foreground: Color, background: Color) -> ColorCode
+    pub const fn new(foreground: Color, background: Color) -> ColorCode
     {
         ColorCode((background as u8) << 4 | (foreground as u8))
     }
+
+    const fn foreground(self) -> u8
+    {
+        self.0 & 0x0F
+    }
+
+    const fn background(self) -> u8
+    {
+        self.0 >> 4
+    }
+
+    const fn with_foreground(self, foreground: u8) -> ColorCode
+    {
+        ColorCode(self.0 & 0xF0 | foreground & 0x0F)
+    }
+
+    const fn with_background(self, background: u8) -> ColorCode
+    {
+        ColorCode((background & 0x07) << 4 | self.0 & 0x0F)
+    }
 }
 
 /// Repräsentiert ein einzelnes Zeichen im VGA-Puffer.
@@ -84,8 +114,8 @@ struct ScreenChar
 ///
 /// Jedes Feld ist als [Volatile] markiert, damit der Compiler keine
 /// Speicherzugriffe entfernt, da sie **sichtbare Nebeneffekte** haben.
-const BUFFER_HEIGHT: usize = 25;
-const BUFFER_WIDTH: usize = 80;
+pub const BUFFER_HEIGHT: usize = 25;
+pub const BUFFER_WIDTH: usize = 80;
 
 #[repr(transparent)]
 struct Buffer
@@ -93,28 +123,89 @@ struct Buffer
     chars: [[Volatile<ScreenChar>; BUFFER_WIDTH]; BUFFER_HEIGHT],
 }
 
+/// Farbe nach dem Start und nach `ESC [ 0 m`.
+const DEFAULT_COLOR: ColorCode = ColorCode::new(Color::Yellow, Color::Black);
+
+/// Die acht ANSI-Farben in der Reihenfolge ihrer SGR-Nummern (30–37 bzw.
+/// 40–47). Die hellen Varianten (90–97) liegen im [Color]-Enum jeweils 8
+/// weiter.
+const ANSI_COLORS: [Color; 8] =
+[
+    Color::Black, Color::Red, Color::Green, Color::Brown,
+    Color::Blue, Color::Magenta, Color::Cyan, Color::LightGray,
+];
+
+/// Mit `ESC 7` gesicherte Position und Farbe.
+#[derive(Debug, Clone, Copy)]
+struct SavedCursor
+{
+    row: usize,
+    column: usize,
+    color_code: ColorCode,
+    bold: bool,
+}
+
 /// ## Writer
 /// 
 /// Schreibt Zeichen in den VGA-Puffer.
 ///
 /// Der [Writer] hält:
-/// - die aktuelle Spaltenposition,
+/// - die aktuelle Zeilen- und Spaltenposition,
 /// - den aktuellen [ColorCode],
+/// - den Zustand des [ANSI-Parsers](ansi::Parser),
+/// - die Form des Hardware-Cursors, solange er sichtbar ist,
 /// - eine mutable Referenz auf den [Buffer].
+///
+/// Geschrieben wird anfangs in der untersten Zeile; erst
+/// Escape-Sequenzen bewegen die Position woandershin. Der Hardware-Cursor
+/// folgt der Schreibposition.
 pub struct Writer
 {
     column_position: usize,
+    row_position: usize,
     color_code: ColorCode,
+    /// Helle Vordergrundfarben nach `ESC [ 1 m`.
+    bold: bool,
+    saved: SavedCursor,
+    /// Erste und letzte Zeile, die beim Zeilenumbruch scrollen.
+    scroll_region: (usize, usize),
+    parser: ansi::Parser,
+    cursor: Option<CursorShape>,
     buffer: &'static mut Buffer,
 }
 
 impl Writer 
 {
+    /// Ein Writer für `buffer`, der unten links beginnt.
+    fn new(buffer: &'static mut Buffer) -> Writer
+    {
+        let saved = SavedCursor { row: BUFFER_HEIGHT - 1, column: 0, color_code: DEFAULT_COLOR, bold: false };
+        Writer
+        {
+            column_position: 0,
+            row_position: BUFFER_HEIGHT - 1,
+            color_code: DEFAULT_COLOR,
+            bold: false,
+            saved,
+            scroll_region: (0, BUFFER_HEIGHT - 1),
+            parser: ansi::Parser::new(),
+            cursor: None,
+            buffer,
+        }
+    }
+
     /// Schreibt ein einzelnes Byte in den VGA-Puffer.
     ///
     /// - Bei \n wird eine neue Zeile begonnen.
     /// - Wenn die Zeile voll ist, wird automatisch nach unten gescrollt.
     pub fn write_byte(&mut self, byte: u8)
+    {
+        self.put_byte(byte);
+        self.update_cursor();
+    }
+
+    /// Wie [Writer::write_byte], aber ohne den Cursor nachzuziehen.
+    fn put_byte(&mut self, byte: u8)
     {
         match byte
         {
@@ -125,7 +216,7 @@ impl Writer
                 {
                     self.new_line()
                 }
-                let row = BUFFER_HEIGHT -1;             //um zu wissen in welcher row man sich gerade befindet zum tracken
+                let row = self.row_position;            //um zu wissen in welcher row man sich gerade befindet zum tracken
                 let col = self.column_position;
 
                 let color_code = self.color_code;
@@ -140,45 +231,269 @@ impl Writer
 
     /// Schreibt einen String in den VGA-Puffer.
     ///
-    /// Nicht druckbare ASCII-Zeichen werden als ■ (0xfe) dargestellt.
+    /// ANSI-Escape-Sequenzen (siehe [ansi]) werden ausgeführt, `\r`, `\t`
+    /// und `\x08` bewegen die Position. Übrige nicht druckbare
+    /// ASCII-Zeichen werden als ■ (0xfe) dargestellt.
     pub fn write_string(&mut self, s: &str)
     {
         for byte in s.bytes()
         {
-            match byte
+            if let Some(action) = self.parser.advance(byte)
             {
-                //ascii byte oder newline
-                0x20..=0x7e | b'\n' => self.write_byte(byte),
-                //nicht in der ausgebbaren ascii range
-                _ =>  self.write_byte(0xfe),
+                self.apply(action);
             }
         }
+        self.update_cursor();
     }
 
-    /// Scrollt den Puffer um eine Zeile nach oben.
-    fn new_line(&mut self)
+    /// Führt eine Anweisung des [ANSI-Parsers](ansi::Parser) aus.
+    fn apply(&mut self, action: Action)
+    {
+        let last_row = BUFFER_HEIGHT - 1;
+        let last_col = BUFFER_WIDTH - 1;
+        match action
+        {
+            //ascii byte
+            Action::Print(byte @ 0x20..=0x7e) => self.put_byte(byte),
+            //nicht in der ausgebbaren ascii range
+            Action::Print(_) => self.put_byte(0xfe),
+            Action::Control(b'\n') => self.new_line(),
+            Action::Control(b'\r') => self.column_position = 0,
+            Action::Control(b'\t') =>
+            {
+                let next = (self.column_position / 8 + 1) * 8;
+                while self.column_position < next.min(BUFFER_WIDTH)
+                {
+                    self.put_byte(b' ');
+                }
+            }
+            Action::Control(0x08) => self.column_position = self.column_position.min(last_col).saturating_sub(1),
+            Action::Control(_) => (),
+            Action::CursorUp(n) => self.row_position = self.row_position.saturating_sub(n.into()),
+            Action::CursorDown(n) => self.row_position = (self.row_position + usize::from(n)).min(last_row),
+            Action::CursorForward(n) => self.column_position = (self.column_position + usize::from(n)).min(last_col),
+            Action::CursorBack(n) => self.column_position = self.column_position.min(last_col).saturating_sub(n.into()),
+            Action::CursorPosition(row, col) =>
+            {
+                self.row_position = usize::from(row).min(last_row);
+                self.column_position = usize::from(col).min(last_col);
+            }
+            Action::EraseLine(mode) =>
+            {
+                let (row, col) = (self.row_position, self.column_position);
+                match mode
+                {
+                    0 => self.clear_cells(row, col, BUFFER_WIDTH),
+                    1 => self.clear_cells(row, 0, (col + 1).min(BUFFER_WIDTH)),
+                    _ => self.clear_row(row),
+                }
+            }
+            Action::EraseScreen(mode) =>
+            {
+                let (row, col) = (self.row_position, self.column_position);
+                match mode
+                {
+                    0 =>
+                    {
+                        self.clear_cells(row, col, BUFFER_WIDTH);
+                        (row + 1..BUFFER_HEIGHT).for_each(|row| self.clear_row(row));
+                    }
+                    1 =>
+                    {
+                        (0..row).for_each(|row| self.clear_row(row));
+                        self.clear_cells(row, 0, (col + 1).min(BUFFER_WIDTH));
+                    }
+                    _ => (0..BUFFER_HEIGHT).for_each(|row| self.clear_row(row)),
+                }
+            }
+            Action::Sgr(params) => self.select_graphic_rendition(&params),
+            Action::SaveCursor =>
+            {
+                self.saved = SavedCursor { row: self.row_position, column: self.column_position, color_code: self.color_code, bold: self.bold };
+            }
+            Action::RestoreCursor =>
+            {
+                let saved = self.saved;
+                self.row_position = saved.row;
+                self.column_position = saved.column;
+                self.color_code = saved.color_code;
+                self.bold = saved.bold;
+            }
+        }
+    }
+
+    /// Setzt die Farben nach `ESC [ ... m`. Helle Hintergründe (100–107)
+    /// werden zu den normalen, da Bit 7 des Farbbytes den Text blinken lässt.
+    fn select_graphic_rendition(&mut self, params: &ansi::Params)
     {
-        for row in 1..BUFFER_HEIGHT
+        let mut values = params.iter().peekable();
+        // `ESC [ m` steht für `ESC [ 0 m`.
+        if values.peek().is_none()
         {
-            for col in 0..BUFFER_WIDTH
+            self.color_code = DEFAULT_COLOR;
+            self.bold = false;
+        }
+        while let Some(value) = values.next()
+        {
+            let bright = if self.bold { 8 } else { 0 };
+            match value
             {
-                let character = self.buffer.chars[row][col].read();
-                self.buffer.chars[row - 1][col].write(character);
+                0 =>
+                {
+                    self.color_code = DEFAULT_COLOR;
+                    self.bold = false;
+                }
+                1 =>
+                {
+                    self.bold = true;
+                    self.color_code = self.color_code.with_foreground(self.color_code.foreground() | 8);
+                }
+                22 =>
+                {
+                    self.bold = false;
+                    self.color_code = self.color_code.with_foreground(self.color_code.foreground() & 7);
+                }
+                30..=37 => self.color_code = self.color_code.with_foreground(ANSI_COLORS[usize::from(value - 30)] as u8 | bright),
+                39 => self.color_code = self.color_code.with_foreground(DEFAULT_COLOR.foreground() | bright),
+                40..=47 => self.color_code = self.color_code.with_background(ANSI_COLORS[usize::from(value - 40)] as u8),
+                49 => self.color_code = self.color_code.with_background(DEFAULT_COLOR.background()),
+                90..=97 => self.color_code = self.color_code.with_foreground(ANSI_COLORS[usize::from(value - 90)] as u8 | 8),
+                100..=107 => self.color_code = self.color_code.with_background(ANSI_COLORS[usize::from(value - 100)] as u8),
+                // 256 Farben (`38;5;n`) und RGB (`38;2;r;g;b`) gibt es nicht;
+                // ihre Parameter werden übersprungen.
+                38 | 48 =>
+                {
+                    let skip = match values.next()
+                    {
+                        Some(5) => 1,
+                        Some(2) => 3,
+                        _ => 0,
+                    };
+                    values.by_ref().take(skip).for_each(drop);
+                }
+                _ => (),
             }
         }
-        self.clear_row(BUFFER_HEIGHT -1);
+    }
+
+    /// Spalte, in die das nächste Zeichen geschrieben wird.
+    pub fn column(&self) -> usize
+    {
+        self.column_position
+    }
+
+    /// Setzt die Schreibposition innerhalb der aktuellen Zeile. Folgende
+    /// Zeichen überschreiben, was dort steht.
+    pub fn set_column(&mut self, column: usize)
+    {
+        self.column_position = column.min(BUFFER_WIDTH);
+        self.update_cursor();
+    }
+
+    /// Löscht die aktuelle Zeile ab der Schreibposition.
+    pub fn clear_to_end(&mut self)
+    {
+        self.clear_cells(self.row_position, self.column_position, BUFFER_WIDTH);
+    }
+
+    /// Löscht den ganzen Bildschirm und beginnt wieder am Anfang der
+    /// untersten Zeile des Scrollbereichs.
+    pub fn clear_screen(&mut self)
+    {
+        for row in 0..BUFFER_HEIGHT
+        {
+            self.clear_row(row);
+        }
+        self.row_position = self.scroll_region.1;
+        self.column_position = 0;
+        self.update_cursor();
+    }
+
+    /// Beschränkt das Scrollen auf die Zeilen `top..=bottom` und setzt die
+    /// Schreibposition an den Anfang von `bottom`. Die Zeilen außerhalb
+    /// bleiben stehen, etwa für eine Statuszeile. Ungültige Bereiche werden
+    /// ignoriert.
+    pub fn set_scroll_region(&mut self, top: usize, bottom: usize)
+    {
+        if top > bottom || bottom >= BUFFER_HEIGHT
+        {
+            return;
+        }
+        self.scroll_region = (top, bottom);
+        self.row_position = bottom;
+        self.column_position = 0;
+        self.update_cursor();
+    }
+
+    /// Erste und letzte Zeile des Scrollbereichs.
+    pub fn scroll_region(&self) -> (usize, usize)
+    {
+        self.scroll_region
+    }
+
+    /// Blendet den Hardware-Cursor mit der zuletzt gesetzten Form ein.
+    pub fn show_cursor(&mut self)
+    {
+        let shape = self.cursor.unwrap_or(CursorShape::UNDERLINE);
+        self.set_cursor_shape(shape);
+    }
+
+    /// Blendet den Hardware-Cursor aus.
+    pub fn hide_cursor(&mut self)
+    {
+        self.cursor = None;
+        cursor::disable();
+    }
+
+    /// Setzt die Bildzeilen des Cursors und blendet ihn ein.
+    pub fn set_cursor_shape(&mut self, shape: CursorShape)
+    {
+        self.cursor = Some(shape);
+        cursor::enable(shape);
+        self.update_cursor();
+    }
+
+    /// Setzt den Hardware-Cursor auf die Schreibposition. Am Zeilenende
+    /// bleibt er auf dem letzten Zeichen.
+    fn update_cursor(&mut self)
+    {
+        if self.cursor.is_some()
+        {
+            cursor::set_position(self.row_position, self.column_position);
+        }
+    }
+
+    /// Beginnt eine neue Zeile. In der letzten Zeile des Scrollbereichs
+    /// wird dieser dazu um eine Zeile nach oben gescrollt.
+    fn new_line(&mut self)
+    {
         self.column_position = 0;
+        let (top, bottom) = self.scroll_region;
+        if self.row_position == bottom
+        {
+            self.scroll_up(Rect::new(top, 0, bottom - top + 1, BUFFER_WIDTH), self.color_code);
+        }
+        else if self.row_position < BUFFER_HEIGHT - 1
+        {
+            self.row_position += 1;
+        }
     }
 
     /// Löscht den Inhalt einer bestimmten Zeile.
     fn clear_row(&mut self, row: usize)
+    {
+        self.clear_cells(row, 0, BUFFER_WIDTH);
+    }
+
+    /// Löscht die Spalten `start..end` einer Zeile in der aktuellen Farbe.
+    fn clear_cells(&mut self, row: usize, start: usize, end: usize)
     {
         let blank = ScreenChar
         {
             ascii_character: b' ',
             color_code: self.color_code,
         };
-        for col in 0..BUFFER_WIDTH
+        for col in start..end
         {
             self.buffer.chars[row][col].write(blank);
         }
@@ -196,21 +511,19 @@ impl fmt::Write for Writer
     }
 }
 
-lazy_static!
+/// Globale Writer-Instanz.
+///
+/// Wird **lazy** initialisiert, um Reihenfolgeprobleme während des
+/// Programmstarts zu vermeiden.  
+/// Das [IrqSpinlock] stellt sicher, dass immer nur ein Kontext gleichzeitig
+/// auf den Writer zugreift, und deaktiviert dabei die Interrupts, sodass auch
+/// Interrupt-Handler gefahrlos auf den Bildschirm schreiben können.
+pub static WRITER: Lazy<IrqSpinlock<Writer>> = Lazy::new(||
 {
-    /// Globale Writer-Instanz.
-    ///
-    /// Wird **lazy** initialisiert, um Reihenfolgeprobleme während des
-    /// Programmstarts zu vermeiden.  
-    /// Der [Mutex] stellt sicher, dass immer nur ein Thread gleichzeitig
-    /// auf den Writer zugreift.
-    pub static ref WRITER: Mutex<Writer> = Mutex::new(Writer
-    {
-        column_position: 0,
-        color_code: ColorCode::new(Color::Yellow, Color::Black),
-        buffer: unsafe { &mut *(0xb8000 as *mut Buffer) }, //what the helly🤨 => mit einem raw pointer auf die Speicheradresse für VGA zeigen (es ist sicher das es dort liegt)
-    });
-}
+    let mut writer = Writer::new(unsafe { &mut *(0xb8000 as *mut Buffer) }); //what the helly🤨 => mit einem raw pointer auf die Speicheradresse für VGA zeigen (es ist sicher das es dort liegt)
+    writer.show_cursor();
+    IrqSpinlock::new(writer)
+});
 
 /// Gibt Text auf den VGA-Puffer aus.
 ///
@@ -232,15 +545,11 @@ macro_rules! println {
 
 #[doc(hidden)]
 /// Interne Hilfsfunktion zum Schreiben formatierten Textes.
-/// without_interrupts nimmt eine Closure
+/// Das [IrqSpinlock] deaktiviert die Interrupts, solange geschrieben wird.
 pub fn _print(args: fmt::Arguments) {
     use core::fmt::Write;
-    use x86_64::instructions::interrupts;
 
-    interrupts::without_interrupts(||
-    {
-        WRITER.lock().write_fmt(args).unwrap();
-    });
+    WRITER.lock().write_fmt(args).unwrap();
 }
 
 /// ## Tests   
@@ -290,4 +599,66 @@ fn test_println_output()
             assert_eq!(char::from(screen_char.ascii_character), c);
         }
     });
-}
\ No newline at end of file
+}
+
+#[test_case]
+fn test_cursor_follows_writer()
+{
+    use core::fmt::Write;
+
+    let mut writer = WRITER.lock();
+    writeln!(writer).unwrap();
+    write!(writer, "abc").unwrap();
+    assert!(cursor::is_enabled());
+    assert_eq!(cursor::position(), (BUFFER_HEIGHT - 1, 3));
+
+    writer.set_column(1);
+    assert_eq!(cursor::position(), (BUFFER_HEIGHT - 1, 1));
+    writer.hide_cursor();
+    assert!(!cursor::is_enabled());
+    writer.set_cursor_shape(CursorShape::BLOCK);
+    assert!(cursor::is_enabled());
+    writer.set_cursor_shape(CursorShape::UNDERLINE);
+}
+
+/// Ein [Writer] auf einem eigenen Puffer im Heap, damit Tests den
+/// Bildschirm nicht verstellen.
+#[cfg(test)]
+fn test_writer() -> Writer
+{
+    use alloc::boxed::Box;
+
+    let blank = ScreenChar { ascii_character: b' ', color_code: DEFAULT_COLOR };
+    let buffer = Box::new(Buffer { chars: core::array::from_fn(|_| core::array::from_fn(|_| Volatile::new(blank))) });
+    Writer::new(Box::leak(buffer))
+}
+
+#[test_case]
+fn test_ansi_sequences()
+{
+    let mut writer = test_writer();
+    let cell = |writer: &Writer, row: usize, col: usize| writer.buffer.chars[row][col].read();
+
+    // Position ab 1, dann helles Rot, Reset und blauer Hintergrund.
+    writer.write_string("\x1b[2;3Hab\x1b[31;1mc\x1b[0m\x1b[44md");
+    assert_eq!(cell(&writer, 1, 2), ScreenChar { ascii_character: b'a', color_code: DEFAULT_COLOR });
+    assert_eq!(cell(&writer, 1, 4), ScreenChar { ascii_character: b'c', color_code: ColorCode::new(Color::LightRed, Color::Black) });
+    assert_eq!(cell(&writer, 1, 5), ScreenChar { ascii_character: b'd', color_code: ColorCode::new(Color::Yellow, Color::Blue) });
+
+    // Sichern, woanders schreiben und einen Teil der Zeile löschen,
+    // Wiederherstellen samt Farbe.
+    writer.write_string("\x1b7\x1b[m\x1b[5;1Hxyz\x1b[2D\x1b[K\x1b8e");
+    assert_eq!(cell(&writer, 4, 0).ascii_character, b'x');
+    assert_eq!(cell(&writer, 4, 1).ascii_character, b' ');
+    assert_eq!(cell(&writer, 1, 6), ScreenChar { ascii_character: b'e', color_code: ColorCode::new(Color::Yellow, Color::Blue) });
+
+    // Nicht druckbare Zeichen und ein Zeilenumbruch in der Mitte.
+    writer.write_string("\x1b[0m\u{e4}\n\tt");
+    assert_eq!(cell(&writer, 1, 7).ascii_character, 0xfe);
+    assert_eq!(cell(&writer, 1, 8).ascii_character, 0xfe);
+    assert_eq!(cell(&writer, 2, 8).ascii_character, b't');
+
+    writer.write_string("\x1b[2J");
+    assert!((0..BUFFER_HEIGHT).all(|row| (0..BUFFER_WIDTH).all(|col| cell(&writer, row, col).ascii_character == b' ')));
+    assert_eq!((writer.row_position, writer.column_position), (2, 9));
+}
@@ -0,0 +1,249 @@
+//! Parser für ANSI/VT100-Escape-Sequenzen.
+//!
+//! Der [Parser] zerlegt den Bytestrom in druckbare Bytes, Steuerzeichen und
+//! Sequenzen; umgesetzt werden sie vom [Writer](super::Writer). Unterstützt
+//! werden:
+//!
+//! | Sequenz | Wirkung |
+//! |---------|---------|
+//! | `ESC [ n A`, `B`, `C`, `D` | Cursor `n` Zeilen/Spalten hoch, runter, vor, zurück |
+//! | `ESC [ r ; c H`, `f` | Cursor auf Zeile `r`, Spalte `c` (ab 1) |
+//! | `ESC [ n K` | Zeile löschen: ab Cursor (0), bis Cursor (1), ganz (2) |
+//! | `ESC [ n J` | Bildschirm löschen, ebenso |
+//! | `ESC [ ... m` | Farben und Helligkeit (SGR) |
+//! | `ESC 7`, `ESC [ s` / `ESC 8`, `ESC [ u` | Cursor sichern / wiederherstellen |
+//!
+//! Unbekannte Sequenzen, private wie `ESC [ ? 25 l` und die Wahl des
+//! Zeichensatzes (`ESC ( B`) werden vollständig gelesen und verworfen.
+
+/// Höchstzahl der Parameter einer Sequenz; weitere werden ignoriert.
+pub const MAX_PARAMS: usize = 16;
+
+/// Die Zahlenparameter einer CSI-Sequenz. Fehlende Parameter sind 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Params
+{
+    values: [u16; MAX_PARAMS],
+    len: usize,
+}
+
+impl Params
+{
+    const fn new() -> Params
+    {
+        Params { values: [0; MAX_PARAMS], len: 0 }
+    }
+
+    /// Die Parameter der Reihe nach.
+    pub fn iter(&self) -> impl Iterator<Item = u16> + '_
+    {
+        self.values[..self.len].iter().copied()
+    }
+
+    /// Parameter `index`, oder `default`, wenn er fehlt oder 0 ist.
+    pub fn get_or(&self, index: usize, default: u16) -> u16
+    {
+        match self.values[..self.len].get(index)
+        {
+            Some(&value) if value != 0 => value,
+            _ => default,
+        }
+    }
+}
+
+/// Was der [Writer](super::Writer) tun soll.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action
+{
+    /// Ein Byte ausgeben.
+    Print(u8),
+    /// Ein Steuerzeichen wie `\n`, `\r`, `\t` oder `\x08`.
+    Control(u8),
+    CursorUp(u16),
+    CursorDown(u16),
+    CursorForward(u16),
+    CursorBack(u16),
+    /// Zeile und Spalte ab 0.
+    CursorPosition(u16, u16),
+    EraseLine(u16),
+    EraseScreen(u16),
+    /// Farben setzen (Select Graphic Rendition).
+    Sgr(Params),
+    SaveCursor,
+    RestoreCursor,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State
+{
+    Ground,
+    /// Nach `ESC`.
+    Escape,
+    /// Nach `ESC (` oder `ESC )`; das nächste Byte wählt einen Zeichensatz.
+    Charset,
+    /// Nach `ESC [`; `private` bei Sequenzen wie `ESC [ ? 25 h`.
+    Csi { params: Params, private: bool },
+}
+
+/// ## Parser
+///
+/// Zustandsautomat nach dem Vorbild des VT100. Sequenzen dürfen über
+/// mehrere Aufrufe verteilt ankommen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Parser
+{
+    state: State,
+}
+
+impl Default for Parser
+{
+    fn default() -> Parser
+    {
+        Parser::new()
+    }
+}
+
+impl Parser
+{
+    pub const fn new() -> Parser
+    {
+        Parser { state: State::Ground }
+    }
+
+    /// Verarbeitet ein Byte.
+    pub fn advance(&mut self, byte: u8) -> Option<Action>
+    {
+        match self.state
+        {
+            State::Ground => match byte
+            {
+                0x1b =>
+                {
+                    self.state = State::Escape;
+                    None
+                }
+                b'\n' | b'\r' | b'\t' | 0x08 => Some(Action::Control(byte)),
+                _ => Some(Action::Print(byte)),
+            },
+            State::Escape =>
+            {
+                self.state = State::Ground;
+                match byte
+                {
+                    b'[' =>
+                    {
+                        self.state = State::Csi { params: Params::new(), private: false };
+                        None
+                    }
+                    b'(' | b')' =>
+                    {
+                        self.state = State::Charset;
+                        None
+                    }
+                    b'7' => Some(Action::SaveCursor),
+                    b'8' => Some(Action::RestoreCursor),
+                    _ => None,
+                }
+            }
+            State::Charset =>
+            {
+                self.state = State::Ground;
+                None
+            }
+            State::Csi { mut params, private } => match byte
+            {
+                b'0'..=b'9' =>
+                {
+                    if params.len == 0
+                    {
+                        params.len = 1;
+                    }
+                    if let Some(value) = params.values.get_mut(params.len - 1)
+                    {
+                        *value = value.saturating_mul(10).saturating_add(u16::from(byte - b'0'));
+                    }
+                    self.state = State::Csi { params, private };
+                    None
+                }
+                b';' =>
+                {
+                    // Ein leerer erster Parameter zählt auch.
+                    params.len = (params.len.max(1) + 1).min(MAX_PARAMS + 1);
+                    self.state = State::Csi { params, private };
+                    None
+                }
+                b'<'..=b'?' =>
+                {
+                    self.state = State::Csi { params, private: true };
+                    None
+                }
+                // Zwischenbytes werden überlesen.
+                0x20..=0x2f =>
+                {
+                    None
+                }
+                0x40..=0x7e =>
+                {
+                    self.state = State::Ground;
+                    params.len = params.len.min(MAX_PARAMS);
+                    if private
+                    {
+                        return None;
+                    }
+                    Self::dispatch(byte, params)
+                }
+                // Steuerzeichen mitten in der Sequenz werden wie im VT100 ausgeführt.
+                b'\n' | b'\r' | b'\t' | 0x08 => Some(Action::Control(byte)),
+                _ =>
+                {
+                    self.state = State::Ground;
+                    None
+                }
+            },
+        }
+    }
+
+    fn dispatch(final_byte: u8, params: Params) -> Option<Action>
+    {
+        let count = params.get_or(0, 1);
+        Some(match final_byte
+        {
+            b'A' => Action::CursorUp(count),
+            b'B' => Action::CursorDown(count),
+            b'C' => Action::CursorForward(count),
+            b'D' => Action::CursorBack(count),
+            b'H' | b'f' => Action::CursorPosition(params.get_or(0, 1) - 1, params.get_or(1, 1) - 1),
+            b'K' => Action::EraseLine(params.get_or(0, 0)),
+            b'J' => Action::EraseScreen(params.get_or(0, 0)),
+            b'm' => Action::Sgr(params),
+            b's' => Action::SaveCursor,
+            b'u' => Action::RestoreCursor,
+            _ => return None,
+        })
+    }
+}
+
+#[test_case]
+fn test_parser()
+{
+    use alloc::vec::Vec;
+
+    let mut parser = Parser::new();
+    let mut parse = |bytes: &[u8]| bytes.iter().filter_map(|byte| parser.advance(*byte)).collect::<Vec<_>>();
+
+    assert_eq!(parse(b"a\n"), [Action::Print(b'a'), Action::Control(b'\n')]);
+    assert_eq!(parse(b"\x1b[A\x1b[3C\x1b[;5H\x1b[2J\x1b[K"), [
+        Action::CursorUp(1), Action::CursorForward(3), Action::CursorPosition(0, 4), Action::EraseScreen(2), Action::EraseLine(0),
+    ]);
+    assert_eq!(parse(b"\x1b7\x1b[u\x1b[?25l\x1b(Bx"), [Action::SaveCursor, Action::RestoreCursor, Action::Print(b'x')]);
+
+    // Über zwei Aufrufe verteilt.
+    assert_eq!(parse(b"\x1b[1;3"), []);
+    let sgr = parse(b"1m");
+    let [Action::Sgr(params)] = sgr.as_slice()
+    else
+    {
+        panic!("expected SGR, got {:?}", sgr);
+    };
+    assert_eq!(params.iter().collect::<Vec<_>>(), [1, 31]);
+}
@@ -0,0 +1,108 @@
+//! Hardware-Cursor über den CRT-Controller.
+//!
+//! Der CRT-Controller der VGA-Karte wird über ein Indexregister (Port
+//! `0x3D4`) und ein Datenregister (Port `0x3D5`) angesprochen:
+//!
+//! | Index | Register |
+//! |-------|----------|
+//! | `0x0A` | Erste Bildzeile des Cursors, Bit 5 blendet ihn aus |
+//! | `0x0B` | Letzte Bildzeile des Cursors |
+//! | `0x0E`, `0x0F` | Position als `row * BUFFER_WIDTH + col`, oberes und unteres Byte |
+//!
+//! Ein Zeichen ist im Textmodus 16 Bildzeilen hoch; der übliche Strich
+//! unter dem Zeichen liegt auf den Zeilen 14 und 15.
+
+use x86_64::instructions::port::Port;
+
+use super::{BUFFER_HEIGHT, BUFFER_WIDTH};
+
+/// Letzte Bildzeile eines Zeichens.
+pub const MAX_SCANLINE: u8 = 15;
+
+mod register
+{
+    pub const CURSOR_START: u8 = 0x0A;
+    pub const CURSOR_END: u8 = 0x0B;
+    pub const LOCATION_HIGH: u8 = 0x0E;
+    pub const LOCATION_LOW: u8 = 0x0F;
+}
+
+/// Bit im Register [register::CURSOR_START], das den Cursor ausblendet.
+const DISABLE: u8 = 0x20;
+
+/// ## CursorShape
+///
+/// Die Bildzeilen `start..=end`, die der Cursor innerhalb eines Zeichens
+/// füllt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CursorShape
+{
+    pub start: u8,
+    pub end: u8,
+}
+
+impl CursorShape
+{
+    /// Strich unter dem Zeichen, wie vom BIOS eingestellt.
+    pub const UNDERLINE: CursorShape = CursorShape { start: 14, end: 15 };
+
+    /// Das ganze Zeichen.
+    pub const BLOCK: CursorShape = CursorShape { start: 0, end: MAX_SCANLINE };
+}
+
+fn read(index: u8) -> u8
+{
+    unsafe
+    {
+        Port::new(0x3D4).write(index);
+        Port::new(0x3D5).read()
+    }
+}
+
+fn write(index: u8, value: u8)
+{
+    unsafe
+    {
+        Port::new(0x3D4).write(index);
+        Port::new(0x3D5).write(value);
+    }
+}
+
+/// Blendet den Cursor mit `shape` ein. Zeilen über [MAX_SCANLINE] werden
+/// gekürzt; die übrigen Bits der Register bleiben erhalten.
+pub fn enable(shape: CursorShape)
+{
+    let start = shape.start.min(MAX_SCANLINE);
+    let end = shape.end.min(MAX_SCANLINE);
+    write(register::CURSOR_START, read(register::CURSOR_START) & 0xC0 | start);
+    write(register::CURSOR_END, read(register::CURSOR_END) & 0xE0 | end);
+}
+
+/// Blendet den Cursor aus.
+pub fn disable()
+{
+    write(register::CURSOR_START, DISABLE);
+}
+
+/// Ob der Cursor eingeblendet ist.
+pub fn is_enabled() -> bool
+{
+    read(register::CURSOR_START) & DISABLE == 0
+}
+
+/// Setzt den Cursor auf `row`/`col`; Werte außerhalb des Bildschirms
+/// werden auf den Rand begrenzt.
+pub fn set_position(row: usize, col: usize)
+{
+    let location = row.min(BUFFER_HEIGHT - 1) * BUFFER_WIDTH + col.min(BUFFER_WIDTH - 1);
+    let [low, high] = (location as u16).to_le_bytes();
+    write(register::LOCATION_HIGH, high);
+    write(register::LOCATION_LOW, low);
+}
+
+/// Die aktuelle Position als `(row, col)`.
+pub fn position() -> (usize, usize)
+{
+    let location = usize::from(u16::from_le_bytes([read(register::LOCATION_LOW), read(register::LOCATION_HIGH)]));
+    (location / BUFFER_WIDTH, location % BUFFER_WIDTH)
+}
@@ -0,0 +1,363 @@
+//! Vollbild-Zugriff auf den Textpuffer.
+//!
+//! Neben der fortlaufenden Ausgabe kann der [Writer] einzelne Zellen
+//! beschreiben, Rechtecke füllen und Rahmen aus den Linienzeichen der
+//! Codepage 437 zeichnen. Ein [Window] ist ein Ausschnitt mit eigener
+//! Schreibposition, der für sich scrollt; zusammen mit
+//! [Writer::set_scroll_region] lassen sich so Oberflächen aus Statuszeile,
+//! Rahmen und Logbereich bauen:
+//!
+//! ```ignore
+//! let mut writer = WRITER.lock();
+//! writer.set_scroll_region(1, BUFFER_HEIGHT - 1);
+//! writer.write_at(0, 0, " simple_os ", ColorCode::new(Color::Black, Color::LightGray));
+//! writer.draw_box(Rect::new(2, 40, 10, 30), BoxStyle::Double, ColorCode::new(Color::White, Color::Blue));
+//! let mut stats = Window::new(Rect::new(2, 40, 10, 30).inner(), ColorCode::new(Color::White, Color::Blue));
+//! stats.clear(&mut writer);
+//! stats.write(&mut writer, "ticks: 42\n");
+//! ```
+//!
+//! Zeilen und Spalten zählen ab 0; was außerhalb des Bildschirms liegt, wird
+//! abgeschnitten.
+
+use core::fmt;
+
+use super::{BUFFER_HEIGHT, BUFFER_WIDTH, ColorCode, ScreenChar, WRITER, Writer};
+
+/// ## Rect
+///
+/// Ein Rechteck auf dem Bildschirm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect
+{
+    pub row: usize,
+    pub col: usize,
+    pub height: usize,
+    pub width: usize,
+}
+
+impl Rect
+{
+    pub const fn new(row: usize, col: usize, height: usize, width: usize) -> Rect
+    {
+        Rect { row, col, height, width }
+    }
+
+    /// Der ganze Bildschirm.
+    pub const fn screen() -> Rect
+    {
+        Rect::new(0, 0, BUFFER_HEIGHT, BUFFER_WIDTH)
+    }
+
+    /// Das Innere ohne den äußeren Rand, z. B. innerhalb eines Rahmens.
+    pub const fn inner(self) -> Rect
+    {
+        Rect::new(self.row + 1, self.col + 1, self.height.saturating_sub(2), self.width.saturating_sub(2))
+    }
+
+    /// Der Teil, der auf dem Bildschirm liegt.
+    pub fn clip(self) -> Rect
+    {
+        let row = self.row.min(BUFFER_HEIGHT);
+        let col = self.col.min(BUFFER_WIDTH);
+        Rect::new(row, col, self.height.min(BUFFER_HEIGHT - row), self.width.min(BUFFER_WIDTH - col))
+    }
+}
+
+/// ## BoxStyle
+///
+/// Linienzeichen eines Rahmens aus der Codepage 437.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoxStyle
+{
+    /// `┌─┐ │ └─┘`
+    Single,
+    /// `╔═╗ ║ ╚═╝`
+    Double,
+}
+
+impl BoxStyle
+{
+    /// Ecken oben links, oben rechts, unten links, unten rechts, dann waagerechte
+    /// und senkrechte Linie.
+    const fn glyphs(self) -> [u8; 6]
+    {
+        match self
+        {
+            BoxStyle::Single => [0xDA, 0xBF, 0xC0, 0xD9, 0xC4, 0xB3],
+            BoxStyle::Double => [0xC9, 0xBB, 0xC8, 0xBC, 0xCD, 0xBA],
+        }
+    }
+}
+
+/// Das Byte für `character` in der Codepage 437; außerhalb von ASCII ■.
+fn cp437(character: char) -> u8
+{
+    match character
+    {
+        ' '..='~' => character as u8,
+        _ => 0xfe,
+    }
+}
+
+impl Writer
+{
+    /// Schreibt das Byte `byte` der Codepage 437 an `row`/`col`.
+    pub fn put_char(&mut self, row: usize, col: usize, byte: u8, color: ColorCode)
+    {
+        if row < BUFFER_HEIGHT && col < BUFFER_WIDTH
+        {
+            self.buffer.chars[row][col].write(ScreenChar { ascii_character: byte, color_code: color });
+        }
+    }
+
+    /// Zeichen und Farbe an `row`/`col`.
+    pub fn char_at(&self, row: usize, col: usize) -> Option<(u8, ColorCode)>
+    {
+        let cell = self.buffer.chars.get(row)?.get(col)?.read();
+        Some((cell.ascii_character, cell.color_code))
+    }
+
+    /// Ändert nur die Farbe an `row`/`col`.
+    pub fn set_color_at(&mut self, row: usize, col: usize, color: ColorCode)
+    {
+        if let Some((byte, _)) = self.char_at(row, col)
+        {
+            self.put_char(row, col, byte, color);
+        }
+    }
+
+    /// Schreibt `text` ab `row`/`col` ohne Umbruch, Escape-Sequenzen und
+    /// ohne die Schreibposition zu ändern. Gibt die Anzahl der Zeichen
+    /// zurück, die auf die Zeile gepasst haben.
+    pub fn write_at(&mut self, row: usize, col: usize, text: &str, color: ColorCode) -> usize
+    {
+        let mut written = 0;
+        for (offset, character) in text.chars().enumerate().take(BUFFER_WIDTH.saturating_sub(col))
+        {
+            self.put_char(row, col + offset, cp437(character), color);
+            written += 1;
+        }
+        if row < BUFFER_HEIGHT { written } else { 0 }
+    }
+
+    /// Füllt `rect` mit `byte`.
+    pub fn fill(&mut self, rect: Rect, byte: u8, color: ColorCode)
+    {
+        let rect = rect.clip();
+        for row in rect.row..rect.row + rect.height
+        {
+            for col in rect.col..rect.col + rect.width
+            {
+                self.put_char(row, col, byte, color);
+            }
+        }
+    }
+
+    /// Zeichnet einen Rahmen auf den Rand von `rect`; das Innere bleibt
+    /// unverändert. Rechtecke unter 2×2 Zellen werden nicht gezeichnet.
+    pub fn draw_box(&mut self, rect: Rect, style: BoxStyle, color: ColorCode)
+    {
+        if rect.height < 2 || rect.width < 2
+        {
+            return;
+        }
+        let [top_left, top_right, bottom_left, bottom_right, horizontal, vertical] = style.glyphs();
+        let (top, bottom) = (rect.row, rect.row + rect.height - 1);
+        let (left, right) = (rect.col, rect.col + rect.width - 1);
+        for col in left + 1..right
+        {
+            self.put_char(top, col, horizontal, color);
+            self.put_char(bottom, col, horizontal, color);
+        }
+        for row in top + 1..bottom
+        {
+            self.put_char(row, left, vertical, color);
+            self.put_char(row, right, vertical, color);
+        }
+        self.put_char(top, left, top_left, color);
+        self.put_char(top, right, top_right, color);
+        self.put_char(bottom, left, bottom_left, color);
+        self.put_char(bottom, right, bottom_right, color);
+    }
+
+    /// Schiebt den Inhalt von `rect` um eine Zeile nach oben und füllt die
+    /// letzte Zeile mit Leerzeichen in `color`.
+    pub fn scroll_up(&mut self, rect: Rect, color: ColorCode)
+    {
+        let rect = rect.clip();
+        if rect.height == 0
+        {
+            return;
+        }
+        for row in rect.row + 1..rect.row + rect.height
+        {
+            for col in rect.col..rect.col + rect.width
+            {
+                let character = self.buffer.chars[row][col].read();
+                self.buffer.chars[row - 1][col].write(character);
+            }
+        }
+        self.fill(Rect::new(rect.row + rect.height - 1, rect.col, 1, rect.width), b' ', color);
+    }
+}
+
+/// ## Window
+///
+/// Ein Ausschnitt des Bildschirms mit eigener Schreibposition und Farbe.
+/// Text wird am rechten Rand umgebrochen; ist die letzte Zeile voll, scrollt
+/// nur der Inhalt des Fensters.
+///
+/// Die Methoden nehmen den [Writer] entgegen, damit mehrere Fenster unter
+/// einer Sperre gezeichnet werden können; `write!` auf das Fenster sperrt
+/// [WRITER] selbst.
+#[derive(Debug, Clone)]
+pub struct Window
+{
+    rect: Rect,
+    row: usize,
+    col: usize,
+    color: ColorCode,
+}
+
+impl Window
+{
+    /// Ein Fenster über `rect`, das oben links beginnt.
+    pub fn new(rect: Rect, color: ColorCode) -> Window
+    {
+        Window { rect: rect.clip(), row: 0, col: 0, color }
+    }
+
+    pub fn rect(&self) -> Rect
+    {
+        self.rect
+    }
+
+    /// Schreibposition innerhalb des Fensters als `(row, col)`.
+    pub fn position(&self) -> (usize, usize)
+    {
+        (self.row, self.col)
+    }
+
+    pub fn set_color(&mut self, color: ColorCode)
+    {
+        self.color = color;
+    }
+
+    /// Füllt das Fenster mit Leerzeichen und beginnt wieder oben links.
+    pub fn clear(&mut self, writer: &mut Writer)
+    {
+        writer.fill(self.rect, b' ', self.color);
+        self.row = 0;
+        self.col = 0;
+    }
+
+    /// Schreibt `text` an der Schreibposition; `\n` beginnt eine neue Zeile,
+    /// `\r` kehrt an ihren Anfang zurück.
+    pub fn write(&mut self, writer: &mut Writer, text: &str)
+    {
+        if self.rect.width == 0 || self.rect.height == 0
+        {
+            return;
+        }
+        for character in text.chars()
+        {
+            match character
+            {
+                '\n' => self.new_line(writer),
+                '\r' => self.col = 0,
+                _ =>
+                {
+                    if self.col == self.rect.width
+                    {
+                        self.new_line(writer);
+                    }
+                    writer.put_char(self.rect.row + self.row, self.rect.col + self.col, cp437(character), self.color);
+                    self.col += 1;
+                }
+            }
+        }
+    }
+
+    fn new_line(&mut self, writer: &mut Writer)
+    {
+        self.col = 0;
+        if self.row + 1 < self.rect.height
+        {
+            self.row += 1;
+        }
+        else
+        {
+            writer.scroll_up(self.rect, self.color);
+        }
+    }
+}
+
+impl fmt::Write for Window
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result
+    {
+        self.write(&mut WRITER.lock(), s);
+        Ok(())
+    }
+}
+
+#[test_case]
+fn test_boxes_and_positioned_writes()
+{
+    use super::Color;
+
+    let mut writer = super::test_writer();
+    let color = ColorCode::new(Color::White, Color::Blue);
+    writer.draw_box(Rect::new(1, 2, 3, 5), BoxStyle::Double, color);
+    assert_eq!(writer.char_at(1, 2), Some((0xC9, color)));
+    assert_eq!(writer.char_at(1, 4), Some((0xCD, color)));
+    assert_eq!(writer.char_at(2, 6), Some((0xBA, color)));
+    assert_eq!(writer.char_at(3, 6), Some((0xBC, color)));
+    assert_eq!(writer.char_at(2, 4).map(|(byte, _)| byte), Some(b' '));
+
+    assert_eq!(writer.write_at(0, BUFFER_WIDTH - 2, "abc\u{e4}", color), 2);
+    assert_eq!(writer.char_at(0, BUFFER_WIDTH - 1), Some((b'b', color)));
+    assert_eq!(writer.write_at(BUFFER_HEIGHT, 0, "x", color), 0);
+    writer.set_color_at(0, BUFFER_WIDTH - 1, super::DEFAULT_COLOR);
+    assert_eq!(writer.char_at(0, BUFFER_WIDTH - 1), Some((b'b', super::DEFAULT_COLOR)));
+    assert_eq!(writer.char_at(BUFFER_HEIGHT, 0), None);
+}
+
+#[test_case]
+fn test_window_scrolls_only_itself()
+{
+    use super::Color;
+
+    let mut writer = super::test_writer();
+    let color = ColorCode::new(Color::LightGreen, Color::Black);
+    writer.fill(Rect::screen(), b'.', super::DEFAULT_COLOR);
+    let mut window = Window::new(Rect::new(5, 10, 2, 4), color);
+    window.clear(&mut writer);
+
+    // "abcdef" bricht nach vier Zeichen um, "gh" schiebt "abcd" hinaus.
+    window.write(&mut writer, "abcdef\ngh");
+    let row = |writer: &Writer, row: usize| (9..15).map(|col| writer.char_at(row, col).unwrap().0 as char).collect::<alloc::string::String>();
+    assert_eq!(row(&writer, 5), ".ef  .");
+    assert_eq!(row(&writer, 6), ".gh  .");
+    assert_eq!(row(&writer, 4), "......");
+    assert_eq!(row(&writer, 7), "......");
+    assert_eq!(window.position(), (1, 2));
+}
+
+#[test_case]
+fn test_scroll_region_keeps_status_line()
+{
+    use core::fmt::Write;
+
+    let mut writer = super::test_writer();
+    writer.set_scroll_region(1, BUFFER_HEIGHT - 1);
+    writer.write_at(0, 0, "status", super::DEFAULT_COLOR);
+    for line in 0..BUFFER_HEIGHT + 2
+    {
+        writeln!(writer, "line {}", line).unwrap();
+    }
+    assert_eq!(writer.char_at(0, 0).map(|(byte, _)| byte), Some(b's'));
+    assert_eq!(writer.char_at(1, 5).map(|(byte, _)| byte), Some(b'4'));
+    assert_eq!(writer.char_at(BUFFER_HEIGHT - 2, 5).map(|(byte, _)| byte), Some(b'2'));
+}
@@ -0,0 +1,71 @@
+//! # Modul: virtio
+//!
+//! **virtio** ist die Schnittstelle der paravirtualisierten Geräte von QEMU
+//! und anderen Hypervisoren. Statt echte Hardware nachzubilden, tauschen
+//! Treiber und Gerät Puffer über Warteschlangen im gemeinsamen Speicher aus.
+//!
+//! # Aufbau
+//!
+//! | Komponente | Aufgabe |
+//! |------------|---------|
+//! | [Transport] | Register des Geräts über PCI, „legacy“ (I/O-Ports) oder „modern“ (Capabilities) |
+//! | [VirtQueue] | Split Virtqueue aus Deskriptortabelle, verfügbarem und benutztem Ring |
+//!
+//! Die eigentlichen Treiber liegen bei ihren Geräteklassen, z. B.
+//! [crate::block::virtio].
+//!
+//! # Ablauf der Initialisierung
+//!
+//! 1. Gerät zurücksetzen, Status `ACKNOWLEDGE` und `DRIVER` setzen
+//! 2. Merkmale (Features) des Geräts lesen und die unterstützten bestätigen;
+//!    bei modernen Geräten zusätzlich `FEATURES_OK` setzen und prüfen
+//! 3. Warteschlangen einrichten
+//! 4. Status `DRIVER_OK` setzen – ab jetzt bearbeitet das Gerät Anfragen
+
+pub mod pci;
+pub mod queue;
+
+pub use pci::Transport;
+pub use queue::{Buffer, VirtQueue};
+
+/// Hersteller aller virtio-Geräte.
+pub const VENDOR_ID: u16 = 0x1AF4;
+
+/// Geräteart, wie sie in der Gerätekennung steht: Übergangsgeräte
+/// (legacy und modern) haben `0x1000 + Art - 1`, rein moderne `0x1040 + Art`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum DeviceType
+{
+    Network = 1,
+    Block = 2,
+}
+
+impl DeviceType
+{
+    /// Gerätekennung eines Übergangsgeräts.
+    pub const fn transitional_id(self) -> u16
+    {
+        0x1000 + self as u16 - 1
+    }
+
+    /// Gerätekennung eines rein modernen Geräts.
+    pub const fn modern_id(self) -> u16
+    {
+        0x1040 + self as u16
+    }
+}
+
+/// Bits des Gerätestatus.
+pub mod status
+{
+    pub const ACKNOWLEDGE: u8 = 1;
+    pub const DRIVER: u8 = 2;
+    pub const DRIVER_OK: u8 = 4;
+    pub const FEATURES_OK: u8 = 8;
+    pub const DEVICE_NEEDS_RESET: u8 = 64;
+    pub const FAILED: u8 = 128;
+}
+
+/// Merkmal aller modernen Geräte (virtio 1.0).
+pub const FEATURE_VERSION_1: u64 = 1 << 32;
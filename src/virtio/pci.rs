@@ -0,0 +1,522 @@
+//! virtio über PCI.
+//!
+//! **Legacy**-Geräte (virtio 0.9) haben alle Register im I/O-BAR 0:
+//!
+//! | Offset | Größe | Register |
+//! |--------|-------|----------|
+//! | `0x00` | 4 | Merkmale des Geräts |
+//! | `0x04` | 4 | Merkmale des Treibers |
+//! | `0x08` | 4 | Seitennummer der gewählten Warteschlange |
+//! | `0x0C` | 2 | Größe der gewählten Warteschlange |
+//! | `0x0E` | 2 | Auswahl der Warteschlange |
+//! | `0x10` | 2 | Benachrichtigung |
+//! | `0x12` | 1 | Gerätestatus |
+//! | `0x13` | 1 | Interrupt-Status (ISR), wird beim Lesen gelöscht |
+//! | `0x14` | 2 | MSI-X-Eintrag für Konfigurationsänderungen (nur mit MSI-X) |
+//! | `0x16` | 2 | MSI-X-Eintrag der gewählten Warteschlange (nur mit MSI-X) |
+//!
+//! Danach folgt die gerätespezifische Konfiguration, ab `0x18` mit und ab
+//! `0x14` ohne MSI-X.
+//!
+//! **Moderne** Geräte (virtio 1.0) beschreiben mit herstellerspezifischen
+//! Capabilities, in welchem BAR ihre Bereiche liegen: die gemeinsame
+//! Konfiguration (Typ 1), die Benachrichtigungen (Typ 2), den ISR (Typ 3) und
+//! die gerätespezifische Konfiguration (Typ 4). Übergangsgeräte bieten beides
+//! an; dann wird die moderne Schnittstelle verwendet.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use x86_64::instructions::port::Port;
+use x86_64::{PhysAddr, VirtAddr};
+
+use super::queue::VirtQueue;
+use super::{FEATURE_VERSION_1, status};
+use crate::interrupts::vector::Handler;
+use crate::memory;
+use crate::pci::msi::MsiX;
+use crate::pci::{Bar, PciDevice, PciError, capability, config};
+
+/// Register der legacy-Schnittstelle.
+mod legacy
+{
+    pub const DEVICE_FEATURES: u16 = 0x00;
+    pub const DRIVER_FEATURES: u16 = 0x04;
+    pub const QUEUE_ADDRESS: u16 = 0x08;
+    pub const QUEUE_SIZE: u16 = 0x0C;
+    pub const QUEUE_SELECT: u16 = 0x0E;
+    pub const QUEUE_NOTIFY: u16 = 0x10;
+    pub const DEVICE_STATUS: u16 = 0x12;
+    pub const ISR: u16 = 0x13;
+    pub const CONFIG_VECTOR: u16 = 0x14;
+    pub const QUEUE_VECTOR: u16 = 0x16;
+    pub const CONFIG: u16 = 0x14;
+    pub const CONFIG_MSIX: u16 = 0x18;
+}
+
+/// Register der gemeinsamen Konfiguration moderner Geräte.
+mod common
+{
+    pub const DEVICE_FEATURE_SELECT: u16 = 0x00;
+    pub const DEVICE_FEATURE: u16 = 0x04;
+    pub const DRIVER_FEATURE_SELECT: u16 = 0x08;
+    pub const DRIVER_FEATURE: u16 = 0x0C;
+    pub const CONFIG_VECTOR: u16 = 0x10;
+    pub const DEVICE_STATUS: u16 = 0x14;
+    pub const QUEUE_SELECT: u16 = 0x16;
+    pub const QUEUE_SIZE: u16 = 0x18;
+    pub const QUEUE_VECTOR: u16 = 0x1A;
+    pub const QUEUE_ENABLE: u16 = 0x1C;
+    pub const QUEUE_NOTIFY_OFFSET: u16 = 0x1E;
+    pub const QUEUE_DESCRIPTOR: u16 = 0x20;
+    pub const QUEUE_DRIVER: u16 = 0x28;
+    pub const QUEUE_DEVICE: u16 = 0x30;
+}
+
+/// Arten der herstellerspezifischen Capabilities.
+mod cfg_type
+{
+    pub const COMMON: u8 = 1;
+    pub const NOTIFY: u8 = 2;
+    pub const ISR: u8 = 3;
+    pub const DEVICE: u8 = 4;
+}
+
+/// Kein MSI-X-Eintrag für ein Ereignis.
+const NO_VECTOR: u16 = 0xFFFF;
+
+/// Höchste Anzahl an Abfragen, bis ein Zurücksetzen abgeschlossen sein muss.
+const RESET_POLL_LIMIT: usize = 1_000_000;
+
+/// Ein Registerbereich im I/O- oder Speicheradressraum.
+#[derive(Debug, Clone, Copy)]
+enum Region
+{
+    Io(u16),
+    Memory(VirtAddr),
+}
+
+impl Region
+{
+    /// Bildet `length` Bytes ab `offset` im BAR ab.
+    fn map(bar: Bar, offset: u32, length: u32) -> Result<Region, PciError>
+    {
+        match bar
+        {
+            Bar::Io { port, .. } => Ok(Region::Io(port + offset as u16)),
+            Bar::Memory { address, .. } =>
+            {
+                let start = PhysAddr::new(address + u64::from(offset));
+                memory::map_mmio(start, u64::from(length)).map(Region::Memory).map_err(|_| PciError::BadBar)
+            }
+        }
+    }
+
+    fn read8(&self, offset: u16) -> u8
+    {
+        match *self
+        {
+            Region::Io(port) => unsafe { Port::new(port + offset).read() },
+            Region::Memory(base) => unsafe { (base + u64::from(offset)).as_ptr::<u8>().read_volatile() },
+        }
+    }
+
+    fn read16(&self, offset: u16) -> u16
+    {
+        match *self
+        {
+            Region::Io(port) => unsafe { Port::new(port + offset).read() },
+            Region::Memory(base) => unsafe { (base + u64::from(offset)).as_ptr::<u16>().read_volatile() },
+        }
+    }
+
+    fn read32(&self, offset: u16) -> u32
+    {
+        match *self
+        {
+            Region::Io(port) => unsafe { Port::new(port + offset).read() },
+            Region::Memory(base) => unsafe { (base + u64::from(offset)).as_ptr::<u32>().read_volatile() },
+        }
+    }
+
+    fn write8(&self, offset: u16, value: u8)
+    {
+        match *self
+        {
+            Region::Io(port) => unsafe { Port::new(port + offset).write(value) },
+            Region::Memory(base) => unsafe { (base + u64::from(offset)).as_mut_ptr::<u8>().write_volatile(value) },
+        }
+    }
+
+    fn write16(&self, offset: u16, value: u16)
+    {
+        match *self
+        {
+            Region::Io(port) => unsafe { Port::new(port + offset).write(value) },
+            Region::Memory(base) => unsafe { (base + u64::from(offset)).as_mut_ptr::<u16>().write_volatile(value) },
+        }
+    }
+
+    fn write32(&self, offset: u16, value: u32)
+    {
+        match *self
+        {
+            Region::Io(port) => unsafe { Port::new(port + offset).write(value) },
+            Region::Memory(base) => unsafe { (base + u64::from(offset)).as_mut_ptr::<u32>().write_volatile(value) },
+        }
+    }
+
+    fn write64(&self, offset: u16, value: u64)
+    {
+        self.write32(offset, value as u32);
+        self.write32(offset + 4, (value >> 32) as u32);
+    }
+}
+
+/// Die Registerbereiche eines Geräts.
+enum Layout
+{
+    Legacy
+    {
+        io: Region,
+    },
+    Modern
+    {
+        common: Region,
+        notify: Region,
+        notify_multiplier: u32,
+        isr: Region,
+        device: Region,
+    },
+}
+
+/// ## Transport
+///
+/// Zugriff auf ein virtio-Gerät am PCI-Bus.
+pub struct Transport
+{
+    pci: Arc<PciDevice>,
+    layout: Layout,
+    msix: Option<MsiX>,
+    /// Offset der Benachrichtigung je eingerichteter Warteschlange (modern).
+    notify_offsets: Vec<u32>,
+}
+
+impl Transport
+{
+    /// Erkennt die Schnittstelle des Geräts, bevorzugt die moderne, und
+    /// schaltet seine BARs und DMA ein.
+    pub fn new(pci: &Arc<PciDevice>) -> Result<Transport, PciError>
+    {
+        let layout = match Self::modern_layout(pci)?
+        {
+            Some(layout) => layout,
+            None => match pci.bars[0]
+            {
+                Some(bar @ Bar::Io { .. }) => Layout::Legacy { io: Region::map(bar, 0, 0)? },
+                _ => return Err(PciError::Unsupported),
+            },
+        };
+        pci.enable(true);
+        Ok(Transport { pci: pci.clone(), layout, msix: None, notify_offsets: Vec::new() })
+    }
+
+    /// Sucht die Capabilities der modernen Schnittstelle.
+    fn modern_layout(pci: &PciDevice) -> Result<Option<Layout>, PciError>
+    {
+        let mut regions: [Option<Region>; 5] = [None; 5];
+        let mut notify_multiplier = 0;
+        for entry in pci.capabilities.iter().filter(|entry| entry.id == capability::VENDOR_SPECIFIC)
+        {
+            let offset = u16::from(entry.offset);
+            let kind = config::read_u8(pci.address, offset + 3);
+            let Some(slot) = regions.get_mut(usize::from(kind)).filter(|slot| slot.is_none() && kind != 0)
+            else
+            {
+                continue;
+            };
+            let Some(bar) = pci.bars.get(usize::from(config::read_u8(pci.address, offset + 4))).copied().flatten()
+            else
+            {
+                continue;
+            };
+            *slot = Some(Region::map(bar, config::read_u32(pci.address, offset + 8), config::read_u32(pci.address, offset + 12))?);
+            if kind == cfg_type::NOTIFY
+            {
+                notify_multiplier = config::read_u32(pci.address, offset + 16);
+            }
+        }
+        let region = |kind: u8| regions[usize::from(kind)];
+        let (Some(common), Some(notify), Some(isr), Some(device)) =
+            (region(cfg_type::COMMON), region(cfg_type::NOTIFY), region(cfg_type::ISR), region(cfg_type::DEVICE))
+        else
+        {
+            return Ok(None);
+        };
+        Ok(Some(Layout::Modern { common, notify, notify_multiplier, isr, device }))
+    }
+
+    /// Das PCI-Gerät hinter dem Transport.
+    pub fn pci(&self) -> &Arc<PciDevice>
+    {
+        &self.pci
+    }
+
+    /// Gibt an, ob die moderne Schnittstelle verwendet wird.
+    pub fn is_modern(&self) -> bool
+    {
+        matches!(self.layout, Layout::Modern { .. })
+    }
+
+    pub fn status(&self) -> u8
+    {
+        match &self.layout
+        {
+            Layout::Legacy { io } => io.read8(legacy::DEVICE_STATUS),
+            Layout::Modern { common, .. } => common.read8(common::DEVICE_STATUS),
+        }
+    }
+
+    fn set_status(&self, value: u8)
+    {
+        match &self.layout
+        {
+            Layout::Legacy { io } => io.write8(legacy::DEVICE_STATUS, value),
+            Layout::Modern { common, .. } => common.write8(common::DEVICE_STATUS, value),
+        }
+    }
+
+    /// Setzt zusätzlich die Statusbits `bits`.
+    pub fn add_status(&self, bits: u8)
+    {
+        self.set_status(self.status() | bits);
+    }
+
+    /// Setzt das Gerät zurück. Es vergisst dabei alle Warteschlangen.
+    pub fn reset(&mut self)
+    {
+        self.set_status(0);
+        for _ in 0..RESET_POLL_LIMIT
+        {
+            if self.status() == 0
+            {
+                break;
+            }
+            core::hint::spin_loop();
+        }
+        self.notify_offsets.clear();
+    }
+
+    /// Setzt das Gerät zurück, meldet den Treiber an und handelt die Merkmale
+    /// aus: bestätigt werden die von `supported`, die auch das Gerät anbietet.
+    /// Moderne Geräte erhalten zusätzlich [FEATURE_VERSION_1]. Gibt die
+    /// ausgehandelten Merkmale zurück.
+    pub fn negotiate(&mut self, supported: u64) -> Result<u64, PciError>
+    {
+        self.reset();
+        self.add_status(status::ACKNOWLEDGE | status::DRIVER);
+        match &self.layout
+        {
+            Layout::Legacy { io } =>
+            {
+                let features = u64::from(io.read32(legacy::DEVICE_FEATURES)) & supported & 0xFFFF_FFFF;
+                io.write32(legacy::DRIVER_FEATURES, features as u32);
+                Ok(features)
+            }
+            Layout::Modern { common, .. } =>
+            {
+                let mut offered = 0;
+                for half in 0..2
+                {
+                    common.write32(common::DEVICE_FEATURE_SELECT, half);
+                    offered |= u64::from(common.read32(common::DEVICE_FEATURE)) << (32 * half);
+                }
+                if offered & FEATURE_VERSION_1 == 0
+                {
+                    self.add_status(status::FAILED);
+                    return Err(PciError::Unsupported);
+                }
+                let features = offered & (supported | FEATURE_VERSION_1);
+                for half in 0..2
+                {
+                    common.write32(common::DRIVER_FEATURE_SELECT, half);
+                    common.write32(common::DRIVER_FEATURE, (features >> (32 * half)) as u32);
+                }
+                self.add_status(status::FEATURES_OK);
+                if self.status() & status::FEATURES_OK == 0
+                {
+                    self.add_status(status::FAILED);
+                    return Err(PciError::Unsupported);
+                }
+                Ok(features)
+            }
+        }
+    }
+
+    /// Schaltet MSI-X ein, sofern das Gerät es unterstützt. Danach erhält jede
+    /// mit Handler eingerichtete Warteschlange einen eigenen Vektor.
+    pub fn enable_msix(&mut self) -> bool
+    {
+        if self.msix.is_none()
+        {
+            self.msix = MsiX::enable(&self.pci).ok();
+            // Konfigurationsänderungen werden nicht gemeldet.
+            match &self.layout
+            {
+                Layout::Legacy { io } if self.msix.is_some() => io.write16(legacy::CONFIG_VECTOR, NO_VECTOR),
+                Layout::Modern { common, .. } if self.msix.is_some() => common.write16(common::CONFIG_VECTOR, NO_VECTOR),
+                _ => (),
+            }
+        }
+        self.msix.is_some()
+    }
+
+    /// Richtet die Warteschlange `index` mit höchstens `max_size` Einträgen
+    /// ein; legacy-Geräte geben ihre Größe dagegen fest vor. Ist MSI-X eingeschaltet und `handler` angegeben, meldet das Gerät
+    /// fertige Einträge über einen eigenen Vektor; andernfalls muss der
+    /// Treiber die Warteschlange abfragen.
+    pub fn setup_queue(&mut self, index: u16, max_size: u16, handler: Option<Handler>) -> Result<VirtQueue, PciError>
+    {
+        let vector = match (self.msix.as_mut(), handler)
+        {
+            (Some(msix), Some(handler)) =>
+            {
+                msix.set_handler(usize::from(index), handler)?;
+                index
+            }
+            _ => NO_VECTOR,
+        };
+
+        match &self.layout
+        {
+            Layout::Legacy { io } =>
+            {
+                io.write16(legacy::QUEUE_SELECT, index);
+                // Die Größe legt bei legacy-Geräten allein das Gerät fest.
+                let size = io.read16(legacy::QUEUE_SIZE);
+                if size == 0
+                {
+                    return Err(PciError::Unsupported);
+                }
+                let queue = VirtQueue::new(index, size).ok_or(PciError::Unsupported)?;
+                if self.msix.is_some()
+                {
+                    io.write16(legacy::QUEUE_VECTOR, vector);
+                    if io.read16(legacy::QUEUE_VECTOR) != vector
+                    {
+                        return Err(PciError::NoVectors);
+                    }
+                }
+                io.write32(legacy::QUEUE_ADDRESS, (queue.descriptor_address().as_u64() >> 12) as u32);
+                Ok(queue)
+            }
+            Layout::Modern { common, .. } =>
+            {
+                common.write16(common::QUEUE_SELECT, index);
+                let offered = common.read16(common::QUEUE_SIZE);
+                if offered == 0
+                {
+                    return Err(PciError::Unsupported);
+                }
+                let size = 1 << offered.min(max_size).ilog2();
+                let queue = VirtQueue::new(index, size).ok_or(PciError::Unsupported)?;
+                common.write16(common::QUEUE_SIZE, size);
+                common.write16(common::QUEUE_VECTOR, vector);
+                if common.read16(common::QUEUE_VECTOR) != vector
+                {
+                    return Err(PciError::NoVectors);
+                }
+                common.write64(common::QUEUE_DESCRIPTOR, queue.descriptor_address().as_u64());
+                common.write64(common::QUEUE_DRIVER, queue.driver_address().as_u64());
+                common.write64(common::QUEUE_DEVICE, queue.device_address().as_u64());
+                let notify_offset = u32::from(common.read16(common::QUEUE_NOTIFY_OFFSET));
+                common.write16(common::QUEUE_ENABLE, 1);
+
+                let slot = usize::from(index);
+                if self.notify_offsets.len() <= slot
+                {
+                    self.notify_offsets.resize(slot + 1, 0);
+                }
+                self.notify_offsets[slot] = notify_offset;
+                Ok(queue)
+            }
+        }
+    }
+
+    /// Meldet dem Gerät, dass die Warteschlange neue Einträge hat.
+    pub fn notify(&self, queue: &VirtQueue)
+    {
+        if !queue.needs_notification()
+        {
+            return;
+        }
+        match &self.layout
+        {
+            Layout::Legacy { io } => io.write16(legacy::QUEUE_NOTIFY, queue.index()),
+            Layout::Modern { notify, notify_multiplier, .. } =>
+            {
+                let offset = self.notify_offsets[usize::from(queue.index())] * notify_multiplier;
+                notify.write16(offset as u16, queue.index());
+            }
+        }
+    }
+
+    /// Schließt die Initialisierung ab; das Gerät beginnt zu arbeiten.
+    pub fn finish(&self)
+    {
+        self.add_status(status::DRIVER_OK);
+    }
+
+    /// Liest und löscht den Interrupt-Status: Bit 0 für Warteschlangen, Bit 1
+    /// für Konfigurationsänderungen. Nur ohne MSI-X von Bedeutung.
+    pub fn read_isr(&self) -> u8
+    {
+        match &self.layout
+        {
+            Layout::Legacy { io } => io.read8(legacy::ISR),
+            Layout::Modern { isr, .. } => isr.read8(0),
+        }
+    }
+
+    /// Bereich und Offset der gerätespezifischen Konfiguration.
+    fn device_config(&self) -> (Region, u16)
+    {
+        match &self.layout
+        {
+            Layout::Legacy { io } => (*io, if self.msix.is_some() { legacy::CONFIG_MSIX } else { legacy::CONFIG }),
+            Layout::Modern { device, .. } => (*device, 0),
+        }
+    }
+
+    pub fn config_read_u8(&self, offset: u16) -> u8
+    {
+        let (region, start) = self.device_config();
+        region.read8(start + offset)
+    }
+
+    pub fn config_read_u16(&self, offset: u16) -> u16
+    {
+        let (region, start) = self.device_config();
+        region.read16(start + offset)
+    }
+
+    pub fn config_read_u32(&self, offset: u16) -> u32
+    {
+        let (region, start) = self.device_config();
+        region.read32(start + offset)
+    }
+
+    pub fn config_read_u64(&self, offset: u16) -> u64
+    {
+        u64::from(self.config_read_u32(offset)) | u64::from(self.config_read_u32(offset + 4)) << 32
+    }
+}
+
+impl Drop for Transport
+{
+    /// Setzt das Gerät zurück, damit es nicht mehr auf den Speicher der
+    /// Warteschlangen zugreift.
+    fn drop(&mut self)
+    {
+        self.reset();
+    }
+}
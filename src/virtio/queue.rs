@@ -0,0 +1,230 @@
+//! Split Virtqueue.
+//!
+//! Eine Warteschlange mit `size` Einträgen besteht aus drei Teilen, die hier
+//! im Layout der legacy-Schnittstelle hintereinander liegen:
+//!
+//! | Teil | Größe | Schreibt | Inhalt |
+//! |------|-------|----------|--------|
+//! | Deskriptortabelle | 16 · size | Treiber | Adresse, Länge, Flags und Nachfolger je Puffer |
+//! | verfügbarer Ring | 6 + 2 · size | Treiber | Köpfe der Ketten, die das Gerät bearbeiten soll |
+//! | benutzter Ring (an 4 KiB ausgerichtet) | 6 + 8 · size | Gerät | Köpfe der fertigen Ketten mit geschriebener Länge |
+//!
+//! Eine Anfrage ist eine Kette von Deskriptoren, verbunden über `next`; vom
+//! Gerät beschreibbare Puffer tragen das Flag `WRITE`. Freie Deskriptoren sind
+//! ebenfalls über `next` zu einer Liste verkettet.
+
+use core::sync::atomic::{Ordering, fence};
+use x86_64::PhysAddr;
+
+use crate::memory::dma::DmaBuffer;
+
+/// Der Deskriptor hat einen Nachfolger in `next`.
+const DESC_F_NEXT: u16 = 1;
+/// Das Gerät schreibt in den Puffer.
+const DESC_F_WRITE: u16 = 2;
+
+/// Das Gerät braucht keine Benachrichtigung über neue Einträge.
+const USED_F_NO_NOTIFY: u16 = 1;
+
+const DESCRIPTOR_SIZE: usize = 16;
+
+/// Ausrichtung des benutzten Rings.
+const USED_ALIGNMENT: usize = 4096;
+
+/// Ein Puffer einer Anfrage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Buffer
+{
+    pub address: PhysAddr,
+    pub len: u32,
+    /// Das Gerät schreibt in den Puffer (sonst liest es ihn).
+    pub writable: bool,
+}
+
+/// ## VirtQueue
+///
+/// Eine Split Virtqueue im [DmaBuffer]. Die Warteschlange kennt das Gerät
+/// nicht; Einrichten und Benachrichtigen übernimmt der
+/// [Transport](super::Transport).
+pub struct VirtQueue
+{
+    index: u16,
+    size: u16,
+    memory: DmaBuffer,
+    avail_offset: usize,
+    used_offset: usize,
+    /// Erster freier Deskriptor.
+    free_head: u16,
+    free_count: u16,
+    /// Nächster Index im verfügbaren Ring.
+    next_avail: u16,
+    /// Nächster noch nicht ausgewerteter Index im benutzten Ring.
+    last_used: u16,
+}
+
+impl VirtQueue
+{
+    /// Legt die Warteschlange `index` mit `size` Einträgen an. `size` muss eine
+    /// Zweierpotenz sein.
+    pub fn new(index: u16, size: u16) -> Option<VirtQueue>
+    {
+        if !size.is_power_of_two()
+        {
+            return None;
+        }
+        let entries = usize::from(size);
+        let avail_offset = DESCRIPTOR_SIZE * entries;
+        let used_offset = (avail_offset + 6 + 2 * entries).next_multiple_of(USED_ALIGNMENT);
+        let memory = DmaBuffer::new(used_offset + 6 + 8 * entries)?;
+        for descriptor in 0..size - 1
+        {
+            memory.write(usize::from(descriptor) * DESCRIPTOR_SIZE + 14, descriptor + 1);
+        }
+        Some(VirtQueue { index, size, memory, avail_offset, used_offset, free_head: 0, free_count: size, next_avail: 0, last_used: 0 })
+    }
+
+    pub fn index(&self) -> u16
+    {
+        self.index
+    }
+
+    pub fn size(&self) -> u16
+    {
+        self.size
+    }
+
+    /// Physische Adresse der Deskriptortabelle.
+    pub fn descriptor_address(&self) -> PhysAddr
+    {
+        self.memory.physical_address()
+    }
+
+    /// Physische Adresse des verfügbaren Rings.
+    pub fn driver_address(&self) -> PhysAddr
+    {
+        self.memory.physical_address() + self.avail_offset as u64
+    }
+
+    /// Physische Adresse des benutzten Rings.
+    pub fn device_address(&self) -> PhysAddr
+    {
+        self.memory.physical_address() + self.used_offset as u64
+    }
+
+    /// Anzahl der freien Deskriptoren.
+    pub fn free_descriptors(&self) -> u16
+    {
+        self.free_count
+    }
+
+    /// Stellt die Puffer als eine Kette in den verfügbaren Ring und gibt den
+    /// Kopf der Kette zurück. Gibt `None` zurück, wenn nicht genug Deskriptoren
+    /// frei sind. Das Gerät muss danach benachrichtigt werden.
+    pub fn add(&mut self, buffers: &[Buffer]) -> Option<u16>
+    {
+        if buffers.is_empty() || buffers.len() > usize::from(self.free_count)
+        {
+            return None;
+        }
+        let head = self.free_head;
+        let mut descriptor = head;
+        for (position, buffer) in buffers.iter().enumerate()
+        {
+            let offset = usize::from(descriptor) * DESCRIPTOR_SIZE;
+            let next: u16 = self.memory.read(offset + 14);
+            let mut flags = if buffer.writable { DESC_F_WRITE } else { 0 };
+            if position + 1 < buffers.len()
+            {
+                flags |= DESC_F_NEXT;
+            }
+            self.memory.write(offset, buffer.address.as_u64());
+            self.memory.write(offset + 8, buffer.len);
+            self.memory.write(offset + 12, flags);
+            if position + 1 < buffers.len()
+            {
+                descriptor = next;
+            }
+            else
+            {
+                self.free_head = next;
+            }
+        }
+        self.free_count -= buffers.len() as u16;
+
+        let slot = usize::from(self.next_avail % self.size);
+        self.memory.write(self.avail_offset + 4 + 2 * slot, head);
+        // Der Eintrag muss sichtbar sein, bevor das Gerät den neuen Index liest.
+        fence(Ordering::SeqCst);
+        self.next_avail = self.next_avail.wrapping_add(1);
+        self.memory.write(self.avail_offset + 2, self.next_avail);
+        fence(Ordering::SeqCst);
+        Some(head)
+    }
+
+    /// Gibt an, ob das Gerät über neue Einträge benachrichtigt werden will.
+    pub fn needs_notification(&self) -> bool
+    {
+        self.memory.read::<u16>(self.used_offset) & USED_F_NO_NOTIFY == 0
+    }
+
+    /// Nimmt die nächste fertige Kette aus dem benutzten Ring, gibt ihre
+    /// Deskriptoren frei und liefert Kopf und vom Gerät geschriebene Länge.
+    pub fn pop_used(&mut self) -> Option<(u16, u32)>
+    {
+        let used_index: u16 = self.memory.read(self.used_offset + 2);
+        if used_index == self.last_used
+        {
+            return None;
+        }
+        fence(Ordering::SeqCst);
+        let slot = usize::from(self.last_used % self.size);
+        let head = self.memory.read::<u32>(self.used_offset + 4 + 8 * slot) as u16;
+        let len = self.memory.read::<u32>(self.used_offset + 8 + 8 * slot);
+        self.last_used = self.last_used.wrapping_add(1);
+
+        // Die Kette bis zu ihrem Ende verfolgen und vor die Freiliste hängen.
+        let mut tail = head;
+        let mut count = 1;
+        loop
+        {
+            let offset = usize::from(tail) * DESCRIPTOR_SIZE;
+            if self.memory.read::<u16>(offset + 12) & DESC_F_NEXT == 0
+            {
+                self.memory.write(offset + 14, self.free_head);
+                break;
+            }
+            tail = self.memory.read(offset + 14);
+            count += 1;
+        }
+        self.free_head = head;
+        self.free_count += count;
+        Some((head, len))
+    }
+}
+
+#[test_case]
+fn test_chains_and_free_list()
+{
+    let mut queue = VirtQueue::new(0, 4).unwrap();
+    assert!(VirtQueue::new(0, 3).is_none());
+    let buffer = |len, writable| Buffer { address: PhysAddr::new(0x1000), len, writable };
+
+    let first = queue.add(&[buffer(16, false), buffer(512, true), buffer(1, true)]).unwrap();
+    assert_eq!(queue.free_descriptors(), 1);
+    assert!(queue.add(&[buffer(1, false), buffer(1, false)]).is_none());
+    let second = queue.add(&[buffer(8, false)]).unwrap();
+    assert_eq!(queue.memory.read::<u16>(queue.avail_offset + 2), 2);
+    assert!(queue.pop_used().is_none());
+
+    // Das Gerät meldet die zweite Kette vor der ersten als fertig.
+    let used = queue.used_offset;
+    queue.memory.write(used + 4, u32::from(second));
+    queue.memory.write(used + 8, 0u32);
+    queue.memory.write(used + 12, u32::from(first));
+    queue.memory.write(used + 16, 513u32);
+    queue.memory.write(used + 2, 2u16);
+    assert_eq!(queue.pop_used(), Some((second, 0)));
+    assert_eq!(queue.pop_used(), Some((first, 513)));
+    assert_eq!(queue.free_descriptors(), 4);
+    assert!(queue.add(&[buffer(1, false); 4]).is_some());
+}
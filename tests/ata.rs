@@ -0,0 +1,104 @@
+//! # ata.rs
+//!
+//! Dieses Modul testet den **ATA-Treiber** gegen die Laufwerke, die QEMU
+//! bereitstellt (siehe `test-args` in `Cargo.toml`):
+//!
+//! - `ata0`: das Boot-Image als primärer Master
+//! - `ata1`: `tests/disk.img` (64 KiB) als primärer Slave
+//!
+//! ## Übersicht
+//!
+//! - Kein std und kein main, da Bare-Metal-Umgebung
+//! - Nutzt das Custom Test Framework aus [lib.rs](../lib.rs.html)
+//! - Schreibt nur auf `ata1`; QEMU verwirft die Änderungen (`snapshot=on`)
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(simple_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
+use bootloader::{BootInfo, entry_point};
+use core::panic::PanicInfo;
+use simple_os::block::{self, BlockError};
+use simple_os::memory;
+
+entry_point!(main);
+
+/// ## Einstiegspunkt (main)
+///
+/// Initialisiert den Kernel samt Speicherverwaltung, erkennt die Laufwerke und
+/// führt anschließend alle Tests aus.
+fn main(boot_info: &'static BootInfo) -> !
+{
+    simple_os::init();
+    unsafe { memory::init(boot_info) };
+    block::ata::init();
+
+    test_main();
+    simple_os::hlt_loop();
+}
+
+/// ## Panic Handler
+///
+/// Leitet alle Panic-Informationen an [simple_os::test_panic_handler] weiter.
+#[panic_handler]
+fn panic(info: &PanicInfo) -> !
+{
+    simple_os::test_panic_handler(info)
+}
+
+#[test_case]
+fn test_drives_detected()
+{
+    let disk = block::get("ata1").expect("test disk not found");
+    assert_eq!(disk.sector_size(), 512);
+    assert_eq!(disk.sector_count(), 128);
+    assert_eq!(disk.capacity(), 64 * 1024);
+    assert!(block::get("ata0").is_some());
+}
+
+#[test_case]
+fn test_read_boot_sector()
+{
+    let boot = block::get("ata0").unwrap();
+    let mut sector = [0; 512];
+    boot.read_sectors(0, &mut sector).unwrap();
+    assert_eq!(&sector[510..], &[0x55, 0xAA]);
+}
+
+#[test_case]
+fn test_read_multiple_sectors()
+{
+    let disk = block::get("ata1").unwrap();
+    let mut buffer = vec![0; 3 * 512];
+    disk.read_sectors(1, &mut buffer).unwrap();
+    assert!(buffer.starts_with(b"simple_os test disk"));
+    assert!(buffer[512..1024].iter().all(|&byte| byte == 2));
+    assert!(buffer[1024..].iter().all(|&byte| byte == 3));
+}
+
+#[test_case]
+fn test_write_and_read_back()
+{
+    let disk = block::get("ata1").unwrap();
+    let data: Vec<u8> = (0..1024).map(|index| (index * 7) as u8).collect();
+    disk.write_sectors(126, &data).unwrap();
+    disk.flush().unwrap();
+
+    let mut buffer = vec![0; 1024];
+    disk.read_sectors(126, &mut buffer).unwrap();
+    assert_eq!(buffer, data);
+}
+
+#[test_case]
+fn test_invalid_requests()
+{
+    let disk = block::get("ata1").unwrap();
+    assert_eq!(disk.read_sectors(127, &mut [0; 1024]), Err(BlockError::OutOfRange));
+    assert_eq!(disk.read_sectors(0, &mut [0; 100]), Err(BlockError::BadBufferSize));
+    assert_eq!(disk.write_sectors(u64::MAX, &[0; 512]), Err(BlockError::OutOfRange));
+}
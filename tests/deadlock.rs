@@ -0,0 +1,84 @@
+//! # deadlock.rs
+//!
+//! Dieses Modul testet die **Deadlock-Erkennung** der Sperren aus
+//! [simple_os::sync].
+//!
+//! Dasselbe [Mutex] wird bei gesperrten Interrupts zweimal gesperrt. Da auf
+//! einem Einkernsystem dann niemand mehr die Sperre freigeben kann, muss der
+//! Detektor sofort eine Panic mit `deadlock detected` auslösen, statt das
+//! System für immer schlafen zu lassen.
+//!
+//! ## Übersicht
+//!
+//! - Kein std und kein main, da Bare-Metal-Umgebung
+//! - Nutzt [QemuExitCode] und [exit_qemu] für die Testauswertung
+//! - Die Erkennung gibt es nur mit `debug_assertions`, also im Test-Profil
+#![no_std]
+#![no_main]
+
+use core::fmt::Write;
+use core::panic::PanicInfo;
+use simple_os::sync::Mutex;
+use simple_os::{QemuExitCode, exit_qemu, serial_print, serial_println};
+
+/// Das zweimal gesperrte Mutex.
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// ## Einstiegspunkt (_start)
+///
+/// Sperrt [LOCK] zweimal. Kehrt der zweite Aufruf zurück, gilt der Test als
+/// fehlgeschlagen.
+#[unsafe(no_mangle)]
+pub extern "C" fn _start() -> !
+{
+    serial_print!("deadlock::double_lock_panics...\t");
+
+    x86_64::instructions::interrupts::disable();
+    let _first = LOCK.lock();
+    let _second = LOCK.lock();
+
+    serial_println!("[test did not panic]");
+    exit_qemu(QemuExitCode::Failed);
+    simple_os::hlt_loop();
+}
+
+/// Sammelt die Panic-Meldung in einem festen Puffer, denn einen Heap gibt es in
+/// diesem Test nicht. Was nicht hineinpasst, wird abgeschnitten.
+struct Message
+{
+    buffer: [u8; 128],
+    len: usize,
+}
+
+impl Write for Message
+{
+    fn write_str(&mut self, text: &str) -> core::fmt::Result
+    {
+        let count = text.len().min(self.buffer.len() - self.len);
+        self.buffer[self.len..self.len + count].copy_from_slice(&text.as_bytes()[..count]);
+        self.len += count;
+        Ok(())
+    }
+}
+
+/// ## Panic Handler
+///
+/// Der Test gilt als bestanden, wenn die Panic vom Deadlock-Detektor stammt.
+#[panic_handler]
+fn panic(info: &PanicInfo) -> !
+{
+    let mut message = Message { buffer: [0; 128], len: 0 };
+    let _ = write!(message, "{}", info.message());
+    if message.buffer[..message.len].starts_with(b"deadlock detected")
+    {
+        serial_println!("[ok]");
+        exit_qemu(QemuExitCode::Success);
+    }
+    else
+    {
+        serial_println!("[failed]");
+        serial_println!("unexpected panic: {}", info);
+        exit_qemu(QemuExitCode::Failed);
+    }
+    simple_os::hlt_loop();
+}
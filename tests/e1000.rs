@@ -0,0 +1,168 @@
+//! # e1000.rs
+//!
+//! Dieses Modul testet den **e1000-Treiber** gegen das User-Netz von
+//! QEMU (siehe `test-args` in `Cargo.toml`). Der Test fragt per ARP nach dem
+//! Gateway `10.0.2.2`, das QEMU selbst beantwortet; ein äußeres Netz wird
+//! nicht gebraucht. Die Karte meldet sich über ihre IRQ-Leitung.
+//!
+//! ## Übersicht
+//!
+//! - Kein std und kein main, da Bare-Metal-Umgebung
+//! - Nutzt das Custom Test Framework aus [lib.rs](../lib.rs.html)
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(simple_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+extern crate alloc;
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use bootloader::{BootInfo, entry_point};
+use core::panic::PanicInfo;
+use simple_os::net::{self, MacAddress, NetDevice, NetError};
+use simple_os::{memory, pci, time};
+
+entry_point!(main);
+
+/// Adresse, die QEMU dem Gast im User-Netz zuweist.
+const GUEST_IP: [u8; 4] = [10, 0, 2, 15];
+
+/// Adresse des Gateways im User-Netz.
+const GATEWAY_IP: [u8; 4] = [10, 0, 2, 2];
+
+/// ## Einstiegspunkt (main)
+///
+/// Initialisiert den Kernel samt Speicherverwaltung, erkennt die PCI-Geräte
+/// und führt anschließend alle Tests aus.
+fn main(boot_info: &'static BootInfo) -> !
+{
+    simple_os::init();
+    unsafe { memory::init(boot_info) };
+    pci::init();
+    net::e1000::init();
+
+    test_main();
+    simple_os::hlt_loop();
+}
+
+/// ## Panic Handler
+///
+/// Leitet alle Panic-Informationen an [simple_os::test_panic_handler] weiter.
+#[panic_handler]
+fn panic(info: &PanicInfo) -> !
+{
+    simple_os::test_panic_handler(info)
+}
+
+fn device() -> Arc<dyn NetDevice>
+{
+    let device = net::e1000::init().into_iter().next().expect("e1000 network card not found");
+    net::get(device.name()).unwrap()
+}
+
+/// Eine ARP-Anfrage nach `GATEWAY_IP` an alle Geräte im Netz.
+fn arp_request(mac: MacAddress) -> Vec<u8>
+{
+    let mut frame = Vec::new();
+    frame.extend_from_slice(&MacAddress::BROADCAST.0);
+    frame.extend_from_slice(&mac.0);
+    frame.extend_from_slice(&[0x08, 0x06]);
+    // Ethernet, IPv4, Adresslängen 6 und 4, Anfrage
+    frame.extend_from_slice(&[0, 1, 0x08, 0x00, 6, 4, 0, 1]);
+    frame.extend_from_slice(&mac.0);
+    frame.extend_from_slice(&GUEST_IP);
+    frame.extend_from_slice(&[0; 6]);
+    frame.extend_from_slice(&GATEWAY_IP);
+    frame.resize(60, 0);
+    frame
+}
+
+/// Wartet bis zu einer Sekunde auf einen Frame, der `accept` erfüllt.
+fn receive_matching(device: &dyn NetDevice, accept: impl Fn(&[u8]) -> bool) -> Option<Vec<u8>>
+{
+    let deadline = time::ticks() + time::ms_to_ticks(1000);
+    while time::ticks() < deadline
+    {
+        match device.receive()
+        {
+            Some(frame) if accept(&frame) => return Some(frame),
+            Some(_) => (),
+            None => x86_64::instructions::hlt(),
+        }
+    }
+    None
+}
+
+#[test_case]
+fn test_device_detected()
+{
+    let device = device();
+    assert!(device.name().starts_with("eth"));
+    assert_ne!(device.mac_address(), MacAddress::default());
+    assert!(!device.mac_address().is_multicast());
+    assert_eq!(device.mtu(), 1500);
+    assert!(device.link_up());
+}
+
+#[test_case]
+fn test_invalid_frames()
+{
+    let device = device();
+    assert_eq!(device.send(&[0; 10]), Err(NetError::BadFrameSize));
+    assert_eq!(device.send(&[0; 1515]), Err(NetError::BadFrameSize));
+}
+
+#[test_case]
+fn test_arp_with_gateway()
+{
+    let device = device();
+    let mac = device.mac_address();
+    device.send(&arp_request(mac)).unwrap();
+
+    let reply = receive_matching(&*device, |frame| frame.len() >= 42 && frame[12..14] == [0x08, 0x06] && frame[20..22] == [0, 2])
+        .expect("no ARP reply from the gateway");
+    assert_eq!(&reply[0..6], &mac.0);
+    assert_eq!(&reply[28..32], &GATEWAY_IP);
+    assert_eq!(&reply[38..42], &GUEST_IP);
+}
+
+#[test_case]
+fn test_transmit_completion()
+{
+    // Mehr Frames als Sendeplätze: Nur wenn versendete Frames ihre Plätze
+    // freigeben, kommen alle durch.
+    let device = device();
+    let request = arp_request(device.mac_address());
+    for _ in 0..100
+    {
+        let deadline = time::ticks() + time::ms_to_ticks(1000);
+        loop
+        {
+            match device.send(&request)
+            {
+                Ok(()) => break,
+                Err(NetError::QueueFull) if time::ticks() < deadline => core::hint::spin_loop(),
+                Err(error) => panic!("send failed: {:?}", error),
+            }
+        }
+    }
+    // Jede Anfrage wird beantwortet; der Empfang füllt seine Plätze nach.
+    let mut replies = 0;
+    while replies < 100 && receive_matching(&*device, |frame| frame[12..14] == [0x08, 0x06]).is_some()
+    {
+        replies += 1;
+    }
+    assert!(replies > 32, "only {} replies", replies);
+}
+
+#[test_case]
+fn test_interrupt_causes()
+{
+    // Empfangene Frames und versendete Deskriptoren lösen Interrupts aus,
+    // deren Ursachen der Handler quittiert.
+    let card = net::e1000::init().into_iter().next().unwrap();
+    assert!(card.interrupt_vector().is_some());
+    assert!(card.interrupt_count() > 0);
+}
@@ -0,0 +1,15 @@
+#!/bin/sh
+# Baut die Test-Programme für tests/elf_loader.rs, tests/processes.rs und tests/fork.rs.
+#
+# Die Programme werden statisch und ohne Laufzeitbibliothek oberhalb von
+# memory::USER_SPACE_START (0x80_0000_0000) gelinkt.
+set -e
+cd "$(dirname "$0")"
+for program in hello args yield fork orphan
+do
+    as --64 -o "$program.o" "$program.S"
+    ld -static -nostdlib --build-id=none -z max-page-size=0x1000 -z noexecstack \
+        -Ttext=0x8000401000 -e _start -o "$program.elf" "$program.o"
+    strip "$program.elf"
+    rm "$program.o"
+done
@@ -0,0 +1,78 @@
+//! # elf_loader.rs
+//!
+//! Dieses Modul testet den **ELF-Loader** mit kleinen, statisch gelinkten
+//! Programmen aus `tests/elf/` (erzeugt mit `tests/elf/build.sh`).
+//!
+//! ## Übersicht
+//!
+//! - Kein std und kein main, da Bare-Metal-Umgebung
+//! - Nutzt das Custom Test Framework aus [lib.rs](../lib.rs.html)
+//! - `hello.elf` gibt eine Zeile aus und beendet sich mit der Anzahl geschriebener Bytes
+//! - `args.elf` prüft argv, envp, auxv sowie ein genulltes `.bss` und beendet sich
+//!   mit `argc + 10 * envc`
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(simple_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use bootloader::{BootInfo, entry_point};
+use core::panic::PanicInfo;
+use simple_os::elf::{self, ElfError};
+use simple_os::memory;
+
+entry_point!(main);
+
+static HELLO: &[u8] = include_bytes!("elf/hello.elf");
+static ARGS: &[u8] = include_bytes!("elf/args.elf");
+
+/// ## Einstiegspunkt (main)
+///
+/// Initialisiert den Kernel samt Speicherverwaltung und führt anschließend alle Tests aus.
+fn main(boot_info: &'static BootInfo) -> !
+{
+    simple_os::init();
+    unsafe { memory::init(boot_info) };
+
+    test_main();
+    simple_os::hlt_loop();
+}
+
+/// ## Panic Handler
+///
+/// Leitet alle Panic-Informationen an [simple_os::test_panic_handler] weiter.
+#[panic_handler]
+fn panic(info: &PanicInfo) -> !
+{
+    simple_os::test_panic_handler(info)
+}
+
+#[test_case]
+fn test_hello()
+{
+    let program = elf::load(HELLO, &["hello"], &[]).expect("loading hello.elf failed");
+    assert_eq!(unsafe { program.run() }, 26);
+}
+
+#[test_case]
+fn test_args_env_and_auxv()
+{
+    let program = elf::load(ARGS, &["args", "xyz"], &["A=1", "B=2"]).expect("loading args.elf failed");
+    assert_eq!(unsafe { program.run() }, 22);
+}
+
+#[test_case]
+fn test_run_twice()
+{
+    let first = elf::load(HELLO, &["hello"], &[]).expect("loading hello.elf failed");
+    let second = elf::load(ARGS, &["args", "x"], &[]).expect("loading args.elf failed");
+    assert_eq!(unsafe { second.run() }, 2);
+    assert_eq!(unsafe { first.run() }, 26);
+}
+
+#[test_case]
+fn test_rejects_truncated_file()
+{
+    assert_eq!(elf::load(&HELLO[..32], &[], &[]).err(), Some(ElfError::TooShort));
+    assert_eq!(elf::load(&HELLO[..100], &[], &[]).err(), Some(ElfError::BadProgramHeaders));
+}
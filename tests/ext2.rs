@@ -0,0 +1,159 @@
+//! # ext2.rs
+//!
+//! Dieses Modul testet den **ext2-Treiber** gegen `tests/ext2/disk.img`, das
+//! QEMU als sekundären Slave (`ata3`) bereitstellt – genauer gegen die Kopie
+//! `tests/ext2/scratch.img`, die `tests/run.sh` vor jedem Lauf anlegt und
+//! danach mit `e2fsck` prüft. Das Dateisystem liegt ohne
+//! Partitionstabelle auf der Platte und wird von `tests/ext2/build.sh` mit
+//! `mke2fs -d` erzeugt (1-KiB-Blöcke, zwei Blockgruppen):
+//!
+//! - `hello.txt` (Rechte `0640`), `script.sh` (`0755`)
+//! - `big.bin`: 300 KiB, Byte `i` ist `i % 253` (zweifach indirekte Blöcke)
+//! - `sparse.bin`: 64 KiB Loch, danach `after the hole\n`
+//! - `dir/sub/nested.txt`
+//! - `link` → `hello.txt` (im Inode), `long-link` (mit eigenem Datenblock)
+//!
+//! ## Übersicht
+//!
+//! - Kein std und kein main, da Bare-Metal-Umgebung
+//! - Nutzt das Custom Test Framework aus [lib.rs](../lib.rs.html)
+//! - Schreibtests laufen über das VFS und landen in der Kopie; danach müssen
+//!   die Zähler freier Blöcke und Inodes wieder genau stimmen
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(simple_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use bootloader::{BootInfo, entry_point};
+use core::panic::PanicInfo;
+use simple_os::block;
+use simple_os::memory;
+use simple_os::sync::Once;
+use simple_os::vfs::{self, Ext2Fs, FileSystem, FileType, OpenFlags, SeekFrom, VfsError};
+
+entry_point!(main);
+
+/// Einhängepunkt des Testdateisystems.
+const MOUNT_POINT: &str = "/ext2";
+
+/// Das eingehängte Dateisystem, für seine Zähler.
+static FS: Once<Arc<Ext2Fs>> = Once::new();
+
+/// ## Einstiegspunkt (main)
+///
+/// Initialisiert den Kernel samt Speicherverwaltung, hängt das Dateisystem
+/// der Testplatte unter [MOUNT_POINT] ein und führt anschließend alle Tests aus.
+fn main(boot_info: &'static BootInfo) -> !
+{
+    simple_os::init();
+    unsafe { memory::init(boot_info) };
+    block::ata::init();
+    let fs = FS.call_once(|| Arc::new(Ext2Fs::new(block::get("ata3").expect("ext2 test disk not found")).unwrap()));
+    vfs::mkdir(MOUNT_POINT).unwrap();
+    vfs::mount(MOUNT_POINT, fs.clone()).unwrap();
+
+    test_main();
+    simple_os::hlt_loop();
+}
+
+/// ## Panic Handler
+///
+/// Leitet alle Panic-Informationen an [simple_os::test_panic_handler] weiter.
+#[panic_handler]
+fn panic(info: &PanicInfo) -> !
+{
+    simple_os::test_panic_handler(info)
+}
+
+fn path(name: &str) -> String
+{
+    format!("{}/{}", MOUNT_POINT, name)
+}
+
+#[test_case]
+fn test_superblock()
+{
+    let fs = Ext2Fs::new(block::get("ata3").unwrap()).unwrap();
+    assert_eq!(fs.name(), "ext2");
+    assert_eq!(fs.label(), "simple_os");
+    assert_eq!(fs.block_size(), 1024);
+    assert!(!fs.is_read_only());
+    assert!(fs.free_blocks() > 0 && fs.free_inodes() > 0);
+    // Das Boot-Image ist kein ext2-Dateisystem.
+    assert!(Ext2Fs::new(block::get("ata0").unwrap()).is_err());
+}
+
+#[test_case]
+fn test_read_files()
+{
+    assert_eq!(vfs::read_to_end(&path("hello.txt")).unwrap(), b"Hello from ext2!\n");
+    assert_eq!(vfs::read_to_end(&path("dir/sub/nested.txt")).unwrap(), b"nested\n");
+
+    let big = vfs::read_to_end(&path("big.bin")).unwrap();
+    assert_eq!(big.len(), 300 * 1024);
+    assert!(big.iter().enumerate().all(|(index, &byte)| byte == (index % 253) as u8));
+
+    let sparse = vfs::read_to_end(&path("sparse.bin")).unwrap();
+    assert_eq!(sparse.len(), 65536 + 15);
+    assert!(sparse[..65536].iter().all(|&byte| byte == 0));
+    assert_eq!(&sparse[65536..], b"after the hole\n");
+}
+
+#[test_case]
+fn test_metadata_and_symlinks()
+{
+    assert_eq!(vfs::stat(&path("hello.txt")).unwrap().mode, 0o640);
+    assert_eq!(vfs::stat(&path("script.sh")).unwrap().mode, 0o755);
+    assert_eq!(vfs::stat(&path("dir")).unwrap().file_type, FileType::Directory);
+
+    assert_eq!(vfs::readlink(&path("link")).unwrap(), "hello.txt");
+    assert_eq!(vfs::lstat(&path("link")).unwrap().file_type, FileType::Symlink);
+    assert_eq!(vfs::read_to_end(&path("link")).unwrap(), b"Hello from ext2!\n");
+    assert_eq!(vfs::read_to_end(&path("long-link")).unwrap(), b"nested\n");
+
+    let names: Vec<_> = vfs::readdir(MOUNT_POINT).unwrap().into_iter().map(|entry| entry.name).collect();
+    assert!(names.iter().any(|name| name == "lost+found"));
+    assert!(names.iter().any(|name| name == "sparse.bin"));
+}
+
+#[test_case]
+fn test_write_and_delete()
+{
+    let fs = FS.get().unwrap();
+    let before = (fs.free_blocks(), fs.free_inodes(), fs.group_free_counts());
+
+    let dir = path("new dir");
+    vfs::mkdir(&dir).unwrap();
+    let name = format!("{}/file.bin", dir);
+    let data: Vec<u8> = (0..50_000u32).map(|index| (index * 7) as u8).collect();
+    let fd = vfs::open(&name, OpenFlags::WRITE | OpenFlags::CREATE).unwrap();
+    assert_eq!(vfs::write(fd, &data).unwrap(), data.len());
+    vfs::seek(fd, SeekFrom::Start(200_000)).unwrap();
+    vfs::write(fd, b"end").unwrap();
+    vfs::close(fd).unwrap();
+    assert!(fs.free_blocks() < before.0 && fs.free_inodes() == before.1 - 2);
+
+    let read = vfs::read_to_end(&name).unwrap();
+    assert_eq!(read.len(), 200_003);
+    assert_eq!(&read[..data.len()], &data[..]);
+    assert!(read[data.len()..200_000].iter().all(|&byte| byte == 0));
+
+    vfs::chmod(&name, 0o600).unwrap();
+    assert_eq!(vfs::stat(&name).unwrap().mode, 0o600);
+    assert_eq!(vfs::unlink(&dir), Err(VfsError::NotEmpty));
+    vfs::unlink(&name).unwrap();
+    vfs::unlink(&dir).unwrap();
+    assert_eq!(vfs::stat(&dir), Err(VfsError::NotFound));
+
+    // Jeder belegte Block und Inode muss in Superblock und Deskriptoren
+    // wieder freigegeben sein.
+    assert_eq!((fs.free_blocks(), fs.free_inodes(), fs.group_free_counts()), before);
+    fs.sync().unwrap();
+}
@@ -0,0 +1,31 @@
+#!/bin/sh
+# Baut tests/ext2/disk.img für tests/ext2.rs: ein ext2-Dateisystem ohne
+# Partitionstabelle mit 1-KiB-Blöcken und zwei Blockgruppen, befüllt mit
+# mke2fs -d und anschließend mit e2fsck geprüft.
+#
+# Benötigt: mke2fs und e2fsck (e2fsprogs).
+set -e
+cd "$(dirname "$0")"
+
+content=$(mktemp -d)
+# Erst am Ende umbenennen, damit ein Fehler kein halbes Abbild hinterlässt.
+trap 'rm -rf "$content" disk.img.tmp' EXIT
+printf 'Hello from ext2!\n' > "$content/hello.txt"
+# 300 KiB reichen mit 1-KiB-Blöcken bis in die zweifach indirekten Blöcke:
+# Byte i hat den Wert i mod 253.
+python3 -c 'import sys; sys.stdout.buffer.write(bytes(i % 253 for i in range(300 * 1024)))' > "$content/big.bin"
+# Loch von 64 KiB vor den Daten.
+python3 -c 'import sys; f = open(sys.argv[1], "wb"); f.seek(65536); f.write(b"after the hole\n")' "$content/sparse.bin"
+mkdir -p "$content/dir/sub"
+printf 'nested\n' > "$content/dir/sub/nested.txt"
+printf '#!/bin/sh\necho hi\n' > "$content/script.sh"
+chmod 755 "$content/script.sh"
+chmod 640 "$content/hello.txt"
+ln -s hello.txt "$content/link"
+# Länger als 60 Zeichen, daher mit eigenem Datenblock.
+ln -s dir/sub/../sub/../sub/../sub/../sub/../sub/../sub/nested.txt "$content/long-link"
+
+rm -f disk.img.tmp
+mke2fs -q -F -t ext2 -b 1024 -N 256 -L simple_os -E root_owner=0:0 -d "$content" disk.img.tmp 16M
+e2fsck -fn disk.img.tmp
+mv disk.img.tmp disk.img
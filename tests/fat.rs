@@ -0,0 +1,181 @@
+//! # fat.rs
+//!
+//! Dieses Modul testet den **FAT-Treiber** gegen `tests/fat/disk.img`, das
+//! QEMU als sekundären Master (`ata2`) bereitstellt. Die Platte enthält drei
+//! Partitionen, erzeugt von `tests/fat/build.sh`:
+//!
+//! - `ata2p1`: FAT12, Bezeichnung `FAT12`
+//! - `ata2p2`: FAT16, Bezeichnung `FAT16`
+//! - `ata2p3`: FAT32, Bezeichnung `FAT32`
+//!
+//! Jede Partition enthält `HELLO.TXT`, `A long file name.bin` (5000 Bytes,
+//! Byte `i` ist `i % 251`) und `docs/sub dir/Nested File.txt`.
+//!
+//! ## Übersicht
+//!
+//! - Kein std und kein main, da Bare-Metal-Umgebung
+//! - Nutzt das Custom Test Framework aus [lib.rs](../lib.rs.html)
+//! - Schreibtests laufen über das VFS; QEMU verwirft die Änderungen (`snapshot=on`)
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(simple_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use bootloader::{BootInfo, entry_point};
+use core::panic::PanicInfo;
+use simple_os::block::{self, BlockDevice};
+use simple_os::memory;
+use simple_os::vfs::fat::FatType;
+use simple_os::vfs::{self, FatFs, FileSystem, OpenFlags, VfsError};
+
+entry_point!(main);
+
+/// Partitionen mit Einhängepunkt und erwartetem FAT-Typ.
+const VOLUMES: [(&str, &str, FatType); 3] = [
+    ("ata2p1", "/fat12", FatType::Fat12),
+    ("ata2p2", "/fat16", FatType::Fat16),
+    ("ata2p3", "/fat32", FatType::Fat32),
+];
+
+/// ## Einstiegspunkt (main)
+///
+/// Initialisiert den Kernel samt Speicherverwaltung, liest die Partitionen der
+/// Testplatte und führt anschließend alle Tests aus.
+fn main(boot_info: &'static BootInfo) -> !
+{
+    simple_os::init();
+    unsafe { memory::init(boot_info) };
+    block::ata::init();
+    let disk = block::get("ata2").expect("FAT test disk not found");
+    block::partition::scan(&disk).unwrap();
+
+    test_main();
+    simple_os::hlt_loop();
+}
+
+/// ## Panic Handler
+///
+/// Leitet alle Panic-Informationen an [simple_os::test_panic_handler] weiter.
+#[panic_handler]
+fn panic(info: &PanicInfo) -> !
+{
+    simple_os::test_panic_handler(info)
+}
+
+/// Hängt alle drei Partitionen ein, sofern noch nicht geschehen.
+fn mount_all()
+{
+    for (device, path, _) in VOLUMES
+    {
+        if vfs::stat(path).is_err()
+        {
+            vfs::mkdir(path).unwrap();
+        }
+        let fs = FatFs::new(block::get(device).unwrap()).unwrap();
+        let _ = vfs::mount(path, Arc::new(fs));
+    }
+}
+
+#[test_case]
+fn test_partitions()
+{
+    let disk: Arc<dyn BlockDevice> = block::get("ata2").unwrap();
+    let partitions = block::partition::read_mbr(&disk).unwrap();
+    let kinds: Vec<u8> = partitions.iter().map(|partition| partition.kind()).collect();
+    assert_eq!(kinds, [0x01, 0x06, 0x0C]);
+    assert_eq!(partitions[0].start(), 2048);
+    assert_eq!(partitions[2].sector_count(), 81920);
+}
+
+#[test_case]
+fn test_type_and_label()
+{
+    for (device, _, fat_type) in VOLUMES
+    {
+        let fs = FatFs::new(block::get(device).unwrap()).unwrap();
+        assert_eq!(fs.fat_type(), fat_type);
+        assert_eq!(fs.label().unwrap().as_deref(), Some(format!("{:?}", fat_type).to_uppercase().as_str()));
+        assert_eq!(fs.name(), "fat");
+    }
+}
+
+#[test_case]
+fn test_read_files()
+{
+    mount_all();
+    for (_, path, _) in VOLUMES
+    {
+        assert_eq!(vfs::read_to_end(&format!("{}/HELLO.TXT", path)).unwrap(), b"Hello from FAT!\n");
+        // Kurze Namen sind unabhängig von Groß- und Kleinschreibung.
+        assert!(vfs::stat(&format!("{}/hello.txt", path)).is_ok());
+
+        let data = vfs::read_to_end(&format!("{}/A long file name.bin", path)).unwrap();
+        assert_eq!(data.len(), 5000);
+        assert!(data.iter().enumerate().all(|(index, &byte)| byte == (index % 251) as u8));
+
+        let nested = vfs::read_to_end(&format!("{}/docs/sub dir/Nested File.txt", path)).unwrap();
+        assert_eq!(nested, b"nested\n");
+    }
+}
+
+#[test_case]
+fn test_write_and_delete()
+{
+    mount_all();
+    for (device, path, _) in VOLUMES
+    {
+        let fs = FatFs::new(block::get(device).unwrap()).unwrap();
+        let free = fs.free_clusters().unwrap();
+
+        let dir = format!("{}/Neues Verzeichnis", path);
+        vfs::mkdir(&dir).unwrap();
+        let name = format!("{}/eine Datei mit langem Namen.txt", dir);
+        let data: Vec<u8> = (0..10_000u32).map(|index| (index * 7) as u8).collect();
+        let fd = vfs::open(&name, OpenFlags::WRITE | OpenFlags::CREATE).unwrap();
+        assert_eq!(vfs::write(fd, &data).unwrap(), data.len());
+        vfs::close(fd).unwrap();
+        assert_eq!(vfs::read_to_end(&name).unwrap(), data);
+        assert_eq!(vfs::unlink(&dir), Err(VfsError::NotEmpty));
+
+        vfs::unlink(&name).unwrap();
+        vfs::unlink(&dir).unwrap();
+        assert_eq!(vfs::stat(&dir), Err(VfsError::NotFound));
+        assert_eq!(fs.free_clusters().unwrap(), free);
+    }
+}
+
+#[test_case]
+fn test_write_after_unlink()
+{
+    mount_all();
+    for (device, path, _) in VOLUMES
+    {
+        let fs = FatFs::new(block::get(device).unwrap()).unwrap();
+        let free = fs.free_clusters().unwrap();
+
+        let name = format!("{}/geloescht.txt", path);
+        let fd = vfs::open(&name, OpenFlags::WRITE | OpenFlags::CREATE).unwrap();
+        assert_eq!(vfs::write(fd, b"vorher").unwrap(), 6);
+        vfs::unlink(&name).unwrap();
+
+        // Der freie Eintrag kann sofort neu vergeben werden und darf von dem
+        // noch geöffneten Inode nicht überschrieben werden.
+        let other = format!("{}/neu.txt", path);
+        let other_fd = vfs::open(&other, OpenFlags::WRITE | OpenFlags::CREATE).unwrap();
+        assert_eq!(vfs::write(other_fd, b"neu").unwrap(), 3);
+        vfs::close(other_fd).unwrap();
+
+        assert_eq!(vfs::write(fd, b"nachher"), Err(VfsError::NotFound));
+        vfs::close(fd).unwrap();
+        assert_eq!(vfs::read_to_end(&other).unwrap(), b"neu");
+
+        vfs::unlink(&other).unwrap();
+        assert_eq!(fs.free_clusters().unwrap(), free);
+    }
+}
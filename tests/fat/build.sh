@@ -0,0 +1,38 @@
+#!/bin/sh
+# Baut tests/fat/disk.img für tests/fat.rs: ein Datenträger mit MBR und drei
+# Partitionen, die mit mkfs.fat formatiert und mit mtools befüllt werden.
+#
+#   Partition 1: FAT12,  1 MiB ab Sektor  2048
+#   Partition 2: FAT16,  8 MiB ab Sektor  4096
+#   Partition 3: FAT32, 40 MiB ab Sektor 20480
+#
+# Benötigt: sfdisk (util-linux), mkfs.fat (dosfstools), mtools.
+set -e
+cd "$(dirname "$0")"
+
+# Erst am Ende umbenennen, damit ein Fehler kein halbes Abbild hinterlässt.
+rm -f disk.img.tmp
+content=$(mktemp -d)
+trap 'rm -rf "$content" disk.img.tmp' EXIT
+
+truncate -s 50M disk.img.tmp
+printf 'label: dos\n2048,2048,1\n4096,16384,6\n20480,81920,c\n' | sfdisk -q disk.img.tmp
+
+mkfs.fat -F 12 -s 4 -n FAT12 --offset 2048 disk.img.tmp 1024
+mkfs.fat -F 16 -s 1 -n FAT16 --offset 4096 disk.img.tmp 8192
+mkfs.fat -F 32 -s 1 -n FAT32 --offset 20480 disk.img.tmp 40960
+
+printf 'Hello from FAT!\n' > "$content/HELLO.TXT"
+# 5000 Bytes über mehrere Cluster: Byte i hat den Wert i mod 251.
+python3 -c 'import sys; sys.stdout.buffer.write(bytes(i % 251 for i in range(5000)))' > "$content/long.bin"
+mkdir -p "$content/docs/sub dir"
+printf 'nested\n' > "$content/docs/sub dir/Nested File.txt"
+
+for start in 2048 4096 20480
+do
+    image="disk.img.tmp@@$((start * 512))"
+    mcopy -i "$image" "$content/HELLO.TXT" ::HELLO.TXT
+    mcopy -i "$image" "$content/long.bin" "::A long file name.bin"
+    mcopy -s -i "$image" "$content/docs" ::docs
+done
+mv disk.img.tmp disk.img
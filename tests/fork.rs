@@ -0,0 +1,154 @@
+//! # fork.rs
+//!
+//! Dieses Modul testet **Copy-on-Write** beim Kopieren von Adressräumen:
+//! geteilte Frames, Referenzzähler, Isolation nach Schreibzugriffen und den
+//! Systemaufruf `fork` aus einem User-Programm samt Abräumen verwaister Kinder.
+//!
+//! ## Übersicht
+//!
+//! - Kein std und kein main, da Bare-Metal-Umgebung
+//! - Nutzt das Custom Test Framework aus [lib.rs](../lib.rs.html)
+//! - Verwendet `tests/elf/fork.elf` und `tests/elf/orphan.elf` (siehe
+//!   `tests/elf/build.sh`)
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(simple_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use bootloader::{BootInfo, entry_point};
+use core::panic::PanicInfo;
+use simple_os::memory::{self, AddressSpace, COW, USER_SPACE_START};
+use simple_os::process::{self, WaitError};
+use x86_64::VirtAddr;
+use x86_64::structures::paging::{Page, PageTableFlags};
+
+entry_point!(main);
+
+static FORK: &[u8] = include_bytes!("elf/fork.elf");
+static ORPHAN: &[u8] = include_bytes!("elf/orphan.elf");
+
+/// ## Einstiegspunkt (main)
+///
+/// Initialisiert den Kernel samt Speicherverwaltung und führt anschließend alle Tests aus.
+fn main(boot_info: &'static BootInfo) -> !
+{
+    simple_os::init();
+    unsafe { memory::init(boot_info) };
+
+    test_main();
+    simple_os::hlt_loop();
+}
+
+/// ## Panic Handler
+///
+/// Leitet alle Panic-Informationen an [simple_os::test_panic_handler] weiter.
+#[panic_handler]
+fn panic(info: &PanicInfo) -> !
+{
+    simple_os::test_panic_handler(info)
+}
+
+/// Adressraum mit einer beschreibbaren Seite, die `contents` enthält.
+fn address_space_with(contents: &[u8]) -> (AddressSpace, Page)
+{
+    let page = Page::containing_address(VirtAddr::new(USER_SPACE_START));
+    let mut address_space = AddressSpace::new().expect("address space");
+    address_space.map_user_page(page, PageTableFlags::WRITABLE).expect("map");
+    assert!(address_space.write(page.start_address(), contents));
+    (address_space, page)
+}
+
+/// Liest die ersten sechs Bytes von `page`.
+fn read(address_space: &AddressSpace, page: Page) -> [u8; 6]
+{
+    let mut buffer = [0; 6];
+    assert!(address_space.read(page.start_address(), &mut buffer));
+    buffer
+}
+
+#[test_case]
+fn test_fork_shares_frames()
+{
+    let (mut parent, page) = address_space_with(b"shared");
+    let child = parent.fork().expect("fork");
+
+    let (parent_frame, parent_flags) = parent.translate(page).unwrap();
+    let (child_frame, child_flags) = child.translate(page).unwrap();
+    assert_eq!(parent_frame, child_frame);
+    assert_eq!(memory::frame_ref_count(parent_frame), 2);
+    for flags in [parent_flags, child_flags]
+    {
+        assert!(flags.contains(COW));
+        assert!(!flags.contains(PageTableFlags::WRITABLE));
+    }
+    assert_eq!(&read(&child, page), b"shared");
+}
+
+#[test_case]
+fn test_write_after_fork_is_isolated()
+{
+    let (mut parent, page) = address_space_with(b"before");
+    let mut child = parent.fork().expect("fork");
+    let (shared, _) = parent.translate(page).unwrap();
+
+    // Der Elternprozess schreibt zuerst und bekommt eine Kopie.
+    assert!(parent.write(page.start_address(), b"parent"));
+    let (parent_frame, parent_flags) = parent.translate(page).unwrap();
+    assert_ne!(parent_frame, shared);
+    assert!(parent_flags.contains(PageTableFlags::WRITABLE) && !parent_flags.contains(COW));
+    assert_eq!(memory::frame_ref_count(shared), 1);
+
+    // Das Kind ist jetzt alleiniger Besitzer und muss nicht mehr kopieren.
+    assert!(child.write(page.start_address(), b"child!"));
+    let (child_frame, child_flags) = child.translate(page).unwrap();
+    assert_eq!(child_frame, shared);
+    assert!(child_flags.contains(PageTableFlags::WRITABLE) && !child_flags.contains(COW));
+
+    assert_eq!(&read(&parent, page), b"parent");
+    assert_eq!(&read(&child, page), b"child!");
+}
+
+#[test_case]
+fn test_dropping_forks_releases_frames()
+{
+    let frames = memory::allocated_frames();
+    {
+        let (mut parent, page) = address_space_with(b"frames");
+        let child = parent.fork().expect("fork");
+        let grandchild = { let mut child = child; child.fork().expect("fork") };
+        let (frame, _) = grandchild.translate(page).unwrap();
+        assert_eq!(memory::frame_ref_count(frame), 2);
+        drop(parent);
+        assert_eq!(memory::frame_ref_count(frame), 1);
+    }
+    assert_eq!(memory::allocated_frames(), frames);
+}
+
+#[test_case]
+fn test_fork_syscall()
+{
+    let pid = process::spawn("fork", FORK, &["fork"], &[]).expect("spawn failed");
+    assert_eq!(process::wait(Some(pid)), Ok((pid, 11_101)));
+    assert_eq!(process::count(), 1);
+}
+
+#[test_case]
+fn test_orphans_are_reaped()
+{
+    let frames = memory::allocated_frames();
+    let pid = process::spawn("orphan", ORPHAN, &["orphan"], &[]).expect("spawn failed");
+    assert_eq!(process::wait(Some(pid)), Ok((pid, 0)));
+
+    for _ in 0..100
+    {
+        if process::count() == 1
+        {
+            break;
+        }
+        process::yield_now();
+    }
+    assert_eq!(process::count(), 1);
+    assert_eq!(process::wait(None), Err(WaitError::NoChildren));
+    assert_eq!(memory::allocated_frames(), frames);
+}
@@ -0,0 +1,150 @@
+//! # net.rs
+//!
+//! Dieses Modul testet den **Netzwerkstapel** über die Loopback-Schnittstelle
+//! `lo`: ICMP, UDP und TCP laufen vollständig im Kernel, eine Netzwerkkarte
+//! wird nicht gebraucht.
+//!
+//! ## Übersicht
+//!
+//! - Kein std und kein main, da Bare-Metal-Umgebung
+//! - Nutzt das Custom Test Framework aus [lib.rs](../lib.rs.html)
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(simple_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
+use bootloader::{BootInfo, entry_point};
+use core::net::{Ipv4Addr, SocketAddrV4};
+use core::panic::PanicInfo;
+use simple_os::memory;
+use simple_os::net::tcp::TcpState;
+use simple_os::net::{self, SocketError, TcpListener, TcpStream, UdpSocket};
+
+entry_point!(main);
+
+/// ## Einstiegspunkt (main)
+///
+/// Initialisiert den Kernel samt Speicherverwaltung und Loopback-Schnittstelle
+/// und führt anschließend alle Tests aus.
+fn main(boot_info: &'static BootInfo) -> !
+{
+    simple_os::init();
+    unsafe { memory::init(boot_info) };
+    net::init();
+
+    test_main();
+    simple_os::hlt_loop();
+}
+
+/// ## Panic Handler
+///
+/// Leitet alle Panic-Informationen an [simple_os::test_panic_handler] weiter.
+#[panic_handler]
+fn panic(info: &PanicInfo) -> !
+{
+    simple_os::test_panic_handler(info)
+}
+
+fn localhost(port: u16) -> SocketAddrV4
+{
+    SocketAddrV4::new(Ipv4Addr::LOCALHOST, port)
+}
+
+#[test_case]
+fn test_loopback_interface()
+{
+    let config = net::config("lo").expect("lo not configured");
+    assert_eq!(config.address, Ipv4Addr::LOCALHOST);
+    assert_eq!(config.prefix_len, 8);
+    assert!(net::ping(Ipv4Addr::LOCALHOST, 1000).is_ok());
+    assert!(net::ping(Ipv4Addr::new(127, 1, 2, 3), 1000).is_ok());
+    assert_eq!(net::ping(Ipv4Addr::new(192, 0, 2, 1), 100), Err(SocketError::Unreachable));
+}
+
+#[test_case]
+fn test_udp_round_trip()
+{
+    let server = UdpSocket::bind(localhost(7)).unwrap();
+    let mut client = UdpSocket::bind(localhost(0)).unwrap();
+    assert_eq!(UdpSocket::bind(localhost(7)).err(), Some(SocketError::AddressInUse));
+    assert_eq!(UdpSocket::bind(SocketAddrV4::new(Ipv4Addr::new(192, 0, 2, 1), 0)).err(), Some(SocketError::AddressNotAvailable));
+
+    client.send_to(b"echo", server.local_addr()).unwrap();
+    let mut buffer = [0; 64];
+    let (len, source) = server.recv_from(&mut buffer).unwrap();
+    assert_eq!(&buffer[..len], b"echo");
+    assert_eq!(source, client.local_addr());
+
+    server.send_to(&buffer[..len], source).unwrap();
+    assert_eq!(client.recv_from(&mut buffer).unwrap(), (4, server.local_addr()));
+
+    client.set_read_timeout(Some(100));
+    assert_eq!(client.recv_from(&mut buffer), Err(SocketError::TimedOut));
+    assert_eq!(client.send_to(&[0; 1500], server.local_addr()), Err(SocketError::MessageTooLarge));
+}
+
+#[test_case]
+fn test_tcp_connection()
+{
+    let listener = TcpListener::bind(localhost(80)).unwrap();
+    let client = TcpStream::connect(localhost(80)).unwrap();
+    let (server, peer) = listener.accept().unwrap();
+    assert_eq!(peer, client.local_addr());
+    assert_eq!(server.peer_addr(), client.local_addr());
+    assert_eq!(client.state(), TcpState::Established);
+
+    client.write_all(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+    let mut buffer = [0; 64];
+    let len = server.read(&mut buffer).unwrap();
+    assert_eq!(&buffer[..len], b"GET / HTTP/1.0\r\n\r\n");
+
+    server.write_all(b"HTTP/1.0 200 OK\r\n").unwrap();
+    server.shutdown().unwrap();
+    assert_eq!(server.write(b"more"), Err(SocketError::NotConnected));
+    let len = client.read(&mut buffer).unwrap();
+    assert_eq!(&buffer[..len], b"HTTP/1.0 200 OK\r\n");
+    assert_eq!(client.read(&mut buffer), Ok(0));
+    assert_eq!(client.state(), TcpState::CloseWait);
+
+    drop(client);
+    assert_eq!(server.read(&mut buffer), Ok(0));
+    assert_eq!(server.state(), TcpState::TimeWait);
+}
+
+#[test_case]
+fn test_tcp_refused()
+{
+    assert_eq!(TcpStream::connect(localhost(81)).err(), Some(SocketError::ConnectionRefused));
+    assert_eq!(TcpStream::connect(localhost(0)).err(), Some(SocketError::InvalidInput));
+}
+
+#[test_case]
+fn test_tcp_large_transfer()
+{
+    // Größer als Sende- und Empfangspuffer zusammen: Ohne Fenster und
+    // Bestätigungen käme nicht alles an.
+    let listener = TcpListener::bind(localhost(0)).unwrap();
+    let client = TcpStream::connect(listener.local_addr()).unwrap();
+    let (server, _) = listener.accept().unwrap();
+
+    let data: Vec<u8> = (0..100_000u32).map(|i| (i * 7 + i / 1000) as u8).collect();
+    let mut received = Vec::new();
+    let mut buffer = vec![0; 4096];
+    for chunk in data.chunks(8192)
+    {
+        client.write_all(chunk).unwrap();
+        let expected = received.len() + chunk.len();
+        while received.len() < expected
+        {
+            let len = server.read(&mut buffer).unwrap();
+            received.extend_from_slice(&buffer[..len]);
+        }
+    }
+    client.flush().unwrap();
+    assert!(received == data);
+}
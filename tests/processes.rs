@@ -0,0 +1,90 @@
+//! # processes.rs
+//!
+//! Dieses Modul testet die **Prozessverwaltung**: Starten von ELF-Programmen
+//! als eigene Prozesse, Warten auf ihr Ende und das Abräumen der Zombies.
+//!
+//! ## Übersicht
+//!
+//! - Kein std und kein main, da Bare-Metal-Umgebung
+//! - Nutzt das Custom Test Framework aus [lib.rs](../lib.rs.html)
+//! - Verwendet die Programme aus `tests/elf/` (siehe `tests/elf/build.sh`)
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(simple_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use bootloader::{BootInfo, entry_point};
+use core::panic::PanicInfo;
+use simple_os::memory;
+use simple_os::process::{self, Pid, State, WaitError};
+
+entry_point!(main);
+
+static HELLO: &[u8] = include_bytes!("elf/hello.elf");
+static YIELD: &[u8] = include_bytes!("elf/yield.elf");
+
+/// ## Einstiegspunkt (main)
+///
+/// Initialisiert den Kernel samt Speicherverwaltung und führt anschließend alle Tests aus.
+fn main(boot_info: &'static BootInfo) -> !
+{
+    simple_os::init();
+    unsafe { memory::init(boot_info) };
+
+    test_main();
+    simple_os::hlt_loop();
+}
+
+/// ## Panic Handler
+///
+/// Leitet alle Panic-Informationen an [simple_os::test_panic_handler] weiter.
+#[panic_handler]
+fn panic(info: &PanicInfo) -> !
+{
+    simple_os::test_panic_handler(info)
+}
+
+#[test_case]
+fn test_spawn_and_wait()
+{
+    let pid = process::spawn("hello", HELLO, &["hello"], &[]).expect("spawn failed");
+    assert_eq!(process::state(pid), Some(State::Ready));
+    assert_eq!(process::wait(Some(pid)), Ok((pid, 26)));
+    assert_eq!(process::state(pid), None);
+}
+
+#[test_case]
+fn test_wait_for_any_child()
+{
+    let pids = [
+        process::spawn("yield", YIELD, &["yield"], &[]).expect("spawn failed"),
+        process::spawn("yield", YIELD, &["yield"], &[]).expect("spawn failed"),
+        process::spawn("yield", YIELD, &["yield"], &[]).expect("spawn failed"),
+    ];
+    assert!(pids[0] != pids[1] && pids[1] != pids[2]);
+
+    let mut reaped = 0;
+    while let Ok((pid, code)) = process::wait(None)
+    {
+        assert!(pids.contains(&pid));
+        assert_eq!(code, pid.as_u64() as i64);
+        reaped += 1;
+    }
+    assert_eq!(reaped, pids.len());
+    assert_eq!(process::wait(None), Err(WaitError::NoChildren));
+}
+
+#[test_case]
+fn test_reaping_frees_resources()
+{
+    let frames = memory::allocated_frames();
+    for _ in 0..3
+    {
+        let pid = process::spawn("hello", HELLO, &["hello"], &[]).expect("spawn failed");
+        assert!(matches!(process::wait(Some(pid)), Ok((_, 26))));
+    }
+    assert_eq!(memory::allocated_frames(), frames);
+    assert_eq!(process::count(), 1);
+    assert_eq!(process::current_pid(), Pid::KERNEL);
+}
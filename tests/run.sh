@@ -0,0 +1,14 @@
+#!/bin/sh
+# Führt alle Tests in QEMU aus und prüft danach das Dateisystem, in das
+# tests/ext2.rs geschrieben hat, mit e2fsck. Argumente gehen an cargo test.
+#
+# Benötigt: bootimage, QEMU und e2fsck (e2fsprogs).
+set -e
+cd "$(dirname "$0")/.."
+
+# Der Build baut fehlende Abbilder (siehe build.rs).
+cargo test --no-run "$@"
+# Jeder Lauf beginnt mit einer frischen Kopie; das Original bleibt unverändert.
+cp tests/ext2/disk.img tests/ext2/scratch.img
+cargo test "$@"
+e2fsck -fn tests/ext2/scratch.img
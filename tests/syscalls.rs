@@ -0,0 +1,310 @@
+//! # syscalls.rs
+//!
+//! Dieses Modul testet die **Systemaufrufe** aus dem Ring 3.
+//!
+//! Jeder Test startet eine kleine naked function über
+//! [simple_os::usermode::run_user_mode], die einen oder mehrere Systemaufrufe
+//! ausführt und ihr Ergebnis als Exit-Code an den Kernel zurückgibt.
+//!
+//! ## Übersicht
+//!
+//! - Kein std und kein main, da Bare-Metal-Umgebung
+//! - Nutzt das Custom Test Framework aus [lib.rs](../lib.rs.html)
+//! - Testet `write`, `exit`, `getpid`, `sleep` und `yield` über `syscall`
+//!   sowie `write` über das klassische `int 0x80`-Gate
+//! - Prüft `getpid` zusätzlich in einem eigenen Prozess aus `tests/elf/yield.elf`
+//! - Prüft, dass `write` Zeiger in den Kernel mit `EFAULT` ablehnt
+//! - Prüft, dass Exceptions im Ring 3 nur das Programm beenden
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(simple_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use bootloader::{BootInfo, entry_point};
+use core::panic::PanicInfo;
+use simple_os::process::{self, Pid};
+use simple_os::{memory, time, usermode};
+use x86_64::VirtAddr;
+
+entry_point!(main);
+
+/// Beendet sich mit der eigenen PID (siehe `tests/elf/yield.S`).
+static YIELD: &[u8] = include_bytes!("elf/yield.elf");
+
+/// Nachricht, die von den User-Funktionen ausgegeben wird.
+static MESSAGE: [u8; 13] = *b"hello, ring3\n";
+
+/// Adresse einer Seite im User-Space, in die [MESSAGE] kopiert wird. Das
+/// Original im Kernel-Image darf `write` nicht lesen.
+const MESSAGE_ADDR: u64 = 0x0000_0100_0000_0000;
+
+/// ## Einstiegspunkt (main)
+///
+/// Initialisiert den Kernel samt Speicherverwaltung, legt den User-Stack an und
+/// führt anschließend alle Tests aus.
+fn main(boot_info: &'static BootInfo) -> !
+{
+    simple_os::init();
+    unsafe { memory::init(boot_info) };
+
+    let mut mapper = unsafe { memory::active_mapper() };
+    usermode::map_user_stack(
+        &mut mapper,
+        &mut *memory::frame_allocator(),
+        VirtAddr::new(usermode::USER_STACK_TOP),
+        usermode::USER_STACK_PAGES,
+    ).expect("mapping the user stack failed");
+    usermode::map_user_stack(&mut mapper, &mut *memory::frame_allocator(), VirtAddr::new(MESSAGE_ADDR + memory::PAGE_SIZE), 1)
+        .expect("mapping the message page failed");
+    unsafe
+    {
+        core::ptr::copy_nonoverlapping(MESSAGE.as_ptr(), MESSAGE_ADDR as *mut u8, MESSAGE.len());
+        usermode::make_user_accessible(&mut mapper, VirtAddr::from_ptr(&MESSAGE), MESSAGE.len() as u64)
+            .expect("message not mapped");
+    }
+
+    test_main();
+    simple_os::hlt_loop();
+}
+
+/// ## Panic Handler
+///
+/// Leitet alle Panic-Informationen an [simple_os::test_panic_handler] weiter.
+#[panic_handler]
+fn panic(info: &PanicInfo) -> !
+{
+    simple_os::test_panic_handler(info)
+}
+
+/// Führt `function` im Ring 3 aus und gibt deren Exit-Code zurück.
+fn run(function: extern "C" fn() -> !) -> i64
+{
+    let entry = VirtAddr::from_ptr(function as *const ());
+    let mut mapper = unsafe { memory::active_mapper() };
+    unsafe
+    {
+        usermode::make_user_accessible(&mut mapper, entry, 64).expect("user function not mapped");
+        usermode::run_user_mode(entry, VirtAddr::new(usermode::USER_STACK_TOP))
+    }
+}
+
+/// `exit(42)`
+#[unsafe(naked)]
+extern "C" fn user_exit() -> !
+{
+    core::arch::naked_asm!(
+        "mov rax, 1",
+        "mov rdi, 42",
+        "syscall",
+        "ud2",
+    );
+}
+
+/// `exit(write(1, MESSAGE, len))`
+#[unsafe(naked)]
+extern "C" fn user_write() -> !
+{
+    core::arch::naked_asm!(
+        "mov rax, 0",
+        "mov rdi, 1",
+        "mov rsi, {message}",
+        "mov rdx, {len}",
+        "syscall",
+        "mov rdi, rax",
+        "mov rax, 1",
+        "syscall",
+        "ud2",
+        message = const MESSAGE_ADDR,
+        len = const MESSAGE.len(),
+    );
+}
+
+/// `exit(write(7, MESSAGE, len))` – ungültiger Dateideskriptor
+#[unsafe(naked)]
+extern "C" fn user_write_bad_fd() -> !
+{
+    core::arch::naked_asm!(
+        "mov rax, 0",
+        "mov rdi, 7",
+        "mov rsi, {message}",
+        "mov rdx, {len}",
+        "syscall",
+        "mov rdi, rax",
+        "mov rax, 1",
+        "syscall",
+        "ud2",
+        message = const MESSAGE_ADDR,
+        len = const MESSAGE.len(),
+    );
+}
+
+/// `exit(write(1, MESSAGE, len))` mit der Adresse im Kernel-Image, obwohl die
+/// Seite für Ring 3 lesbar ist
+#[unsafe(naked)]
+extern "C" fn user_write_kernel_pointer() -> !
+{
+    core::arch::naked_asm!(
+        "mov rax, 0",
+        "mov rdi, 1",
+        "lea rsi, [rip + {message}]",
+        "mov rdx, {len}",
+        "syscall",
+        "mov rdi, rax",
+        "mov rax, 1",
+        "syscall",
+        "ud2",
+        message = sym MESSAGE,
+        len = const MESSAGE.len(),
+    );
+}
+
+/// `hlt` im Ring 3 löst eine General Protection Fault aus.
+#[unsafe(naked)]
+extern "C" fn user_privileged_instruction() -> !
+{
+    core::arch::naked_asm!("hlt", "ud2");
+}
+
+/// Liest von einer nicht abgebildeten Adresse im User-Space.
+#[unsafe(naked)]
+extern "C" fn user_unmapped_read() -> !
+{
+    core::arch::naked_asm!(
+        "mov rax, {addr}",
+        "mov rax, [rax]",
+        "ud2",
+        addr = const MESSAGE_ADDR + 16 * memory::PAGE_SIZE,
+    );
+}
+
+/// `exit(getpid())`
+#[unsafe(naked)]
+extern "C" fn user_getpid() -> !
+{
+    core::arch::naked_asm!(
+        "mov rax, 2",
+        "syscall",
+        "mov rdi, rax",
+        "mov rax, 1",
+        "syscall",
+        "ud2",
+    );
+}
+
+/// `sleep(200); exit(0)`
+#[unsafe(naked)]
+extern "C" fn user_sleep() -> !
+{
+    core::arch::naked_asm!(
+        "mov rax, 3",
+        "mov rdi, 200",
+        "syscall",
+        "mov rdi, rax",
+        "mov rax, 1",
+        "syscall",
+        "ud2",
+    );
+}
+
+/// `exit(yield())`, prüft zusätzlich, dass `rbx` erhalten bleibt.
+#[unsafe(naked)]
+extern "C" fn user_yield() -> !
+{
+    core::arch::naked_asm!(
+        "mov rbx, 0x1234",
+        "mov rax, 4",
+        "syscall",
+        "mov rdi, rax",
+        "cmp rbx, 0x1234",
+        "je 2f",
+        "mov rdi, -1",
+        "2:",
+        "mov rax, 1",
+        "syscall",
+        "ud2",
+    );
+}
+
+/// `exit(write(1, MESSAGE, len))` über `int 0x80`
+#[unsafe(naked)]
+extern "C" fn user_int80_write() -> !
+{
+    core::arch::naked_asm!(
+        "mov rax, 0",
+        "mov rdi, 1",
+        "mov rsi, {message}",
+        "mov rdx, {len}",
+        "int 0x80",
+        "mov rdi, rax",
+        "mov rax, 1",
+        "int 0x80",
+        "ud2",
+        message = const MESSAGE_ADDR,
+        len = const MESSAGE.len(),
+    );
+}
+
+#[test_case]
+fn test_exit()
+{
+    assert_eq!(run(user_exit), 42);
+}
+
+#[test_case]
+fn test_write()
+{
+    assert_eq!(run(user_write), MESSAGE.len() as i64);
+}
+
+#[test_case]
+fn test_write_bad_fd()
+{
+    assert_eq!(run(user_write_bad_fd), -9);
+}
+
+#[test_case]
+fn test_write_kernel_pointer()
+{
+    assert_eq!(run(user_write_kernel_pointer), -14);
+}
+
+#[test_case]
+fn test_getpid()
+{
+    // Über run_user_mode läuft das Programm im Kernel-Prozess.
+    assert_eq!(run(user_getpid), Pid::KERNEL.as_u64() as i64);
+
+    // Ein eigener Prozess beendet sich mit der PID, die `getpid` ihm meldet.
+    let pid = process::spawn("yield", YIELD, &["yield"], &[]).expect("spawn failed");
+    assert_ne!(pid, Pid::KERNEL);
+    assert_eq!(process::wait(Some(pid)), Ok((pid, pid.as_u64() as i64)));
+}
+
+#[test_case]
+fn test_sleep()
+{
+    let start = time::ticks();
+    assert_eq!(run(user_sleep), 0);
+    assert!(time::ticks() - start >= time::ms_to_ticks(200));
+}
+
+#[test_case]
+fn test_yield()
+{
+    assert_eq!(run(user_yield), 0);
+}
+
+#[test_case]
+fn test_int80_write()
+{
+    assert_eq!(run(user_int80_write), MESSAGE.len() as i64);
+}
+
+#[test_case]
+fn test_faults_terminate_program()
+{
+    assert_eq!(run(user_privileged_instruction), process::FAULT_EXIT_CODE);
+    assert_eq!(run(user_unmapped_read), process::FAULT_EXIT_CODE);
+    assert!(x86_64::instructions::interrupts::are_enabled(), "interrupts still disabled after a fault");
+}
@@ -0,0 +1,158 @@
+//! # user_mode.rs
+//!
+//! Dieses Modul testet den Wechsel in den **Ring 3** (User Mode).
+//!
+//! Zuerst wird mit der IDT des Kernels über [simple_os::usermode::run_user_mode]
+//! eine Funktion gestartet, die `ud2` ausführt. Die Invalid-Opcode-Exception
+//! darf nur das Programm mit [simple_os::process::FAULT_EXIT_CODE] beenden.
+//!
+//! Danach wird eine Funktion, die den privilegierten Befehl `hlt` ausführt, mit
+//! [simple_os::usermode::enter_user_mode] auf einem eigenen User-Stack gestartet.
+//! Die CPU muss daraufhin eine **General Protection Fault** auslösen, die vom
+//! Kernel auf dem Stack aus `TSS.privilege_stack_table[0]` behandelt wird.
+//!
+//! ## Übersicht
+//!
+//! - Kein std und kein main, da Bare-Metal-Umgebung
+//! - Nutzt [QemuExitCode] und [exit_qemu] für die Testauswertung
+//! - Initialisiert für den zweiten Teil eine eigene Interrupt Descriptor Table
+//!   (IDT) mit #GP-Handler
+#![no_std]
+#![no_main]
+#![feature(abi_x86_interrupt)]
+
+use bootloader::{BootInfo, entry_point};
+use core::panic::PanicInfo;
+use lazy_static::lazy_static;
+use simple_os::{exit_qemu, serial_print, serial_println, QemuExitCode};
+use simple_os::{memory, process, usermode};
+use x86_64::VirtAddr;
+use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame};
+
+entry_point!(main);
+
+/// ## Einstiegspunkt (main)
+///
+/// Lädt GDT und die IDT des Kernels, maskiert alle Hardware-Interrupts und legt
+/// einen User-Stack an. Nach dem `ud2`-Test wird die Test-IDT geladen und in
+/// den User Mode gesprungen.
+///
+/// Kehrt die Ausführung danach jemals hierher zurück, gilt der Test als
+/// fehlgeschlagen.
+fn main(boot_info: &'static BootInfo) -> !
+{
+    simple_os::gdt::init();
+    simple_os::interrupts::init_idt();
+    unsafe
+    {
+        let mut pics = simple_os::interrupts::PICS.lock();
+        pics.initialize();
+        pics.write_masks(0xff, 0xff);
+        memory::init(boot_info);
+    }
+
+    let mut mapper = unsafe { memory::active_mapper() };
+    let stack_top = usermode::map_user_stack(
+        &mut mapper,
+        &mut *memory::frame_allocator(),
+        VirtAddr::new(usermode::USER_STACK_TOP),
+        usermode::USER_STACK_PAGES,
+    ).expect("mapping the user stack failed");
+
+    serial_print!("user_mode::invalid_opcode_terminates_program...\t");
+    let entry = VirtAddr::from_ptr(user_invalid_opcode as *const ());
+    let code = unsafe
+    {
+        usermode::make_user_accessible(&mut mapper, entry, 16).expect("user function not mapped");
+        usermode::run_user_mode(entry, stack_top)
+    };
+    if code != process::FAULT_EXIT_CODE
+    {
+        serial_println!("[failed]");
+        serial_println!("expected exit code {}, got {}", process::FAULT_EXIT_CODE, code);
+        exit_qemu(QemuExitCode::Failed);
+    }
+    serial_println!("[ok]");
+
+    serial_print!("user_mode::privileged_instruction_faults...\t");
+    init_test_idt();
+    let entry = VirtAddr::from_ptr(user_function as *const ());
+    unsafe
+    {
+        usermode::make_user_accessible(&mut mapper, entry, 16).expect("user function not mapped");
+        usermode::enter_user_mode(entry, stack_top);
+    }
+}
+
+/// ## user_invalid_opcode()
+///
+/// Läuft im Ring 3 und führt `ud2` aus.
+#[unsafe(naked)]
+extern "C" fn user_invalid_opcode() -> !
+{
+    core::arch::naked_asm!("ud2");
+}
+
+/// ## user_function()
+///
+/// Läuft im Ring 3 und führt `hlt` aus. Als naked function erzeugt der
+/// Compiler keinen Prolog, es wird also ausschließlich dieser Code ausgeführt.
+#[unsafe(naked)]
+extern "C" fn user_function() -> !
+{
+    core::arch::naked_asm!("2:", "hlt", "jmp 2b");
+}
+
+/// ## General Protection Fault Handler
+///
+/// Prüft anhand der unteren zwei Bits des gesicherten Code-Segments, dass die
+/// Exception wirklich aus dem Ring 3 kam, und beendet QEMU entsprechend.
+extern "x86-interrupt" fn test_general_protection_fault_handler(
+    stack_frame: InterruptStackFrame,
+    _error_code: u64,
+)
+{
+    if stack_frame.code_segment & 0b11 == 3
+    {
+        serial_println!("[ok]");
+        exit_qemu(QemuExitCode::Success);
+    }
+    else
+    {
+        serial_println!("[failed]");
+        serial_println!("#GP did not originate from ring 3: {:#?}", stack_frame);
+        exit_qemu(QemuExitCode::Failed);
+    }
+    simple_os::hlt_loop();
+}
+
+/// ## Panic Handler
+///
+/// Leitet die Ausgabe an das Test-Framework von `simple_os` weiter.
+#[panic_handler]
+fn panic(info: &PanicInfo) -> !
+{
+    simple_os::test_panic_handler(info);
+}
+
+lazy_static!
+{
+    /// ## Test-IDT (`TEST_IDT`)
+    ///
+    /// Interrupt Descriptor Table für diesen Test, die nur die General
+    /// Protection Fault abfängt.
+    static ref TEST_IDT: InterruptDescriptorTable =
+    {
+        let mut idt = InterruptDescriptorTable::new();
+        idt.general_protection_fault.set_handler_fn(test_general_protection_fault_handler);
+        idt
+    };
+}
+
+/// ## init_test_idt()
+///
+/// Lädt die Test-IDT (`TEST_IDT`) in die CPU.
+pub fn init_test_idt()
+{
+    TEST_IDT.load();
+}
@@ -0,0 +1,126 @@
+//! # virtio_blk.rs
+//!
+//! Dieses Modul testet den **virtio-blk-Treiber** gegen `tests/disk.img`, das
+//! QEMU als `virtio-blk-pci` bereitstellt (siehe `test-args` in `Cargo.toml`).
+//!
+//! ## Übersicht
+//!
+//! - Kein std und kein main, da Bare-Metal-Umgebung
+//! - Nutzt das Custom Test Framework aus [lib.rs](../lib.rs.html)
+//! - Schreibt auf `vda`; QEMU verwirft die Änderungen (`snapshot=on`)
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(simple_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+extern crate alloc;
+
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use bootloader::{BootInfo, entry_point};
+use core::panic::PanicInfo;
+use simple_os::block::virtio::VirtioBlk;
+use simple_os::block::{self, BlockDevice, BlockError};
+use simple_os::virtio::{DeviceType, VENDOR_ID};
+use simple_os::{memory, pci};
+
+entry_point!(main);
+
+/// ## Einstiegspunkt (main)
+///
+/// Initialisiert den Kernel samt Speicherverwaltung, erkennt die PCI-Geräte
+/// und führt anschließend alle Tests aus.
+fn main(boot_info: &'static BootInfo) -> !
+{
+    simple_os::init();
+    unsafe { memory::init(boot_info) };
+    block::virtio::register_driver();
+    pci::init();
+
+    test_main();
+    simple_os::hlt_loop();
+}
+
+/// ## Panic Handler
+///
+/// Leitet alle Panic-Informationen an [simple_os::test_panic_handler] weiter.
+#[panic_handler]
+fn panic(info: &PanicInfo) -> !
+{
+    simple_os::test_panic_handler(info)
+}
+
+fn disk() -> Arc<VirtioBlk>
+{
+    block::virtio::disks().into_iter().next().expect("virtio disk not found")
+}
+
+fn disk_address() -> pci::PciAddress
+{
+    let ids = [DeviceType::Block.transitional_id(), DeviceType::Block.modern_id()];
+    pci::devices().iter().find(|device| device.vendor_id == VENDOR_ID && ids.contains(&device.device_id)).unwrap().address
+}
+
+#[test_case]
+fn test_disk_detected()
+{
+    let disk = disk();
+    assert_eq!(disk.name(), "vda");
+    assert_eq!(disk.sector_count(), 128);
+    assert!(!disk.is_read_only());
+    assert!(block::get("vda").is_some());
+    assert_eq!(pci::driver_name(disk_address()).as_deref(), Some("virtio-blk"));
+}
+
+#[test_case]
+fn test_read_multiple_sectors()
+{
+    let disk = disk();
+    let mut buffer = vec![0; 3 * 512];
+    disk.read_sectors(1, &mut buffer).unwrap();
+    assert!(buffer.starts_with(b"simple_os test disk"));
+    assert!(buffer[512..1024].iter().all(|&byte| byte == 2));
+    assert!(buffer[1024..].iter().all(|&byte| byte == 3));
+}
+
+#[test_case]
+fn test_asynchronous_requests()
+{
+    let disk = disk();
+    let data: Vec<u8> = (0..1024).map(|index| (index * 7) as u8).collect();
+    let write = disk.submit_write(126, &data).unwrap();
+    let read = disk.submit_read(2, 1).unwrap();
+    write.wait().unwrap();
+
+    let mut sector = [0; 512];
+    read.read_into(&mut sector).unwrap();
+    assert!(sector.iter().all(|&byte| byte == 2));
+
+    let mut buffer = vec![0; 1024];
+    disk.read_sectors(126, &mut buffer).unwrap();
+    assert_eq!(buffer, data);
+    disk.flush().unwrap();
+}
+
+#[test_case]
+fn test_large_transfer()
+{
+    // Alle Sektoren hinter dem Testmuster in einer großen Anfrage.
+    let disk = disk();
+    let data: Vec<u8> = (0..124 * 512).map(|index| (index / 512) as u8).collect();
+    disk.write_sectors(4, &data).unwrap();
+    let mut buffer = vec![0; data.len()];
+    disk.read_sectors(4, &mut buffer).unwrap();
+    assert!(buffer == data);
+}
+
+#[test_case]
+fn test_invalid_requests()
+{
+    let disk = disk();
+    assert_eq!(disk.read_sectors(127, &mut [0; 1024]), Err(BlockError::OutOfRange));
+    assert_eq!(disk.read_sectors(0, &mut [0; 100]), Err(BlockError::BadBufferSize));
+    assert_eq!(disk.write_sectors(u64::MAX, &[0; 512]).err(), Some(BlockError::OutOfRange));
+}